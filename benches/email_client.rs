@@ -0,0 +1,67 @@
+//! Mean latency of sending emails one after the other, opening a connection
+//! per email versus reusing the pooled ones.
+//!
+//! Run with `cargo bench --bench email_client`.
+use std::time::{Duration, Instant};
+
+use newsletter::{
+    configuration::{ConnectionSettings, EmailClientSettings},
+    domain::Email,
+    email_log::EmailKind,
+};
+use secrecy::Secret;
+use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+
+const EMAILS: u32 = 500;
+
+async fn mean_latency(email_server: &MockServer, connection: ConnectionSettings) -> Duration {
+    let email_client = EmailClientSettings {
+        base_url: email_server.uri(),
+        sender_email: "newsletter@example.com".into(),
+        verified_senders: vec![],
+        authorization_token: Secret::new("token".into()),
+        timeout_milliseconds: 10_000,
+        max_attachments_bytes: newsletter::email_client::MAX_ATTACHMENTS_BYTES,
+        connection,
+        sync_suppressions_on_startup: false,
+    }
+    .client();
+    let recipient = Email::parse("subscriber@example.com".into()).unwrap();
+
+    let start = Instant::now();
+    for _ in 0..EMAILS {
+        email_client
+            .send_email(
+                EmailKind::Issue,
+                &recipient,
+                "Issue",
+                "<p>Issue body</p>",
+                "Issue body",
+                &[],
+                None,
+            )
+            .await
+            .expect("Failed to send email.");
+    }
+
+    start.elapsed() / EMAILS
+}
+
+#[tokio::main]
+async fn main() {
+    let email_server = MockServer::start().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&email_server)
+        .await;
+
+    let unpooled = ConnectionSettings {
+        pool_max_idle_per_host: 0,
+        ..ConnectionSettings::default()
+    };
+    let unpooled = mean_latency(&email_server, unpooled).await;
+    let pooled = mean_latency(&email_server, ConnectionSettings::default()).await;
+
+    println!("connection per email: {:?} per email", unpooled);
+    println!("pooled connections:   {:?} per email", pooled);
+}
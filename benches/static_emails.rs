@@ -0,0 +1,57 @@
+//! Mean time spent rendering the confirmation email of a subscription, with
+//! Tera for each message versus splicing the link into the pre-rendered
+//! template. Subscriptions are the bulk of the emails the application sends
+//! on its own, each one with a different link.
+//!
+//! Run with `cargo bench --bench static_emails`.
+use std::time::{Duration, Instant};
+
+use newsletter::template::{render_subscription_confirmation, TEMPLATES};
+use tera::Context;
+
+const EMAILS: u32 = 20_000;
+
+fn confirmation_link(i: u32) -> String {
+    format!(
+        "https://example.com/subscriptions/confirm?subscription_token={:025}",
+        i
+    )
+}
+
+fn mean_duration(render: impl Fn(&str) -> usize) -> Duration {
+    // Leaves the one-off parsing and pre-rendering out.
+    render(&confirmation_link(0));
+
+    let start = Instant::now();
+    let mut rendered = 0;
+    for i in 0..EMAILS {
+        rendered += render(&confirmation_link(i));
+    }
+    assert!(rendered > 0);
+
+    start.elapsed() / EMAILS
+}
+
+fn main() {
+    let tera = mean_duration(|link| {
+        let mut context = Context::new();
+        context.insert("confirmation_link", link);
+        let templates = TEMPLATES.load();
+        let html = templates
+            .render("subscription_confirmation.html", &context)
+            .unwrap();
+        let text = templates
+            .render("subscription_confirmation.txt", &context)
+            .unwrap();
+
+        html.len() + text.len()
+    });
+    let spliced = mean_duration(|link| {
+        let email = render_subscription_confirmation(link, &[]).unwrap();
+
+        email.html.len() + email.text.len()
+    });
+
+    println!("rendered with tera: {:?} per email", tera);
+    println!("spliced link:       {:?} per email", spliced);
+}
@@ -0,0 +1,10 @@
+CREATE TYPE admin_action_status AS ENUM ('pending', 'approved', 'rejected');
+CREATE TABLE admin_actions (
+  id uuid PRIMARY KEY,
+  payload JSONB NOT NULL,
+  requested_by uuid NOT NULL REFERENCES users (user_id),
+  requested_at timestamptz NOT NULL,
+  status admin_action_status NOT NULL,
+  reviewed_by uuid NULL REFERENCES users (user_id),
+  reviewed_at timestamptz NULL
+);
@@ -0,0 +1,19 @@
+ALTER TABLE subscriptions ADD COLUMN confirmed_at timestamptz NULL;
+UPDATE subscriptions SET confirmed_at = subscribed_at WHERE status = 'confirmed';
+CREATE TABLE topics (
+  id uuid PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE,
+  description TEXT NOT NULL,
+  created_at timestamptz NOT NULL
+);
+CREATE TABLE subscriber_topics (
+  subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+  topic_id uuid NOT NULL REFERENCES topics (id) ON DELETE CASCADE,
+  PRIMARY KEY (subscriber_id, topic_id)
+);
+CREATE TABLE segments (
+  id uuid PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE,
+  rules JSONB NOT NULL,
+  created_at timestamptz NOT NULL
+);
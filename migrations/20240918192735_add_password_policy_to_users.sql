@@ -0,0 +1,10 @@
+ALTER TABLE users ADD COLUMN password_changed_at timestamptz NOT NULL DEFAULT now();
+
+CREATE TABLE password_history(
+  id uuid PRIMARY KEY,
+  user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  password_hash TEXT NOT NULL,
+  created_at timestamptz NOT NULL
+);
+
+CREATE INDEX password_history_user_id_idx ON password_history (user_id, created_at);
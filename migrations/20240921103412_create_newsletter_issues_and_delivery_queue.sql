@@ -0,0 +1,28 @@
+CREATE TABLE newsletter_issues(
+  newsletter_issue_id uuid PRIMARY KEY,
+  title TEXT NOT NULL,
+  text_content TEXT NOT NULL,
+  html_content TEXT NOT NULL,
+  published_at timestamptz NOT NULL
+);
+
+CREATE TABLE issue_delivery_queue(
+  newsletter_issue_id uuid NOT NULL
+    REFERENCES newsletter_issues (newsletter_issue_id),
+  subscriber_email TEXT NOT NULL,
+  n_retries SMALLINT NOT NULL DEFAULT 0,
+  execute_after timestamptz NOT NULL DEFAULT now(),
+  PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
+
+-- Deliveries that kept failing after every retry. They stay here until an
+-- operator requeues or purges them.
+CREATE TABLE issue_delivery_dead_letters(
+  newsletter_issue_id uuid NOT NULL
+    REFERENCES newsletter_issues (newsletter_issue_id),
+  subscriber_email TEXT NOT NULL,
+  n_retries SMALLINT NOT NULL,
+  last_error TEXT NOT NULL,
+  failed_at timestamptz NOT NULL,
+  PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
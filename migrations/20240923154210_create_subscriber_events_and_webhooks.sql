@@ -0,0 +1,40 @@
+CREATE TABLE subscriber_events(
+  event_id BIGSERIAL PRIMARY KEY,
+  subscriber_id uuid NOT NULL
+    REFERENCES subscriptions (id) ON DELETE CASCADE,
+  event_type TEXT NOT NULL,
+  details JSONB NOT NULL DEFAULT '{}',
+  occurred_at timestamptz NOT NULL
+);
+
+CREATE INDEX subscriber_events_subscriber_id_idx
+  ON subscriber_events (subscriber_id, event_id);
+
+-- Rebuilds the history known so far from the subscriptions themselves.
+INSERT INTO subscriber_events (subscriber_id, event_type, occurred_at)
+SELECT subscriber_id, event_type, occurred_at
+FROM (
+  SELECT id AS subscriber_id, 'subscribed' AS event_type, subscribed_at AS occurred_at
+  FROM subscriptions
+  UNION ALL
+  SELECT id, 'confirmed', confirmed_at
+  FROM subscriptions
+  WHERE confirmed_at IS NOT NULL
+) AS history
+ORDER BY occurred_at;
+
+CREATE TABLE webhooks(
+  webhook_id uuid PRIMARY KEY,
+  url TEXT NOT NULL,
+  created_at timestamptz NOT NULL
+);
+
+CREATE TABLE webhook_delivery_queue(
+  webhook_id uuid NOT NULL
+    REFERENCES webhooks (webhook_id) ON DELETE CASCADE,
+  event_id BIGINT NOT NULL
+    REFERENCES subscriber_events (event_id) ON DELETE CASCADE,
+  n_retries SMALLINT NOT NULL DEFAULT 0,
+  execute_after timestamptz NOT NULL DEFAULT now(),
+  PRIMARY KEY (webhook_id, event_id)
+);
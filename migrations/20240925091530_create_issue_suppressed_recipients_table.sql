@@ -0,0 +1,11 @@
+-- Recipients the email provider refused to deliver an issue to because it
+-- marked them as inactive.
+CREATE TABLE issue_suppressed_recipients(
+  newsletter_issue_id uuid NOT NULL
+    REFERENCES newsletter_issues (newsletter_issue_id),
+  subscriber_email TEXT NOT NULL,
+  error_code BIGINT NOT NULL,
+  message TEXT NOT NULL,
+  suppressed_at timestamptz NOT NULL,
+  PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
@@ -0,0 +1,20 @@
+-- Short-lived links that let confirmed subscribers reach the preferences
+-- center without an account.
+CREATE TABLE preferences_tokens(
+  preferences_token TEXT NOT NULL,
+  subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+  created_at timestamptz NOT NULL,
+  PRIMARY KEY (preferences_token)
+);
+
+-- Every email change a subscriber asked for. The subscription keeps the old
+-- address until the new one is confirmed.
+CREATE TABLE subscriber_email_changes(
+  change_token TEXT NOT NULL,
+  subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+  old_email TEXT NOT NULL,
+  new_email TEXT NOT NULL,
+  requested_at timestamptz NOT NULL,
+  confirmed_at timestamptz,
+  PRIMARY KEY (change_token)
+);
@@ -0,0 +1,113 @@
+-- Events are recorded per recipient and outgrow every other table. Monthly
+-- partitions keep their indexes small and let old months be purged by
+-- dropping whole partitions instead of deleting rows.
+ALTER TABLE webhook_delivery_queue
+  DROP CONSTRAINT webhook_delivery_queue_event_id_fkey;
+-- Lets lookups of queued events skip the partitions of other months.
+ALTER TABLE webhook_delivery_queue ADD COLUMN occurred_at timestamptz;
+UPDATE webhook_delivery_queue q
+SET occurred_at = e.occurred_at
+FROM subscriber_events e
+WHERE e.event_id = q.event_id;
+DELETE FROM webhook_delivery_queue WHERE occurred_at IS NULL;
+ALTER TABLE webhook_delivery_queue ALTER COLUMN occurred_at SET NOT NULL;
+
+ALTER TABLE subscriber_events RENAME TO subscriber_events_unpartitioned;
+ALTER INDEX subscriber_events_subscriber_id_idx
+  RENAME TO subscriber_events_unpartitioned_subscriber_id_idx;
+ALTER TABLE subscriber_events_unpartitioned
+  RENAME CONSTRAINT subscriber_events_pkey TO subscriber_events_unpartitioned_pkey;
+ALTER TABLE subscriber_events_unpartitioned
+  RENAME CONSTRAINT subscriber_events_subscriber_id_fkey
+  TO subscriber_events_unpartitioned_subscriber_id_fkey;
+
+CREATE TABLE subscriber_events(
+  event_id BIGINT NOT NULL DEFAULT nextval('subscriber_events_event_id_seq'),
+  subscriber_id uuid NOT NULL
+    REFERENCES subscriptions (id) ON DELETE CASCADE,
+  event_type TEXT NOT NULL,
+  details JSONB NOT NULL DEFAULT '{}',
+  occurred_at timestamptz NOT NULL,
+  PRIMARY KEY (event_id, occurred_at)
+) PARTITION BY RANGE (occurred_at);
+ALTER SEQUENCE subscriber_events_event_id_seq OWNED BY subscriber_events.event_id;
+
+CREATE INDEX subscriber_events_subscriber_id_idx
+  ON subscriber_events (subscriber_id, event_id);
+
+-- Catches the events of months whose partition wasn't created in time.
+CREATE TABLE subscriber_events_default PARTITION OF subscriber_events DEFAULT;
+
+-- Creates the partition of the month (in UTC) the given instant falls in,
+-- moving into it the events the default partition holds for that month.
+CREATE FUNCTION create_subscriber_events_partition(instant timestamptz)
+RETURNS text AS $$
+DECLARE
+  partition_start timestamptz :=
+    date_trunc('month', instant AT TIME ZONE 'UTC') AT TIME ZONE 'UTC';
+  partition_end timestamptz :=
+    (date_trunc('month', instant AT TIME ZONE 'UTC') + interval '1 month') AT TIME ZONE 'UTC';
+  partition_name text :=
+    'subscriber_events_' || to_char(instant AT TIME ZONE 'UTC', 'YYYY_MM');
+BEGIN
+  IF to_regclass(partition_name) IS NOT NULL THEN
+    RETURN partition_name;
+  END IF;
+
+  EXECUTE format(
+    'CREATE TABLE %I (LIKE subscriber_events INCLUDING DEFAULTS)',
+    partition_name
+  );
+  EXECUTE format(
+    'WITH moved AS (
+       DELETE FROM subscriber_events_default
+       WHERE occurred_at >= %L AND occurred_at < %L
+       RETURNING *
+     )
+     INSERT INTO %I SELECT * FROM moved',
+    partition_start, partition_end, partition_name
+  );
+  EXECUTE format(
+    'ALTER TABLE subscriber_events ATTACH PARTITION %I FOR VALUES FROM (%L) TO (%L)',
+    partition_name, partition_start, partition_end
+  );
+
+  RETURN partition_name;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Drops the monthly partitions that end before the given instant.
+CREATE FUNCTION drop_subscriber_events_partitions(before timestamptz)
+RETURNS SETOF text AS $$
+DECLARE
+  partition_name text;
+BEGIN
+  FOR partition_name IN
+    SELECT c.relname::text
+    FROM pg_inherits i
+    JOIN pg_class c ON c.oid = i.inhrelid
+    WHERE i.inhparent = 'subscriber_events'::regclass
+      AND c.relname ~ '^subscriber_events_\d{4}_\d{2}$'
+      AND (to_date(right(c.relname, 7), 'YYYY_MM') + interval '1 month')
+        AT TIME ZONE 'UTC' <= before
+    ORDER BY c.relname
+  LOOP
+    EXECUTE format('DROP TABLE %I', partition_name);
+    RETURN NEXT partition_name;
+  END LOOP;
+END;
+$$ LANGUAGE plpgsql;
+
+SELECT create_subscriber_events_partition(month)
+FROM (
+  SELECT DISTINCT date_trunc('month', occurred_at AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS month
+  FROM subscriber_events_unpartitioned
+  UNION
+  SELECT now()
+) AS months;
+
+INSERT INTO subscriber_events (event_id, subscriber_id, event_type, details, occurred_at)
+SELECT event_id, subscriber_id, event_type, details, occurred_at
+FROM subscriber_events_unpartitioned;
+
+DROP TABLE subscriber_events_unpartitioned;
@@ -0,0 +1,27 @@
+CREATE TABLE newsletter_lists(
+  list_id uuid PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE,
+  created_at timestamptz NOT NULL
+);
+
+-- The list every subscription and issue implicitly belonged to so far. Its
+-- id is known by the application, which falls back to it.
+INSERT INTO newsletter_lists (list_id, name, created_at)
+VALUES ('00000000-0000-0000-0000-000000000001', 'Newsletter', now());
+
+-- The same email can now subscribe to several lists, each subscription
+-- having its own status and confirmation token.
+ALTER TABLE subscriptions
+  ADD COLUMN list_id uuid NOT NULL
+    DEFAULT '00000000-0000-0000-0000-000000000001'
+    REFERENCES newsletter_lists (list_id);
+ALTER TABLE subscriptions ALTER COLUMN list_id DROP DEFAULT;
+ALTER TABLE subscriptions DROP CONSTRAINT subscriptions_email_key;
+ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_list_id_email_key
+  UNIQUE (list_id, email);
+
+ALTER TABLE newsletter_issues
+  ADD COLUMN list_id uuid NOT NULL
+    DEFAULT '00000000-0000-0000-0000-000000000001'
+    REFERENCES newsletter_lists (list_id);
+ALTER TABLE newsletter_issues ALTER COLUMN list_id DROP DEFAULT;
@@ -0,0 +1,9 @@
+CREATE TYPE subscription_status AS ENUM (
+  'pending_confirmation',
+  'confirmed',
+  'suppressed',
+  'unsubscribed'
+);
+
+ALTER TABLE subscriptions
+  ALTER COLUMN status TYPE subscription_status USING status::subscription_status;
@@ -0,0 +1,4 @@
+-- Deliveries being processed are leased to a worker instead of being kept
+-- locked, so that those of a worker that died are picked up again once
+-- their lease expires.
+ALTER TABLE issue_delivery_queue ADD COLUMN leased_until timestamptz NULL;
@@ -0,0 +1,8 @@
+-- Only SHA-256 digests of the tokens sent by email are kept from now on.
+ALTER TABLE subscription_tokens RENAME COLUMN subscription_token TO subscription_token_hash;
+UPDATE subscription_tokens
+SET subscription_token_hash = encode(sha256(convert_to(subscription_token_hash, 'UTF8')), 'hex');
+
+ALTER TABLE invitation_tokens RENAME COLUMN invitation_token TO invitation_token_hash;
+UPDATE invitation_tokens
+SET invitation_token_hash = encode(sha256(convert_to(invitation_token_hash, 'UTF8')), 'hex');
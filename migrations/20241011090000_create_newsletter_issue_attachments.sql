@@ -0,0 +1,10 @@
+CREATE TABLE newsletter_issue_attachments(
+  newsletter_issue_id uuid NOT NULL
+    REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+  position SMALLINT NOT NULL,
+  filename TEXT NOT NULL,
+  content_type TEXT NOT NULL,
+  -- Kept in base64, as sent to the email provider.
+  content TEXT NOT NULL,
+  PRIMARY KEY (newsletter_issue_id, position)
+);
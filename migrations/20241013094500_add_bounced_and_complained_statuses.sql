@@ -0,0 +1,3 @@
+-- Set from the bounce and complaint webhooks of the email provider.
+ALTER TYPE subscription_status ADD VALUE 'bounced';
+ALTER TYPE subscription_status ADD VALUE 'complained';
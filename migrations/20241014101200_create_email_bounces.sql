@@ -0,0 +1,14 @@
+-- Bounces reported by the email provider, classified by category.
+CREATE TABLE email_bounces(
+  bounce_id BIGSERIAL PRIMARY KEY,
+  -- Missing for emails that weren't issues, e.g. confirmations.
+  newsletter_issue_id uuid NULL
+    REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+  subscriber_email TEXT NOT NULL,
+  category TEXT NOT NULL,
+  bounce_type TEXT NULL,
+  bounced_at timestamptz NOT NULL
+);
+
+CREATE INDEX email_bounces_newsletter_issue_id_idx
+  ON email_bounces (newsletter_issue_id, subscriber_email);
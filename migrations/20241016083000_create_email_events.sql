@@ -0,0 +1,14 @@
+-- Opens and clicks of the issues, recorded by the tracking routes.
+CREATE TABLE email_events(
+  event_id BIGSERIAL PRIMARY KEY,
+  newsletter_issue_id uuid NOT NULL
+    REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+  subscriber_email TEXT NOT NULL,
+  event_type TEXT NOT NULL CHECK (event_type IN ('open', 'click')),
+  -- The link followed, for clicks.
+  url TEXT NULL,
+  occurred_at timestamptz NOT NULL
+);
+
+CREATE INDEX email_events_newsletter_issue_id_idx
+  ON email_events (newsletter_issue_id, event_type);
@@ -0,0 +1,10 @@
+-- Recipients an issue could not be rendered for. Rendering the same issue
+-- again would fail the same way, so these are not retried.
+CREATE TABLE issue_render_failures(
+  newsletter_issue_id uuid NOT NULL
+    REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+  subscriber_email TEXT NOT NULL,
+  error TEXT NOT NULL,
+  failed_at timestamptz NOT NULL,
+  PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
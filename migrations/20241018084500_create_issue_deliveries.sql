@@ -0,0 +1,8 @@
+-- Recipients an issue was handed to the email provider for.
+CREATE TABLE issue_deliveries(
+  newsletter_issue_id uuid NOT NULL
+    REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+  subscriber_email TEXT NOT NULL,
+  delivered_at timestamptz NOT NULL,
+  PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
@@ -0,0 +1,3 @@
+-- Set once the personal data of a subscriber was erased on request. The row
+-- is kept, anonymized, so that the counts of past issues stay right.
+ALTER TABLE subscriptions ADD COLUMN erased_at timestamptz NULL;
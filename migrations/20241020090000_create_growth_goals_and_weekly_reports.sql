@@ -0,0 +1,17 @@
+-- Confirmed subscribers each list should gain per week.
+CREATE TABLE growth_goals(
+  list_id uuid PRIMARY KEY
+    REFERENCES newsletter_lists (list_id) ON DELETE CASCADE,
+  weekly_new_subscribers INTEGER NOT NULL CHECK (weekly_new_subscribers > 0),
+  updated_at timestamptz NOT NULL
+);
+
+-- Where admins who opted in get the weekly report, none if they didn't.
+ALTER TABLE users ADD COLUMN report_email TEXT NULL;
+
+-- Weeks whose report was sent, so that it goes out once whatever the number
+-- of instances running the job.
+CREATE TABLE weekly_reports(
+  week_start timestamptz PRIMARY KEY,
+  sent_at timestamptz NOT NULL
+);
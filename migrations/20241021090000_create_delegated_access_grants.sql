@@ -0,0 +1,25 @@
+-- Time-boxed access links given to people without an account, e.g. a
+-- sponsor looking at the stats of the issues.
+CREATE TABLE delegated_access_grants(
+  grant_id uuid PRIMARY KEY,
+  scope TEXT NOT NULL,
+  -- Who the link was made for, as told by the admin.
+  recipient TEXT NOT NULL,
+  granted_by uuid NOT NULL REFERENCES users (user_id),
+  granted_at timestamptz NOT NULL,
+  expires_at timestamptz NOT NULL,
+  revoked_by uuid NULL REFERENCES users (user_id),
+  revoked_at timestamptz NULL
+);
+
+-- Every request made with a grant, including the rejected ones.
+CREATE TABLE delegated_access_log(
+  id BIGSERIAL PRIMARY KEY,
+  grant_id uuid NOT NULL REFERENCES delegated_access_grants (grant_id),
+  method TEXT NOT NULL,
+  path TEXT NOT NULL,
+  status SMALLINT NOT NULL,
+  accessed_at timestamptz NOT NULL
+);
+
+CREATE INDEX delegated_access_log_grant_id_idx ON delegated_access_log (grant_id);
@@ -0,0 +1,15 @@
+-- Backs the similarity of addresses looked at when searching for duplicate
+-- subscribers.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX subscriptions_email_trgm_idx
+  ON subscriptions USING gin (lower(email) gin_trgm_ops);
+
+-- Pairs of subscribers an admin looked at and found to be different people,
+-- no longer reported as probable duplicates. The lowest id comes first.
+CREATE TABLE subscriber_duplicate_dismissals(
+  first_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+  second_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+  dismissed_at timestamptz NOT NULL,
+  PRIMARY KEY (first_id, second_id),
+  CHECK (first_id < second_id)
+);
@@ -0,0 +1,12 @@
+-- Every ANALYZE and REINDEX run by the maintenance job, to tell whether
+-- they still fit in the low-traffic window as the tables grow.
+CREATE TABLE table_maintenance_runs(
+  run_id BIGSERIAL PRIMARY KEY,
+  table_name TEXT NOT NULL,
+  operation TEXT NOT NULL,
+  started_at timestamptz NOT NULL,
+  duration_ms BIGINT NOT NULL,
+  succeeded BOOLEAN NOT NULL
+);
+CREATE INDEX table_maintenance_runs_started_at_idx
+  ON table_maintenance_runs (started_at);
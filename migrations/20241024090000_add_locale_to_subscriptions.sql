@@ -0,0 +1,3 @@
+-- Language of the emails sent to the subscriber, the default templates' if
+-- unknown.
+ALTER TABLE subscriptions ADD COLUMN locale TEXT NULL;
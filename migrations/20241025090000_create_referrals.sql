@@ -0,0 +1,16 @@
+-- Shared by a subscriber in the links they refer others with. Existing
+-- subscribers get one each, the default being volatile.
+ALTER TABLE subscriptions
+    ADD COLUMN referral_code TEXT NOT NULL
+        DEFAULT substr(md5(random()::text || clock_timestamp()::text), 1, 12),
+    ADD COLUMN referred_by uuid NULL REFERENCES subscriptions (id) ON DELETE SET NULL;
+CREATE UNIQUE INDEX subscriptions_referral_code_idx ON subscriptions (referral_code);
+CREATE INDEX subscriptions_referred_by_idx ON subscriptions (referred_by);
+
+-- Milestones referrers were thanked for, each one once.
+CREATE TABLE referral_rewards (
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    milestone INTEGER NOT NULL,
+    rewarded_at timestamptz NOT NULL,
+    PRIMARY KEY (subscriber_id, milestone)
+);
@@ -0,0 +1,24 @@
+-- Payloads are signed with a secret of their webhook, so that endpoints can
+-- tell they come from the application. Existing webhooks get a random one.
+ALTER TABLE webhooks ADD COLUMN secret TEXT NOT NULL DEFAULT md5(random()::text);
+ALTER TABLE webhooks ALTER COLUMN secret DROP DEFAULT;
+
+-- Events about the list as a whole, such as published issues, concern no
+-- subscriber in particular.
+ALTER TABLE subscriber_events ALTER COLUMN subscriber_id DROP NOT NULL;
+
+-- Every attempt to deliver an event to a webhook, successful or not.
+CREATE TABLE webhook_deliveries(
+  delivery_id BIGSERIAL PRIMARY KEY,
+  webhook_id uuid NOT NULL
+    REFERENCES webhooks (webhook_id) ON DELETE CASCADE,
+  event_id BIGINT NOT NULL,
+  event_type TEXT NOT NULL,
+  attempt SMALLINT NOT NULL,
+  status_code SMALLINT NULL,
+  error TEXT NULL,
+  attempted_at timestamptz NOT NULL
+);
+
+CREATE INDEX webhook_deliveries_webhook_id_idx
+  ON webhook_deliveries (webhook_id, delivery_id);
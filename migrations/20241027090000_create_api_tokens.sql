@@ -0,0 +1,14 @@
+-- Personal access tokens of scripted integrations. Only the SHA-256 digest of
+-- a token is kept, the token itself is shown once to its owner.
+CREATE TABLE api_tokens(
+  token_id uuid PRIMARY KEY,
+  user_id uuid NOT NULL
+    REFERENCES users (user_id) ON DELETE CASCADE,
+  name TEXT NOT NULL,
+  token_hash TEXT NOT NULL UNIQUE,
+  created_at timestamptz NOT NULL,
+  last_used_at timestamptz NULL,
+  revoked_at timestamptz NULL
+);
+
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id, created_at);
@@ -0,0 +1,8 @@
+-- Logins refused because of their credentials, listed in the admin UI.
+CREATE TABLE failed_logins(
+  attempt_id BIGSERIAL PRIMARY KEY,
+  username TEXT NOT NULL,
+  client_ip TEXT NULL,
+  user_agent TEXT NULL,
+  attempted_at timestamptz NOT NULL
+);
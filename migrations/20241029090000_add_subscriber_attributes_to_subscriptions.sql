@@ -0,0 +1,3 @@
+-- Answers to the custom signup fields, keyed by field name.
+ALTER TABLE subscriptions
+  ADD COLUMN subscriber_attributes JSONB NOT NULL DEFAULT '{}';
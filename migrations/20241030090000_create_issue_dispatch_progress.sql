@@ -0,0 +1,20 @@
+-- Each lease of tasks gets its own id, for the progress of the batch to be
+-- told apart from the progress of batches leased at the same time.
+ALTER TABLE issue_delivery_queue ADD COLUMN lease_id uuid NULL;
+CREATE INDEX issue_delivery_queue_lease_id_idx ON issue_delivery_queue (lease_id);
+
+-- How far the batch a worker is sending got for an issue: every recipient
+-- up to the last one, in order, was handed to the email provider. A worker
+-- taking over the deliveries of one that died, once its lease expired,
+-- doesn't send to them again. Workers send batches of the same issue at
+-- once, so each lease has its own progress.
+CREATE TABLE issue_dispatch_progress(
+  newsletter_issue_id uuid NOT NULL
+    REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+  lease_id uuid NOT NULL,
+  last_subscriber_email TEXT NOT NULL,
+  -- When the lease of the batch expires.
+  leased_until timestamptz NOT NULL,
+  updated_at timestamptz NOT NULL,
+  PRIMARY KEY (newsletter_issue_id, lease_id)
+);
@@ -0,0 +1,14 @@
+-- Deliveries are also told apart by subscriber, so that an issue isn't
+-- delivered twice to someone whose address changed in between.
+ALTER TABLE issue_deliveries ADD COLUMN subscriber_id uuid NULL
+  REFERENCES subscriptions (id) ON DELETE SET NULL;
+
+UPDATE issue_deliveries d
+SET subscriber_id = s.id
+FROM newsletter_issues i, subscriptions s
+WHERE i.newsletter_issue_id = d.newsletter_issue_id
+  AND s.list_id = i.list_id
+  AND s.email = d.subscriber_email;
+
+CREATE UNIQUE INDEX issue_deliveries_issue_subscriber
+  ON issue_deliveries (newsletter_issue_id, subscriber_id);
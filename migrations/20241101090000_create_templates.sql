@@ -0,0 +1,12 @@
+-- Emails edited from the admin UI. Saving one adds a version, the latest is
+-- sent; without any, the templates on disk are.
+CREATE TABLE templates(
+  name TEXT NOT NULL,
+  version INT NOT NULL,
+  subject TEXT NOT NULL,
+  html_body TEXT NOT NULL,
+  text_body TEXT NOT NULL,
+  created_by uuid NULL REFERENCES users (user_id) ON DELETE SET NULL,
+  created_at timestamptz NOT NULL,
+  PRIMARY KEY (name, version)
+);
@@ -0,0 +1,4 @@
+-- Invitations are bound to the address they were sent to, which the
+-- collaborator registers with. Ones sent before have none.
+ALTER TABLE invitation_tokens ADD COLUMN email TEXT NULL;
+ALTER TABLE users ADD COLUMN email TEXT NULL;
@@ -0,0 +1,23 @@
+-- Usernames are case insensitive, so they are stored in lowercase and two
+-- users can't differ only by case.
+--
+-- Users that did, e.g. "Alice" and "alice", can't all keep their username:
+-- the one already in lowercase, or else the first one, does, the others get
+-- the start of their id appended to it and have to be told about it.
+UPDATE users u
+SET username = CASE
+    WHEN folded.rank = 1 THEN lower(u.username)
+    ELSE lower(u.username) || '-' || left(u.user_id::text, 8)
+  END
+FROM (
+  SELECT
+    user_id,
+    row_number() OVER (
+      PARTITION BY lower(username)
+      ORDER BY username = lower(username) DESC, user_id
+    ) AS rank
+  FROM users
+) folded
+WHERE u.user_id = folded.user_id AND u.username <> lower(u.username);
+ALTER TABLE users
+  ADD CONSTRAINT users_username_lowercase CHECK (username = lower(username));
@@ -0,0 +1,6 @@
+-- Users may give a name to be shown with instead of their username, and an
+-- address to be contacted at. Issues keep the name of who published them as
+-- it was at the time.
+ALTER TABLE users ADD COLUMN display_name TEXT NULL;
+ALTER TABLE users ADD COLUMN contact_email TEXT NULL;
+ALTER TABLE newsletter_issues ADD COLUMN author TEXT NULL;
@@ -0,0 +1,4 @@
+-- Who published each issue. Their name is kept in `author` should they be
+-- removed.
+ALTER TABLE newsletter_issues
+  ADD COLUMN published_by uuid NULL REFERENCES users (user_id) ON DELETE SET NULL;
@@ -0,0 +1,6 @@
+-- Who the emails of an issue are from and where replies go, when the
+-- publisher overrode the defaults.
+ALTER TABLE newsletter_issues
+  ADD COLUMN sender_name TEXT NULL,
+  ADD COLUMN sender_email TEXT NULL,
+  ADD COLUMN reply_to TEXT NULL;
@@ -0,0 +1,13 @@
+-- A/B tests of the subject of an issue: half of its recipients, picked at
+-- random, get the alternative title instead of the title.
+ALTER TABLE newsletter_issues ADD COLUMN alternative_title TEXT NULL;
+
+-- The subject each recipient of a tested issue was assigned, kept across
+-- retries so that they always get the same one.
+CREATE TABLE issue_subject_variants(
+  newsletter_issue_id uuid NOT NULL
+    REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+  subscriber_email TEXT NOT NULL,
+  variant TEXT NOT NULL CHECK (variant IN ('a', 'b')),
+  PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
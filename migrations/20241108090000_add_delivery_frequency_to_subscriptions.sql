@@ -0,0 +1,12 @@
+-- How often subscribers hear from their list: every issue as it's
+-- published, or the issues of the week gathered in a weekly digest.
+CREATE TYPE delivery_frequency AS ENUM ('every_issue', 'weekly_digest');
+
+ALTER TABLE subscriptions
+  ADD COLUMN delivery_frequency delivery_frequency NOT NULL DEFAULT 'every_issue';
+
+-- Weeks whose digest went out to every subscriber asking for one.
+CREATE TABLE weekly_digests(
+  week_start timestamptz PRIMARY KEY,
+  sent_at timestamptz NOT NULL
+);
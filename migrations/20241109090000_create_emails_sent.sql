@@ -0,0 +1,16 @@
+-- Every email handed to the provider and how it answered, to find out
+-- whether someone was sent the email they say they never got.
+CREATE TABLE emails_sent(
+  email_id BIGSERIAL PRIMARY KEY,
+  kind TEXT NOT NULL,
+  recipient TEXT NOT NULL,
+  subject TEXT NOT NULL,
+  -- Set for the emails of an issue.
+  newsletter_issue_id uuid NULL,
+  provider_message_id TEXT NULL,
+  status TEXT NOT NULL CHECK (status IN ('sent', 'rejected', 'failed')),
+  error TEXT NULL,
+  sent_at timestamptz NOT NULL
+);
+
+CREATE INDEX emails_sent_recipient_idx ON emails_sent (lower(recipient));
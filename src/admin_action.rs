@@ -0,0 +1,95 @@
+use chrono::Utc;
+use sqlx::{types::Json, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    delivery_queue::IssueSender, email_client::Attachment, newsletter_list::DEFAULT_LIST_ID,
+};
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, sqlx::Type, PartialEq)]
+#[sqlx(type_name = "admin_action_status", rename_all = "lowercase")]
+pub enum AdminActionStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+// Actions are only held while being stored or carried out, one at a time.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AdminAction {
+    DeleteSubscribers {
+        emails: Vec<String>,
+    },
+    PublishNewsletter {
+        // Actions stored before lists existed published to the default one.
+        #[serde(default = "default_list_id")]
+        list_id: Uuid,
+        #[serde(default)]
+        tag: Option<String>,
+        title: String,
+        #[serde(default)]
+        alternative_title: Option<String>,
+        html: String,
+        text: String,
+        #[serde(default)]
+        attachments: Vec<Attachment>,
+        #[serde(default)]
+        sender: IssueSender,
+        flagged_links: Vec<String>,
+    },
+}
+
+fn default_list_id() -> Uuid {
+    DEFAULT_LIST_ID
+}
+
+impl std::fmt::Display for AdminAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdminAction::DeleteSubscribers { emails } => {
+                write!(
+                    f,
+                    "Delete {} subscribers: {}",
+                    emails.len(),
+                    emails.join(", ")
+                )
+            }
+            AdminAction::PublishNewsletter {
+                title,
+                flagged_links,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Publish newsletter issue \"{}\" with flagged links: {}",
+                    title,
+                    flagged_links.join(", ")
+                )
+            }
+        }
+    }
+}
+
+#[tracing::instrument(name = "Store pending admin action", skip(pool, action))]
+pub async fn insert_pending_action(
+    pool: &PgPool,
+    requested_by: Uuid,
+    action: &AdminAction,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO admin_actions (id, payload, requested_by, requested_at, status)
+        VALUES ($1, $2, $3, $4, 'pending')
+        "#,
+        Uuid::new_v4(),
+        Json(action) as _,
+        requested_by,
+        Utc::now(),
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
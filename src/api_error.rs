@@ -0,0 +1,105 @@
+use actix_web::{
+    http::{header::CONTENT_TYPE, StatusCode},
+    HttpResponse, ResponseError,
+};
+
+use crate::request_id::RequestId;
+
+/// Errors that can be described to clients as RFC 7807 problem details.
+pub trait Problem: ResponseError {
+    /// Identifies the kind of problem, e.g. `duplicated-subscriber`.
+    fn problem_type(&self) -> &'static str;
+
+    /// Additional members to include in the problem details.
+    fn extensions(&self) -> Option<serde_json::Map<String, serde_json::Value>> {
+        None
+    }
+}
+
+/// Body of every error response of the JSON endpoints.
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ProblemDetails {
+    /// e.g. `/problems/duplicated-subscriber`.
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: &'static str,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_id: Option<String>,
+    #[serde(flatten)]
+    #[schema(ignore)]
+    extensions: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Renders the wrapped error as an `application/problem+json` response.
+///
+/// The detail is left out of internal errors so that their causes are only
+/// found in the logs, through the trace id, which is the `X-Request-Id` of the
+/// request.
+pub struct ApiError<'a, E>(pub &'a E);
+
+impl<E: std::fmt::Debug> std::fmt::Debug for ApiError<'_, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for ApiError<'_, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<E: Problem> ResponseError for ApiError<'_, E> {
+    fn status_code(&self) -> StatusCode {
+        self.0.status_code()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        let detail = (!status.is_server_error()).then(|| self.0.to_string());
+        let problem = ProblemDetails {
+            problem_type: format!("/problems/{}", self.0.problem_type()),
+            title: status.canonical_reason().unwrap_or("Unknown error"),
+            status: status.as_u16(),
+            detail,
+            trace_id: RequestId::current().map(|id| id.to_string()),
+            extensions: self.0.extensions(),
+        };
+
+        problem.into_response(status)
+    }
+}
+
+impl ProblemDetails {
+    fn into_response(self, status: StatusCode) -> HttpResponse {
+        match serde_json::to_string(&self) {
+            Ok(body) => HttpResponse::build(status)
+                .insert_header((CONTENT_TYPE, "application/problem+json"))
+                .body(body),
+            Err(_) => HttpResponse::new(status),
+        }
+    }
+}
+
+/// Problem details of an error raised outside of the handlers, e.g. by the
+/// router, which has little more to tell than its status.
+pub fn problem_response(
+    status: StatusCode,
+    problem_type: &str,
+    detail: Option<String>,
+    trace_id: Option<String>,
+) -> HttpResponse {
+    let problem = ProblemDetails {
+        problem_type: format!("/problems/{}", problem_type),
+        title: status.canonical_reason().unwrap_or("Unknown error"),
+        status: status.as_u16(),
+        detail,
+        trace_id,
+        extensions: None,
+    };
+
+    problem.into_response(status)
+}
@@ -0,0 +1,146 @@
+use actix_web::http::header::{self, HeaderMap};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::token_generator::{hash_token, TokenGenerator};
+
+/// Lets secret scanners recognize a leaked token.
+const API_TOKEN_PREFIX: &str = "z2p_";
+
+/// A personal access token, as listed to its owner. The token itself can't
+/// be retrieved once created.
+#[derive(Debug, serde::Serialize)]
+pub struct ApiToken {
+    pub token_id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+pub fn generate_api_token(generator: &dyn TokenGenerator) -> String {
+    format!("{}{}", API_TOKEN_PREFIX, generator.alphanumeric(40))
+}
+
+/// The token of an `Authorization: Bearer <token>` header, if the request
+/// has one.
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Stores the digest of a new token of the user.
+#[tracing::instrument(name = "Store API token", skip(pool, token))]
+pub async fn store_api_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    token: &str,
+) -> Result<ApiToken, sqlx::Error> {
+    sqlx::query_as!(
+        ApiToken,
+        r#"
+        INSERT INTO api_tokens (token_id, user_id, name, token_hash, created_at)
+        VALUES ($1, $2, $3, $4, now())
+        RETURNING token_id, name, created_at, last_used_at, revoked_at
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        name,
+        hash_token(token),
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Tokens of the user, newest first, revoked ones included.
+#[tracing::instrument(name = "Get API tokens", skip(pool))]
+pub async fn get_api_tokens(pool: &PgPool, user_id: Uuid) -> Result<Vec<ApiToken>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiToken,
+        r#"
+        SELECT token_id, name, created_at, last_used_at, revoked_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// `false` if the user has no such live token.
+#[tracing::instrument(name = "Revoke API token", skip(pool))]
+pub async fn revoke_user_api_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    token_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE api_tokens
+        SET revoked_at = now()
+        WHERE token_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        token_id,
+        user_id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// The owner of the token, unless it's unknown or revoked. Each use is
+/// recorded, so that owners can tell which tokens are still needed.
+#[tracing::instrument(name = "Validate API token", skip(pool, token))]
+pub async fn validate_api_token(pool: &PgPool, token: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        UPDATE api_tokens
+        SET last_used_at = now()
+        WHERE token_hash = $1 AND revoked_at IS NULL
+        RETURNING user_id
+        "#,
+        hash_token(token),
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+
+    use crate::token_generator::SeededTokenGenerator;
+
+    use super::{bearer_token, generate_api_token};
+
+    fn headers(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(authorization).unwrap());
+
+        headers
+    }
+
+    #[test]
+    fn tokens_are_prefixed() {
+        let token = generate_api_token(&SeededTokenGenerator::new(42));
+
+        assert!(token.starts_with("z2p_"));
+        assert_eq!(token.len(), 44);
+    }
+
+    #[test]
+    fn bearer_tokens_are_read_from_the_authorization_header() {
+        assert_eq!(bearer_token(&headers("Bearer z2p_abc")), Some("z2p_abc"));
+        assert_eq!(bearer_token(&headers("Basic dXNlcjpwYXNz")), None);
+        assert_eq!(bearer_token(&HeaderMap::new()), None);
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Context};
+use argon2::PasswordHash;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    configuration::InitialAdminSettings, domain::Username, telemetry::spawn_blocking_with_tracing,
+    user_role::UserRole,
+};
+
+use super::{compute_password_hash, PasswordPeppers};
+
+/// The hash to store for the initial admin, computed if it was given a
+/// password in plain text.
+async fn initial_password_hash(
+    settings: &InitialAdminSettings,
+    peppers: &PasswordPeppers,
+) -> Result<Secret<String>, anyhow::Error> {
+    match (&settings.password_hash, &settings.password) {
+        (Some(password_hash), None) => {
+            PasswordHash::new(password_hash.expose_secret())
+                .map_err(|e| anyhow!("The password hash of the initial admin is invalid: {}", e))?;
+
+            Ok(password_hash.clone())
+        }
+        (None, Some(password)) => {
+            if password.expose_secret().is_empty() {
+                return Err(anyhow!("The password of the initial admin is empty"));
+            }
+            let password = password.clone();
+            let peppers = peppers.clone();
+
+            spawn_blocking_with_tracing(move || compute_password_hash(password, &peppers))
+                .await
+                .context("Failed to spawn blocking task.")?
+        }
+        _ => Err(anyhow!(
+            "The initial admin needs either a password or a password hash"
+        )),
+    }
+}
+
+/// Creates the configured admin if no user has its username yet, and makes
+/// sure it's an admin otherwise. The password of an existing user is left
+/// alone.
+#[tracing::instrument(name = "Ensure initial admin", skip_all, fields(username = %settings.username))]
+pub async fn ensure_initial_admin(
+    pool: &PgPool,
+    settings: &InitialAdminSettings,
+    peppers: &PasswordPeppers,
+) -> Result<(), anyhow::Error> {
+    let username = Username::parse(settings.username.clone())
+        .context("The username of the initial admin is invalid")?;
+    let password_hash = initial_password_hash(settings, peppers).await?;
+
+    let created = sqlx::query_scalar!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, role)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (username) DO UPDATE SET role = EXCLUDED.role
+        RETURNING (xmax = 0) AS "created!"
+        "#,
+        Uuid::new_v4(),
+        username.as_ref(),
+        password_hash.expose_secret(),
+        UserRole::Admin as UserRole,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to upsert the initial admin")?;
+
+    if created {
+        tracing::info!("Created the initial admin");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::{net::IpAddr, time::Duration};
+
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use sqlx::PgPool;
+
+/// Failed logins kept for the admin UI, the oldest ones are dropped first.
+const FAILED_LOGIN_LOG_LENGTH: i64 = 1000;
+
+/// A login refused because of its credentials.
+#[derive(Debug, serde::Serialize)]
+pub struct FailedLogin {
+    pub username: String,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub attempted_at: DateTime<Utc>,
+}
+
+impl FailedLogin {
+    pub fn new(username: &str, client_ip: Option<IpAddr>, user_agent: Option<&str>) -> Self {
+        Self {
+            username: username.to_string(),
+            client_ip: client_ip.map(|ip| ip.to_string()),
+            user_agent: user_agent.map(str::to_string),
+            attempted_at: Utc::now(),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct AlertPayload<'a> {
+    #[serde(rename = "type")]
+    alert_type: &'static str,
+    #[serde(flatten)]
+    login: &'a FailedLogin,
+}
+
+/// Reports failed logins to the logs, to the admin UI and, if one is
+/// configured, to an alerting webhook.
+pub struct LoginAlerts {
+    http_client: Client,
+    webhook_url: Option<reqwest::Url>,
+}
+
+impl LoginAlerts {
+    pub fn new(webhook_url: Option<reqwest::Url>, timeout: Duration) -> Self {
+        let http_client = Client::builder().timeout(timeout).build().unwrap();
+
+        Self {
+            http_client,
+            webhook_url,
+        }
+    }
+
+    /// Failing to store or to send the alert is only logged, it mustn't
+    /// change the answer to the login.
+    #[tracing::instrument(name = "Report failed login", skip_all)]
+    pub async fn report(&self, pool: &PgPool, login: FailedLogin) {
+        tracing::warn!(
+            security_event = "login.failed",
+            username = %login.username,
+            client_ip = login.client_ip.as_deref().unwrap_or("unknown"),
+            user_agent = login.user_agent.as_deref().unwrap_or("unknown"),
+            "Failed login attempt"
+        );
+
+        if let Err(e) = store_failed_login(pool, &login).await {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to store the failed login attempt"
+            );
+        }
+
+        if let Some(webhook_url) = self.webhook_url.clone() {
+            let request = self.http_client.post(webhook_url).json(&AlertPayload {
+                alert_type: "login.failed",
+                login: &login,
+            });
+            // Sent in the background, so that a slow endpoint doesn't tell
+            // the client its attempt is being reported.
+            tokio::spawn(async move {
+                if let Err(e) = request.send().await.and_then(|r| r.error_for_status()) {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to send the failed login alert"
+                    );
+                }
+            });
+        }
+    }
+}
+
+/// Appends the attempt to the failed logins, dropping the oldest ones beyond
+/// the last [`FAILED_LOGIN_LOG_LENGTH`].
+#[tracing::instrument(name = "Store failed login", skip_all)]
+async fn store_failed_login(pool: &PgPool, login: &FailedLogin) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO failed_logins (username, client_ip, user_agent, attempted_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        login.username,
+        login.client_ip,
+        login.user_agent,
+        login.attempted_at,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM failed_logins
+        WHERE attempt_id <= (
+            SELECT attempt_id
+            FROM failed_logins
+            ORDER BY attempt_id DESC
+            OFFSET $1
+            LIMIT 1
+        )
+        "#,
+        FAILED_LOGIN_LOG_LENGTH,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await
+}
+
+/// Latest failed logins, most recent first.
+#[tracing::instrument(name = "Get recent failed logins", skip(pool))]
+pub async fn recent_failed_logins(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<FailedLogin>, sqlx::Error> {
+    sqlx::query_as!(
+        FailedLogin,
+        r#"
+        SELECT username, client_ip, user_agent, attempted_at
+        FROM failed_logins
+        ORDER BY attempt_id DESC
+        LIMIT $1
+        "#,
+        limit,
+    )
+    .fetch_all(pool)
+    .await
+}
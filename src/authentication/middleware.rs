@@ -1,19 +1,36 @@
 use std::ops::Deref;
 
+use actix_multipart::Multipart;
 use actix_web::{
     body::MessageBody,
     dev::{ServiceRequest, ServiceResponse},
     error::InternalError,
+    http::header::{self, HeaderMap},
     middleware::Next,
-    FromRequest, HttpMessage,
+    web, FromRequest, HttpMessage, HttpResponse,
 };
+use anyhow::Context;
+use base64::Engine;
+use futures_util::{stream, TryStreamExt};
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+use url::form_urlencoded;
 use uuid::Uuid;
 
 use crate::{
+    configuration::{AdminBasePath, PayloadLimitSettings},
+    domain::Username,
+    payload_limits::PayloadTooLargeError,
     session_state::TypedSession,
+    user_role::get_user_role,
     util::{e500, see_other},
 };
 
+use super::{
+    api_tokens::{bearer_token, validate_api_token},
+    validate_credentials, AuthError, Credentials, PasswordPeppers,
+};
+
 #[derive(Copy, Clone, Debug)]
 pub struct UserId(Uuid);
 
@@ -42,6 +59,13 @@ pub async fn reject_anonymous_users(
     }?;
 
     match session.get_user_id().map_err(e500)? {
+        Some(user_id) if !session.is_registered(user_id).await.map_err(e500)? => {
+            session.log_out().await.map_err(e500)?;
+
+            let response = see_other("/login");
+            let e = anyhow::anyhow!("The user session was revoked");
+            Err(InternalError::from_response(e, response).into())
+        }
         Some(user_id) => {
             req.extensions_mut().insert(UserId(user_id));
 
@@ -54,3 +78,291 @@ pub async fn reject_anonymous_users(
         }
     }
 }
+
+/// Keeps users whose password expired on the password change page until they
+/// pick a new one.
+pub async fn reject_expired_passwords(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let admin_base_path = req
+        .app_data::<web::Data<AdminBasePath>>()
+        .cloned()
+        .ok_or_else(|| e500(anyhow::anyhow!("The admin base path is not configured")))?;
+    let password_path = admin_base_path.join("/password");
+    let allowed_paths = [password_path.clone(), admin_base_path.join("/logout")];
+
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+
+        TypedSession::from_request(http_request, payload).await
+    }?;
+
+    if session.is_password_expired().map_err(e500)?
+        && !allowed_paths.iter().any(|path| path == req.path())
+    {
+        let response = see_other(&password_path);
+        let e = anyhow::anyhow!("The user password has expired");
+        return Err(InternalError::from_response(e, response).into());
+    }
+
+    next.call(req).await
+}
+
+/// Rejects state-changing requests that don't carry the CSRF token of the
+/// session, either in the `X-CSRF-Token` header or in the `csrf_token` field
+/// of an url-encoded or multipart form.
+pub async fn reject_invalid_csrf_tokens(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if req.method().is_safe() {
+        return next.call(req).await;
+    }
+
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+
+        TypedSession::from_request(http_request, payload).await
+    }?;
+    let expected_token = session.get_csrf_token().map_err(e500)?;
+
+    let limits = req
+        .app_data::<web::Data<PayloadLimitSettings>>()
+        .map(|limits| limits.get_ref().clone())
+        .unwrap_or_default();
+    let submitted_token = match req.headers().get("X-CSRF-Token") {
+        Some(value) => value.to_str().ok().map(str::to_string),
+        None if req.content_type() == "application/x-www-form-urlencoded" => {
+            let body = read_payload(&mut req, limits.default_bytes).await?;
+            let token = form_urlencoded::parse(&body)
+                .find(|(field, _)| field == "csrf_token")
+                .map(|(_, value)| value.into_owned());
+            // The handler still has to read the form.
+            req.set_payload(body.into());
+
+            token
+        }
+        None if req.content_type() == "multipart/form-data" => {
+            let limit = limits.publish_bytes.max(MAX_MULTIPART_FORM_SIZE);
+            let body = read_payload(&mut req, limit).await?;
+            let token = multipart_field(req.headers(), body.clone(), "csrf_token").await;
+            // The handler still has to read the form.
+            req.set_payload(body.into());
+
+            token
+        }
+        None => None,
+    };
+
+    match (expected_token, submitted_token) {
+        (Some(expected), Some(submitted)) if csrf_tokens_match(&expected, &submitted) => {
+            next.call(req).await
+        }
+        _ => {
+            let e = anyhow::anyhow!("The request has a missing or invalid CSRF token");
+            Err(InternalError::from_response(e, HttpResponse::Forbidden().finish()).into())
+        }
+    }
+}
+
+/// Compares the tokens in constant time, so that the time it takes doesn't
+/// give away how much of the submitted token is right.
+fn csrf_tokens_match(expected: &str, submitted: &str) -> bool {
+    expected.as_bytes().ct_eq(submitted.as_bytes()).into()
+}
+
+/// Largest multipart form read to look for a CSRF token, unless issues can
+/// be larger: as big as the largest subscriber import.
+const MAX_MULTIPART_FORM_SIZE: usize = 10 * 1024 * 1024;
+
+async fn read_payload(
+    req: &mut ServiceRequest,
+    limit: usize,
+) -> Result<web::Bytes, actix_web::Error> {
+    let mut payload = req.take_payload();
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.try_next().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(PayloadTooLargeError { limit }.into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body.freeze())
+}
+
+/// The value of the first field of the multipart body with the given name.
+async fn multipart_field(headers: &HeaderMap, body: web::Bytes, name: &str) -> Option<String> {
+    let mut multipart = Multipart::new(headers, stream::once(async { Ok(body) }));
+    while let Some(mut field) = multipart.try_next().await.ok()? {
+        if field.name() != Some(name) {
+            continue;
+        }
+
+        let mut value = Vec::new();
+        while let Some(chunk) = field.try_next().await.ok()? {
+            value.extend_from_slice(&chunk);
+        }
+
+        return String::from_utf8(value).ok();
+    }
+
+    None
+}
+
+pub fn basic_authentication(headers: &HeaderMap) -> Result<Credentials, anyhow::Error> {
+    let header_value = headers
+        .get("Authorization")
+        .context("The 'Authorization' header is missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid string")?;
+    let base64_encoded_segment = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic'")?;
+    let decoded_bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64_encoded_segment)
+        .context("Failed to base64-decode 'Basic' credentials")?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .context("The decoded credential string is not valid UTF8")?;
+
+    let mut credentials = decoded_credentials.splitn(2, ':');
+    let username = credentials
+        .next()
+        .map(Username::fold)
+        .ok_or_else(|| anyhow::anyhow!("A username must be providaded in 'Basic' auth"))?;
+    let password = credentials
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("A password must be providaded in 'Basic' auth"))?
+        .to_string()
+        .into();
+
+    Ok(Credentials { username, password })
+}
+
+fn unauthorized_api_client(e: anyhow::Error) -> actix_web::Error {
+    let response = HttpResponse::Unauthorized()
+        .insert_header((header::WWW_AUTHENTICATE, r#"Basic realm="api""#))
+        .finish();
+
+    InternalError::from_response(e, response).into()
+}
+
+/// Who an API client is, from the personal access token of an
+/// `Authorization: Bearer` header or from 'Basic' credentials.
+async fn authenticate_api_client(
+    req: &ServiceRequest,
+    pool: &PgPool,
+) -> Result<Uuid, actix_web::Error> {
+    if let Some(token) = bearer_token(req.headers()) {
+        return match validate_api_token(pool, token).await.map_err(e500)? {
+            Some(user_id) => Ok(user_id),
+            None => Err(unauthorized_api_client(anyhow::anyhow!(
+                "The API token is unknown or revoked"
+            ))),
+        };
+    }
+
+    let credentials = basic_authentication(req.headers()).map_err(unauthorized_api_client)?;
+    let peppers = req
+        .app_data::<web::Data<PasswordPeppers>>()
+        .context("Password peppers are not registered in the application")
+        .map_err(e500)?;
+
+    match validate_credentials(credentials, pool, peppers).await {
+        Ok(user_id) => Ok(user_id),
+        Err(AuthError::InvalidCredentials(e)) => Err(unauthorized_api_client(e)),
+        Err(AuthError::UnexpectedError(e)) => Err(e500(e)),
+    }
+}
+
+pub async fn reject_unauthenticated_api_clients(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .context("Database pool is not registered in the application")
+        .map_err(e500)?
+        .clone();
+
+    let user_id = authenticate_api_client(&req, &pool).await?;
+    req.extensions_mut().insert(UserId(user_id));
+
+    next.call(req).await
+}
+
+/// Lets in API clients through their API token or 'Basic' credentials, as well
+/// as logged in users through their session, so that the admin API can back
+/// both scripts and alternative frontends. Session requests that change state
+/// must carry the CSRF token of the session in the `X-CSRF-Token` header.
+///
+/// The role of the user is made available to the handlers.
+pub async fn reject_unauthenticated_admin_api_clients(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    const PASSWORD_PATH: &str = "/api/v1/admin/password";
+
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .context("Database pool is not registered in the application")
+        .map_err(e500)?
+        .clone();
+
+    if req.headers().contains_key(header::AUTHORIZATION) {
+        let user_id = authenticate_api_client(&req, &pool).await?;
+        let role = get_user_role(&user_id, &pool).await.map_err(e500)?;
+
+        req.extensions_mut().insert(UserId(user_id));
+        req.extensions_mut().insert(role);
+
+        return next.call(req).await;
+    }
+
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+
+        TypedSession::from_request(http_request, payload).await
+    }?;
+
+    let user_id = match session.get_user_id().map_err(e500)? {
+        Some(user_id) if session.is_registered(user_id).await.map_err(e500)? => user_id,
+        _ => {
+            let e = anyhow::anyhow!("The request has neither credentials nor a live session");
+            return Err(unauthorized_api_client(e));
+        }
+    };
+
+    if !req.method().is_safe() {
+        let expected_token = session.get_csrf_token().map_err(e500)?;
+        let submitted_token = req
+            .headers()
+            .get("X-CSRF-Token")
+            .and_then(|value| value.to_str().ok());
+
+        let valid = match (expected_token, submitted_token) {
+            (Some(expected), Some(submitted)) => csrf_tokens_match(&expected, submitted),
+            _ => false,
+        };
+        if !valid {
+            let e = anyhow::anyhow!("The request has a missing or invalid CSRF token");
+            return Err(InternalError::from_response(e, HttpResponse::Forbidden().finish()).into());
+        }
+    }
+
+    if session.is_password_expired().map_err(e500)? && req.path() != PASSWORD_PATH {
+        let e = anyhow::anyhow!("The user password has expired");
+        return Err(InternalError::from_response(e, HttpResponse::Forbidden().finish()).into());
+    }
+
+    let role = match session.get_user_role().map_err(e500)? {
+        Some(role) => role,
+        None => get_user_role(&user_id, &pool).await.map_err(e500)?,
+    };
+
+    req.extensions_mut().insert(UserId(user_id));
+    req.extensions_mut().insert(role);
+
+    next.call(req).await
+}
@@ -1,7 +1,20 @@
+mod api_tokens;
+mod initial_admin;
+mod login_attempts;
 mod middleware;
 mod password;
 
-pub use middleware::{reject_anonymous_users, UserId};
+pub use api_tokens::{
+    generate_api_token, get_api_tokens, revoke_user_api_token, store_api_token, ApiToken,
+};
+pub use initial_admin::ensure_initial_admin;
+pub use login_attempts::{recent_failed_logins, FailedLogin, LoginAlerts};
+pub use middleware::{
+    basic_authentication, reject_anonymous_users, reject_expired_passwords,
+    reject_invalid_csrf_tokens, reject_unauthenticated_admin_api_clients,
+    reject_unauthenticated_api_clients, UserId,
+};
 pub use password::{
-    change_password, compute_password_hash, validate_credentials, AuthError, Credentials,
+    change_password, compute_password_hash, get_password_changed_at, is_password_reused,
+    validate_credentials, AuthError, Credentials, PasswordPeppers,
 };
@@ -1,13 +1,21 @@
-use anyhow::Context;
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context};
 use argon2::{
-    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
-    PasswordVerifier, Version,
+    password_hash::SaltString, Algorithm, Argon2, KeyId, Params, ParamsBuilder, PasswordHash,
+    PasswordHasher, PasswordVerifier, Version,
 };
+use chrono::{DateTime, Utc};
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::telemetry::spawn_blocking_with_tracing;
+use crate::{configuration::PasswordPepperSettings, telemetry::spawn_blocking_with_tracing};
+
+/// Argon2 parameters of the hashes of new passwords.
+const MEMORY_COST: u32 = 12288;
+const TIME_COST: u32 = 3;
+const PARALLELISM: u32 = 1;
 
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
@@ -22,10 +30,77 @@ pub struct Credentials {
     pub password: Secret<String>,
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+/// Server-side secrets mixed into password hashes, so that a dump of the
+/// `users` table alone isn't enough to brute force the passwords.
+///
+/// Hashes keep the version of the pepper they were computed with as their
+/// Argon2 `keyid`. Configuring a new current version leaves the hashes made
+/// with the previous ones verifiable, as long as their secrets are kept.
+/// Hashes without a `keyid` were computed before any pepper was configured.
+#[derive(Clone, Debug, Default)]
+pub struct PasswordPeppers {
+    current_version: Option<u32>,
+    secrets: HashMap<u32, Secret<String>>,
+}
+
+impl PasswordPeppers {
+    pub fn new(settings: Option<PasswordPepperSettings>) -> Result<Self, anyhow::Error> {
+        let Some(settings) = settings else {
+            return Ok(Self::default());
+        };
+
+        let mut secrets = HashMap::new();
+        for pepper in settings.secrets {
+            if pepper.secret.expose_secret().is_empty() {
+                return Err(anyhow!("The secret of pepper {} is empty", pepper.version));
+            }
+            if secrets.insert(pepper.version, pepper.secret).is_some() {
+                return Err(anyhow!("Pepper {} is configured twice", pepper.version));
+            }
+        }
+        if !secrets.contains_key(&settings.current_version) {
+            return Err(anyhow!(
+                "The current pepper version {} has no secret",
+                settings.current_version
+            ));
+        }
+
+        Ok(Self {
+            current_version: Some(settings.current_version),
+            secrets,
+        })
+    }
+
+    /// `keyid` of the hashes of new passwords.
+    fn current_keyid(&self) -> Vec<u8> {
+        self.current_version
+            .map(|version| version.to_be_bytes().to_vec())
+            .unwrap_or_default()
+    }
+
+    /// The secret a hash was computed with, given its `keyid`.
+    fn secret(&self, keyid: &[u8]) -> Result<Option<&Secret<String>>, anyhow::Error> {
+        if keyid.is_empty() {
+            return Ok(None);
+        }
+
+        let version = keyid
+            .try_into()
+            .map(u32::from_be_bytes)
+            .map_err(|_| anyhow!("Invalid pepper version in password hash"))?;
+
+        self.secrets
+            .get(&version)
+            .map(Some)
+            .ok_or_else(|| anyhow!("Pepper {} is not configured", version))
+    }
+}
+
+#[tracing::instrument(name = "Validate credentials", skip(credentials, pool, peppers))]
 pub async fn validate_credentials(
     credentials: Credentials,
     pool: &PgPool,
+    peppers: &PasswordPeppers,
 ) -> Result<uuid::Uuid, AuthError> {
     let mut user_id = None;
     let mut expected_password_hash = Secret::new(
@@ -44,37 +119,122 @@ pub async fn validate_credentials(
         expected_password_hash = stored_password_hash;
     }
 
+    let stored_password_hash = expected_password_hash.clone();
+    let password = credentials.password.clone();
+    let verifying_peppers = peppers.clone();
     spawn_blocking_with_tracing(move || {
-        verify_password_hash(expected_password_hash, credentials.password)
+        verify_password_hash(
+            expected_password_hash,
+            credentials.password,
+            &verifying_peppers,
+        )
     })
     .await
     .context("Failed to spawn blocking task")??;
 
-    user_id.ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Unknown username")))
+    let user_id = user_id
+        .ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Unknown username")))?;
+
+    if needs_rehash(&stored_password_hash, peppers) {
+        if let Err(e) =
+            upgrade_password_hash(user_id, stored_password_hash, password, pool, peppers).await
+        {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to upgrade the password hash of the user"
+            );
+        }
+    }
+
+    Ok(user_id)
+}
+
+/// Whether a hash was computed with another algorithm, other parameters or
+/// another pepper than the hashes of new passwords.
+fn needs_rehash(password_hash: &Secret<String>, peppers: &PasswordPeppers) -> bool {
+    let Ok(password_hash) = PasswordHash::new(password_hash.expose_secret()) else {
+        return false;
+    };
+    let Ok(params) = Params::try_from(&password_hash) else {
+        return false;
+    };
+
+    password_hash.algorithm != Algorithm::Argon2id.ident()
+        || password_hash.version != Some(Version::V0x13.into())
+        || params.m_cost() != MEMORY_COST
+        || params.t_cost() != TIME_COST
+        || params.p_cost() != PARALLELISM
+        || params.keyid() != peppers.current_keyid()
+}
+
+/// Replaces an outdated hash with one computed like the hashes of new
+/// passwords, unless the password changed in the meantime.
+#[tracing::instrument(
+    name = "Upgrade password hash",
+    skip(stored_password_hash, password, pool, peppers)
+)]
+async fn upgrade_password_hash(
+    user_id: Uuid,
+    stored_password_hash: Secret<String>,
+    password: Secret<String>,
+    pool: &PgPool,
+    peppers: &PasswordPeppers,
+) -> Result<(), anyhow::Error> {
+    let peppers = peppers.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &peppers))
+            .await?
+            .context("Failed to hash password")?;
+
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $1
+        WHERE user_id = $2 AND password_hash = $3
+        "#,
+        password_hash.expose_secret(),
+        user_id,
+        stored_password_hash.expose_secret(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the upgraded password hash")?;
+
+    Ok(())
 }
 
 #[tracing::instrument(
     name = "Verify password hash",
-    skip(expected_password_hash, password_candidate)
+    skip(expected_password_hash, password_candidate, peppers)
 )]
 fn verify_password_hash(
     expected_password_hash: Secret<String>,
     password_candidate: Secret<String>,
+    peppers: &PasswordPeppers,
 ) -> Result<(), AuthError> {
     let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
         .context("Failed to parse hash in PHC string format")?;
+    let params = Params::try_from(&expected_password_hash)
+        .context("Failed to parse the parameters of the password hash")?;
+    let argon2 = match peppers.secret(params.keyid())? {
+        Some(pepper) => Argon2::new_with_secret(
+            pepper.expose_secret().as_bytes(),
+            Algorithm::Argon2id,
+            Version::V0x13,
+            params,
+        )
+        .context("Failed to build a peppered password verifier")?,
+        None => Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+    };
 
-    Argon2::new(
-        Algorithm::Argon2id,
-        Version::V0x13,
-        Params::new(19000, 2, 1, None).unwrap(),
-    )
-    .verify_password(
-        password_candidate.expose_secret().as_bytes(),
-        &expected_password_hash,
-    )
-    .context("Invalid password")
-    .map_err(AuthError::InvalidCredentials)
+    argon2
+        .verify_password(
+            password_candidate.expose_secret().as_bytes(),
+            &expected_password_hash,
+        )
+        .context("Invalid password")
+        .map_err(AuthError::InvalidCredentials)
 }
 
 #[tracing::instrument(name = "Get stired credentials", skip(username, pool))]
@@ -98,42 +258,282 @@ async fn get_stored_credentials(
     Ok(row)
 }
 
-pub fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+pub fn compute_password_hash(
+    password: Secret<String>,
+    peppers: &PasswordPeppers,
+) -> Result<Secret<String>, anyhow::Error> {
     let salt = SaltString::generate(&mut rand::thread_rng());
-    let password_hash = Argon2::new(
-        Algorithm::Argon2id,
-        Version::V0x13,
-        Params::new(12288, 3, 1, None).unwrap(),
-    )
-    .hash_password(password.expose_secret().as_bytes(), &salt)
-    .unwrap()
-    .to_string();
+    let mut params = ParamsBuilder::new();
+    params
+        .m_cost(MEMORY_COST)
+        .t_cost(TIME_COST)
+        .p_cost(PARALLELISM);
+
+    let pepper = match peppers.current_version {
+        Some(version) => {
+            params.keyid(
+                KeyId::new(&version.to_be_bytes()).context("Failed to encode pepper version")?,
+            );
+            peppers.secrets.get(&version)
+        }
+        None => None,
+    };
+    let params = params
+        .build()
+        .context("Invalid password hashing parameters")?;
+
+    let argon2 = match pepper {
+        Some(pepper) => Argon2::new_with_secret(
+            pepper.expose_secret().as_bytes(),
+            Algorithm::Argon2id,
+            Version::V0x13,
+            params,
+        )
+        .context("Failed to build a peppered password hasher")?,
+        None => Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+    };
+    let password_hash = argon2
+        .hash_password(password.expose_secret().as_bytes(), &salt)
+        .context("Failed to hash password")?
+        .to_string();
 
     Ok(Secret::new(password_hash))
 }
 
-#[tracing::instrument(name = "Change password", skip(password, pool))]
+#[tracing::instrument(name = "Get password change date", skip(pool))]
+pub async fn get_password_changed_at(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<DateTime<Utc>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT password_changed_at
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the password change date")?;
+
+    Ok(row.password_changed_at)
+}
+
+/// Checks if the password matches any of the last `history_size` passwords of
+/// the user, the current one included.
+#[tracing::instrument(name = "Check password reuse", skip(password, pool, peppers))]
+pub async fn is_password_reused(
+    user_id: Uuid,
+    password: Secret<String>,
+    history_size: u32,
+    pool: &PgPool,
+    peppers: &PasswordPeppers,
+) -> Result<bool, anyhow::Error> {
+    let password_hashes: Vec<Secret<String>> = sqlx::query!(
+        r#"
+        SELECT password_hash as "password_hash!"
+        FROM users
+        WHERE user_id = $1
+        UNION ALL
+        (
+            SELECT password_hash
+            FROM password_history
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+        )
+        "#,
+        user_id,
+        i64::from(history_size.saturating_sub(1)),
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the password history")?
+    .into_iter()
+    .map(|row| Secret::new(row.password_hash))
+    .collect();
+
+    let peppers = peppers.clone();
+    spawn_blocking_with_tracing(move || {
+        password_hashes
+            .into_iter()
+            .any(|hash| verify_password_hash(hash, password.clone(), &peppers).is_ok())
+    })
+    .await
+    .context("Failed to spawn blocking task")
+}
+
+#[tracing::instrument(name = "Change password", skip(password, pool, peppers))]
 pub async fn change_password(
     user_id: Uuid,
     password: Secret<String>,
+    history_size: u32,
     pool: &PgPool,
+    peppers: &PasswordPeppers,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password")?;
+    let peppers = peppers.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &peppers))
+            .await?
+            .context("Failed to hash password")?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO password_history (id, user_id, password_hash, created_at)
+        SELECT $1, user_id, password_hash, $2
+        FROM users
+        WHERE user_id = $3
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        user_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store the previous password in the history")?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM password_history
+        WHERE user_id = $1 AND id NOT IN (
+            SELECT id
+            FROM password_history
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+        )
+        "#,
+        user_id,
+        i64::from(history_size.saturating_sub(1)),
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to trim the password history")?;
 
     sqlx::query!(
         r#"
         UPDATE users
-        SET password_hash = $1
-        WHERE user_id = $2
+        SET password_hash = $1, password_changed_at = $2
+        WHERE user_id = $3
         "#,
         password_hash.expose_secret(),
+        Utc::now(),
         user_id,
     )
-    .execute(pool)
+    .execute(&mut *transaction)
     .await
     .context("Failed to change user's password in the database")?;
 
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to change the password")?;
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use secrecy::{ExposeSecret, Secret};
+
+    use super::{compute_password_hash, needs_rehash, verify_password_hash, PasswordPeppers};
+    use crate::configuration::{PasswordPepperSettings, PepperSecretSettings};
+
+    fn peppers(current_version: u32, secrets: &[(u32, &str)]) -> PasswordPeppers {
+        PasswordPeppers::new(Some(PasswordPepperSettings {
+            current_version,
+            secrets: secrets
+                .iter()
+                .map(|(version, secret)| PepperSecretSettings {
+                    version: *version,
+                    secret: Secret::new(secret.to_string()),
+                })
+                .collect(),
+        }))
+        .unwrap()
+    }
+
+    fn password() -> Secret<String> {
+        Secret::new("everything-has-to-start-somewhere".into())
+    }
+
+    #[test]
+    fn peppered_hashes_record_the_pepper_version() {
+        let hash = compute_password_hash(password(), &peppers(7, &[(7, "pepper")])).unwrap();
+
+        assert!(hash.expose_secret().contains("keyid="));
+    }
+
+    #[test]
+    fn peppered_hashes_are_not_verified_without_their_pepper() {
+        let hash = compute_password_hash(password(), &peppers(1, &[(1, "pepper")])).unwrap();
+
+        assert_err!(verify_password_hash(
+            hash.clone(),
+            password(),
+            &PasswordPeppers::default()
+        ));
+        assert_err!(verify_password_hash(
+            hash,
+            password(),
+            &peppers(1, &[(1, "another-pepper")])
+        ));
+    }
+
+    #[test]
+    fn hashes_of_previous_peppers_are_verified_after_a_rotation() {
+        let unpeppered = compute_password_hash(password(), &PasswordPeppers::default()).unwrap();
+        let old = compute_password_hash(password(), &peppers(1, &[(1, "old")])).unwrap();
+        let rotated = peppers(2, &[(1, "old"), (2, "new")]);
+
+        assert_ok!(verify_password_hash(unpeppered, password(), &rotated));
+        assert_ok!(verify_password_hash(old, password(), &rotated));
+        let new = compute_password_hash(password(), &rotated).unwrap();
+        assert_ok!(verify_password_hash(new, password(), &rotated));
+    }
+
+    #[test]
+    fn the_current_pepper_must_be_configured() {
+        let settings = PasswordPepperSettings {
+            current_version: 2,
+            secrets: vec![PepperSecretSettings {
+                version: 1,
+                secret: Secret::new("old".into()),
+            }],
+        };
+
+        assert_err!(PasswordPeppers::new(Some(settings)));
+    }
+
+    #[test]
+    fn hashes_computed_like_new_ones_are_not_rehashed() {
+        let peppers = peppers(1, &[(1, "pepper")]);
+        let hash = compute_password_hash(password(), &peppers).unwrap();
+
+        assert!(!needs_rehash(&hash, &peppers));
+    }
+
+    #[test]
+    fn hashes_with_outdated_parameters_or_peppers_are_rehashed() {
+        let weak = Secret::new(
+            "$argon2i$v=19$m=4096,t=2,p=1$\
+            mX5753E+aPsfXck0YnbNPw$\
+            cB4Uy6OGWwkHzjaESqhvc3jWP7ZEpHU9L2xZBhm/OOU"
+                .to_string(),
+        );
+        let unpeppered = compute_password_hash(password(), &PasswordPeppers::default()).unwrap();
+        let old_pepper = compute_password_hash(password(), &peppers(1, &[(1, "old")])).unwrap();
+        let rotated = peppers(2, &[(1, "old"), (2, "new")]);
+
+        assert!(needs_rehash(&weak, &PasswordPeppers::default()));
+        assert!(needs_rehash(&unpeppered, &rotated));
+        assert!(needs_rehash(&old_pepper, &rotated));
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context};
+use reqwest::Client;
+
+/// Path of the endpoint answering with the nonce of this instance.
+pub const PROBE_PATH: &str = "/health_check/probe";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BaseUrlStatus {
+    Reachable,
+    Unreachable(String),
+}
+
+/// Makes sure the links put in emails (confirmation, preferences, tracking...)
+/// lead back to this application when followed from the public internet.
+///
+/// The probe endpoint is requested through an external fetch service, which
+/// receives the url to fetch in the `url` query parameter and answers with
+/// the body it got. Only when that body is the nonce of this instance is the
+/// base url considered reachable. Results are cached for a while, so sends
+/// don't wait on the external service each time.
+pub struct BaseUrlCheck {
+    enabled: bool,
+    http_client: Client,
+    checker_url: reqwest::Url,
+    probe_url: String,
+    nonce: String,
+    cache_ttl: Duration,
+    last_check: Mutex<Option<(Instant, BaseUrlStatus)>>,
+}
+
+impl BaseUrlCheck {
+    pub fn new(
+        enabled: bool,
+        checker_url: reqwest::Url,
+        base_url: &str,
+        nonce: String,
+        timeout: Duration,
+        cache_ttl: Duration,
+    ) -> Self {
+        let http_client = Client::builder().timeout(timeout).build().unwrap();
+        let probe_url = format!("{}{}", base_url.trim_end_matches('/'), PROBE_PATH);
+
+        Self {
+            enabled,
+            http_client,
+            checker_url,
+            probe_url,
+            nonce,
+            cache_ttl,
+            last_check: Mutex::new(None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn nonce(&self) -> &str {
+        &self.nonce
+    }
+
+    /// Whether the base url is reachable, always the case when the check is
+    /// disabled.
+    #[tracing::instrument(name = "Check base url reachability", skip(self))]
+    pub async fn status(&self) -> BaseUrlStatus {
+        if !self.enabled {
+            return BaseUrlStatus::Reachable;
+        }
+
+        if let Some((checked_at, status)) = self.last_check.lock().unwrap().as_ref() {
+            if checked_at.elapsed() < self.cache_ttl {
+                return status.clone();
+            }
+        }
+
+        let status = match self.probe().await {
+            Ok(()) => BaseUrlStatus::Reachable,
+            Err(e) => {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "{} is not reachable from the internet",
+                    self.probe_url
+                );
+                BaseUrlStatus::Unreachable(e.to_string())
+            }
+        };
+        *self.last_check.lock().unwrap() = Some((Instant::now(), status.clone()));
+
+        status
+    }
+
+    async fn probe(&self) -> Result<(), anyhow::Error> {
+        let response = self
+            .http_client
+            .get(self.checker_url.clone())
+            .query(&[("url", &self.probe_url)])
+            .send()
+            .await
+            .context("the external checker could not be reached")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!(
+                "the external checker failed to fetch {} ({})",
+                self.probe_url,
+                status
+            ));
+        }
+
+        let body = response
+            .text()
+            .await
+            .context("the external checker returned an unreadable response")?;
+        if body.trim() != self.nonce {
+            return Err(anyhow!(
+                "{} is served by something other than this application",
+                self.probe_url
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wiremock::matchers::{any, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::{BaseUrlCheck, BaseUrlStatus};
+
+    fn base_url_check(checker_uri: &str, cache_ttl: Duration) -> BaseUrlCheck {
+        BaseUrlCheck::new(
+            true,
+            reqwest::Url::parse(checker_uri).unwrap(),
+            "https://newsletter.example.com/",
+            "nonce".into(),
+            Duration::from_millis(400),
+            cache_ttl,
+        )
+    }
+
+    #[tokio::test]
+    async fn disabled_check_does_not_probe_anything() {
+        let mock_server = MockServer::start().await;
+        let check = BaseUrlCheck::new(
+            false,
+            reqwest::Url::parse(&mock_server.uri()).unwrap(),
+            "https://newsletter.example.com",
+            "nonce".into(),
+            Duration::from_millis(400),
+            Duration::ZERO,
+        );
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        assert_eq!(check.status().await, BaseUrlStatus::Reachable);
+    }
+
+    #[tokio::test]
+    async fn base_url_is_reachable_when_the_checker_relays_the_nonce() {
+        let mock_server = MockServer::start().await;
+        let check = base_url_check(&mock_server.uri(), Duration::ZERO);
+
+        Mock::given(query_param(
+            "url",
+            "https://newsletter.example.com/health_check/probe",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_string("nonce"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+        assert_eq!(check.status().await, BaseUrlStatus::Reachable);
+    }
+
+    #[tokio::test]
+    async fn base_url_is_unreachable_when_another_server_answers() {
+        let mock_server = MockServer::start().await;
+        let check = base_url_check(&mock_server.uri(), Duration::ZERO);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_string("<html>parked</html>"))
+            .mount(&mock_server)
+            .await;
+
+        assert!(matches!(
+            check.status().await,
+            BaseUrlStatus::Unreachable(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn results_are_cached() {
+        let mock_server = MockServer::start().await;
+        let check = base_url_check(&mock_server.uri(), Duration::from_secs(60));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(502))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let first = check.status().await;
+        let second = check.status().await;
+
+        assert!(matches!(first, BaseUrlStatus::Unreachable(_)));
+        assert_eq!(first, second);
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use actix_web::web::{Bytes, BytesMut};
+use anyhow::Context;
+use chrono::Utc;
+use futures_util::StreamExt;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+use super::{Blob, BlobKey, BlobStore, BlobStoreError, ByteStream};
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Signs urls to the blobs of a [`LocalBlobStore`], served by the
+/// application itself, so that they can be downloaded without a session
+/// until they expire.
+#[derive(Clone)]
+pub struct BlobUrlSigner {
+    base_url: String,
+    secret: Secret<String>,
+}
+
+impl BlobUrlSigner {
+    pub fn new(base_url: &str, secret: &Secret<String>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            secret: secret.clone(),
+        }
+    }
+
+    fn mac(&self, key: &BlobKey, expires: i64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(format!("{}\n{}", key, expires).as_bytes());
+
+        mac
+    }
+
+    pub fn url(&self, key: &BlobKey, expires_in: Duration) -> String {
+        let expires = Utc::now().timestamp() + expires_in.as_secs() as i64;
+        let signature = hex::encode(self.mac(key, expires).finalize().into_bytes());
+
+        format!(
+            "{}/blobs/{}?expires={}&signature={}",
+            self.base_url, key, expires, signature
+        )
+    }
+
+    pub fn verify(&self, key: &BlobKey, expires: i64, signature: &str) -> bool {
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+
+        expires > Utc::now().timestamp() && self.mac(key, expires).verify_slice(&signature).is_ok()
+    }
+}
+
+/// Keeps blobs as files under a directory, their content type aside.
+pub struct LocalBlobStore {
+    root: PathBuf,
+    signer: BlobUrlSigner,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: PathBuf, signer: BlobUrlSigner) -> Self {
+        Self { root, signer }
+    }
+
+    fn blob_path(&self, key: &BlobKey) -> PathBuf {
+        self.root.join("blobs").join(key.as_ref())
+    }
+
+    fn content_type_path(&self, key: &BlobKey) -> PathBuf {
+        self.root.join("content_types").join(key.as_ref())
+    }
+}
+
+async fn create_parent(path: &Path) -> Result<(), std::io::Error> {
+    match path.parent() {
+        Some(parent) => tokio::fs::create_dir_all(parent).await,
+        None => Ok(()),
+    }
+}
+
+async fn remove_if_exists(path: &Path) -> Result<(), std::io::Error> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl BlobStore for LocalBlobStore {
+    #[tracing::instrument(name = "Store blob on disk", skip(self, body))]
+    async fn put(
+        &self,
+        key: &BlobKey,
+        content_type: &str,
+        mut body: ByteStream,
+    ) -> Result<u64, BlobStoreError> {
+        let path = self.blob_path(key);
+        let content_type_path = self.content_type_path(key);
+        create_parent(&path)
+            .await
+            .and(create_parent(&content_type_path).await)
+            .context("Failed to create the blob directory")?;
+
+        // Written aside first, so that readers never see a partial blob.
+        let partial_path = path.with_file_name(format!(".{}.partial", Uuid::new_v4()));
+        let mut file = tokio::fs::File::create(&partial_path)
+            .await
+            .context("Failed to create the blob file")?;
+
+        let mut size = 0;
+        let written: Result<(), BlobStoreError> = async {
+            while let Some(chunk) = body.next().await {
+                let chunk = chunk?;
+                size += chunk.len() as u64;
+                file.write_all(&chunk)
+                    .await
+                    .context("Failed to write the blob file")?;
+            }
+            file.sync_all()
+                .await
+                .context("Failed to write the blob file")?;
+
+            Ok(())
+        }
+        .await;
+        if let Err(e) = written {
+            let _ = tokio::fs::remove_file(&partial_path).await;
+            return Err(e);
+        }
+
+        tokio::fs::write(&content_type_path, content_type)
+            .await
+            .context("Failed to write the blob content type")?;
+        tokio::fs::rename(&partial_path, &path)
+            .await
+            .context("Failed to move the blob in place")?;
+
+        Ok(size)
+    }
+
+    #[tracing::instrument(name = "Read blob from disk", skip(self))]
+    async fn get(&self, key: &BlobKey) -> Result<Blob, BlobStoreError> {
+        let file = match tokio::fs::File::open(self.blob_path(key)).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Err(BlobStoreError::NotFound),
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context("Failed to open the blob")
+                    .into())
+            }
+        };
+        let content_type = tokio::fs::read_to_string(self.content_type_path(key))
+            .await
+            .unwrap_or_else(|_| "application/octet-stream".into());
+
+        let body = futures_util::stream::unfold(Some(file), |file| async move {
+            let mut file = file?;
+            let mut chunk = BytesMut::with_capacity(READ_CHUNK_SIZE);
+            match file.read_buf(&mut chunk).await {
+                Ok(0) => None,
+                Ok(_) => Some((Ok(Bytes::from(chunk)), Some(file))),
+                Err(e) => {
+                    let e = anyhow::Error::new(e).context("Failed to read the blob");
+                    Some((Err(e.into()), None))
+                }
+            }
+        });
+
+        Ok(Blob {
+            content_type,
+            body: Box::pin(body),
+        })
+    }
+
+    #[tracing::instrument(name = "Delete blob from disk", skip(self))]
+    async fn delete(&self, key: &BlobKey) -> Result<(), BlobStoreError> {
+        remove_if_exists(&self.blob_path(key))
+            .await
+            .context("Failed to delete the blob")?;
+        remove_if_exists(&self.content_type_path(key))
+            .await
+            .context("Failed to delete the blob content type")?;
+
+        Ok(())
+    }
+
+    fn presigned_url(&self, key: &BlobKey, expires_in: Duration) -> Result<String, BlobStoreError> {
+        Ok(self.signer.url(key, expires_in))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix_web::web::Bytes;
+    use futures_util::{stream, TryStreamExt};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::{BlobUrlSigner, LocalBlobStore};
+    use crate::blob_store::{BlobKey, BlobStore, BlobStoreError};
+
+    fn store() -> LocalBlobStore {
+        LocalBlobStore::new(
+            std::env::temp_dir().join(Uuid::new_v4().to_string()),
+            signer(),
+        )
+    }
+
+    fn signer() -> BlobUrlSigner {
+        BlobUrlSigner::new("https://example.com/", &Secret::new("secret".into()))
+    }
+
+    fn key() -> BlobKey {
+        BlobKey::parse("images/logo.png".into()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn blobs_can_be_read_back_until_deleted() {
+        let store = store();
+        let body = stream::iter(["hello ", "world"].map(|s| Ok(Bytes::from_static(s.as_bytes()))));
+
+        let size = store
+            .put(&key(), "text/plain", Box::pin(body))
+            .await
+            .unwrap();
+        let blob = store.get(&key()).await.unwrap();
+        let content: Vec<Bytes> = blob.body.try_collect().await.unwrap();
+
+        assert_eq!(size, 11);
+        assert_eq!(blob.content_type, "text/plain");
+        assert_eq!(content.concat(), b"hello world");
+
+        store.delete(&key()).await.unwrap();
+        assert!(matches!(
+            store.get(&key()).await,
+            Err(BlobStoreError::NotFound)
+        ));
+        store.delete(&key()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_uploads_leave_nothing_behind() {
+        let store = store();
+        let body = stream::iter([
+            Ok(Bytes::from_static(b"partial")),
+            Err(BlobStoreError::TooLarge(7)),
+        ]);
+
+        assert!(store
+            .put(&key(), "text/plain", Box::pin(body))
+            .await
+            .is_err());
+        assert!(matches!(
+            store.get(&key()).await,
+            Err(BlobStoreError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn signed_urls_are_only_valid_for_their_key_until_they_expire() {
+        let signer = signer();
+        let url = url::Url::parse(&signer.url(&key(), Duration::from_secs(60))).unwrap();
+        let query: std::collections::HashMap<_, _> = url.query_pairs().collect();
+        let expires: i64 = query["expires"].parse().unwrap();
+        let signature = &query["signature"];
+
+        assert_eq!(url.path(), "/blobs/images/logo.png");
+        assert!(signer.verify(&key(), expires, signature));
+        assert!(!signer.verify(&key(), expires + 1, signature));
+        let other = BlobKey::parse("images/other.png".into()).unwrap();
+        assert!(!signer.verify(&other, expires, signature));
+        let expired = signer.url(&key(), Duration::ZERO);
+        let expired = url::Url::parse(&expired).unwrap();
+        let query: std::collections::HashMap<_, _> = expired.query_pairs().collect();
+        assert!(!signer.verify(
+            &key(),
+            query["expires"].parse().unwrap(),
+            &query["signature"]
+        ));
+    }
+}
@@ -0,0 +1,309 @@
+mod local;
+mod s3;
+
+use std::{pin::Pin, sync::Arc, time::Duration};
+
+use actix_web::web::Bytes;
+use futures_util::{Stream, StreamExt};
+use secrecy::Secret;
+
+use crate::configuration::{BlobStoreBackend, BlobStoreSettings};
+
+pub use local::{BlobUrlSigner, LocalBlobStore};
+pub use s3::S3BlobStore;
+
+/// Request payloads are bound to their worker thread, hence streams aren't
+/// required to be `Send`.
+pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, BlobStoreError>>>>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum BlobStoreError {
+    #[error("The blob does not exist")]
+    NotFound,
+    #[error("The upload exceeds {0} bytes")]
+    TooLarge(u64),
+    #[error("The upload is not a {0} file")]
+    UnexpectedContent(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+/// Path of a blob in the store, made of `/`-separated segments of letters,
+/// digits, `.`, `-` and `_`, none of them being only dots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlobKey(String);
+
+impl BlobKey {
+    pub fn parse(key: String) -> Result<Self, String> {
+        let is_valid_segment = |segment: &str| {
+            !segment.is_empty()
+                && !segment.chars().all(|c| c == '.')
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        };
+
+        if key.len() > 512 || !key.split('/').all(is_valid_segment) {
+            return Err(format!("{} is not a valid blob key.", key));
+        }
+
+        Ok(Self(key))
+    }
+}
+
+impl AsRef<str> for BlobKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for BlobKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+pub struct Blob {
+    pub content_type: String,
+    pub body: ByteStream,
+}
+
+/// Where uploaded files are kept, e.g. the images of newsletter issues.
+#[async_trait::async_trait(?Send)]
+pub trait BlobStore: Send + Sync {
+    /// Stores the body under the key, replacing any previous blob, and
+    /// returns its size.
+    async fn put(
+        &self,
+        key: &BlobKey,
+        content_type: &str,
+        body: ByteStream,
+    ) -> Result<u64, BlobStoreError>;
+
+    async fn get(&self, key: &BlobKey) -> Result<Blob, BlobStoreError>;
+
+    /// Deleting a missing blob is not an error.
+    async fn delete(&self, key: &BlobKey) -> Result<(), BlobStoreError>;
+
+    /// Url downloading the blob without credentials until it expires.
+    fn presigned_url(&self, key: &BlobKey, expires_in: Duration) -> Result<String, BlobStoreError>;
+}
+
+pub fn build_blob_store(
+    settings: &BlobStoreSettings,
+    base_url: &str,
+    hmac_secret: &Secret<String>,
+) -> Result<Arc<dyn BlobStore>, anyhow::Error> {
+    let store: Arc<dyn BlobStore> = match settings.backend {
+        BlobStoreBackend::Local => Arc::new(LocalBlobStore::new(
+            settings.local.path.clone(),
+            BlobUrlSigner::new(base_url, hmac_secret),
+        )),
+        BlobStoreBackend::S3 => {
+            let s3 = settings
+                .s3
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("The S3 blob store is not configured"))?;
+
+            Arc::new(S3BlobStore::new(
+                s3.endpoint_url()?,
+                s3.bucket.clone(),
+                s3.region.clone(),
+                s3.access_key_id.clone(),
+                s3.secret_access_key.clone(),
+                s3.path_style,
+                s3.timeout(),
+            ))
+        }
+    };
+
+    Ok(store)
+}
+
+/// Kinds of files that can be uploaded, told apart by their first bytes
+/// rather than by the content type the client claims.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UploadKind {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+}
+
+impl UploadKind {
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type {
+            "image/png" => Some(UploadKind::Png),
+            "image/jpeg" => Some(UploadKind::Jpeg),
+            "image/gif" => Some(UploadKind::Gif),
+            "image/webp" => Some(UploadKind::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            UploadKind::Png => "image/png",
+            UploadKind::Jpeg => "image/jpeg",
+            UploadKind::Gif => "image/gif",
+            UploadKind::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            UploadKind::Png => "png",
+            UploadKind::Jpeg => "jpg",
+            UploadKind::Gif => "gif",
+            UploadKind::Webp => "webp",
+        }
+    }
+
+    fn matches(&self, head: &[u8]) -> bool {
+        match self {
+            UploadKind::Png => head.starts_with(b"\x89PNG\r\n\x1a\n"),
+            UploadKind::Jpeg => head.starts_with(b"\xff\xd8\xff"),
+            UploadKind::Gif => head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a"),
+            UploadKind::Webp => {
+                head.len() >= 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WEBP"
+            }
+        }
+    }
+}
+
+/// Bytes needed to recognize every kind of upload.
+const SIGNATURE_LENGTH: usize = 12;
+
+/// Passes the upload through while checking, as it flows, that it starts
+/// like a file of the expected kind and doesn't exceed the size limit.
+pub fn validate_upload<S, E>(body: S, kind: UploadKind, max_size: u64) -> ByteStream
+where
+    S: Stream<Item = Result<Bytes, E>> + 'static,
+    E: std::fmt::Display,
+{
+    struct State<S> {
+        body: Pin<Box<S>>,
+        head: Vec<u8>,
+        size: u64,
+        done: bool,
+    }
+
+    let state = State {
+        body: Box::pin(body),
+        head: Vec::new(),
+        size: 0,
+        done: false,
+    };
+
+    Box::pin(futures_util::stream::unfold(
+        state,
+        move |mut state| async move {
+            if state.done {
+                return None;
+            }
+
+            let chunk = match state.body.next().await {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(e)) => {
+                    state.done = true;
+                    let e = anyhow::anyhow!("Failed to read the upload: {}", e);
+                    return Some((Err(e.into()), state));
+                }
+                None if state.head.len() < SIGNATURE_LENGTH && !kind.matches(&state.head) => {
+                    state.done = true;
+                    let e = BlobStoreError::UnexpectedContent(kind.content_type().into());
+                    return Some((Err(e), state));
+                }
+                None => return None,
+            };
+
+            state.size += chunk.len() as u64;
+            if state.size > max_size {
+                state.done = true;
+                return Some((Err(BlobStoreError::TooLarge(max_size)), state));
+            }
+
+            if state.head.len() < SIGNATURE_LENGTH {
+                let missing = SIGNATURE_LENGTH - state.head.len();
+                state
+                    .head
+                    .extend_from_slice(&chunk[..missing.min(chunk.len())]);
+                if state.head.len() == SIGNATURE_LENGTH && !kind.matches(&state.head) {
+                    state.done = true;
+                    let e = BlobStoreError::UnexpectedContent(kind.content_type().into());
+                    return Some((Err(e), state));
+                }
+            }
+
+            Some((Ok(chunk), state))
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::web::Bytes;
+    use futures_util::{stream, TryStreamExt};
+
+    use super::{validate_upload, BlobKey, BlobStoreError, UploadKind};
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    async fn read(chunks: Vec<&'static [u8]>, max_size: u64) -> Result<Vec<u8>, BlobStoreError> {
+        let body = stream::iter(
+            chunks
+                .into_iter()
+                .map(|chunk| Ok::<_, std::io::Error>(Bytes::from_static(chunk))),
+        );
+
+        validate_upload(body, UploadKind::Png, max_size)
+            .try_fold(Vec::new(), |mut content, chunk| async move {
+                content.extend_from_slice(&chunk);
+                Ok(content)
+            })
+            .await
+    }
+
+    #[test]
+    fn keys_cannot_escape_the_store() {
+        for key in [
+            "../secret",
+            "images/../../secret",
+            "/etc/passwd",
+            "a//b",
+            "a b",
+            "",
+        ] {
+            assert!(BlobKey::parse(key.into()).is_err(), "{} was accepted", key);
+        }
+        assert!(BlobKey::parse("images/1f0c.png".into()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn uploads_of_the_expected_kind_go_through() {
+        let content = read(vec![&PNG[..5], &PNG[5..]], 1024).await.unwrap();
+
+        assert_eq!(content, PNG);
+    }
+
+    #[tokio::test]
+    async fn uploads_of_another_kind_are_refused() {
+        let e = read(vec![b"GIF89a-not-a-png"], 1024).await.unwrap_err();
+
+        assert!(matches!(e, BlobStoreError::UnexpectedContent(_)));
+    }
+
+    #[tokio::test]
+    async fn uploads_too_short_to_be_recognized_are_refused() {
+        let e = read(vec![b"\x89PNG"], 1024).await.unwrap_err();
+
+        assert!(matches!(e, BlobStoreError::UnexpectedContent(_)));
+    }
+
+    #[tokio::test]
+    async fn uploads_over_the_size_limit_are_refused() {
+        let e = read(vec![PNG, PNG], PNG.len() as u64).await.unwrap_err();
+
+        assert!(matches!(e, BlobStoreError::TooLarge(_)));
+    }
+}
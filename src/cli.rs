@@ -0,0 +1,233 @@
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    config_schema::describe_configuration,
+    delivery_queue::{inspect_issue, purge_deliveries, requeue_dead_letters, summarize_queue},
+};
+
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Start with the public endpoints in maintenance mode.
+    #[arg(long)]
+    pub maintenance: bool,
+    /// Leave maintenance mode at this time (RFC 3339).
+    #[arg(long, requires = "maintenance")]
+    pub maintenance_until: Option<DateTime<Utc>>,
+    /// Run the pending migrations and exit, without starting the server.
+    #[arg(long)]
+    pub migrate_only: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Inspect and repair the issue delivery queue.
+    Queue {
+        #[command(subcommand)]
+        command: QueueCommand,
+    },
+    /// Inspect the configuration.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print every setting with its type and where it comes from, secrets
+    /// redacted.
+    Schema {
+        /// Print the settings as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum QueueCommand {
+    /// List issues with pending, dead or suppressed deliveries.
+    List,
+    /// Show every pending, dead and suppressed delivery of an issue.
+    Inspect { newsletter_issue_id: Uuid },
+    /// Move dead deliveries of an issue back to the queue.
+    Requeue {
+        newsletter_issue_id: Uuid,
+        /// Only requeue the delivery to this subscriber.
+        #[arg(long)]
+        email: Option<String>,
+    },
+    /// Drop the pending deliveries of an issue.
+    Purge {
+        newsletter_issue_id: Uuid,
+        /// Only purge the delivery to this subscriber.
+        #[arg(long)]
+        email: Option<String>,
+        /// Purge dead deliveries instead of pending ones.
+        #[arg(long)]
+        dead: bool,
+    },
+}
+
+pub fn run_config_command(command: ConfigCommand) -> Result<(), anyhow::Error> {
+    match command {
+        ConfigCommand::Schema { json } => {
+            let entries = describe_configuration()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+                return Ok(());
+            }
+
+            for entry in entries {
+                println!(
+                    "{}  {}  {}  {}",
+                    entry.key,
+                    entry.value_type,
+                    entry.source.as_str(),
+                    entry.value,
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run_queue_command(command: QueueCommand, pool: &PgPool) -> Result<(), anyhow::Error> {
+    match command {
+        QueueCommand::List => {
+            let issues = summarize_queue(pool).await?;
+            if issues.is_empty() {
+                println!("The delivery queue is empty.");
+            }
+
+            for issue in issues {
+                println!(
+                    "{}  {}  pending: {}  dead: {}  suppressed: {}  {}",
+                    issue.newsletter_issue_id,
+                    issue.published_at.to_rfc3339(),
+                    issue.pending,
+                    issue.dead,
+                    issue.suppressed,
+                    issue.title,
+                );
+            }
+        }
+        QueueCommand::Inspect {
+            newsletter_issue_id,
+        } => {
+            let issue = inspect_issue(pool, newsletter_issue_id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Unknown issue {}", newsletter_issue_id))?;
+
+            println!("{}", issue.title);
+            println!("Delivered: {}", issue.delivered);
+            println!("Pending deliveries: {}", issue.pending.len());
+            for delivery in issue.pending {
+                let lease = delivery
+                    .leased_until
+                    .map(|until| format!("  in flight until: {}", until.to_rfc3339()))
+                    .unwrap_or_default();
+                println!(
+                    "  {}  retries: {}  next attempt: {}{}",
+                    delivery.subscriber_email,
+                    delivery.n_retries,
+                    delivery.execute_after.to_rfc3339(),
+                    lease,
+                );
+            }
+            println!("Dead deliveries: {}", issue.dead.len());
+            for delivery in issue.dead {
+                println!(
+                    "  {}  retries: {}  failed at: {}  error: {}",
+                    delivery.subscriber_email,
+                    delivery.n_retries,
+                    delivery.failed_at.to_rfc3339(),
+                    delivery.last_error,
+                );
+            }
+            println!(
+                "Suppressed by the email provider: {}",
+                issue.suppressed.len()
+            );
+            for recipient in issue.suppressed {
+                println!(
+                    "  {}  at: {}  error: {} (error code {})",
+                    recipient.subscriber_email,
+                    recipient.suppressed_at.to_rfc3339(),
+                    recipient.message,
+                    recipient.error_code,
+                );
+            }
+            println!("Render failures: {}", issue.render_failures.len());
+            for failure in issue.render_failures {
+                println!(
+                    "  {}  at: {}  error: {}",
+                    failure.subscriber_email,
+                    failure.failed_at.to_rfc3339(),
+                    failure.error,
+                );
+            }
+        }
+        QueueCommand::Requeue {
+            newsletter_issue_id,
+            email,
+        } => {
+            let requeued =
+                requeue_dead_letters(pool, newsletter_issue_id, email.as_deref()).await?;
+
+            println!("{} deliveries were requeued.", requeued);
+        }
+        QueueCommand::Purge {
+            newsletter_issue_id,
+            email,
+            dead,
+        } => {
+            let purged =
+                purge_deliveries(pool, newsletter_issue_id, email.as_deref(), dead).await?;
+
+            println!("{} deliveries were purged.", purged);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::{Cli, Command, QueueCommand};
+
+    #[test]
+    fn purge_arguments_are_parsed() {
+        let cli = Cli::try_parse_from([
+            "newsletter",
+            "queue",
+            "purge",
+            "0b5a2b1e-8d4f-4a55-8c2f-3f1d9c3f7a10",
+            "--dead",
+        ])
+        .unwrap();
+
+        match cli.command {
+            Some(Command::Queue {
+                command: QueueCommand::Purge { email, dead, .. },
+            }) => {
+                assert!(email.is_none());
+                assert!(dead);
+            }
+            _ => panic!("Expected the purge command"),
+        }
+    }
+
+    #[test]
+    fn invalid_issue_ids_are_rejected() {
+        assert!(Cli::try_parse_from(["newsletter", "queue", "inspect", "not-an-id"]).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+
+use config::{Source, Value, ValueKind};
+
+use crate::configuration::configuration_layers;
+
+/// Where the effective value of a setting comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigSource {
+    /// `base.yaml`.
+    Default,
+    /// The file of the environment, e.g. `production.yaml`.
+    File,
+    /// An `APP_` environment variable.
+    Env,
+}
+
+impl ConfigSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigSource::Default => "default",
+            ConfigSource::File => "file",
+            ConfigSource::Env => "env",
+        }
+    }
+}
+
+/// A setting as the application sees it once every layer is applied.
+#[derive(Debug, serde::Serialize)]
+pub struct ConfigEntry {
+    /// Dotted path, e.g. `application.port`.
+    pub key: String,
+    #[serde(rename = "type")]
+    pub value_type: &'static str,
+    pub source: ConfigSource,
+    pub value: serde_json::Value,
+}
+
+const REDACTED: &str = "[redacted]";
+
+/// Names of the settings holding the `Secret` fields of
+/// [`Settings`](crate::configuration::Settings), whose values are never shown.
+const SECRET_KEYS: &[&str] = &[
+    "redis_uri",
+    "hmac_secret",
+    "previous_hmac_secrets",
+    "password",
+    "password_hash",
+    "secrets",
+    "authorization_token",
+    "api_key",
+    "secret",
+    "secret_access_key",
+];
+
+/// Lists every setting set by the configuration files and the environment,
+/// along with its type and the layer it comes from. Settings left to the
+/// defaults of their struct are not listed.
+///
+/// The type of a setting is the one of the lowest layer setting it, as
+/// environment variables are always read as strings.
+pub fn describe_configuration() -> Result<Vec<ConfigEntry>, config::ConfigError> {
+    let layers = configuration_layers();
+    let mut entries = BTreeMap::new();
+    describe_layer(
+        &mut entries,
+        ConfigSource::Default,
+        layers.defaults.collect()?,
+    );
+    describe_layer(&mut entries, ConfigSource::File, layers.file.collect()?);
+    describe_layer(
+        &mut entries,
+        ConfigSource::Env,
+        layers.environment.collect()?,
+    );
+
+    Ok(entries.into_values().collect())
+}
+
+fn describe_layer(
+    entries: &mut BTreeMap<String, ConfigEntry>,
+    source: ConfigSource,
+    values: config::Map<String, Value>,
+) {
+    let mut leaves = Vec::new();
+    for (key, value) in values {
+        flatten(key, value.kind, &mut leaves);
+    }
+
+    for (key, kind) in leaves {
+        let value_type = entries
+            .get(&key)
+            .map(|entry| entry.value_type)
+            .unwrap_or_else(|| type_name(&kind));
+        let value = if is_secret(&key) {
+            REDACTED.into()
+        } else {
+            to_json(kind)
+        };
+        entries.insert(
+            key.clone(),
+            ConfigEntry {
+                key,
+                value_type,
+                source,
+                value,
+            },
+        );
+    }
+}
+
+/// Tables are walked down to their values, arrays are kept whole.
+fn flatten(key: String, kind: ValueKind, leaves: &mut Vec<(String, ValueKind)>) {
+    match kind {
+        ValueKind::Table(table) if !table.is_empty() => {
+            for (name, value) in table {
+                flatten(format!("{}.{}", key, name), value.kind, leaves);
+            }
+        }
+        kind => leaves.push((key, kind)),
+    }
+}
+
+fn is_secret(key: &str) -> bool {
+    key.split('.').any(|segment| SECRET_KEYS.contains(&segment))
+}
+
+fn type_name(kind: &ValueKind) -> &'static str {
+    match kind {
+        ValueKind::Nil => "null",
+        ValueKind::Boolean(_) => "boolean",
+        ValueKind::I64(_) | ValueKind::I128(_) | ValueKind::U64(_) | ValueKind::U128(_) => {
+            "integer"
+        }
+        ValueKind::Float(_) => "float",
+        ValueKind::String(_) => "string",
+        ValueKind::Table(_) => "table",
+        ValueKind::Array(_) => "array",
+    }
+}
+
+fn to_json(kind: ValueKind) -> serde_json::Value {
+    match kind {
+        ValueKind::Nil => serde_json::Value::Null,
+        ValueKind::Boolean(value) => value.into(),
+        ValueKind::I64(value) => value.into(),
+        ValueKind::U64(value) => value.into(),
+        ValueKind::I128(value) => value.to_string().into(),
+        ValueKind::U128(value) => value.to_string().into(),
+        ValueKind::Float(value) => value.into(),
+        ValueKind::String(value) => value.into(),
+        ValueKind::Table(table) => table
+            .into_iter()
+            .map(|(name, value)| (name, to_json(value.kind)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        ValueKind::Array(values) => values
+            .into_iter()
+            .map(|value| to_json(value.kind))
+            .collect::<Vec<_>>()
+            .into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use config::{Value, ValueKind};
+
+    use super::{describe_layer, ConfigSource};
+
+    fn layer(entries: &[(&str, ValueKind)]) -> config::Map<String, Value> {
+        let mut application = config::Map::new();
+        for (key, kind) in entries {
+            application.insert(key.to_string(), Value::new(None, kind.clone()));
+        }
+        let mut values = config::Map::new();
+        values.insert(
+            "application".to_string(),
+            Value::new(None, ValueKind::Table(application)),
+        );
+
+        values
+    }
+
+    #[test]
+    fn later_layers_override_the_value_and_the_source() {
+        let mut entries = BTreeMap::new();
+
+        describe_layer(
+            &mut entries,
+            ConfigSource::Default,
+            layer(&[("port", ValueKind::I64(8000))]),
+        );
+        describe_layer(
+            &mut entries,
+            ConfigSource::Env,
+            layer(&[("port", ValueKind::String("9000".into()))]),
+        );
+
+        let entry = &entries["application.port"];
+        assert_eq!(entry.source, ConfigSource::Env);
+        assert_eq!(entry.value, "9000");
+        assert_eq!(entry.value_type, "integer");
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        let mut entries = BTreeMap::new();
+
+        describe_layer(
+            &mut entries,
+            ConfigSource::File,
+            layer(&[("hmac_secret", ValueKind::String("hunter2".into()))]),
+        );
+
+        let entry = &entries["application.hmac_secret"];
+        assert_eq!(entry.value, "[redacted]");
+        assert_eq!(entry.value_type, "string");
+    }
+}
@@ -1,18 +1,66 @@
+use std::{fs::File, io::BufReader, path::PathBuf, sync::Arc};
+
+use actix_cors::Cors;
+use actix_web::http::header::CONTENT_TYPE;
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use ipnet::IpNet;
 use secrecy::{ExposeSecret, Secret};
 use serde_aux::prelude::deserialize_number_from_string;
-use sqlx::postgres::{PgConnectOptions, PgSslMode};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions, PgSslMode};
 use sqlx::ConnectOptions;
 
-use crate::domain::{Email, EmailError};
+use crate::{
+    domain::{Email, EmailError, SignupField},
+    email_client::EmailClient,
+    feature_flags::FeatureFlags,
+    request_id::REQUEST_ID_HEADER,
+};
 
 #[derive(Clone, serde::Deserialize)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    pub link_validation: LinkValidationSettings,
+    pub base_url_check: BaseUrlCheckSettings,
+    pub mx_check: MxCheckSettings,
+    pub login_alerts: LoginAlertSettings,
+    pub features: FeatureFlags,
+    pub delivery_queue: DeliveryQueueSettings,
+    pub webhooks: WebhookSettings,
+    pub maintenance: MaintenanceSettings,
+    pub weekly_report: WeeklyReportSettings,
+    pub weekly_digest: WeeklyDigestSettings,
+    pub maintenance_mode: MaintenanceModeSettings,
+    pub blob_store: BlobStoreSettings,
+    pub public_stats: PublicStatsSettings,
+    pub tracking: TrackingSettings,
+    pub referrals: ReferralSettings,
+    pub email_webhooks: EmailWebhookSettings,
+    pub dynamic: DynamicSettings,
+    pub session_backend: SessionBackend,
+    /// Only used with the `redis` session backend.
     pub redis_uri: Secret<String>,
 }
 
+/// Where the state of the sessions is kept.
+///
+/// Only Redis shares sessions between instances. In memory, revoking the
+/// sessions of a user only reaches the instance handling the request, so it's
+/// meant for single instance deployments and local development. In cookies,
+/// sessions can't be revoked at all: logging out everywhere, including after
+/// a password change, only ends the current session.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SessionBackend {
+    Redis,
+    /// Encrypted in the session cookie, without logging out everywhere.
+    Cookie,
+    /// Lost on restarts.
+    InMemory,
+}
+
 #[derive(Clone, serde::Deserialize)]
 pub struct ApplicationSettings {
     pub host: String,
@@ -20,6 +68,150 @@ pub struct ApplicationSettings {
     pub port: u16,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// Secrets replaced by `hmac_secret`, whose cookies are still accepted.
+    #[serde(default)]
+    pub previous_hmac_secrets: Vec<Secret<String>>,
+    pub two_person_rule: TwoPersonRuleSettings,
+    pub password_policy: PasswordPolicySettings,
+    pub cors: CorsSettings,
+    /// Whether the CSS of `<style>` blocks is inlined when issues are published.
+    pub inline_css: bool,
+    /// Serves HTTPS directly, for deployments without a reverse proxy.
+    pub tls: Option<TlsSettings>,
+    /// Routes too noisy to log every successful request of.
+    pub quiet_routes: Vec<QuietRouteSettings>,
+    pub admin_base_path: AdminBasePath,
+    /// Secret mixed into password hashes, none if missing.
+    pub password_pepper: Option<PasswordPepperSettings>,
+    #[serde(default)]
+    pub source_allow_list: SourceAllowListSettings,
+    /// Whether pending migrations are run before the server starts listening.
+    #[serde(default)]
+    pub migrate_on_startup: bool,
+    /// Admin created at startup if missing, none if not configured.
+    pub initial_admin: Option<InitialAdminSettings>,
+    /// Tells the instances of a deployment apart in logs and responses. The
+    /// host name by default, e.g. the name of the container.
+    #[serde(default = "default_instance_id")]
+    pub instance_id: String,
+    /// Stylesheets, scripts and icons served under `/static`.
+    pub static_files: StaticFilesSettings,
+    #[serde(default)]
+    pub signup_page: SignupPageSettings,
+    #[serde(default)]
+    pub payload_limits: PayloadLimitSettings,
+    /// Whether new subscribers confirm their address through an emailed link
+    /// (double opt-in). If not, they are subscribed right away and welcomed.
+    #[serde(default = "default_require_confirmation")]
+    pub require_confirmation: bool,
+    #[serde(default)]
+    pub confirmation_tokens: ConfirmationTokenSettings,
+}
+
+fn default_require_confirmation() -> bool {
+    true
+}
+
+/// How the links confirming a subscription prove they were sent to it.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfirmationTokenMode {
+    /// Random tokens whose hash is stored until they are used.
+    Stored,
+    /// Tokens carrying the subscriber and an expiry, signed with the HMAC
+    /// secret, so that confirming needs no stored token.
+    Signed,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct ConfirmationTokenSettings {
+    /// Of the links sent from now on. Links of either mode are accepted, so
+    /// that the ones already sent keep working after changing it.
+    pub mode: ConfirmationTokenMode,
+    /// How long signed links can be followed. Stored ones don't expire.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub signed_valid_for_hours: u32,
+}
+
+impl ConfirmationTokenSettings {
+    pub fn signed_valid_for(&self) -> Duration {
+        Duration::hours(self.signed_valid_for_hours.into())
+    }
+}
+
+impl Default for ConfirmationTokenSettings {
+    fn default() -> Self {
+        Self {
+            mode: ConfirmationTokenMode::Stored,
+            signed_valid_for_hours: 72,
+        }
+    }
+}
+
+/// Largest request bodies accepted, in bytes. Larger ones are answered with
+/// a 413.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct PayloadLimitSettings {
+    /// Forms anyone can send: subscriptions and logins.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub public_form_bytes: usize,
+    /// Issues being published or previewed, attachments included.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub publish_bytes: usize,
+    /// Every other form or JSON body.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub default_bytes: usize,
+}
+
+impl Default for PayloadLimitSettings {
+    fn default() -> Self {
+        Self {
+            public_form_bytes: 4 * 1024,
+            publish_bytes: 32 * 1024 * 1024,
+            default_bytes: 2 * 1024 * 1024,
+        }
+    }
+}
+
+/// Branding of the public signup page, `/subscribe`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct SignupPageSettings {
+    pub title: String,
+    /// Shown below the title, nothing if missing.
+    pub description: Option<String>,
+    /// Image shown above the title, e.g. one of the static files.
+    pub logo_url: Option<String>,
+    /// CSS color of the subscribe button, the stylesheet's if missing.
+    pub accent_color: Option<String>,
+    /// Extra questions of the subscription form, kept with each subscriber.
+    pub fields: Vec<SignupField>,
+}
+
+impl Default for SignupPageSettings {
+    fn default() -> Self {
+        Self {
+            title: "Subscribe to our newsletter".into(),
+            description: None,
+            logo_url: None,
+            accent_color: None,
+            fields: vec![],
+        }
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct StaticFilesSettings {
+    pub directory: String,
+    /// How long browsers may reuse a file without asking for it again.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_age_seconds: u32,
+}
+
+fn default_instance_id() -> String {
+    std::env::var("HOSTNAME").unwrap_or_else(|_| uuid::Uuid::new_v4().to_string())
 }
 
 impl ApplicationSettings {
@@ -28,6 +220,208 @@ impl ApplicationSettings {
     }
 }
 
+/// An admin to log in with in a fresh environment. Its password is only set
+/// when it's created, changing it later on goes through the admin UI.
+///
+/// The password is given either as a PHC string, e.g. `$argon2id$...`, or
+/// in plain text, e.g. from `APP_APPLICATION__INITIAL_ADMIN__PASSWORD`.
+#[derive(Clone, serde::Deserialize)]
+pub struct InitialAdminSettings {
+    pub username: String,
+    pub password_hash: Option<Secret<String>>,
+    pub password: Option<Secret<String>>,
+}
+
+/// Networks, in CIDR notation, allowed to reach the admin surface. Other
+/// sources get a 404, as if it didn't exist. A missing list allows any source.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct SourceAllowListSettings {
+    /// Sources allowed to reach the admin UI.
+    pub admin: Option<Vec<IpNet>>,
+    /// Sources allowed to reach the admin API and `/health/config`.
+    pub internal: Option<Vec<IpNet>>,
+    /// Reverse proxies trusted to name the client in `X-Forwarded-For`.
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+/// Path prefix the admin UI is mounted at, `/admin` by default. Picking
+/// another one, e.g. `/backstage`, keeps it away from the obvious location.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct AdminBasePath(String);
+
+impl AdminBasePath {
+    /// The path of an admin page, e.g. `join("/dashboard")`.
+    pub fn join(&self, path: &str) -> String {
+        format!("{}{}", self.0, path)
+    }
+}
+
+impl TryFrom<String> for AdminBasePath {
+    type Error = String;
+
+    fn try_from(path: String) -> Result<Self, Self::Error> {
+        let is_valid = path
+            .strip_prefix('/')
+            .map(|segments| {
+                segments.split('/').all(|segment| {
+                    !segment.is_empty()
+                        && segment
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                })
+            })
+            .unwrap_or(false);
+
+        if is_valid {
+            Ok(Self(path))
+        } else {
+            Err(format!(
+                "{} is not a valid admin base path. Use a path like `/admin`, \
+                without a trailing slash.",
+                path
+            ))
+        }
+    }
+}
+
+impl AsRef<str> for AdminBasePath {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct QuietRouteSettings {
+    pub path: String,
+    /// Share of the successful requests that are logged, from 0 (none) to 1.
+    pub sample_rate: f64,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct TwoPersonRuleSettings {
+    pub enabled: bool,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub threshold: usize,
+}
+
+impl TwoPersonRuleSettings {
+    pub fn requires_approval(&self, affected_subscribers: usize) -> bool {
+        self.enabled && affected_subscribers > self.threshold
+    }
+}
+
+/// Cross-origin access, e.g. to embed the subscription form in a widget
+/// served from another domain.
+#[derive(Clone, serde::Deserialize)]
+pub struct CorsSettings {
+    /// Origins allowed to call the application, `*` meaning any of them.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allow_credentials: bool,
+}
+
+impl CorsSettings {
+    pub fn cors(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods(self.allowed_methods.iter().map(String::as_str))
+            .allowed_header(CONTENT_TYPE)
+            .allowed_header(REQUEST_ID_HEADER)
+            .expose_headers([REQUEST_ID_HEADER]);
+
+        for origin in &self.allowed_origins {
+            cors = if origin == "*" {
+                cors.allow_any_origin()
+            } else {
+                cors.allowed_origin(origin)
+            };
+        }
+
+        if self.allow_credentials {
+            cors = cors.supports_credentials();
+        }
+
+        cors
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct TlsSettings {
+    /// PEM file with the certificate chain, leaf first.
+    pub cert_path: PathBuf,
+    /// PEM file with the private key of the certificate.
+    pub key_path: PathBuf,
+}
+
+impl TlsSettings {
+    pub fn server_config(&self) -> Result<rustls::ServerConfig, anyhow::Error> {
+        let mut cert_file = BufReader::new(
+            File::open(&self.cert_path).context("Failed to open the TLS certificate")?,
+        );
+        let cert_chain = rustls_pemfile::certs(&mut cert_file)
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to parse the TLS certificate")?;
+
+        let mut key_file = BufReader::new(
+            File::open(&self.key_path).context("Failed to open the TLS private key")?,
+        );
+        let key = rustls_pemfile::private_key(&mut key_file)
+            .context("Failed to parse the TLS private key")?
+            .context("The TLS private key file has no key")?;
+
+        rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .context("Failed to set the TLS protocol versions")?
+        .with_no_client_auth()
+        .with_single_cert(cert_chain, key)
+        .context("The TLS certificate doesn't match its private key")
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct PasswordPolicySettings {
+    pub enabled: bool,
+    /// Days after which a password must be rotated (0 means never).
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_age_days: u32,
+    /// Number of last passwords, the current one included, that can't be reused.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub history_size: u32,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct PasswordPepperSettings {
+    /// Version of the pepper new password hashes are computed with.
+    pub current_version: u32,
+    /// The current pepper and the previous ones still protecting some hashes.
+    pub secrets: Vec<PepperSecretSettings>,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct PepperSecretSettings {
+    pub version: u32,
+    pub secret: Secret<String>,
+}
+
+impl PasswordPolicySettings {
+    pub fn is_expired(&self, password_changed_at: DateTime<Utc>) -> bool {
+        self.enabled
+            && self.max_age_days > 0
+            && password_changed_at + Duration::days(self.max_age_days.into()) < Utc::now()
+    }
+
+    pub fn history_size(&self) -> u32 {
+        if self.enabled {
+            self.history_size
+        } else {
+            0
+        }
+    }
+}
+
 #[derive(Clone, serde::Deserialize)]
 pub struct DatabaseSettings {
     pub username: String,
@@ -37,9 +431,35 @@ pub struct DatabaseSettings {
     pub host: String,
     pub database_name: String,
     pub require_ssl: bool,
+    /// Connections the pool opens at most.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_connections: u32,
+    /// Connections the pool keeps open even when idle.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_connections: u32,
+    /// Seconds a query waits for a connection before failing.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub acquire_timeout_seconds: u64,
+    /// Seconds after which an idle connection above `min_connections` is
+    /// closed, 0 to keep them open.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub idle_timeout_seconds: u64,
 }
 
 impl DatabaseSettings {
+    pub fn pool_options(&self) -> PgPoolOptions {
+        let idle_timeout = match self.idle_timeout_seconds {
+            0 => None,
+            seconds => Some(std::time::Duration::from_secs(seconds)),
+        };
+
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(std::time::Duration::from_secs(self.acquire_timeout_seconds))
+            .idle_timeout(idle_timeout)
+    }
+
     pub fn without_db(&self) -> PgConnectOptions {
         let ssl_mode = if self.require_ssl {
             PgSslMode::Require
@@ -65,15 +485,75 @@ impl DatabaseSettings {
 pub struct EmailClientSettings {
     pub base_url: String,
     pub sender_email: String,
+    /// Other addresses verified with the provider, that issues may be sent
+    /// from or have their replies sent to.
+    #[serde(default)]
+    pub verified_senders: Vec<String>,
     pub authorization_token: Secret<String>,
     pub timeout_milliseconds: u64,
+    /// Total size of the files attached to an email.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attachments_bytes: u64,
+    #[serde(default)]
+    pub connection: ConnectionSettings,
+    /// Reconciles the suppressions of the provider with ours on startup.
+    #[serde(default)]
+    pub sync_suppressions_on_startup: bool,
 }
 
 impl EmailClientSettings {
+    pub fn client(self) -> EmailClient {
+        let sender_email = self.sender().expect("Invalid sender email address.");
+        let verified_senders = self
+            .verified_senders()
+            .expect("Invalid verified sender email address.");
+        let base_url = self.url().expect("Invalid email base url.");
+        let http_client = self.http_client().expect("Invalid email client settings.");
+
+        EmailClient::new(
+            http_client,
+            base_url,
+            sender_email,
+            self.authorization_token,
+        )
+        .with_max_attachments_bytes(self.max_attachments_bytes)
+        .with_verified_senders(verified_senders)
+    }
+
+    /// HTTP client reusing its connections to the provider across sends.
+    pub fn http_client(&self) -> Result<reqwest::Client, reqwest::Error> {
+        let connection = &self.connection;
+        let mut builder = reqwest::Client::builder()
+            .timeout(self.timeout())
+            .pool_max_idle_per_host(connection.pool_max_idle_per_host)
+            .pool_idle_timeout(std::time::Duration::from_secs(
+                connection.pool_idle_timeout_seconds,
+            ))
+            .tcp_nodelay(connection.tcp_nodelay);
+
+        if connection.http2_keep_alive_interval_seconds > 0 {
+            builder = builder
+                .http2_keep_alive_interval(std::time::Duration::from_secs(
+                    connection.http2_keep_alive_interval_seconds,
+                ))
+                .http2_keep_alive_while_idle(true);
+        }
+
+        builder.build()
+    }
+
     pub fn sender(&self) -> Result<Email, EmailError> {
         Email::parse(self.sender_email.clone())
     }
 
+    pub fn verified_senders(&self) -> Result<Vec<Email>, EmailError> {
+        self.verified_senders
+            .iter()
+            .cloned()
+            .map(Email::parse)
+            .collect()
+    }
+
     pub fn url(&self) -> Result<url::Url, url::ParseError> {
         reqwest::Url::parse(&self.base_url)
     }
@@ -83,6 +563,398 @@ impl EmailClientSettings {
     }
 }
 
+/// Connections to the email provider, tuned for large sends.
+#[derive(Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct ConnectionSettings {
+    /// Idle connections kept open to the provider, 0 to open one per email.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub pool_max_idle_per_host: usize,
+    /// Seconds after which an idle connection is closed.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub pool_idle_timeout_seconds: u64,
+    /// Seconds between the pings keeping HTTP/2 connections alive, 0 to
+    /// disable them.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub http2_keep_alive_interval_seconds: u64,
+    pub tcp_nodelay: bool,
+}
+
+impl Default for ConnectionSettings {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: 32,
+            pool_idle_timeout_seconds: 90,
+            http2_keep_alive_interval_seconds: 0,
+            tcp_nodelay: true,
+        }
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct LinkValidationSettings {
+    pub enabled: bool,
+    pub blocklist: Vec<String>,
+    pub safe_browsing: Option<SafeBrowsingSettings>,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct SafeBrowsingSettings {
+    pub base_url: String,
+    pub api_key: Secret<String>,
+    pub timeout_milliseconds: u64,
+}
+
+impl SafeBrowsingSettings {
+    pub fn url(&self) -> Result<url::Url, url::ParseError> {
+        reqwest::Url::parse(&self.base_url)
+    }
+
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout_milliseconds)
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct BaseUrlCheckSettings {
+    pub enabled: bool,
+    /// External service fetching the url given in its `url` query parameter.
+    pub checker_url: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_milliseconds: u64,
+    /// How long a check result is reused before probing again.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub cache_seconds: u64,
+}
+
+impl BaseUrlCheckSettings {
+    pub fn url(&self) -> Result<url::Url, url::ParseError> {
+        reqwest::Url::parse(&self.checker_url)
+    }
+
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout_milliseconds)
+    }
+
+    pub fn cache_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.cache_seconds)
+    }
+}
+
+/// Lookups of the mail servers of subscribers' domains, done when
+/// `features.mx_validation` is enabled.
+#[derive(Clone, serde::Deserialize)]
+pub struct MxCheckSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_milliseconds: u64,
+    /// How long the answer for a domain is reused before resolving it again.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub cache_seconds: u64,
+}
+
+impl MxCheckSettings {
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout_milliseconds)
+    }
+
+    pub fn cache_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.cache_seconds)
+    }
+}
+
+/// Where failed logins are reported, besides the logs and the admin UI.
+#[derive(Clone, serde::Deserialize)]
+pub struct LoginAlertSettings {
+    /// Gets a JSON payload for every failed login, e.g. an incoming webhook
+    /// of a chat or of an incident management tool.
+    pub webhook_url: Option<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_milliseconds: u64,
+}
+
+impl LoginAlertSettings {
+    pub fn url(&self) -> Result<Option<reqwest::Url>, url::ParseError> {
+        self.webhook_url
+            .as_deref()
+            .map(reqwest::Url::parse)
+            .transpose()
+    }
+
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout_milliseconds)
+    }
+}
+
+/// How the public stats hide the exact size of the list.
+#[derive(Clone, serde::Deserialize)]
+pub struct PublicStatsSettings {
+    /// How long the aggregates are reused before querying them again.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub cache_seconds: u64,
+    /// The subscriber count is rounded to a multiple of this.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub rounding: u64,
+    /// Scale of the Laplace noise added to the subscriber count, none if 0.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub noise_scale: f64,
+}
+
+impl PublicStatsSettings {
+    pub fn cache_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.cache_seconds)
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct ReferralSettings {
+    /// Confirmed referrals a subscriber is thanked by email for reaching.
+    /// None are rewarded if empty.
+    #[serde(default)]
+    pub reward_milestones: Vec<u32>,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct TrackingSettings {
+    /// Most opens and clicks written to the database at once.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub event_batch_size: usize,
+    /// How long opens and clicks may wait for others before being written.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub event_flush_milliseconds: u64,
+}
+
+impl TrackingSettings {
+    pub fn flush_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.event_flush_milliseconds)
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct DeliveryQueueSettings {
+    /// Failed attempts after which a delivery is moved to the dead letters.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_retries: i16,
+    /// Delay before the first retry, doubled on each following attempt.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retry_backoff_seconds: i64,
+    /// Deliveries a worker claims at once, sent to the provider in as few
+    /// requests as it allows. How far each issue got is saved after every
+    /// request, their outcomes when the whole batch has been attempted.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub batch_size: i64,
+    /// How long a worker has to attempt a batch before its deliveries can be
+    /// claimed again, e.g. because it crashed.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lease_seconds: i64,
+    /// Share of the attempted recipients of an issue it failed to render
+    /// for, above which an alert is raised.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub render_failure_alert_rate: f64,
+    /// Sends issues in the name of whoever published them, e.g.
+    /// `"Ursula K. Le Guin" <newsletter@example.com>`.
+    #[serde(default)]
+    pub author_as_sender_name: bool,
+}
+
+impl DeliveryQueueSettings {
+    pub fn retry_backoff(&self) -> Duration {
+        Duration::seconds(self.retry_backoff_seconds)
+    }
+
+    pub fn lease(&self) -> Duration {
+        Duration::seconds(self.lease_seconds)
+    }
+}
+
+/// Webhooks the email provider calls us back on.
+#[derive(Clone, serde::Deserialize)]
+pub struct EmailWebhookSettings {
+    /// Key of the HMAC-SHA256 signature of their bodies.
+    pub secret: Secret<String>,
+    /// Times an issue is sent again to a recipient bouncing it temporarily.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_bounce_retries: i64,
+    /// Delay before the first resend, doubled on each following bounce.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub bounce_retry_backoff_seconds: i64,
+}
+
+impl EmailWebhookSettings {
+    pub fn bounce_retry_backoff(&self) -> Duration {
+        Duration::seconds(self.bounce_retry_backoff_seconds)
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct WebhookSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_milliseconds: u64,
+}
+
+impl WebhookSettings {
+    pub fn client(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .timeout(std::time::Duration::from_millis(self.timeout_milliseconds))
+            .build()
+            .unwrap()
+    }
+}
+
+/// Upkeep of the monthly partitions of the subscriber events.
+#[derive(Clone, serde::Deserialize)]
+pub struct MaintenanceSettings {
+    /// Pause between two runs of the maintenance job.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub interval_seconds: u64,
+    /// Months, after the current one, whose partition is created in advance.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub partitions_ahead_months: i32,
+    /// Months of events kept besides the current one (0 means forever).
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retention_months: i32,
+    pub tables: TableMaintenanceSettings,
+}
+
+impl MaintenanceSettings {
+    pub fn interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.interval_seconds)
+    }
+}
+
+/// Statistics and indexes of the busiest tables, refreshed once a day in a
+/// low-traffic window. The window is in UTC hours and may wrap around
+/// midnight, e.g. from 23 to 2.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct TableMaintenanceSettings {
+    pub enabled: bool,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_start_hour: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_end_hour: u32,
+    /// Whether indexes are rebuilt after the tables are analyzed.
+    pub reindex: bool,
+}
+
+/// Settings read again, without restarting, when the process gets `SIGHUP`.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct DynamicSettings {
+    /// Directives in the `RUST_LOG` syntax. `RUST_LOG` wins at startup, the
+    /// configured filter is only applied once it's changed.
+    pub log_filter: String,
+    /// Emails sent per second at most, across issues and transactional
+    /// emails. No limit if missing.
+    #[serde(default)]
+    pub max_emails_per_second: Option<u32>,
+    /// Emails sent at once before `max_emails_per_second` spaces them out,
+    /// matching the burst the provider tolerates. 1 if missing.
+    #[serde(default)]
+    pub max_email_burst: Option<u32>,
+    /// Templates are read from it again on each reload, even if it's the same.
+    pub template_directory: String,
+}
+
+/// Growth report emailed each week to the admins who opted in.
+#[derive(Clone, serde::Deserialize)]
+pub struct WeeklyReportSettings {
+    pub enabled: bool,
+    /// Pause between two checks for a week left to report.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub check_interval_seconds: u64,
+}
+
+impl WeeklyReportSettings {
+    pub fn check_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.check_interval_seconds)
+    }
+}
+
+/// Digest of the issues of the week, emailed to the subscribers asking for
+/// one instead of every issue.
+#[derive(Clone, serde::Deserialize)]
+pub struct WeeklyDigestSettings {
+    pub enabled: bool,
+    /// Pause between two checks for a week left to send the digest of.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub check_interval_seconds: u64,
+}
+
+impl WeeklyDigestSettings {
+    pub fn check_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.check_interval_seconds)
+    }
+}
+
+/// Takes the public endpoints offline and pauses issue deliveries.
+#[derive(Clone, serde::Deserialize)]
+pub struct MaintenanceModeSettings {
+    pub enabled: bool,
+    /// When maintenance is left on its own, never if missing.
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// Suggested to clients when there's no end time.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retry_after_seconds: u64,
+}
+
+/// Where uploaded files are kept.
+#[derive(Clone, serde::Deserialize)]
+pub struct BlobStoreSettings {
+    pub backend: BlobStoreBackend,
+    /// Uploads larger than this are refused while they're received.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_upload_bytes: u64,
+    /// How long the links handed out to download a blob stay valid.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub url_expiration_seconds: u64,
+    pub local: LocalBlobStoreSettings,
+    pub s3: Option<S3BlobStoreSettings>,
+}
+
+impl BlobStoreSettings {
+    pub fn url_expiration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.url_expiration_seconds)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlobStoreBackend {
+    Local,
+    S3,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct LocalBlobStoreSettings {
+    /// Directory holding the blobs, created when missing.
+    pub path: PathBuf,
+}
+
+/// Any S3 compatible service, e.g. AWS, MinIO or R2.
+#[derive(Clone, serde::Deserialize)]
+pub struct S3BlobStoreSettings {
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: Secret<String>,
+    /// Addresses the bucket in the path rather than in the host name, as
+    /// most self-hosted services expect.
+    #[serde(default)]
+    pub path_style: bool,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_milliseconds: u64,
+}
+
+impl S3BlobStoreSettings {
+    pub fn endpoint_url(&self) -> Result<url::Url, url::ParseError> {
+        reqwest::Url::parse(&self.endpoint)
+    }
+
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout_milliseconds)
+    }
+}
+
 pub enum Environment {
     Local,
     Production,
@@ -95,6 +967,14 @@ impl Environment {
             Environment::Production => "production",
         }
     }
+
+    /// The file of the environment as it was when the binary was built.
+    fn embedded_file(&self) -> &'static str {
+        match self {
+            Environment::Local => include_str!("../configuration/local.yaml"),
+            Environment::Production => include_str!("../configuration/production.yaml"),
+        }
+    }
 }
 
 impl TryFrom<String> for Environment {
@@ -112,28 +992,310 @@ impl TryFrom<String> for Environment {
     }
 }
 
-pub fn get_configuration() -> Result<Settings, config::ConfigError> {
-    let base_path = std::env::current_dir().expect("Failed to determine the current directory");
-    let configuration_directory = base_path.join("configuration");
+/// Where the configuration files are read from, set with
+/// `APP_CONFIGURATION_SOURCE`.
+pub enum ConfigurationSource {
+    /// The `configuration` directory next to the binary.
+    Files,
+    /// The copies of the files built into the binary, so that every setting
+    /// comes either from them or from an `APP_` variable. Meant for images
+    /// without a `configuration` directory. The secrets must come from
+    /// `APP_` variables.
+    Env,
+}
+
+impl TryFrom<String> for ConfigurationSource {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "files" => Ok(Self::Files),
+            "env" => Ok(Self::Env),
+            other => Err(format!(
+                "{} is not a supported configuration source. Use either `files` or `env`.",
+                other
+            )),
+        }
+    }
+}
+
+/// A YAML layer of the configuration, either on disk or built into the binary.
+#[derive(Clone, Debug)]
+pub(crate) enum ConfigurationFile {
+    OnDisk(config::File<config::FileSourceFile, config::FileFormat>),
+    Embedded(config::File<config::FileSourceString, config::FileFormat>),
+}
+
+impl ConfigurationFile {
+    fn embedded(contents: &'static str) -> Self {
+        Self::Embedded(config::File::from_str(contents, config::FileFormat::Yaml))
+    }
+}
+
+impl config::Source for ConfigurationFile {
+    fn clone_into_box(&self) -> Box<dyn config::Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<config::Map<String, config::Value>, config::ConfigError> {
+        match self {
+            ConfigurationFile::OnDisk(file) => file.collect(),
+            ConfigurationFile::Embedded(file) => file.collect(),
+        }
+    }
+}
+
+/// Settings whose value in `base.yaml` is only fit for development, so that
+/// they must be set with an `APP_` variable when the configuration source is
+/// `env`.
+const SECRET_SETTINGS: [&str; 4] = [
+    "application.hmac_secret",
+    "database.password",
+    "email_client.authorization_token",
+    "email_webhooks.secret",
+];
+
+/// The sources of the configuration, from lowest to highest precedence.
+pub(crate) struct ConfigurationLayers {
+    source: ConfigurationSource,
+    /// `base.yaml`.
+    pub defaults: ConfigurationFile,
+    /// Specific to the environment, e.g. `production.yaml`.
+    pub file: ConfigurationFile,
+    /// `APP_` variables, e.g. `APP_APPLICATION__PORT`.
+    pub environment: config::Environment,
+}
+
+impl ConfigurationLayers {
+    /// Fails, with the variables missing, unless every secret comes from an
+    /// `APP_` variable when the configuration source is `env`. The values
+    /// built into the binary would silently be used otherwise.
+    fn check_secrets(&self) -> Result<(), config::ConfigError> {
+        if let ConfigurationSource::Files = self.source {
+            return Ok(());
+        }
+
+        let variables = config::Source::collect(&self.environment)?;
+        let missing: Vec<String> = SECRET_SETTINGS
+            .iter()
+            .filter(|setting| !variables.contains_key(**setting))
+            .map(|setting| format!("APP_{}", setting.to_uppercase().replace('.', "__")))
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(config::ConfigError::Message(format!(
+                "{} must be set when the configuration source is `env`",
+                missing.join(", ")
+            )))
+        }
+    }
+
+    fn build(self) -> Result<Settings, config::ConfigError> {
+        self.check_secrets()?;
 
+        config::Config::builder()
+            .add_source(self.defaults)
+            .add_source(self.file)
+            .add_source(self.environment)
+            .build()?
+            .try_deserialize()
+    }
+}
+
+pub(crate) fn configuration_layers() -> ConfigurationLayers {
     let environment: Environment = std::env::var("APP_ENVIRONMENT")
         .unwrap_or_else(|_| "local".into())
         .try_into()
         .expect("Failed to parse APP_ENVIRONMENT");
-    let environment_filename = format!("{}.yaml", environment.as_str());
-    let settings = config::Config::builder()
-        .add_source(config::File::from(
-            configuration_directory.join("base.yaml"),
-        ))
-        .add_source(config::File::from(
-            configuration_directory.join(environment_filename),
-        ))
-        .add_source(
+    let source: ConfigurationSource = std::env::var("APP_CONFIGURATION_SOURCE")
+        .unwrap_or_else(|_| "files".into())
+        .try_into()
+        .expect("Failed to parse APP_CONFIGURATION_SOURCE");
+
+    layers(
+        environment,
+        source,
+        config::Environment::with_prefix("APP")
+            .prefix_separator("_")
+            .separator("__"),
+    )
+}
+
+fn layers(
+    environment: Environment,
+    source: ConfigurationSource,
+    variables: config::Environment,
+) -> ConfigurationLayers {
+    let (defaults, file) = match source {
+        ConfigurationSource::Files => {
+            let base_path =
+                std::env::current_dir().expect("Failed to determine the current directory");
+            let configuration_directory = base_path.join("configuration");
+            let environment_filename = format!("{}.yaml", environment.as_str());
+
+            (
+                ConfigurationFile::OnDisk(config::File::from(
+                    configuration_directory.join("base.yaml"),
+                )),
+                ConfigurationFile::OnDisk(config::File::from(
+                    configuration_directory.join(environment_filename),
+                )),
+            )
+        }
+        ConfigurationSource::Env => (
+            ConfigurationFile::embedded(EMBEDDED_BASE_FILE),
+            ConfigurationFile::embedded(environment.embedded_file()),
+        ),
+    };
+
+    ConfigurationLayers {
+        source,
+        defaults,
+        file,
+        environment: variables,
+    }
+}
+
+const EMBEDDED_BASE_FILE: &str = include_str!("../configuration/base.yaml");
+
+pub fn get_configuration() -> Result<Settings, config::ConfigError> {
+    configuration_layers().build()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use secrecy::ExposeSecret;
+
+    use super::{
+        layers, AdminBasePath, ConfigurationFile, ConfigurationLayers, ConfigurationSource,
+        Environment, Settings, EMBEDDED_BASE_FILE,
+    };
+
+    #[test]
+    fn admin_base_paths_are_absolute_without_a_trailing_slash() {
+        for path in ["/admin", "/back-stage/ui_2"] {
+            assert!(
+                AdminBasePath::try_from(path.to_string()).is_ok(),
+                "{}",
+                path
+            );
+        }
+        for path in [
+            "", "/", "admin", "/admin/", "//admin", "/ad min", "/admin?x",
+        ] {
+            assert!(
+                AdminBasePath::try_from(path.to_string()).is_err(),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn admin_pages_are_joined_to_the_base_path() {
+        let base_path = AdminBasePath::try_from("/backstage".to_string()).unwrap();
+
+        assert_eq!(base_path.join("/dashboard"), "/backstage/dashboard");
+    }
+
+    fn embedded_local_configuration() -> Result<Settings, config::ConfigError> {
+        config::Config::builder()
+            .add_source(ConfigurationFile::embedded(EMBEDDED_BASE_FILE))
+            .add_source(ConfigurationFile::embedded(
+                Environment::Local.embedded_file(),
+            ))
+            .build()?
+            .try_deserialize::<Settings>()
+    }
+
+    #[test]
+    fn embedded_files_are_a_complete_local_configuration() {
+        assert!(embedded_local_configuration().is_ok());
+    }
+
+    fn env_source_layers(variables: &[(&str, &str)]) -> ConfigurationLayers {
+        let variables = variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        layers(
+            Environment::Production,
+            ConfigurationSource::Env,
             config::Environment::with_prefix("APP")
                 .prefix_separator("_")
-                .separator("__"),
+                .separator("__")
+                .source(Some(variables)),
         )
-        .build()?;
+    }
+
+    #[test]
+    fn the_env_source_requires_the_secrets_from_the_environment() {
+        let layers = env_source_layers(&[("APP_DATABASE__PASSWORD", "db-password")]);
 
-    settings.try_deserialize()
+        let error = match layers.build() {
+            Ok(_) => panic!("The built-in secrets were used"),
+            Err(e) => e.to_string(),
+        };
+
+        assert_eq!(
+            error,
+            "APP_APPLICATION__HMAC_SECRET, APP_EMAIL_CLIENT__AUTHORIZATION_TOKEN, \
+            APP_EMAIL_WEBHOOKS__SECRET must be set when the configuration source is `env`"
+        );
+    }
+
+    #[test]
+    fn the_env_source_needs_no_configuration_files() {
+        let layers = env_source_layers(&[
+            (
+                "APP_APPLICATION__BASE_URL",
+                "https://newsletter.example.com",
+            ),
+            ("APP_APPLICATION__HMAC_SECRET", "hmac-secret"),
+            ("APP_DATABASE__PASSWORD", "db-password"),
+            ("APP_EMAIL_CLIENT__AUTHORIZATION_TOKEN", "postmark-token"),
+            ("APP_EMAIL_WEBHOOKS__SECRET", "webhooks-secret"),
+        ]);
+        assert!(matches!(layers.defaults, ConfigurationFile::Embedded(_)));
+        assert!(matches!(layers.file, ConfigurationFile::Embedded(_)));
+
+        let settings = match layers.build() {
+            Ok(settings) => settings,
+            Err(e) => panic!("Failed to build the configuration: {}", e),
+        };
+
+        assert_eq!(
+            settings.application.hmac_secret.expose_secret(),
+            "hmac-secret"
+        );
+        assert_eq!(settings.database.password.expose_secret(), "db-password");
+        assert_eq!(
+            settings.email_client.authorization_token.expose_secret(),
+            "postmark-token"
+        );
+        assert_eq!(
+            settings.email_webhooks.secret.expose_secret(),
+            "webhooks-secret"
+        );
+    }
+
+    #[test]
+    fn pool_options_follow_the_database_settings() {
+        let mut database = embedded_local_configuration().unwrap().database;
+        database.max_connections = 3;
+        database.min_connections = 1;
+        database.acquire_timeout_seconds = 5;
+        database.idle_timeout_seconds = 0;
+
+        let options = database.pool_options();
+
+        assert_eq!(options.get_max_connections(), 3);
+        assert_eq!(options.get_min_connections(), 1);
+        assert_eq!(options.get_acquire_timeout(), Duration::from_secs(5));
+        assert_eq!(options.get_idle_timeout(), None);
+    }
 }
@@ -0,0 +1,158 @@
+use base64::Engine;
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::configuration::{ConfirmationTokenMode, ConfirmationTokenSettings};
+
+/// What a signed confirmation token carries. Nothing is stored for it, so
+/// it can be followed again until it expires, which only matters while the
+/// subscription is pending.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SignedConfirmation {
+    #[serde(rename = "s")]
+    pub subscriber_id: Uuid,
+    #[serde(rename = "x", with = "chrono::serde::ts_seconds")]
+    pub expires_at: DateTime<Utc>,
+}
+
+impl SignedConfirmation {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
+
+/// Signs the tokens of confirmation links when they aren't stored, and
+/// verifies them whatever the mode.
+#[derive(Clone)]
+pub struct ConfirmationSigner {
+    secret: Secret<String>,
+    mode: ConfirmationTokenMode,
+    valid_for: Duration,
+}
+
+impl ConfirmationSigner {
+    pub fn new(secret: &Secret<String>, settings: &ConfirmationTokenSettings) -> Self {
+        Self {
+            secret: secret.clone(),
+            mode: settings.mode,
+            valid_for: settings.signed_valid_for(),
+        }
+    }
+
+    /// Whether new links carry signed tokens rather than stored ones.
+    pub fn is_signing(&self) -> bool {
+        self.mode == ConfirmationTokenMode::Signed
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        // Keeps the confirmation tokens apart from the other ones signed
+        // with the same secret.
+        mac.update(b"confirmation.");
+        mac.update(payload.as_bytes());
+
+        mac
+    }
+
+    fn sign(&self, confirmation: &SignedConfirmation) -> String {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let payload = engine
+            .encode(serde_json::to_vec(confirmation).expect("Confirmations are serializable"));
+        let signature = engine.encode(self.mac(&payload).finalize().into_bytes());
+
+        format!("{}.{}", payload, signature)
+    }
+
+    /// A token confirming the subscriber until the configured validity runs
+    /// out.
+    pub fn token(&self, subscriber_id: Uuid) -> String {
+        self.sign(&SignedConfirmation {
+            subscriber_id,
+            // Tokens carry whole seconds.
+            expires_at: (Utc::now() + self.valid_for).trunc_subsecs(0),
+        })
+    }
+
+    /// Gives back what the token was created for, if it was signed by us,
+    /// expired or not.
+    pub fn verify(&self, token: &str) -> Option<SignedConfirmation> {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let (payload, signature) = token.split_once('.')?;
+        let signature = engine.decode(signature).ok()?;
+        self.mac(payload).verify_slice(&signature).ok()?;
+
+        serde_json::from_slice(&engine.decode(payload).ok()?).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::{ConfirmationSigner, SignedConfirmation};
+    use crate::configuration::{ConfirmationTokenMode, ConfirmationTokenSettings};
+
+    fn signer(secret: &str) -> ConfirmationSigner {
+        ConfirmationSigner::new(
+            &Secret::new(secret.into()),
+            &ConfirmationTokenSettings {
+                mode: ConfirmationTokenMode::Signed,
+                signed_valid_for_hours: 72,
+            },
+        )
+    }
+
+    fn confirmation() -> SignedConfirmation {
+        SignedConfirmation {
+            subscriber_id: Uuid::new_v4(),
+            expires_at: Utc.with_ymd_and_hms(2024, 10, 28, 9, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn tokens_give_back_their_confirmation() {
+        let signer = signer("secret");
+        let confirmation = confirmation();
+
+        assert_eq!(
+            signer.verify(&signer.sign(&confirmation)),
+            Some(confirmation)
+        );
+    }
+
+    #[test]
+    fn tokens_signed_with_another_secret_are_rejected() {
+        assert_eq!(
+            signer("secret").verify(&signer("other").sign(&confirmation())),
+            None
+        );
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        let signer = signer("secret");
+        let token = signer.sign(&confirmation());
+        let (payload, signature) = token.split_once('.').unwrap();
+        let tampered = format!("{}A.{}", payload, signature);
+
+        assert_eq!(signer.verify(&tampered), None);
+    }
+
+    #[test]
+    fn new_tokens_expire_after_the_configured_validity() {
+        let signer = signer("secret");
+        let subscriber_id = Uuid::new_v4();
+
+        let confirmation = signer.verify(&signer.token(subscriber_id)).unwrap();
+
+        assert_eq!(confirmation.subscriber_id, subscriber_id);
+        assert!(!confirmation.is_expired(Utc::now()));
+        assert!(confirmation.is_expired(Utc::now() + chrono::Duration::hours(73)));
+    }
+}
@@ -0,0 +1,204 @@
+use actix_web::{
+    body::MessageBody,
+    cookie::{Cookie, CookieJar, Key},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderValue, COOKIE},
+    middleware::Next,
+    web,
+};
+use anyhow::anyhow;
+use hkdf::Hkdf;
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+
+/// Length, in bytes, of the master key signing and encrypting cookies.
+const KEY_LENGTH: usize = 64;
+const KEY_DERIVATION_SALT: &[u8] = b"newsletter-cookie-key";
+
+/// Keys protecting the session and flash message cookies.
+///
+/// Cookies are always issued with the current key. Those protected with one
+/// of the previous keys keep being accepted, which allows the HMAC secret to
+/// be rotated without logging everyone out.
+#[derive(Clone)]
+pub struct CookieKeys {
+    current: Key,
+    previous: Vec<Key>,
+}
+
+impl CookieKeys {
+    pub fn new(
+        hmac_secret: &Secret<String>,
+        previous_hmac_secrets: &[Secret<String>],
+    ) -> Result<Self, anyhow::Error> {
+        let current = derive_key(hmac_secret).map_err(|e| anyhow!("Invalid hmac_secret: {}", e))?;
+        if hmac_secret.expose_secret().len() < KEY_LENGTH {
+            tracing::warn!(
+                "hmac_secret is shorter than {} bytes, the cookie key is derived from it. \
+                Move it to previous_hmac_secrets and configure a longer secret.",
+                KEY_LENGTH
+            );
+        }
+
+        let previous = previous_hmac_secrets
+            .iter()
+            .enumerate()
+            .map(|(i, secret)| {
+                derive_key(secret)
+                    .map_err(|e| anyhow!("Invalid previous_hmac_secrets[{}]: {}", i, e))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { current, previous })
+    }
+
+    pub fn current(&self) -> &Key {
+        &self.current
+    }
+
+    /// Re-signs or re-encrypts with the current key a cookie protected with
+    /// one of the previous keys, leaving any other cookie untouched.
+    fn migrate(&self, cookie: Cookie<'static>) -> Cookie<'static> {
+        let name = cookie.name().to_string();
+        let mut jar = CookieJar::new();
+        jar.add_original(cookie.clone());
+
+        if jar.private(&self.current).get(&name).is_some()
+            || jar.signed(&self.current).get(&name).is_some()
+        {
+            return cookie;
+        }
+
+        for key in &self.previous {
+            let mut migrated = CookieJar::new();
+            if let Some(plain) = jar.private(key).get(&name) {
+                migrated.private_mut(&self.current).add(plain);
+            } else if let Some(plain) = jar.signed(key).get(&name) {
+                migrated.signed_mut(&self.current).add(plain);
+            } else {
+                continue;
+            }
+
+            return migrated.get(&name).cloned().unwrap_or(cookie);
+        }
+
+        cookie
+    }
+}
+
+/// Turns a secret into a cookie key.
+///
+/// Secrets of at least 64 bytes are used as they are, so existing cookies stay
+/// valid; shorter ones are stretched with HKDF-SHA256.
+pub fn derive_key(secret: &Secret<String>) -> Result<Key, anyhow::Error> {
+    let secret = secret.expose_secret().as_bytes();
+    if secret.is_empty() {
+        return Err(anyhow!("the secret must not be empty"));
+    }
+    if secret.len() >= KEY_LENGTH {
+        return Ok(Key::from(secret));
+    }
+
+    let mut key = [0u8; KEY_LENGTH];
+    Hkdf::<Sha256>::new(Some(KEY_DERIVATION_SALT), secret)
+        .expand(b"cookie-key", &mut key)
+        .map_err(|e| anyhow!("failed to derive a key from the secret: {}", e))?;
+
+    Ok(Key::from(&key))
+}
+
+/// Rewrites the cookies protected with a previous key before the session and
+/// flash message middlewares read them.
+pub async fn migrate_rotated_cookies(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let keys = req.app_data::<web::Data<CookieKeys>>().cloned();
+
+    if let Some(keys) = keys.filter(|keys| !keys.previous.is_empty()) {
+        let cookies: Vec<String> = req
+            .headers()
+            .get_all(COOKIE)
+            .filter_map(|header| header.to_str().ok())
+            .flat_map(|header| header.split(';'))
+            .map(str::trim)
+            .filter_map(|cookie| Cookie::parse_encoded(cookie).ok())
+            .map(|cookie| {
+                keys.migrate(cookie.into_owned())
+                    .encoded()
+                    .stripped()
+                    .to_string()
+            })
+            .collect();
+
+        if !cookies.is_empty() {
+            if let Ok(header) = HeaderValue::from_str(&cookies.join("; ")) {
+                req.headers_mut().insert(COOKIE, header);
+            }
+        }
+    }
+
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::cookie::{Cookie, CookieJar};
+    use secrecy::Secret;
+
+    use super::{derive_key, CookieKeys};
+
+    fn secret(value: &str) -> Secret<String> {
+        Secret::new(value.to_string())
+    }
+
+    #[test]
+    fn short_secrets_are_stretched_deterministically() {
+        let first = derive_key(&secret("short-secret")).unwrap();
+        let second = derive_key(&secret("short-secret")).unwrap();
+        let other = derive_key(&secret("other-secret")).unwrap();
+
+        assert_eq!(first.master(), second.master());
+        assert_ne!(first.master(), other.master());
+    }
+
+    #[test]
+    fn long_secrets_are_used_as_they_are() {
+        let long_secret = "a".repeat(64);
+
+        let key = derive_key(&secret(&long_secret)).unwrap();
+
+        assert_eq!(key.master(), long_secret.as_bytes());
+    }
+
+    #[test]
+    fn empty_secrets_are_rejected() {
+        assert!(CookieKeys::new(&secret(""), &[]).is_err());
+        assert!(CookieKeys::new(&secret("current"), &[secret("")]).is_err());
+    }
+
+    #[test]
+    fn cookies_signed_with_a_previous_secret_are_migrated() {
+        let old_keys = CookieKeys::new(&secret("old-secret"), &[]).unwrap();
+        let keys = CookieKeys::new(&secret("new-secret"), &[secret("old-secret")]).unwrap();
+        let mut jar = CookieJar::new();
+        jar.signed_mut(old_keys.current())
+            .add(Cookie::new("_flash", "hello"));
+        let old_cookie = jar.get("_flash").cloned().unwrap();
+
+        let migrated = keys.migrate(old_cookie);
+
+        let mut jar = CookieJar::new();
+        jar.add_original(migrated);
+        let verified = jar.signed(keys.current()).get("_flash").unwrap();
+        assert_eq!(verified.value(), "hello");
+    }
+
+    #[test]
+    fn cookies_from_unknown_secrets_are_left_untouched() {
+        let keys = CookieKeys::new(&secret("new-secret"), &[secret("old-secret")]).unwrap();
+        let cookie = Cookie::new("id", "forged");
+
+        assert_eq!(keys.migrate(cookie.clone()), cookie);
+    }
+}
@@ -0,0 +1,244 @@
+use kuchikiki::{iter::NodeIterator, traits::TendrilSink, NodeRef, Selectors, Specificity};
+
+struct Rule {
+    selectors: Selectors,
+    declarations: Vec<(String, String)>,
+}
+
+/// Moves the rules of `<style>` blocks into the `style` attribute of the
+/// elements they match, since most email clients ignore style sheets.
+///
+/// At-rules and rules with pseudo-classes can't be expressed inline, so they
+/// are left in a `<style>` block for the clients that do support it.
+pub struct CssInliner {
+    enabled: bool,
+}
+
+impl CssInliner {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    #[tracing::instrument(name = "Inline CSS", skip_all)]
+    pub fn inline(&self, html: &str) -> String {
+        if !self.enabled || !html.to_lowercase().contains("<style") {
+            return html.to_string();
+        }
+
+        inline_css(html)
+    }
+}
+
+fn inline_css(html: &str) -> String {
+    let document = kuchikiki::parse_html().one(html);
+    let style_nodes: Vec<NodeRef> = document
+        .select("style")
+        .unwrap()
+        .map(|style| style.as_node().clone())
+        .collect();
+
+    let mut rules = Vec::new();
+    let mut kept_css = String::new();
+    for style in &style_nodes {
+        let (parsed, kept) = parse_style_sheet(&style.text_contents());
+        rules.extend(parsed);
+        kept_css.push_str(&kept);
+        style.detach();
+    }
+
+    for element in document.descendants().elements() {
+        let mut matches: Vec<(Specificity, usize)> = rules
+            .iter()
+            .enumerate()
+            .filter_map(|(position, rule)| {
+                rule.selectors
+                    .0
+                    .iter()
+                    .filter(|selector| selector.matches(&element))
+                    .map(|selector| selector.specificity())
+                    .max()
+                    .map(|specificity| (specificity, position))
+            })
+            .collect();
+        if matches.is_empty() {
+            continue;
+        }
+        // Same cascade as browsers: more specific rules win, then later ones.
+        matches.sort();
+
+        let mut attributes = element.attributes.borrow_mut();
+        let mut declarations = Vec::new();
+        for (_, position) in matches {
+            merge_declarations(&mut declarations, rules[position].declarations.clone());
+        }
+        if let Some(inline_style) = attributes.get("style") {
+            merge_declarations(&mut declarations, parse_declarations(inline_style));
+        }
+
+        let style = declarations
+            .iter()
+            .map(|(property, value)| format!("{}: {}", property, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        attributes.insert("style", style);
+    }
+
+    if !kept_css.trim().is_empty() {
+        if let (Ok(head), Some(style)) = (document.select_first("head"), style_nodes.first()) {
+            for child in style.children() {
+                child.detach();
+            }
+            style.append(NodeRef::new_text(kept_css.trim()));
+            head.as_node().append(style.clone());
+        }
+    }
+
+    document.to_string()
+}
+
+/// Splits a style sheet into the rules that can be inlined and the CSS that
+/// must stay in a style block.
+fn parse_style_sheet(css: &str) -> (Vec<Rule>, String) {
+    let css = strip_comments(css);
+    let mut rules = Vec::new();
+    let mut kept = String::new();
+    let mut rest = css.as_str();
+
+    while let Some(open) = rest.find('{') {
+        let prelude = rest[..open].trim();
+        let close = match matching_brace(&rest[open..]) {
+            Some(close) => open + close,
+            None => break,
+        };
+        let block = &rest[open + 1..close];
+
+        let selectors = (!prelude.starts_with('@') && !prelude.contains(':'))
+            .then(|| Selectors::compile(prelude).ok())
+            .flatten();
+        match selectors {
+            Some(selectors) => rules.push(Rule {
+                selectors,
+                declarations: parse_declarations(block),
+            }),
+            None => {
+                kept.push_str(&rest[..=close]);
+                kept.push('\n');
+            }
+        }
+
+        rest = &rest[close + 1..];
+    }
+
+    (rules, kept)
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+
+    stripped
+}
+
+/// Position of the brace closing the block that `s` starts with.
+fn matching_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+
+    for (position, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(position);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn parse_declarations(block: &str) -> Vec<(String, String)> {
+    block
+        .split(';')
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let (property, value) = (property.trim(), value.trim());
+
+            (!property.is_empty() && !value.is_empty())
+                .then(|| (property.to_lowercase(), value.to_string()))
+        })
+        .collect()
+}
+
+fn merge_declarations(declarations: &mut Vec<(String, String)>, overrides: Vec<(String, String)>) {
+    for (property, value) in overrides {
+        declarations.retain(|(existing, _)| *existing != property);
+        declarations.push((property, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CssInliner;
+
+    fn inline(html: &str) -> String {
+        CssInliner::new(true).inline(html)
+    }
+
+    #[test]
+    fn html_without_style_blocks_is_left_untouched() {
+        let html = "<p>Newsletter body as HTML</p>";
+
+        assert_eq!(inline(html), html);
+    }
+
+    #[test]
+    fn disabled_inliner_leaves_style_blocks() {
+        let html = "<style>p { color: red }</style><p>Hi</p>";
+
+        assert_eq!(CssInliner::new(false).inline(html), html);
+    }
+
+    #[test]
+    fn rules_are_moved_to_the_matching_elements() {
+        let html = r#"<style>p { color: red; margin: 0 } .note { color: blue }</style>
+            <p>First</p><p class="note">Second</p>"#;
+
+        let inlined = inline(html);
+
+        assert!(!inlined.contains("<style"));
+        assert!(inlined.contains(r#"<p style="color: red; margin: 0">First</p>"#));
+        assert!(inlined.contains(r#"<p class="note" style="margin: 0; color: blue">Second</p>"#));
+    }
+
+    #[test]
+    fn existing_inline_styles_take_precedence() {
+        let html = r#"<style>p { color: red }</style><p style="color: green">Hi</p>"#;
+
+        assert!(inline(html).contains(r#"<p style="color: green">Hi</p>"#));
+    }
+
+    #[test]
+    fn rules_that_cannot_be_inlined_are_kept() {
+        let html = r#"<style>/* links */ a:hover { color: red }
+            @media (max-width: 600px) { p { margin: 0 } }
+            p { color: blue }</style><p>Hi</p>"#;
+
+        let inlined = inline(html);
+
+        assert!(inlined.contains("a:hover { color: red }"));
+        assert!(inlined.contains("@media (max-width: 600px) { p { margin: 0 } }"));
+        assert!(inlined.contains(r#"<p style="color: blue">Hi</p>"#));
+    }
+}
@@ -0,0 +1,403 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::InternalError,
+    http::{Method, StatusCode},
+    middleware::Next,
+    web, HttpMessage, HttpResponse,
+};
+use anyhow::Context;
+use base64::Engine;
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::util::e500;
+
+/// What an access link lets its holder do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DelegatedScope {
+    /// Read the stats of the published issues.
+    ViewAnalytics,
+}
+
+impl DelegatedScope {
+    pub const ALL: &'static [DelegatedScope] = &[DelegatedScope::ViewAnalytics];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DelegatedScope::ViewAnalytics => "view_analytics",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            DelegatedScope::ViewAnalytics => "View analytics",
+        }
+    }
+
+    /// Every shared page is about analytics, so reading is all it takes.
+    fn allows(&self, method: &Method) -> bool {
+        match self {
+            DelegatedScope::ViewAnalytics => method == Method::GET,
+        }
+    }
+}
+
+impl TryFrom<String> for DelegatedScope {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        DelegatedScope::ALL
+            .iter()
+            .find(|scope| scope.as_str() == value)
+            .copied()
+            .ok_or_else(|| format!("{} is not a known scope.", value))
+    }
+}
+
+/// What a capability token carries. The grant it names is still checked on
+/// each request, so that it can be revoked before it expires.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Capability {
+    #[serde(rename = "g")]
+    pub grant_id: Uuid,
+    #[serde(rename = "s")]
+    pub scope: DelegatedScope,
+    #[serde(rename = "x", with = "chrono::serde::ts_seconds")]
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Signs and verifies the tokens of the access links.
+#[derive(Clone)]
+pub struct CapabilitySigner {
+    secret: Secret<String>,
+}
+
+impl CapabilitySigner {
+    pub fn new(secret: &Secret<String>) -> Self {
+        Self {
+            secret: secret.clone(),
+        }
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        // Keeps the tokens of the access links apart from the other ones
+        // signed with the same secret.
+        mac.update(b"capability.");
+        mac.update(payload.as_bytes());
+
+        mac
+    }
+
+    pub fn token(&self, capability: &Capability) -> String {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let payload =
+            engine.encode(serde_json::to_vec(capability).expect("Capabilities are serializable"));
+        let signature = engine.encode(self.mac(&payload).finalize().into_bytes());
+
+        format!("{}.{}", payload, signature)
+    }
+
+    /// Gives back what the token was created for, if it was signed by us.
+    pub fn verify(&self, token: &str) -> Option<Capability> {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let (payload, signature) = token.split_once('.')?;
+        let signature = engine.decode(signature).ok()?;
+        self.mac(payload).verify_slice(&signature).ok()?;
+
+        serde_json::from_slice(&engine.decode(payload).ok()?).ok()
+    }
+}
+
+#[derive(Debug)]
+pub struct DelegatedGrant {
+    pub grant_id: Uuid,
+    pub scope: String,
+    pub recipient: String,
+    pub granted_by: String,
+    pub granted_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub accesses: i64,
+    pub last_accessed_at: Option<DateTime<Utc>>,
+}
+
+impl DelegatedGrant {
+    pub fn is_live(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+}
+
+/// Records the grant and returns the capability of its access link.
+#[tracing::instrument(name = "Grant delegated access", skip(pool))]
+pub async fn grant_access(
+    pool: &PgPool,
+    granted_by: Uuid,
+    recipient: &str,
+    scope: DelegatedScope,
+    valid_for: Duration,
+) -> Result<Capability, sqlx::Error> {
+    let granted_at = Utc::now();
+    // Tokens carry whole seconds.
+    let expires_at = (granted_at + valid_for).trunc_subsecs(0);
+    let capability = Capability {
+        grant_id: Uuid::new_v4(),
+        scope,
+        expires_at,
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO delegated_access_grants (
+            grant_id, scope, recipient, granted_by, granted_at, expires_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        capability.grant_id,
+        scope.as_str(),
+        recipient,
+        granted_by,
+        granted_at,
+        expires_at,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(capability)
+}
+
+/// Answers whether the grant was live until now.
+#[tracing::instrument(name = "Revoke delegated access", skip(pool))]
+pub async fn revoke_access(
+    pool: &PgPool,
+    grant_id: Uuid,
+    revoked_by: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE delegated_access_grants
+        SET revoked_by = $2, revoked_at = now()
+        WHERE grant_id = $1 AND revoked_at IS NULL AND expires_at > now()
+        "#,
+        grant_id,
+        revoked_by,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Every grant, newest first, along with how much it was used.
+#[tracing::instrument(name = "List delegated access grants", skip(pool))]
+pub async fn list_grants(pool: &PgPool) -> Result<Vec<DelegatedGrant>, sqlx::Error> {
+    sqlx::query_as!(
+        DelegatedGrant,
+        r#"
+        SELECT g.grant_id, g.scope, g.recipient, u.username AS granted_by, g.granted_at,
+            g.expires_at, g.revoked_at,
+            (SELECT count(*) FROM delegated_access_log l
+                WHERE l.grant_id = g.grant_id) AS "accesses!",
+            (SELECT max(accessed_at) FROM delegated_access_log l
+                WHERE l.grant_id = g.grant_id) AS last_accessed_at
+        FROM delegated_access_grants g
+        JOIN users u ON u.user_id = g.granted_by
+        ORDER BY g.granted_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[derive(Debug)]
+pub struct DelegatedAccess {
+    pub method: String,
+    pub path: String,
+    pub status: i16,
+    pub accessed_at: DateTime<Utc>,
+}
+
+/// Requests made with the grant, newest first.
+#[tracing::instrument(name = "Get delegated access log", skip(pool))]
+pub async fn access_log(
+    pool: &PgPool,
+    grant_id: Uuid,
+) -> Result<Vec<DelegatedAccess>, sqlx::Error> {
+    sqlx::query_as!(
+        DelegatedAccess,
+        r#"
+        SELECT method, path, status, accessed_at
+        FROM delegated_access_log
+        WHERE grant_id = $1
+        ORDER BY accessed_at DESC, id DESC
+        "#,
+        grant_id,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+async fn record_access(
+    pool: &PgPool,
+    grant_id: Uuid,
+    method: &Method,
+    path: &str,
+    status: StatusCode,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO delegated_access_log (grant_id, method, path, status, accessed_at)
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        grant_id,
+        method.as_str(),
+        path,
+        status.as_u16() as i16,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn access_denied(status: StatusCode, reason: &'static str) -> actix_web::Error {
+    let response = HttpResponse::build(status)
+        .content_type("text/plain")
+        .body(reason);
+
+    InternalError::from_response(anyhow::anyhow!(reason), response).into()
+}
+
+/// Lets in the holders of an access link, whose token is the `token` segment
+/// of the path, for what the scope of its grant allows. Every request with a
+/// genuine token is logged, whether it's let in or not.
+///
+/// The grant is made available to the handlers as its [`Capability`].
+pub async fn reject_invalid_capability_tokens(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let signer = req
+        .app_data::<web::Data<CapabilitySigner>>()
+        .context("The capability signer is not registered in the application")
+        .map_err(e500)?
+        .clone();
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .context("Database pool is not registered in the application")
+        .map_err(e500)?
+        .clone();
+
+    let token = req
+        .match_info()
+        .get("token")
+        .unwrap_or_default()
+        .to_string();
+    let Some(capability) = signer.verify(&token) else {
+        return Err(access_denied(StatusCode::NOT_FOUND, "Unknown access link"));
+    };
+    // The token is left out of the log, it's as good as a password.
+    let path = req
+        .path()
+        .strip_prefix(&format!("/shared/{}", token))
+        .filter(|path| !path.is_empty())
+        .unwrap_or("/")
+        .to_string();
+    let method = req.method().clone();
+
+    let revoked = sqlx::query!(
+        r#"
+        SELECT revoked_at IS NOT NULL AS "revoked!"
+        FROM delegated_access_grants
+        WHERE grant_id = $1
+        "#,
+        capability.grant_id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to look up the grant of the access link")
+    .map_err(e500)?
+    .map(|r| r.revoked);
+
+    let denied = match revoked {
+        None => return Err(access_denied(StatusCode::NOT_FOUND, "Unknown access link")),
+        Some(true) => Some((StatusCode::GONE, "The access link was revoked")),
+        Some(false) if capability.expires_at <= Utc::now() => {
+            Some((StatusCode::GONE, "The access link expired"))
+        }
+        Some(false) if !capability.scope.allows(&method) => Some((
+            StatusCode::FORBIDDEN,
+            "The access link doesn't allow this request",
+        )),
+        Some(false) => None,
+    };
+    if let Some((status, reason)) = denied {
+        record_access(&pool, capability.grant_id, &method, &path, status)
+            .await
+            .context("Failed to log the access")
+            .map_err(e500)?;
+
+        return Err(access_denied(status, reason));
+    }
+
+    let grant_id = capability.grant_id;
+    req.extensions_mut().insert(capability);
+    let response = next.call(req).await?;
+    record_access(&pool, grant_id, &method, &path, response.status())
+        .await
+        .context("Failed to log the access")
+        .map_err(e500)?;
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::{Capability, CapabilitySigner, DelegatedScope};
+
+    fn capability() -> Capability {
+        Capability {
+            grant_id: Uuid::new_v4(),
+            scope: DelegatedScope::ViewAnalytics,
+            expires_at: Utc.with_ymd_and_hms(2024, 10, 28, 9, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn tokens_give_back_their_capability() {
+        let signer = CapabilitySigner::new(&Secret::new("secret".into()));
+        let capability = capability();
+
+        assert_eq!(signer.verify(&signer.token(&capability)), Some(capability));
+    }
+
+    #[test]
+    fn tokens_signed_with_another_secret_are_rejected() {
+        let signer = CapabilitySigner::new(&Secret::new("secret".into()));
+        let other = CapabilitySigner::new(&Secret::new("other".into()));
+
+        assert_eq!(signer.verify(&other.token(&capability())), None);
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        let signer = CapabilitySigner::new(&Secret::new("secret".into()));
+        let token = signer.token(&capability());
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = CapabilitySigner::new(&Secret::new("other".into())).token(&capability());
+        let (payload, _) = forged.split_once('.').unwrap();
+
+        assert_eq!(signer.verify(&format!("{}.{}", payload, signature)), None);
+    }
+}
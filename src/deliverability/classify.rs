@@ -0,0 +1,139 @@
+use crate::domain::{ProviderEvent, ProviderEventKind};
+
+/// Why an email bounced, as far as deciding what to do next goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BounceCategory {
+    /// The address doesn't exist or can't ever receive emails.
+    Hard,
+    /// A temporary failure, e.g. a full mailbox or a DNS error.
+    Soft,
+    /// The receiving server refused the email, e.g. as spam.
+    Block,
+    /// An automatic answer, such as an out-of-office reply.
+    AutoReply,
+}
+
+/// What a bounce of a given category leads to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BouncePolicy {
+    /// Stop sending anything to the recipient.
+    Suppress,
+    /// Send the issue again later on.
+    RetryAfterBackoff,
+    /// Keep track of it and carry on.
+    Ignore,
+}
+
+impl BounceCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BounceCategory::Hard => "hard",
+            BounceCategory::Soft => "soft",
+            BounceCategory::Block => "block",
+            BounceCategory::AutoReply => "auto_reply",
+        }
+    }
+
+    pub fn policy(&self) -> BouncePolicy {
+        match self {
+            BounceCategory::Hard => BouncePolicy::Suppress,
+            BounceCategory::Soft | BounceCategory::Block => BouncePolicy::RetryAfterBackoff,
+            BounceCategory::AutoReply => BouncePolicy::Ignore,
+        }
+    }
+}
+
+/// Categorizes a bounce reported by Postmark from its type. Unknown types
+/// are taken as soft bounces, so that a recipient is never suppressed on a
+/// guess. Spam complaints aren't bounces and give `None`.
+pub fn classify(event: &ProviderEvent) -> Option<BounceCategory> {
+    if event.kind != ProviderEventKind::Bounce {
+        return None;
+    }
+
+    let category = match event.bounce_type.as_deref().unwrap_or_default() {
+        "HardBounce" | "BadEmailAddress" | "ManuallyDeactivated" | "Unsubscribe" => {
+            BounceCategory::Hard
+        }
+        "Blocked" | "SpamNotification" | "VirusNotification" | "DMARCPolicy" => {
+            BounceCategory::Block
+        }
+        "AutoResponder" | "AddressChange" | "ChallengeVerification" | "Subscribe" => {
+            BounceCategory::AutoReply
+        }
+        _ => BounceCategory::Soft,
+    };
+
+    Some(category)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, BounceCategory, BouncePolicy};
+    use crate::domain::ProviderEvent;
+
+    fn bounce(bounce_type: &str) -> ProviderEvent {
+        let payload = serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": bounce_type,
+            "Email": "ursula@example.com",
+        });
+
+        ProviderEvent::parse_postmark(payload.to_string().as_bytes())
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn bounces_are_classified_by_type() {
+        let cases = [
+            ("HardBounce", BounceCategory::Hard),
+            ("BadEmailAddress", BounceCategory::Hard),
+            ("SoftBounce", BounceCategory::Soft),
+            ("Transient", BounceCategory::Soft),
+            ("DnsError", BounceCategory::Soft),
+            ("Blocked", BounceCategory::Block),
+            ("SpamNotification", BounceCategory::Block),
+            ("AutoResponder", BounceCategory::AutoReply),
+        ];
+
+        for (bounce_type, category) in cases {
+            assert_eq!(
+                classify(&bounce(bounce_type)),
+                Some(category),
+                "{}",
+                bounce_type
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_bounces_are_soft() {
+        assert_eq!(
+            classify(&bounce("SomethingNew")),
+            Some(BounceCategory::Soft)
+        );
+    }
+
+    #[test]
+    fn complaints_are_not_bounces() {
+        let payload = br#"{"RecordType": "SpamComplaint", "Email": "ursula@example.com"}"#;
+        let complaint = ProviderEvent::parse_postmark(payload).unwrap().unwrap();
+
+        assert_eq!(classify(&complaint), None);
+    }
+
+    #[test]
+    fn only_hard_bounces_suppress_the_recipient() {
+        assert_eq!(BounceCategory::Hard.policy(), BouncePolicy::Suppress);
+        assert_eq!(
+            BounceCategory::Soft.policy(),
+            BouncePolicy::RetryAfterBackoff
+        );
+        assert_eq!(
+            BounceCategory::Block.policy(),
+            BouncePolicy::RetryAfterBackoff
+        );
+        assert_eq!(BounceCategory::AutoReply.policy(), BouncePolicy::Ignore);
+    }
+}
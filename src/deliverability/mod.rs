@@ -0,0 +1,231 @@
+mod classify;
+
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    configuration::EmailWebhookSettings,
+    domain::{ProviderEvent, SubscriptionStatus},
+    subscriber_events::{record_events, SubscriberEventKind},
+};
+
+pub use classify::{classify, BounceCategory, BouncePolicy};
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+/// Stops sending anything to the recipient: every subscription of the
+/// address gets the status, and the deliveries still queued are dropped.
+#[tracing::instrument(skip_all)]
+async fn suppress(
+    transaction: &mut PgTransaction,
+    event: &ProviderEvent,
+    status: SubscriptionStatus,
+    kind: SubscriberEventKind,
+) -> Result<(), anyhow::Error> {
+    let subscriber_ids: Vec<Uuid> = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = $1
+        WHERE email = $2 AND status = ANY($3)
+        RETURNING id
+        "#,
+        status as SubscriptionStatus,
+        event.email.as_ref(),
+        &SubscriptionStatus::sources_of(status) as &[SubscriptionStatus],
+    )
+    .fetch_all(&mut **transaction)
+    .await
+    .context("Failed to update the status of the recipient's subscriptions")?
+    .into_iter()
+    .map(|r| r.id)
+    .collect();
+
+    // Issues being sent would bounce or be reported all the same.
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE subscriber_email = $1
+        "#,
+        event.email.as_ref(),
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to cancel the pending deliveries to the recipient")?;
+
+    if !subscriber_ids.is_empty() {
+        record_events(transaction, &subscriber_ids, kind, event.details.clone())
+            .await
+            .context("Failed to record the provider event")?;
+    }
+
+    Ok(())
+}
+
+/// Keeps track of the bounce, answering how many bounces worth a retry the
+/// recipient already had for the same issue.
+#[tracing::instrument(skip_all)]
+async fn record_bounce(
+    transaction: &mut PgTransaction,
+    event: &ProviderEvent,
+    category: BounceCategory,
+) -> Result<i64, anyhow::Error> {
+    let previous = match event.newsletter_issue_id {
+        Some(newsletter_issue_id) => {
+            sqlx::query!(
+                r#"
+                SELECT count(*) as "count!"
+                FROM email_bounces
+                WHERE newsletter_issue_id = $1 AND subscriber_email = $2
+                    AND category IN ('soft', 'block')
+                "#,
+                newsletter_issue_id,
+                event.email.as_ref(),
+            )
+            .fetch_one(&mut **transaction)
+            .await
+            .context("Failed to count the previous bounces of the recipient")?
+            .count
+        }
+        None => 0,
+    };
+
+    // The issue id comes from the provider, it may not match an issue.
+    sqlx::query!(
+        r#"
+        INSERT INTO email_bounces (
+            newsletter_issue_id, subscriber_email, category, bounce_type, bounced_at
+        )
+        VALUES (
+            (SELECT newsletter_issue_id FROM newsletter_issues WHERE newsletter_issue_id = $1),
+            $2, $3, $4, now()
+        )
+        "#,
+        event.newsletter_issue_id,
+        event.email.as_ref(),
+        category.as_str(),
+        event.bounce_type,
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to record the bounce")?;
+
+    Ok(previous)
+}
+
+/// Queues the issue for the recipient again, if they're still subscribed.
+#[tracing::instrument(skip(transaction, event, settings))]
+async fn retry_delivery(
+    transaction: &mut PgTransaction,
+    event: &ProviderEvent,
+    newsletter_issue_id: Uuid,
+    previous_bounces: i64,
+    settings: &EmailWebhookSettings,
+) -> Result<(), anyhow::Error> {
+    if previous_bounces >= settings.max_bounce_retries {
+        tracing::info!("The recipient kept bouncing the issue, giving up on it");
+        return Ok(());
+    }
+
+    let execute_after =
+        Utc::now() + settings.bounce_retry_backoff() * 2i32.pow(previous_bounces as u32);
+
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id, subscriber_email, n_retries, execute_after
+        )
+        SELECT i.newsletter_issue_id, s.email, $3, $4
+        FROM newsletter_issues i
+        JOIN subscriptions s ON s.list_id = i.list_id
+        WHERE i.newsletter_issue_id = $1 AND s.email = $2 AND s.status = 'confirmed'
+        ON CONFLICT DO NOTHING
+        "#,
+        newsletter_issue_id,
+        event.email.as_ref(),
+        previous_bounces as i16,
+        execute_after,
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to queue the issue again")?;
+
+    // The issue never reached the recipient, it can be delivered again.
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_deliveries
+        WHERE newsletter_issue_id = $1 AND subscriber_email = $2
+        "#,
+        newsletter_issue_id,
+        event.email.as_ref(),
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to forget the delivery of the issue")?;
+
+    Ok(())
+}
+
+/// Acts upon a bounce or complaint reported by the email provider.
+///
+/// Complaints and hard bounces stop every email to the recipient. Soft
+/// bounces and blocks send the issue again after a backoff doubling on each
+/// bounce, as long as the issue is known. Automatic replies are only kept
+/// track of.
+#[tracing::instrument(name = "Apply email provider event", skip(pool, settings))]
+pub async fn apply_provider_event(
+    pool: &PgPool,
+    event: &ProviderEvent,
+    settings: &EmailWebhookSettings,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    match classify(event) {
+        None => {
+            suppress(
+                &mut transaction,
+                event,
+                SubscriptionStatus::Complained,
+                SubscriberEventKind::Complained,
+            )
+            .await?
+        }
+        Some(category) => {
+            let previous_bounces = record_bounce(&mut transaction, event, category).await?;
+
+            match (category.policy(), event.newsletter_issue_id) {
+                (BouncePolicy::Suppress, _) => {
+                    suppress(
+                        &mut transaction,
+                        event,
+                        SubscriptionStatus::Bounced,
+                        SubscriberEventKind::Bounced,
+                    )
+                    .await?
+                }
+                (BouncePolicy::RetryAfterBackoff, Some(newsletter_issue_id)) => {
+                    retry_delivery(
+                        &mut transaction,
+                        event,
+                        newsletter_issue_id,
+                        previous_bounces,
+                        settings,
+                    )
+                    .await?
+                }
+                (BouncePolicy::RetryAfterBackoff, None) | (BouncePolicy::Ignore, _) => {}
+            }
+        }
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to apply a provider event")?;
+
+    Ok(())
+}
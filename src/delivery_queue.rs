@@ -0,0 +1,591 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::email_client::Attachment;
+
+/// Who the emails of an issue are from and where replies go, when not the
+/// defaults. Addresses are checked against the verified senders on publish.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct IssueSender {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub reply_to: Option<String>,
+}
+
+/// Stores the issue as published by its author, keeping the name they go
+/// by.
+#[tracing::instrument(name = "Store newsletter issue", skip_all)]
+#[allow(clippy::too_many_arguments)]
+pub async fn insert_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    tag: Option<&str>,
+    title: &str,
+    text_content: &str,
+    html_content: &str,
+    alternative_title: Option<&str>,
+    author_id: Uuid,
+    sender: &IssueSender,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, list_id, tag, title, text_content, html_content, published_at,
+            published_by, author, sender_name, sender_email, reply_to, alternative_title
+        )
+        VALUES (
+            $1, $2, $3, $4, $5, $6, $7,
+            $8, (SELECT COALESCE(display_name, username) FROM users WHERE user_id = $8),
+            $9, $10, $11, $12
+        )
+        "#,
+        newsletter_issue_id,
+        list_id,
+        tag,
+        title,
+        text_content,
+        html_content,
+        Utc::now(),
+        author_id,
+        sender.name,
+        sender.email,
+        sender.reply_to,
+        alternative_title,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(newsletter_issue_id)
+}
+
+/// Stores the files sent along with every email of the issue, in order.
+#[tracing::instrument(name = "Store newsletter issue attachments", skip_all)]
+pub async fn insert_issue_attachments(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    attachments: &[Attachment],
+) -> Result<(), sqlx::Error> {
+    if attachments.is_empty() {
+        return Ok(());
+    }
+
+    let filenames: Vec<String> = attachments.iter().map(|a| a.filename.clone()).collect();
+    let content_types: Vec<String> = attachments.iter().map(|a| a.content_type.clone()).collect();
+    let contents: Vec<String> = attachments.iter().map(|a| a.content.clone()).collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issue_attachments (
+            newsletter_issue_id, position, filename, content_type, content
+        )
+        SELECT $1, a.position, a.filename, a.content_type, a.content
+        FROM UNNEST($2::text[], $3::text[], $4::text[])
+            WITH ORDINALITY AS a(filename, content_type, content, position)
+        "#,
+        newsletter_issue_id,
+        &filenames,
+        &content_types,
+        &contents,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Files sent along with every email of the issue.
+#[tracing::instrument(name = "Get newsletter issue attachments", skip(pool))]
+pub async fn get_issue_attachments(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Vec<Attachment>, sqlx::Error> {
+    sqlx::query_as!(
+        Attachment,
+        r#"
+        SELECT filename, content_type, content
+        FROM newsletter_issue_attachments
+        WHERE newsletter_issue_id = $1
+        ORDER BY position
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Schedules the delivery of the issue to the confirmed subscribers of its
+/// list, only those having its tag when it has one. Subscribers asking for a
+/// weekly digest get it later on, see [`crate::digest`].
+#[tracing::instrument(name = "Enqueue delivery tasks", skip(transaction))]
+pub async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT i.newsletter_issue_id, s.email
+        FROM subscriptions s
+        JOIN newsletter_issues i ON i.list_id = s.list_id
+        WHERE i.newsletter_issue_id = $1
+            AND s.status = 'confirmed'
+            AND s.delivery_frequency = 'every_issue'
+            AND (
+                i.tag IS NULL OR EXISTS (
+                    SELECT 1 FROM subscriber_tags t
+                    WHERE t.subscriber_id = s.id AND t.tag = i.tag
+                )
+            )
+        "#,
+        newsletter_issue_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Splits the recipients of an issue testing an alternative title in two
+/// halves at random, the second one gets the alternative title. Recipients
+/// of an issue without one are left alone.
+#[tracing::instrument(name = "Assign subject variants", skip(transaction))]
+pub async fn assign_subject_variants(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_subject_variants (newsletter_issue_id, subscriber_email, variant)
+        SELECT q.newsletter_issue_id, q.subscriber_email,
+            CASE WHEN row_number() OVER (ORDER BY random()) % 2 = 0 THEN 'b' ELSE 'a' END
+        FROM issue_delivery_queue q
+        JOIN newsletter_issues i ON i.newsletter_issue_id = q.newsletter_issue_id
+        WHERE q.newsletter_issue_id = $1 AND i.alternative_title IS NOT NULL
+        "#,
+        newsletter_issue_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct IssueQueueSummary {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub published_at: DateTime<Utc>,
+    pub pending: i64,
+    pub dead: i64,
+    pub suppressed: i64,
+}
+
+/// Issues that still have pending or dead deliveries, or that some recipients
+/// were suppressed from by the email provider.
+#[tracing::instrument(name = "Summarize delivery queue", skip(pool))]
+pub async fn summarize_queue(pool: &PgPool) -> Result<Vec<IssueQueueSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        IssueQueueSummary,
+        r#"
+        SELECT i.newsletter_issue_id, i.title, i.published_at,
+            (SELECT count(*) FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id) as "pending!",
+            (SELECT count(*) FROM issue_delivery_dead_letters d
+                WHERE d.newsletter_issue_id = i.newsletter_issue_id) as "dead!",
+            (SELECT count(*) FROM issue_suppressed_recipients s
+                WHERE s.newsletter_issue_id = i.newsletter_issue_id) as "suppressed!"
+        FROM newsletter_issues i
+        WHERE EXISTS (
+            SELECT 1 FROM issue_delivery_queue q
+            WHERE q.newsletter_issue_id = i.newsletter_issue_id
+        ) OR EXISTS (
+            SELECT 1 FROM issue_delivery_dead_letters d
+            WHERE d.newsletter_issue_id = i.newsletter_issue_id
+        ) OR EXISTS (
+            SELECT 1 FROM issue_suppressed_recipients s
+            WHERE s.newsletter_issue_id = i.newsletter_issue_id
+        )
+        ORDER BY i.published_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct PendingDelivery {
+    pub subscriber_email: String,
+    pub n_retries: i16,
+    pub execute_after: DateTime<Utc>,
+    /// Set while a worker is attempting the delivery.
+    pub leased_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct DeadDelivery {
+    pub subscriber_email: String,
+    pub n_retries: i16,
+    pub last_error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct SuppressedRecipient {
+    pub subscriber_email: String,
+    pub error_code: i64,
+    pub message: String,
+    pub suppressed_at: DateTime<Utc>,
+}
+
+/// A recipient the issue could not be rendered for.
+#[derive(Debug, serde::Serialize)]
+pub struct RenderFailure {
+    pub subscriber_email: String,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Bounces the email provider reported about an issue, by category.
+#[derive(Debug, serde::Serialize)]
+pub struct BounceBreakdown {
+    pub hard: i64,
+    pub soft: i64,
+    pub block: i64,
+    pub auto_reply: i64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct IssueDeliveries {
+    pub title: String,
+    /// Who published the issue, by the name they go by.
+    pub author: Option<String>,
+    pub delivered: i64,
+    pub pending: Vec<PendingDelivery>,
+    pub dead: Vec<DeadDelivery>,
+    pub suppressed: Vec<SuppressedRecipient>,
+    pub render_failures: Vec<RenderFailure>,
+    pub bounces: BounceBreakdown,
+}
+
+#[tracing::instrument(name = "Inspect issue deliveries", skip(pool))]
+pub async fn inspect_issue(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<IssueDeliveries>, sqlx::Error> {
+    let issue = sqlx::query!(
+        r#"
+        SELECT i.title, COALESCE(u.display_name, u.username, i.author) AS author
+        FROM newsletter_issues i
+        LEFT JOIN users u ON u.user_id = i.published_by
+        WHERE i.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(issue) = issue else {
+        return Ok(None);
+    };
+
+    let pending = sqlx::query_as!(
+        PendingDelivery,
+        r#"
+        SELECT subscriber_email, n_retries, execute_after, leased_until
+        FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1
+        ORDER BY subscriber_email
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let delivered = sqlx::query!(
+        r#"
+        SELECT count(*) as "count!"
+        FROM subscriber_events
+        WHERE event_type = 'delivered' AND details ->> 'newsletter_issue_id' = $1::uuid::text
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(pool)
+    .await?
+    .count;
+
+    let dead = sqlx::query_as!(
+        DeadDelivery,
+        r#"
+        SELECT subscriber_email, n_retries, last_error, failed_at
+        FROM issue_delivery_dead_letters
+        WHERE newsletter_issue_id = $1
+        ORDER BY subscriber_email
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let suppressed = sqlx::query_as!(
+        SuppressedRecipient,
+        r#"
+        SELECT subscriber_email, error_code, message, suppressed_at
+        FROM issue_suppressed_recipients
+        WHERE newsletter_issue_id = $1
+        ORDER BY subscriber_email
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let render_failures = sqlx::query_as!(
+        RenderFailure,
+        r#"
+        SELECT subscriber_email, error, failed_at
+        FROM issue_render_failures
+        WHERE newsletter_issue_id = $1
+        ORDER BY subscriber_email
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let bounces = sqlx::query_as!(
+        BounceBreakdown,
+        r#"
+        SELECT
+            count(*) FILTER (WHERE category = 'hard') as "hard!",
+            count(*) FILTER (WHERE category = 'soft') as "soft!",
+            count(*) FILTER (WHERE category = 'block') as "block!",
+            count(*) FILTER (WHERE category = 'auto_reply') as "auto_reply!"
+        FROM email_bounces
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(Some(IssueDeliveries {
+        title: issue.title,
+        author: issue.author,
+        delivered,
+        pending,
+        dead,
+        suppressed,
+        render_failures,
+        bounces,
+    }))
+}
+
+/// Recipients of an issue by outcome, as shown on its stats page.
+#[derive(Debug, serde::Serialize)]
+pub struct IssueStats {
+    pub title: String,
+    /// Who published the issue, by the name they go by.
+    pub author: Option<String>,
+    pub sent: i64,
+    pub pending: i64,
+    /// Out of retries, or refused by the email provider.
+    pub failed: i64,
+    pub render_failed: i64,
+    pub bounced: i64,
+    pub opened: i64,
+    pub clicked: i64,
+}
+
+#[tracing::instrument(name = "Get issue stats", skip(pool))]
+pub async fn issue_stats(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<IssueStats>, sqlx::Error> {
+    sqlx::query_as!(
+        IssueStats,
+        r#"
+        SELECT i.title, COALESCE(u.display_name, u.username, i.author) AS author,
+            (SELECT count(*) FROM issue_deliveries d
+                WHERE d.newsletter_issue_id = i.newsletter_issue_id) AS "sent!",
+            (SELECT count(*) FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id) AS "pending!",
+            (SELECT count(*) FROM issue_delivery_dead_letters d
+                WHERE d.newsletter_issue_id = i.newsletter_issue_id)
+            + (SELECT count(*) FROM issue_suppressed_recipients s
+                WHERE s.newsletter_issue_id = i.newsletter_issue_id) AS "failed!",
+            (SELECT count(*) FROM issue_render_failures r
+                WHERE r.newsletter_issue_id = i.newsletter_issue_id) AS "render_failed!",
+            (SELECT count(DISTINCT subscriber_email) FROM email_bounces b
+                WHERE b.newsletter_issue_id = i.newsletter_issue_id) AS "bounced!",
+            (SELECT count(DISTINCT subscriber_email) FROM email_events e
+                WHERE e.newsletter_issue_id = i.newsletter_issue_id
+                    AND e.event_type = 'open') AS "opened!",
+            (SELECT count(DISTINCT subscriber_email) FROM email_events e
+                WHERE e.newsletter_issue_id = i.newsletter_issue_id
+                    AND e.event_type = 'click') AS "clicked!"
+        FROM newsletter_issues i
+        LEFT JOIN users u ON u.user_id = i.published_by
+        WHERE i.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Moves dead deliveries of an issue back to the queue, with their retries
+/// reset. Returns how many were requeued.
+#[tracing::instrument(name = "Requeue dead deliveries", skip(pool))]
+pub async fn requeue_dead_letters(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    subscriber_email: Option<&str>,
+) -> Result<u64, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    let requeued = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT newsletter_issue_id, subscriber_email
+        FROM issue_delivery_dead_letters
+        WHERE newsletter_issue_id = $1 AND ($2::text IS NULL OR subscriber_email = $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        newsletter_issue_id,
+        subscriber_email,
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_dead_letters
+        WHERE newsletter_issue_id = $1 AND ($2::text IS NULL OR subscriber_email = $2)
+        "#,
+        newsletter_issue_id,
+        subscriber_email,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(requeued)
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ResendOutcome {
+    pub requeued: u64,
+    pub skipped: u64,
+}
+
+/// Requeues the dead deliveries of an issue whose recipient is still a
+/// confirmed subscriber of its list. Those already delivered the issue are
+/// left out, so nobody gets it twice; the deliveries that were not requeued
+/// stay in the dead letters.
+#[tracing::instrument(name = "Resend issue to failed recipients", skip(pool))]
+pub async fn resend_to_failed_recipients(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<ResendOutcome, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    let requeued: Vec<String> = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT d.newsletter_issue_id, d.subscriber_email
+        FROM issue_delivery_dead_letters d
+        JOIN newsletter_issues i ON i.newsletter_issue_id = d.newsletter_issue_id
+        WHERE d.newsletter_issue_id = $1 AND EXISTS (
+            SELECT 1 FROM subscriptions s
+            WHERE s.list_id = i.list_id
+                AND s.email = d.subscriber_email
+                AND s.status = 'confirmed'
+        ) AND NOT EXISTS (
+            SELECT 1 FROM subscriber_events e
+            JOIN subscriptions s ON s.id = e.subscriber_id
+            WHERE s.email = d.subscriber_email
+                AND e.event_type = 'delivered'
+                AND e.details ->> 'newsletter_issue_id' = $1::text
+        )
+        ON CONFLICT DO NOTHING
+        RETURNING subscriber_email
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_all(&mut *transaction)
+    .await?
+    .into_iter()
+    .map(|r| r.subscriber_email)
+    .collect();
+
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_dead_letters
+        WHERE newsletter_issue_id = $1 AND subscriber_email = ANY($2)
+        "#,
+        newsletter_issue_id,
+        &requeued,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    let skipped = sqlx::query!(
+        r#"
+        SELECT count(*) as "count!"
+        FROM issue_delivery_dead_letters
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_one(&mut *transaction)
+    .await?
+    .count;
+
+    transaction.commit().await?;
+
+    Ok(ResendOutcome {
+        requeued: requeued.len() as u64,
+        skipped: skipped as u64,
+    })
+}
+
+/// Drops the pending (or dead, when `dead` is set) deliveries of an issue.
+/// Returns how many were removed.
+#[tracing::instrument(name = "Purge deliveries", skip(pool))]
+pub async fn purge_deliveries(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    subscriber_email: Option<&str>,
+    dead: bool,
+) -> Result<u64, sqlx::Error> {
+    let result = if dead {
+        sqlx::query!(
+            r#"
+            DELETE FROM issue_delivery_dead_letters
+            WHERE newsletter_issue_id = $1 AND ($2::text IS NULL OR subscriber_email = $2)
+            "#,
+            newsletter_issue_id,
+            subscriber_email,
+        )
+        .execute(pool)
+        .await?
+    } else {
+        sqlx::query!(
+            r#"
+            DELETE FROM issue_delivery_queue
+            WHERE newsletter_issue_id = $1 AND ($2::text IS NULL OR subscriber_email = $2)
+            "#,
+            newsletter_issue_id,
+            subscriber_email,
+        )
+        .execute(pool)
+        .await?
+    };
+
+    Ok(result.rows_affected())
+}
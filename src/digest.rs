@@ -0,0 +1,310 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    configuration::Settings,
+    domain::Email,
+    email_client::{EmailClient, SendEmailError},
+    email_log::EmailKind,
+    growth_report::week_start,
+    leader_election::{LeaderElection, SingletonJob},
+    startup::get_connection_pool,
+    subscriber_events::{record_events, SubscriberEventKind},
+    template::{render_issue, render_weekly_digest, DigestIssue, IssueRecipient},
+};
+
+/// An issue of the week not yet delivered to a subscriber asking for a
+/// digest.
+struct PendingIssue {
+    subscriber_id: Uuid,
+    email: String,
+    name: String,
+    list_id: Uuid,
+    newsletter_issue_id: Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+/// The issues of the week gathered for one subscriber.
+struct Digest {
+    subscriber_id: Uuid,
+    email: String,
+    name: String,
+    list_id: Uuid,
+    issues: Vec<PendingIssue>,
+}
+
+/// Groups the issues, ordered by subscriber, into one digest each.
+fn group_by_subscriber(pending: Vec<PendingIssue>) -> Vec<Digest> {
+    let mut digests: Vec<Digest> = Vec::new();
+    for issue in pending {
+        match digests.last_mut() {
+            Some(digest) if digest.subscriber_id == issue.subscriber_id => {
+                digest.issues.push(issue)
+            }
+            _ => digests.push(Digest {
+                subscriber_id: issue.subscriber_id,
+                email: issue.email.clone(),
+                name: issue.name.clone(),
+                list_id: issue.list_id,
+                issues: vec![issue],
+            }),
+        }
+    }
+
+    digests
+}
+
+/// Issues published during the week starting at `week_start` the
+/// subscribers asking for a digest would have received, leaving out those
+/// they were already delivered, e.g. before they switched to the digest.
+#[tracing::instrument(name = "Get pending digest issues", skip(pool))]
+async fn get_pending_issues(
+    pool: &PgPool,
+    week_start: DateTime<Utc>,
+) -> Result<Vec<PendingIssue>, sqlx::Error> {
+    sqlx::query_as!(
+        PendingIssue,
+        r#"
+        SELECT
+            s.id AS subscriber_id, s.email, s.name, s.list_id,
+            i.newsletter_issue_id, i.title, i.text_content, i.html_content
+        FROM subscriptions s
+        JOIN newsletter_issues i ON i.list_id = s.list_id
+        WHERE s.status = 'confirmed'
+            AND s.delivery_frequency = 'weekly_digest'
+            AND i.published_at >= $1 AND i.published_at < $2
+            AND (
+                i.tag IS NULL OR EXISTS (
+                    SELECT 1 FROM subscriber_tags t
+                    WHERE t.subscriber_id = s.id AND t.tag = i.tag
+                )
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM issue_deliveries d
+                WHERE d.newsletter_issue_id = i.newsletter_issue_id
+                    AND (d.subscriber_id = s.id OR d.subscriber_email = s.email)
+            )
+        ORDER BY s.id, i.published_at
+        "#,
+        week_start,
+        week_start + Duration::weeks(1),
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Records the issues of the digest as delivered to its subscriber, so that
+/// they are left out of a retry.
+#[tracing::instrument(skip_all)]
+async fn record_digest_delivery(pool: &PgPool, digest: &Digest) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+    for issue in &digest.issues {
+        sqlx::query!(
+            r#"
+            INSERT INTO issue_deliveries (
+                newsletter_issue_id, subscriber_email, subscriber_id, delivered_at
+            )
+            VALUES ($1, $2, $3, now())
+            ON CONFLICT DO NOTHING
+            "#,
+            issue.newsletter_issue_id,
+            digest.email,
+            digest.subscriber_id,
+        )
+        .execute(&mut *transaction)
+        .await?;
+        record_events(
+            &mut transaction,
+            &[digest.subscriber_id],
+            SubscriberEventKind::Delivered,
+            serde_json::json!({ "newsletter_issue_id": issue.newsletter_issue_id }),
+        )
+        .await?;
+    }
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Renders the issues of the digest for its subscriber. Issues failing to
+/// render are left out rather than holding back the whole digest.
+fn render_digest_issues(digest: &Digest) -> Vec<DigestIssue> {
+    let recipient = IssueRecipient {
+        email: &digest.email,
+        name: &digest.name,
+    };
+
+    digest
+        .issues
+        .iter()
+        .filter_map(|issue| {
+            match render_issue(&issue.html_content, &issue.text_content, &recipient) {
+                Ok(content) => Some(DigestIssue {
+                    title: issue.title.clone(),
+                    html: content.html,
+                    text: content.text,
+                }),
+                Err(e) => {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        newsletter_issue_id = %issue.newsletter_issue_id,
+                        "Failed to render an issue of a digest. Leaving it out",
+                    );
+
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Sends the issues published during the week before the one `now` falls
+/// in to the subscribers asking for a digest, unless it was already done.
+/// Gives back whether it was.
+///
+/// Each digest is recorded as delivered once sent, and the week only once
+/// every digest was sent, so that a failure, of the database or the
+/// provider, is retried on the next run without sending anyone their digest
+/// twice. A digest the provider rejects for good is logged and skipped
+/// instead of holding back the others.
+#[tracing::instrument(name = "Send weekly digests", skip(pool, email_client, base_url))]
+pub async fn send_weekly_digests(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    now: DateTime<Utc>,
+) -> Result<bool, anyhow::Error> {
+    let digest_week = week_start(now) - Duration::weeks(1);
+
+    let sent = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM weekly_digests WHERE week_start = $1) AS "sent!""#,
+        digest_week,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to check whether the weekly digests were sent")?;
+    if sent {
+        return Ok(false);
+    }
+
+    let pending = get_pending_issues(pool, digest_week)
+        .await
+        .context("Failed to retrieve the issues of the weekly digests")?;
+    let subject = format!("Your weekly digest, {}", digest_week.format("%Y-%m-%d"));
+    let base_url = base_url.trim_end_matches('/');
+
+    let mut n_failed = 0;
+    for digest in group_by_subscriber(pending) {
+        let email = match Email::parse(digest.email.clone()) {
+            Ok(email) => email,
+            Err(e) => {
+                tracing::warn!(error.message = %e, "Skipping an invalid digest subscriber email");
+                continue;
+            }
+        };
+        let issues = render_digest_issues(&digest);
+        if issues.is_empty() {
+            continue;
+        }
+        let preferences_link = format!("{}/preferences/link?list_id={}", base_url, digest.list_id);
+        let template = render_weekly_digest(&issues, &preferences_link)
+            .context("Failed to render a weekly digest")?;
+
+        let sent = email_client
+            .send_email(
+                EmailKind::WeeklyDigest,
+                &email,
+                &subject,
+                &template.html,
+                &template.text,
+                &[],
+                None,
+            )
+            .await;
+        match sent {
+            Ok(()) => {}
+            Err(e @ (SendEmailError::Rejected(_) | SendEmailError::InactiveRecipient(_))) => {
+                let e = anyhow::Error::new(e).context("Failed to send a weekly digest");
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    subscriber_id = %digest.subscriber_id,
+                    "Failed to send a weekly digest. Skipping it",
+                );
+                continue;
+            }
+            Err(e) => {
+                let e = anyhow::Error::new(e).context("Failed to send a weekly digest");
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    subscriber_id = %digest.subscriber_id,
+                    "Failed to send a weekly digest. Retrying it on the next run",
+                );
+                n_failed += 1;
+                continue;
+            }
+        }
+        record_digest_delivery(pool, &digest)
+            .await
+            .context("Failed to record the delivery of a weekly digest")?;
+    }
+
+    if n_failed > 0 {
+        anyhow::bail!("Failed to send {} weekly digests", n_failed);
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO weekly_digests (week_start, sent_at)
+        VALUES ($1, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        digest_week,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to mark the weekly digests as sent")?;
+
+    Ok(true)
+}
+
+pub async fn run_worker_until_stopped(
+    configuration: Settings,
+    email_client: EmailClient,
+) -> Result<(), anyhow::Error> {
+    let pool = get_connection_pool(&configuration.database);
+    let settings = configuration.weekly_digest;
+    let mut election = LeaderElection::new(
+        pool.clone(),
+        SingletonJob::WeeklyDigest,
+        &configuration.application.instance_id,
+    );
+
+    loop {
+        if settings.enabled && election.is_leader().await {
+            let outcome = send_weekly_digests(
+                &pool,
+                &email_client,
+                &configuration.application.base_url,
+                Utc::now(),
+            )
+            .await;
+            if let Err(e) = outcome {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to send the weekly digests",
+                );
+            }
+        }
+        tokio::time::sleep(settings.check_interval()).await;
+    }
+}
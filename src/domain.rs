@@ -1,21 +1,49 @@
 mod collaborator_email;
+mod delivery_frequency;
+mod display_name;
 mod email;
 mod invitation_token;
+mod label;
+mod list_name;
+mod locale;
 mod new_collaborator;
 mod new_subscriber;
+mod provider_event;
+mod segment_name;
+mod subscriber_attributes;
 mod subscriber_email;
 mod subscriber_name;
+mod subscription_status;
 mod subscription_token;
+mod tag_name;
 mod token;
+mod topic_name;
+mod username;
 mod validation_code;
+mod webhook_url;
 
 pub use collaborator_email::{CollaboratorEmail, CollaboratorEmailError};
+pub use delivery_frequency::DeliveryFrequency;
+pub use display_name::{DisplayName, DisplayNameError};
 pub use email::{Email, EmailError};
 pub use invitation_token::{InvitationToken, InvitationTokenError};
+pub use label::{Label, LabelError};
+pub use list_name::{ListName, ListNameError};
+pub use locale::{Locale, LocaleError};
 pub use new_collaborator::NewCollaborator;
 pub use new_subscriber::NewSubscriber;
+pub use provider_event::{ProviderEvent, ProviderEventError, ProviderEventKind};
+pub use segment_name::{SegmentName, SegmentNameError};
+pub use subscriber_attributes::{
+    SignupField, SignupFieldError, SubscriberAttributes, SubscriberAttributesError,
+};
 pub use subscriber_email::{SubscriberEmail, SubscriberEmailError};
 pub use subscriber_name::{SubscriberName, SubscriberNameError};
+pub use subscription_status::{IllegalTransition, SubscriptionStatus};
 pub use subscription_token::{SubscriptionToken, SubscriptionTokenError};
+pub use tag_name::{TagName, TagNameError};
 pub use token::{Token, TokenError};
+pub use topic_name::{TopicName, TopicNameError};
+pub use username::{Username, UsernameError};
 pub use validation_code::{ValidationCode, ValidationCodeError};
+pub use webhook_url::{WebhookUrl, WebhookUrlError};
@@ -0,0 +1,11 @@
+/// How often a subscriber hears from their list, stored as the
+/// `delivery_frequency` type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "delivery_frequency", rename_all = "snake_case")]
+pub enum DeliveryFrequency {
+    /// Every issue, as soon as it's published.
+    EveryIssue,
+    /// The issues of the week, gathered in a single email the week after.
+    WeeklyDigest,
+}
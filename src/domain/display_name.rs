@@ -0,0 +1,43 @@
+use super::{SubscriberName, SubscriberNameError};
+
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct DisplayNameError(#[from] SubscriberNameError);
+
+/// Name a user is shown with instead of their username, e.g. as the author
+/// of the issues they publish.
+#[derive(Debug)]
+pub struct DisplayName(SubscriberName);
+
+impl DisplayName {
+    pub fn parse(s: String) -> Result<DisplayName, DisplayNameError> {
+        SubscriberName::parse(s.trim().to_string())
+            .map(Self)
+            .map_err(DisplayNameError)
+    }
+}
+
+impl AsRef<str> for DisplayName {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::DisplayName;
+
+    #[test]
+    fn display_names_are_trimmed() {
+        let name = assert_ok!(DisplayName::parse(" Ursula K. Le Guin ".into()));
+
+        assert_eq!(name.as_ref(), "Ursula K. Le Guin");
+    }
+
+    #[test]
+    fn blank_display_names_are_rejected() {
+        assert_err!(DisplayName::parse(" ".into()));
+    }
+}
@@ -6,7 +6,7 @@ pub enum EmailError {
     InvalidFormat,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Email(String);
 
 impl std::fmt::Display for Email {
@@ -0,0 +1,83 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LabelError {
+    #[error("Label is empty")]
+    Empty,
+    #[error("Label is too long")]
+    TooLong,
+    #[error("Label contains invalid characters")]
+    InvalidCharacters,
+}
+
+/// Short human readable identifier, e.g. the name of a topic.
+#[derive(Debug)]
+pub struct Label(String);
+
+impl Label {
+    pub fn parse(s: String) -> Result<Label, LabelError> {
+        let s = s.trim().to_string();
+        if s.is_empty() {
+            return Err(LabelError::Empty);
+        }
+
+        // s must be less than 64 grapheme clusters.
+        let is_too_long = s.graphemes(true).nth(64).is_some();
+        if is_too_long {
+            return Err(LabelError::TooLong);
+        }
+
+        let contains_invalid_chars = s
+            .chars()
+            .any(|c| !(c.is_alphanumeric() || [' ', '-', '_'].contains(&c)));
+        if contains_invalid_chars {
+            return Err(LabelError::InvalidCharacters);
+        }
+
+        Ok(Self(s))
+    }
+}
+
+impl AsRef<str> for Label {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::Label;
+
+    #[test]
+    fn a_64_graphemes_long_label_is_valid() {
+        let label = "ë".repeat(64);
+        assert_ok!(Label::parse(label));
+    }
+
+    #[test]
+    fn a_label_longer_than_64_graphemes_is_rejected() {
+        let label = "a".repeat(65);
+        assert_err!(Label::parse(label));
+    }
+
+    #[test]
+    fn whitespace_only_labels_are_rejected() {
+        let label = "  ".to_string();
+        assert_err!(Label::parse(label));
+    }
+
+    #[test]
+    fn labels_containing_invalid_chars_are_rejected() {
+        for label in ["rust!", "<b>", "a/b", "{x}"] {
+            assert_err!(Label::parse(label.to_string()));
+        }
+    }
+
+    #[test]
+    fn a_valid_label_is_parsed_successfully() {
+        let label = "Rust news_2024".to_string();
+        assert_ok!(Label::parse(label));
+    }
+}
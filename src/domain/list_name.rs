@@ -0,0 +1,20 @@
+use super::{Label, LabelError};
+
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct ListNameError(#[from] LabelError);
+
+#[derive(Debug)]
+pub struct ListName(Label);
+
+impl ListName {
+    pub fn parse(s: String) -> Result<ListName, ListNameError> {
+        Label::parse(s).map(Self).map_err(ListNameError)
+    }
+}
+
+impl AsRef<str> for ListName {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
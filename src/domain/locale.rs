@@ -0,0 +1,117 @@
+#[derive(Debug, thiserror::Error)]
+pub enum LocaleError {
+    #[error("Locale is not a language tag")]
+    InvalidTag,
+}
+
+/// Language a subscriber reads, the primary subtag of a language tag, e.g.
+/// `pt` for `pt-BR`. Templates are translated per language, not per region.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale(String);
+
+impl Locale {
+    pub fn parse(s: &str) -> Result<Locale, LocaleError> {
+        let language = s.trim().split(['-', '_']).next().unwrap_or_default();
+
+        let is_language =
+            (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic());
+        if !is_language {
+            return Err(LocaleError::InvalidTag);
+        }
+
+        Ok(Self(language.to_ascii_lowercase()))
+    }
+
+    /// Languages of an `Accept-Language` header, most preferred first.
+    /// Wildcards, languages refused with `q=0` and malformed entries are
+    /// left out.
+    pub fn from_accept_language(header: &str) -> Vec<Locale> {
+        let mut weighted = Vec::new();
+        for entry in header.split(',') {
+            let mut parts = entry.split(';');
+            let Ok(locale) = Locale::parse(parts.next().unwrap_or_default()) else {
+                continue;
+            };
+            let quality = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok());
+            match quality {
+                Some(q) if q > 0.0 && q <= 1.0 => weighted.push((locale, q)),
+                _ => continue,
+            }
+        }
+
+        // Stable, so that languages of equal quality keep their order.
+        weighted.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        let mut locales: Vec<Locale> = Vec::with_capacity(weighted.len());
+        for (locale, _) in weighted {
+            if !locales.contains(&locale) {
+                locales.push(locale);
+            }
+        }
+
+        locales
+    }
+}
+
+impl AsRef<str> for Locale {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok_eq};
+
+    use super::Locale;
+
+    fn languages(header: &str) -> Vec<String> {
+        Locale::from_accept_language(header)
+            .into_iter()
+            .map(|l| l.0)
+            .collect()
+    }
+
+    #[test]
+    fn the_region_is_dropped() {
+        assert_ok_eq!(Locale::parse("pt-BR"), Locale("pt".into()));
+        assert_ok_eq!(Locale::parse("en_GB"), Locale("en".into()));
+    }
+
+    #[test]
+    fn languages_are_lowercased() {
+        assert_ok_eq!(Locale::parse("FR"), Locale("fr".into()));
+    }
+
+    #[test]
+    fn non_language_tags_are_rejected() {
+        for tag in ["", "*", "e", "english", "p7", "<x>"] {
+            assert_err!(Locale::parse(tag));
+        }
+    }
+
+    #[test]
+    fn languages_are_sorted_by_quality() {
+        assert_eq!(
+            languages("de;q=0.5, pt-PT;q=0.9, en;q=0.7"),
+            vec!["pt", "en", "de"]
+        );
+    }
+
+    #[test]
+    fn languages_of_equal_quality_keep_their_order() {
+        assert_eq!(languages("fr, es, it;q=0.1"), vec!["fr", "es", "it"]);
+    }
+
+    #[test]
+    fn refused_wildcard_and_malformed_entries_are_left_out() {
+        assert_eq!(languages("*, es;q=0, fr;q=abc, de;q=2, , pt"), vec!["pt"]);
+    }
+
+    #[test]
+    fn a_language_is_listed_once() {
+        assert_eq!(languages("pt-BR, en;q=0.8, pt;q=0.5"), vec!["pt", "en"]);
+    }
+}
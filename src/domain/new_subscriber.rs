@@ -1,6 +1,11 @@
-use super::{Email, SubscriberName};
+use super::{Email, Locale, SubscriberAttributes, SubscriberName};
 
 pub struct NewSubscriber {
     pub email: Email,
     pub name: SubscriberName,
+    /// Language of the emails sent to the subscriber, the default templates'
+    /// if unknown.
+    pub locale: Option<Locale>,
+    /// Answers to the custom signup fields.
+    pub attributes: SubscriberAttributes,
 }
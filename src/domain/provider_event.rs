@@ -0,0 +1,150 @@
+use uuid::Uuid;
+
+use super::{Email, EmailError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProviderEventError {
+    #[error("The event is not a valid Postmark webhook payload: {0}")]
+    InvalidPayload(#[source] serde_json::Error),
+    #[error(transparent)]
+    InvalidEmail(EmailError),
+}
+
+/// What the email provider reported about a recipient.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProviderEventKind {
+    Bounce,
+    SpamComplaint,
+}
+
+/// A bounce or complaint sent to us by the email provider.
+#[derive(Debug)]
+pub struct ProviderEvent {
+    pub kind: ProviderEventKind,
+    pub email: Email,
+    /// The issue the email belonged to, when it was one.
+    pub newsletter_issue_id: Option<Uuid>,
+    /// As named by the provider, e.g. `HardBounce`.
+    pub bounce_type: Option<String>,
+    /// Provider-specific details, e.g. the type of bounce.
+    pub details: serde_json::Value,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(tag = "RecordType")]
+enum PostmarkRecord {
+    Bounce(PostmarkBounce),
+    SpamComplaint(PostmarkBounce),
+    #[serde(other)]
+    Other,
+}
+
+/// Postmark sends complaints with the same fields as bounces.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PostmarkBounce {
+    email: String,
+    #[serde(rename = "Type", default)]
+    bounce_type: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    metadata: PostmarkMetadata,
+}
+
+/// Set by [`EmailClient::send_issue_email`](crate::email_client::EmailClient::send_issue_email)
+/// and on the issue emails of [`EmailClient::send_email_batch`](crate::email_client::EmailClient::send_email_batch).
+#[derive(Default, serde::Deserialize)]
+struct PostmarkMetadata {
+    newsletter_issue_id: Option<Uuid>,
+}
+
+impl ProviderEvent {
+    /// Reads a Postmark webhook payload. Records we don't act upon, such as
+    /// deliveries or opens, give `None`.
+    pub fn parse_postmark(payload: &[u8]) -> Result<Option<Self>, ProviderEventError> {
+        let record: PostmarkRecord =
+            serde_json::from_slice(payload).map_err(ProviderEventError::InvalidPayload)?;
+        let (kind, bounce) = match record {
+            PostmarkRecord::Bounce(bounce) => (ProviderEventKind::Bounce, bounce),
+            PostmarkRecord::SpamComplaint(bounce) => (ProviderEventKind::SpamComplaint, bounce),
+            PostmarkRecord::Other => return Ok(None),
+        };
+        let email = Email::parse(bounce.email).map_err(ProviderEventError::InvalidEmail)?;
+
+        Ok(Some(Self {
+            kind,
+            email,
+            newsletter_issue_id: bounce.metadata.newsletter_issue_id,
+            bounce_type: bounce.bounce_type.clone(),
+            details: serde_json::json!({
+                "type": bounce.bounce_type,
+                "description": bounce.description,
+            }),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_none, assert_ok};
+
+    use super::{ProviderEvent, ProviderEventKind};
+
+    #[test]
+    fn bounces_are_parsed() {
+        let payload = br#"{
+            "RecordType": "Bounce",
+            "Type": "HardBounce",
+            "Email": "ursula@example.com",
+            "Description": "The server was unable to deliver your message"
+        }"#;
+
+        let event = assert_ok!(ProviderEvent::parse_postmark(payload)).unwrap();
+
+        assert_eq!(event.kind, ProviderEventKind::Bounce);
+        assert_eq!(event.email.as_ref(), "ursula@example.com");
+        assert_eq!(event.bounce_type.as_deref(), Some("HardBounce"));
+        assert_eq!(event.newsletter_issue_id, None);
+    }
+
+    #[test]
+    fn bounces_of_issues_are_traced_back_to_them() {
+        let payload = br#"{
+            "RecordType": "Bounce",
+            "Type": "SoftBounce",
+            "Email": "ursula@example.com",
+            "Metadata": {"newsletter_issue_id": "67e55044-10b1-426f-9247-bb680e5fe0c8"}
+        }"#;
+
+        let event = assert_ok!(ProviderEvent::parse_postmark(payload)).unwrap();
+
+        assert_eq!(
+            event.newsletter_issue_id.unwrap().to_string(),
+            "67e55044-10b1-426f-9247-bb680e5fe0c8"
+        );
+    }
+
+    #[test]
+    fn spam_complaints_are_parsed() {
+        let payload = br#"{"RecordType": "SpamComplaint", "Email": "ursula@example.com"}"#;
+
+        let event = assert_ok!(ProviderEvent::parse_postmark(payload)).unwrap();
+
+        assert_eq!(event.kind, ProviderEventKind::SpamComplaint);
+    }
+
+    #[test]
+    fn other_records_are_ignored() {
+        let payload = br#"{"RecordType": "Delivery", "Recipient": "ursula@example.com"}"#;
+
+        assert_none!(assert_ok!(ProviderEvent::parse_postmark(payload)));
+    }
+
+    #[test]
+    fn bounces_with_an_invalid_email_are_rejected() {
+        let payload = br#"{"RecordType": "Bounce", "Email": "not-an-email"}"#;
+
+        assert_err!(ProviderEvent::parse_postmark(payload));
+    }
+}
@@ -0,0 +1,20 @@
+use super::{Label, LabelError};
+
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct SegmentNameError(#[from] LabelError);
+
+#[derive(Debug)]
+pub struct SegmentName(Label);
+
+impl SegmentName {
+    pub fn parse(s: String) -> Result<SegmentName, SegmentNameError> {
+        Label::parse(s).map(Self).map_err(SegmentNameError)
+    }
+}
+
+impl AsRef<str> for SegmentName {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
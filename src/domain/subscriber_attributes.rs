@@ -0,0 +1,211 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{Map, Value};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Keys of the subscription form that custom fields can't take.
+const RESERVED_NAMES: &[&str] = &["email", "name", "locale", "referral_code", "csrf_token"];
+
+fn default_max_length() -> usize {
+    200
+}
+
+/// Extra question of the subscription form, answered with free text or, if
+/// it has options, with one of them.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct SignupField {
+    /// Key of the form field and of the answer in the subscriber attributes.
+    pub name: String,
+    pub label: String,
+    #[serde(default)]
+    pub required: bool,
+    /// In graphemes.
+    #[serde(default = "default_max_length")]
+    pub max_length: usize,
+    /// Answers allowed, any text if empty.
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SignupFieldError {
+    #[error("Signup field name {0:?} must only have lowercase letters, digits and underscores")]
+    InvalidName(String),
+    #[error("Signup field name {0:?} is used by the subscription form")]
+    ReservedName(String),
+    #[error("Signup field name {0:?} is repeated")]
+    DuplicatedName(String),
+}
+
+impl SignupField {
+    /// Fields must be told apart from each other and from the ones every
+    /// subscription form has.
+    pub fn check_all(fields: &[SignupField]) -> Result<(), SignupFieldError> {
+        let mut names = HashSet::new();
+
+        for field in fields {
+            let name = &field.name;
+            let is_valid = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+            if !is_valid {
+                return Err(SignupFieldError::InvalidName(name.clone()));
+            }
+            if RESERVED_NAMES.contains(&name.as_str()) {
+                return Err(SignupFieldError::ReservedName(name.clone()));
+            }
+            if !names.insert(name.as_str()) {
+                return Err(SignupFieldError::DuplicatedName(name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SubscriberAttributesError {
+    #[error("{0} is required")]
+    Missing(String),
+    #[error("{0} is too long")]
+    TooLong(String),
+    #[error("{0} isn't one of the options")]
+    UnknownOption(String),
+}
+
+/// Answers to the signup fields, kept as a JSON object keyed by field name.
+#[derive(Debug)]
+pub struct SubscriberAttributes(Map<String, Value>);
+
+impl SubscriberAttributes {
+    /// Blank answers count as missing and the ones to fields that aren't
+    /// configured are dropped.
+    pub fn parse(
+        fields: &[SignupField],
+        mut submitted: HashMap<String, String>,
+    ) -> Result<SubscriberAttributes, SubscriberAttributesError> {
+        let mut attributes = Map::new();
+
+        for field in fields {
+            let answer = submitted
+                .remove(&field.name)
+                .map(|answer| answer.trim().to_string())
+                .filter(|answer| !answer.is_empty());
+            let Some(answer) = answer else {
+                if field.required {
+                    return Err(SubscriberAttributesError::Missing(field.label.clone()));
+                }
+                continue;
+            };
+
+            if answer.graphemes(true).nth(field.max_length).is_some() {
+                return Err(SubscriberAttributesError::TooLong(field.label.clone()));
+            }
+            if !field.options.is_empty() && !field.options.contains(&answer) {
+                return Err(SubscriberAttributesError::UnknownOption(
+                    field.label.clone(),
+                ));
+            }
+
+            attributes.insert(field.name.clone(), Value::String(answer));
+        }
+
+        Ok(Self(attributes))
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::Object(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use claims::{assert_err, assert_ok};
+
+    use super::{SignupField, SubscriberAttributes, SubscriberAttributesError};
+
+    fn field(name: &str) -> SignupField {
+        SignupField {
+            name: name.into(),
+            label: name.into(),
+            required: false,
+            max_length: 10,
+            options: vec![],
+        }
+    }
+
+    fn answers(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn unknown_answers_are_dropped() {
+        let attributes = assert_ok!(SubscriberAttributes::parse(
+            &[field("company")],
+            answers(&[("company", " ACME "), ("other", "ignored")]),
+        ));
+
+        assert_eq!(attributes.to_json(), serde_json::json!({"company": "ACME"}));
+    }
+
+    #[test]
+    fn blank_answers_to_required_fields_are_rejected() {
+        let company = SignupField {
+            required: true,
+            ..field("company")
+        };
+
+        let error = assert_err!(SubscriberAttributes::parse(
+            &[company],
+            answers(&[("company", "  ")]),
+        ));
+        assert!(matches!(error, SubscriberAttributesError::Missing(_)));
+    }
+
+    #[test]
+    fn answers_longer_than_the_field_are_rejected() {
+        let error = assert_err!(SubscriberAttributes::parse(
+            &[field("company")],
+            answers(&[("company", "a".repeat(11).as_str())]),
+        ));
+        assert!(matches!(error, SubscriberAttributesError::TooLong(_)));
+    }
+
+    #[test]
+    fn answers_must_be_one_of_the_options() {
+        let fields = [SignupField {
+            options: vec!["Search".into(), "A friend".into()],
+            ..field("source")
+        }];
+
+        assert_ok!(SubscriberAttributes::parse(
+            &fields,
+            answers(&[("source", "A friend")]),
+        ));
+        let error = assert_err!(SubscriberAttributes::parse(
+            &fields,
+            answers(&[("source", "Radio")]),
+        ));
+        assert!(matches!(error, SubscriberAttributesError::UnknownOption(_)));
+    }
+
+    #[test]
+    fn field_names_must_not_clash() {
+        assert_ok!(SignupField::check_all(&[
+            field("company"),
+            field("heard_from")
+        ]));
+        assert_err!(SignupField::check_all(&[field("email")]));
+        assert_err!(SignupField::check_all(&[field("Company")]));
+        assert_err!(SignupField::check_all(&[
+            field("company"),
+            field("company")
+        ]));
+    }
+}
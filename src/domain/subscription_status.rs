@@ -0,0 +1,159 @@
+/// Where a subscription stands, stored as the `subscription_status` type.
+///
+/// A subscription starts pending, or with the status it's imported with, and
+/// only moves along the transitions allowed by [`SubscriptionStatus::can_become`]:
+///
+/// ```text
+/// pending_confirmation ──> confirmed ──> unsubscribed
+///          │                   │              ^
+///          └───────┬───────────┘              │ (from any status)
+///                  v
+///   suppressed <──> bounced <──> complained
+///
+/// unsubscribed, suppressed, bounced, complained ──> pending_confirmation
+/// ```
+///
+/// Leaving an undeliverable status takes a new opt-in, through the pending
+/// status: a bounced subscriber is never confirmed straight away.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    sqlx::Type,
+    utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "subscription_status", rename_all = "snake_case")]
+pub enum SubscriptionStatus {
+    PendingConfirmation,
+    Confirmed,
+    /// The email provider refuses to deliver to the subscriber.
+    Suppressed,
+    Unsubscribed,
+    /// The provider reported that emails to the subscriber bounced.
+    Bounced,
+    /// The subscriber marked an email as spam.
+    Complained,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+#[error("A subscription can't go from {from:?} to {to:?}")]
+pub struct IllegalTransition {
+    pub from: SubscriptionStatus,
+    pub to: SubscriptionStatus,
+}
+
+impl SubscriptionStatus {
+    pub const ALL: [SubscriptionStatus; 6] = [
+        SubscriptionStatus::PendingConfirmation,
+        SubscriptionStatus::Confirmed,
+        SubscriptionStatus::Suppressed,
+        SubscriptionStatus::Unsubscribed,
+        SubscriptionStatus::Bounced,
+        SubscriptionStatus::Complained,
+    ];
+
+    /// Whether nothing can be delivered to the subscriber anymore, as
+    /// reported by the email provider.
+    pub fn is_undeliverable(&self) -> bool {
+        matches!(
+            self,
+            SubscriptionStatus::Suppressed
+                | SubscriptionStatus::Bounced
+                | SubscriptionStatus::Complained
+        )
+    }
+
+    /// Whether a subscription can move from this status to `next`. Staying
+    /// in the same status isn't a transition.
+    pub fn can_become(&self, next: SubscriptionStatus) -> bool {
+        use SubscriptionStatus::*;
+
+        if *self == next {
+            return false;
+        }
+
+        match (*self, next) {
+            (PendingConfirmation, Confirmed) => true,
+            (_, Unsubscribed) => true,
+            (PendingConfirmation | Confirmed, next) if next.is_undeliverable() => true,
+            // The provider changed its mind on why it doesn't deliver.
+            (current, next) if current.is_undeliverable() && next.is_undeliverable() => true,
+            // Opting in again.
+            (Unsubscribed | Suppressed | Bounced | Complained, PendingConfirmation) => true,
+            _ => false,
+        }
+    }
+
+    pub fn transition_to(&self, next: SubscriptionStatus) -> Result<Self, IllegalTransition> {
+        if self.can_become(next) {
+            Ok(next)
+        } else {
+            Err(IllegalTransition {
+                from: *self,
+                to: next,
+            })
+        }
+    }
+
+    /// The statuses subscriptions may be in to become `next`, to restrict
+    /// the rows of bulk updates with, e.g. `WHERE status = ANY($1)`.
+    pub fn sources_of(next: SubscriptionStatus) -> Vec<SubscriptionStatus> {
+        Self::ALL
+            .into_iter()
+            .filter(|status| status.can_become(next))
+            .collect()
+    }
+}
+
+impl sqlx::postgres::PgHasArrayType for SubscriptionStatus {
+    fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("_subscription_status")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::SubscriptionStatus::{self, *};
+
+    #[test]
+    fn pending_subscriptions_can_be_confirmed() {
+        assert_ok!(PendingConfirmation.transition_to(Confirmed));
+    }
+
+    #[test]
+    fn undeliverable_subscriptions_need_a_new_opt_in() {
+        for status in [Suppressed, Bounced, Complained, Unsubscribed] {
+            assert_err!(status.transition_to(Confirmed));
+            assert_ok!(status.transition_to(PendingConfirmation));
+        }
+    }
+
+    #[test]
+    fn any_subscription_can_be_unsubscribed() {
+        for status in SubscriptionStatus::ALL {
+            assert_eq!(status.can_become(Unsubscribed), status != Unsubscribed);
+        }
+    }
+
+    #[test]
+    fn unsubscribed_subscriptions_are_not_suppressed() {
+        assert_eq!(
+            SubscriptionStatus::sources_of(Suppressed),
+            vec![PendingConfirmation, Confirmed, Bounced, Complained]
+        );
+    }
+
+    #[test]
+    fn staying_in_the_same_status_is_not_a_transition() {
+        for status in SubscriptionStatus::ALL {
+            assert!(!status.can_become(status));
+        }
+    }
+}
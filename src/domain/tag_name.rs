@@ -0,0 +1,42 @@
+use super::{Label, LabelError};
+
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct TagNameError(#[from] LabelError);
+
+/// Tags are case insensitive, so they are stored in lowercase.
+#[derive(Debug)]
+pub struct TagName(Label);
+
+impl TagName {
+    pub fn parse(s: String) -> Result<TagName, TagNameError> {
+        Label::parse(s.to_lowercase())
+            .map(Self)
+            .map_err(TagNameError)
+    }
+}
+
+impl AsRef<str> for TagName {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::TagName;
+
+    #[test]
+    fn tags_are_lowercased() {
+        let tag = assert_ok!(TagName::parse("Early-Adopters".into()));
+
+        assert_eq!(tag.as_ref(), "early-adopters");
+    }
+
+    #[test]
+    fn empty_tags_are_rejected() {
+        assert_err!(TagName::parse(" ".into()));
+    }
+}
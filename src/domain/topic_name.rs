@@ -0,0 +1,20 @@
+use super::{Label, LabelError};
+
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct TopicNameError(#[from] LabelError);
+
+#[derive(Debug)]
+pub struct TopicName(Label);
+
+impl TopicName {
+    pub fn parse(s: String) -> Result<TopicName, TopicNameError> {
+        Label::parse(s).map(Self).map_err(TopicNameError)
+    }
+}
+
+impl AsRef<str> for TopicName {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
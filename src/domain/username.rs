@@ -0,0 +1,93 @@
+#[derive(Debug, thiserror::Error)]
+pub enum UsernameError {
+    #[error("Username must contain at least 3 and up to 64 characters.")]
+    InvalidLength,
+    #[error("Username may only contain letters, digits, dots, hyphens and underscores.")]
+    InvalidCharacters,
+}
+
+/// Name users log in with. Usernames are case insensitive, so they are
+/// stored in lowercase: "Alice" and "alice" are the same user.
+///
+/// Users registered before these rules keep their username, which may not
+/// follow them: only new usernames are parsed.
+#[derive(Debug)]
+pub struct Username(String);
+
+impl Username {
+    pub fn parse(s: String) -> Result<Username, UsernameError> {
+        let s = Self::fold(&s);
+
+        if !(3..=64).contains(&s.chars().count()) {
+            return Err(UsernameError::InvalidLength);
+        }
+
+        let contains_invalid_chars = s
+            .chars()
+            .any(|c| !(c.is_ascii_alphanumeric() || ['.', '-', '_'].contains(&c)));
+        if contains_invalid_chars {
+            return Err(UsernameError::InvalidCharacters);
+        }
+
+        Ok(Self(s))
+    }
+
+    /// The form usernames are stored and looked up in, e.g. to log in,
+    /// without checking that they follow the rules.
+    pub fn fold(s: &str) -> String {
+        s.trim().to_lowercase()
+    }
+}
+
+impl AsRef<str> for Username {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Username {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::Username;
+
+    #[test]
+    fn usernames_are_lowercased() {
+        let username = assert_ok!(Username::parse(" Ursula.Le-Guin ".into()));
+
+        assert_eq!(username.as_ref(), "ursula.le-guin");
+    }
+
+    #[test]
+    fn legacy_usernames_are_folded_without_being_checked() {
+        assert_eq!(Username::fold(" Le Guin "), "le guin");
+    }
+
+    #[test]
+    fn a_64_characters_long_username_is_valid() {
+        assert_ok!(Username::parse("a".repeat(64)));
+    }
+
+    #[test]
+    fn a_username_longer_than_64_characters_is_rejected() {
+        assert_err!(Username::parse("a".repeat(65)));
+    }
+
+    #[test]
+    fn a_username_shorter_than_3_characters_is_rejected() {
+        assert_err!(Username::parse("ab".into()));
+    }
+
+    #[test]
+    fn usernames_containing_invalid_chars_are_rejected() {
+        for username in ["ursula le guin", "ursula@guin", "<script>", "ürsula"] {
+            assert_err!(Username::parse(username.into()));
+        }
+    }
+}
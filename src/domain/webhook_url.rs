@@ -0,0 +1,54 @@
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookUrlError {
+    #[error("Invalid webhook URL")]
+    InvalidFormat,
+    #[error("Webhook URL must use http or https")]
+    UnsupportedScheme,
+}
+
+/// Endpoint that receives the events recorded by the application.
+#[derive(Debug)]
+pub struct WebhookUrl(String);
+
+impl WebhookUrl {
+    pub fn parse(s: String) -> Result<WebhookUrl, WebhookUrlError> {
+        let url = reqwest::Url::parse(s.trim()).map_err(|_| WebhookUrlError::InvalidFormat)?;
+
+        if !["http", "https"].contains(&url.scheme()) {
+            return Err(WebhookUrlError::UnsupportedScheme);
+        }
+        if url.host_str().is_none() {
+            return Err(WebhookUrlError::InvalidFormat);
+        }
+
+        Ok(Self(url.into()))
+    }
+}
+
+impl AsRef<str> for WebhookUrl {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::WebhookUrl;
+
+    #[test]
+    fn an_https_url_is_valid() {
+        assert_ok!(WebhookUrl::parse("https://crm.example.com/hooks".into()));
+    }
+
+    #[test]
+    fn a_non_http_url_is_rejected() {
+        assert_err!(WebhookUrl::parse("ftp://crm.example.com/hooks".into()));
+    }
+
+    #[test]
+    fn a_malformed_url_is_rejected() {
+        assert_err!(WebhookUrl::parse("crm.example.com/hooks".into()));
+    }
+}
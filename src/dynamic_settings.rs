@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use arc_swap::ArcSwap;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    configuration::{get_configuration, DynamicSettings},
+    email_client::EmailClient,
+    telemetry::set_log_filter,
+    template::use_templates,
+};
+
+/// Applies the [`DynamicSettings`] to the parts of the application that
+/// depend on them, and keeps the ones in use for the handlers to read.
+#[derive(Clone)]
+pub struct SettingsReloader {
+    settings: Arc<ArcSwap<DynamicSettings>>,
+    email_client: EmailClient,
+}
+
+impl SettingsReloader {
+    /// Applies the settings the application starts with, except for the log
+    /// filter which is set up along with the logs.
+    pub fn new(
+        settings: DynamicSettings,
+        email_client: EmailClient,
+    ) -> Result<Self, anyhow::Error> {
+        use_templates(&settings.template_directory).context("Failed to load the templates")?;
+        email_client.set_rate_limit(settings.max_emails_per_second, settings.max_email_burst);
+
+        Ok(Self {
+            settings: Arc::new(ArcSwap::from_pointee(settings)),
+            email_client,
+        })
+    }
+
+    pub fn settings(&self) -> Arc<ArcSwap<DynamicSettings>> {
+        self.settings.clone()
+    }
+
+    /// Nothing changes unless every setting is valid.
+    pub fn apply(&self, settings: DynamicSettings) -> Result<(), anyhow::Error> {
+        let current = self.settings.load();
+        let log_filter = if settings.log_filter != current.log_filter {
+            Some(EnvFilter::try_new(&settings.log_filter).context("Invalid log filter")?)
+        } else {
+            None
+        };
+        use_templates(&settings.template_directory).context("Failed to load the templates")?;
+
+        if let Some(log_filter) = log_filter {
+            set_log_filter(log_filter)?;
+        }
+        self.email_client
+            .set_rate_limit(settings.max_emails_per_second, settings.max_email_burst);
+        self.settings.store(Arc::new(settings));
+
+        Ok(())
+    }
+
+    /// Reads the configuration again and applies its dynamic settings.
+    #[tracing::instrument(name = "Reload dynamic settings", skip(self), err)]
+    pub fn reload(&self) -> Result<(), anyhow::Error> {
+        let configuration = get_configuration().context("Failed to read configuration")?;
+
+        self.apply(configuration.dynamic)
+    }
+
+    /// Reloads the settings each time the process gets `SIGHUP`. A failed
+    /// reload keeps the settings in use.
+    pub async fn reload_on_sighup(self) -> Result<(), anyhow::Error> {
+        let mut hangups = signal(SignalKind::hangup()).context("Failed to listen to SIGHUP")?;
+
+        while hangups.recv().await.is_some() {
+            if self.reload().is_ok() {
+                tracing::info!(settings = ?self.settings.load(), "Reloaded dynamic settings");
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,72 +1,758 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use base64::Engine;
 use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::{
+    domain::Email,
+    email_log::{record_emails, EmailKind, LoggedEmail},
+};
 
-use crate::domain::Email;
+/// Postmark refuses emails whose attachments weigh more than this.
+pub const MAX_ATTACHMENTS_BYTES: u64 = 10 * 1024 * 1024;
+
+/// A file sent along with an email.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    /// Encoded in base64, as the provider expects it.
+    pub content: String,
+}
+
+impl Attachment {
+    pub fn new(filename: String, content_type: String, content: &[u8]) -> Self {
+        Self {
+            filename,
+            content_type,
+            content: base64::engine::general_purpose::STANDARD.encode(content),
+        }
+    }
+
+    /// Size of the decoded content.
+    pub fn size(&self) -> u64 {
+        let padding = self
+            .content
+            .bytes()
+            .rev()
+            .take_while(|b| *b == b'=')
+            .count();
+
+        (self.content.len() / 4 * 3).saturating_sub(padding) as u64
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct PostmarkAttachment<'a> {
+    name: &'a str,
+    content: &'a str,
+    content_type: &'a str,
+}
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "PascalCase")]
 struct SendEmailRequest<'a> {
-    from: &'a str,
+    from: String,
     to: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<&'a str>,
     subject: &'a str,
     html_body: &'a str,
     text_body: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<PostmarkAttachment<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<EmailMetadata>,
+}
+
+/// Echoed back by Postmark in the webhooks about the email.
+#[derive(serde::Serialize)]
+struct EmailMetadata {
+    newsletter_issue_id: Uuid,
+}
+
+/// Postmark code of the error returned when the recipient was marked as
+/// inactive after a hard bounce, a spam complaint or a manual suppression.
+const INACTIVE_RECIPIENT_ERROR_CODE: i64 = 406;
+
+/// Error body Postmark returns along with a 422 status code.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PostmarkError {
+    pub error_code: i64,
+    pub message: String,
+}
+
+impl std::fmt::Display for PostmarkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (error code {})", self.message, self.error_code)
+    }
+}
+
+/// Answer of Postmark about an email, alone or in a batch. An email whose
+/// answer can't be read is taken as having no message id.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PostmarkResponse {
+    error_code: i64,
+    message: String,
+    #[serde(rename = "MessageID", default)]
+    message_id: Option<String>,
+}
+
+/// Stream the emails are sent through when the request doesn't name one.
+const MESSAGE_STREAM: &str = "outbound";
+
+/// Postmark takes at most this many emails per batch request, and requests
+/// of at most this many bytes.
+const MAX_EMAILS_PER_BATCH: usize = 500;
+const MAX_BATCH_BYTES: usize = 50 * 1024 * 1024;
+
+/// Postmark takes at most this many addresses per suppression request.
+const MAX_SUPPRESSIONS_PER_REQUEST: usize = 50;
+
+/// Why the provider stopped delivering to a recipient.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum SuppressionReason {
+    HardBounce,
+    SpamComplaint,
+    /// Suppressed through the dashboard or the API, or a reason unknown
+    /// to us.
+    #[serde(other, rename = "ManualSuppression")]
+    Manual,
+}
+
+/// A recipient the provider doesn't deliver to anymore.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Suppression {
+    pub email_address: String,
+    #[serde(rename = "SuppressionReason")]
+    pub reason: SuppressionReason,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SuppressionDump {
+    suppressions: Vec<Suppression>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SuppressionEntry<'a> {
+    email_address: &'a str,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct CreateSuppressionsRequest<'a> {
+    suppressions: Vec<SuppressionEntry<'a>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SendEmailError {
+    #[error("The recipient is inactive: {0}")]
+    InactiveRecipient(PostmarkError),
+    #[error("The email was rejected: {0}")]
+    Rejected(PostmarkError),
+    #[error("The attachments exceed {0} bytes")]
+    AttachmentsTooLarge(u64),
+    #[error("The provider kept refusing the email for exceeding its rate limit")]
+    RateLimited,
+    #[error("The provider answered for {0} emails out of the {1} of the batch")]
+    IncompleteBatchResponse(usize, usize),
+    #[error("The batch the email was part of failed")]
+    BatchFailed(#[source] Arc<SendEmailError>),
+    #[error(transparent)]
+    RequestError(#[from] reqwest::Error),
 }
 
+/// Times an email refused with a 429 is sent again before giving up.
+const MAX_RATE_LIMITED_RETRIES: u32 = 3;
+
+/// Pause after a 429 without a usable `Retry-After`, and the longest one
+/// honoured.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Spaces out the emails sent through a client and its clones, as a token
+/// bucket refilled with `per_second` tokens each second and holding `burst`
+/// of them at most.
+#[derive(Default)]
+struct RateLimiter {
+    /// No limit when 0.
+    per_second: AtomicU32,
+    /// Emails sent back to back before being spaced out, at least 1.
+    burst: AtomicU32,
+    /// When the bucket is empty again, given the emails sent so far.
+    next_slot: Mutex<Option<Instant>>,
+    /// Set when the provider answers with a 429, limit or not.
+    paused_until: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    /// Waits until `n` emails can go out.
+    async fn wait_for_slots(&self, n: u32) {
+        let now = Instant::now();
+        let earliest = self
+            .paused_until
+            .lock()
+            .unwrap()
+            .map_or(now, |paused_until| paused_until.max(now));
+
+        let per_second = self.per_second.load(Ordering::Relaxed);
+        if per_second == 0 {
+            tokio::time::sleep_until(earliest).await;
+            return;
+        }
+
+        let interval = Duration::from_secs(1) / per_second;
+        let tolerance = interval * (self.burst.load(Ordering::Relaxed).max(1) - 1);
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let empty_at = next_slot.map_or(earliest, |next| next.max(earliest));
+            *next_slot = Some(empty_at + interval * n);
+            // The slot of the last of the emails.
+            (empty_at + interval * n.saturating_sub(1))
+                .checked_sub(tolerance)
+                .map_or(earliest, |slot| slot.max(earliest))
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+
+    /// Holds back every email, so that the provider isn't hammered while it
+    /// refuses them.
+    fn pause(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut paused_until = self.paused_until.lock().unwrap();
+        *paused_until = Some(paused_until.map_or(until, |paused_until| paused_until.max(until)));
+    }
+}
+
+/// Seconds to wait according to the `Retry-After` header of a 429.
+fn retry_after(response: &reqwest::Response) -> Duration {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map_or(DEFAULT_RETRY_AFTER, |seconds| {
+            Duration::from_secs(seconds).min(MAX_RETRY_AFTER)
+        })
+}
+
+fn rejection(error: PostmarkError) -> SendEmailError {
+    if error.error_code == INACTIVE_RECIPIENT_ERROR_CODE {
+        SendEmailError::InactiveRecipient(error)
+    } else {
+        SendEmailError::Rejected(error)
+    }
+}
+
+/// Turns a 422 into the error Postmark describes in its body.
+async fn check_rejection(response: reqwest::Response) -> Result<reqwest::Response, SendEmailError> {
+    if response.status() == reqwest::StatusCode::UNPROCESSABLE_ENTITY {
+        let error = response.json::<PostmarkError>().await?;
+
+        return Err(rejection(error));
+    }
+
+    Ok(response.error_for_status()?)
+}
+
+/// An email sent along with others, see [`EmailClient::send_email_batch`].
+#[derive(Clone, Copy)]
+pub struct OutgoingEmail<'a> {
+    pub kind: EmailKind,
+    pub recipient: &'a Email,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub attachments: &'a [Attachment],
+    /// Shown as who the email is from, before the sender address.
+    pub sender_name: Option<&'a str>,
+    /// Sends from this address instead of the default one. It must be one of
+    /// the verified senders, see [`EmailClient::is_verified_sender`].
+    pub sender_email: Option<&'a Email>,
+    /// Where replies go, the sender address otherwise.
+    pub reply_to: Option<&'a Email>,
+    /// Tags the email as one of the issue, like
+    /// [`EmailClient::send_issue_email`] does.
+    pub newsletter_issue_id: Option<Uuid>,
+}
+
+impl OutgoingEmail<'_> {
+    /// Rough size of the email in a request, attachments included.
+    fn size(&self) -> usize {
+        self.subject.len()
+            + self.html_content.len()
+            + self.text_content.len()
+            + self
+                .attachments
+                .iter()
+                .map(|attachment| attachment.content.len())
+                .sum::<usize>()
+    }
+}
+
+/// Splits the emails at the given positions into batches the provider
+/// accepts. An email too large to share a batch is sent on its own.
+fn batches(emails: &[OutgoingEmail<'_>], positions: &[usize]) -> Vec<Vec<usize>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_size = 0;
+    for &i in positions {
+        let size = emails[i].size();
+        if !batch.is_empty()
+            && (batch.len() == MAX_EMAILS_PER_BATCH || batch_size + size > MAX_BATCH_BYTES)
+        {
+            batches.push(std::mem::take(&mut batch));
+            batch_size = 0;
+        }
+        batch.push(i);
+        batch_size += size;
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+}
+
+/// Cheap to clone: clones share the same pool of connections to the provider
+/// and the same rate limit.
+#[derive(Clone)]
 pub struct EmailClient {
     http_client: Client,
     base_url: reqwest::Url,
     sender: Email,
+    /// Other addresses the provider lets us send from.
+    verified_senders: Arc<[Email]>,
     authorization_token: Secret<String>,
+    max_attachments_bytes: u64,
+    rate_limiter: Arc<RateLimiter>,
+    /// Where sent emails are recorded, nowhere if missing.
+    email_log: Option<PgPool>,
 }
 
 impl EmailClient {
     pub fn new(
+        http_client: Client,
         base_url: reqwest::Url,
         sender: Email,
         authorization_token: Secret<String>,
-        timeout: std::time::Duration,
     ) -> Self {
-        let http_client = Client::builder().timeout(timeout).build().unwrap();
-
         Self {
             http_client,
             base_url,
             sender,
+            verified_senders: Arc::new([]),
             authorization_token,
+            max_attachments_bytes: MAX_ATTACHMENTS_BYTES,
+            rate_limiter: Arc::default(),
+            email_log: None,
         }
     }
 
+    /// Caps the emails sent per second by this client and its clones, from
+    /// now on, letting `max_email_burst` of them go out at once. None lifts
+    /// the limit, a missing burst is 1.
+    pub fn set_rate_limit(&self, max_emails_per_second: Option<u32>, max_email_burst: Option<u32>) {
+        self.rate_limiter
+            .per_second
+            .store(max_emails_per_second.unwrap_or(0), Ordering::Relaxed);
+        self.rate_limiter
+            .burst
+            .store(max_email_burst.unwrap_or(1), Ordering::Relaxed);
+    }
+
+    pub fn with_max_attachments_bytes(mut self, max_attachments_bytes: u64) -> Self {
+        self.max_attachments_bytes = max_attachments_bytes;
+        self
+    }
+
+    pub fn with_verified_senders(mut self, verified_senders: Vec<Email>) -> Self {
+        self.verified_senders = verified_senders.into();
+        self
+    }
+
+    /// Records every email sent from now on, with how the provider answered,
+    /// see [`crate::email_log`].
+    pub fn with_email_log(mut self, pool: PgPool) -> Self {
+        self.email_log = Some(pool);
+        self
+    }
+
+    /// Whether emails can be sent from the address: the sender address, or
+    /// one of the other verified senders.
+    pub fn is_verified_sender(&self, address: &Email) -> bool {
+        std::iter::once(&self.sender)
+            .chain(self.verified_senders.iter())
+            .any(|sender| sender.as_ref().eq_ignore_ascii_case(address.as_ref()))
+    }
+
+    /// Total size of the attachments of an email.
+    pub fn max_attachments_bytes(&self) -> u64 {
+        self.max_attachments_bytes
+    }
+
+    /// Sends an email from the sender address, shown after `sender_name` if
+    /// given.
+    #[allow(clippy::too_many_arguments)]
     pub async fn send_email(
         &self,
+        kind: EmailKind,
         recipient: &Email,
         subject: &str,
         html_content: &str,
         text_content: &str,
-    ) -> Result<(), reqwest::Error> {
-        let url = self.base_url.join("email").unwrap();
-        let request_body = SendEmailRequest {
-            from: self.sender.as_ref(),
-            to: recipient.as_ref(),
+        attachments: &[Attachment],
+        sender_name: Option<&str>,
+    ) -> Result<(), SendEmailError> {
+        self.send(OutgoingEmail {
+            kind,
+            recipient,
             subject,
-            html_body: html_content,
-            text_body: text_content,
+            html_content,
+            text_content,
+            attachments,
+            sender_name,
+            sender_email: None,
+            reply_to: None,
+            newsletter_issue_id: None,
+        })
+        .await
+    }
+
+    /// Sends an email of the issue, tagged so that the bounces the provider
+    /// reports about it can be traced back to the issue.
+    pub async fn send_issue_email(
+        &self,
+        newsletter_issue_id: Uuid,
+        recipient: &Email,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        attachments: &[Attachment],
+    ) -> Result<(), SendEmailError> {
+        self.send(OutgoingEmail {
+            kind: EmailKind::Issue,
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            attachments,
+            sender_name: None,
+            sender_email: None,
+            reply_to: None,
+            newsletter_issue_id: Some(newsletter_issue_id),
+        })
+        .await
+    }
+
+    async fn send(&self, email: OutgoingEmail<'_>) -> Result<(), SendEmailError> {
+        let outcome = self.send_one(&email).await;
+        self.log(&[email], std::slice::from_ref(&outcome)).await;
+
+        outcome.map(|_| ())
+    }
+
+    /// Gives back the id the provider assigned to the email, if it said.
+    async fn send_one(&self, email: &OutgoingEmail<'_>) -> Result<Option<String>, SendEmailError> {
+        let attachments_size: u64 = email.attachments.iter().map(Attachment::size).sum();
+        if attachments_size > self.max_attachments_bytes {
+            return Err(SendEmailError::AttachmentsTooLarge(
+                self.max_attachments_bytes,
+            ));
+        }
+        let request_body = self.request(email);
+
+        let response = self.post_emails("email", &request_body, 1).await?;
+        let message_id = check_rejection(response)
+            .await?
+            .json::<PostmarkResponse>()
+            .await
+            .ok()
+            .and_then(|response| response.message_id);
+
+        Ok(message_id)
+    }
+
+    /// Records the emails along with their outcome, in the same order. A
+    /// failure to do so is only logged: the emails are gone already.
+    async fn log(
+        &self,
+        emails: &[OutgoingEmail<'_>],
+        outcomes: &[Result<Option<String>, SendEmailError>],
+    ) {
+        let Some(pool) = &self.email_log else {
+            return;
         };
+        let entries: Vec<LoggedEmail> = emails
+            .iter()
+            .zip(outcomes)
+            .map(|(email, outcome)| LoggedEmail::new(email, outcome))
+            .collect();
+
+        if let Err(e) = record_emails(pool, &entries).await {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to record the emails sent",
+            );
+        }
+    }
+
+    /// Groups the emails, by position, the way
+    /// [`EmailClient::send_email_batch`] sends them: each group goes out in a
+    /// single request.
+    pub fn split_into_requests(emails: &[OutgoingEmail<'_>]) -> Vec<Vec<usize>> {
+        let positions: Vec<usize> = (0..emails.len()).collect();
+
+        batches(emails, &positions)
+    }
+
+    /// Sends many emails in as few requests as the provider allows. There's
+    /// one outcome per email, in the same order: an email may be refused
+    /// while the others of its batch go out.
+    pub async fn send_email_batch(
+        &self,
+        emails: Vec<OutgoingEmail<'_>>,
+    ) -> Vec<Result<(), SendEmailError>> {
+        let mut outcomes: Vec<Option<Result<Option<String>, SendEmailError>>> =
+            emails.iter().map(|_| None).collect();
+        let mut sendable = Vec::with_capacity(emails.len());
+        for (i, email) in emails.iter().enumerate() {
+            let attachments_size: u64 = email.attachments.iter().map(Attachment::size).sum();
+            if attachments_size > self.max_attachments_bytes {
+                outcomes[i] = Some(Err(SendEmailError::AttachmentsTooLarge(
+                    self.max_attachments_bytes,
+                )));
+            } else {
+                sendable.push(i);
+            }
+        }
+
+        for chunk in batches(&emails, &sendable) {
+            let requests: Vec<SendEmailRequest> =
+                chunk.iter().map(|i| self.request(&emails[*i])).collect();
+
+            match self.send_batch(&requests).await {
+                Ok(results) => {
+                    for (i, result) in chunk.iter().zip(results) {
+                        outcomes[*i] = Some(result);
+                    }
+                }
+                Err(e) => {
+                    let e = Arc::new(e);
+                    for i in chunk {
+                        outcomes[i] = Some(Err(SendEmailError::BatchFailed(e.clone())));
+                    }
+                }
+            }
+        }
+
+        let outcomes: Vec<_> = outcomes
+            .into_iter()
+            .map(|outcome| outcome.expect("Every email of the batch has an outcome"))
+            .collect();
+        self.log(&emails, &outcomes).await;
+
+        outcomes
+            .into_iter()
+            .map(|outcome| outcome.map(|_| ()))
+            .collect()
+    }
+
+    async fn send_batch(
+        &self,
+        requests: &[SendEmailRequest<'_>],
+    ) -> Result<Vec<Result<Option<String>, SendEmailError>>, SendEmailError> {
+        let response = self
+            .post_emails("email/batch", requests, requests.len() as u32)
+            .await?;
+        let results = check_rejection(response)
+            .await?
+            .json::<Vec<PostmarkResponse>>()
+            .await?;
+        if results.len() != requests.len() {
+            return Err(SendEmailError::IncompleteBatchResponse(
+                results.len(),
+                requests.len(),
+            ));
+        }
+
+        Ok(results
+            .into_iter()
+            .map(|result| match result.error_code {
+                0 => Ok(result.message_id),
+                error_code => Err(rejection(PostmarkError {
+                    error_code,
+                    message: result.message,
+                })),
+            })
+            .collect())
+    }
+
+    /// The sender address, after the given name if any. The name is quoted,
+    /// with line breaks dropped so that it can't add headers.
+    fn from_header(sender_name: Option<&str>, sender_email: &Email) -> String {
+        let Some(sender_name) = sender_name else {
+            return sender_email.as_ref().to_string();
+        };
+
+        let mut quoted = String::with_capacity(sender_name.len() + 2);
+        quoted.push('"');
+        for c in sender_name.chars().filter(|c| !c.is_control()) {
+            if c == '"' || c == '\\' {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+
+        format!("{} <{}>", quoted, sender_email.as_ref())
+    }
+
+    fn request<'a>(&self, email: &OutgoingEmail<'a>) -> SendEmailRequest<'a> {
+        SendEmailRequest {
+            from: Self::from_header(
+                email.sender_name,
+                email.sender_email.unwrap_or(&self.sender),
+            ),
+            to: email.recipient.as_ref(),
+            reply_to: email.reply_to.map(|reply_to| reply_to.as_ref()),
+            subject: email.subject,
+            html_body: email.html_content,
+            text_body: email.text_content,
+            attachments: email
+                .attachments
+                .iter()
+                .map(|attachment| PostmarkAttachment {
+                    name: &attachment.filename,
+                    content: &attachment.content,
+                    content_type: &attachment.content_type,
+                })
+                .collect(),
+            metadata: email
+                .newsletter_issue_id
+                .map(|newsletter_issue_id| EmailMetadata {
+                    newsletter_issue_id,
+                }),
+        }
+    }
 
-        self.http_client
-            .post(url)
+    /// Posts emails once the rate limit lets them go, trying again while the
+    /// provider answers with a 429.
+    async fn post_emails<T: serde::Serialize + ?Sized>(
+        &self,
+        path: &str,
+        body: &T,
+        n_emails: u32,
+    ) -> Result<reqwest::Response, SendEmailError> {
+        let url = self.base_url.join(path).unwrap();
+
+        let mut retries = 0;
+        loop {
+            self.rate_limiter.wait_for_slots(n_emails).await;
+
+            let response = self
+                .http_client
+                .post(url.clone())
+                .header(
+                    "X-Postmark-Server-Token",
+                    self.authorization_token.expose_secret(),
+                )
+                .header("Accept", "application/json")
+                // json method sets the header at this time.
+                // However, I prefer to be sceptical about that.
+                .header("Content-Type", "application/json")
+                .json(body)
+                .send()
+                .await?;
+
+            if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+            if retries == MAX_RATE_LIMITED_RETRIES {
+                return Err(SendEmailError::RateLimited);
+            }
+            retries += 1;
+
+            let pause = retry_after(&response);
+            tracing::warn!(
+                retry_after = ?pause,
+                "The email provider is rate limiting us, slowing down"
+            );
+            self.rate_limiter.pause(pause);
+        }
+    }
+
+    fn suppressions_url(&self, path: &str) -> reqwest::Url {
+        self.base_url
+            .join(&format!(
+                "message-streams/{}/suppressions{}",
+                MESSAGE_STREAM, path
+            ))
+            .unwrap()
+    }
+
+    /// Every recipient the provider suppressed.
+    pub async fn list_suppressions(&self) -> Result<Vec<Suppression>, reqwest::Error> {
+        let dump = self
+            .http_client
+            .get(self.suppressions_url("/dump"))
             .header(
                 "X-Postmark-Server-Token",
                 self.authorization_token.expose_secret(),
             )
             .header("Accept", "application/json")
-            // json method sets the header at this time.
-            // However, I prefer to be sceptical about that.
-            .header("Content-Type", "application/json")
-            .json(&request_body)
             .send()
             .await?
-            .error_for_status()?;
+            .error_for_status()?
+            .json::<SuppressionDump>()
+            .await?;
+
+        Ok(dump.suppressions)
+    }
+
+    /// Asks the provider to stop delivering to the recipients, as a manual
+    /// suppression.
+    pub async fn suppress_recipients(&self, emails: &[String]) -> Result<(), reqwest::Error> {
+        for chunk in emails.chunks(MAX_SUPPRESSIONS_PER_REQUEST) {
+            let request_body = CreateSuppressionsRequest {
+                suppressions: chunk
+                    .iter()
+                    .map(|email| SuppressionEntry {
+                        email_address: email,
+                    })
+                    .collect(),
+            };
+
+            self.http_client
+                .post(self.suppressions_url(""))
+                .header(
+                    "X-Postmark-Server-Token",
+                    self.authorization_token.expose_secret(),
+                )
+                .header("Accept", "application/json")
+                .header("Content-Type", "application/json")
+                .json(&request_body)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
 
         Ok(())
     }
@@ -74,16 +760,20 @@ impl EmailClient {
 
 #[cfg(test)]
 mod test {
-    use claims::{assert_err, assert_ok};
+    use claims::{assert_err, assert_matches, assert_ok};
     use fake::faker::lorem::en::Sentence;
     use fake::Faker;
     use fake::{faker::internet::en::SafeEmail, Fake};
     use secrecy::Secret;
+    use uuid::Uuid;
     use wiremock::matchers::{any, header, header_exists, method, path};
     use wiremock::{Match, Mock, MockServer, ResponseTemplate};
 
     use crate::domain::Email;
-    use crate::email_client::EmailClient;
+    use crate::email_client::{
+        Attachment, EmailClient, OutgoingEmail, SendEmailError, SuppressionReason,
+    };
+    use crate::email_log::EmailKind;
 
     struct SendEmailBodyMatcher;
 
@@ -115,12 +805,12 @@ mod test {
         let base_url = reqwest::Url::parse(&base_url).unwrap();
         let sender = email();
 
-        EmailClient::new(
-            base_url,
-            sender,
-            Secret::new(Faker.fake()),
-            std::time::Duration::from_millis(400),
-        )
+        let http_client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_millis(400))
+            .build()
+            .unwrap();
+
+        EmailClient::new(http_client, base_url, sender, Secret::new(Faker.fake()))
     }
 
     #[tokio::test]
@@ -140,7 +830,15 @@ mod test {
             .await;
 
         let _ = email_client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email(
+                EmailKind::SubscriptionConfirmation,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[],
+                None,
+            )
             .await;
     }
 
@@ -156,7 +854,15 @@ mod test {
             .await;
 
         let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email(
+                EmailKind::SubscriptionConfirmation,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[],
+                None,
+            )
             .await;
 
         assert_ok!(outcome);
@@ -174,12 +880,78 @@ mod test {
             .await;
 
         let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email(
+                EmailKind::SubscriptionConfirmation,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[],
+                None,
+            )
             .await;
 
         assert_err!(outcome);
     }
 
+    #[tokio::test]
+    async fn send_email_reports_inactive_recipients() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+                "ErrorCode": 406,
+                "Message": "You tried to send to a recipient that has been marked as inactive."
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(
+                EmailKind::SubscriptionConfirmation,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[],
+                None,
+            )
+            .await;
+
+        assert_matches!(outcome, Err(SendEmailError::InactiveRecipient(_)));
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_other_rejections() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+                "ErrorCode": 300,
+                "Message": "Invalid email request"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(
+                EmailKind::SubscriptionConfirmation,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[],
+                None,
+            )
+            .await;
+
+        assert_matches!(outcome, Err(SendEmailError::Rejected(_)));
+    }
+
     #[tokio::test]
     async fn send_email_times_out_if_the_server_takes_too_long() {
         let mock_server = MockServer::start().await;
@@ -193,9 +965,516 @@ mod test {
             .await;
 
         let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email(
+                EmailKind::SubscriptionConfirmation,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[],
+                None,
+            )
             .await;
 
         assert_err!(outcome);
     }
+
+    #[tokio::test]
+    async fn send_email_sends_attachments_encoded_in_base64() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let attachment = Attachment::new("issue.pdf".into(), "application/pdf".into(), b"%PDF-1.7");
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        email_client
+            .send_email(
+                EmailKind::SubscriptionConfirmation,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[attachment],
+                None,
+            )
+            .await
+            .unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(
+            body["Attachments"],
+            serde_json::json!([{
+                "Name": "issue.pdf",
+                "Content": "JVBERi0xLjc=",
+                "ContentType": "application/pdf",
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_shows_the_sender_name_before_the_sender_address() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+
+        email_client
+            .send_email(
+                EmailKind::SubscriptionConfirmation,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[],
+                Some("Ursula \"K.\"\r\nBcc: eve@example.com"),
+            )
+            .await
+            .unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(
+            body["From"],
+            format!(
+                r#""Ursula \"K.\"Bcc: eve@example.com" <{}>"#,
+                email_client.sender.as_ref()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_emails_can_override_the_sender_and_reply_to_addresses() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "ErrorCode": 0, "Message": "OK" },
+                { "ErrorCode": 0, "Message": "OK" },
+            ])))
+            .mount(&mock_server)
+            .await;
+        let sender = Email::parse("editor@example.com".into()).unwrap();
+        let reply_to = Email::parse("letters@example.com".into()).unwrap();
+        let recipient = email();
+        let outgoing = OutgoingEmail {
+            kind: EmailKind::Issue,
+            recipient: &recipient,
+            subject: "Issue",
+            html_content: "<p>Issue</p>",
+            text_content: "Issue",
+            attachments: &[],
+            sender_name: Some("The editor"),
+            sender_email: Some(&sender),
+            reply_to: Some(&reply_to),
+            newsletter_issue_id: None,
+        };
+
+        email_client
+            .send_email_batch(vec![
+                outgoing,
+                OutgoingEmail {
+                    sender_email: None,
+                    reply_to: None,
+                    ..outgoing
+                },
+            ])
+            .await;
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body[0]["From"], r#""The editor" <editor@example.com>"#);
+        assert_eq!(body[0]["ReplyTo"], "letters@example.com");
+        assert_eq!(
+            body[1]["From"],
+            format!(r#""The editor" <{}>"#, email_client.sender.as_ref())
+        );
+        assert!(body[1].get("ReplyTo").is_none());
+    }
+
+    #[test]
+    fn the_sender_address_and_the_configured_ones_are_verified() {
+        let email_client = email_client("http://localhost".into())
+            .with_verified_senders(vec![Email::parse("editor@example.com".into()).unwrap()]);
+
+        assert!(email_client.is_verified_sender(&email_client.sender));
+        assert!(
+            email_client.is_verified_sender(&Email::parse("Editor@Example.com".into()).unwrap())
+        );
+        assert!(!email_client.is_verified_sender(&Email::parse("eve@example.com".into()).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn send_email_refuses_attachments_over_the_limit() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri()).with_max_attachments_bytes(7);
+        let attachment = Attachment::new("issue.pdf".into(), "application/pdf".into(), b"%PDF-1.7");
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(
+                EmailKind::SubscriptionConfirmation,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[attachment],
+                None,
+            )
+            .await;
+
+        assert_matches!(outcome, Err(SendEmailError::AttachmentsTooLarge(7)));
+    }
+
+    #[tokio::test]
+    async fn issue_emails_carry_the_issue_id_as_metadata() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let newsletter_issue_id = Uuid::new_v4();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        email_client
+            .send_issue_email(
+                newsletter_issue_id,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[],
+            )
+            .await
+            .unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(
+            body["Metadata"]["newsletter_issue_id"],
+            newsletter_issue_id.to_string()
+        );
+    }
+
+    #[test]
+    fn attachment_size_is_the_decoded_size() {
+        for content in [&b""[..], b"a", b"ab", b"abc", b"abcd"] {
+            let attachment = Attachment::new("a".into(), "text/plain".into(), content);
+
+            assert_eq!(attachment.size(), content.len() as u64);
+        }
+    }
+
+    #[tokio::test]
+    async fn emails_are_spaced_out_by_the_rate_limit() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+        email_client.clone().set_rate_limit(Some(10), None);
+
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            email_client
+                .send_email(
+                    EmailKind::SubscriptionConfirmation,
+                    &email(),
+                    &subject(),
+                    &content(),
+                    &content(),
+                    &[],
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+
+        assert!(start.elapsed() >= std::time::Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn emails_within_the_burst_are_not_spaced_out() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+        email_client.set_rate_limit(Some(1), Some(3));
+
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            email_client
+                .send_email(
+                    EmailKind::SubscriptionConfirmation,
+                    &email(),
+                    &subject(),
+                    &content(),
+                    &content(),
+                    &[],
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+
+        assert!(start.elapsed() < std::time::Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn rate_limited_emails_are_sent_again_after_retry_after() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let start = std::time::Instant::now();
+        let outcome = email_client
+            .send_email(
+                EmailKind::SubscriptionConfirmation,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[],
+                None,
+            )
+            .await;
+
+        assert_ok!(outcome);
+        assert!(start.elapsed() >= std::time::Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn emails_refused_for_the_rate_limit_too_many_times_fail() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .expect(4)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(
+                EmailKind::SubscriptionConfirmation,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[],
+                None,
+            )
+            .await;
+
+        assert_matches!(outcome, Err(SendEmailError::RateLimited));
+    }
+
+    #[tokio::test]
+    async fn batches_report_the_outcome_of_each_email() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(method("POST"))
+            .and(path("/email/batch"))
+            .and(header_exists("X-Postmark-Server-Token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "ErrorCode": 0, "Message": "OK" },
+                { "ErrorCode": 406, "Message": "Inactive recipient" },
+                { "ErrorCode": 300, "Message": "Invalid email request" },
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let recipients = [email(), email(), email()];
+        let newsletter_issue_id = Uuid::new_v4();
+
+        let outcomes = email_client
+            .send_email_batch(
+                recipients
+                    .iter()
+                    .map(|recipient| OutgoingEmail {
+                        kind: EmailKind::Issue,
+                        recipient,
+                        subject: "Issue",
+                        html_content: "<p>Issue</p>",
+                        text_content: "Issue",
+                        attachments: &[],
+                        sender_name: None,
+                        sender_email: None,
+                        reply_to: None,
+                        newsletter_issue_id: Some(newsletter_issue_id),
+                    })
+                    .collect(),
+            )
+            .await;
+
+        assert_ok!(&outcomes[0]);
+        assert_matches!(&outcomes[1], Err(SendEmailError::InactiveRecipient(_)));
+        assert_matches!(&outcomes[2], Err(SendEmailError::Rejected(_)));
+        let requests = mock_server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 3);
+        assert_eq!(body[1]["To"], recipients[1].as_ref());
+        assert_eq!(
+            body[0]["Metadata"]["newsletter_issue_id"],
+            newsletter_issue_id.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn batches_are_split_to_fit_the_provider_limits() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(path("/email/batch"))
+            .respond_with(|request: &wiremock::Request| {
+                let emails: Vec<serde_json::Value> = serde_json::from_slice(&request.body).unwrap();
+                let results: Vec<_> = emails
+                    .iter()
+                    .map(|_| serde_json::json!({ "ErrorCode": 0, "Message": "OK" }))
+                    .collect();
+                ResponseTemplate::new(200).set_body_json(results)
+            })
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let recipients: Vec<Email> = (0..501).map(|_| email()).collect();
+
+        let outcomes = email_client
+            .send_email_batch(
+                recipients
+                    .iter()
+                    .map(|recipient| OutgoingEmail {
+                        kind: EmailKind::Issue,
+                        recipient,
+                        subject: "Issue",
+                        html_content: "<p>Issue</p>",
+                        text_content: "Issue",
+                        attachments: &[],
+                        sender_name: None,
+                        sender_email: None,
+                        reply_to: None,
+                        newsletter_issue_id: None,
+                    })
+                    .collect(),
+            )
+            .await;
+
+        assert_eq!(outcomes.len(), 501);
+        assert!(outcomes.iter().all(Result::is_ok));
+    }
+
+    #[tokio::test]
+    async fn every_email_of_a_failed_batch_fails() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let recipients = [email(), email()];
+
+        let outcomes = email_client
+            .send_email_batch(
+                recipients
+                    .iter()
+                    .map(|recipient| OutgoingEmail {
+                        kind: EmailKind::Issue,
+                        recipient,
+                        subject: "Issue",
+                        html_content: "<p>Issue</p>",
+                        text_content: "Issue",
+                        attachments: &[],
+                        sender_name: None,
+                        sender_email: None,
+                        reply_to: None,
+                        newsletter_issue_id: None,
+                    })
+                    .collect(),
+            )
+            .await;
+
+        assert_matches!(&outcomes[0], Err(SendEmailError::BatchFailed(_)));
+        assert_matches!(&outcomes[1], Err(SendEmailError::BatchFailed(_)));
+    }
+
+    #[tokio::test]
+    async fn suppressions_are_listed_with_their_reason() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(method("GET"))
+            .and(path("/message-streams/outbound/suppressions/dump"))
+            .and(header_exists("X-Postmark-Server-Token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "Suppressions": [
+                    {
+                        "EmailAddress": "bounced@example.com",
+                        "SuppressionReason": "HardBounce",
+                        "Origin": "Recipient",
+                        "CreatedAt": "2024-10-20T08:58:33-05:00"
+                    },
+                    {
+                        "EmailAddress": "other@example.com",
+                        "SuppressionReason": "SomethingNew",
+                        "Origin": "Admin",
+                        "CreatedAt": "2024-10-20T08:58:33-05:00"
+                    }
+                ]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let suppressions = email_client.list_suppressions().await.unwrap();
+
+        assert_eq!(suppressions.len(), 2);
+        assert_eq!(suppressions[0].email_address, "bounced@example.com");
+        assert_eq!(suppressions[0].reason, SuppressionReason::HardBounce);
+        assert_eq!(suppressions[1].reason, SuppressionReason::Manual);
+    }
+
+    #[tokio::test]
+    async fn recipients_are_suppressed_in_batches() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(method("POST"))
+            .and(path("/message-streams/outbound/suppressions"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let emails: Vec<String> = (0..60).map(|i| format!("{}@example.com", i)).collect();
+
+        email_client.suppress_recipients(&emails).await.unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!(body["Suppressions"].as_array().unwrap().len(), 10);
+        assert_eq!(body["Suppressions"][0]["EmailAddress"], "50@example.com");
+    }
 }
@@ -0,0 +1,212 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::email_client::{OutgoingEmail, SendEmailError};
+
+/// What an email is about, recorded along with it in the email log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmailKind {
+    SubscriptionConfirmation,
+    SubscriptionWelcome,
+    PreferencesLink,
+    EmailChangeConfirmation,
+    CollaboratorInvitation,
+    ReferralReward,
+    WeeklyReport,
+    WeeklyDigest,
+    Issue,
+}
+
+impl EmailKind {
+    pub const ALL: &'static [EmailKind] = &[
+        EmailKind::SubscriptionConfirmation,
+        EmailKind::SubscriptionWelcome,
+        EmailKind::PreferencesLink,
+        EmailKind::EmailChangeConfirmation,
+        EmailKind::CollaboratorInvitation,
+        EmailKind::ReferralReward,
+        EmailKind::WeeklyReport,
+        EmailKind::WeeklyDigest,
+        EmailKind::Issue,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailKind::SubscriptionConfirmation => "subscription_confirmation",
+            EmailKind::SubscriptionWelcome => "subscription_welcome",
+            EmailKind::PreferencesLink => "preferences_link",
+            EmailKind::EmailChangeConfirmation => "email_change_confirmation",
+            EmailKind::CollaboratorInvitation => "collaborator_invitation",
+            EmailKind::ReferralReward => "referral_reward",
+            EmailKind::WeeklyReport => "weekly_report",
+            EmailKind::WeeklyDigest => "weekly_digest",
+            EmailKind::Issue => "issue",
+        }
+    }
+}
+
+/// How the provider answered when the email was handed to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmailStatus {
+    /// Accepted, which doesn't mean it was delivered yet.
+    Sent,
+    /// Refused, e.g. because the recipient is suppressed.
+    Rejected,
+    /// Never reached the provider, or got no usable answer from it.
+    Failed,
+}
+
+impl EmailStatus {
+    pub const ALL: &'static [EmailStatus] = &[
+        EmailStatus::Sent,
+        EmailStatus::Rejected,
+        EmailStatus::Failed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailStatus::Sent => "sent",
+            EmailStatus::Rejected => "rejected",
+            EmailStatus::Failed => "failed",
+        }
+    }
+}
+
+/// An email handed to the provider, about to be recorded.
+pub struct LoggedEmail {
+    kind: EmailKind,
+    recipient: String,
+    subject: String,
+    newsletter_issue_id: Option<Uuid>,
+    provider_message_id: Option<String>,
+    status: EmailStatus,
+    error: Option<String>,
+}
+
+impl LoggedEmail {
+    /// `outcome` carries the id the provider gave the email, if it did.
+    pub fn new(email: &OutgoingEmail, outcome: &Result<Option<String>, SendEmailError>) -> Self {
+        let (status, provider_message_id, error) = match outcome {
+            Ok(message_id) => (EmailStatus::Sent, message_id.clone(), None),
+            Err(e @ (SendEmailError::InactiveRecipient(_) | SendEmailError::Rejected(_))) => {
+                (EmailStatus::Rejected, None, Some(e.to_string()))
+            }
+            Err(e) => (EmailStatus::Failed, None, Some(e.to_string())),
+        };
+
+        Self {
+            kind: email.kind,
+            recipient: email.recipient.as_ref().to_string(),
+            subject: email.subject.to_string(),
+            newsletter_issue_id: email.newsletter_issue_id,
+            provider_message_id,
+            status,
+            error,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Record sent emails", skip_all, fields(emails = emails.len()))]
+pub async fn record_emails(pool: &PgPool, emails: &[LoggedEmail]) -> Result<(), sqlx::Error> {
+    if emails.is_empty() {
+        return Ok(());
+    }
+
+    let mut kinds = Vec::with_capacity(emails.len());
+    let mut recipients = Vec::with_capacity(emails.len());
+    let mut subjects = Vec::with_capacity(emails.len());
+    let mut newsletter_issue_ids = Vec::with_capacity(emails.len());
+    let mut provider_message_ids = Vec::with_capacity(emails.len());
+    let mut statuses = Vec::with_capacity(emails.len());
+    let mut errors = Vec::with_capacity(emails.len());
+    for email in emails {
+        kinds.push(email.kind.as_str().to_string());
+        recipients.push(email.recipient.clone());
+        subjects.push(email.subject.clone());
+        newsletter_issue_ids.push(email.newsletter_issue_id);
+        provider_message_ids.push(email.provider_message_id.clone());
+        statuses.push(email.status.as_str().to_string());
+        errors.push(email.error.clone());
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO emails_sent (
+            kind, recipient, subject, newsletter_issue_id, provider_message_id, status, error,
+            sent_at
+        )
+        SELECT *, now()
+        FROM UNNEST(
+            $1::text[], $2::text[], $3::text[], $4::uuid[], $5::text[], $6::text[], $7::text[]
+        )
+        "#,
+        &kinds,
+        &recipients,
+        &subjects,
+        &newsletter_issue_ids as _,
+        &provider_message_ids as _,
+        &statuses,
+        &errors as _,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// An email of the log, as listed in the admin UI.
+#[derive(Debug, serde::Serialize)]
+pub struct SentEmail {
+    pub kind: String,
+    pub recipient: String,
+    pub subject: String,
+    pub provider_message_id: Option<String>,
+    pub status: String,
+    pub error: Option<String>,
+    pub sent_at: DateTime<Utc>,
+}
+
+/// Narrows down the emails of the log. Missing or blank values match any
+/// email.
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct EmailLogFilter {
+    /// Matched regardless of case.
+    pub recipient: Option<String>,
+    pub kind: Option<String>,
+    pub status: Option<String>,
+}
+
+fn non_blank(value: &Option<String>) -> Option<&str> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// Latest emails matching the filter, the most recent first.
+#[tracing::instrument(name = "List sent emails", skip(pool))]
+pub async fn list_sent_emails(
+    pool: &PgPool,
+    filter: &EmailLogFilter,
+    limit: i64,
+) -> Result<Vec<SentEmail>, sqlx::Error> {
+    sqlx::query_as!(
+        SentEmail,
+        r#"
+        SELECT kind, recipient, subject, provider_message_id, status, error, sent_at
+        FROM emails_sent
+        WHERE ($1::TEXT IS NULL OR lower(recipient) = lower($1))
+            AND ($2::TEXT IS NULL OR kind = $2)
+            AND ($3::TEXT IS NULL OR status = $3)
+        ORDER BY email_id DESC
+        LIMIT $4
+        "#,
+        non_blank(&filter.recipient),
+        non_blank(&filter.kind),
+        non_blank(&filter.status),
+        limit,
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,277 @@
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    domain::SubscriptionStatus,
+    subscriber_events::{record_events, SubscriberEventKind},
+};
+
+/// Domain of the addresses erased subscribers are left with. `.invalid` is
+/// reserved, nothing can ever be delivered there.
+const ERASED_EMAIL_DOMAIN: &str = "erased.invalid";
+
+/// The address replacing the one of an erased subscriber. It stays unique,
+/// so the history of the subscriber is still counted once.
+pub fn erased_email(subscriber_id: Uuid) -> String {
+    format!("{}@{}", subscriber_id, ERASED_EMAIL_DOMAIN)
+}
+
+/// Replaces the email and name of a subscriber with placeholders, here and in
+/// the history of the issues of its list, and drops whatever could still
+/// reach or identify them. Gives back `false` if there is no such subscriber
+/// or it was already erased.
+#[tracing::instrument(name = "Erase subscriber", skip(transaction))]
+pub async fn erase_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let Some(subscriber) = sqlx::query!(
+        r#"
+        SELECT email, list_id
+        FROM subscriptions
+        WHERE id = $1 AND erased_at IS NULL
+        FOR UPDATE
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    else {
+        return Ok(false);
+    };
+    let erased_email = erased_email(subscriber_id);
+
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET
+            email = $2,
+            name = '',
+            subscriber_attributes = '{}',
+            status = $3,
+            erased_at = now()
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        erased_email,
+        SubscriptionStatus::Unsubscribed as SubscriptionStatus,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM subscription_tokens
+        WHERE subscriber_id = $1
+        "#,
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM preferences_tokens
+        WHERE subscriber_id = $1
+        "#,
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM subscriber_email_changes
+        WHERE subscriber_id = $1
+        "#,
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    // Only email changes carry addresses in their details.
+    sqlx::query!(
+        r#"
+        UPDATE subscriber_events
+        SET details = '{}'
+        WHERE subscriber_id = $1 AND event_type = 'email_changed'
+        "#,
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    // Pending and dead deliveries would never be sent anyway.
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE subscriber_email = $1 AND newsletter_issue_id IN (
+            SELECT newsletter_issue_id FROM newsletter_issues WHERE list_id = $2
+        )
+        "#,
+        subscriber.email,
+        subscriber.list_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_dead_letters
+        WHERE subscriber_email = $1 AND newsletter_issue_id IN (
+            SELECT newsletter_issue_id FROM newsletter_issues WHERE list_id = $2
+        )
+        "#,
+        subscriber.email,
+        subscriber.list_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    // Only needed should a worker die while sending, at worst the issue is
+    // sent again to the recipients of its last batch.
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_dispatch_progress
+        WHERE last_subscriber_email = $1 AND newsletter_issue_id IN (
+            SELECT newsletter_issue_id FROM newsletter_issues WHERE list_id = $2
+        )
+        "#,
+        subscriber.email,
+        subscriber.list_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    // The same address may still be subscribed to other lists, whose history
+    // is left alone.
+    sqlx::query!(
+        r#"
+        UPDATE issue_deliveries
+        SET subscriber_email = $3
+        WHERE subscriber_email = $1 AND newsletter_issue_id IN (
+            SELECT newsletter_issue_id FROM newsletter_issues WHERE list_id = $2
+        )
+        "#,
+        subscriber.email,
+        subscriber.list_id,
+        erased_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE issue_suppressed_recipients
+        SET subscriber_email = $3
+        WHERE subscriber_email = $1 AND newsletter_issue_id IN (
+            SELECT newsletter_issue_id FROM newsletter_issues WHERE list_id = $2
+        )
+        "#,
+        subscriber.email,
+        subscriber.list_id,
+        erased_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE issue_render_failures
+        SET subscriber_email = $3
+        WHERE subscriber_email = $1 AND newsletter_issue_id IN (
+            SELECT newsletter_issue_id FROM newsletter_issues WHERE list_id = $2
+        )
+        "#,
+        subscriber.email,
+        subscriber.list_id,
+        erased_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE issue_subject_variants
+        SET subscriber_email = $3
+        WHERE subscriber_email = $1 AND newsletter_issue_id IN (
+            SELECT newsletter_issue_id FROM newsletter_issues WHERE list_id = $2
+        )
+        "#,
+        subscriber.email,
+        subscriber.list_id,
+        erased_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE email_events
+        SET subscriber_email = $3
+        WHERE subscriber_email = $1 AND newsletter_issue_id IN (
+            SELECT newsletter_issue_id FROM newsletter_issues WHERE list_id = $2
+        )
+        "#,
+        subscriber.email,
+        subscriber.list_id,
+        erased_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    // Bounces of emails that weren't issues belong to no list, they go
+    // along with the last subscription of the address.
+    sqlx::query!(
+        r#"
+        UPDATE email_bounces
+        SET subscriber_email = $3
+        WHERE subscriber_email = $1 AND (
+            newsletter_issue_id IN (
+                SELECT newsletter_issue_id FROM newsletter_issues WHERE list_id = $2
+            )
+            OR (
+                newsletter_issue_id IS NULL
+                AND NOT EXISTS (SELECT 1 FROM subscriptions WHERE email = $1)
+            )
+        )
+        "#,
+        subscriber.email,
+        subscriber.list_id,
+        erased_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    // Same for the log of the emails sent.
+    sqlx::query!(
+        r#"
+        UPDATE emails_sent
+        SET recipient = $3
+        WHERE recipient = $1 AND (
+            newsletter_issue_id IN (
+                SELECT newsletter_issue_id FROM newsletter_issues WHERE list_id = $2
+            )
+            OR (
+                newsletter_issue_id IS NULL
+                AND NOT EXISTS (SELECT 1 FROM subscriptions WHERE email = $1)
+            )
+        )
+        "#,
+        subscriber.email,
+        subscriber.list_id,
+        erased_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    record_events(
+        transaction,
+        &[subscriber_id],
+        SubscriberEventKind::Erased,
+        serde_json::json!({}),
+    )
+    .await?;
+
+    Ok(true)
+}
@@ -0,0 +1,128 @@
+use actix_web::{
+    dev::ServiceResponse,
+    http::{
+        header::{ContentType, CONTENT_LENGTH, CONTENT_TYPE},
+        StatusCode,
+    },
+    middleware::{ErrorHandlerResponse, ErrorHandlers},
+    HttpMessage, HttpResponse,
+};
+
+use crate::{
+    api_error::problem_response,
+    payload_limits::PayloadTooLargeError,
+    request_id::RequestId,
+    template::{render_error_page, ErrorPage},
+};
+
+/// Errors answered with a page of ours, or problem details under `/api`,
+/// when nothing more specific was rendered for them.
+const HANDLED: &[(StatusCode, &str, &str)] = &[
+    (
+        StatusCode::NOT_FOUND,
+        "not-found",
+        "The page you are looking for doesn't exist.",
+    ),
+    (
+        StatusCode::METHOD_NOT_ALLOWED,
+        "method-not-allowed",
+        "This page can't be used that way.",
+    ),
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "payload-too-large",
+        "What was sent is larger than allowed.",
+    ),
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "internal-error",
+        "Something went wrong on our side. Please try again later.",
+    ),
+];
+
+/// Replaces the bare bodies actix gives errors, e.g. for unknown routes or
+/// the causes of internal errors, which must not reach clients.
+pub fn error_handlers<B: 'static>() -> ErrorHandlers<B> {
+    HANDLED
+        .iter()
+        .fold(ErrorHandlers::new(), |handlers, (status, _, _)| {
+            handlers.handler(*status, render_error)
+        })
+}
+
+/// Whether the response was rendered by actix rather than by us: either
+/// without a body or with the plain text of an error.
+fn is_bare<B>(response: &HttpResponse<B>) -> bool {
+    match response.headers().get(CONTENT_TYPE) {
+        None => true,
+        Some(content_type) => content_type
+            .to_str()
+            .is_ok_and(|value| value.starts_with("text/plain")),
+    }
+}
+
+fn render_error<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    if !is_bare(res.response()) {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    }
+
+    let status = res.status();
+    let Some((_, problem_type, message)) = HANDLED.iter().find(|(s, _, _)| *s == status) else {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    };
+    let request_id = res
+        .request()
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.to_string());
+    // Limits of the routes are worth telling, unlike other causes.
+    let detail = res
+        .response()
+        .error()
+        .and_then(|e| e.as_error::<PayloadTooLargeError>())
+        .map(|e| e.to_string());
+    let (request, bare) = res.into_parts();
+
+    let mut response = if request.path().starts_with("/api/") {
+        problem_response(status, problem_type, detail, request_id)
+    } else {
+        let page = ErrorPage {
+            status: status.as_u16(),
+            title: status.canonical_reason().unwrap_or("Error"),
+            message: detail.as_deref().unwrap_or(message),
+            request_id,
+        };
+        match render_error_page(&page) {
+            Ok(body) => HttpResponse::build(status)
+                .content_type(ContentType::html())
+                .body(body),
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to render an error page",
+                );
+                HttpResponse::new(status)
+            }
+        }
+    };
+
+    // Headers such as Cache-Control still apply to the new body.
+    let kept: Vec<_> = bare
+        .headers()
+        .iter()
+        .filter(|(name, _)| {
+            **name != CONTENT_TYPE
+                && **name != CONTENT_LENGTH
+                && !response.headers().contains_key(*name)
+        })
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    for (name, value) in kept {
+        response.headers_mut().append(name, value);
+    }
+
+    Ok(ErrorHandlerResponse::Response(
+        ServiceResponse::new(request, response).map_into_right_body(),
+    ))
+}
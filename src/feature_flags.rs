@@ -0,0 +1,109 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::ErrorNotFound,
+    middleware::Next,
+    web,
+};
+use anyhow::Context;
+
+use crate::util::e500;
+
+/// Optional subsystems that operators can enable incrementally.
+#[derive(Clone, Copy, Debug)]
+pub enum Feature {
+    Tracking,
+    PublicArchive,
+    Api,
+    Webhooks,
+    PublicStats,
+    Referrals,
+    MxValidation,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct FeatureFlags {
+    pub tracking: bool,
+    pub public_archive: bool,
+    pub api: bool,
+    pub webhooks: bool,
+    pub public_stats: bool,
+    pub referrals: bool,
+    pub mx_validation: bool,
+}
+
+impl FeatureFlags {
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::Tracking => self.tracking,
+            Feature::PublicArchive => self.public_archive,
+            Feature::Api => self.api,
+            Feature::Webhooks => self.webhooks,
+            Feature::PublicStats => self.public_stats,
+            Feature::Referrals => self.referrals,
+            Feature::MxValidation => self.mx_validation,
+        }
+    }
+}
+
+/// Makes the routes of a disabled feature behave as if they didn't exist.
+pub fn reject_disabled_feature(
+    req: &ServiceRequest,
+    feature: Feature,
+) -> Result<(), actix_web::Error> {
+    let features = req
+        .app_data::<web::Data<FeatureFlags>>()
+        .context("Feature flags are not registered in the application")
+        .map_err(e500)?;
+
+    if !features.is_enabled(feature) {
+        return Err(ErrorNotFound(format!("{:?} is disabled", feature)));
+    }
+
+    Ok(())
+}
+
+pub async fn reject_disabled_api(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    reject_disabled_feature(&req, Feature::Api)?;
+
+    next.call(req).await
+}
+
+pub async fn reject_disabled_public_archive(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    reject_disabled_feature(&req, Feature::PublicArchive)?;
+
+    next.call(req).await
+}
+
+pub async fn reject_disabled_webhooks(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    reject_disabled_feature(&req, Feature::Webhooks)?;
+
+    next.call(req).await
+}
+
+pub async fn reject_disabled_public_stats(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    reject_disabled_feature(&req, Feature::PublicStats)?;
+
+    next.call(req).await
+}
+
+pub async fn reject_disabled_tracking(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    reject_disabled_feature(&req, Feature::Tracking)?;
+
+    next.call(req).await
+}
@@ -0,0 +1,189 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    issue_export::{anonymous_content, PublishedIssue},
+    newsletter_list::DEFAULT_LIST_ID,
+};
+
+/// Issues listed in the feed, the most recent ones.
+const FEED_LENGTH: i64 = 20;
+
+/// Latest issues of the default list. Issues restricted to a tag were only
+/// meant for some subscribers, so they are left out.
+pub struct Feed {
+    pub title: String,
+    pub issues: Vec<PublishedIssue>,
+}
+
+#[tracing::instrument(name = "Get feed", skip(pool))]
+pub async fn get_feed(pool: &PgPool) -> Result<Feed, sqlx::Error> {
+    let title = sqlx::query_scalar!(
+        r#"
+        SELECT name
+        FROM newsletter_lists
+        WHERE list_id = $1
+        "#,
+        DEFAULT_LIST_ID,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let issues = sqlx::query_as!(
+        PublishedIssue,
+        r#"
+        SELECT i.newsletter_issue_id, i.title, i.text_content, i.html_content, i.published_at,
+            COALESCE(u.display_name, u.username, i.author) AS author
+        FROM newsletter_issues i
+        LEFT JOIN users u ON u.user_id = i.published_by
+        WHERE i.list_id = $1 AND i.tag IS NULL
+        ORDER BY i.published_at DESC
+        LIMIT $2
+        "#,
+        DEFAULT_LIST_ID,
+        FEED_LENGTH,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Feed { title, issues })
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn escape(value: &str) -> String {
+    htmlescape::encode_minimal(value)
+}
+
+impl Feed {
+    /// The feed as an Atom document, served from `<base_url>/feed.xml`. An
+    /// empty feed was last updated when it's generated.
+    pub fn to_atom(&self, base_url: &str) -> String {
+        let updated = self
+            .issues
+            .first()
+            .map(|issue| issue.published_at)
+            .unwrap_or_else(Utc::now);
+
+        let mut atom = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <id>{base_url}/feed.xml</id>
+  <title>{title}</title>
+  <updated>{updated}</updated>
+  <link rel="self" href="{base_url}/feed.xml"/>
+  <link rel="alternate" href="{base_url}/"/>
+"#,
+            base_url = escape(base_url),
+            title = escape(&self.title),
+            updated = timestamp(updated),
+        );
+        for issue in &self.issues {
+            let (html, _) = anonymous_content(issue);
+            let author = issue
+                .author
+                .as_deref()
+                .map(|author| format!("\n    <author><name>{}</name></author>", escape(author)))
+                .unwrap_or_default();
+            atom.push_str(&format!(
+                r#"  <entry>
+    <id>urn:uuid:{id}</id>
+    <title>{title}</title>{author}
+    <published>{published_at}</published>
+    <updated>{published_at}</updated>
+    <content type="html">{content}</content>
+  </entry>
+"#,
+                id = issue.newsletter_issue_id,
+                title = escape(&issue.title),
+                published_at = timestamp(issue.published_at),
+                content = escape(&html),
+            ));
+        }
+        atom.push_str("</feed>\n");
+
+        atom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
+
+    use crate::issue_export::PublishedIssue;
+
+    use super::Feed;
+
+    fn issue(title: &str, html_content: &str) -> PublishedIssue {
+        PublishedIssue {
+            newsletter_issue_id: Uuid::nil(),
+            title: title.into(),
+            text_content: String::new(),
+            html_content: html_content.into(),
+            published_at: Utc.with_ymd_and_hms(2024, 10, 28, 9, 30, 0).unwrap(),
+            author: None,
+        }
+    }
+
+    #[test]
+    fn the_feed_is_updated_when_its_latest_issue_was_published() {
+        let feed = Feed {
+            title: "Newsletter".into(),
+            issues: vec![issue("Issue #1", "<p>Hello</p>")],
+        };
+
+        let atom = feed.to_atom("https://example.com");
+
+        assert!(atom.contains("<updated>2024-10-28T09:30:00Z</updated>\n  <link"));
+        assert!(atom.contains(r#"<link rel="self" href="https://example.com/feed.xml"/>"#));
+        assert!(atom.contains("<id>urn:uuid:00000000-0000-0000-0000-000000000000</id>"));
+    }
+
+    #[test]
+    fn titles_and_content_are_escaped() {
+        let feed = Feed {
+            title: "News & views".into(),
+            issues: vec![issue("<Issue>", r#"<a href="https://example.com">Hi</a>"#)],
+        };
+
+        let atom = feed.to_atom("https://example.com");
+
+        assert!(atom.contains("<title>News &amp; views</title>"));
+        assert!(atom.contains("<title>&lt;Issue&gt;</title>"));
+        assert!(atom.contains(
+            "<content type=\"html\">&lt;a href=&quot;https://example.com&quot;&gt;Hi&lt;/a&gt;</content>"
+        ));
+    }
+
+    #[test]
+    fn entries_name_their_author() {
+        let mut authored = issue("Issue #2", "<p>Hello</p>");
+        authored.author = Some("Ursula K. Le Guin".into());
+        let feed = Feed {
+            title: "Newsletter".into(),
+            issues: vec![authored, issue("Issue #1", "<p>Hello</p>")],
+        };
+
+        let atom = feed.to_atom("https://example.com");
+
+        assert!(atom.contains(
+            "<title>Issue #2</title>\n    <author><name>Ursula K. Le Guin</name></author>\n"
+        ));
+        assert_eq!(atom.matches("<author>").count(), 1);
+    }
+
+    #[test]
+    fn placeholders_are_rendered_for_no_one_in_particular() {
+        let feed = Feed {
+            title: "Newsletter".into(),
+            issues: vec![issue("Issue #1", "<p>Hi {{ subscriber.name }}!</p>")],
+        };
+
+        let atom = feed.to_atom("https://example.com");
+
+        assert!(atom.contains("&lt;p&gt;Hi !&lt;/p&gt;"));
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::Context;
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    configuration::Settings,
+    domain::Email,
+    email_client::EmailClient,
+    email_log::EmailKind,
+    leader_election::{LeaderElection, SingletonJob},
+    startup::get_connection_pool,
+    template::render_weekly_report,
+};
+
+/// How a list did over the week, against its goal if it has one.
+#[derive(Debug, serde::Serialize)]
+pub struct ListGrowth {
+    pub list_id: Uuid,
+    pub name: String,
+    /// Subscriptions confirmed during the week.
+    pub new_subscribers: i64,
+    /// Subscribers lost to bounces, complaints, suppressions and erasures.
+    pub churned: i64,
+    pub goal: Option<i32>,
+    /// Share of the goal reached by the new subscribers.
+    pub goal_percent: Option<i64>,
+}
+
+/// The issue published during the week with the best open rate.
+#[derive(Debug, serde::Serialize)]
+pub struct BestIssue {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub delivered: i64,
+    pub unique_opens: i64,
+    pub open_percent: i64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct GrowthReport {
+    pub week_start: DateTime<Utc>,
+    pub week_end: DateTime<Utc>,
+    pub lists: Vec<ListGrowth>,
+    pub best_issue: Option<BestIssue>,
+}
+
+/// Monday, midnight UTC, of the week `now` falls in.
+pub fn week_start(now: DateTime<Utc>) -> DateTime<Utc> {
+    let monday = now.date_naive() - Duration::days(now.weekday().num_days_from_monday().into());
+
+    Utc.from_utc_datetime(&monday.and_hms_opt(0, 0, 0).unwrap())
+}
+
+fn percent(count: i64, total: i64) -> Option<i64> {
+    (total > 0).then(|| count * 100 / total)
+}
+
+/// Gathers the figures of the week starting at `week_start`.
+#[tracing::instrument(name = "Compile growth report", skip(pool))]
+pub async fn compile_report(
+    pool: &PgPool,
+    week_start: DateTime<Utc>,
+) -> Result<GrowthReport, sqlx::Error> {
+    let week_end = week_start + Duration::weeks(1);
+
+    let lists = sqlx::query!(
+        r#"
+        SELECT
+            l.list_id,
+            l.name,
+            g.weekly_new_subscribers AS "goal?",
+            (
+                SELECT count(DISTINCT e.subscriber_id)
+                FROM subscriber_events e
+                JOIN subscriptions s ON s.id = e.subscriber_id
+                WHERE s.list_id = l.list_id
+                  AND e.event_type = 'confirmed'
+                  AND e.occurred_at >= $1 AND e.occurred_at < $2
+            ) AS "new_subscribers!",
+            (
+                SELECT count(DISTINCT e.subscriber_id)
+                FROM subscriber_events e
+                JOIN subscriptions s ON s.id = e.subscriber_id
+                WHERE s.list_id = l.list_id
+                  AND e.event_type IN ('suppressed', 'bounced', 'complained', 'erased')
+                  AND e.occurred_at >= $1 AND e.occurred_at < $2
+            ) AS "churned!"
+        FROM newsletter_lists l
+        LEFT JOIN growth_goals g ON g.list_id = l.list_id
+        ORDER BY l.name
+        "#,
+        week_start,
+        week_end,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| ListGrowth {
+        list_id: r.list_id,
+        name: r.name,
+        new_subscribers: r.new_subscribers,
+        churned: r.churned,
+        goal: r.goal,
+        goal_percent: r
+            .goal
+            .and_then(|goal| percent(r.new_subscribers, goal.into())),
+    })
+    .collect();
+
+    let best_issue = sqlx::query!(
+        r#"
+        SELECT
+            i.newsletter_issue_id,
+            i.title,
+            (
+                SELECT count(*) FROM issue_deliveries d
+                WHERE d.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "delivered!",
+            (
+                SELECT count(DISTINCT e.subscriber_email) FROM email_events e
+                WHERE e.newsletter_issue_id = i.newsletter_issue_id AND e.event_type = 'open'
+            ) AS "unique_opens!"
+        FROM newsletter_issues i
+        WHERE i.published_at >= $1 AND i.published_at < $2
+        "#,
+        week_start,
+        week_end,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .filter_map(|r| {
+        Some(BestIssue {
+            newsletter_issue_id: r.newsletter_issue_id,
+            title: r.title,
+            delivered: r.delivered,
+            unique_opens: r.unique_opens,
+            open_percent: percent(r.unique_opens, r.delivered)?,
+        })
+    })
+    .max_by_key(|issue| (issue.open_percent, issue.unique_opens));
+
+    Ok(GrowthReport {
+        week_start,
+        week_end,
+        lists,
+        best_issue,
+    })
+}
+
+/// Sends the report of the week before the one `now` falls in to every admin
+/// who opted in, unless it was already sent. Gives back whether it was.
+///
+/// The week is only marked as reported once every email is sent, so that a
+/// failure is retried on the next run, possibly sending the report twice to
+/// some admins.
+#[tracing::instrument(name = "Send weekly report", skip(pool, email_client))]
+pub async fn send_weekly_report(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    now: DateTime<Utc>,
+) -> Result<bool, anyhow::Error> {
+    let report_week = week_start(now) - Duration::weeks(1);
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+    let claimed = sqlx::query!(
+        r#"
+        INSERT INTO weekly_reports (week_start, sent_at)
+        VALUES ($1, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        report_week,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to claim the weekly report")?
+    .rows_affected()
+        > 0;
+    if !claimed {
+        return Ok(false);
+    }
+
+    let recipients = sqlx::query!(
+        r#"
+        SELECT report_email AS "report_email!"
+        FROM users
+        WHERE role = 'admin' AND report_email IS NOT NULL
+        "#
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to retrieve the recipients of the weekly report")?;
+
+    let report = compile_report(pool, report_week)
+        .await
+        .context("Failed to compile the weekly report")?;
+    let template = render_weekly_report(&report).context("Failed to render the weekly report")?;
+    let subject = format!("Weekly report, {}", report_week.format("%Y-%m-%d"));
+
+    for recipient in recipients {
+        let email = match Email::parse(recipient.report_email) {
+            Ok(email) => email,
+            Err(e) => {
+                tracing::warn!(error.message = %e, "Skipping an invalid report email");
+                continue;
+            }
+        };
+        email_client
+            .send_email(
+                EmailKind::WeeklyReport,
+                &email,
+                &subject,
+                &template.html,
+                &template.text,
+                &[],
+                None,
+            )
+            .await
+            .context("Failed to send the weekly report")?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to mark the weekly report as sent")?;
+
+    Ok(true)
+}
+
+pub async fn run_worker_until_stopped(
+    configuration: Settings,
+    email_client: EmailClient,
+) -> Result<(), anyhow::Error> {
+    let pool = get_connection_pool(&configuration.database);
+    let settings = configuration.weekly_report;
+    let mut election = LeaderElection::new(
+        pool.clone(),
+        SingletonJob::WeeklyReport,
+        &configuration.application.instance_id,
+    );
+
+    loop {
+        if settings.enabled && election.is_leader().await {
+            if let Err(e) = send_weekly_report(&pool, &email_client, Utc::now()).await {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to send the weekly report",
+                );
+            }
+        }
+        tokio::time::sleep(settings.check_interval()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::week_start;
+
+    #[test]
+    fn weeks_start_on_monday_at_midnight() {
+        let sunday_night = Utc.with_ymd_and_hms(2024, 10, 20, 23, 59, 59).unwrap();
+        let monday = Utc.with_ymd_and_hms(2024, 10, 14, 0, 0, 0).unwrap();
+
+        assert_eq!(week_start(sunday_night), monday);
+        assert_eq!(week_start(monday), monday);
+    }
+}
@@ -0,0 +1,981 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    configuration::{DeliveryQueueSettings, Settings},
+    delivery_queue::get_issue_attachments,
+    domain::{Email, SubscriberEmail, SubscriptionStatus},
+    email_client::{Attachment, EmailClient, OutgoingEmail, PostmarkError, SendEmailError},
+    email_log::EmailKind,
+    maintenance_mode::MaintenanceMode,
+    referrals::ReferralLinks,
+    startup::get_connection_pool,
+    subscriber_events::{record_events, SubscriberEventKind},
+    template::{render_issue, IssueRecipient},
+    tracking::EmailTracker,
+};
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+struct Task {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i16,
+    lease_id: Uuid,
+    leased_until: DateTime<Utc>,
+    /// Lease of the worker that died attempting the task, if any.
+    previous_lease: Option<Uuid>,
+}
+
+struct NewsletterIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+    author: Option<String>,
+    sender_name: Option<String>,
+    sender_email: Option<String>,
+    reply_to: Option<String>,
+}
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+/// What came out of an attempt to deliver an issue to a subscriber.
+enum DeliveryOutcome {
+    Delivered,
+    Suppressed(PostmarkError),
+    Failed(anyhow::Error),
+    /// The issue can't be rendered for this recipient. Other recipients
+    /// of the batch are still attempted.
+    RenderFailed(tera::Error),
+    /// The stored address is invalid, there is nothing to retry.
+    Skipped,
+    /// The subscriber was already delivered the issue, e.g. before a retry
+    /// or under the address they had then.
+    AlreadyDelivered,
+}
+
+/// An issue rendered for a subscriber, waiting to be sent with the rest of
+/// the batch.
+struct ReadyEmail {
+    email: SubscriberEmail,
+    subject: String,
+    html_content: String,
+    text_content: String,
+    attachments: Vec<Attachment>,
+    author: Option<String>,
+    sender_name: Option<String>,
+    sender_email: Option<Email>,
+    reply_to: Option<Email>,
+}
+
+enum Delivery {
+    Ready(ReadyEmail),
+    /// Nothing to send, the task already has its outcome.
+    Done(DeliveryOutcome),
+}
+
+/// Leases up to a batch of due tasks to the caller. Tasks whose lease expired,
+/// because the worker attempting them died, are leased again.
+#[tracing::instrument(skip_all)]
+async fn lease_tasks(
+    pool: &PgPool,
+    settings: &DeliveryQueueSettings,
+) -> Result<Vec<Task>, anyhow::Error> {
+    let mut tasks = sqlx::query_as!(
+        Task,
+        r#"
+        UPDATE issue_delivery_queue q
+        SET lease_id = $1, leased_until = $2
+        FROM (
+            SELECT newsletter_issue_id, subscriber_email, lease_id AS previous_lease
+            FROM issue_delivery_queue
+            WHERE execute_after <= now() AND (leased_until IS NULL OR leased_until <= now())
+            ORDER BY execute_after
+            FOR UPDATE
+            SKIP LOCKED
+            LIMIT $3
+        ) due
+        WHERE q.newsletter_issue_id = due.newsletter_issue_id
+            AND q.subscriber_email = due.subscriber_email
+        RETURNING q.newsletter_issue_id, q.subscriber_email, q.n_retries,
+            q.lease_id AS "lease_id!", q.leased_until AS "leased_until!", due.previous_lease
+        "#,
+        Uuid::new_v4(),
+        Utc::now() + settings.lease(),
+        settings.batch_size,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // Sent in this order, for the progress of each issue to be saved as the
+    // last recipient it got to.
+    tasks.sort_by(|a, b| {
+        (a.newsletter_issue_id, &a.subscriber_email)
+            .cmp(&(b.newsletter_issue_id, &b.subscriber_email))
+    });
+
+    Ok(tasks)
+}
+
+/// How far the batch of a worker got with an issue, see
+/// [`save_dispatch_progress`].
+struct DispatchProgress {
+    newsletter_issue_id: Uuid,
+    lease_id: Uuid,
+    last_subscriber_email: String,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_dispatch_progress(
+    pool: &PgPool,
+    tasks: &[Task],
+) -> Result<Vec<DispatchProgress>, anyhow::Error> {
+    let mut lease_ids: Vec<Uuid> = tasks
+        .iter()
+        .filter_map(|task| task.previous_lease)
+        .collect();
+    if lease_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    lease_ids.sort();
+    lease_ids.dedup();
+
+    let progress = sqlx::query_as!(
+        DispatchProgress,
+        r#"
+        SELECT newsletter_issue_id, lease_id, last_subscriber_email
+        FROM issue_dispatch_progress
+        WHERE lease_id = ANY($1)
+        "#,
+        &lease_ids,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(progress)
+}
+
+/// Whether the worker that died attempting the task had already handed its
+/// email to the provider.
+fn was_sent_before(task: &Task, progress: &[DispatchProgress]) -> bool {
+    let Some(previous_lease) = task.previous_lease else {
+        return false;
+    };
+
+    progress.iter().any(|p| {
+        p.newsletter_issue_id == task.newsletter_issue_id
+            && p.lease_id == previous_lease
+            && task.subscriber_email <= p.last_subscriber_email
+    })
+}
+
+/// Drops the progress of the batch, whose outcomes are being saved, along
+/// with the progress of any batch whose lease is long over and whose tasks
+/// have all been taken over or done since.
+#[tracing::instrument(skip_all)]
+async fn clear_dispatch_progress(
+    transaction: &mut PgTransaction,
+    tasks: &[(Task, Option<DeliveryOutcome>)],
+    settings: &DeliveryQueueSettings,
+) -> Result<(), anyhow::Error> {
+    let Some((task, _)) = tasks.first() else {
+        return Ok(());
+    };
+
+    // A worker taking over a batch reads its progress right after leasing
+    // the tasks, long before a whole lease went by.
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_dispatch_progress p
+        WHERE p.lease_id = $1 OR (
+            p.leased_until < $2
+            AND NOT EXISTS (
+                SELECT 1 FROM issue_delivery_queue q WHERE q.lease_id = p.lease_id
+            )
+        )
+        "#,
+        task.lease_id,
+        Utc::now() - settings.lease(),
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Saves how far the batch got with the issues of the tasks. Done as soon as
+/// the provider accepted the emails, out of the checkpoint of the batch, so
+/// that it survives the worker dying before it.
+#[tracing::instrument(skip_all)]
+async fn save_dispatch_progress(pool: &PgPool, tasks: &[&Task]) -> Result<(), anyhow::Error> {
+    for task in tasks {
+        sqlx::query!(
+            r#"
+            INSERT INTO issue_dispatch_progress (
+                newsletter_issue_id, lease_id, last_subscriber_email, leased_until, updated_at
+            )
+            VALUES ($1, $2, $3, $4, now())
+            ON CONFLICT (newsletter_issue_id, lease_id) DO UPDATE
+            SET last_subscriber_email = EXCLUDED.last_subscriber_email,
+                updated_at = EXCLUDED.updated_at
+            "#,
+            task.newsletter_issue_id,
+            task.lease_id,
+            task.subscriber_email,
+            task.leased_until,
+        )
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_task(transaction: &mut PgTransaction, task: &Task) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1 AND subscriber_email = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Hands a task that was not attempted back to the queue.
+#[tracing::instrument(skip_all)]
+async fn release_task(transaction: &mut PgTransaction, task: &Task) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET lease_id = NULL, leased_until = NULL
+        WHERE newsletter_issue_id = $1 AND subscriber_email = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Schedules another attempt with an exponential backoff or, once the
+/// retries are exhausted, moves the task to the dead letters.
+#[tracing::instrument(skip_all)]
+async fn handle_failed_task(
+    transaction: &mut PgTransaction,
+    task: &Task,
+    error: &anyhow::Error,
+    settings: &DeliveryQueueSettings,
+) -> Result<(), anyhow::Error> {
+    let n_retries = task.n_retries + 1;
+
+    if n_retries >= settings.max_retries {
+        sqlx::query!(
+            r#"
+            INSERT INTO issue_delivery_dead_letters (
+                newsletter_issue_id, subscriber_email, n_retries, last_error, failed_at
+            )
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+            SET n_retries = EXCLUDED.n_retries,
+                last_error = EXCLUDED.last_error,
+                failed_at = EXCLUDED.failed_at
+            "#,
+            task.newsletter_issue_id,
+            task.subscriber_email,
+            n_retries,
+            format!("{:#}", error),
+            Utc::now(),
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        return delete_task(transaction, task).await;
+    }
+
+    let backoff = settings.retry_backoff() * 2i32.saturating_pow(task.n_retries as u32);
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET n_retries = $1, execute_after = $2, lease_id = NULL, leased_until = NULL
+        WHERE newsletter_issue_id = $3 AND subscriber_email = $4
+        "#,
+        n_retries,
+        Utc::now() + backoff,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn record_delivery(
+    transaction: &mut PgTransaction,
+    task: &Task,
+) -> Result<(), anyhow::Error> {
+    let subscriber_id = sqlx::query_scalar!(
+        r#"
+        SELECT s.id
+        FROM subscriptions s
+        JOIN newsletter_issues i ON i.list_id = s.list_id
+        WHERE s.email = $1 AND i.newsletter_issue_id = $2
+        "#,
+        task.subscriber_email,
+        task.newsletter_issue_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+
+    // Only the first delivery to the address or to the subscriber counts.
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO issue_deliveries (
+            newsletter_issue_id, subscriber_email, subscriber_id, delivered_at
+        )
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected();
+
+    if inserted == 0 {
+        return Ok(());
+    }
+
+    if let Some(subscriber_id) = subscriber_id {
+        record_events(
+            transaction,
+            &[subscriber_id],
+            SubscriberEventKind::Delivered,
+            serde_json::json!({ "newsletter_issue_id": task.newsletter_issue_id }),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Stops sending issues to a subscriber the email provider refuses to deliver
+/// to, keeping track of the issue it happened with.
+#[tracing::instrument(skip_all)]
+async fn suppress_recipient(
+    transaction: &mut PgTransaction,
+    task: &Task,
+    error: &PostmarkError,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_suppressed_recipients (
+            newsletter_issue_id, subscriber_email, error_code, message, suppressed_at
+        )
+        VALUES ($1, $2, $3, $4, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        error.error_code,
+        error.message,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    // The provider refuses the address itself, whatever the list.
+    let subscriber_ids: Vec<Uuid> = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = $1
+        WHERE email = $2 AND status = ANY($3)
+        RETURNING id
+        "#,
+        SubscriptionStatus::Suppressed as SubscriptionStatus,
+        task.subscriber_email,
+        &SubscriptionStatus::sources_of(SubscriptionStatus::Suppressed) as &[SubscriptionStatus],
+    )
+    .fetch_all(&mut **transaction)
+    .await?
+    .into_iter()
+    .map(|r| r.id)
+    .collect();
+
+    if !subscriber_ids.is_empty() {
+        record_events(
+            transaction,
+            &subscriber_ids,
+            SubscriberEventKind::Suppressed,
+            serde_json::json!({
+                "newsletter_issue_id": task.newsletter_issue_id,
+                "error_code": error.error_code,
+                "message": error.message,
+            }),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Tera tells what went wrong in the sources of its errors.
+fn render_error_chain(error: &tera::Error) -> String {
+    let mut chain = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        chain.push_str(": ");
+        chain.push_str(&cause.to_string());
+        source = cause.source();
+    }
+
+    chain
+}
+
+#[tracing::instrument(skip_all)]
+async fn record_render_failure(
+    transaction: &mut PgTransaction,
+    task: &Task,
+    error: &tera::Error,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_render_failures (
+            newsletter_issue_id, subscriber_email, error, failed_at
+        )
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET error = EXCLUDED.error,
+            failed_at = EXCLUDED.failed_at
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        render_error_chain(error),
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Raises an alert when the issue failed to render for too many of the
+/// recipients attempted so far, which hints at a broken issue rather than
+/// at odd subscriber data.
+#[tracing::instrument(skip(pool, settings))]
+async fn check_render_failure_rate(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    settings: &DeliveryQueueSettings,
+) -> Result<(), anyhow::Error> {
+    let counts = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT count(*) FROM issue_render_failures
+                WHERE newsletter_issue_id = $1) AS "failed!",
+            (SELECT count(*) FROM subscriber_events
+                WHERE event_type = 'delivered'
+                    AND details ->> 'newsletter_issue_id' = $1::uuid::text) AS "delivered!"
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let rate = counts.failed as f64 / (counts.failed + counts.delivered).max(1) as f64;
+    if rate > settings.render_failure_alert_rate {
+        tracing::error!(
+            alert = true,
+            failed = counts.failed,
+            delivered = counts.delivered,
+            "The issue failed to render for {:.1}% of its recipients",
+            rate * 100.,
+        );
+    }
+
+    Ok(())
+}
+
+struct Recipient {
+    id: Option<Uuid>,
+    name: String,
+    referral_code: Option<String>,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_recipient(pool: &PgPool, task: &Task) -> Result<Recipient, anyhow::Error> {
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT s.id, s.name, s.referral_code
+        FROM subscriptions s
+        JOIN newsletter_issues i ON i.list_id = s.list_id
+        WHERE s.email = $1 AND i.newsletter_issue_id = $2
+        "#,
+        task.subscriber_email,
+        task.newsletter_issue_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(match subscriber {
+        Some(s) => Recipient {
+            id: Some(s.id),
+            name: s.name,
+            referral_code: Some(s.referral_code),
+        },
+        None => Recipient {
+            id: None,
+            name: String::new(),
+            referral_code: None,
+        },
+    })
+}
+
+/// Whether the issue was delivered to the address of the task or to the
+/// subscriber behind it.
+#[tracing::instrument(skip_all)]
+async fn was_delivered(
+    pool: &PgPool,
+    task: &Task,
+    subscriber_id: Option<Uuid>,
+) -> Result<bool, anyhow::Error> {
+    let delivered = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM issue_deliveries
+            WHERE newsletter_issue_id = $1
+                AND (subscriber_email = $2 OR subscriber_id = $3)
+        ) AS "delivered!"
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        subscriber_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(delivered)
+}
+
+/// The issue as the recipient of the task gets it, with the subject they
+/// were assigned when it tests two of them.
+#[tracing::instrument(skip_all)]
+async fn get_issue(pool: &PgPool, task: &Task) -> Result<NewsletterIssue, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+        SELECT
+            COALESCE(CASE WHEN v.variant = 'b' THEN i.alternative_title END, i.title) AS "title!",
+            i.text_content, i.html_content, i.author, i.sender_name, i.sender_email, i.reply_to
+        FROM newsletter_issues i
+        LEFT JOIN issue_subject_variants v ON v.newsletter_issue_id = i.newsletter_issue_id
+            AND v.subscriber_email = $2
+        WHERE i.newsletter_issue_id = $1
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(issue)
+}
+
+/// An address stored with the issue, checked when it was published. The
+/// default one is used should it be invalid.
+fn stored_address(address: Option<String>) -> Option<Email> {
+    match Email::parse(address?) {
+        Ok(address) => Some(address),
+        Err(e) => {
+            tracing::warn!(
+                error.message = %e,
+                "Ignoring an invalid address stored with the issue",
+            );
+
+            None
+        }
+    }
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        newsletter_issue_id=%task.newsletter_issue_id,
+        subscriber_email=%task.subscriber_email
+    ),
+    err
+)]
+async fn prepare_delivery(
+    pool: &PgPool,
+    tracker: &EmailTracker,
+    referral_links: &ReferralLinks,
+    task: &Task,
+) -> Result<Delivery, anyhow::Error> {
+    let email = match SubscriberEmail::parse(task.subscriber_email.clone()) {
+        Ok(email) => email,
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Skipping a confirmed subscriber. Their stored contact details are invalid",
+            );
+
+            return Ok(Delivery::Done(DeliveryOutcome::Skipped));
+        }
+    };
+
+    let issue = get_issue(pool, task).await?;
+    let recipient = get_recipient(pool, task).await?;
+    if was_delivered(pool, task, recipient.id).await? {
+        tracing::info!("The subscriber was already delivered the issue. Skipping them");
+
+        return Ok(Delivery::Done(DeliveryOutcome::AlreadyDelivered));
+    }
+    let rendered = render_issue(
+        &issue.html_content,
+        &issue.text_content,
+        &IssueRecipient {
+            email: email.as_ref().as_ref(),
+            name: &recipient.name,
+        },
+    )
+    .and_then(|content| match &recipient.referral_code {
+        Some(referral_code) => referral_links.add_footer(content, referral_code),
+        None => Ok(content),
+    });
+    let content = match rendered {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = %render_error_chain(&e),
+                "Failed to render the issue for a confirmed subscriber. Skipping them",
+            );
+
+            return Ok(Delivery::Done(DeliveryOutcome::RenderFailed(e)));
+        }
+    };
+
+    let attachments = get_issue_attachments(pool, task.newsletter_issue_id).await?;
+    let html_content = tracker.track(
+        &content.html,
+        task.newsletter_issue_id,
+        email.as_ref().as_ref(),
+    );
+
+    Ok(Delivery::Ready(ReadyEmail {
+        email,
+        subject: issue.title,
+        html_content,
+        text_content: content.text,
+        attachments,
+        author: issue.author,
+        sender_name: issue.sender_name,
+        sender_email: stored_address(issue.sender_email),
+        reply_to: stored_address(issue.reply_to),
+    }))
+}
+
+/// What the provider said about an email of the batch.
+#[tracing::instrument(
+    skip_all,
+    fields(
+        newsletter_issue_id=%task.newsletter_issue_id,
+        subscriber_email=%task.subscriber_email
+    )
+)]
+fn delivery_outcome(task: &Task, result: Result<(), SendEmailError>) -> DeliveryOutcome {
+    match result {
+        Ok(()) => DeliveryOutcome::Delivered,
+        Err(SendEmailError::InactiveRecipient(error)) => {
+            tracing::warn!(
+                error.message = %error,
+                "The email provider suppressed a confirmed subscriber",
+            );
+
+            DeliveryOutcome::Suppressed(error)
+        }
+        Err(e) => {
+            let e =
+                anyhow::Error::new(e).context("Failed to deliver issue to a confirmed subscriber");
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to deliver issue to a confirmed subscriber. Retrying later.",
+            );
+
+            DeliveryOutcome::Failed(e)
+        }
+    }
+}
+
+/// Saves the outcome of the attempted tasks of a batch in one go. Tasks left
+/// without an outcome are handed back to the queue.
+#[tracing::instrument(skip_all)]
+async fn checkpoint(
+    pool: &PgPool,
+    tasks: &[(Task, Option<DeliveryOutcome>)],
+    settings: &DeliveryQueueSettings,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+
+    for (task, outcome) in tasks {
+        match outcome {
+            Some(DeliveryOutcome::Delivered) => {
+                record_delivery(&mut transaction, task).await?;
+                delete_task(&mut transaction, task).await?;
+            }
+            Some(DeliveryOutcome::Suppressed(error)) => {
+                suppress_recipient(&mut transaction, task, error).await?;
+                delete_task(&mut transaction, task).await?;
+            }
+            Some(DeliveryOutcome::Failed(error)) => {
+                handle_failed_task(&mut transaction, task, error, settings).await?;
+            }
+            Some(DeliveryOutcome::RenderFailed(error)) => {
+                record_render_failure(&mut transaction, task, error).await?;
+                delete_task(&mut transaction, task).await?;
+            }
+            Some(DeliveryOutcome::Skipped | DeliveryOutcome::AlreadyDelivered) => {
+                delete_task(&mut transaction, task).await?
+            }
+            None => release_task(&mut transaction, task).await?,
+        }
+    }
+    clear_dispatch_progress(&mut transaction, tasks, settings).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Per issue, the last task of the batch up to which every email was handed
+/// to the provider. Tasks with nothing to send don't hold it back.
+fn dispatched_up_to<'a>(
+    tasks: &'a [Task],
+    deliveries: &[Option<Delivery>],
+    results: &[Option<Result<(), SendEmailError>>],
+) -> Vec<&'a Task> {
+    let mut dispatched: Vec<&Task> = Vec::new();
+    let mut held_back = None;
+    for ((task, delivery), result) in tasks.iter().zip(deliveries).zip(results) {
+        if held_back == Some(task.newsletter_issue_id) {
+            continue;
+        }
+        match (delivery, result) {
+            (Some(Delivery::Ready(_)), Some(Ok(())))
+            | (Some(Delivery::Done(DeliveryOutcome::Delivered)), _) => {
+                match dispatched.last_mut() {
+                    Some(last) if last.newsletter_issue_id == task.newsletter_issue_id => {
+                        *last = task
+                    }
+                    _ => dispatched.push(task),
+                }
+            }
+            (Some(Delivery::Done(_)), _) => {}
+            _ => held_back = Some(task.newsletter_issue_id),
+        }
+    }
+
+    dispatched
+}
+
+/// Attempts a batch of due tasks, saving their outcomes once all of them were
+/// attempted. Should the worker die halfway, the tasks of the batch are
+/// attempted again when their lease expires, except for those the saved
+/// dispatch progress shows were already sent.
+pub async fn try_execute_task(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    tracker: &EmailTracker,
+    referral_links: &ReferralLinks,
+    settings: &DeliveryQueueSettings,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let tasks = lease_tasks(pool, settings).await?;
+    if tasks.is_empty() {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    }
+    let progress = get_dispatch_progress(pool, &tasks).await?;
+
+    let mut deliveries = Vec::with_capacity(tasks.len());
+    let mut error = None;
+    for task in &tasks {
+        let delivery = match error {
+            None => match prepare_delivery(pool, tracker, referral_links, task).await {
+                Ok(Delivery::Ready(_)) if was_sent_before(task, &progress) => {
+                    tracing::info!(
+                        newsletter_issue_id = %task.newsletter_issue_id,
+                        subscriber_email = %task.subscriber_email,
+                        "Not sending the issue again, it was sent before the last worker died",
+                    );
+                    Some(Delivery::Done(DeliveryOutcome::Delivered))
+                }
+                Ok(delivery) => Some(delivery),
+                Err(e) => {
+                    error = Some(e);
+                    None
+                }
+            },
+            Some(_) => None,
+        };
+        deliveries.push(delivery);
+    }
+
+    // Sent in as few requests as the provider allows, instead of one each.
+    let (positions, batch): (Vec<usize>, Vec<OutgoingEmail>) = deliveries
+        .iter()
+        .zip(&tasks)
+        .enumerate()
+        .filter_map(|(i, (delivery, task))| match delivery {
+            Some(Delivery::Ready(ready)) => Some((
+                i,
+                OutgoingEmail {
+                    kind: EmailKind::Issue,
+                    recipient: ready.email.as_ref(),
+                    subject: &ready.subject,
+                    html_content: &ready.html_content,
+                    text_content: &ready.text_content,
+                    attachments: &ready.attachments,
+                    sender_name: ready.sender_name.as_deref().or_else(|| {
+                        ready
+                            .author
+                            .as_deref()
+                            .filter(|_| settings.author_as_sender_name)
+                    }),
+                    // Sent from the default address once no longer verified,
+                    // the provider would refuse every email otherwise.
+                    sender_email: ready
+                        .sender_email
+                        .as_ref()
+                        .filter(|sender| email_client.is_verified_sender(sender)),
+                    reply_to: ready.reply_to.as_ref(),
+                    newsletter_issue_id: Some(task.newsletter_issue_id),
+                },
+            )),
+            _ => None,
+        })
+        .unzip();
+    let mut results: Vec<Option<Result<(), SendEmailError>>> = tasks.iter().map(|_| None).collect();
+    let mut saved: Vec<&Task> = Vec::new();
+    for request in EmailClient::split_into_requests(&batch) {
+        let emails = request.iter().map(|i| batch[*i]).collect();
+        let outcomes = email_client.send_email_batch(emails).await;
+        for (i, outcome) in request.iter().zip(outcomes) {
+            results[positions[*i]] = Some(outcome);
+        }
+
+        let dispatched = dispatched_up_to(&tasks, &deliveries, &results);
+        let moved: Vec<&Task> = dispatched
+            .iter()
+            .filter(|task| !saved.iter().any(|s| std::ptr::eq(*s, **task)))
+            .copied()
+            .collect();
+        // Sending more would risk sending it twice should the worker die.
+        if let Err(e) = save_dispatch_progress(pool, &moved).await {
+            error.get_or_insert(e);
+            break;
+        }
+        saved = dispatched;
+    }
+
+    let attempted: Vec<(Task, Option<DeliveryOutcome>)> = tasks
+        .into_iter()
+        .zip(deliveries)
+        .zip(results)
+        .map(|((task, delivery), result)| {
+            let outcome = match (delivery, result) {
+                (Some(Delivery::Ready(_)), Some(result)) => Some(delivery_outcome(&task, result)),
+                (Some(Delivery::Done(outcome)), _) => Some(outcome),
+                // Left unsent, handed back to the queue.
+                _ => None,
+            };
+            (task, outcome)
+        })
+        .collect();
+
+    checkpoint(pool, &attempted, settings).await?;
+
+    let mut failing_issues: Vec<Uuid> = attempted
+        .iter()
+        .filter(|(_, outcome)| matches!(outcome, Some(DeliveryOutcome::RenderFailed(_))))
+        .map(|(task, _)| task.newsletter_issue_id)
+        .collect();
+    failing_issues.sort();
+    failing_issues.dedup();
+    for newsletter_issue_id in failing_issues {
+        check_render_failure_rate(pool, newsletter_issue_id, settings).await?;
+    }
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(ExecutionOutcome::TaskCompleted),
+    }
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    tracker: EmailTracker,
+    referral_links: ReferralLinks,
+    settings: DeliveryQueueSettings,
+    maintenance_mode: MaintenanceMode,
+) -> Result<(), anyhow::Error> {
+    loop {
+        // Pending deliveries wait for the maintenance to be over.
+        if maintenance_mode.is_active() {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            continue;
+        }
+
+        match try_execute_task(&pool, &email_client, &tracker, &referral_links, &settings).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+pub async fn run_worker_until_stopped(
+    configuration: Settings,
+    email_client: EmailClient,
+    maintenance_mode: MaintenanceMode,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    let tracker = EmailTracker::new(
+        configuration.features.tracking,
+        &configuration.application.base_url,
+        &configuration.application.hmac_secret,
+    );
+    let referral_links = ReferralLinks::new(
+        configuration.features.referrals,
+        &configuration.application.base_url,
+    );
+
+    worker_loop(
+        connection_pool,
+        email_client,
+        tracker,
+        referral_links,
+        configuration.delivery_queue,
+        maintenance_mode,
+    )
+    .await
+}
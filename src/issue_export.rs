@@ -0,0 +1,434 @@
+use std::io::{Cursor, Write};
+
+use chrono::{DateTime, Utc};
+use kuchikiki::{traits::TendrilSink, NodeData, NodeRef};
+use sqlx::PgPool;
+use uuid::Uuid;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::template::{render_issue, IssueRecipient};
+
+/// Documents a published issue can be exported to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Pdf,
+    Epub,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Pdf => "application/pdf",
+            ExportFormat::Epub => "application/epub+zip",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Epub => "epub",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PublishedIssue {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    pub published_at: DateTime<Utc>,
+    /// Who published the issue, by the name they go by.
+    pub author: Option<String>,
+}
+
+impl PublishedIssue {
+    /// Name of the exported file, e.g. `my-first-issue.pdf`.
+    pub fn file_name(&self, format: ExportFormat) -> String {
+        let mut slug = String::new();
+        for word in self
+            .title
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+        {
+            if !slug.is_empty() {
+                slug.push('-');
+            }
+            slug.push_str(&word.to_ascii_lowercase());
+        }
+        if slug.is_empty() {
+            slug = self.newsletter_issue_id.to_string();
+        }
+
+        format!("{}.{}", slug, format.extension())
+    }
+}
+
+#[tracing::instrument(name = "Get published issue", skip(pool))]
+pub async fn get_published_issue(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<PublishedIssue>, sqlx::Error> {
+    sqlx::query_as!(
+        PublishedIssue,
+        r#"
+        SELECT i.newsletter_issue_id, i.title, i.text_content, i.html_content, i.published_at,
+            COALESCE(u.display_name, u.username, i.author) AS author
+        FROM newsletter_issues i
+        LEFT JOIN users u ON u.user_id = i.published_by
+        WHERE i.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// The HTML and text content of the issue, as read by no one in particular.
+/// Issues that only render for some recipients keep their placeholders
+/// rather than failing.
+pub fn anonymous_content(issue: &PublishedIssue) -> (String, String) {
+    let recipient = IssueRecipient {
+        email: "",
+        name: "",
+    };
+
+    match render_issue(&issue.html_content, &issue.text_content, &recipient) {
+        Ok(content) => (content.html, content.text),
+        Err(_) => (issue.html_content.clone(), issue.text_content.clone()),
+    }
+}
+
+/// Renders the issue into a document, see [`anonymous_content`].
+pub fn export_issue(
+    issue: &PublishedIssue,
+    format: ExportFormat,
+) -> Result<Vec<u8>, anyhow::Error> {
+    let (html, text) = anonymous_content(issue);
+
+    match format {
+        ExportFormat::Pdf => Ok(to_pdf(&issue.title, &text)),
+        ExportFormat::Epub => Ok(to_epub(issue, &html)?),
+    }
+}
+
+const PAGE_WIDTH: usize = 595;
+const PAGE_HEIGHT: usize = 842;
+const MARGIN: usize = 56;
+const FONT_SIZE: usize = 11;
+const LEADING: usize = 14;
+/// Helvetica fits about this many average characters in the text width.
+const LINE_CHARS: usize = 90;
+
+/// A text-only PDF of the issue, set in one of the fonts every reader has.
+fn to_pdf(title: &str, text: &str) -> Vec<u8> {
+    let mut lines = wrap(title, LINE_CHARS);
+    lines.push(String::new());
+    for paragraph in text.lines() {
+        lines.extend(wrap(paragraph, LINE_CHARS));
+    }
+    let lines_per_page = (PAGE_HEIGHT - 2 * MARGIN) / LEADING;
+    let pages: Vec<&[String]> = lines.chunks(lines_per_page).collect();
+
+    // Objects 1 to 3 are the catalog, the page tree and the font; each page
+    // then takes two objects, itself and its content stream.
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len())
+                .map(|i| format!("{} 0 R", 4 + 2 * i))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+    for (i, page) in pages.iter().enumerate() {
+        let mut content = format!(
+            "BT /F1 {} Tf {} TL {} {} Td\n",
+            FONT_SIZE,
+            LEADING,
+            MARGIN,
+            PAGE_HEIGHT - MARGIN
+        );
+        for line in page.iter() {
+            content.push_str(&format!("({}) Tj T*\n", pdf_string(line)));
+        }
+        content.push_str("ET");
+
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            5 + 2 * i
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}\nendstream",
+            content.len(),
+            content
+        ));
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).into_bytes());
+    }
+    let xref = pdf.len();
+    pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+    for offset in offsets {
+        pdf.extend(format!("{:010} 00000 n \n", offset).into_bytes());
+    }
+    pdf.extend(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .into_bytes(),
+    );
+
+    pdf
+}
+
+/// Breaks a paragraph into lines of at most `width` characters, between
+/// words when possible.
+fn wrap(paragraph: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for word in paragraph.split_whitespace() {
+        let line = lines.last_mut().unwrap();
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(String::new());
+        }
+        let line = lines.last_mut().unwrap();
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+        // Words longer than a line are cut.
+        while lines.last().unwrap().chars().count() > width {
+            let line = lines.last_mut().unwrap();
+            let rest: String = line.chars().skip(width).collect();
+            *line = line.chars().take(width).collect();
+            lines.push(rest);
+        }
+    }
+
+    lines
+}
+
+/// Escapes a line for a PDF literal string. Characters WinAnsi can't encode
+/// are replaced, as the standard fonts have no glyph for them.
+fn pdf_string(line: &str) -> String {
+    let mut escaped = String::with_capacity(line.len());
+    for c in line.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '..='~' => escaped.push(c),
+            '\u{a0}'..='\u{ff}' => escaped.push_str(&format!("\\{:03o}", c as u32)),
+            _ => escaped.push('?'),
+        }
+    }
+
+    escaped
+}
+
+/// An EPUB 3 holding the HTML version of the issue as its only chapter.
+fn to_epub(issue: &PublishedIssue, html: &str) -> Result<Vec<u8>, zip::result::ZipError> {
+    let title = htmlescape::encode_minimal(&issue.title);
+    let body = xhtml_body(html);
+    let container = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+    let package = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="issue-id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="issue-id">urn:uuid:{id}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>en</dc:language>
+    <dc:date>{published_at}</dc:date>
+    <meta property="dcterms:modified">{published_at}</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="issue" href="issue.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="issue"/>
+  </spine>
+</package>"#,
+        id = issue.newsletter_issue_id,
+        published_at = issue.published_at.format("%Y-%m-%dT%H:%M:%SZ"),
+    );
+    let nav = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head><title>{title}</title></head>
+<body>
+  <nav epub:type="toc"><ol><li><a href="issue.xhtml">{title}</a></li></ol></nav>
+</body>
+</html>"#
+    );
+    let chapter = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>{title}</title></head>
+<body>
+<h1>{title}</h1>
+{body}
+</body>
+</html>"#
+    );
+
+    let mut epub = ZipWriter::new(Cursor::new(Vec::new()));
+    // Readers expect the media type first and uncompressed.
+    epub.start_file(
+        "mimetype",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    epub.write_all(b"application/epub+zip")?;
+    for (path, content) in [
+        ("META-INF/container.xml", container),
+        ("OEBPS/content.opf", package.as_str()),
+        ("OEBPS/nav.xhtml", nav.as_str()),
+        ("OEBPS/issue.xhtml", chapter.as_str()),
+    ] {
+        epub.start_file(path, SimpleFileOptions::default())?;
+        epub.write_all(content.as_bytes())?;
+    }
+
+    Ok(epub.finish()?.into_inner())
+}
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// The content of the body of an HTML document, as well-formed XHTML:
+/// EPUB readers parse chapters as XML. Scripts and styles are left out.
+fn xhtml_body(html: &str) -> String {
+    let document = kuchikiki::parse_html().one(html);
+    let mut xhtml = String::new();
+    if let Ok(body) = document.select_first("body") {
+        for child in body.as_node().children() {
+            push_xhtml(&mut xhtml, &child);
+        }
+    }
+
+    xhtml
+}
+
+fn push_xhtml(xhtml: &mut String, node: &NodeRef) {
+    match node.data() {
+        NodeData::Text(text) => xhtml.push_str(&htmlescape::encode_minimal(&text.borrow())),
+        NodeData::Element(element) => {
+            let name = element.name.local.to_string();
+            if name == "script" || name == "style" {
+                return;
+            }
+
+            xhtml.push('<');
+            xhtml.push_str(&name);
+            for (attribute, value) in element.attributes.borrow().map.iter() {
+                xhtml.push_str(&format!(
+                    r#" {}="{}""#,
+                    attribute.local,
+                    htmlescape::encode_minimal(&value.value)
+                ));
+            }
+            if VOID_ELEMENTS.contains(&name.as_str()) {
+                xhtml.push_str("/>");
+                return;
+            }
+            xhtml.push('>');
+            for child in node.children() {
+                push_xhtml(xhtml, &child);
+            }
+            xhtml.push_str(&format!("</{}>", name));
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use super::{export_issue, wrap, xhtml_body, ExportFormat, PublishedIssue};
+
+    fn issue() -> PublishedIssue {
+        PublishedIssue {
+            newsletter_issue_id: Uuid::new_v4(),
+            title: "My (first) issue!".into(),
+            text_content: "Hello, world.".into(),
+            html_content: "<p>Hello,<br>world.</p>".into(),
+            published_at: Utc::now(),
+            author: None,
+        }
+    }
+
+    #[test]
+    fn file_names_are_slugs_of_the_title() {
+        assert_eq!(issue().file_name(ExportFormat::Pdf), "my-first-issue.pdf");
+        assert_eq!(issue().file_name(ExportFormat::Epub), "my-first-issue.epub");
+    }
+
+    #[test]
+    fn long_paragraphs_are_wrapped_between_words() {
+        assert_eq!(wrap("one two three", 8), vec!["one two", "three"]);
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("", 4), vec![""]);
+    }
+
+    #[test]
+    fn pdfs_hold_the_text_of_the_issue() {
+        let pdf = export_issue(&issue(), ExportFormat::Pdf).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.starts_with("%PDF-1.4"));
+        assert!(pdf.contains(r"(My \(first\) issue!) Tj"));
+        assert!(pdf.contains("(Hello, world.) Tj"));
+        assert!(pdf.trim_end().ends_with("%%EOF"));
+    }
+
+    #[test]
+    fn epubs_start_with_their_media_type() {
+        let epub = export_issue(&issue(), ExportFormat::Epub).unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(epub)).unwrap();
+
+        let mut mimetype = String::new();
+        archive
+            .by_index(0)
+            .unwrap()
+            .read_to_string(&mut mimetype)
+            .unwrap();
+        assert_eq!(mimetype, "application/epub+zip");
+        assert!(archive.by_name("OEBPS/issue.xhtml").is_ok());
+    }
+
+    #[test]
+    fn chapters_are_well_formed_xhtml() {
+        let xhtml = xhtml_body(r#"<p class="a&b">Fish &amp; chips<br><img src="x.png"></p>"#);
+
+        assert_eq!(
+            xhtml,
+            r#"<p class="a&amp;b">Fish &amp; chips<br/><img src="x.png"/></p>"#
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use sqlx::{pool::PoolConnection, Connection, PgPool, Postgres};
+
+/// Background jobs that must not run on several instances at once. Workers
+/// draining a queue don't need one, they skip the rows locked by the others.
+#[derive(Clone, Copy, Debug)]
+pub enum SingletonJob {
+    Maintenance,
+    WeeklyReport,
+    SuppressionSync,
+    WeeklyDigest,
+}
+
+impl SingletonJob {
+    /// Key of the Postgres advisory lock held by the leader of the job.
+    fn lock_key(&self) -> i64 {
+        // Arbitrary, only meant not to collide with the locks of sqlx
+        // migrations.
+        match self {
+            SingletonJob::Maintenance => 0x6e6c_0001,
+            SingletonJob::WeeklyReport => 0x6e6c_0002,
+            SingletonJob::SuppressionSync => 0x6e6c_0003,
+            SingletonJob::WeeklyDigest => 0x6e6c_0004,
+        }
+    }
+}
+
+/// Elects the single instance running a job through a session-level
+/// advisory lock.
+///
+/// The leader keeps the connection holding the lock for as long as it
+/// leads. If the instance dies, Postgres drops the connection and the lock
+/// along with it, so another instance takes over on its next attempt.
+pub struct LeaderElection {
+    pool: PgPool,
+    job: SingletonJob,
+    instance_id: String,
+    lock: Option<PoolConnection<Postgres>>,
+}
+
+impl LeaderElection {
+    pub fn new(pool: PgPool, job: SingletonJob, instance_id: &str) -> Self {
+        Self {
+            pool,
+            job,
+            instance_id: instance_id.to_string(),
+            lock: None,
+        }
+    }
+
+    /// Whether this instance leads the job, taking the lead if nobody else
+    /// holds it. Errors are logged and count as not leading.
+    #[tracing::instrument(name = "Elect job leader", skip(self), fields(job = ?self.job, instance_id = %self.instance_id))]
+    pub async fn is_leader(&mut self) -> bool {
+        if let Some(connection) = self.lock.as_mut() {
+            if connection.ping().await.is_ok() {
+                return true;
+            }
+            tracing::warn!("Lost the connection holding the lead of the job");
+            self.step_down();
+        }
+
+        match self.try_lead().await {
+            Ok(true) => {
+                tracing::info!("Took the lead of the job");
+                true
+            }
+            Ok(false) => false,
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to take the lead of the job",
+                );
+                false
+            }
+        }
+    }
+
+    async fn try_lead(&mut self) -> Result<bool, sqlx::Error> {
+        let mut connection = self.pool.acquire().await?;
+        let acquired = sqlx::query!(
+            r#"SELECT pg_try_advisory_lock($1) AS "acquired!""#,
+            self.job.lock_key(),
+        )
+        .fetch_one(&mut *connection)
+        .await?
+        .acquired;
+
+        if acquired {
+            self.lock = Some(connection);
+        }
+
+        Ok(acquired)
+    }
+
+    /// Gives the lead up by closing the connection holding the lock, rather
+    /// than returning it to the pool where the lock would live on.
+    pub fn step_down(&mut self) {
+        if let Some(connection) = self.lock.take() {
+            drop(connection.detach());
+        }
+    }
+}
+
+impl Drop for LeaderElection {
+    fn drop(&mut self) {
+        self.step_down();
+    }
+}
@@ -1,11 +1,56 @@
+pub mod admin_action;
+pub mod api_error;
 pub mod authentication;
+pub mod base_url_check;
+pub mod blob_store;
+pub mod cli;
+pub mod config_schema;
 pub mod configuration;
+pub mod confirmation_tokens;
+pub mod cookie_keys;
+pub mod css_inliner;
+pub mod delegated_access;
+pub mod deliverability;
+pub mod delivery_queue;
+pub mod digest;
 pub mod domain;
+pub mod dynamic_settings;
 pub mod email_client;
+pub mod email_log;
+pub mod erasure;
+pub mod error_pages;
+pub mod feature_flags;
+pub mod feed;
+pub mod growth_report;
+pub mod issue_delivery_worker;
+pub mod issue_export;
+pub mod leader_election;
+pub mod link_validator;
+pub mod maintenance;
+pub mod maintenance_mode;
+pub mod mx_check;
+pub mod newsletter_list;
+pub mod openapi;
+pub mod payload_limits;
+pub mod privacy;
+pub mod public_stats;
+pub mod referrals;
+pub mod request_id;
 pub mod routes;
+pub mod segment;
 pub mod session_state;
+pub mod source_allow_list;
 pub mod startup;
+pub mod stats;
+pub mod stored_templates;
+pub mod subscriber_duplicates;
+pub mod subscriber_events;
+pub mod suppression_sync;
 pub mod telemetry;
 pub mod template;
+pub mod token_generator;
+pub mod tracking;
 pub mod user_role;
 pub mod util;
+pub mod webhook_delivery_worker;
+pub mod webhooks;
@@ -0,0 +1,374 @@
+use std::collections::BTreeSet;
+
+use anyhow::Context;
+use linkify::{LinkFinder, LinkKind};
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+
+const THREAT_TYPES: [&str; 4] = [
+    "MALWARE",
+    "SOCIAL_ENGINEERING",
+    "UNWANTED_SOFTWARE",
+    "POTENTIALLY_HARMFUL_APPLICATION",
+];
+
+/// Most threat entries the API accepts in a single request.
+const MAX_THREAT_ENTRIES: usize = 500;
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClientInfo<'a> {
+    client_id: &'a str,
+    client_version: &'a str,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ThreatEntry {
+    url: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ThreatInfo<'a> {
+    threat_types: &'a [&'a str],
+    platform_types: &'a [&'a str],
+    threat_entry_types: &'a [&'a str],
+    threat_entries: Vec<ThreatEntry>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FindThreatMatchesRequest<'a> {
+    client: ClientInfo<'a>,
+    threat_info: ThreatInfo<'a>,
+}
+
+#[derive(serde::Deserialize)]
+struct ThreatMatch {
+    threat: ThreatEntry,
+}
+
+#[derive(serde::Deserialize)]
+struct FindThreatMatchesResponse {
+    #[serde(default)]
+    matches: Vec<ThreatMatch>,
+}
+
+/// Client of the Google Safe Browsing Lookup API (v4).
+pub struct SafeBrowsingClient {
+    http_client: Client,
+    find_url: reqwest::Url,
+    api_key: Secret<String>,
+}
+
+impl SafeBrowsingClient {
+    pub fn new(
+        base_url: reqwest::Url,
+        api_key: Secret<String>,
+        timeout: std::time::Duration,
+    ) -> Result<Self, anyhow::Error> {
+        let http_client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .context("Failed to build the Safe Browsing http client")?;
+        let find_url = base_url
+            .join("v4/threatMatches:find")
+            .context("Invalid Safe Browsing base url")?;
+
+        Ok(Self {
+            http_client,
+            find_url,
+            api_key,
+        })
+    }
+
+    /// Returns the links Safe Browsing flags, asking for them in as many
+    /// requests as the limit of entries per request demands.
+    pub async fn find_threats(&self, links: &[String]) -> Result<Vec<String>, reqwest::Error> {
+        let mut threats = Vec::new();
+        for chunk in links.chunks(MAX_THREAT_ENTRIES) {
+            threats.extend(self.find_chunk_threats(chunk).await?);
+        }
+
+        Ok(threats)
+    }
+
+    async fn find_chunk_threats(&self, links: &[String]) -> Result<Vec<String>, reqwest::Error> {
+        let request_body = FindThreatMatchesRequest {
+            client: ClientInfo {
+                client_id: env!("CARGO_PKG_NAME"),
+                client_version: env!("CARGO_PKG_VERSION"),
+            },
+            threat_info: ThreatInfo {
+                threat_types: &THREAT_TYPES,
+                platform_types: &["ANY_PLATFORM"],
+                threat_entry_types: &["URL"],
+                threat_entries: links
+                    .iter()
+                    .map(|link| ThreatEntry { url: link.clone() })
+                    .collect(),
+            },
+        };
+
+        let response = self
+            .http_client
+            .post(self.find_url.clone())
+            .query(&[("key", self.api_key.expose_secret())])
+            .json(&request_body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<FindThreatMatchesResponse>()
+            .await?;
+
+        Ok(response.matches.into_iter().map(|m| m.threat.url).collect())
+    }
+}
+
+/// Looks for links in the content of an issue that could hurt the reputation
+/// of the sender domain, either because they point to a blocked domain or
+/// because Safe Browsing flags them.
+pub struct LinkValidator {
+    enabled: bool,
+    blocklist: Vec<String>,
+    safe_browsing: Option<SafeBrowsingClient>,
+}
+
+impl LinkValidator {
+    pub fn new(
+        enabled: bool,
+        blocklist: Vec<String>,
+        safe_browsing: Option<SafeBrowsingClient>,
+    ) -> Self {
+        let blocklist = blocklist
+            .into_iter()
+            .map(|domain| domain.trim().trim_matches('.').to_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .collect();
+
+        Self {
+            enabled,
+            blocklist,
+            safe_browsing,
+        }
+    }
+
+    fn is_blocked(&self, link: &str) -> bool {
+        let host = match reqwest::Url::parse(link)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+        {
+            Some(host) => host,
+            None => return false,
+        };
+
+        self.blocklist.iter().any(|domain| {
+            host == *domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+
+    /// Returns the links found in `contents` that must not be sent.
+    #[tracing::instrument(name = "Validate links", skip(self, contents))]
+    pub async fn flagged_links(&self, contents: &[&str]) -> Result<Vec<String>, anyhow::Error> {
+        if !self.enabled {
+            return Ok(vec![]);
+        }
+
+        let links = extract_links(contents);
+        if links.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut flagged: BTreeSet<String> = links
+            .iter()
+            .filter(|link| self.is_blocked(link))
+            .cloned()
+            .collect();
+
+        if let Some(safe_browsing) = &self.safe_browsing {
+            flagged.extend(safe_browsing.find_threats(&links).await?);
+        }
+
+        Ok(flagged.into_iter().collect())
+    }
+}
+
+fn extract_links(contents: &[&str]) -> Vec<String> {
+    let mut finder = LinkFinder::new();
+    finder.kinds(&[LinkKind::Url]);
+
+    contents
+        .iter()
+        .flat_map(|content| finder.links(content))
+        .map(|link| link.as_str().to_string())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+    use wiremock::matchers::{any, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::{extract_links, LinkValidator, SafeBrowsingClient};
+
+    fn safe_browsing_client(base_url: String) -> SafeBrowsingClient {
+        SafeBrowsingClient::new(
+            reqwest::Url::parse(&base_url).unwrap(),
+            Secret::new("api-key".into()),
+            std::time::Duration::from_millis(400),
+        )
+        .unwrap()
+    }
+
+    fn link_validator(
+        blocklist: &[&str],
+        safe_browsing: Option<SafeBrowsingClient>,
+    ) -> LinkValidator {
+        LinkValidator::new(
+            true,
+            blocklist.iter().map(|domain| domain.to_string()).collect(),
+            safe_browsing,
+        )
+    }
+
+    #[test]
+    fn links_are_extracted_from_text_and_html_without_duplicates() {
+        let links = extract_links(&[
+            "Read https://example.com/post now",
+            r#"<a href="https://example.com/post">post</a> <a href="http://other.org">x</a>"#,
+        ]);
+
+        assert_eq!(links, vec!["http://other.org", "https://example.com/post"]);
+    }
+
+    #[test]
+    fn blocked_domains_match_their_subdomains() {
+        let validator = link_validator(&["evil.com"], None);
+
+        assert!(validator.is_blocked("https://evil.com/path"));
+        assert!(validator.is_blocked("https://www.EVIL.com"));
+        assert!(!validator.is_blocked("https://notevil.com"));
+        assert!(!validator.is_blocked("https://evil.com.example.org"));
+    }
+
+    #[tokio::test]
+    async fn disabled_validator_does_not_flag_anything() {
+        let validator = LinkValidator::new(false, vec!["evil.com".into()], None);
+
+        let flagged = validator
+            .flagged_links(&["https://evil.com"])
+            .await
+            .unwrap();
+
+        assert!(flagged.is_empty());
+    }
+
+    #[tokio::test]
+    async fn links_flagged_by_safe_browsing_are_returned() {
+        let mock_server = MockServer::start().await;
+        let validator = link_validator(&[], Some(safe_browsing_client(mock_server.uri())));
+
+        Mock::given(method("POST"))
+            .and(path("/v4/threatMatches:find"))
+            .and(query_param("key", "api-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "matches": [{
+                    "threatType": "MALWARE",
+                    "threat": { "url": "https://malware.test/" }
+                }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let flagged = validator
+            .flagged_links(&["https://malware.test/ and https://example.com"])
+            .await;
+
+        assert_eq!(assert_ok!(flagged), vec!["https://malware.test/"]);
+    }
+
+    #[tokio::test]
+    async fn safe_browsing_is_asked_in_requests_of_at_most_500_links() {
+        let mock_server = MockServer::start().await;
+        let validator = link_validator(&[], Some(safe_browsing_client(mock_server.uri())));
+        let links: Vec<String> = (0..501)
+            .map(|i| format!("https://example.com/{}", i))
+            .collect();
+
+        Mock::given(method("POST"))
+            .and(path("/v4/threatMatches:find"))
+            .and(|request: &wiremock::Request| {
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                body["threatInfo"]["threatEntries"]
+                    .as_array()
+                    .unwrap()
+                    .len()
+                    == 500
+            })
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "matches": [{ "threat": { "url": "https://example.com/0" } }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v4/threatMatches:find"))
+            .and(|request: &wiremock::Request| {
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                body["threatInfo"]["threatEntries"]
+                    .as_array()
+                    .unwrap()
+                    .len()
+                    == 1
+            })
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "matches": [{ "threat": { "url": "https://example.com/99" } }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let contents = links.join(" ");
+        let flagged = validator.flagged_links(&[&contents]).await;
+
+        assert_eq!(
+            assert_ok!(flagged),
+            vec!["https://example.com/0", "https://example.com/99"]
+        );
+    }
+
+    #[test]
+    fn a_base_url_that_cannot_be_joined_is_rejected() {
+        let client = SafeBrowsingClient::new(
+            reqwest::Url::parse("mailto:safe@browsing.test").unwrap(),
+            Secret::new("api-key".into()),
+            std::time::Duration::from_millis(400),
+        );
+
+        assert!(client.is_err());
+    }
+
+    #[tokio::test]
+    async fn validation_fails_if_safe_browsing_returns_500() {
+        let mock_server = MockServer::start().await;
+        let validator = link_validator(&[], Some(safe_browsing_client(mock_server.uri())));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let flagged = validator.flagged_links(&["https://example.com"]).await;
+
+        assert_err!(flagged);
+    }
+}
@@ -1,17 +1,122 @@
+use clap::Parser;
+use newsletter::cli::{run_config_command, run_queue_command, Cli, Command};
 use newsletter::configuration::get_configuration;
-use newsletter::startup::Application;
+use newsletter::digest;
+use newsletter::growth_report;
+use newsletter::issue_delivery_worker;
+use newsletter::maintenance;
+use newsletter::startup::{get_connection_pool, migrate_database, Application};
+use newsletter::suppression_sync;
 use newsletter::telemetry::{get_subscriber, init_subscriber};
+use newsletter::webhook_delivery_worker;
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    let subscriber = get_subscriber("newsletter".into(), "info".into(), std::io::stdout);
-    init_subscriber(subscriber);
+    let cli = Cli::parse();
+    let queue_command = match cli.command {
+        // Before reading the configuration, so that invalid ones can be debugged.
+        Some(Command::Config { command }) => return run_config_command(command),
+        Some(Command::Queue { command }) => Some(command),
+        None => None,
+    };
+
+    let mut configuration = get_configuration().expect("Failed to read configuration.");
+    if cli.maintenance {
+        configuration.maintenance_mode.enabled = true;
+    }
+    if cli.maintenance_until.is_some() {
+        configuration.maintenance_mode.until = cli.maintenance_until;
+    }
+
+    if let Some(command) = queue_command {
+        let pool = get_connection_pool(&configuration.database);
 
-    let configuration = get_configuration().expect("Failed to read configuration.");
+        return run_queue_command(command, &pool).await;
+    }
 
-    let application = Application::build(configuration).await?;
+    if cli.migrate_only {
+        let pool = get_connection_pool(&configuration.database);
 
-    application.run_until_stopped().await?;
+        return migrate_database(&pool).await;
+    }
+
+    let subscriber = get_subscriber(
+        "newsletter".into(),
+        configuration.dynamic.log_filter.clone(),
+        Some(configuration.application.instance_id.clone()),
+        std::io::stdout,
+    );
+    init_subscriber(subscriber);
+
+    let email_client = configuration
+        .email_client
+        .clone()
+        .client()
+        .with_email_log(get_connection_pool(&configuration.database));
+    let application =
+        Application::build_with_email_client(configuration.clone(), email_client.clone()).await?;
+    let maintenance_mode = application.maintenance_mode();
+    let settings_task = tokio::spawn(application.settings_reloader().reload_on_sighup());
+    let application_task = tokio::spawn(application.run_until_stopped());
+    let worker_task = tokio::spawn(issue_delivery_worker::run_worker_until_stopped(
+        configuration.clone(),
+        email_client.clone(),
+        maintenance_mode,
+    ));
+    // Not awaited: the application doesn't depend on it.
+    tokio::spawn(suppression_sync::sync_on_startup(
+        configuration.clone(),
+        email_client.clone(),
+    ));
+    let report_task = tokio::spawn(growth_report::run_worker_until_stopped(
+        configuration.clone(),
+        email_client.clone(),
+    ));
+    let digest_task = tokio::spawn(digest::run_worker_until_stopped(
+        configuration.clone(),
+        email_client,
+    ));
+    let webhook_worker_task = tokio::spawn(webhook_delivery_worker::run_worker_until_stopped(
+        configuration.clone(),
+    ));
+    let maintenance_task = tokio::spawn(maintenance::run_worker_until_stopped(configuration));
+
+    tokio::select! {
+        outcome = application_task => report_exit("API", outcome),
+        outcome = worker_task => report_exit("Background worker", outcome),
+        outcome = webhook_worker_task => report_exit("Webhook worker", outcome),
+        outcome = maintenance_task => report_exit("Maintenance job", outcome),
+        outcome = report_task => report_exit("Weekly report job", outcome),
+        outcome = digest_task => report_exit("Weekly digest job", outcome),
+        outcome = settings_task => report_exit("Settings reloader", outcome),
+    };
 
     Ok(())
 }
+
+fn report_exit(
+    task_name: &str,
+    outcome: Result<Result<(), impl std::fmt::Debug + std::fmt::Display>, tokio::task::JoinError>,
+) {
+    match outcome {
+        Ok(Ok(())) => {
+            tracing::info!("{} has exited", task_name)
+        }
+        Ok(Err(e)) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "{} failed",
+                task_name
+            )
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "{} task failed to complete",
+                task_name
+            )
+        }
+    }
+}
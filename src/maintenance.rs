@@ -0,0 +1,298 @@
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, TimeZone, Timelike, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    configuration::{MaintenanceSettings, Settings, TableMaintenanceSettings},
+    leader_election::{LeaderElection, SingletonJob},
+    startup::get_connection_pool,
+};
+
+/// Tables written to on every subscription and delivery, whose statistics
+/// and indexes go stale first.
+pub const HOT_TABLES: &[&str] = &["subscriptions", "issue_delivery_queue", "issue_deliveries"];
+
+#[derive(Clone, Copy, Debug)]
+enum TableOperation {
+    Analyze,
+    Reindex,
+}
+
+impl TableOperation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TableOperation::Analyze => "analyze",
+            TableOperation::Reindex => "reindex",
+        }
+    }
+
+    /// Table names can't be bound, they only ever come from [`HOT_TABLES`].
+    fn statement(&self, table: &str) -> String {
+        match self {
+            TableOperation::Analyze => format!("ANALYZE {}", table),
+            // Doesn't lock out writes, but can't run in a transaction.
+            TableOperation::Reindex => format!("REINDEX TABLE CONCURRENTLY {}", table),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TableMaintenanceRun {
+    pub table_name: String,
+    pub operation: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: i64,
+    pub succeeded: bool,
+}
+
+/// Creates the partitions of the months to come, so that events never land
+/// in the default partition, and drops the ones past the retention period.
+/// Once a day, in their window, the hot tables are analyzed too.
+#[tracing::instrument(name = "Run maintenance", skip_all, err)]
+pub async fn run_maintenance(
+    pool: &PgPool,
+    settings: &MaintenanceSettings,
+) -> Result<(), anyhow::Error> {
+    create_upcoming_partitions(pool, settings.partitions_ahead_months).await?;
+
+    if settings.retention_months > 0 {
+        let dropped = drop_expired_partitions(pool, settings.retention_months).await?;
+        if !dropped.is_empty() {
+            tracing::info!(partitions = ?dropped, "Dropped expired event partitions");
+        }
+    }
+
+    if settings.tables.enabled
+        && table_maintenance_is_due(pool, &settings.tables, Utc::now()).await?
+    {
+        run_table_maintenance(pool, settings.tables.reindex).await?;
+    }
+
+    Ok(())
+}
+
+/// Start of the window `now` falls in, if it falls in one.
+fn current_window_start(
+    now: DateTime<Utc>,
+    start_hour: u32,
+    end_hour: u32,
+) -> Option<DateTime<Utc>> {
+    let hour = now.hour();
+    let in_window = if start_hour <= end_hour {
+        start_hour <= hour && hour < end_hour
+    } else {
+        hour >= start_hour || hour < end_hour
+    };
+    if !in_window {
+        return None;
+    }
+
+    let start = Utc.from_utc_datetime(&now.date_naive().and_hms_opt(start_hour, 0, 0)?);
+    if hour >= start_hour {
+        Some(start)
+    } else {
+        Some(start - Duration::days(1))
+    }
+}
+
+/// Whether `now` is in the window and the tables weren't taken care of yet
+/// since it started.
+async fn table_maintenance_is_due(
+    pool: &PgPool,
+    settings: &TableMaintenanceSettings,
+    now: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let Some(window_start) =
+        current_window_start(now, settings.window_start_hour, settings.window_end_hour)
+    else {
+        return Ok(false);
+    };
+
+    let last_run = sqlx::query_scalar!(
+        r#"
+        SELECT max(started_at)
+        FROM table_maintenance_runs
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(!matches!(last_run, Some(last_run) if last_run >= window_start))
+}
+
+/// Analyzes the hot tables, then rebuilds their indexes if asked to. A
+/// failure doesn't stop the other tables from being taken care of, every
+/// run is recorded with how long it took.
+#[tracing::instrument(name = "Run table maintenance", skip(pool))]
+pub async fn run_table_maintenance(
+    pool: &PgPool,
+    reindex: bool,
+) -> Result<Vec<TableMaintenanceRun>, sqlx::Error> {
+    let mut operations = vec![TableOperation::Analyze];
+    if reindex {
+        operations.push(TableOperation::Reindex);
+    }
+
+    let mut runs = Vec::new();
+    for operation in operations {
+        for table in HOT_TABLES {
+            let started_at = Utc::now();
+            let timer = Instant::now();
+            let outcome = sqlx::query(&operation.statement(table)).execute(pool).await;
+            let duration_ms = timer.elapsed().as_millis() as i64;
+
+            match &outcome {
+                Ok(_) => tracing::info!(
+                    table,
+                    operation = operation.as_str(),
+                    duration_ms,
+                    "Table maintenance done"
+                ),
+                Err(e) => tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    table,
+                    operation = operation.as_str(),
+                    duration_ms,
+                    "Table maintenance failed"
+                ),
+            }
+
+            let run = TableMaintenanceRun {
+                table_name: table.to_string(),
+                operation: operation.as_str().to_string(),
+                started_at,
+                duration_ms,
+                succeeded: outcome.is_ok(),
+            };
+            sqlx::query!(
+                r#"
+                INSERT INTO table_maintenance_runs
+                    (table_name, operation, started_at, duration_ms, succeeded)
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+                run.table_name,
+                run.operation,
+                run.started_at,
+                run.duration_ms,
+                run.succeeded,
+            )
+            .execute(pool)
+            .await?;
+            runs.push(run);
+        }
+    }
+
+    Ok(runs)
+}
+
+/// The last runs of the table maintenance, most recent first.
+#[tracing::instrument(name = "Fetch table maintenance runs", skip(pool))]
+pub async fn recent_table_maintenance_runs(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<TableMaintenanceRun>, sqlx::Error> {
+    sqlx::query_as!(
+        TableMaintenanceRun,
+        r#"
+        SELECT table_name, operation, started_at, duration_ms, succeeded
+        FROM table_maintenance_runs
+        ORDER BY run_id DESC
+        LIMIT $1
+        "#,
+        limit,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Create upcoming event partitions", skip(pool))]
+async fn create_upcoming_partitions(pool: &PgPool, months_ahead: i32) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT create_subscriber_events_partition(now() + make_interval(months => month))
+            AS "partition!"
+        FROM generate_series(0, $1) AS month
+        "#,
+        months_ahead,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Drops the partitions of the months before the last `retention_months`
+/// ones. The current month isn't counted.
+#[tracing::instrument(name = "Drop expired event partitions", skip(pool))]
+async fn drop_expired_partitions(
+    pool: &PgPool,
+    retention_months: i32,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT partition AS "partition!"
+        FROM drop_subscriber_events_partitions(
+            (date_trunc('month', now() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC')
+                - make_interval(months => $1)
+        ) AS partition
+        "#,
+        retention_months,
+    )
+    .fetch_all(pool)
+    .await
+    .map(|rows| rows.into_iter().map(|r| r.partition).collect())
+}
+
+pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let pool = get_connection_pool(&configuration.database);
+    let settings = configuration.maintenance;
+    let mut election = LeaderElection::new(
+        pool.clone(),
+        SingletonJob::Maintenance,
+        &configuration.application.instance_id,
+    );
+
+    loop {
+        if election.is_leader().await {
+            // Failures are logged and retried on the next run.
+            let _ = run_maintenance(&pool, &settings).await;
+        }
+        tokio::time::sleep(settings.interval()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::current_window_start;
+
+    #[test]
+    fn the_window_starts_on_the_same_day() {
+        let now = Utc.with_ymd_and_hms(2024, 10, 23, 4, 30, 0).unwrap();
+
+        assert_eq!(
+            current_window_start(now, 3, 5),
+            Some(Utc.with_ymd_and_hms(2024, 10, 23, 3, 0, 0).unwrap())
+        );
+        assert_eq!(current_window_start(now, 5, 7), None);
+    }
+
+    #[test]
+    fn windows_can_wrap_around_midnight() {
+        let after_midnight = Utc.with_ymd_and_hms(2024, 10, 23, 1, 0, 0).unwrap();
+        let before_midnight = Utc.with_ymd_and_hms(2024, 10, 23, 23, 30, 0).unwrap();
+
+        assert_eq!(
+            current_window_start(after_midnight, 23, 2),
+            Some(Utc.with_ymd_and_hms(2024, 10, 22, 23, 0, 0).unwrap())
+        );
+        assert_eq!(
+            current_window_start(before_midnight, 23, 2),
+            Some(Utc.with_ymd_and_hms(2024, 10, 23, 23, 0, 0).unwrap())
+        );
+        assert_eq!(current_window_start(after_midnight, 23, 1), None);
+    }
+}
@@ -0,0 +1,154 @@
+use std::sync::{Arc, RwLock};
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{ContentType, RETRY_AFTER},
+    middleware::Next,
+    web, HttpResponse,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+
+use crate::{configuration::MaintenanceModeSettings, util::e500};
+
+/// Takes the public endpoints offline and pauses the delivery of issues,
+/// while the admin UI stays reachable.
+///
+/// The state is shared by the API and the delivery worker. When an end time
+/// is set, maintenance is left on its own once it's reached.
+#[derive(Clone, Debug)]
+pub struct MaintenanceMode {
+    /// Set while in maintenance, holding the time it ends at, if any.
+    window: Arc<RwLock<Option<Option<DateTime<Utc>>>>>,
+    /// Suggested to clients when maintenance has no end time.
+    retry_after_seconds: u64,
+}
+
+impl MaintenanceMode {
+    pub fn new(settings: &MaintenanceModeSettings) -> Self {
+        let window = settings.enabled.then_some(settings.until);
+
+        Self {
+            window: Arc::new(RwLock::new(window)),
+            retry_after_seconds: settings.retry_after_seconds,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.is_active_at(Utc::now())
+    }
+
+    fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        match *self.window.read().unwrap() {
+            None => return false,
+            Some(None) => return true,
+            Some(Some(until)) if now < until => return true,
+            Some(Some(_)) => {}
+        }
+
+        let mut window = self.window.write().unwrap();
+        if matches!(*window, Some(Some(until)) if now >= until) {
+            *window = None;
+            tracing::info!("Maintenance window is over, leaving maintenance mode");
+        }
+
+        window.is_some()
+    }
+
+    /// Seconds clients should wait before trying again.
+    pub fn retry_after(&self) -> u64 {
+        self.retry_after_at(Utc::now())
+    }
+
+    fn retry_after_at(&self, now: DateTime<Utc>) -> u64 {
+        match *self.window.read().unwrap() {
+            Some(Some(until)) => (until - now).num_seconds().max(1) as u64,
+            _ => self.retry_after_seconds,
+        }
+    }
+}
+
+fn maintenance_page(retry_after: u64) -> HttpResponse {
+    HttpResponse::ServiceUnavailable()
+        .insert_header((RETRY_AFTER, retry_after.to_string()))
+        .content_type(ContentType::html())
+        .body(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Down for maintenance</title>
+</head>
+<body>
+    <p>We are doing some maintenance and will be back shortly.</p>
+    <p>Please try again in a few minutes.</p>
+</body>
+</html>"#,
+        )
+}
+
+/// Answers public endpoints with a maintenance page while in maintenance.
+pub async fn reject_during_maintenance(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let maintenance_mode = req
+        .app_data::<web::Data<MaintenanceMode>>()
+        .context("Maintenance mode is not registered in the application")
+        .map_err(e500)?;
+
+    if maintenance_mode.is_active() {
+        let response = maintenance_page(maintenance_mode.retry_after());
+
+        return Ok(req.into_response(response));
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_boxed_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use crate::configuration::MaintenanceModeSettings;
+
+    use super::MaintenanceMode;
+
+    fn maintenance_mode(enabled: bool, until: Option<chrono::DateTime<Utc>>) -> MaintenanceMode {
+        MaintenanceMode::new(&MaintenanceModeSettings {
+            enabled,
+            until,
+            retry_after_seconds: 600,
+        })
+    }
+
+    #[test]
+    fn maintenance_without_end_time_lasts_until_disabled() {
+        let maintenance_mode = maintenance_mode(true, None);
+
+        assert!(maintenance_mode.is_active_at(Utc::now() + Duration::days(365)));
+        assert_eq!(maintenance_mode.retry_after(), 600);
+    }
+
+    #[test]
+    fn disabled_maintenance_ignores_the_end_time() {
+        let maintenance_mode = maintenance_mode(false, Some(Utc::now() + Duration::hours(1)));
+
+        assert!(!maintenance_mode.is_active());
+    }
+
+    #[test]
+    fn maintenance_is_left_at_the_end_time() {
+        let now = Utc::now();
+        let maintenance_mode = maintenance_mode(true, Some(now + Duration::minutes(5)));
+
+        assert!(maintenance_mode.is_active_at(now));
+        assert_eq!(maintenance_mode.retry_after_at(now), 300);
+        assert!(!maintenance_mode.is_active_at(now + Duration::minutes(5)));
+        // Once left, it doesn't come back.
+        assert!(!maintenance_mode.is_active_at(now));
+    }
+}
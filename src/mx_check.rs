@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use hickory_resolver::{
+    config::{ResolverConfig, ResolverOpts},
+    error::ResolveErrorKind,
+    proto::op::ResponseCode,
+    TokioAsyncResolver,
+};
+
+use crate::domain::Email;
+
+/// Domains remembered at most, so that a flood of made up domains can't grow
+/// the cache without bounds.
+const MAX_CACHED_DOMAINS: usize = 10_000;
+
+/// Tells whether a domain has a mail server.
+#[async_trait::async_trait]
+pub trait MxResolver: Send + Sync {
+    async fn receives_mail(&self, domain: &str) -> Result<bool, anyhow::Error>;
+}
+
+/// Resolves with the nameservers of the system, or with public ones when
+/// the system has none configured.
+pub struct DnsMxResolver(TokioAsyncResolver);
+
+impl DnsMxResolver {
+    pub fn from_system_conf() -> Self {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf().unwrap_or_else(|e| {
+            tracing::warn!(
+                error.message = %e,
+                "Failed to read the DNS configuration of the system, using public nameservers"
+            );
+            TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())
+        });
+
+        Self(resolver)
+    }
+}
+
+#[async_trait::async_trait]
+impl MxResolver for DnsMxResolver {
+    /// A domain without MX records still receives mail on its address
+    /// (RFC 5321, 5.1), unless it doesn't exist. A single MX record pointing
+    /// to the root is a null MX (RFC 7505), a domain refusing all mail.
+    async fn receives_mail(&self, domain: &str) -> Result<bool, anyhow::Error> {
+        // Fully qualified, so that the search domains of the host aren't tried.
+        let fqdn = format!("{}.", domain.trim_end_matches('.'));
+
+        match self.0.mx_lookup(fqdn.as_str()).await {
+            Ok(lookup) => Ok(lookup.iter().any(|mx| !mx.exchange().is_root())),
+            Err(e) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { response_code, .. }
+                    if *response_code == ResponseCode::NXDomain =>
+                {
+                    Ok(false)
+                }
+                ResolveErrorKind::NoRecordsFound { .. } => {
+                    match self.0.lookup_ip(fqdn.as_str()).await {
+                        Ok(lookup) => Ok(lookup.iter().next().is_some()),
+                        Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {
+                            Ok(false)
+                        }
+                        Err(e) => Err(e.into()),
+                    }
+                }
+                _ => Err(e.into()),
+            },
+        }
+    }
+}
+
+/// Rejects subscriptions of addresses whose domain can't receive mail,
+/// before a confirmation email is wasted on them.
+///
+/// Lookups are cached per domain and bounded by a timeout. When the resolver
+/// fails or is too slow the address is accepted: a DNS outage shouldn't stop
+/// people from subscribing.
+pub struct MxCheck {
+    enabled: bool,
+    resolver: Box<dyn MxResolver>,
+    timeout: Duration,
+    cache_ttl: Duration,
+    cache: Mutex<HashMap<String, (Instant, bool)>>,
+}
+
+impl MxCheck {
+    pub fn new(
+        enabled: bool,
+        resolver: Box<dyn MxResolver>,
+        timeout: Duration,
+        cache_ttl: Duration,
+    ) -> Self {
+        Self {
+            enabled,
+            resolver,
+            timeout,
+            cache_ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the domain of the email can receive mail, always the case
+    /// when the check is disabled.
+    #[tracing::instrument(name = "Check email domain", skip(self, email))]
+    pub async fn accepts(&self, email: &Email) -> bool {
+        if !self.enabled {
+            return true;
+        }
+
+        let domain = match email.as_ref().rsplit_once('@') {
+            Some((_, domain)) => domain.to_lowercase(),
+            None => return true,
+        };
+
+        if let Some((checked_at, receives_mail)) = self.cache.lock().unwrap().get(&domain) {
+            if checked_at.elapsed() < self.cache_ttl {
+                return *receives_mail;
+            }
+        }
+
+        let receives_mail =
+            match tokio::time::timeout(self.timeout, self.resolver.receives_mail(&domain)).await {
+                Ok(Ok(receives_mail)) => receives_mail,
+                Ok(Err(e)) => {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to look up the mail servers of {}",
+                        domain
+                    );
+                    return true;
+                }
+                Err(_) => {
+                    tracing::warn!("Looking up the mail servers of {} timed out", domain);
+                    return true;
+                }
+            };
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED_DOMAINS {
+            let cache_ttl = self.cache_ttl;
+            cache.retain(|_, (checked_at, _)| checked_at.elapsed() < cache_ttl);
+            if cache.len() >= MAX_CACHED_DOMAINS {
+                cache.clear();
+            }
+        }
+        cache.insert(domain, (Instant::now(), receives_mail));
+
+        receives_mail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use crate::domain::Email;
+
+    use super::{MxCheck, MxResolver};
+
+    /// Answers from a fixed list of domains receiving mail, counting lookups.
+    struct FakeResolver {
+        domains: &'static [&'static str],
+        delay: Duration,
+        lookups: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl MxResolver for FakeResolver {
+        async fn receives_mail(&self, domain: &str) -> Result<bool, anyhow::Error> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+
+            Ok(self.domains.contains(&domain))
+        }
+    }
+
+    fn mx_check(
+        enabled: bool,
+        delay: Duration,
+        cache_ttl: Duration,
+    ) -> (MxCheck, Arc<AtomicUsize>) {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let resolver = FakeResolver {
+            domains: &["gmail.com"],
+            delay,
+            lookups: lookups.clone(),
+        };
+        let check = MxCheck::new(
+            enabled,
+            Box::new(resolver),
+            Duration::from_millis(200),
+            cache_ttl,
+        );
+
+        (check, lookups)
+    }
+
+    fn email(email: &str) -> Email {
+        Email::parse(email.into()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn disabled_check_does_not_resolve_anything() {
+        let (check, lookups) = mx_check(false, Duration::ZERO, Duration::ZERO);
+
+        assert!(check.accepts(&email("ursula@nowhere.invalid")).await);
+        assert_eq!(lookups.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn only_domains_receiving_mail_are_accepted() {
+        let (check, _) = mx_check(true, Duration::ZERO, Duration::ZERO);
+
+        assert!(check.accepts(&email("ursula@gmail.com")).await);
+        assert!(check.accepts(&email("ursula@GMAIL.com")).await);
+        assert!(!check.accepts(&email("ursula@nowhere.invalid")).await);
+    }
+
+    #[tokio::test]
+    async fn domains_are_resolved_once_while_cached() {
+        let (check, lookups) = mx_check(true, Duration::ZERO, Duration::from_secs(60));
+
+        assert!(!check.accepts(&email("ursula@nowhere.invalid")).await);
+        assert!(!check.accepts(&email("le.guin@nowhere.invalid")).await);
+
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn slow_lookups_are_accepted_and_not_cached() {
+        let (check, lookups) = mx_check(true, Duration::from_secs(1), Duration::from_secs(60));
+
+        assert!(check.accepts(&email("ursula@nowhere.invalid")).await);
+        assert!(check.accepts(&email("ursula@nowhere.invalid")).await);
+
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,20 @@
+use sqlx::PgPool;
+use uuid::{uuid, Uuid};
+
+/// The list subscriptions and issues belong to unless another one is picked.
+pub const DEFAULT_LIST_ID: Uuid = uuid!("00000000-0000-0000-0000-000000000001");
+
+#[tracing::instrument(name = "Check newsletter list existence", skip(pool))]
+pub async fn list_exists(pool: &PgPool, list_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT 1 as exists
+        FROM newsletter_lists
+        WHERE list_id = $1
+        "#,
+        list_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|r| r.is_some())
+}
@@ -0,0 +1,69 @@
+use utoipa::{
+    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+    Modify, OpenApi,
+};
+
+/// Where the spec is served, Swagger UI being served from `/api/docs/`.
+pub const OPENAPI_PATH: &str = "/api/docs/openapi.json";
+
+/// Spec of the JSON endpoints, for integrators to build their clients from.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Newsletter API",
+        description = "Errors are answered with `application/problem+json` bodies."
+    ),
+    paths(
+        crate::routes::subscribe,
+        crate::routes::confirm,
+        crate::routes::api_publish_newsletter,
+        crate::routes::api_invite_collaborator,
+        crate::routes::get_stats,
+        crate::routes::list_subscribers,
+        crate::routes::create_subscriber,
+        crate::routes::get_subscriber,
+        crate::routes::update_subscriber,
+        crate::routes::delete_subscriber,
+    ),
+    components(schemas(crate::api_error::ProblemDetails)),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "subscriptions", description = "Subscribing to the newsletter"),
+        (name = "admin", description = "Publishing and administration"),
+        (name = "subscribers", description = "Managing subscribers"),
+    )
+)]
+pub struct ApiDoc;
+
+/// `/api/v1` clients authenticate with 'Basic' credentials or with a
+/// personal access token.
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "basic",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Basic)),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utoipa::OpenApi;
+
+    use super::ApiDoc;
+
+    #[test]
+    fn both_ways_to_authenticate_are_documented() {
+        let components = ApiDoc::openapi().components.unwrap();
+
+        assert!(components.security_schemes.contains_key("basic"));
+        assert!(components.security_schemes.contains_key("bearer"));
+    }
+}
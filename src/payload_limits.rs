@@ -0,0 +1,100 @@
+use actix_multipart::{form::MultipartFormConfig, MultipartError};
+use actix_web::{
+    error::{JsonPayloadError, PayloadError, UrlencodedError},
+    http::StatusCode,
+    web, ResponseError,
+};
+
+/// A request body over the limit of its route.
+///
+/// Its message is shown on the error page, or as the detail of the problem,
+/// rather than the generic one of 413 responses.
+#[derive(Debug)]
+pub struct PayloadTooLargeError {
+    pub limit: usize,
+}
+
+impl std::fmt::Display for PayloadTooLargeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "What was sent is larger than the {} allowed.",
+            human_size(self.limit)
+        )
+    }
+}
+
+impl std::error::Error for PayloadTooLargeError {}
+
+impl ResponseError for PayloadTooLargeError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::PAYLOAD_TOO_LARGE
+    }
+}
+
+fn human_size(bytes: usize) -> String {
+    const KIB: usize = 1024;
+    const MIB: usize = 1024 * KIB;
+
+    let (mib, kib) = (bytes / MIB, bytes / KIB);
+    if mib > 0 && mib * MIB == bytes {
+        format!("{} MiB", mib)
+    } else if kib > 0 && kib * KIB == bytes {
+        format!("{} KiB", kib)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+/// Url-encoded forms of at most `limit` bytes.
+pub fn form_config(limit: usize) -> web::FormConfig {
+    web::FormConfig::default()
+        .limit(limit)
+        .error_handler(move |e, _| match e {
+            UrlencodedError::Overflow { .. } => PayloadTooLargeError { limit }.into(),
+            e => e.into(),
+        })
+}
+
+/// JSON bodies of at most `limit` bytes.
+pub fn json_config(limit: usize) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(limit)
+        .error_handler(move |e, _| match e {
+            JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+                PayloadTooLargeError { limit }.into()
+            }
+            e => e.into(),
+        })
+}
+
+/// Multipart forms of at most `limit` bytes, held in memory.
+pub fn multipart_form_config(limit: usize) -> MultipartFormConfig {
+    MultipartFormConfig::default()
+        .total_limit(limit)
+        .memory_limit(limit)
+        .error_handler(move |e, _| match e {
+            MultipartError::Payload(PayloadError::Overflow) => {
+                PayloadTooLargeError { limit }.into()
+            }
+            e => e.into(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PayloadTooLargeError;
+
+    #[test]
+    fn limits_are_shown_in_the_largest_whole_unit() {
+        for (limit, shown) in [
+            (4 * 1024, "4 KiB"),
+            (32 * 1024 * 1024, "32 MiB"),
+            (1536, "1536 bytes"),
+            (512, "512 bytes"),
+        ] {
+            let message = PayloadTooLargeError { limit }.to_string();
+            assert!(message.contains(shown), "{}", message);
+        }
+    }
+}
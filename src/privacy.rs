@@ -0,0 +1,204 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    domain::SubscriptionStatus,
+    subscriber_events::{get_subscriber_events, SubscriberEvent},
+};
+
+/// Everything stored about a subscriber, as handed over on an access request.
+#[derive(Debug, serde::Serialize)]
+pub struct SubscriberData {
+    pub subscription: Subscription,
+    pub tags: Vec<String>,
+    pub topics: Vec<String>,
+    pub email_changes: Vec<EmailChange>,
+    pub deliveries: Vec<Delivery>,
+    pub bounces: Vec<Bounce>,
+    pub engagement: Vec<EngagementEvent>,
+    pub events: Vec<SubscriberEvent>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Subscription {
+    pub id: Uuid,
+    pub list_id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: SubscriptionStatus,
+    pub subscribed_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub erased_at: Option<DateTime<Utc>>,
+    /// Answers to the custom signup fields.
+    pub attributes: serde_json::Value,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct EmailChange {
+    pub old_email: String,
+    pub new_email: String,
+    pub requested_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Delivery {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub delivered_at: DateTime<Utc>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Bounce {
+    pub newsletter_issue_id: Option<Uuid>,
+    pub category: String,
+    pub bounce_type: Option<String>,
+    pub bounced_at: DateTime<Utc>,
+}
+
+/// An open or a click of an issue.
+#[derive(Debug, serde::Serialize)]
+pub struct EngagementEvent {
+    pub newsletter_issue_id: Uuid,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub url: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// Gathers the data of a subscriber from every table keeping some. The
+/// history of issues is the one of its list, as the same address may be
+/// subscribed to others.
+#[tracing::instrument(name = "Collect subscriber data", skip(pool))]
+pub async fn collect_subscriber_data(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberData>, sqlx::Error> {
+    let Some(subscription) = sqlx::query_as!(
+        Subscription,
+        r#"
+        SELECT
+            id,
+            list_id,
+            email,
+            name,
+            status AS "status: SubscriptionStatus",
+            subscribed_at,
+            confirmed_at,
+            erased_at,
+            subscriber_attributes AS attributes
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let tags = sqlx::query!(
+        r#"
+        SELECT tag
+        FROM subscriber_tags
+        WHERE subscriber_id = $1
+        ORDER BY tag
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| r.tag)
+    .collect();
+
+    let topics = sqlx::query!(
+        r#"
+        SELECT topics.name
+        FROM subscriber_topics
+        JOIN topics ON topics.id = subscriber_topics.topic_id
+        WHERE subscriber_topics.subscriber_id = $1
+        ORDER BY topics.name
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| r.name)
+    .collect();
+
+    let email_changes = sqlx::query_as!(
+        EmailChange,
+        r#"
+        SELECT old_email, new_email, requested_at, confirmed_at
+        FROM subscriber_email_changes
+        WHERE subscriber_id = $1
+        ORDER BY requested_at
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let deliveries = sqlx::query_as!(
+        Delivery,
+        r#"
+        SELECT d.newsletter_issue_id, i.title, d.delivered_at
+        FROM issue_deliveries d
+        JOIN newsletter_issues i ON i.newsletter_issue_id = d.newsletter_issue_id
+        WHERE d.subscriber_email = $1 AND i.list_id = $2
+        ORDER BY d.delivered_at
+        "#,
+        subscription.email,
+        subscription.list_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let bounces = sqlx::query_as!(
+        Bounce,
+        r#"
+        SELECT b.newsletter_issue_id, b.category, b.bounce_type, b.bounced_at
+        FROM email_bounces b
+        LEFT JOIN newsletter_issues i ON i.newsletter_issue_id = b.newsletter_issue_id
+        WHERE b.subscriber_email = $1
+          AND (b.newsletter_issue_id IS NULL OR i.list_id = $2)
+        ORDER BY b.bounced_at
+        "#,
+        subscription.email,
+        subscription.list_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let engagement = sqlx::query_as!(
+        EngagementEvent,
+        r#"
+        SELECT e.newsletter_issue_id, e.event_type, e.url, e.occurred_at
+        FROM email_events e
+        JOIN newsletter_issues i ON i.newsletter_issue_id = e.newsletter_issue_id
+        WHERE e.subscriber_email = $1 AND i.list_id = $2
+        ORDER BY e.occurred_at
+        "#,
+        subscription.email,
+        subscription.list_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let events = get_subscriber_events(pool, subscriber_id, None, i64::MAX).await?;
+
+    Ok(Some(SubscriberData {
+        subscription,
+        tags,
+        topics,
+        email_changes,
+        deliveries,
+        bounces,
+        engagement,
+        events,
+    }))
+}
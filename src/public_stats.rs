@@ -0,0 +1,129 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use sqlx::PgPool;
+
+use crate::{configuration::PublicStatsSettings, domain::SubscriptionStatus};
+
+/// Figures safe to show on marketing pages.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct Stats {
+    /// Fuzzed and rounded, it never tells the exact size of the list.
+    pub subscribers: u64,
+    pub issues_published: u64,
+    pub last_issue_published_at: Option<DateTime<Utc>>,
+}
+
+/// Serves [`Stats`] from aggregates computed at most once per cache period,
+/// so that the public endpoint can't be used to hammer the database.
+///
+/// The noise added to the subscriber count is drawn once per computation:
+/// asking again while the result is cached gives the same figure back, so
+/// averaging many answers doesn't reveal the exact count.
+pub struct PublicStats {
+    cache_ttl: Duration,
+    rounding: u64,
+    noise_scale: f64,
+    cached: Mutex<Option<(Instant, Stats)>>,
+}
+
+impl PublicStats {
+    pub fn new(settings: &PublicStatsSettings) -> Self {
+        Self {
+            cache_ttl: settings.cache_ttl(),
+            rounding: settings.rounding,
+            noise_scale: settings.noise_scale,
+            cached: Mutex::new(None),
+        }
+    }
+
+    #[tracing::instrument(name = "Get public stats", skip_all)]
+    pub async fn get(&self, pool: &PgPool) -> Result<Stats, sqlx::Error> {
+        if let Some((computed_at, stats)) = self.cached.lock().unwrap().as_ref() {
+            if computed_at.elapsed() < self.cache_ttl {
+                return Ok(stats.clone());
+            }
+        }
+
+        let aggregates = sqlx::query!(
+            r#"
+            SELECT
+                (SELECT count(DISTINCT email) FROM subscriptions WHERE status = $1) AS "subscribers!",
+                (SELECT count(*) FROM newsletter_issues) AS "issues_published!",
+                (SELECT max(published_at) FROM newsletter_issues) AS last_issue_published_at
+            "#,
+            SubscriptionStatus::Confirmed as SubscriptionStatus,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        let stats = Stats {
+            subscribers: fuzz(
+                aggregates.subscribers as u64,
+                self.rounding,
+                self.noise_scale,
+                &mut rand::thread_rng(),
+            ),
+            issues_published: aggregates.issues_published as u64,
+            last_issue_published_at: aggregates.last_issue_published_at,
+        };
+        *self.cached.lock().unwrap() = Some((Instant::now(), stats.clone()));
+
+        Ok(stats)
+    }
+}
+
+/// Adds Laplace noise of the given scale to the count, then rounds it to the
+/// nearest multiple of `rounding`.
+fn fuzz(count: u64, rounding: u64, noise_scale: f64, rng: &mut impl Rng) -> u64 {
+    let noise = if noise_scale > 0. {
+        let u: f64 = rng.gen_range(-0.5..0.5);
+        -noise_scale * u.signum() * (1. - 2. * u.abs()).ln()
+    } else {
+        0.
+    };
+    let rounding = rounding.max(1) as f64;
+    let fuzzed = ((count as f64 + noise) / rounding).round() * rounding;
+
+    fuzzed.max(0.) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::fuzz;
+
+    #[test]
+    fn counts_are_rounded_to_the_nearest_multiple() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_eq!(fuzz(1234, 100, 0., &mut rng), 1200);
+        assert_eq!(fuzz(1250, 100, 0., &mut rng), 1300);
+        assert_eq!(fuzz(7, 0, 0., &mut rng), 7);
+    }
+
+    #[test]
+    fn noisy_counts_stay_rounded() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for _ in 0..1000 {
+            let fuzzed = fuzz(3, 10, 50., &mut rng);
+
+            assert_eq!(fuzzed % 10, 0);
+        }
+    }
+
+    #[test]
+    fn noise_changes_the_count() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let fuzzed: Vec<u64> = (0..100).map(|_| fuzz(1000, 1, 10., &mut rng)).collect();
+
+        assert!(fuzzed.iter().any(|count| *count != 1000));
+    }
+}
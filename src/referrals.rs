@@ -0,0 +1,337 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    domain::{Email, Locale},
+    email_client::EmailClient,
+    email_log::EmailKind,
+    template::{render_issue_footer, render_referral_reward, Template},
+};
+
+/// Referrers listed on the admin referrals page.
+const LEADERBOARD_LENGTH: i64 = 20;
+
+/// Builds the links subscribers refer others with, shown in the footer of
+/// issues and on the preferences page. Nothing is shown when referrals are
+/// disabled.
+#[derive(Clone)]
+pub struct ReferralLinks {
+    enabled: bool,
+    base_url: String,
+}
+
+impl ReferralLinks {
+    pub fn new(enabled: bool, base_url: &str) -> Self {
+        Self {
+            enabled,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Leads to the home page, whose subscription form carries the code.
+    pub fn link(&self, referral_code: &str) -> Option<String> {
+        self.enabled.then(|| {
+            format!(
+                "{}/?referral_code={}",
+                self.base_url,
+                urlencoding::encode(referral_code)
+            )
+        })
+    }
+
+    /// Appends the footer of issues, with the referral link of the
+    /// recipient, to both parts of an issue.
+    pub fn add_footer(
+        &self,
+        mut content: Template,
+        referral_code: &str,
+    ) -> Result<Template, tera::Error> {
+        let Some(link) = self.link(referral_code) else {
+            return Ok(content);
+        };
+        let footer = render_issue_footer(&link)?;
+
+        match content.html.rfind("</body>") {
+            Some(end) => content.html.insert_str(end, &footer.html),
+            None => content.html.push_str(&footer.html),
+        }
+        content.text.push_str("\n\n");
+        content.text.push_str(&footer.text);
+
+        Ok(content)
+    }
+}
+
+/// Confirmed subscriber of the list sharing the code. Unknown codes are
+/// ignored rather than keeping anyone from subscribing.
+#[tracing::instrument(name = "Find referrer", skip(transaction))]
+pub async fn find_referrer(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    referral_code: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT id
+        FROM subscriptions
+        WHERE list_id = $1 AND referral_code = $2 AND status = 'confirmed'
+        "#,
+        list_id,
+        referral_code,
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+}
+
+/// Confirmed subscribers referred by someone, counted on the preferences
+/// page of the referrer.
+#[tracing::instrument(name = "Count referrals", skip(pool))]
+pub async fn count_referrals(pool: &PgPool, subscriber_id: Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "referrals!"
+        FROM subscriptions
+        WHERE referred_by = $1 AND status = 'confirmed'
+        "#,
+        subscriber_id,
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// A referrer who reached one of the reward milestones.
+#[derive(Debug)]
+pub struct ReferralReward {
+    pub referrer_id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub locale: Option<String>,
+    pub referrals: i64,
+}
+
+/// The reward owed to whoever referred a subscriber who was just confirmed,
+/// if the confirmation makes them reach one of `milestones`. It is recorded
+/// along with the confirmation, so that each milestone is rewarded once.
+#[tracing::instrument(name = "Claim referral reward", skip(transaction))]
+pub async fn claim_referral_reward(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    milestones: &[u32],
+) -> Result<Option<ReferralReward>, sqlx::Error> {
+    if milestones.is_empty() {
+        return Ok(None);
+    }
+
+    let Some(reward) = sqlx::query_as!(
+        ReferralReward,
+        r#"
+        SELECT
+            referrer.id AS referrer_id,
+            referrer.email,
+            referrer.name,
+            referrer.locale,
+            (
+                SELECT count(*)
+                FROM subscriptions s
+                WHERE s.referred_by = referrer.id AND s.status = 'confirmed'
+            ) AS "referrals!"
+        FROM subscriptions referred
+        JOIN subscriptions referrer ON referrer.id = referred.referred_by
+        WHERE referred.id = $1 AND referrer.status = 'confirmed'
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    if !milestones.iter().any(|m| i64::from(*m) == reward.referrals) {
+        return Ok(None);
+    }
+
+    let claimed = sqlx::query!(
+        r#"
+        INSERT INTO referral_rewards (subscriber_id, milestone, rewarded_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        reward.referrer_id,
+        reward.referrals as i32,
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected()
+        == 1;
+
+    Ok(claimed.then_some(reward))
+}
+
+/// Thanks a referrer for reaching a milestone. Failures are only logged, the
+/// confirmation that led to it already went through.
+#[tracing::instrument(
+    name = "Send referral reward",
+    skip(email_client, reward),
+    fields(referrer_id = %reward.referrer_id)
+)]
+pub async fn send_referral_reward(email_client: &EmailClient, reward: &ReferralReward) {
+    let result = async {
+        let email = Email::parse(reward.email.clone())
+            .map_err(|e| anyhow::anyhow!("The referrer's address is invalid: {}", e))?;
+        let locale = reward.locale.as_deref().and_then(|l| Locale::parse(l).ok());
+        let template = render_referral_reward(&reward.name, reward.referrals, locale.as_slice())?;
+        email_client
+            .send_email(
+                EmailKind::ReferralReward,
+                &email,
+                "Thank you for spreading the word!",
+                &template.html,
+                &template.text,
+                &[],
+                None,
+            )
+            .await?;
+
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a referral reward",
+        );
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Referrer {
+    pub email: String,
+    pub name: String,
+    pub confirmed: i64,
+    pub pending: i64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct MilestoneProgress {
+    pub milestone: i32,
+    /// Referrers with at least as many confirmed referrals.
+    pub referrers: i64,
+    pub rewarded: i64,
+}
+
+/// What the admin referrals page shows.
+#[derive(Debug, serde::Serialize)]
+pub struct ReferralStats {
+    /// Confirmed subscribers who signed up through a referral link.
+    pub referred_subscribers: i64,
+    /// Most confirmed referrals first.
+    pub leaderboard: Vec<Referrer>,
+    pub milestones: Vec<MilestoneProgress>,
+}
+
+#[tracing::instrument(name = "Compute referral stats", skip(pool))]
+pub async fn referral_stats(
+    pool: &PgPool,
+    milestones: &[u32],
+) -> Result<ReferralStats, sqlx::Error> {
+    let referred_subscribers = sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "referred!"
+        FROM subscriptions
+        WHERE referred_by IS NOT NULL AND status = 'confirmed'
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let leaderboard = sqlx::query_as!(
+        Referrer,
+        r#"
+        SELECT
+            referrer.email,
+            referrer.name,
+            count(*) FILTER (WHERE referred.status = 'confirmed') AS "confirmed!",
+            count(*) FILTER (WHERE referred.status = 'pending_confirmation') AS "pending!"
+        FROM subscriptions referred
+        JOIN subscriptions referrer ON referrer.id = referred.referred_by
+        GROUP BY referrer.id
+        ORDER BY 3 DESC, 4 DESC, referrer.email
+        LIMIT $1
+        "#,
+        LEADERBOARD_LENGTH,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let milestones: Vec<i32> = milestones.iter().map(|m| *m as i32).collect();
+    let milestones = sqlx::query_as!(
+        MilestoneProgress,
+        r#"
+        SELECT
+            m.milestone AS "milestone!",
+            count(r.referrer) FILTER (WHERE r.confirmed >= m.milestone) AS "referrers!",
+            (
+                SELECT count(*) FROM referral_rewards rr WHERE rr.milestone = m.milestone
+            ) AS "rewarded!"
+        FROM unnest($1::int4[]) AS m (milestone)
+        LEFT JOIN (
+            SELECT referred_by AS referrer, count(*) AS confirmed
+            FROM subscriptions
+            WHERE referred_by IS NOT NULL AND status = 'confirmed'
+            GROUP BY referred_by
+        ) r ON true
+        GROUP BY m.milestone
+        ORDER BY m.milestone
+        "#,
+        &milestones,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ReferralStats {
+        referred_subscribers,
+        leaderboard,
+        milestones,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::template::Template;
+
+    use super::ReferralLinks;
+
+    fn issue() -> Template {
+        Template {
+            html: "<html><body><p>Hi</p></body></html>".into(),
+            text: "Hi".into(),
+        }
+    }
+
+    #[test]
+    fn the_footer_goes_at_the_end_of_the_body() {
+        let links = ReferralLinks::new(true, "https://example.com/");
+
+        let content = links.add_footer(issue(), "abc123").unwrap();
+
+        let link = "https://example.com/?referral_code=abc123";
+        assert!(content.html.ends_with("</body></html>"));
+        assert!(content.html.contains(link));
+        assert!(content.text.starts_with("Hi\n\n"));
+        assert!(content.text.contains(link));
+    }
+
+    #[test]
+    fn nothing_is_added_when_referrals_are_disabled() {
+        let links = ReferralLinks::new(false, "https://example.com");
+
+        let content = links.add_footer(issue(), "abc123").unwrap();
+
+        assert_eq!(links.link("abc123"), None);
+        assert_eq!(content.html, issue().html);
+        assert_eq!(content.text, issue().text);
+    }
+}
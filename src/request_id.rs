@@ -0,0 +1,105 @@
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    HttpMessage,
+};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Longest id accepted from clients, e.g. from a proxy in front of us.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: RequestId;
+}
+
+/// Identifies a request in the logs, in its response and in the error page or
+/// problem details shown for it, so that users can quote it when asking for
+/// support.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+
+    /// The id a client or a proxy assigned, unless it could mess up the logs.
+    pub fn parse(s: &str) -> Option<Self> {
+        let valid = !s.is_empty()
+            && s.len() <= MAX_REQUEST_ID_LENGTH
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+
+        valid.then(|| Self(s.to_string()))
+    }
+
+    /// The id of the request being handled, if any.
+    pub fn current() -> Option<Self> {
+        CURRENT_REQUEST_ID.try_with(Clone::clone).ok()
+    }
+}
+
+impl AsRef<str> for RequestId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Takes the `X-Request-Id` of the request, or makes one up, and returns it
+/// in the response. Must wrap `TracingLogger`, whose root span records it.
+pub async fn assign_request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(RequestId::parse)
+        .unwrap_or_else(RequestId::generate);
+    req.extensions_mut().insert(request_id.clone());
+
+    // Holding on to a clone of the request would keep the router from
+    // matching it, errors are left to the outer middlewares instead.
+    let mut response = CURRENT_REQUEST_ID
+        .scope(request_id.clone(), next.call(req))
+        .await?
+        .map_into_boxed_body();
+    if let Ok(value) = HeaderValue::from_str(request_id.as_ref()) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static("x-request-id"), value);
+    }
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RequestId;
+
+    #[test]
+    fn ids_of_proxies_are_kept() {
+        for id in ["f3b1c2d4-1a2b-4c3d-8e9f-0a1b2c3d4e5f", "req_42", "lb.1:abc"] {
+            assert_eq!(RequestId::parse(id).unwrap().as_ref(), id);
+        }
+    }
+
+    #[test]
+    fn ids_that_could_mess_up_the_logs_are_refused() {
+        let too_long = "a".repeat(129);
+        for id in ["", "a b", "<script>", "a\"b", too_long.as_str()] {
+            assert!(RequestId::parse(id).is_none(), "{:?} was accepted", id);
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    configuration::AdminBasePath,
+    delegated_access::{
+        access_log, grant_access, list_grants, revoke_access, CapabilitySigner, DelegatedScope,
+    },
+    routes::admin::navigation_menu,
+    session_state::TypedSession,
+    startup::ApplicationBaseUrl,
+    user_role::UserRole,
+    util::see_other,
+};
+
+use super::actions::{reject_non_admin_users, AdminActionError};
+
+/// Access links can't be made to last longer than this.
+pub const MAX_ACCESS_LINK_DAYS: i64 = 90;
+
+/// Access links given so far, how much they were used, and a form to give
+/// a new one.
+#[tracing::instrument(
+    name = "Get access links page",
+    skip(flash_messages, session, pool, admin_base_path)
+)]
+pub async fn access_links_page(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+
+    let csrf_token = session.csrf_token()?;
+    let admin: &str = admin_base_path.get_ref().as_ref();
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+    let grants = list_grants(&pool)
+        .await
+        .context("Failed to retrieve the access links")?;
+
+    let now = Utc::now();
+    let mut grants_html = String::new();
+    for grant in grants {
+        let state = if grant.is_live(now) {
+            format!(
+                r#"<form action="{admin}/access_links/{id}/revoke" method="post">
+                <input type="hidden" name="csrf_token" value="{csrf_token}">
+                <button type="submit">Revoke</button>
+            </form>"#,
+                id = grant.grant_id,
+            )
+        } else if grant.revoked_at.is_some() {
+            "Revoked".to_string()
+        } else {
+            "Expired".to_string()
+        };
+        let last_access = grant
+            .last_accessed_at
+            .map(|at| at.format("%Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_else(|| "never".to_string());
+
+        writeln!(
+            grants_html,
+            r#"<tr>
+            <td>{recipient}</td>
+            <td>{scope}</td>
+            <td>{granted_by}</td>
+            <td>{expires_at}</td>
+            <td><a href="{admin}/access_links/{id}">{accesses}</a>, last {last_access}</td>
+            <td>{state}</td>
+        </tr>"#,
+            recipient = htmlescape::encode_minimal(&grant.recipient),
+            scope = htmlescape::encode_minimal(&grant.scope),
+            granted_by = htmlescape::encode_minimal(&grant.granted_by),
+            expires_at = grant.expires_at.format("%Y-%m-%d %H:%M UTC"),
+            id = grant.grant_id,
+            accesses = grant.accesses,
+        )
+        .unwrap();
+    }
+
+    let mut scopes_html = String::new();
+    for scope in DelegatedScope::ALL {
+        writeln!(
+            scopes_html,
+            r#"<option value="{}">{}</option>"#,
+            scope.as_str(),
+            scope.title()
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Access links</title>
+</head>
+<body>
+    {navigation}
+    {msg_html}
+    <form action="{admin}/access_links" method="post">
+        <label>For
+            <input type="text" placeholder="Who the link is for" name="recipient">
+        </label>
+        <label>Allowing
+            <select name="scope">{scopes_html}</select>
+        </label>
+        <label>Days
+            <input type="number" name="days" value="7" min="1" max="{MAX_ACCESS_LINK_DAYS}">
+        </label>
+        <input type="hidden" name="csrf_token" value="{csrf_token}">
+        <button type="submit">Create link</button>
+    </form>
+    <table>
+        <tr>
+            <th>For</th><th>Scope</th><th>Given by</th><th>Expires at</th>
+            <th>Requests</th><th></th>
+        </tr>
+        {grants_html}
+    </table>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct AccessLinkFormData {
+    recipient: String,
+    scope: String,
+    days: i64,
+}
+
+/// Gives an access link, shown once in a flash message: only its grant is
+/// stored.
+#[tracing::instrument(
+    name = "Create access link",
+    skip(form, session, pool, signer, base_url, admin_base_path),
+    fields(recipient = %form.recipient, scope = %form.scope, days = form.days)
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_access_link(
+    form: web::Form<AccessLinkFormData>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    signer: web::Data<CapabilitySigner>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let access_links_path = admin_base_path.join("/access_links");
+    let form = form.into_inner();
+    let recipient = form.recipient.trim();
+    if recipient.is_empty() {
+        FlashMessage::error("Tell who the access link is for.").send();
+        return Ok(see_other(&access_links_path));
+    }
+    let Ok(scope) = DelegatedScope::try_from(form.scope) else {
+        FlashMessage::error("Unknown scope.").send();
+        return Ok(see_other(&access_links_path));
+    };
+    if !(1..=MAX_ACCESS_LINK_DAYS).contains(&form.days) {
+        FlashMessage::error(format!(
+            "Access links last from 1 to {} days.",
+            MAX_ACCESS_LINK_DAYS
+        ))
+        .send();
+        return Ok(see_other(&access_links_path));
+    }
+
+    let capability = grant_access(
+        &pool,
+        **user_id,
+        recipient,
+        scope,
+        Duration::days(form.days),
+    )
+    .await
+    .context("Failed to store the access grant")?;
+    let link = format!(
+        "{}/shared/{}",
+        base_url.0.trim_end_matches('/'),
+        signer.token(&capability)
+    );
+
+    FlashMessage::info(format!(
+        "Access link for {}, only shown now: {}",
+        recipient, link
+    ))
+    .send();
+
+    Ok(see_other(&access_links_path))
+}
+
+#[tracing::instrument(name = "Revoke access link", skip(session, pool, admin_base_path))]
+pub async fn revoke_access_link(
+    grant_id: web::Path<Uuid>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let revoked = revoke_access(&pool, grant_id.into_inner(), **user_id)
+        .await
+        .context("Failed to revoke the access grant")?;
+
+    if revoked {
+        FlashMessage::info("The access link was revoked.").send();
+    } else {
+        FlashMessage::error("The access link doesn't exist or no longer works.").send();
+    }
+
+    Ok(see_other(&admin_base_path.join("/access_links")))
+}
+
+/// Every request made with an access link.
+#[tracing::instrument(
+    name = "Get access link log page",
+    skip(session, pool, admin_base_path)
+)]
+pub async fn access_link_log_page(
+    grant_id: web::Path<Uuid>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+    let accesses = access_log(&pool, grant_id.into_inner())
+        .await
+        .context("Failed to retrieve the access log")?;
+
+    let mut accesses_html = String::new();
+    for access in accesses {
+        writeln!(
+            accesses_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            access.accessed_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            htmlescape::encode_minimal(&access.method),
+            htmlescape::encode_minimal(&access.path),
+            access.status
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Access log</title>
+</head>
+<body>
+    {navigation}
+    <table>
+        <tr><th>At</th><th>Method</th><th>Path</th><th>Status</th></tr>
+        {accesses_html}
+    </table>
+</body>
+</html>"#,
+        )))
+}
@@ -0,0 +1,130 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{types::Json, PgPool};
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    admin_action::AdminAction, authentication::UserId, configuration::AdminBasePath,
+    routes::admin::navigation_menu, session_state::TypedSession, user_role::UserRole,
+};
+
+use super::{reject_non_admin_users, AdminActionError};
+
+#[derive(serde::Serialize)]
+pub struct PendingAction {
+    pub id: Uuid,
+    pub payload: Json<AdminAction>,
+    pub requested_by: Uuid,
+    pub requester: String,
+    pub requested_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Get pending admin actions", skip(pool))]
+pub async fn get_pending_actions(pool: &PgPool) -> Result<Vec<PendingAction>, sqlx::Error> {
+    sqlx::query_as!(
+        PendingAction,
+        r#"
+        SELECT a.id, a.payload as "payload: Json<AdminAction>", a.requested_by,
+            u.username as requester, a.requested_at
+        FROM admin_actions a
+        JOIN users u ON u.user_id = a.requested_by
+        WHERE a.status = 'pending'
+        ORDER BY a.requested_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn pending_actions(
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    flash_messages: IncomingFlashMessages,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+
+    let csrf_token = session.csrf_token()?;
+    let admin: &str = admin_base_path.get_ref().as_ref();
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+    let actions = get_pending_actions(&pool)
+        .await
+        .context("Failed to retrieve pending admin actions")?;
+
+    let mut actions_html = String::new();
+    for action in actions {
+        let review_html = if action.requested_by == **user_id {
+            "Waiting for another admin".to_string()
+        } else {
+            format!(
+                r#"<form action="{admin}/actions/{id}/approve" method="post">
+                <input type="hidden" name="csrf_token" value="{csrf_token}">
+                <button type="submit">Approve</button>
+            </form>
+            <form action="{admin}/actions/{id}/reject" method="post">
+                <input type="hidden" name="csrf_token" value="{csrf_token}">
+                <button type="submit">Reject</button>
+            </form>"#,
+                id = action.id
+            )
+        };
+
+        writeln!(
+            actions_html,
+            r#"<li>
+            <p>{description}</p>
+            <p>Requested by {requester} at {requested_at}</p>
+            {review_html}
+        </li>"#,
+            description = htmlescape::encode_minimal(&action.payload.to_string()),
+            requester = htmlescape::encode_minimal(&action.requester),
+            requested_at = action.requested_at.to_rfc3339(),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Pending actions</title>
+</head>
+<body>
+    {navigation}
+    {msg_html}
+    <p>Pending actions:</p>
+    <ol>
+        {actions_html}
+    </ol>
+    <form action="{admin}/subscribers/delete" method="post">
+        <input type="hidden" name="csrf_token" value="{csrf_token}">
+        <label>Subscribers to delete
+            <textarea
+                placeholder="Enter the emails, one per line"
+                name="emails"
+            ></textarea>
+        </label>
+        <br>
+        <button type="submit">Delete subscribers</button>
+    </form>
+</body>
+</html>"#,
+        )))
+}
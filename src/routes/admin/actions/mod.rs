@@ -0,0 +1,82 @@
+mod get;
+mod post;
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use anyhow::Context;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    routes::error_chain_fmt,
+    session_state::TypedSession,
+    user_role::UserRole,
+};
+
+pub use get::*;
+pub use post::*;
+
+#[derive(thiserror::Error)]
+pub enum AdminActionError {
+    #[error("Restricted operation")]
+    NonAdminError,
+    #[error("You must provide at least one email.")]
+    MissingEmails,
+    #[error("\"{0}\" is not a valid email.")]
+    InvalidEmail(String),
+    #[error("The action is no longer pending.")]
+    NotPendingError,
+    #[error("The action must be reviewed by another admin.")]
+    SelfReviewError,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for AdminActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for AdminActionError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AdminActionError::NonAdminError => StatusCode::METHOD_NOT_ALLOWED,
+            AdminActionError::MissingEmails | AdminActionError::InvalidEmail(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            AdminActionError::NotPendingError => StatusCode::CONFLICT,
+            AdminActionError::SelfReviewError => StatusCode::FORBIDDEN,
+            AdminActionError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for AdminActionError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            AdminActionError::NonAdminError => "restricted-operation",
+            AdminActionError::MissingEmails | AdminActionError::InvalidEmail(_) => "invalid-emails",
+            AdminActionError::NotPendingError => "action-not-pending",
+            AdminActionError::SelfReviewError => "self-review",
+            AdminActionError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+pub fn reject_non_admin_users(session: &TypedSession) -> Result<(), AdminActionError> {
+    let role = session
+        .get_user_role()
+        .context("Failed to get user rule from its session")?;
+
+    reject_non_admin_roles(role)
+}
+
+pub fn reject_non_admin_roles(role: Option<UserRole>) -> Result<(), AdminActionError> {
+    match role {
+        Some(UserRole::Admin) => Ok(()),
+        _ => Err(AdminActionError::NonAdminError),
+    }
+}
@@ -0,0 +1,214 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{types::Json, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    admin_action::{AdminAction, AdminActionStatus},
+    authentication::UserId,
+    configuration::AdminBasePath,
+    routes::{admin::subscribers::delete_subscribers, schedule_newsletter_issue},
+    session_state::TypedSession,
+    util::see_other,
+};
+
+use super::{reject_non_admin_users, AdminActionError};
+
+struct StoredAction {
+    payload: Json<AdminAction>,
+    requested_by: Uuid,
+    status: AdminActionStatus,
+}
+
+#[tracing::instrument(name = "Lock admin action", skip(transaction))]
+async fn lock_action(
+    transaction: &mut Transaction<'_, Postgres>,
+    action_id: Uuid,
+) -> Result<Option<StoredAction>, sqlx::Error> {
+    sqlx::query_as!(
+        StoredAction,
+        r#"
+        SELECT payload as "payload: Json<AdminAction>", requested_by,
+            status as "status: AdminActionStatus"
+        FROM admin_actions
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        action_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+}
+
+#[tracing::instrument(name = "Mark admin action as reviewed", skip(transaction))]
+async fn mark_as_reviewed(
+    transaction: &mut Transaction<'_, Postgres>,
+    action_id: Uuid,
+    reviewed_by: Uuid,
+    status: AdminActionStatus,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE admin_actions
+        SET status = $1, reviewed_by = $2, reviewed_at = $3
+        WHERE id = $4
+        "#,
+        status as AdminActionStatus,
+        reviewed_by,
+        Utc::now(),
+        action_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Executes the action on behalf of whoever requested it.
+#[tracing::instrument(name = "Execute admin action", skip(transaction, action))]
+async fn execute_action(
+    transaction: &mut Transaction<'_, Postgres>,
+    action: &AdminAction,
+    requested_by: Uuid,
+) -> Result<(), anyhow::Error> {
+    match action {
+        AdminAction::DeleteSubscribers { emails } => {
+            delete_subscribers(transaction, emails).await?;
+        }
+        AdminAction::PublishNewsletter {
+            list_id,
+            tag,
+            title,
+            alternative_title,
+            html,
+            text,
+            attachments,
+            sender,
+            ..
+        } => {
+            schedule_newsletter_issue(
+                transaction,
+                *list_id,
+                tag.as_deref(),
+                title,
+                alternative_title.as_deref(),
+                html,
+                text,
+                attachments,
+                requested_by,
+                sender,
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Executes the action when approved. Actions must be reviewed by an admin
+/// other than the one who requested them.
+pub async fn review_action(
+    action_id: Uuid,
+    reviewer: Uuid,
+    status: AdminActionStatus,
+    pool: &PgPool,
+) -> Result<(), AdminActionError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    let action = match lock_action(&mut transaction, action_id)
+        .await
+        .context("Failed to retrieve admin action")?
+    {
+        Some(action) if action.status == AdminActionStatus::Pending => action,
+        _ => return Err(AdminActionError::NotPendingError),
+    };
+
+    if action.requested_by == reviewer {
+        return Err(AdminActionError::SelfReviewError);
+    }
+
+    if status == AdminActionStatus::Approved {
+        execute_action(&mut transaction, &action.payload, action.requested_by)
+            .await
+            .context("Failed to execute admin action")?;
+    }
+
+    mark_as_reviewed(&mut transaction, action_id, reviewer, status)
+        .await
+        .context("Failed to mark admin action as reviewed")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to review admin action")?;
+
+    Ok(())
+}
+
+async fn review_action_from_form(
+    action_id: Uuid,
+    reviewer: Uuid,
+    status: AdminActionStatus,
+    pool: &PgPool,
+    admin_base_path: &AdminBasePath,
+) -> Result<HttpResponse, AdminActionError> {
+    let message = match status {
+        AdminActionStatus::Approved => "The action was approved and executed.",
+        _ => "The action was rejected.",
+    };
+
+    match review_action(action_id, reviewer, status, pool).await {
+        Ok(()) => FlashMessage::info(message).send(),
+        Err(e @ (AdminActionError::NotPendingError | AdminActionError::SelfReviewError)) => {
+            FlashMessage::error(e.to_string()).send()
+        }
+        Err(e) => return Err(e),
+    }
+
+    Ok(see_other(&admin_base_path.join("/actions")))
+}
+
+#[tracing::instrument(name = "Approve admin action", skip(session, pool, admin_base_path))]
+pub async fn approve_action(
+    action_id: web::Path<Uuid>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    review_action_from_form(
+        action_id.into_inner(),
+        **user_id,
+        AdminActionStatus::Approved,
+        &pool,
+        &admin_base_path,
+    )
+    .await
+}
+
+#[tracing::instrument(name = "Reject admin action", skip(session, pool, admin_base_path))]
+pub async fn reject_action(
+    action_id: web::Path<Uuid>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    review_action_from_form(
+        action_id.into_inner(),
+        **user_id,
+        AdminActionStatus::Rejected,
+        &pool,
+        &admin_base_path,
+    )
+    .await
+}
@@ -0,0 +1,116 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{
+        generate_api_token, get_api_tokens, revoke_user_api_token, store_api_token, UserId,
+    },
+    configuration::AdminBasePath,
+    routes::admin::navigation_menu,
+    session_state::TypedSession,
+    template::{render_api_tokens_page, PageLayout},
+    token_generator::TokenGenerator,
+    util::{e500, see_other},
+};
+
+/// Longest name a token can be given.
+const MAX_TOKEN_NAME_LENGTH: usize = 100;
+
+/// Personal access tokens of the user, with which scripts use the API on
+/// their behalf.
+#[tracing::instrument(
+    name = "Get API tokens page",
+    skip(flash_messages, session, pool, admin_base_path)
+)]
+pub async fn api_tokens_page(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.csrf_token().map_err(e500)?;
+    let navigation = navigation_menu(&admin_base_path, session.get_user_role().map_err(e500)?);
+    let layout = PageLayout::new(Some(navigation), &flash_messages);
+    let tokens = get_api_tokens(&pool, **user_id)
+        .await
+        .context("Failed to retrieve the API tokens")
+        .map_err(e500)?;
+    let page = render_api_tokens_page(
+        &layout,
+        admin_base_path.get_ref().as_ref(),
+        &csrf_token,
+        &tokens,
+    )
+    .context("Failed to render the API tokens page")
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(page))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ApiTokenFormData {
+    name: String,
+}
+
+/// Creates a token, shown once in a flash message: only its digest is
+/// stored.
+#[tracing::instrument(
+    name = "Create API token",
+    skip(form, pool, token_generator, admin_base_path),
+    fields(name = %form.name)
+)]
+pub async fn create_api_token(
+    form: web::Form<ApiTokenFormData>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    token_generator: web::Data<dyn TokenGenerator>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let api_tokens_path = admin_base_path.join("/api_tokens");
+    let name = form.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_TOKEN_NAME_LENGTH {
+        FlashMessage::error(format!(
+            "Give the token a name of at most {} characters.",
+            MAX_TOKEN_NAME_LENGTH
+        ))
+        .send();
+        return Ok(see_other(&api_tokens_path));
+    }
+
+    let token = generate_api_token(token_generator.get_ref());
+    store_api_token(&pool, **user_id, name, &token)
+        .await
+        .context("Failed to store the API token")
+        .map_err(e500)?;
+
+    FlashMessage::info(format!("API token {}, only shown now: {}", name, token)).send();
+
+    Ok(see_other(&api_tokens_path))
+}
+
+#[tracing::instrument(name = "Revoke API token", skip(pool, admin_base_path))]
+pub async fn revoke_api_token(
+    token_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let revoked = revoke_user_api_token(&pool, **user_id, token_id.into_inner())
+        .await
+        .context("Failed to revoke the API token")
+        .map_err(e500)?;
+
+    if revoked {
+        FlashMessage::info("The API token was revoked.").send();
+    } else {
+        FlashMessage::error("The API token was already revoked.").send();
+    }
+
+    Ok(see_other(&admin_base_path.join("/api_tokens")))
+}
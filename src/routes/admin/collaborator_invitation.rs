@@ -1,18 +1,26 @@
 use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
 use anyhow::Context;
-use rand::{thread_rng, Rng};
 use sqlx::{PgPool, Postgres, Transaction};
 
 use crate::{
+    api_error::{ApiError, Problem},
     domain::{CollaboratorEmail, CollaboratorEmailError, NewCollaborator},
-    email_client::EmailClient,
+    email_client::{EmailClient, SendEmailError},
+    email_log::EmailKind,
     routes::error_chain_fmt,
     session_state::TypedSession,
     startup::ApplicationBaseUrl,
-    template::{self, render_collaborator_invitation},
+    stored_templates::{get_template, StoredTemplate, INVITATION_TEMPLATE},
+    template::{self, render_collaborator_invitation, render_stored_collaborator_invitation},
+    token_generator::{
+        generate_invitation_token, generate_validation_code, hash_token, TokenGenerator,
+    },
     user_role::UserRole,
 };
 
+/// Subject of the invitation until one is edited from the admin UI.
+pub const DEFAULT_INVITATION_SUBJECT: &str = "Welcome!";
+
 #[derive(thiserror::Error)]
 pub enum CollaboratorParseError {
     #[error(transparent)]
@@ -74,9 +82,23 @@ impl ResponseError for InviteError {
             InviteError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
 }
 
-#[derive(serde::Deserialize)]
+impl Problem for InviteError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            InviteError::NonAdminError => "restricted-operation",
+            InviteError::ValidationError(_) => "invalid-collaborator",
+            InviteError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct CollaboratorFormData {
     email: String,
 }
@@ -92,40 +114,24 @@ impl TryFrom<CollaboratorFormData> for NewCollaborator {
     }
 }
 
-fn generate_invitation_token() -> String {
-    let mut rng = thread_rng();
-
-    std::iter::repeat_with(|| rng.sample(rand::distributions::Alphanumeric))
-        .map(char::from)
-        .take(30)
-        .collect()
-}
-
-fn generate_validation_code() -> String {
-    let mut rng = thread_rng();
-
-    std::iter::repeat_with(|| rng.sample(rand::distributions::Uniform::new_inclusive(0, 9)))
-        .map(|d| char::from_digit(d, 10).unwrap())
-        .take(6)
-        .collect()
-}
-
 #[tracing::instrument(
     name = "Saving new collaborator invitation",
-    skip(transaction, invitation_token, validation_code)
+    skip(transaction, invitation_token, validation_code, email)
 )]
 async fn insert_collaborator_token(
     transaction: &mut Transaction<'_, Postgres>,
     invitation_token: &str,
     validation_code: &str,
+    email: &CollaboratorEmail,
 ) -> Result<(), StoreCollaboratorTokenError> {
     sqlx::query!(
         r#"
-        INSERT INTO invitation_tokens (invitation_token, validation_code)
-        VALUES ($1, $2)
+        INSERT INTO invitation_tokens (invitation_token_hash, validation_code, email)
+        VALUES ($1, $2, $3)
         "#,
-        invitation_token,
+        hash_token(invitation_token),
         validation_code,
+        email.as_ref().as_ref(),
     )
     .execute(&mut **transaction)
     .await
@@ -134,44 +140,58 @@ async fn insert_collaborator_token(
     Ok(())
 }
 
+/// Renders the invitation edited from the admin UI, if any, the one on disk
+/// otherwise.
 #[tracing::instrument(
     name = "Render collaborator invitation message",
-    skip(base_url, invitation_token)
+    skip(base_url, invitation_token, stored)
 )]
 fn build_collaborator_invitation_template(
     base_url: &str,
     invitation_token: &str,
+    stored: Option<&StoredTemplate>,
 ) -> Result<template::CollaboratorInvitation, tera::Error> {
     let invitiation_link = format!(
         "{}/collaborator?invitation_token={}",
         base_url, invitation_token,
     );
 
-    render_collaborator_invitation(&invitiation_link)
+    match stored {
+        Some(stored) => render_stored_collaborator_invitation(
+            &stored.html_body,
+            &stored.text_body,
+            &invitiation_link,
+        ),
+        None => render_collaborator_invitation(&invitiation_link),
+    }
 }
 
 #[tracing::instrument(
     name = "Send invitation email",
-    skip(email_client, new_collaborator, template)
+    skip(email_client, new_collaborator, subject, template)
 )]
 async fn send_invitation_email(
     email_client: &EmailClient,
     new_collaborator: NewCollaborator,
+    subject: &str,
     template: template::CollaboratorInvitation,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), SendEmailError> {
     email_client
         .send_email(
+            EmailKind::CollaboratorInvitation,
             new_collaborator.email.as_ref(),
-            "Welcome!",
+            subject,
             &template.html,
             &template.text,
+            &[],
+            None,
         )
         .await
 }
 
 #[tracing::instrument(
     name = "Inviting new collaborator",
-    skip(form, session, pool, email_client, base_url),
+    skip(form, session, pool, email_client, base_url, token_generator),
     fields(collaborator_email = %form.email)
 )]
 pub async fn invite_collaborator(
@@ -180,6 +200,7 @@ pub async fn invite_collaborator(
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
+    token_generator: web::Data<dyn TokenGenerator>,
 ) -> Result<HttpResponse, InviteError> {
     if session
         .get_user_role()
@@ -190,29 +211,59 @@ pub async fn invite_collaborator(
         return Err(InviteError::NonAdminError);
     }
 
-    let new_collaborator: NewCollaborator =
-        form.0.try_into().map_err(InviteError::ValidationError)?;
+    let new_collaborator = form.0.try_into().map_err(InviteError::ValidationError)?;
 
-    let invitation_token = generate_invitation_token();
-    let validation_code = generate_validation_code();
+    send_invitation(
+        new_collaborator,
+        &pool,
+        &email_client,
+        &base_url,
+        token_generator.get_ref(),
+    )
+    .await
+}
+
+/// Stores a new invitation and emails its link to the collaborator. The
+/// validation code is returned to the admin, who hands it over separately.
+pub async fn send_invitation(
+    new_collaborator: NewCollaborator,
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
+    token_generator: &dyn TokenGenerator,
+) -> Result<HttpResponse, InviteError> {
+    let invitation_token = generate_invitation_token(token_generator);
+    let validation_code = generate_validation_code(token_generator);
 
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to aquire a Postgres connection from the pool")?;
 
-    insert_collaborator_token(&mut transaction, &invitation_token, &validation_code)
-        .await
-        .context("Failed to insert invitation token for new collaborator")?;
+    insert_collaborator_token(
+        &mut transaction,
+        &invitation_token,
+        &validation_code,
+        &new_collaborator.email,
+    )
+    .await
+    .context("Failed to insert invitation token for new collaborator")?;
 
     transaction
         .commit()
         .await
         .context("Failed to commit SQL transaction to store new collaborator token")?;
 
-    let template = build_collaborator_invitation_template(&base_url.0, &invitation_token)
-        .context("Failed to generate email template for invitation")?;
-    send_invitation_email(&email_client, new_collaborator, template)
+    let stored = get_template(pool, INVITATION_TEMPLATE, None)
+        .await
+        .context("Failed to retrieve the edited invitation")?;
+    let template =
+        build_collaborator_invitation_template(&base_url.0, &invitation_token, stored.as_ref())
+            .context("Failed to generate email template for invitation")?;
+    let subject = stored
+        .as_ref()
+        .map_or(DEFAULT_INVITATION_SUBJECT, |stored| stored.subject.as_str());
+    send_invitation_email(email_client, new_collaborator, subject, template)
         .await
         .context("Failed to send invitation email")?;
 
@@ -0,0 +1,229 @@
+use std::fmt::Write;
+
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    configuration::AdminBasePath, session_state::TypedSession, user_role::UserRole, util::e500,
+};
+
+use super::{Permission, ADMIN_PAGES};
+
+/// Subscribers suggested at most, the most recent first.
+const MAX_SUBSCRIBERS: i64 = 5;
+
+/// An action of the admin UI that is a form to submit rather than a page.
+struct FormCommand {
+    /// Relative to the admin base path.
+    path: &'static str,
+    title: &'static str,
+    permission: Permission,
+    /// Fields to fill in besides the CSRF token.
+    fields: &'static [&'static str],
+}
+
+static FORM_COMMANDS: &[FormCommand] = &[
+    FormCommand {
+        path: "/collaborator",
+        title: "Invite collaborator",
+        permission: Permission::AdminOnly,
+        fields: &["email"],
+    },
+    FormCommand {
+        path: "/logout",
+        title: "Log out",
+        permission: Permission::AnyUser,
+        fields: &[],
+    },
+    FormCommand {
+        path: "/sessions/revoke_all",
+        title: "Log out everywhere",
+        permission: Permission::AnyUser,
+        fields: &[],
+    },
+];
+
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandsFormat {
+    #[default]
+    Json,
+    Html,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CommandsQuery {
+    /// Filters commands by title and subscribers by email or name.
+    #[serde(default)]
+    q: String,
+    #[serde(default)]
+    format: CommandsFormat,
+}
+
+#[derive(serde::Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Command {
+    /// Jumps to a page.
+    Page { title: &'static str, url: String },
+    /// Submits a form, with the CSRF token and the given fields, by POST.
+    Form {
+        title: &'static str,
+        url: String,
+        fields: &'static [&'static str],
+    },
+}
+
+#[derive(serde::Serialize)]
+struct SubscriberEntry {
+    id: Uuid,
+    email: String,
+    name: String,
+    url: String,
+}
+
+#[derive(serde::Serialize)]
+struct Commands {
+    commands: Vec<Command>,
+    subscribers: Vec<SubscriberEntry>,
+}
+
+/// Commands of the admin UI the user may run, filtered by the query, as JSON
+/// or as an HTML fragment for a command palette.
+#[tracing::instrument(name = "List admin commands", skip(session, pool, admin_base_path))]
+pub async fn admin_commands(
+    query: web::Query<CommandsQuery>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let role = session.get_user_role().map_err(e500)?;
+    let search = query.q.trim().to_lowercase();
+    let matches = |title: &str| title.to_lowercase().contains(&search);
+
+    let pages = ADMIN_PAGES
+        .iter()
+        .filter(|page| page.permission.allows(role) && matches(page.title))
+        .map(|page| Command::Page {
+            title: page.title,
+            url: admin_base_path.join(page.path),
+        });
+    let forms = FORM_COMMANDS
+        .iter()
+        .filter(|form| form.permission.allows(role) && matches(form.title))
+        .map(|form| Command::Form {
+            title: form.title,
+            url: admin_base_path.join(form.path),
+            fields: form.fields,
+        });
+    let commands = pages.chain(forms).collect();
+
+    // Subscribers are only shown to those who can manage them.
+    let subscribers = if role == Some(UserRole::Admin) {
+        find_subscribers(&search, &pool)
+            .await
+            .map_err(e500)?
+            .into_iter()
+            .map(|(id, email, name)| SubscriberEntry {
+                url: admin_base_path.join(&format!("/subscribers/{}", id)),
+                id,
+                email,
+                name,
+            })
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let commands = Commands {
+        commands,
+        subscribers,
+    };
+
+    Ok(match query.format {
+        CommandsFormat::Json => HttpResponse::Ok().json(commands),
+        CommandsFormat::Html => {
+            HttpResponse::Ok()
+                .content_type(ContentType::html())
+                .body(commands_fragment(
+                    &commands,
+                    &session.csrf_token().map_err(e500)?,
+                ))
+        }
+    })
+}
+
+/// The most recent subscribers whose email or name contains the search.
+#[tracing::instrument(name = "Find subscribers for the command palette", skip(pool))]
+async fn find_subscribers(
+    search: &str,
+    pool: &PgPool,
+) -> Result<Vec<(Uuid, String, String)>, anyhow::Error> {
+    let subscribers = sqlx::query!(
+        r#"
+        SELECT id, email, name
+        FROM subscriptions
+        WHERE strpos(lower(email), $1) > 0 OR strpos(lower(name), $1) > 0
+        ORDER BY subscribed_at DESC
+        LIMIT $2
+        "#,
+        search,
+        MAX_SUBSCRIBERS
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to search subscribers")?;
+
+    Ok(subscribers
+        .into_iter()
+        .map(|s| (s.id, s.email, s.name))
+        .collect())
+}
+
+fn commands_fragment(commands: &Commands, csrf_token: &str) -> String {
+    let mut commands_html = String::new();
+    for command in &commands.commands {
+        match command {
+            Command::Page { title, url } => {
+                writeln!(commands_html, r#"<li><a href="{}">{}</a></li>"#, url, title).unwrap();
+            }
+            Command::Form { title, url, fields } => {
+                let mut inputs = String::new();
+                for field in *fields {
+                    write!(
+                        inputs,
+                        r#"<input type="text" name="{0}" placeholder="{0}">"#,
+                        field
+                    )
+                    .unwrap();
+                }
+                writeln!(
+                    commands_html,
+                    r#"<li><form action="{}" method="post"><input type="hidden" name="csrf_token" value="{}">{}<button type="submit">{}</button></form></li>"#,
+                    url, csrf_token, inputs, title
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    let mut subscribers_html = String::new();
+    for subscriber in &commands.subscribers {
+        writeln!(
+            subscribers_html,
+            r#"<li><a href="{}">{} ({})</a></li>"#,
+            subscriber.url,
+            htmlescape::encode_minimal(&subscriber.email),
+            htmlescape::encode_minimal(&subscriber.name),
+        )
+        .unwrap();
+    }
+
+    format!(
+        r#"<ul class="commands">
+{commands_html}</ul>
+<ul class="subscribers">
+{subscribers_html}</ul>"#
+    )
+}
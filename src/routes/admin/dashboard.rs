@@ -3,7 +3,17 @@ use anyhow::Context;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::{authentication::UserId, util::e500};
+use crate::{
+    authentication::UserId,
+    base_url_check::{BaseUrlCheck, BaseUrlStatus},
+    configuration::AdminBasePath,
+    routes::admin::{get_profile, navigation_menu},
+    session_state::TypedSession,
+    startup::ApplicationBaseUrl,
+    stats::dashboard_stats,
+    template::{render_admin_dashboard, DashboardPage, PageLayout},
+    util::e500,
+};
 
 #[tracing::instrument(name = "Get username", skip(pool))]
 pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow::Error> {
@@ -22,34 +32,52 @@ pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow
     Ok(row.username)
 }
 
+#[tracing::instrument(
+    name = "Get admin dashboard",
+    skip(session, pool, user_id, admin_base_path, base_url, base_url_check)
+)]
 pub async fn admin_dashboard(
+    session: TypedSession,
     pool: web::Data<PgPool>,
     user_id: web::ReqData<UserId>,
+    admin_base_path: web::Data<AdminBasePath>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    base_url_check: web::Data<BaseUrlCheck>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
-    let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    let profile = get_profile(*user_id, &pool)
+        .await
+        .context("Failed to retrieve the profile")
+        .map_err(e500)?;
+    let csrf_token = session.csrf_token().map_err(e500)?;
+    let navigation = navigation_menu(&admin_base_path, session.get_user_role().map_err(e500)?);
+    let base_url_status = base_url_check.status().await;
+    let stats = dashboard_stats(&pool)
+        .await
+        .context("Failed to compute the dashboard stats")
+        .map_err(e500)?;
+
+    let page = DashboardPage {
+        admin: admin_base_path.get_ref().as_ref(),
+        username: &profile.username,
+        display_name: profile.display_name.as_deref(),
+        csrf_token: &csrf_token,
+        base_url: &base_url.0,
+        base_url_unreachable: match &base_url_status {
+            BaseUrlStatus::Reachable => None,
+            BaseUrlStatus::Unreachable(reason) => Some(reason.as_str()),
+        },
+        stats: &stats,
+    };
+    let layout = PageLayout {
+        navigation: Some(navigation),
+        ..Default::default()
+    };
+    let body = render_admin_dashboard(&layout, &page)
+        .context("Failed to render the admin dashboard")
+        .map_err(e500)?;
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta http-equiv="content-type" content="text/html; charset=utf-8">
-    <title>Login</title>
-</head>
-<body>
-    <p>Welcome {username}</p>
-    <p>Available actions:</p>
-    <ol>
-    <li><a href="/admin/password">Change password</a></li>
-    <li>
-        <form name="logoutForm" action="admin/logout" method="post">
-            <input type="Submit" value="Logout">
-        </form>
-    </li>
-    </ol>
-</body>
-</html>"#,
-        )))
+        .body(body))
 }
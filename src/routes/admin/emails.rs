@@ -0,0 +1,38 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    configuration::AdminBasePath,
+    email_log::{list_sent_emails, EmailLogFilter},
+    routes::admin::{actions::reject_non_admin_users, navigation_menu, AdminActionError},
+    session_state::TypedSession,
+    template::render_emails_page,
+    user_role::UserRole,
+};
+
+/// Emails shown at most, the most recent ones.
+const EMAILS_SHOWN: i64 = 100;
+
+/// Latest emails sent, e.g. to check whether someone was sent the
+/// confirmation they say they never got.
+#[tracing::instrument(name = "Get emails page", skip(session, pool, admin_base_path))]
+pub async fn emails_page(
+    filter: web::Query<EmailLogFilter>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+    let emails = list_sent_emails(&pool, &filter, EMAILS_SHOWN)
+        .await
+        .context("Failed to get the emails sent")?;
+    let body = render_emails_page(&navigation, &filter, &emails)
+        .context("Failed to render the emails page")?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(body))
+}
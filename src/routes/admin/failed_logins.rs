@@ -0,0 +1,37 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::recent_failed_logins,
+    configuration::AdminBasePath,
+    routes::admin::{actions::reject_non_admin_users, navigation_menu, AdminActionError},
+    session_state::TypedSession,
+    template::render_failed_logins_page,
+    user_role::UserRole,
+};
+
+/// Failed logins shown at most, the most recent ones.
+const FAILED_LOGINS_SHOWN: i64 = 100;
+
+/// Latest logins refused because of their credentials, with where they came
+/// from.
+#[tracing::instrument(name = "Get failed logins page", skip(session, pool, admin_base_path))]
+pub async fn failed_logins_page(
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+    let failed_logins = recent_failed_logins(&pool, FAILED_LOGINS_SHOWN)
+        .await
+        .context("Failed to get the recent failed logins")?;
+    let body = render_failed_logins_page(&navigation, &failed_logins)
+        .context("Failed to render the failed logins page")?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(body))
+}
@@ -0,0 +1,219 @@
+use actix_web::{
+    http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType},
+    web, HttpResponse,
+};
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    configuration::AdminBasePath,
+    delivery_queue::{inspect_issue, issue_stats},
+    issue_export::{export_issue, get_published_issue, ExportFormat},
+    routes::admin::{actions::reject_non_admin_users, navigation_menu, AdminActionError},
+    session_state::TypedSession,
+    template::render_issue_stats_page,
+    tracking::{issue_engagement, subject_variant_engagement},
+    user_role::UserRole,
+};
+
+fn issue_not_found(navigation: &str, newsletter_issue_id: Uuid) -> HttpResponse {
+    HttpResponse::NotFound()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Issue not found</title>
+</head>
+<body>
+    {navigation}
+    <p>There is no issue {newsletter_issue_id}.</p>
+</body>
+</html>"#,
+        ))
+}
+
+/// Share of the delivered emails, as shown on the stats page.
+fn rate(count: i64, delivered: i64) -> String {
+    if delivered == 0 {
+        return "-".to_string();
+    }
+
+    format!("{:.1}%", count as f64 * 100. / delivered as f64)
+}
+
+/// Opens and clicks of an issue, as recorded by the tracking routes.
+#[tracing::instrument(name = "Get issue stats page", skip(session, pool, admin_base_path))]
+pub async fn issue_stats_page(
+    newsletter_issue_id: web::Path<Uuid>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let Some(deliveries) = inspect_issue(&pool, newsletter_issue_id)
+        .await
+        .context("Failed to inspect issue deliveries")?
+    else {
+        return Ok(issue_not_found(&navigation, newsletter_issue_id));
+    };
+    let engagement = issue_engagement(&pool, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve the issue engagement")?;
+    let variants = subject_variant_engagement(&pool, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve the engagement per subject")?;
+
+    let mut links_html = String::new();
+    for link in &engagement.links {
+        writeln!(
+            links_html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            htmlescape::encode_minimal(&link.url),
+            link.clicks
+        )
+        .unwrap();
+    }
+
+    let mut variants_html = String::new();
+    for variant in &variants {
+        writeln!(
+            variants_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            htmlescape::encode_minimal(&variant.subject),
+            variant.delivered,
+            variant.unique_opens,
+            rate(variant.unique_opens, variant.delivered)
+        )
+        .unwrap();
+    }
+    // Only shown for issues testing two subjects.
+    if !variants_html.is_empty() {
+        variants_html = format!(
+            r#"<table>
+        <tr><th>Subject</th><th>Delivered</th><th>Opened</th><th>Open rate</th></tr>
+        {variants_html}
+    </table>"#
+        );
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Issue stats</title>
+</head>
+<body>
+    {navigation}
+    <h1>{title}</h1>
+    {author}
+    <dl>
+        <dt>Delivered</dt><dd>{delivered}</dd>
+        <dt>Opens</dt><dd>{opens} ({unique_opens} unique, {open_rate})</dd>
+        <dt>Clicks</dt><dd>{clicks} ({unique_clicks} unique, {click_rate})</dd>
+    </dl>
+    {variants_html}
+    <table>
+        <tr><th>Link</th><th>Clicks</th></tr>
+        {links_html}
+    </table>
+</body>
+</html>"#,
+            title = htmlescape::encode_minimal(&deliveries.title),
+            author = deliveries
+                .author
+                .as_deref()
+                .map(|author| format!("<p>Published by {}</p>", htmlescape::encode_minimal(author)))
+                .unwrap_or_default(),
+            delivered = deliveries.delivered,
+            opens = engagement.opens,
+            unique_opens = engagement.unique_opens,
+            open_rate = rate(engagement.unique_opens, deliveries.delivered),
+            clicks = engagement.clicks,
+            unique_clicks = engagement.unique_clicks,
+            click_rate = rate(engagement.unique_clicks, deliveries.delivered),
+        )))
+}
+
+/// How many recipients of an issue it was sent to, failed for, bounced,
+/// opened or clicked through.
+#[tracing::instrument(
+    name = "Get newsletter stats page",
+    skip(session, pool, admin_base_path)
+)]
+pub async fn newsletter_stats_page(
+    newsletter_issue_id: web::Path<Uuid>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let Some(stats) = issue_stats(&pool, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve the issue stats")?
+    else {
+        return Ok(issue_not_found(&navigation, newsletter_issue_id));
+    };
+    let page = render_issue_stats_page(&navigation, &stats)
+        .context("Failed to render the issue stats page")?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(page))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ExportQuery {
+    format: ExportFormat,
+}
+
+/// Downloads a published issue as a document, e.g. to archive it.
+#[tracing::instrument(
+    name = "Export newsletter issue",
+    skip(session, pool, admin_base_path, query)
+)]
+pub async fn export_newsletter(
+    newsletter_issue_id: web::Path<Uuid>,
+    query: web::Query<ExportQuery>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let Some(issue) = get_published_issue(&pool, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve the issue")?
+    else {
+        let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+
+        return Ok(issue_not_found(&navigation, newsletter_issue_id));
+    };
+    let format = query.format;
+    let file_name = issue.file_name(format);
+    let document = web::block(move || export_issue(&issue, format))
+        .await
+        .context("Failed to run the issue export")?
+        .context("Failed to export the issue")?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        })
+        .body(document))
+}
@@ -1,10 +1,13 @@
 use actix_web::HttpResponse;
 use actix_web_flash_messages::FlashMessage;
 
-use crate::{session_state::TypedSession, util::see_other};
+use crate::{
+    session_state::TypedSession,
+    util::{e500, see_other},
+};
 
 pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
-    session.log_out();
+    session.log_out().await.map_err(e500)?;
 
     FlashMessage::info("You have successfully logged out.").send();
 
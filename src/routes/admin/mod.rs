@@ -1,9 +1,39 @@
+mod access_links;
+mod actions;
+mod api_tokens;
 mod collaborator_invitation;
+mod commands;
 mod dashboard;
+mod emails;
+mod failed_logins;
+mod issues;
 mod logout;
+mod navigation;
+mod newsletters;
 mod password;
+mod profile;
+mod referrals;
+mod sessions;
+mod subscribers;
+mod templates;
+mod webhooks;
 
+pub use access_links::*;
+pub use actions::*;
+pub use api_tokens::*;
 pub use collaborator_invitation::*;
+pub use commands::*;
 pub use dashboard::admin_dashboard;
+pub use emails::*;
+pub use failed_logins::*;
+pub use issues::*;
 pub use logout::*;
+pub use navigation::*;
+pub use newsletters::*;
 pub use password::*;
+pub use profile::*;
+pub use referrals::*;
+pub use sessions::*;
+pub use subscribers::*;
+pub use templates::*;
+pub use webhooks::*;
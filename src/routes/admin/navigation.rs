@@ -0,0 +1,178 @@
+use std::fmt::Write;
+
+use actix_web::{web, Route};
+
+use crate::{configuration::AdminBasePath, user_role::UserRole};
+
+use super::{
+    access_links_page, admin_dashboard, api_tokens_page, change_password_form,
+    duplicate_subscribers_page, emails_page, failed_logins_page, import_subscribers_form,
+    invitation_template_page, pending_actions, profile_page, publish_newsletter_form,
+    referrals_page, webhooks_page,
+};
+
+/// Who may use an admin page.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Permission {
+    AnyUser,
+    AdminOnly,
+}
+
+impl Permission {
+    pub fn allows(&self, role: Option<UserRole>) -> bool {
+        match self {
+            Permission::AnyUser => true,
+            Permission::AdminOnly => role == Some(UserRole::Admin),
+        }
+    }
+}
+
+/// A page of the admin UI, reachable with a GET request.
+pub struct AdminPage {
+    /// Relative to the admin base path.
+    pub path: &'static str,
+    pub title: &'static str,
+    pub permission: Permission,
+    route: fn() -> Route,
+}
+
+impl AdminPage {
+    pub fn route(&self) -> Route {
+        (self.route)()
+    }
+}
+
+/// Every page of the admin UI. They are mounted in the admin scope and listed
+/// in the navigation menu from here, so a new page only has to be added once.
+pub static ADMIN_PAGES: &[AdminPage] = &[
+    AdminPage {
+        path: "/dashboard",
+        title: "Dashboard",
+        permission: Permission::AnyUser,
+        route: || web::get().to(admin_dashboard),
+    },
+    AdminPage {
+        path: "/newsletters",
+        title: "Publish an issue",
+        permission: Permission::AnyUser,
+        route: || web::get().to(publish_newsletter_form),
+    },
+    AdminPage {
+        path: "/profile",
+        title: "Profile",
+        permission: Permission::AnyUser,
+        route: || web::get().to(profile_page),
+    },
+    AdminPage {
+        path: "/password",
+        title: "Change password",
+        permission: Permission::AnyUser,
+        route: || web::get().to(change_password_form),
+    },
+    AdminPage {
+        path: "/api_tokens",
+        title: "API tokens",
+        permission: Permission::AnyUser,
+        route: || web::get().to(api_tokens_page),
+    },
+    AdminPage {
+        path: "/actions",
+        title: "Pending actions",
+        permission: Permission::AdminOnly,
+        route: || web::get().to(pending_actions),
+    },
+    AdminPage {
+        path: "/subscribers/import",
+        title: "Import subscribers",
+        permission: Permission::AdminOnly,
+        route: || web::get().to(import_subscribers_form),
+    },
+    AdminPage {
+        path: "/subscribers/duplicates",
+        title: "Duplicate subscribers",
+        permission: Permission::AdminOnly,
+        route: || web::get().to(duplicate_subscribers_page),
+    },
+    AdminPage {
+        path: "/access_links",
+        title: "Access links",
+        permission: Permission::AdminOnly,
+        route: || web::get().to(access_links_page),
+    },
+    AdminPage {
+        path: "/referrals",
+        title: "Referrals",
+        permission: Permission::AdminOnly,
+        route: || web::get().to(referrals_page),
+    },
+    AdminPage {
+        path: "/failed_logins",
+        title: "Failed logins",
+        permission: Permission::AdminOnly,
+        route: || web::get().to(failed_logins_page),
+    },
+    AdminPage {
+        path: "/emails",
+        title: "Emails sent",
+        permission: Permission::AdminOnly,
+        route: || web::get().to(emails_page),
+    },
+    AdminPage {
+        path: "/webhooks",
+        title: "Webhooks",
+        permission: Permission::AdminOnly,
+        route: || web::get().to(webhooks_page),
+    },
+    AdminPage {
+        path: "/templates/invitation",
+        title: "Invitation email",
+        permission: Permission::AdminOnly,
+        route: || web::get().to(invitation_template_page),
+    },
+];
+
+/// Links to the admin pages the given role may use.
+pub fn navigation_menu(admin_base_path: &AdminBasePath, role: Option<UserRole>) -> String {
+    let mut links = String::new();
+    for page in ADMIN_PAGES
+        .iter()
+        .filter(|page| page.permission.allows(role))
+    {
+        write!(
+            links,
+            r#"<li><a href="{}">{}</a></li>"#,
+            admin_base_path.join(page.path),
+            page.title
+        )
+        .unwrap();
+    }
+
+    format!("<nav><ul>{}</ul></nav>", links)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{configuration::AdminBasePath, user_role::UserRole};
+
+    use super::navigation_menu;
+
+    fn admin_base_path() -> AdminBasePath {
+        "/admin".to_string().try_into().unwrap()
+    }
+
+    #[test]
+    fn admins_see_every_page() {
+        let menu = navigation_menu(&admin_base_path(), Some(UserRole::Admin));
+
+        assert!(menu.contains(r#"<a href="/admin/password">Change password</a>"#));
+        assert!(menu.contains(r#"<a href="/admin/actions">Pending actions</a>"#));
+    }
+
+    #[test]
+    fn collaborators_do_not_see_admin_only_pages() {
+        let menu = navigation_menu(&admin_base_path(), Some(UserRole::Collaborator));
+
+        assert!(menu.contains(r#"<a href="/admin/dashboard">Dashboard</a>"#));
+        assert!(!menu.contains("/admin/actions"));
+    }
+}
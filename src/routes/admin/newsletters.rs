@@ -0,0 +1,187 @@
+use actix_multipart::form::{bytes::Bytes, text::Text, MultipartForm};
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    web, HttpResponse,
+};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    base_url_check::BaseUrlCheck,
+    configuration::AdminBasePath,
+    css_inliner::CssInliner,
+    email_client::{Attachment, EmailClient},
+    link_validator::LinkValidator,
+    routes::{admin::navigation_menu, publish_issue, BodyData, Content, PublishError},
+    session_state::TypedSession,
+    util::{e500, see_other},
+};
+
+#[derive(MultipartForm)]
+pub struct PublishForm {
+    title: Text<String>,
+    alternative_title: Option<Text<String>>,
+    html: Text<String>,
+    text: Option<Text<String>>,
+    list_id: Option<Text<Uuid>>,
+    tag: Option<Text<String>>,
+    sender_name: Option<Text<String>>,
+    sender_email: Option<Text<String>>,
+    reply_to: Option<Text<String>>,
+    /// The total is checked against the configured limit once uploaded.
+    #[multipart(limit = "25MB")]
+    attachments: Vec<Bytes>,
+}
+
+impl PublishForm {
+    fn into_body(self) -> BodyData {
+        let attachments = self
+            .attachments
+            .into_iter()
+            // Browsers send an empty part when no file was picked.
+            .filter(|file| !file.data.is_empty())
+            .map(|file| {
+                Attachment::new(
+                    file.file_name.unwrap_or_else(|| "attachment".into()),
+                    file.content_type
+                        .map(|mime| mime.to_string())
+                        .unwrap_or_else(|| "application/octet-stream".into()),
+                    &file.data,
+                )
+            })
+            .collect();
+
+        BodyData {
+            list_id: self.list_id.map(Text::into_inner),
+            tag: self
+                .tag
+                .map(Text::into_inner)
+                .filter(|tag| !tag.trim().is_empty()),
+            title: self.title.into_inner(),
+            alternative_title: self.alternative_title.map(Text::into_inner),
+            content: Content {
+                html: self.html.into_inner(),
+                text: self.text.map(Text::into_inner),
+            },
+            attachments,
+            sender_name: self.sender_name.map(Text::into_inner),
+            sender_email: self.sender_email.map(Text::into_inner),
+            reply_to: self.reply_to.map(Text::into_inner),
+            override_flagged_links: false,
+        }
+    }
+}
+
+pub async fn publish_newsletter_form(
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.csrf_token().map_err(e500)?;
+    let admin: &str = admin_base_path.get_ref().as_ref();
+    let navigation = navigation_menu(&admin_base_path, session.get_user_role().map_err(e500)?);
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Publish an issue</title>
+</head>
+<body>
+    {navigation}
+    {msg_html}
+    <form action="{admin}/newsletters" method="post" enctype="multipart/form-data">
+        <input type="hidden" name="csrf_token" value="{csrf_token}">
+        <label>Title
+            <input type="text" name="title">
+        </label>
+        <br>
+        <label>Alternative title, sent to half of the subscribers to compare their open rates
+            <input type="text" name="alternative_title">
+        </label>
+        <br>
+        <label>HTML content
+            <textarea name="html"></textarea>
+        </label>
+        <br>
+        <label>Text content, generated from the HTML when left empty
+            <textarea name="text"></textarea>
+        </label>
+        <br>
+        <label>Only to subscribers tagged with
+            <input type="text" name="tag">
+        </label>
+        <br>
+        <label>Sender name
+            <input type="text" name="sender_name">
+        </label>
+        <br>
+        <label>Sender address, one of the verified senders
+            <input type="email" name="sender_email">
+        </label>
+        <br>
+        <label>Reply-To address, one of the verified senders
+            <input type="email" name="reply_to">
+        </label>
+        <br>
+        <label>Attachments, such as a PDF of the issue
+            <input type="file" name="attachments" multiple>
+        </label>
+        <br>
+        <button type="submit">Publish</button>
+    </form>
+</body>
+</html>"#,
+        )))
+}
+
+/// Publishes an issue from the admin UI, along with the uploaded files.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    name = "Publish newsletter issue from the admin UI",
+    skip(form, pool, link_validator, base_url_check, css_inliner, email_client)
+)]
+pub async fn publish_newsletter_upload(
+    MultipartForm(form): MultipartForm<PublishForm>,
+    pool: web::Data<PgPool>,
+    link_validator: web::Data<LinkValidator>,
+    base_url_check: web::Data<BaseUrlCheck>,
+    css_inliner: web::Data<CssInliner>,
+    email_client: web::Data<EmailClient>,
+    user_id: web::ReqData<UserId>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, PublishError> {
+    let response = publish_issue(
+        **user_id,
+        form.into_body(),
+        &pool,
+        &link_validator,
+        &base_url_check,
+        &css_inliner,
+        &email_client,
+    )
+    .await?;
+
+    if response.status() == StatusCode::ACCEPTED {
+        FlashMessage::info("The issue is waiting for an admin to approve it.").send();
+    } else {
+        FlashMessage::info("The issue has been published.").send();
+    }
+
+    Ok(see_other(&admin_base_path.join("/newsletters")))
+}
@@ -1,55 +1,29 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web::{http::header::ContentType, web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
-use std::fmt::Write;
+use anyhow::Context;
+
+use crate::{
+    configuration::AdminBasePath,
+    routes::admin::navigation_menu,
+    session_state::TypedSession,
+    template::{render_change_password_page, PageLayout},
+    util::e500,
+};
 
 pub async fn change_password_form(
+    session: TypedSession,
     flash_messages: IncomingFlashMessages,
+    admin_base_path: web::Data<AdminBasePath>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let mut msg_html = String::new();
-    for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
+    let csrf_token = session.csrf_token().map_err(e500)?;
+    let navigation = navigation_menu(&admin_base_path, session.get_user_role().map_err(e500)?);
+    let layout = PageLayout::new(Some(navigation), &flash_messages);
+    let page =
+        render_change_password_page(&layout, admin_base_path.get_ref().as_ref(), &csrf_token)
+            .context("Failed to render the change password page")
+            .map_err(e500)?;
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta http-equiv="content-type" content="text/html; charset=utf-8">
-    <title>Change Password</title>
-</head>
-<body>
-    {msg_html}
-    <form action="/admin/password" method="post">
-        <label>Current password
-            <input
-                type="password"
-                placeholder="Enter current password"
-                name="current_password"
-            >
-        </label>
-        <br>
-        <label>New password
-            <input
-                type="password"
-                placeholder="Enter new password"
-                name="new_password"
-            >
-        </label>
-        <br>
-        <label>Confirm new password
-            <input
-                type="password"
-                placeholder="Type the new password again"
-                name="new_password_check"
-            >
-        </label>
-        <br>
-        <button type="submit">Change password</button>
-    </form>
-    <p><a href="/admin/dashboard">&lt;- Back</a></p>
-</body>
-</html>"#,
-        )))
+        .body(page))
 }
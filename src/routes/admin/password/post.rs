@@ -1,64 +1,147 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
+use uuid::Uuid;
 
 use crate::{
-    authentication::{self, validate_credentials, AuthError, Credentials, UserId},
-    routes::admin::dashboard::get_username,
+    api_error::{ApiError, Problem},
+    authentication::{
+        self, is_password_reused, validate_credentials, AuthError, Credentials, PasswordPeppers,
+        UserId,
+    },
+    configuration::{AdminBasePath, PasswordPolicySettings},
+    routes::{admin::dashboard::get_username, error_chain_fmt},
+    session_state::TypedSession,
     util::{e500, see_other},
 };
 
+#[derive(thiserror::Error)]
+pub enum PasswordChangeError {
+    #[error("You entered two different new passwords - the field values must match.")]
+    MismatchedPasswords,
+    #[error("New password must contain at least 8 and up to 64 characters.")]
+    InvalidLength,
+    #[error("The current password is incorrect.")]
+    IncorrectPassword,
+    #[error("The new password must differ from your last {0} passwords.")]
+    ReusedPassword(u32),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PasswordChangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PasswordChangeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PasswordChangeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for PasswordChangeError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            PasswordChangeError::MismatchedPasswords => "mismatched-passwords",
+            PasswordChangeError::InvalidLength => "invalid-password",
+            PasswordChangeError::IncorrectPassword => "incorrect-password",
+            PasswordChangeError::ReusedPassword(_) => "reused-password",
+            PasswordChangeError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
 #[derive(serde::Deserialize)]
-pub struct FormData {
+pub struct ChangePasswordFormData {
     current_password: Secret<String>,
     new_password: Secret<String>,
     new_password_check: Secret<String>,
 }
 
-pub async fn change_password(
-    form: web::Form<FormData>,
-    pool: web::Data<PgPool>,
-    user_id: web::ReqData<UserId>,
-) -> Result<HttpResponse, actix_web::Error> {
+/// Replaces the password of the user once the current one is confirmed and
+/// the new one complies with the password policy.
+pub async fn update_password(
+    user_id: Uuid,
+    form: ChangePasswordFormData,
+    pool: &PgPool,
+    peppers: &PasswordPeppers,
+    password_policy: &PasswordPolicySettings,
+) -> Result<(), PasswordChangeError> {
     if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
-        FlashMessage::error(
-            "You entered two different new passwords - the field values must match.",
-        )
-        .send();
-
-        return Ok(see_other("/admin/password"));
+        return Err(PasswordChangeError::MismatchedPasswords);
     }
 
-    if !(8..=64).contains(&form.0.new_password.expose_secret().len()) {
-        FlashMessage::error("New password must contain at least 8 and up to 64 characters.").send();
-
-        return Ok(see_other("/admin/password"));
+    if !(8..=64).contains(&form.new_password.expose_secret().len()) {
+        return Err(PasswordChangeError::InvalidLength);
     }
 
-    let user_id = user_id.into_inner();
-
-    let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    let username = get_username(user_id, pool).await?;
     let credentials = Credentials {
         username,
-        password: form.0.current_password,
+        password: form.current_password,
     };
-    if let Err(e) = validate_credentials(credentials, &pool).await {
+    if let Err(e) = validate_credentials(credentials, pool, peppers).await {
         return match e {
-            AuthError::InvalidCredentials(_) => {
-                FlashMessage::error("The current password is incorrect.").send();
-
-                Ok(see_other("/admin/password"))
-            }
-            AuthError::UnexpectedError(_) => Err(e500(e)),
+            AuthError::InvalidCredentials(_) => Err(PasswordChangeError::IncorrectPassword),
+            AuthError::UnexpectedError(e) => Err(e.into()),
         };
     }
 
-    authentication::change_password(*user_id, form.0.new_password, &pool)
-        .await
-        .map_err(e500)?;
+    let history_size = password_policy.history_size();
+    if history_size > 0
+        && is_password_reused(
+            user_id,
+            form.new_password.clone(),
+            history_size,
+            pool,
+            peppers,
+        )
+        .await?
+    {
+        return Err(PasswordChangeError::ReusedPassword(history_size));
+    }
+
+    authentication::change_password(user_id, form.new_password, history_size, pool, peppers)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn change_password(
+    form: web::Form<ChangePasswordFormData>,
+    pool: web::Data<PgPool>,
+    peppers: web::Data<PasswordPeppers>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    password_policy: web::Data<PasswordPolicySettings>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+
+    match update_password(*user_id, form.0, &pool, &peppers, &password_policy).await {
+        Ok(()) => {}
+        Err(PasswordChangeError::UnexpectedError(e)) => return Err(e500(e)),
+        Err(e) => {
+            FlashMessage::error(e.to_string()).send();
+
+            return Ok(see_other(&admin_base_path.join("/password")));
+        }
+    }
+
+    session.revoke_others(*user_id).await.map_err(e500)?;
+    session.clear_password_expiration();
 
     FlashMessage::error("Your password has been changed.").send();
 
-    Ok(see_other("/admin/password"))
+    Ok(see_other(&admin_base_path.join("/password")))
 }
@@ -0,0 +1,134 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    configuration::AdminBasePath,
+    domain::{DisplayName, Email},
+    routes::admin::navigation_menu,
+    session_state::TypedSession,
+    template::{render_profile_page, PageLayout, ProfilePage},
+    util::{e500, see_other},
+};
+
+/// How a user is shown and can be reached.
+pub struct Profile {
+    pub username: String,
+    pub display_name: Option<String>,
+    /// Where the user asked to be reached. The address they were invited at
+    /// is kept apart and never edited from here.
+    pub contact_email: Option<String>,
+}
+
+#[tracing::instrument(name = "Get profile", skip(pool))]
+pub async fn get_profile(user_id: Uuid, pool: &PgPool) -> Result<Profile, sqlx::Error> {
+    sqlx::query_as!(
+        Profile,
+        r#"
+        SELECT username, display_name, contact_email
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+#[tracing::instrument(
+    name = "Get profile page",
+    skip(flash_messages, session, pool, admin_base_path)
+)]
+pub async fn profile_page(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.csrf_token().map_err(e500)?;
+    let navigation = navigation_menu(&admin_base_path, session.get_user_role().map_err(e500)?);
+    let layout = PageLayout::new(Some(navigation), &flash_messages);
+    let profile = get_profile(**user_id, &pool)
+        .await
+        .context("Failed to retrieve the profile")
+        .map_err(e500)?;
+    let page = render_profile_page(
+        &layout,
+        &ProfilePage {
+            admin: admin_base_path.get_ref().as_ref(),
+            csrf_token: &csrf_token,
+            username: &profile.username,
+            display_name: profile.display_name.as_deref(),
+            contact_email: profile.contact_email.as_deref(),
+        },
+    )
+    .context("Failed to render the profile page")
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(page))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ProfileFormData {
+    display_name: String,
+    contact_email: String,
+}
+
+/// Blank fields are cleared.
+fn parse_profile(form: ProfileFormData) -> Result<(Option<DisplayName>, Option<Email>), String> {
+    let display_name = Some(form.display_name)
+        .filter(|name| !name.trim().is_empty())
+        .map(DisplayName::parse)
+        .transpose()
+        .map_err(|e| format!("Invalid display name: {}", e))?;
+    let contact_email = Some(form.contact_email.trim().to_string())
+        .filter(|email| !email.is_empty())
+        .map(Email::parse)
+        .transpose()
+        .map_err(|e| format!("Invalid contact email: {}", e))?;
+
+    Ok((display_name, contact_email))
+}
+
+#[tracing::instrument(name = "Update profile", skip(form, pool, admin_base_path))]
+pub async fn update_profile(
+    form: web::Form<ProfileFormData>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let profile_path = admin_base_path.join("/profile");
+    let (display_name, contact_email) = match parse_profile(form.into_inner()) {
+        Ok(profile) => profile,
+        Err(e) => {
+            FlashMessage::error(e).send();
+
+            return Ok(see_other(&profile_path));
+        }
+    };
+
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET display_name = $1, contact_email = $2
+        WHERE user_id = $3
+        "#,
+        display_name.as_ref().map(|name| name.as_ref()),
+        contact_email.as_ref().map(|email| email.as_ref()),
+        **user_id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to update the profile")
+    .map_err(e500)?;
+
+    FlashMessage::info("Your profile has been updated.").send();
+
+    Ok(see_other(&profile_path))
+}
@@ -0,0 +1,38 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    configuration::{AdminBasePath, ReferralSettings},
+    referrals::referral_stats,
+    routes::admin::{actions::reject_non_admin_users, navigation_menu, AdminActionError},
+    session_state::TypedSession,
+    template::render_referrals_page,
+    user_role::UserRole,
+};
+
+/// Who referred the most subscribers, and how many referrers reached each
+/// reward milestone.
+#[tracing::instrument(
+    name = "Get referrals page",
+    skip(session, pool, admin_base_path, referrals)
+)]
+pub async fn referrals_page(
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+    referrals: web::Data<ReferralSettings>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+    let stats = referral_stats(&pool, &referrals.reward_milestones)
+        .await
+        .context("Failed to compute the referral stats")?;
+    let body = render_referrals_page(&navigation, &stats)
+        .context("Failed to render the referrals page")?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(body))
+}
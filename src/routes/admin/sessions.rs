@@ -0,0 +1,22 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+
+use crate::{
+    authentication::UserId,
+    session_state::TypedSession,
+    util::{e500, see_other},
+};
+
+pub async fn revoke_all_sessions(
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+
+    session.revoke_all(*user_id).await.map_err(e500)?;
+    session.log_out().await.map_err(e500)?;
+
+    FlashMessage::info("You have been logged out of all sessions.").send();
+
+    Ok(see_other("/login"))
+}
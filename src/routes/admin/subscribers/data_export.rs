@@ -0,0 +1,42 @@
+use actix_web::{
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    web, HttpResponse,
+};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    privacy::collect_subscriber_data,
+    routes::admin::{actions::reject_non_admin_users, AdminActionError},
+    session_state::TypedSession,
+};
+
+/// Downloads everything stored about a subscriber, to answer an access
+/// request.
+#[tracing::instrument(name = "Export subscriber data", skip(session, pool))]
+pub async fn export_subscriber_data(
+    subscriber_id: web::Path<Uuid>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(data) = collect_subscriber_data(&pool, subscriber_id)
+        .await
+        .context("Failed to collect the subscriber data")?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "subscriber-{}.json",
+                subscriber_id
+            ))],
+        })
+        .json(data))
+}
@@ -0,0 +1,148 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    admin_action::{insert_pending_action, AdminAction},
+    authentication::UserId,
+    configuration::{AdminBasePath, TwoPersonRuleSettings},
+    domain::SubscriberEmail,
+    session_state::TypedSession,
+    util::see_other,
+};
+
+use crate::routes::admin::actions::{reject_non_admin_users, AdminActionError};
+
+#[derive(serde::Deserialize)]
+pub struct DeleteSubscribersFormData {
+    emails: String,
+}
+
+/// Validates the emails of the subscribers to delete, at least one is needed.
+pub fn parse_emails<'a>(
+    emails: impl IntoIterator<Item = &'a str>,
+) -> Result<Vec<String>, AdminActionError> {
+    let emails = emails
+        .into_iter()
+        .filter(|email| !email.is_empty())
+        .map(|email| {
+            SubscriberEmail::parse(email.to_string())
+                .map(|email| email.to_string())
+                .map_err(|_| AdminActionError::InvalidEmail(email.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if emails.is_empty() {
+        return Err(AdminActionError::MissingEmails);
+    }
+
+    Ok(emails)
+}
+
+#[tracing::instrument(name = "Delete subscribers", skip(transaction, emails))]
+pub async fn delete_subscribers(
+    transaction: &mut Transaction<'_, Postgres>,
+    emails: &[String],
+) -> Result<u64, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM subscription_tokens
+        WHERE subscriber_id IN (
+            SELECT id FROM subscriptions WHERE email = ANY($1)
+        )
+        "#,
+        emails
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM subscriptions
+        WHERE email = ANY($1)
+        "#,
+        emails
+    )
+    .execute(&mut **transaction)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+pub enum DeletionOutcome {
+    Deleted(u64),
+    /// The deletion is waiting for the approval of another admin.
+    Pending,
+}
+
+/// Deletes the subscribers right away, unless the two-person rule asks for
+/// another admin to approve the deletion first.
+pub async fn delete_or_request_approval(
+    emails: Vec<String>,
+    requested_by: Uuid,
+    pool: &PgPool,
+    two_person_rule: &TwoPersonRuleSettings,
+) -> Result<DeletionOutcome, anyhow::Error> {
+    if two_person_rule.requires_approval(emails.len()) {
+        let action = AdminAction::DeleteSubscribers { emails };
+        insert_pending_action(pool, requested_by, &action)
+            .await
+            .context("Failed to store pending subscribers deletion")?;
+
+        return Ok(DeletionOutcome::Pending);
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    let deleted = delete_subscribers(&mut transaction, &emails)
+        .await
+        .context("Failed to delete subscribers")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete subscribers")?;
+
+    Ok(DeletionOutcome::Deleted(deleted))
+}
+
+#[tracing::instrument(
+    name = "Request subscribers deletion",
+    skip(form, session, pool, two_person_rule, admin_base_path)
+)]
+pub async fn request_subscribers_deletion(
+    form: web::Form<DeleteSubscribersFormData>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    two_person_rule: web::Data<TwoPersonRuleSettings>,
+    user_id: web::ReqData<UserId>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let emails = form.emails.split(|c: char| c == ',' || c.is_whitespace());
+    let emails = match parse_emails(emails) {
+        Ok(emails) => emails,
+        Err(e @ (AdminActionError::MissingEmails | AdminActionError::InvalidEmail(_))) => {
+            FlashMessage::error(e.to_string()).send();
+
+            return Ok(see_other(&admin_base_path.join("/actions")));
+        }
+        Err(e) => return Err(e),
+    };
+
+    match delete_or_request_approval(emails, **user_id, &pool, &two_person_rule).await? {
+        DeletionOutcome::Deleted(deleted) => {
+            FlashMessage::info(format!("{} subscribers were deleted.", deleted)).send()
+        }
+        DeletionOutcome::Pending => {
+            FlashMessage::info("The deletion is waiting for the approval of another admin.").send()
+        }
+    }
+
+    Ok(see_other(&admin_base_path.join("/actions")))
+}
@@ -0,0 +1,234 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    configuration::AdminBasePath,
+    routes::admin::navigation_menu,
+    session_state::TypedSession,
+    subscriber_duplicates::{dismiss_duplicates, find_duplicate_groups, merge_subscribers},
+    user_role::UserRole,
+    util::see_other,
+};
+
+use crate::routes::admin::actions::{reject_non_admin_users, AdminActionError};
+
+/// Groups of subscribers looking like the same person, to merge or dismiss.
+#[tracing::instrument(
+    name = "Get duplicate subscribers page",
+    skip(flash_messages, session, pool, admin_base_path)
+)]
+pub async fn duplicate_subscribers_page(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+
+    let csrf_token = session.csrf_token()?;
+    let admin: &str = admin_base_path.get_ref().as_ref();
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+    let groups = find_duplicate_groups(&pool)
+        .await
+        .context("Failed to look for duplicate subscribers")?;
+
+    let mut groups_html = String::new();
+    if groups.is_empty() {
+        groups_html.push_str("<p>No probable duplicates.</p>");
+    }
+    for group in groups {
+        let members = group
+            .member_ids()
+            .iter()
+            .map(Uuid::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let email_of = |id: Uuid| {
+            group
+                .members
+                .iter()
+                .find(|m| m.id == id)
+                .map(|m| htmlescape::encode_minimal(&m.email))
+                .unwrap_or_default()
+        };
+
+        let mut members_html = String::new();
+        for (i, member) in group.members.iter().enumerate() {
+            writeln!(
+                members_html,
+                r#"<tr>
+                <td><input type="radio" name="kept_id" value="{id}"{checked}></td>
+                <td><a href="{admin}/subscribers/{id}">{email}</a></td>
+                <td>{name}</td>
+                <td>{status:?}</td>
+                <td>{subscribed_at}</td>
+            </tr>"#,
+                id = member.id,
+                checked = if i == 0 { " checked" } else { "" },
+                email = htmlescape::encode_minimal(&member.email),
+                name = htmlescape::encode_minimal(&member.name),
+                status = member.status,
+                subscribed_at = member.subscribed_at.format("%Y-%m-%d %H:%M UTC"),
+            )
+            .unwrap();
+        }
+
+        let mut evidence_html = String::new();
+        for evidence in &group.evidence {
+            writeln!(
+                evidence_html,
+                "<li>{} and {}: {} (similarity {:.2})</li>",
+                email_of(evidence.first_id),
+                email_of(evidence.second_id),
+                evidence.reason.description(),
+                evidence.similarity,
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            groups_html,
+            r#"<section>
+        <ul>{evidence_html}</ul>
+        <form action="{admin}/subscribers/duplicates/merge" method="post">
+            <table>
+                <tr><th>Keep</th><th>Email</th><th>Name</th><th>Status</th><th>Subscribed at</th></tr>
+                {members_html}
+            </table>
+            <input type="hidden" name="members" value="{members}">
+            <input type="hidden" name="csrf_token" value="{csrf_token}">
+            <button type="submit">Merge into the one kept</button>
+        </form>
+        <form action="{admin}/subscribers/duplicates/dismiss" method="post">
+            <input type="hidden" name="members" value="{members}">
+            <input type="hidden" name="csrf_token" value="{csrf_token}">
+            <button type="submit">Not duplicates</button>
+        </form>
+    </section>"#,
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Duplicate subscribers</title>
+</head>
+<body>
+    {navigation}
+    {msg_html}
+    {groups_html}
+</body>
+</html>"#,
+        )))
+}
+
+/// Comma separated ids of the subscribers of a group.
+fn parse_members(members: &str) -> Option<Vec<Uuid>> {
+    members
+        .split(',')
+        .map(|id| id.trim().parse().ok())
+        .collect::<Option<Vec<Uuid>>>()
+        .filter(|ids| ids.len() > 1)
+}
+
+#[derive(serde::Deserialize)]
+pub struct MergeDuplicatesFormData {
+    kept_id: Uuid,
+    members: String,
+}
+
+#[tracing::instrument(
+    name = "Merge duplicate subscribers",
+    skip(form, session, pool, admin_base_path)
+)]
+pub async fn merge_duplicate_subscribers(
+    form: web::Form<MergeDuplicatesFormData>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let duplicates_path = admin_base_path.join("/subscribers/duplicates");
+    let Some(members) = parse_members(&form.members) else {
+        FlashMessage::error("Invalid group of subscribers.").send();
+        return Ok(see_other(&duplicates_path));
+    };
+    if !members.contains(&form.kept_id) {
+        FlashMessage::error("The subscriber kept must be one of the group.").send();
+        return Ok(see_other(&duplicates_path));
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    let merged = merge_subscribers(&mut transaction, form.kept_id, &members)
+        .await
+        .context("Failed to merge subscribers")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to merge subscribers")?;
+
+    if merged {
+        FlashMessage::info(format!("{} subscribers were merged.", members.len())).send();
+    } else {
+        FlashMessage::error("The subscribers changed in the meantime, nothing was merged.").send();
+    }
+
+    Ok(see_other(&duplicates_path))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DismissDuplicatesFormData {
+    members: String,
+}
+
+#[tracing::instrument(
+    name = "Dismiss duplicate subscribers",
+    skip(form, session, pool, admin_base_path)
+)]
+pub async fn dismiss_duplicate_subscribers(
+    form: web::Form<DismissDuplicatesFormData>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let duplicates_path = admin_base_path.join("/subscribers/duplicates");
+    let Some(members) = parse_members(&form.members) else {
+        FlashMessage::error("Invalid group of subscribers.").send();
+        return Ok(see_other(&duplicates_path));
+    };
+
+    dismiss_duplicates(&pool, &members)
+        .await
+        .context("Failed to dismiss the duplicate subscribers")?;
+
+    FlashMessage::info("The subscribers won't be reported as duplicates anymore.").send();
+
+    Ok(see_other(&duplicates_path))
+}
@@ -0,0 +1,48 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    configuration::AdminBasePath, erasure::erase_subscriber, session_state::TypedSession,
+    util::see_other,
+};
+
+use crate::routes::admin::actions::{reject_non_admin_users, AdminActionError};
+
+/// Anonymizes a subscriber who asked to be forgotten.
+#[tracing::instrument(name = "Erase subscriber data", skip(session, pool, admin_base_path))]
+pub async fn erase_subscriber_data(
+    subscriber_id: web::Path<Uuid>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let subscriber_id = subscriber_id.into_inner();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    let erased = erase_subscriber(&mut transaction, subscriber_id)
+        .await
+        .context("Failed to erase subscriber")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to erase subscriber")?;
+
+    if erased {
+        FlashMessage::info("The subscriber was erased.").send();
+    } else {
+        FlashMessage::error("The subscriber doesn't exist or was already erased.").send();
+    }
+
+    Ok(see_other(
+        &admin_base_path.join(&format!("/subscribers/{}", subscriber_id)),
+    ))
+}
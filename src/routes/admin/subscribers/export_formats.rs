@@ -0,0 +1,374 @@
+use csv::StringRecord;
+
+use crate::domain::SubscriptionStatus;
+
+use super::RowError;
+
+/// Where an uploaded subscriber list comes from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportSource {
+    /// A CSV with `email` and `name` columns.
+    #[default]
+    Csv,
+    /// An audience export (CSV) or the members of an audience (JSON).
+    Mailchimp,
+    /// A subscribers export (CSV) or the subscribers API (JSON).
+    Buttondown,
+}
+
+impl ImportSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportSource::Csv => "CSV",
+            ImportSource::Mailchimp => "Mailchimp",
+            ImportSource::Buttondown => "Buttondown",
+        }
+    }
+}
+
+/// A subscriber as found in an export, before its fields are validated.
+/// For JSON exports, the line is the position of the entry in the list.
+#[derive(Debug)]
+pub struct ExportedContact {
+    pub line: u64,
+    pub email: String,
+    pub name: String,
+    pub status: Result<SubscriptionStatus, String>,
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Csv(csv::Error),
+    Json(serde_json::Error),
+}
+
+type Contacts = (Vec<ExportedContact>, Vec<RowError>);
+
+/// Reads the contacts of an export. Exports of the providers are read as JSON
+/// when they look like it and as CSV otherwise.
+pub fn read_export(source: ImportSource, content: &[u8]) -> Result<Contacts, ExportError> {
+    let is_json = content
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .is_some_and(|b| *b == b'{' || *b == b'[');
+
+    match source {
+        ImportSource::Csv => read_csv(content, &["email", "name"], |row| {
+            (
+                row.get("email").to_string(),
+                row.get("name").to_string(),
+                Ok(SubscriptionStatus::Confirmed),
+            )
+        })
+        .map_err(ExportError::Csv),
+        ImportSource::Mailchimp if is_json => {
+            read_json::<MailchimpMember>(content, "members").map_err(ExportError::Json)
+        }
+        ImportSource::Mailchimp => read_csv(content, &["Email Address"], |row| {
+            let email = row.get("Email Address").to_string();
+            let name = full_name(&email, row.get("First Name"), row.get("Last Name"));
+            // Audiences are exported as one file per status, which only the
+            // extra columns tell apart.
+            let status = if row.has_column("CLEAN_TIME") {
+                SubscriptionStatus::Suppressed
+            } else if row.has_column("UNSUB_TIME") {
+                SubscriptionStatus::Unsubscribed
+            } else {
+                SubscriptionStatus::Confirmed
+            };
+
+            (email, name, Ok(status))
+        })
+        .map_err(ExportError::Csv),
+        ImportSource::Buttondown if is_json => {
+            read_json::<ButtondownSubscriber>(content, "results").map_err(ExportError::Json)
+        }
+        ImportSource::Buttondown => read_csv(content, &["email"], |row| {
+            let email = row.get("email").to_string();
+            let name = full_name(&email, "", "");
+            let status = buttondown_status(row.get("subscriber_type"));
+
+            (email, name, status)
+        })
+        .map_err(ExportError::Csv),
+    }
+}
+
+struct CsvRow<'a> {
+    headers: &'a StringRecord,
+    record: StringRecord,
+}
+
+impl CsvRow<'_> {
+    fn has_column(&self, column: &str) -> bool {
+        self.headers.iter().any(|header| header == column)
+    }
+
+    /// The value of the column, empty if the row doesn't have it.
+    fn get(&self, column: &str) -> &str {
+        self.headers
+            .iter()
+            .position(|header| header == column)
+            .and_then(|i| self.record.get(i))
+            .unwrap_or_default()
+    }
+}
+
+/// Reads every row of the CSV, describing the malformed ones. Rows are
+/// identified by their line in the file.
+fn read_csv(
+    content: &[u8],
+    required_columns: &[&str],
+    to_contact: impl Fn(&CsvRow) -> (String, String, Result<SubscriptionStatus, String>),
+) -> Result<Contacts, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content);
+
+    // Fails early when the header is missing the expected columns.
+    let headers = reader.headers()?.clone();
+    for column in required_columns {
+        if !headers.iter().any(|header| header == *column) {
+            return Err(csv::Error::from(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("missing column \"{}\"", column),
+            )));
+        }
+    }
+
+    let mut contacts = vec![];
+    let mut errors = vec![];
+
+    for record in reader.records() {
+        match record {
+            Ok(record) => {
+                let line = record.position().map_or(0, |p| p.line());
+                let (email, name, status) = to_contact(&CsvRow {
+                    headers: &headers,
+                    record,
+                });
+
+                contacts.push(ExportedContact {
+                    line,
+                    email,
+                    name,
+                    status,
+                });
+            }
+            Err(e) => errors.push(RowError {
+                line: e.position().map_or(0, |p| p.line()),
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    Ok((contacts, errors))
+}
+
+trait JsonContact: serde::de::DeserializeOwned {
+    fn into_contact(self, line: u64) -> ExportedContact;
+}
+
+/// Reads the entries of a JSON export, either a list or an API response
+/// holding it in `list_field`. Malformed entries are described instead of
+/// failing the whole export.
+fn read_json<T: JsonContact>(
+    content: &[u8],
+    list_field: &str,
+) -> Result<Contacts, serde_json::Error> {
+    let entries = match serde_json::from_slice(content)? {
+        serde_json::Value::Array(entries) => entries,
+        serde_json::Value::Object(mut response) => match response.remove(list_field) {
+            Some(serde_json::Value::Array(entries)) => entries,
+            _ => {
+                return Err(serde::de::Error::custom(format!(
+                    "missing \"{}\" list",
+                    list_field
+                )))
+            }
+        },
+        _ => return Err(serde::de::Error::custom("expected a list of subscribers")),
+    };
+
+    let mut contacts = vec![];
+    let mut errors = vec![];
+
+    for (i, entry) in entries.into_iter().enumerate() {
+        let line = i as u64 + 1;
+        match serde_json::from_value::<T>(entry) {
+            Ok(entry) => contacts.push(entry.into_contact(line)),
+            Err(e) => errors.push(RowError {
+                line,
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    Ok((contacts, errors))
+}
+
+#[derive(serde::Deserialize)]
+struct MailchimpMember {
+    email_address: String,
+    status: String,
+    #[serde(default)]
+    merge_fields: MailchimpMergeFields,
+}
+
+#[derive(Default, serde::Deserialize)]
+struct MailchimpMergeFields {
+    #[serde(rename = "FNAME", default)]
+    first_name: String,
+    #[serde(rename = "LNAME", default)]
+    last_name: String,
+}
+
+impl JsonContact for MailchimpMember {
+    fn into_contact(self, line: u64) -> ExportedContact {
+        let name = full_name(
+            &self.email_address,
+            &self.merge_fields.first_name,
+            &self.merge_fields.last_name,
+        );
+        let status = match self.status.as_str() {
+            "subscribed" => Ok(SubscriptionStatus::Confirmed),
+            "unsubscribed" => Ok(SubscriptionStatus::Unsubscribed),
+            "cleaned" => Ok(SubscriptionStatus::Suppressed),
+            "pending" => Err("Subscriber never confirmed the subscription".into()),
+            status => Err(format!("Unsupported Mailchimp status \"{}\"", status)),
+        };
+
+        ExportedContact {
+            line,
+            email: self.email_address,
+            name,
+            status,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ButtondownSubscriber {
+    #[serde(alias = "email")]
+    email_address: String,
+    #[serde(alias = "type", default)]
+    subscriber_type: String,
+}
+
+impl JsonContact for ButtondownSubscriber {
+    fn into_contact(self, line: u64) -> ExportedContact {
+        ExportedContact {
+            line,
+            name: full_name(&self.email_address, "", ""),
+            status: buttondown_status(&self.subscriber_type),
+            email: self.email_address,
+        }
+    }
+}
+
+fn buttondown_status(subscriber_type: &str) -> Result<SubscriptionStatus, String> {
+    match subscriber_type {
+        "" | "regular" | "premium" | "gifted" | "trialed" | "churning" | "paused" => {
+            Ok(SubscriptionStatus::Confirmed)
+        }
+        "unsubscribed" | "removed" => Ok(SubscriptionStatus::Unsubscribed),
+        "spammy" | "undeliverable" | "complained" => Ok(SubscriptionStatus::Suppressed),
+        "unactivated" => Err("Subscriber never confirmed the subscription".into()),
+        subscriber_type => Err(format!(
+            "Unsupported Buttondown subscriber type \"{}\"",
+            subscriber_type
+        )),
+    }
+}
+
+/// Providers don't require a name, unlike us: the local part of the email
+/// stands in for it when there's none.
+fn full_name(email: &str, first_name: &str, last_name: &str) -> String {
+    let name = format!("{} {}", first_name.trim(), last_name.trim());
+    let name = name.trim();
+
+    if name.is_empty() {
+        email.split('@').next().unwrap_or_default().to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::assert_err;
+
+    use crate::domain::SubscriptionStatus;
+
+    use super::{read_export, ImportSource};
+
+    #[test]
+    fn mailchimp_members_are_mapped_to_our_statuses() {
+        let content = r#"{"members": [
+            {"email_address": "ursula@gmail.com", "status": "subscribed",
+                "merge_fields": {"FNAME": "Ursula", "LNAME": "Le Guin"}},
+            {"email_address": "le_guin@gmail.com", "status": "cleaned"},
+            {"email_address": "pending@gmail.com", "status": "pending"},
+            {"status": "subscribed"}
+        ]}"#;
+
+        let (contacts, errors) = read_export(ImportSource::Mailchimp, content.as_bytes()).unwrap();
+
+        assert_eq!(contacts.len(), 3);
+        assert_eq!(contacts[0].name, "Ursula Le Guin");
+        assert_eq!(contacts[0].status, Ok(SubscriptionStatus::Confirmed));
+        assert_eq!(contacts[1].name, "le_guin");
+        assert_eq!(contacts[1].status, Ok(SubscriptionStatus::Suppressed));
+        assert!(contacts[2].status.is_err());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 4);
+    }
+
+    #[test]
+    fn mailchimp_csv_status_is_told_by_the_export_columns() {
+        let content = "Email Address,First Name,Last Name,UNSUB_TIME\n\
+            ursula@gmail.com,Ursula,,2024-10-01 10:00:00\n";
+
+        let (contacts, _) = read_export(ImportSource::Mailchimp, content.as_bytes()).unwrap();
+
+        assert_eq!(contacts[0].email, "ursula@gmail.com");
+        assert_eq!(contacts[0].name, "Ursula");
+        assert_eq!(contacts[0].status, Ok(SubscriptionStatus::Unsubscribed));
+    }
+
+    #[test]
+    fn buttondown_subscribers_are_mapped_to_our_statuses() {
+        let content = "email,notes,subscriber_type\n\
+            ursula@gmail.com,,regular\n\
+            le_guin@gmail.com,,spammy\n\
+            new@gmail.com,,unactivated\n";
+
+        let (contacts, errors) = read_export(ImportSource::Buttondown, content.as_bytes()).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(contacts[0].line, 2);
+        assert_eq!(contacts[0].status, Ok(SubscriptionStatus::Confirmed));
+        assert_eq!(contacts[1].status, Ok(SubscriptionStatus::Suppressed));
+        assert!(contacts[2].status.is_err());
+    }
+
+    #[test]
+    fn buttondown_api_responses_are_read() {
+        let content = r#"{"count": 1, "next": null, "results": [
+            {"email_address": "ursula@gmail.com", "subscriber_type": "unsubscribed"}
+        ]}"#;
+
+        let (contacts, _) = read_export(ImportSource::Buttondown, content.as_bytes()).unwrap();
+
+        assert_eq!(contacts[0].status, Ok(SubscriptionStatus::Unsubscribed));
+    }
+
+    #[test]
+    fn json_without_the_subscribers_list_is_rejected() {
+        assert_err!(read_export(
+            ImportSource::Mailchimp,
+            r#"{"lists": []}"#.as_bytes()
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    configuration::AdminBasePath,
+    domain::SubscriptionStatus,
+    routes::admin::{actions::reject_non_admin_users, navigation_menu, AdminActionError},
+    session_state::TypedSession,
+    user_role::UserRole,
+};
+
+/// Details of a subscriber, e.g. to jump to from the command palette.
+#[tracing::instrument(
+    name = "Get subscriber page",
+    skip(flash_messages, session, pool, admin_base_path)
+)]
+pub async fn subscriber_page(
+    subscriber_id: web::Path<Uuid>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+    let subscriber_id = subscriber_id.into_inner();
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT
+            s.email,
+            s.name,
+            s.status AS "status: SubscriptionStatus",
+            s.subscribed_at,
+            s.subscriber_attributes,
+            COALESCE(
+                (SELECT array_agg(tag ORDER BY tag) FROM subscriber_tags WHERE subscriber_id = s.id),
+                '{}'
+            ) AS "tags!"
+        FROM subscriptions s
+        WHERE s.id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve the subscriber")?;
+
+    let Some(subscriber) = subscriber else {
+        return Ok(HttpResponse::NotFound()
+            .content_type(ContentType::html())
+            .body(format!(
+                r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscriber not found</title>
+</head>
+<body>
+    {navigation}
+    {msg_html}
+    <p>There is no subscriber {subscriber_id}.</p>
+</body>
+</html>"#,
+            )));
+    };
+
+    let csrf_token = session.csrf_token()?;
+    let admin: &str = admin_base_path.get_ref().as_ref();
+    let tags = if subscriber.tags.is_empty() {
+        "none".to_string()
+    } else {
+        htmlescape::encode_minimal(&subscriber.tags.join(", "))
+    };
+    let mut attributes_html = String::new();
+    if let Some(attributes) = subscriber.subscriber_attributes.as_object() {
+        for (name, value) in attributes {
+            let value = match value {
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            write!(
+                attributes_html,
+                "\n        <dt>{}</dt><dd>{}</dd>",
+                htmlescape::encode_minimal(name),
+                htmlescape::encode_minimal(&value)
+            )
+            .unwrap();
+        }
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscriber</title>
+</head>
+<body>
+    {navigation}
+    {msg_html}
+    <dl>
+        <dt>Email</dt><dd>{email}</dd>
+        <dt>Name</dt><dd>{name}</dd>
+        <dt>Status</dt><dd>{status:?}</dd>
+        <dt>Subscribed at</dt><dd>{subscribed_at}</dd>
+        <dt>Tags</dt><dd>{tags}</dd>{attributes_html}
+    </dl>
+    <p><a href="{admin}/subscribers/{subscriber_id}/export">Export personal data</a></p>
+    <form action="{admin}/subscribers/{subscriber_id}/erase" method="post">
+        <input type="hidden" name="csrf_token" value="{csrf_token}">
+        <button type="submit">Erase personal data</button>
+    </form>
+</body>
+</html>"#,
+            email = htmlescape::encode_minimal(&subscriber.email),
+            name = htmlescape::encode_minimal(&subscriber.name),
+            status = subscriber.status,
+            subscribed_at = subscriber.subscribed_at.format("%Y-%m-%d %H:%M UTC"),
+        )))
+}
@@ -0,0 +1,310 @@
+use std::collections::HashSet;
+
+use actix_multipart::form::{bytes::Bytes, text::Text, MultipartForm};
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    domain::{SubscriberEmail, SubscriberName, SubscriptionStatus},
+    newsletter_list::DEFAULT_LIST_ID,
+    routes::{
+        admin::actions::{reject_non_admin_users, AdminActionError},
+        error_chain_fmt,
+    },
+    session_state::TypedSession,
+    subscriber_events::{record_events, SubscriberEventKind},
+};
+
+use super::export_formats::{read_export, ExportError, ImportSource};
+
+const BATCH_SIZE: usize = 500;
+
+#[derive(thiserror::Error)]
+pub enum ImportError {
+    #[error("Restricted operation")]
+    NonAdminError,
+    #[error("The uploaded file is not a valid CSV: {0}")]
+    InvalidCsv(#[source] csv::Error),
+    #[error("The uploaded file is not a valid JSON export: {0}")]
+    InvalidJson(#[source] serde_json::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl From<AdminActionError> for ImportError {
+    fn from(e: AdminActionError) -> Self {
+        match e {
+            AdminActionError::UnexpectedError(e) => ImportError::UnexpectedError(e),
+            _ => ImportError::NonAdminError,
+        }
+    }
+}
+
+impl ResponseError for ImportError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ImportError::NonAdminError => StatusCode::METHOD_NOT_ALLOWED,
+            ImportError::InvalidCsv(_) | ImportError::InvalidJson(_) => StatusCode::BAD_REQUEST,
+            ImportError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for ImportError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            ImportError::NonAdminError => "restricted-operation",
+            ImportError::InvalidCsv(_) => "invalid-csv",
+            ImportError::InvalidJson(_) => "invalid-json",
+            ImportError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+#[derive(MultipartForm)]
+pub struct ImportForm {
+    #[multipart(limit = "10MB")]
+    pub file: Bytes,
+    pub source: Option<Text<ImportSource>>,
+}
+
+impl ImportForm {
+    pub fn source(&self) -> ImportSource {
+        self.source.as_deref().copied().unwrap_or_default()
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct ImportedSubscriber {
+    pub(super) line: u64,
+    pub(super) email: SubscriberEmail,
+    pub(super) name: SubscriberName,
+    pub(super) status: SubscriptionStatus,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct RowError {
+    pub line: u64,
+    pub error: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct ImportReport {
+    imported: u64,
+    errors: Vec<RowError>,
+}
+
+/// Reads the export and maps its contacts to our domain types, keeping the
+/// valid ones and describing why the others were skipped.
+pub(super) fn parse_rows(
+    source: ImportSource,
+    content: &[u8],
+) -> Result<(Vec<ImportedSubscriber>, Vec<RowError>), ImportError> {
+    let (contacts, mut errors) = read_export(source, content).map_err(|e| match e {
+        ExportError::Csv(e) => ImportError::InvalidCsv(e),
+        ExportError::Json(e) => ImportError::InvalidJson(e),
+    })?;
+
+    let mut subscribers = vec![];
+    let mut seen = HashSet::new();
+
+    for contact in contacts {
+        let line = contact.line;
+        let parsed = (|| {
+            let email = SubscriberEmail::parse(contact.email).map_err(|e| e.to_string())?;
+            let name = SubscriberName::parse(contact.name).map_err(|e| e.to_string())?;
+            let status = contact.status?;
+
+            Ok(ImportedSubscriber {
+                line,
+                email,
+                name,
+                status,
+            })
+        })();
+
+        match parsed {
+            Ok(subscriber) if !seen.insert(subscriber.email.to_string()) => {
+                errors.push(RowError {
+                    line,
+                    error: "Email is duplicated in the file".into(),
+                });
+            }
+            Ok(subscriber) => subscribers.push(subscriber),
+            Err(error) => errors.push(RowError { line, error }),
+        }
+    }
+
+    errors.sort_by_key(|e| e.line);
+
+    Ok((subscribers, errors))
+}
+
+#[tracing::instrument(name = "Insert imported subscribers", skip(transaction, subscribers))]
+async fn insert_subscribers(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscribers: &[ImportedSubscriber],
+) -> Result<HashSet<String>, sqlx::Error> {
+    let ids: Vec<Uuid> = subscribers.iter().map(|_| Uuid::new_v4()).collect();
+    let emails: Vec<String> = subscribers.iter().map(|s| s.email.to_string()).collect();
+    let names: Vec<String> = subscribers
+        .iter()
+        .map(|s| s.name.as_ref().to_string())
+        .collect();
+    let statuses: Vec<SubscriptionStatus> = subscribers.iter().map(|s| s.status).collect();
+    let now = Utc::now();
+
+    let rows = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (
+            id, list_id, email, name, subscribed_at, status, confirmed_at
+        )
+        SELECT id, $5, email, name, $4, status,
+            CASE WHEN status = 'confirmed' THEN $4::timestamptz END
+        FROM UNNEST($1::uuid[], $2::text[], $3::text[], $6::subscription_status[])
+            AS t(id, email, name, status)
+        ON CONFLICT (list_id, email) DO NOTHING
+        RETURNING id, email, status AS "status: SubscriptionStatus"
+        "#,
+        &ids,
+        &emails,
+        &names,
+        now,
+        DEFAULT_LIST_ID,
+        &statuses as &[SubscriptionStatus],
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+
+    let inserted_ids: Vec<Uuid> = rows.iter().map(|r| r.id).collect();
+    record_events(
+        transaction,
+        &inserted_ids,
+        SubscriberEventKind::Subscribed,
+        serde_json::json!({}),
+    )
+    .await?;
+    for (status, kind) in [
+        (
+            SubscriptionStatus::Confirmed,
+            SubscriberEventKind::Confirmed,
+        ),
+        (
+            SubscriptionStatus::Suppressed,
+            SubscriberEventKind::Suppressed,
+        ),
+    ] {
+        let ids: Vec<Uuid> = rows
+            .iter()
+            .filter(|r| r.status == status)
+            .map(|r| r.id)
+            .collect();
+        record_events(transaction, &ids, kind, serde_json::json!({})).await?;
+    }
+
+    Ok(rows.into_iter().map(|r| r.email).collect())
+}
+
+/// Imports an existing mailing list into the default list. Subscribers keep
+/// the status they had with the provider, confirmed for plain CSV files since
+/// they already opted in elsewhere; emails that are already subscribed are
+/// reported and left untouched.
+#[tracing::instrument(name = "Import subscribers", skip(form, session, pool))]
+pub async fn import_subscribers(
+    MultipartForm(form): MultipartForm<ImportForm>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ImportError> {
+    reject_non_admin_users(&session)?;
+
+    let report = import_file(form.source(), &form.file.data, &pool).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+pub async fn import_file(
+    source: ImportSource,
+    content: &[u8],
+    pool: &PgPool,
+) -> Result<ImportReport, ImportError> {
+    let (subscribers, mut errors) = parse_rows(source, content)?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    let mut imported = 0;
+    for batch in subscribers.chunks(BATCH_SIZE) {
+        let inserted = insert_subscribers(&mut transaction, batch)
+            .await
+            .context("Failed to insert imported subscribers")?;
+
+        for subscriber in batch {
+            if inserted.contains(&subscriber.email.to_string()) {
+                imported += 1;
+            } else {
+                errors.push(RowError {
+                    line: subscriber.line,
+                    error: "Email is already subscribed".into(),
+                });
+            }
+        }
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to import subscribers")?;
+
+    errors.sort_by_key(|e| e.line);
+
+    Ok(ImportReport { imported, errors })
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::assert_err;
+
+    use super::{parse_rows, ImportSource};
+
+    #[test]
+    fn valid_rows_are_parsed_and_invalid_ones_are_reported() {
+        let content = "email,name\n\
+            ursula@gmail.com,Ursula\n\
+            not-an-email,Le Guin\n\
+            le_guin@gmail.com,\n\
+            ursula@gmail.com,Ursula again\n";
+
+        let (subscribers, errors) = parse_rows(ImportSource::Csv, content.as_bytes()).unwrap();
+
+        assert_eq!(subscribers.len(), 1);
+        assert_eq!(subscribers[0].line, 2);
+        assert_eq!(
+            errors.iter().map(|e| e.line).collect::<Vec<_>>(),
+            vec![3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn csv_without_expected_columns_is_rejected() {
+        assert_err!(parse_rows(
+            ImportSource::Csv,
+            "mail,full_name\nursula@gmail.com,Ursula\n".as_bytes()
+        ));
+    }
+}
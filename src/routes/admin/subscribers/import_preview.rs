@@ -0,0 +1,133 @@
+use std::fmt::Write;
+
+use actix_multipart::form::MultipartForm;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+
+use crate::{
+    configuration::AdminBasePath,
+    routes::admin::{actions::reject_non_admin_users, navigation_menu, AdminActionError},
+    session_state::TypedSession,
+    user_role::UserRole,
+};
+
+use super::{parse_rows, ImportError, ImportForm};
+
+/// Rows of the mapping shown before the rest is summarized.
+const PREVIEW_ROWS: usize = 50;
+
+pub async fn import_subscribers_form(
+    session: TypedSession,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let csrf_token = session.csrf_token()?;
+    let admin: &str = admin_base_path.get_ref().as_ref();
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Import subscribers</title>
+</head>
+<body>
+    {navigation}
+    <form action="{admin}/subscribers/import" method="post" enctype="multipart/form-data">
+        <input type="hidden" name="csrf_token" value="{csrf_token}">
+        <label>Exported from
+            <select name="source">
+                <option value="csv">CSV with email and name columns</option>
+                <option value="mailchimp">Mailchimp (CSV or JSON)</option>
+                <option value="buttondown">Buttondown (CSV or JSON)</option>
+            </select>
+        </label>
+        <br>
+        <label>File
+            <input type="file" name="file">
+        </label>
+        <br>
+        <button type="submit" formaction="{admin}/subscribers/import/preview">Preview</button>
+        <button type="submit">Import</button>
+    </form>
+</body>
+</html>"#,
+        )))
+}
+
+/// Shows how the contacts of an export map to subscribers, without importing
+/// anything.
+#[tracing::instrument(name = "Preview subscribers import", skip(form, session))]
+pub async fn preview_import(
+    MultipartForm(form): MultipartForm<ImportForm>,
+    session: TypedSession,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, ImportError> {
+    reject_non_admin_users(&session)?;
+
+    let source = form.source();
+    let (subscribers, errors) = parse_rows(source, &form.file.data)?;
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+
+    let mut rows_html = String::new();
+    for subscriber in subscribers.iter().take(PREVIEW_ROWS) {
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:?}</td></tr>",
+            subscriber.line,
+            htmlescape::encode_minimal(&subscriber.email.to_string()),
+            htmlescape::encode_minimal(subscriber.name.as_ref()),
+            subscriber.status,
+        )
+        .unwrap();
+    }
+    if subscribers.len() > PREVIEW_ROWS {
+        writeln!(
+            rows_html,
+            r#"<tr><td colspan="4">And {} more</td></tr>"#,
+            subscribers.len() - PREVIEW_ROWS
+        )
+        .unwrap();
+    }
+
+    let mut errors_html = String::new();
+    for error in &errors {
+        writeln!(
+            errors_html,
+            "<li>Line {}: {}</li>",
+            error.line,
+            htmlescape::encode_minimal(&error.error)
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Import preview</title>
+</head>
+<body>
+    {navigation}
+    <p>{imported} subscribers found in the {source} export, {skipped} rows skipped.</p>
+    <table>
+        <tr><th>Line</th><th>Email</th><th>Name</th><th>Status</th></tr>
+        {rows_html}
+    </table>
+    <p>Skipped rows:</p>
+    <ul>
+        {errors_html}
+    </ul>
+</body>
+</html>"#,
+            imported = subscribers.len(),
+            source = source.as_str(),
+            skipped = errors.len(),
+        )))
+}
@@ -0,0 +1,17 @@
+mod data_export;
+mod delete;
+mod duplicates;
+mod erase;
+mod export_formats;
+mod get;
+mod import;
+mod import_preview;
+
+pub use data_export::*;
+pub use delete::*;
+pub use duplicates::*;
+pub use erase::*;
+pub use export_formats::ImportSource;
+pub use get::*;
+pub use import::*;
+pub use import_preview::*;
@@ -0,0 +1,155 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::UserId,
+    configuration::AdminBasePath,
+    routes::admin::{
+        actions::reject_non_admin_users, navigation_menu, AdminActionError,
+        DEFAULT_INVITATION_SUBJECT,
+    },
+    session_state::TypedSession,
+    stored_templates::{
+        get_template, get_template_history, store_template_version, NewTemplateVersion,
+        INVITATION_TEMPLATE,
+    },
+    template::{
+        collaborator_invitation_source, render_invitation_template_page,
+        render_stored_collaborator_invitation, InvitationTemplatePage, PageLayout,
+    },
+    user_role::UserRole,
+    util::see_other,
+};
+
+/// Link an edited invitation is rendered with before being saved. It reads
+/// the same once escaped, so it can be looked for in both bodies.
+const PROBE_REGISTRATION_LINK: &str = "https://example.com/collaborator?invitation_token=probe";
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TemplateVersionQuery {
+    version: Option<i32>,
+}
+
+/// Form to edit the collaborator invitation, filled with its latest version
+/// or, with `?version=`, an earlier one to restore. The email on disk is
+/// shown until one is saved.
+#[tracing::instrument(
+    name = "Get invitation template page",
+    skip(flash_messages, session, pool, admin_base_path)
+)]
+pub async fn invitation_template_page(
+    query: web::Query<TemplateVersionQuery>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let csrf_token = session.csrf_token()?;
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+    let layout = PageLayout::new(Some(navigation), &flash_messages);
+    let stored = get_template(&pool, INVITATION_TEMPLATE, query.version)
+        .await
+        .context("Failed to retrieve the invitation template")?;
+    let history = get_template_history(&pool, INVITATION_TEMPLATE)
+        .await
+        .context("Failed to retrieve the invitation template history")?;
+
+    let (html_body, text_body) = match &stored {
+        Some(stored) => (stored.html_body.clone(), stored.text_body.clone()),
+        None => collaborator_invitation_source().unwrap_or_default(),
+    };
+    let body = render_invitation_template_page(
+        &layout,
+        &InvitationTemplatePage {
+            admin: admin_base_path.get_ref().as_ref(),
+            csrf_token: &csrf_token,
+            subject: stored
+                .as_ref()
+                .map_or(DEFAULT_INVITATION_SUBJECT, |stored| stored.subject.as_str()),
+            html_body: &html_body,
+            text_body: &text_body,
+            version: stored.as_ref().map(|stored| stored.version),
+            history: &history,
+        },
+    )
+    .context("Failed to render the invitation template page")?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(body))
+}
+
+#[derive(serde::Deserialize)]
+pub struct InvitationTemplateFormData {
+    subject: String,
+    html_body: String,
+    text_body: String,
+}
+
+/// Why an edited invitation can't be sent, if it can't.
+fn check_invitation(form: &InvitationTemplateFormData) -> Result<(), String> {
+    let subject = form.subject.trim();
+    if subject.is_empty() || subject.chars().any(char::is_control) {
+        return Err("The subject must be a single line of text.".into());
+    }
+
+    let rendered = render_stored_collaborator_invitation(
+        &form.html_body,
+        &form.text_body,
+        PROBE_REGISTRATION_LINK,
+    )
+    .map_err(|e| format!("The invitation can't be rendered: {}", e))?;
+    if !rendered.html.contains(PROBE_REGISTRATION_LINK)
+        || !rendered.text.contains(PROBE_REGISTRATION_LINK)
+    {
+        return Err("Both bodies must use the link, {{ registration_link }}.".into());
+    }
+
+    Ok(())
+}
+
+/// Saves the invitation as its next version, sent from now on.
+#[tracing::instrument(
+    name = "Save invitation template",
+    skip(form, session, user_id, pool, admin_base_path)
+)]
+pub async fn save_invitation_template(
+    form: web::Form<InvitationTemplateFormData>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let template_path = admin_base_path.join("/templates/invitation");
+    if let Err(e) = check_invitation(&form) {
+        FlashMessage::error(e).send();
+        return Ok(see_other(&template_path));
+    }
+
+    let version = store_template_version(
+        &pool,
+        INVITATION_TEMPLATE,
+        &NewTemplateVersion {
+            subject: form.subject.trim(),
+            html_body: &form.html_body,
+            text_body: &form.text_body,
+        },
+        **user_id,
+    )
+    .await
+    .context("Failed to store the invitation template")?;
+
+    FlashMessage::info(format!(
+        "Saved version {} of the invitation, sent from now on.",
+        version
+    ))
+    .send();
+
+    Ok(see_other(&template_path))
+}
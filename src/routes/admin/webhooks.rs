@@ -0,0 +1,121 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    configuration::AdminBasePath,
+    domain::WebhookUrl,
+    routes::admin::{actions::reject_non_admin_users, navigation_menu, AdminActionError},
+    session_state::TypedSession,
+    template::{render_webhooks_page, PageLayout},
+    token_generator::{generate_webhook_secret, TokenGenerator},
+    user_role::UserRole,
+    util::see_other,
+    webhooks::{get_webhooks, recent_deliveries, remove_webhook, store_webhook},
+};
+
+/// Deliveries listed on the webhooks page, across every webhook.
+const RECENT_DELIVERIES: i64 = 20;
+
+/// Registered webhooks, a form to register another and the latest attempts
+/// to deliver events to them.
+#[tracing::instrument(
+    name = "Get webhooks page",
+    skip(flash_messages, session, pool, admin_base_path)
+)]
+pub async fn webhooks_page(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let csrf_token = session.csrf_token()?;
+    let navigation = navigation_menu(&admin_base_path, Some(UserRole::Admin));
+    let layout = PageLayout::new(Some(navigation), &flash_messages);
+    let webhooks = get_webhooks(&pool)
+        .await
+        .context("Failed to retrieve webhooks")?;
+    let deliveries = recent_deliveries(&pool, None, RECENT_DELIVERIES)
+        .await
+        .context("Failed to retrieve webhook deliveries")?;
+    let body = render_webhooks_page(
+        &layout,
+        admin_base_path.get_ref().as_ref(),
+        &csrf_token,
+        &webhooks,
+        &deliveries,
+    )
+    .context("Failed to render the webhooks page")?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(body))
+}
+
+#[derive(serde::Deserialize)]
+pub struct WebhookFormData {
+    url: String,
+}
+
+/// Registers an endpoint. Its secret is only shown now, in a flash message.
+#[tracing::instrument(
+    name = "Create webhook",
+    skip(form, session, pool, token_generator, admin_base_path),
+    fields(url = %form.url)
+)]
+pub async fn create_webhook(
+    form: web::Form<WebhookFormData>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    token_generator: web::Data<dyn TokenGenerator>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let webhooks_path = admin_base_path.join("/webhooks");
+    let url = match WebhookUrl::parse(form.into_inner().url.trim().to_string()) {
+        Ok(url) => url,
+        Err(e) => {
+            FlashMessage::error(e.to_string()).send();
+            return Ok(see_other(&webhooks_path));
+        }
+    };
+
+    let webhook = store_webhook(&pool, &url, &generate_webhook_secret(&**token_generator))
+        .await
+        .context("Failed to store webhook")?;
+
+    FlashMessage::info(format!(
+        "Registered {}. Payloads are signed with this secret, only shown now: {}",
+        webhook.url, webhook.secret
+    ))
+    .send();
+
+    Ok(see_other(&webhooks_path))
+}
+
+#[tracing::instrument(name = "Unregister webhook", skip(session, pool, admin_base_path))]
+pub async fn unregister_webhook(
+    webhook_id: web::Path<Uuid>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    admin_base_path: web::Data<AdminBasePath>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_users(&session)?;
+
+    let removed = remove_webhook(&pool, webhook_id.into_inner())
+        .await
+        .context("Failed to delete webhook")?;
+
+    if removed {
+        FlashMessage::info("The webhook was deleted.").send();
+    } else {
+        FlashMessage::error("The webhook was already deleted.").send();
+    }
+
+    Ok(see_other(&admin_base_path.join("/webhooks")))
+}
@@ -0,0 +1,244 @@
+use actix_multipart::form::MultipartForm;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    admin_action::AdminActionStatus,
+    api_error::ProblemDetails,
+    authentication::{PasswordPeppers, UserId},
+    base_url_check::BaseUrlCheck,
+    configuration::{PasswordPolicySettings, TwoPersonRuleSettings},
+    css_inliner::CssInliner,
+    email_client::EmailClient,
+    link_validator::LinkValidator,
+    routes::{
+        delete_or_request_approval, get_pending_actions, import_file, parse_emails, publish_issue,
+        reject_non_admin_roles, review_action, send_invitation, update_password, AdminActionError,
+        BodyData, ChangePasswordFormData, CollaboratorFormData, DeletionOutcome, ImportError,
+        ImportForm, InviteError, PasswordChangeError, PublishError,
+    },
+    session_state::TypedSession,
+    startup::ApplicationBaseUrl,
+    token_generator::TokenGenerator,
+    user_role::UserRole,
+};
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/newsletters",
+    tag = "admin",
+    request_body = BodyData,
+    responses(
+        (status = 200, description = "The issue is scheduled for delivery"),
+        (status = 202, description = "Some links are flagged, an admin has to approve the issue"),
+        (status = 400, description = "Invalid tag or sender", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown list", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 413, description = "The attachments are too large", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "Some links are flagged, `flagged_links` lists them", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+#[tracing::instrument(
+    name = "Publish newsletter issue through the admin API",
+    skip(body, pool, link_validator, base_url_check, css_inliner, email_client)
+)]
+pub async fn api_publish_newsletter(
+    body: web::Json<BodyData>,
+    pool: web::Data<PgPool>,
+    link_validator: web::Data<LinkValidator>,
+    base_url_check: web::Data<BaseUrlCheck>,
+    css_inliner: web::Data<CssInliner>,
+    email_client: web::Data<EmailClient>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, PublishError> {
+    publish_issue(
+        **user_id,
+        body.into_inner(),
+        &pool,
+        &link_validator,
+        &base_url_check,
+        &css_inliner,
+        &email_client,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/collaborators",
+    tag = "admin",
+    request_body = CollaboratorFormData,
+    responses(
+        (status = 200, description = "The invitation was sent, along with the `validation_code` to hand over to the collaborator"),
+        (status = 400, description = "Invalid email", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 405, description = "Only admins invite collaborators", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+#[tracing::instrument(
+    name = "Invite collaborator through the admin API",
+    skip(body, pool, email_client, base_url, token_generator)
+)]
+pub async fn api_invite_collaborator(
+    body: web::Json<CollaboratorFormData>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    token_generator: web::Data<dyn TokenGenerator>,
+) -> Result<HttpResponse, InviteError> {
+    if *role != UserRole::Admin {
+        return Err(InviteError::NonAdminError);
+    }
+
+    let new_collaborator = body
+        .into_inner()
+        .try_into()
+        .map_err(InviteError::ValidationError)?;
+
+    send_invitation(
+        new_collaborator,
+        &pool,
+        &email_client,
+        &base_url,
+        token_generator.get_ref(),
+    )
+    .await
+}
+
+/// Sessions other than the one making the request are revoked, all of them
+/// when the request is authenticated with credentials.
+#[tracing::instrument(
+    name = "Change password through the admin API",
+    skip(body, pool, peppers, session, password_policy)
+)]
+pub async fn api_change_password(
+    body: web::Json<ChangePasswordFormData>,
+    pool: web::Data<PgPool>,
+    peppers: web::Data<PasswordPeppers>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    password_policy: web::Data<PasswordPolicySettings>,
+) -> Result<HttpResponse, PasswordChangeError> {
+    let user_id = **user_id;
+
+    update_password(
+        user_id,
+        body.into_inner(),
+        &pool,
+        &peppers,
+        &password_policy,
+    )
+    .await?;
+
+    let session_user_id = session
+        .get_user_id()
+        .context("Failed to get user id from its session")?;
+    if session_user_id == Some(user_id) {
+        session.revoke_others(user_id).await?;
+        session.clear_password_expiration();
+    } else {
+        session.revoke_all(user_id).await?;
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteSubscribersData {
+    emails: Vec<String>,
+}
+
+/// Answers `202 Accepted` when the deletion has to be approved by another
+/// admin first.
+#[tracing::instrument(
+    name = "Delete subscribers through the admin API",
+    skip(body, pool, two_person_rule)
+)]
+pub async fn api_delete_subscribers(
+    body: web::Json<DeleteSubscribersData>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+    two_person_rule: web::Data<TwoPersonRuleSettings>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_roles(Some(*role))?;
+
+    let emails = parse_emails(body.emails.iter().map(|email| email.trim()))?;
+
+    match delete_or_request_approval(emails, **user_id, &pool, &two_person_rule).await? {
+        DeletionOutcome::Deleted(deleted) => {
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "deleted": deleted })))
+        }
+        DeletionOutcome::Pending => Ok(HttpResponse::Accepted().finish()),
+    }
+}
+
+#[tracing::instrument(name = "Import subscribers through the admin API", skip(form, pool))]
+pub async fn api_import_subscribers(
+    MultipartForm(form): MultipartForm<ImportForm>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ImportError> {
+    reject_non_admin_roles(Some(*role))?;
+
+    let report = import_file(form.source(), &form.file.data, &pool).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[tracing::instrument(name = "List pending actions through the admin API", skip(pool))]
+pub async fn api_pending_actions(
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_roles(Some(*role))?;
+
+    let actions = get_pending_actions(&pool)
+        .await
+        .context("Failed to retrieve pending admin actions")?;
+
+    Ok(HttpResponse::Ok().json(actions))
+}
+
+#[tracing::instrument(name = "Approve admin action through the admin API", skip(pool))]
+pub async fn api_approve_action(
+    action_id: web::Path<Uuid>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_roles(Some(*role))?;
+
+    review_action(
+        action_id.into_inner(),
+        **user_id,
+        AdminActionStatus::Approved,
+        &pool,
+    )
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[tracing::instrument(name = "Reject admin action through the admin API", skip(pool))]
+pub async fn api_reject_action(
+    action_id: web::Path<Uuid>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, AdminActionError> {
+    reject_non_admin_roles(Some(*role))?;
+
+    review_action(
+        action_id.into_inner(),
+        **user_id,
+        AdminActionStatus::Rejected,
+        &pool,
+    )
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,101 @@
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    delivery_queue::{inspect_issue, resend_to_failed_recipients},
+    routes::error_chain_fmt,
+    user_role::UserRole,
+};
+
+#[derive(thiserror::Error)]
+pub enum IssueReportError {
+    #[error("Restricted operation")]
+    NonAdminError,
+    #[error("Newsletter issue not found")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for IssueReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for IssueReportError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            IssueReportError::NonAdminError => StatusCode::METHOD_NOT_ALLOWED,
+            IssueReportError::NotFound => StatusCode::NOT_FOUND,
+            IssueReportError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for IssueReportError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            IssueReportError::NonAdminError => "restricted-operation",
+            IssueReportError::NotFound => "issue-not-found",
+            IssueReportError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+fn reject_non_admin_roles(role: &UserRole) -> Result<(), IssueReportError> {
+    match role {
+        UserRole::Admin => Ok(()),
+        _ => Err(IssueReportError::NonAdminError),
+    }
+}
+
+/// Pending, dead and suppressed deliveries of an issue.
+#[tracing::instrument(name = "Report issue deliveries", skip(pool))]
+pub async fn issue_report(
+    newsletter_issue_id: web::Path<Uuid>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, IssueReportError> {
+    reject_non_admin_roles(&role)?;
+
+    let report = inspect_issue(&pool, newsletter_issue_id.into_inner())
+        .await
+        .context("Failed to inspect issue deliveries")?
+        .ok_or(IssueReportError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Sends the issue again to the recipients whose deliveries ran out of
+/// retries, skipping those no longer subscribed or already delivered to.
+#[tracing::instrument(name = "Resend issue to failed recipients", skip(pool))]
+pub async fn resend_to_failed(
+    newsletter_issue_id: web::Path<Uuid>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, IssueReportError> {
+    reject_non_admin_roles(&role)?;
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+
+    if inspect_issue(&pool, newsletter_issue_id)
+        .await
+        .context("Failed to inspect issue deliveries")?
+        .is_none()
+    {
+        return Err(IssueReportError::NotFound);
+    }
+
+    let outcome = resend_to_failed_recipients(&pool, newsletter_issue_id)
+        .await
+        .context("Failed to requeue failed deliveries")?;
+
+    Ok(HttpResponse::Ok().json(outcome))
+}
@@ -0,0 +1,107 @@
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    domain::{ListName, ListNameError},
+    routes::error_chain_fmt,
+};
+
+#[derive(thiserror::Error)]
+pub enum ListError {
+    #[error(transparent)]
+    InvalidName(ListNameError),
+    #[error("Duplicated list")]
+    DuplicatedListError,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ListError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ListError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ListError::InvalidName(_) => StatusCode::BAD_REQUEST,
+            ListError::DuplicatedListError => StatusCode::CONFLICT,
+            ListError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for ListError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            ListError::InvalidName(_) => "invalid-list",
+            ListError::DuplicatedListError => "duplicated-list",
+            ListError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct ListData {
+    name: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct NewsletterList {
+    id: Uuid,
+    name: String,
+}
+
+#[tracing::instrument(name = "List newsletter lists", skip(pool))]
+pub async fn list_lists(pool: web::Data<PgPool>) -> Result<HttpResponse, ListError> {
+    let lists = sqlx::query_as!(
+        NewsletterList,
+        r#"
+        SELECT list_id as id, name
+        FROM newsletter_lists
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve newsletter lists")?;
+
+    Ok(HttpResponse::Ok().json(lists))
+}
+
+#[tracing::instrument(name = "Create newsletter list", skip(body, pool))]
+pub async fn create_list(
+    body: web::Json<ListData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ListError> {
+    let name = ListName::parse(body.into_inner().name).map_err(ListError::InvalidName)?;
+
+    let list = sqlx::query_as!(
+        NewsletterList,
+        r#"
+        INSERT INTO newsletter_lists (list_id, name, created_at)
+        VALUES ($1, $2, $3)
+        RETURNING list_id as id, name
+        "#,
+        Uuid::new_v4(),
+        name.as_ref(),
+        Utc::now(),
+    )
+    .fetch_one(pool.get_ref())
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => ListError::DuplicatedListError,
+        e => ListError::UnexpectedError(anyhow::Error::new(e).context("Failed to store list")),
+    })?;
+
+    Ok(HttpResponse::Created().json(list))
+}
@@ -0,0 +1,87 @@
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    routes::error_chain_fmt,
+    telemetry::{current_log_filter, set_log_filter},
+    user_role::UserRole,
+};
+
+#[derive(thiserror::Error)]
+pub enum LogLevelError {
+    #[error("Restricted operation")]
+    NonAdminError,
+    #[error("Invalid log filter: {0}")]
+    InvalidFilter(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for LogLevelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for LogLevelError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            LogLevelError::NonAdminError => StatusCode::METHOD_NOT_ALLOWED,
+            LogLevelError::InvalidFilter(_) => StatusCode::BAD_REQUEST,
+            LogLevelError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for LogLevelError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            LogLevelError::NonAdminError => "restricted-operation",
+            LogLevelError::InvalidFilter(_) => "invalid-log-filter",
+            LogLevelError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+fn reject_non_admin_roles(role: &UserRole) -> Result<(), LogLevelError> {
+    match role {
+        UserRole::Admin => Ok(()),
+        _ => Err(LogLevelError::NonAdminError),
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct LogLevelData {
+    /// Directives in the `RUST_LOG` syntax, e.g. `info,newsletter=debug`.
+    filter: String,
+}
+
+#[tracing::instrument(name = "Get log level", skip_all)]
+pub async fn get_log_level(role: web::ReqData<UserRole>) -> Result<HttpResponse, LogLevelError> {
+    reject_non_admin_roles(&role)?;
+
+    let filter = current_log_filter()
+        .ok_or_else(|| anyhow::anyhow!("No reloadable log filter was installed"))?;
+
+    Ok(HttpResponse::Ok().json(LogLevelData { filter }))
+}
+
+/// The new filter applies to the whole process until it restarts.
+#[tracing::instrument(name = "Set log level", skip(role))]
+pub async fn set_log_level(
+    body: web::Json<LogLevelData>,
+    role: web::ReqData<UserRole>,
+) -> Result<HttpResponse, LogLevelError> {
+    reject_non_admin_roles(&role)?;
+
+    let filter = EnvFilter::try_new(&body.filter)
+        .map_err(|e| LogLevelError::InvalidFilter(e.to_string()))?;
+    set_log_filter(filter)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
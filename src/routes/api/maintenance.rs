@@ -0,0 +1,94 @@
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    maintenance::{recent_table_maintenance_runs, run_table_maintenance},
+    routes::error_chain_fmt,
+    user_role::UserRole,
+};
+
+/// How many runs are listed, a few days worth of daily runs.
+const LISTED_RUNS: i64 = 50;
+
+#[derive(thiserror::Error)]
+pub enum TableMaintenanceError {
+    #[error("Restricted operation")]
+    NonAdminError,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for TableMaintenanceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for TableMaintenanceError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            TableMaintenanceError::NonAdminError => StatusCode::METHOD_NOT_ALLOWED,
+            TableMaintenanceError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for TableMaintenanceError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            TableMaintenanceError::NonAdminError => "restricted-operation",
+            TableMaintenanceError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+fn reject_non_admin_roles(role: &UserRole) -> Result<(), TableMaintenanceError> {
+    match role {
+        UserRole::Admin => Ok(()),
+        _ => Err(TableMaintenanceError::NonAdminError),
+    }
+}
+
+/// The last runs of the table maintenance and how long they took.
+#[tracing::instrument(name = "Get table maintenance runs", skip_all)]
+pub async fn get_table_maintenance_runs(
+    pool: web::Data<PgPool>,
+    role: web::ReqData<UserRole>,
+) -> Result<HttpResponse, TableMaintenanceError> {
+    reject_non_admin_roles(&role)?;
+
+    let runs = recent_table_maintenance_runs(&pool, LISTED_RUNS)
+        .await
+        .context("Failed to retrieve the table maintenance runs")?;
+
+    Ok(HttpResponse::Ok().json(runs))
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TableMaintenanceRequest {
+    #[serde(default)]
+    reindex: bool,
+}
+
+/// Same maintenance as in the low-traffic window, right away, e.g. after a
+/// large import.
+#[tracing::instrument(name = "Run table maintenance on request", skip(pool, role))]
+pub async fn run_table_maintenance_now(
+    body: web::Json<TableMaintenanceRequest>,
+    pool: web::Data<PgPool>,
+    role: web::ReqData<UserRole>,
+) -> Result<HttpResponse, TableMaintenanceError> {
+    reject_non_admin_roles(&role)?;
+
+    let runs = run_table_maintenance(&pool, body.reindex)
+        .await
+        .context("Failed to record the table maintenance runs")?;
+
+    Ok(HttpResponse::Ok().json(runs))
+}
@@ -0,0 +1,27 @@
+mod admin;
+mod issues;
+mod lists;
+mod log_level;
+mod maintenance;
+mod reports;
+mod segments;
+mod settings;
+mod subscribers;
+mod suppressions;
+mod tags;
+mod topics;
+mod webhooks;
+
+pub use admin::*;
+pub use issues::*;
+pub use lists::*;
+pub use log_level::*;
+pub use maintenance::*;
+pub use reports::*;
+pub use segments::*;
+pub use settings::*;
+pub use subscribers::*;
+pub use suppressions::*;
+pub use tags::*;
+pub use topics::*;
+pub use webhooks::*;
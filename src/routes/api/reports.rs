@@ -0,0 +1,166 @@
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    authentication::UserId,
+    domain::{Email, EmailError},
+    routes::error_chain_fmt,
+    stats::{dashboard_stats, DashboardStats},
+    user_role::UserRole,
+};
+
+#[derive(thiserror::Error)]
+pub enum ReportError {
+    #[error("Restricted operation")]
+    NonAdminError,
+    #[error("The goal must be at least one new subscriber per week")]
+    InvalidGoal,
+    #[error(transparent)]
+    InvalidEmail(EmailError),
+    #[error("List not found")]
+    ListNotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ReportError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ReportError::NonAdminError => StatusCode::METHOD_NOT_ALLOWED,
+            ReportError::InvalidGoal | ReportError::InvalidEmail(_) => StatusCode::BAD_REQUEST,
+            ReportError::ListNotFound => StatusCode::NOT_FOUND,
+            ReportError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for ReportError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            ReportError::NonAdminError => "restricted-operation",
+            ReportError::InvalidGoal => "invalid-goal",
+            ReportError::InvalidEmail(_) => "invalid-email",
+            ReportError::ListNotFound => "list-not-found",
+            ReportError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+fn reject_non_admin_roles(role: &UserRole) -> Result<(), ReportError> {
+    match role {
+        UserRole::Admin => Ok(()),
+        _ => Err(ReportError::NonAdminError),
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct GrowthGoalData {
+    weekly_new_subscribers: i32,
+}
+
+/// Sets the number of confirmed subscribers a list should gain per week, as
+/// shown in the weekly report.
+#[tracing::instrument(name = "Set growth goal", skip(body, pool))]
+pub async fn set_growth_goal(
+    list_id: web::Path<Uuid>,
+    body: web::Json<GrowthGoalData>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ReportError> {
+    reject_non_admin_roles(&role)?;
+    if body.weekly_new_subscribers < 1 {
+        return Err(ReportError::InvalidGoal);
+    }
+
+    let updated = sqlx::query!(
+        r#"
+        INSERT INTO growth_goals (list_id, weekly_new_subscribers, updated_at)
+        SELECT list_id, $2, now()
+        FROM newsletter_lists
+        WHERE list_id = $1
+        ON CONFLICT (list_id) DO UPDATE
+        SET weekly_new_subscribers = EXCLUDED.weekly_new_subscribers,
+            updated_at = EXCLUDED.updated_at
+        "#,
+        list_id.into_inner(),
+        body.weekly_new_subscribers,
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to store the growth goal")?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(ReportError::ListNotFound);
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(serde::Deserialize)]
+pub struct ReportSubscriptionData {
+    /// Where the weekly report goes, none to opt out.
+    email: Option<String>,
+}
+
+/// Opts the admin in or out of the weekly report.
+#[tracing::instrument(name = "Set weekly report subscription", skip(body, pool))]
+pub async fn set_report_subscription(
+    body: web::Json<ReportSubscriptionData>,
+    role: web::ReqData<UserRole>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ReportError> {
+    reject_non_admin_roles(&role)?;
+    let email = body
+        .into_inner()
+        .email
+        .map(Email::parse)
+        .transpose()
+        .map_err(ReportError::InvalidEmail)?;
+
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET report_email = $2
+        WHERE user_id = $1
+        "#,
+        **user_id,
+        email.as_ref().map(|email| email.as_ref()),
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to store the weekly report subscription")?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// The figures of the admin dashboard, for any user.
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/stats",
+    tag = "admin",
+    responses((status = 200, description = "The dashboard figures", body = DashboardStats)),
+    security(("basic" = []), ("bearer" = []))
+)]
+#[tracing::instrument(name = "Get stats through the admin API", skip(pool))]
+pub async fn get_stats(pool: web::Data<PgPool>) -> Result<HttpResponse, ReportError> {
+    let stats = dashboard_stats(&pool)
+        .await
+        .context("Failed to compute the dashboard stats")?;
+
+    Ok(HttpResponse::Ok().json(stats))
+}
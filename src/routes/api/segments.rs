@@ -0,0 +1,267 @@
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{types::Json, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    domain::{SegmentName, SegmentNameError, TopicName, TopicNameError},
+    routes::error_chain_fmt,
+    segment::{SegmentRule, SegmentRules},
+};
+
+#[derive(thiserror::Error)]
+pub enum SegmentError {
+    #[error(transparent)]
+    InvalidName(SegmentNameError),
+    #[error(transparent)]
+    InvalidTopic(TopicNameError),
+    #[error("Segment not found")]
+    NotFound,
+    #[error("Duplicated segment")]
+    DuplicatedSegmentError,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SegmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for SegmentError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SegmentError::InvalidName(_) | SegmentError::InvalidTopic(_) => StatusCode::BAD_REQUEST,
+            SegmentError::NotFound => StatusCode::NOT_FOUND,
+            SegmentError::DuplicatedSegmentError => StatusCode::CONFLICT,
+            SegmentError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for SegmentError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            SegmentError::InvalidName(_) => "invalid-segment",
+            SegmentError::InvalidTopic(_) => "invalid-topic",
+            SegmentError::NotFound => "segment-not-found",
+            SegmentError::DuplicatedSegmentError => "duplicated-segment",
+            SegmentError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct SegmentData {
+    name: String,
+    rules: SegmentRules,
+}
+
+#[derive(serde::Serialize)]
+pub struct Segment {
+    id: Uuid,
+    name: String,
+    rules: Json<SegmentRules>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SegmentPreview {
+    subscribers: i64,
+}
+
+fn validate_rules(rules: &SegmentRules) -> Result<(), SegmentError> {
+    for rule in &rules.0 {
+        if let SegmentRule::Topic { name } = rule {
+            TopicName::parse(name.clone()).map_err(SegmentError::InvalidTopic)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn map_unique_violation(e: sqlx::Error) -> SegmentError {
+    match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => SegmentError::DuplicatedSegmentError,
+        e => {
+            SegmentError::UnexpectedError(anyhow::Error::new(e).context("Failed to store segment"))
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get segment rules", skip(pool))]
+async fn get_segment_rules(
+    segment_id: Uuid,
+    pool: &PgPool,
+) -> Result<Option<SegmentRules>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT rules as "rules: Json<SegmentRules>"
+        FROM segments
+        WHERE id = $1
+        "#,
+        segment_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|r| r.map(|r| r.rules.0))
+}
+
+#[tracing::instrument(name = "List segments", skip(pool))]
+pub async fn list_segments(pool: web::Data<PgPool>) -> Result<HttpResponse, SegmentError> {
+    let segments = sqlx::query_as!(
+        Segment,
+        r#"
+        SELECT id, name, rules as "rules: Json<SegmentRules>"
+        FROM segments
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve segments")?;
+
+    Ok(HttpResponse::Ok().json(segments))
+}
+
+#[tracing::instrument(name = "Create segment", skip(body, pool))]
+pub async fn create_segment(
+    body: web::Json<SegmentData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, SegmentError> {
+    let body = body.into_inner();
+    let name = SegmentName::parse(body.name).map_err(SegmentError::InvalidName)?;
+    validate_rules(&body.rules)?;
+
+    let segment = sqlx::query_as!(
+        Segment,
+        r#"
+        INSERT INTO segments (id, name, rules, created_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, name, rules as "rules: Json<SegmentRules>"
+        "#,
+        Uuid::new_v4(),
+        name.as_ref(),
+        Json(&body.rules) as _,
+        Utc::now(),
+    )
+    .fetch_one(pool.get_ref())
+    .await
+    .map_err(map_unique_violation)?;
+
+    Ok(HttpResponse::Created().json(segment))
+}
+
+#[tracing::instrument(name = "Get segment", skip(pool))]
+pub async fn get_segment(
+    segment_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, SegmentError> {
+    let segment = sqlx::query_as!(
+        Segment,
+        r#"
+        SELECT id, name, rules as "rules: Json<SegmentRules>"
+        FROM segments
+        WHERE id = $1
+        "#,
+        segment_id.into_inner(),
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve segment")?
+    .ok_or(SegmentError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(segment))
+}
+
+#[tracing::instrument(name = "Update segment", skip(body, pool))]
+pub async fn update_segment(
+    segment_id: web::Path<Uuid>,
+    body: web::Json<SegmentData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, SegmentError> {
+    let body = body.into_inner();
+    let name = SegmentName::parse(body.name).map_err(SegmentError::InvalidName)?;
+    validate_rules(&body.rules)?;
+
+    let segment = sqlx::query_as!(
+        Segment,
+        r#"
+        UPDATE segments
+        SET name = $1, rules = $2
+        WHERE id = $3
+        RETURNING id, name, rules as "rules: Json<SegmentRules>"
+        "#,
+        name.as_ref(),
+        Json(&body.rules) as _,
+        segment_id.into_inner(),
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(map_unique_violation)?
+    .ok_or(SegmentError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(segment))
+}
+
+#[tracing::instrument(name = "Delete segment", skip(pool))]
+pub async fn delete_segment(
+    segment_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, SegmentError> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM segments
+        WHERE id = $1
+        "#,
+        segment_id.into_inner(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to delete segment")?;
+
+    if result.rows_affected() == 0 {
+        return Err(SegmentError::NotFound);
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[tracing::instrument(name = "Preview segment", skip(pool))]
+pub async fn preview_segment(
+    segment_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, SegmentError> {
+    let rules = get_segment_rules(segment_id.into_inner(), &pool)
+        .await
+        .context("Failed to retrieve segment rules")?
+        .ok_or(SegmentError::NotFound)?;
+
+    let subscribers = rules
+        .count_subscribers(&pool)
+        .await
+        .context("Failed to count segment subscribers")?;
+
+    Ok(HttpResponse::Ok().json(SegmentPreview { subscribers }))
+}
+
+#[tracing::instrument(name = "Preview segment rules", skip(rules, pool))]
+pub async fn preview_segment_rules(
+    rules: web::Json<SegmentRules>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, SegmentError> {
+    validate_rules(&rules)?;
+
+    let subscribers = rules
+        .count_subscribers(&pool)
+        .await
+        .context("Failed to count segment subscribers")?;
+
+    Ok(HttpResponse::Ok().json(SegmentPreview { subscribers }))
+}
@@ -0,0 +1,81 @@
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use arc_swap::ArcSwap;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    configuration::DynamicSettings,
+    dynamic_settings::SettingsReloader,
+    routes::error_chain_fmt,
+    user_role::UserRole,
+};
+
+#[derive(thiserror::Error)]
+pub enum DynamicSettingsError {
+    #[error("Restricted operation")]
+    NonAdminError,
+    #[error("Invalid settings")]
+    InvalidSettings(#[source] anyhow::Error),
+}
+
+impl std::fmt::Debug for DynamicSettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for DynamicSettingsError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            DynamicSettingsError::NonAdminError => StatusCode::METHOD_NOT_ALLOWED,
+            DynamicSettingsError::InvalidSettings(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for DynamicSettingsError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            DynamicSettingsError::NonAdminError => "restricted-operation",
+            DynamicSettingsError::InvalidSettings(_) => "invalid-settings",
+        }
+    }
+}
+
+fn reject_non_admin_roles(role: &UserRole) -> Result<(), DynamicSettingsError> {
+    match role {
+        UserRole::Admin => Ok(()),
+        _ => Err(DynamicSettingsError::NonAdminError),
+    }
+}
+
+/// The settings that can change without restarting the application, as
+/// they were last applied.
+#[tracing::instrument(name = "Get dynamic settings", skip_all)]
+pub async fn get_dynamic_settings(
+    settings: web::Data<ArcSwap<DynamicSettings>>,
+    role: web::ReqData<UserRole>,
+) -> Result<HttpResponse, DynamicSettingsError> {
+    reject_non_admin_roles(&role)?;
+
+    Ok(HttpResponse::Ok().json(&**settings.load()))
+}
+
+/// Same as sending `SIGHUP` to the process, but reports why the
+/// configuration was rejected.
+#[tracing::instrument(name = "Reload dynamic settings on request", skip_all)]
+pub async fn reload_dynamic_settings(
+    settings_reloader: web::Data<SettingsReloader>,
+    role: web::ReqData<UserRole>,
+) -> Result<HttpResponse, DynamicSettingsError> {
+    reject_non_admin_roles(&role)?;
+
+    settings_reloader
+        .reload()
+        .map_err(DynamicSettingsError::InvalidSettings)?;
+
+    Ok(HttpResponse::Ok().json(&**settings_reloader.settings().load()))
+}
@@ -0,0 +1,584 @@
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    api_error::{ApiError, Problem, ProblemDetails},
+    authentication::UserId,
+    configuration::TwoPersonRuleSettings,
+    domain::{
+        IllegalTransition, SubscriberEmail, SubscriberEmailError, SubscriberName,
+        SubscriberNameError, SubscriptionStatus,
+    },
+    newsletter_list::{list_exists, DEFAULT_LIST_ID},
+    routes::{delete_or_request_approval, error_chain_fmt, DeletionOutcome},
+    subscriber_events::{
+        get_subscriber_events, record_events, SubscriberEvent, SubscriberEventKind,
+    },
+    user_role::UserRole,
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(thiserror::Error)]
+pub enum TimelineError {
+    #[error("Subscriber not found")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for TimelineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for TimelineError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            TimelineError::NotFound => StatusCode::NOT_FOUND,
+            TimelineError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for TimelineError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            TimelineError::NotFound => "subscriber-not-found",
+            TimelineError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct TimelineParameters {
+    /// Cursor returned by the previous page.
+    after: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+pub struct Timeline {
+    events: Vec<SubscriberEvent>,
+    next_cursor: Option<i64>,
+}
+
+#[tracing::instrument(name = "Check subscriber existence", skip(pool))]
+async fn subscriber_exists(pool: &PgPool, subscriber_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT 1 as exists
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|r| r.is_some())
+}
+
+/// Lists what happened to a subscriber, oldest events first. A page that
+/// comes back full carries the cursor to fetch the next one.
+#[tracing::instrument(name = "Get subscriber timeline", skip(parameters, pool))]
+pub async fn get_subscriber_timeline(
+    subscriber_id: web::Path<Uuid>,
+    parameters: web::Query<TimelineParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, TimelineError> {
+    let subscriber_id = subscriber_id.into_inner();
+    let limit = parameters
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    if !subscriber_exists(&pool, subscriber_id)
+        .await
+        .context("Failed to check if the subscriber exists")?
+    {
+        return Err(TimelineError::NotFound);
+    }
+
+    let events = get_subscriber_events(&pool, subscriber_id, parameters.after, limit)
+        .await
+        .context("Failed to retrieve subscriber events")?;
+    let next_cursor = match events.last() {
+        Some(event) if events.len() as i64 == limit => Some(event.id),
+        _ => None,
+    };
+
+    Ok(HttpResponse::Ok().json(Timeline {
+        events,
+        next_cursor,
+    }))
+}
+
+#[derive(thiserror::Error)]
+pub enum SubscriberError {
+    #[error("Restricted operation")]
+    NonAdminError,
+    #[error(transparent)]
+    InvalidEmail(SubscriberEmailError),
+    #[error(transparent)]
+    InvalidName(SubscriberNameError),
+    #[error("List not found")]
+    ListNotFound,
+    #[error("Subscriber not found")]
+    NotFound,
+    #[error("The email is already subscribed to the list")]
+    AlreadySubscribed,
+    #[error(transparent)]
+    IllegalTransition(IllegalTransition),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SubscriberError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for SubscriberError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SubscriberError::NonAdminError => StatusCode::METHOD_NOT_ALLOWED,
+            SubscriberError::InvalidEmail(_) | SubscriberError::InvalidName(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            SubscriberError::ListNotFound | SubscriberError::NotFound => StatusCode::NOT_FOUND,
+            SubscriberError::AlreadySubscribed | SubscriberError::IllegalTransition(_) => {
+                StatusCode::CONFLICT
+            }
+            SubscriberError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for SubscriberError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            SubscriberError::NonAdminError => "restricted-operation",
+            SubscriberError::InvalidEmail(_) => "invalid-email",
+            SubscriberError::InvalidName(_) => "invalid-name",
+            SubscriberError::ListNotFound => "list-not-found",
+            SubscriberError::NotFound => "subscriber-not-found",
+            SubscriberError::AlreadySubscribed => "already-subscribed",
+            SubscriberError::IllegalTransition(_) => "illegal-transition",
+            SubscriberError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+fn reject_non_admin_roles(role: &UserRole) -> Result<(), SubscriberError> {
+    match role {
+        UserRole::Admin => Ok(()),
+        _ => Err(SubscriberError::NonAdminError),
+    }
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct Subscriber {
+    pub id: Uuid,
+    pub list_id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: SubscriptionStatus,
+    pub subscribed_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    /// Answers to the custom signup fields, by field name.
+    #[schema(value_type = Object)]
+    pub attributes: serde_json::Value,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SubscribersParameters {
+    status: Option<SubscriptionStatus>,
+    /// 50 by default, at most 200.
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// Lists subscribers, oldest first, optionally in a given status. Erased
+/// subscribers are left out.
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/subscribers",
+    tag = "subscribers",
+    params(SubscribersParameters),
+    responses(
+        (status = 200, description = "A page of subscribers", body = Vec<Subscriber>),
+        (status = 405, description = "Only admins manage subscribers", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+#[tracing::instrument(name = "List subscribers", skip(parameters, pool))]
+pub async fn list_subscribers(
+    parameters: web::Query<SubscribersParameters>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, SubscriberError> {
+    reject_non_admin_roles(&role)?;
+    let limit = parameters
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let offset = parameters.offset.unwrap_or(0).max(0);
+
+    let subscribers = sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT id, list_id, email, name, status AS "status: SubscriptionStatus",
+            subscribed_at, confirmed_at, subscriber_attributes AS attributes
+        FROM subscriptions
+        WHERE erased_at IS NULL AND ($1::subscription_status IS NULL OR status = $1)
+        ORDER BY subscribed_at, id
+        LIMIT $2
+        OFFSET $3
+        "#,
+        parameters.status as Option<SubscriptionStatus>,
+        limit,
+        offset,
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve subscribers")?;
+
+    Ok(HttpResponse::Ok().json(subscribers))
+}
+
+#[tracing::instrument(name = "Fetch subscriber", skip(pool))]
+async fn fetch_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<Subscriber>, sqlx::Error> {
+    sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT id, list_id, email, name, status AS "status: SubscriptionStatus",
+            subscribed_at, confirmed_at, subscriber_attributes AS attributes
+        FROM subscriptions
+        WHERE id = $1 AND erased_at IS NULL
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/subscribers/{subscriber_id}",
+    tag = "subscribers",
+    params(("subscriber_id" = Uuid, Path, description = "Id of the subscriber")),
+    responses(
+        (status = 200, description = "The subscriber", body = Subscriber),
+        (status = 404, description = "Unknown subscriber", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+#[tracing::instrument(name = "Get subscriber", skip(pool))]
+pub async fn get_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, SubscriberError> {
+    reject_non_admin_roles(&role)?;
+
+    let subscriber = fetch_subscriber(&pool, subscriber_id.into_inner())
+        .await
+        .context("Failed to retrieve the subscriber")?
+        .ok_or(SubscriberError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(subscriber))
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct NewSubscriberData {
+    email: String,
+    name: String,
+    /// The default list if missing.
+    list_id: Option<Uuid>,
+}
+
+/// Adds a confirmed subscriber to the list, the default one unless another
+/// is given: whoever calls the API vouches for the opt-in.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/subscribers",
+    tag = "subscribers",
+    request_body = NewSubscriberData,
+    responses(
+        (status = 201, description = "The subscriber was added", body = Subscriber),
+        (status = 400, description = "Invalid email or name", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown list", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "The email is already subscribed to the list", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+#[tracing::instrument(
+    name = "Create subscriber through the admin API",
+    skip(body, pool),
+    fields(subscriber_email = %body.email)
+)]
+pub async fn create_subscriber(
+    body: web::Json<NewSubscriberData>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, SubscriberError> {
+    reject_non_admin_roles(&role)?;
+    let body = body.into_inner();
+    let email = SubscriberEmail::parse(body.email).map_err(SubscriberError::InvalidEmail)?;
+    let name = SubscriberName::parse(body.name).map_err(SubscriberError::InvalidName)?;
+    let list_id = body.list_id.unwrap_or(DEFAULT_LIST_ID);
+
+    if !list_exists(&pool, list_id)
+        .await
+        .context("Failed to check if the list exists")?
+    {
+        return Err(SubscriberError::ListNotFound);
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        INSERT INTO subscriptions (
+            id, list_id, email, name, subscribed_at, status, confirmed_at
+        )
+        VALUES ($1, $2, $3, $4, now(), $5, now())
+        ON CONFLICT (list_id, email) DO NOTHING
+        RETURNING id, list_id, email, name, status AS "status: SubscriptionStatus",
+            subscribed_at, confirmed_at, subscriber_attributes AS attributes
+        "#,
+        Uuid::new_v4(),
+        list_id,
+        email.to_string(),
+        name.as_ref(),
+        SubscriptionStatus::Confirmed as SubscriptionStatus,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to insert the subscriber")?
+    .ok_or(SubscriberError::AlreadySubscribed)?;
+
+    for kind in [
+        SubscriberEventKind::Subscribed,
+        SubscriberEventKind::Confirmed,
+    ] {
+        record_events(
+            &mut transaction,
+            &[subscriber.id],
+            kind,
+            serde_json::json!({}),
+        )
+        .await
+        .context("Failed to record the subscriber events")?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new subscriber")?;
+
+    Ok(HttpResponse::Created().json(subscriber))
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct SubscriberChangesData {
+    /// Left as is if missing, as is the status.
+    name: Option<String>,
+    status: Option<SubscriptionStatus>,
+}
+
+/// The event recorded when a subscriber is moved to the status.
+fn status_event(status: SubscriptionStatus) -> Option<SubscriberEventKind> {
+    match status {
+        SubscriptionStatus::PendingConfirmation => None,
+        SubscriptionStatus::Confirmed => Some(SubscriberEventKind::Confirmed),
+        SubscriptionStatus::Unsubscribed => Some(SubscriberEventKind::Unsubscribed),
+        SubscriptionStatus::Suppressed => Some(SubscriberEventKind::Suppressed),
+        SubscriptionStatus::Bounced => Some(SubscriberEventKind::Bounced),
+        SubscriptionStatus::Complained => Some(SubscriberEventKind::Complained),
+    }
+}
+
+#[tracing::instrument(name = "Lock subscriber status", skip(transaction))]
+async fn lock_subscriber_status(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriptionStatus>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT status AS "status: SubscriptionStatus"
+        FROM subscriptions
+        WHERE id = $1 AND erased_at IS NULL
+        FOR UPDATE
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+}
+
+/// Renames the subscriber and/or moves it to another status, along the
+/// transitions [`SubscriptionStatus`] allows. Subscribers can't be sent
+/// back to pending confirmation from here, since nothing would ask them to
+/// opt in again.
+#[utoipa::path(
+    patch,
+    path = "/api/v1/admin/subscribers/{subscriber_id}",
+    tag = "subscribers",
+    params(("subscriber_id" = Uuid, Path, description = "Id of the subscriber")),
+    request_body = SubscriberChangesData,
+    responses(
+        (status = 200, description = "The updated subscriber", body = Subscriber),
+        (status = 400, description = "Invalid name", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown subscriber", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "The subscriber can't move to the status", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+#[tracing::instrument(name = "Update subscriber through the admin API", skip(body, pool))]
+pub async fn update_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    body: web::Json<SubscriberChangesData>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, SubscriberError> {
+    reject_non_admin_roles(&role)?;
+    let subscriber_id = subscriber_id.into_inner();
+    let body = body.into_inner();
+    let name = body
+        .name
+        .map(SubscriberName::parse)
+        .transpose()
+        .map_err(SubscriberError::InvalidName)?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    let current = lock_subscriber_status(&mut transaction, subscriber_id)
+        .await
+        .context("Failed to retrieve the subscriber status")?
+        .ok_or(SubscriberError::NotFound)?;
+
+    let event = match body.status {
+        Some(next) if next != current => {
+            let event = status_event(next).ok_or(SubscriberError::IllegalTransition(
+                IllegalTransition {
+                    from: current,
+                    to: next,
+                },
+            ))?;
+            current
+                .transition_to(next)
+                .map_err(SubscriberError::IllegalTransition)?;
+            Some(event)
+        }
+        _ => None,
+    };
+
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET
+            name = COALESCE($2, name),
+            status = COALESCE($3, status),
+            confirmed_at = CASE WHEN $3 = 'confirmed' THEN now() ELSE confirmed_at END
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        name.as_ref().map(|name| name.as_ref()),
+        body.status as Option<SubscriptionStatus>,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to update the subscriber")?;
+
+    if let Some(kind) = event {
+        record_events(
+            &mut transaction,
+            &[subscriber_id],
+            kind,
+            serde_json::json!({}),
+        )
+        .await
+        .context("Failed to record the subscriber event")?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a subscriber")?;
+
+    let subscriber = fetch_subscriber(&pool, subscriber_id)
+        .await
+        .context("Failed to retrieve the subscriber")?
+        .ok_or(SubscriberError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(subscriber))
+}
+
+/// Deletes every subscription of the subscriber's address, like
+/// `POST /subscribers/delete` does. Answers `202 Accepted` when the deletion
+/// has to be approved by another admin first.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/subscribers/{subscriber_id}",
+    tag = "subscribers",
+    params(("subscriber_id" = Uuid, Path, description = "Id of the subscriber")),
+    responses(
+        (status = 200, description = "The subscriptions of the address were deleted"),
+        (status = 202, description = "Another admin has to approve the deletion"),
+        (status = 404, description = "Unknown subscriber", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("basic" = []), ("bearer" = []))
+)]
+#[tracing::instrument(
+    name = "Delete subscriber through the admin API",
+    skip(pool, two_person_rule)
+)]
+pub async fn delete_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+    two_person_rule: web::Data<TwoPersonRuleSettings>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, SubscriberError> {
+    reject_non_admin_roles(&role)?;
+
+    let subscriber = fetch_subscriber(&pool, subscriber_id.into_inner())
+        .await
+        .context("Failed to retrieve the subscriber")?
+        .ok_or(SubscriberError::NotFound)?;
+
+    match delete_or_request_approval(vec![subscriber.email], **user_id, &pool, &two_person_rule)
+        .await?
+    {
+        DeletionOutcome::Deleted(deleted) => {
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "deleted": deleted })))
+        }
+        DeletionOutcome::Pending => Ok(HttpResponse::Accepted().finish()),
+    }
+}
@@ -0,0 +1,68 @@
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use sqlx::PgPool;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    email_client::EmailClient,
+    routes::error_chain_fmt,
+    suppression_sync::sync_suppressions,
+    user_role::UserRole,
+};
+
+#[derive(thiserror::Error)]
+pub enum SuppressionSyncError {
+    #[error("Restricted operation")]
+    NonAdminError,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SuppressionSyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for SuppressionSyncError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SuppressionSyncError::NonAdminError => StatusCode::METHOD_NOT_ALLOWED,
+            SuppressionSyncError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for SuppressionSyncError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            SuppressionSyncError::NonAdminError => "restricted-operation",
+            SuppressionSyncError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+fn reject_non_admin_roles(role: &UserRole) -> Result<(), SuppressionSyncError> {
+    match role {
+        UserRole::Admin => Ok(()),
+        _ => Err(SuppressionSyncError::NonAdminError),
+    }
+}
+
+/// Same sync as on startup, answering with the addresses that were
+/// suppressed on one side only.
+#[tracing::instrument(name = "Sync suppressions on request", skip_all)]
+pub async fn api_sync_suppressions(
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    role: web::ReqData<UserRole>,
+) -> Result<HttpResponse, SuppressionSyncError> {
+    reject_non_admin_roles(&role)?;
+
+    let report = sync_suppressions(&pool, email_client.get_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
@@ -0,0 +1,176 @@
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    domain::{TagName, TagNameError},
+    routes::error_chain_fmt,
+    user_role::UserRole,
+};
+
+#[derive(thiserror::Error)]
+pub enum TagError {
+    #[error("Restricted operation")]
+    NonAdminError,
+    #[error(transparent)]
+    InvalidTag(TagNameError),
+    #[error("Subscriber not found")]
+    SubscriberNotFound,
+    #[error("Tag not found")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for TagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for TagError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            TagError::NonAdminError => StatusCode::METHOD_NOT_ALLOWED,
+            TagError::InvalidTag(_) => StatusCode::BAD_REQUEST,
+            TagError::SubscriberNotFound | TagError::NotFound => StatusCode::NOT_FOUND,
+            TagError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for TagError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            TagError::NonAdminError => "restricted-operation",
+            TagError::InvalidTag(_) => "invalid-tag",
+            TagError::SubscriberNotFound => "subscriber-not-found",
+            TagError::NotFound => "tag-not-found",
+            TagError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+fn reject_non_admin_roles(role: &UserRole) -> Result<(), TagError> {
+    match role {
+        UserRole::Admin => Ok(()),
+        _ => Err(TagError::NonAdminError),
+    }
+}
+
+#[tracing::instrument(name = "Check subscriber", skip(pool))]
+async fn subscriber_exists(subscriber_id: Uuid, pool: &PgPool) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM subscriptions WHERE id = $1) AS "exists!""#,
+        subscriber_id
+    )
+    .fetch_one(pool)
+    .await
+    .map(|r| r.exists)
+}
+
+#[tracing::instrument(name = "List subscriber tags", skip(pool))]
+pub async fn list_subscriber_tags(
+    subscriber_id: web::Path<Uuid>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, TagError> {
+    reject_non_admin_roles(&role)?;
+    let subscriber_id = subscriber_id.into_inner();
+
+    if !subscriber_exists(subscriber_id, &pool)
+        .await
+        .context("Failed to check subscriber")?
+    {
+        return Err(TagError::SubscriberNotFound);
+    }
+
+    let tags: Vec<String> = sqlx::query!(
+        r#"
+        SELECT tag
+        FROM subscriber_tags
+        WHERE subscriber_id = $1
+        ORDER BY tag
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve subscriber tags")?
+    .into_iter()
+    .map(|r| r.tag)
+    .collect();
+
+    Ok(HttpResponse::Ok().json(tags))
+}
+
+/// Assigning a tag the subscriber already has is a no-op.
+#[tracing::instrument(name = "Tag subscriber", skip(pool))]
+pub async fn tag_subscriber(
+    path: web::Path<(Uuid, String)>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, TagError> {
+    reject_non_admin_roles(&role)?;
+    let (subscriber_id, tag) = path.into_inner();
+    let tag = TagName::parse(tag).map_err(TagError::InvalidTag)?;
+
+    if !subscriber_exists(subscriber_id, &pool)
+        .await
+        .context("Failed to check subscriber")?
+    {
+        return Err(TagError::SubscriberNotFound);
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag, tagged_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        tag.as_ref(),
+        Utc::now(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to tag subscriber")?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[tracing::instrument(name = "Untag subscriber", skip(pool))]
+pub async fn untag_subscriber(
+    path: web::Path<(Uuid, String)>,
+    role: web::ReqData<UserRole>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, TagError> {
+    reject_non_admin_roles(&role)?;
+    let (subscriber_id, tag) = path.into_inner();
+    let tag = TagName::parse(tag).map_err(TagError::InvalidTag)?;
+
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM subscriber_tags
+        WHERE subscriber_id = $1 AND tag = $2
+        "#,
+        subscriber_id,
+        tag.as_ref(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to untag subscriber")?;
+
+    if result.rows_affected() == 0 {
+        return Err(TagError::NotFound);
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,362 @@
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    domain::{SubscriberEmail, SubscriberEmailError, TopicName, TopicNameError},
+    routes::error_chain_fmt,
+    subscriber_events::{record_events, SubscriberEventKind},
+};
+
+#[derive(thiserror::Error)]
+pub enum TopicError {
+    #[error(transparent)]
+    InvalidName(TopicNameError),
+    #[error(transparent)]
+    InvalidEmail(SubscriberEmailError),
+    #[error("Topic not found")]
+    NotFound,
+    #[error("Subscriber not found")]
+    SubscriberNotFound,
+    #[error("Duplicated topic")]
+    DuplicatedTopicError,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for TopicError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for TopicError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            TopicError::InvalidName(_) | TopicError::InvalidEmail(_) => StatusCode::BAD_REQUEST,
+            TopicError::NotFound | TopicError::SubscriberNotFound => StatusCode::NOT_FOUND,
+            TopicError::DuplicatedTopicError => StatusCode::CONFLICT,
+            TopicError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for TopicError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            TopicError::InvalidName(_) => "invalid-topic",
+            TopicError::InvalidEmail(_) => "invalid-subscriber",
+            TopicError::NotFound => "topic-not-found",
+            TopicError::SubscriberNotFound => "subscriber-not-found",
+            TopicError::DuplicatedTopicError => "duplicated-topic",
+            TopicError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct TopicData {
+    name: String,
+    #[serde(default)]
+    description: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TopicSubscriberData {
+    email: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct Topic {
+    id: Uuid,
+    name: String,
+    description: String,
+}
+
+fn map_unique_violation(e: sqlx::Error) -> TopicError {
+    match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => TopicError::DuplicatedTopicError,
+        e => TopicError::UnexpectedError(anyhow::Error::new(e).context("Failed to store topic")),
+    }
+}
+
+#[tracing::instrument(name = "List topics", skip(pool))]
+pub async fn list_topics(pool: web::Data<PgPool>) -> Result<HttpResponse, TopicError> {
+    let topics = sqlx::query_as!(
+        Topic,
+        r#"
+        SELECT id, name, description
+        FROM topics
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve topics")?;
+
+    Ok(HttpResponse::Ok().json(topics))
+}
+
+#[tracing::instrument(name = "Create topic", skip(body, pool))]
+pub async fn create_topic(
+    body: web::Json<TopicData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, TopicError> {
+    let body = body.into_inner();
+    let name = TopicName::parse(body.name).map_err(TopicError::InvalidName)?;
+
+    let topic = sqlx::query_as!(
+        Topic,
+        r#"
+        INSERT INTO topics (id, name, description, created_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, name, description
+        "#,
+        Uuid::new_v4(),
+        name.as_ref(),
+        body.description,
+        Utc::now(),
+    )
+    .fetch_one(pool.get_ref())
+    .await
+    .map_err(map_unique_violation)?;
+
+    Ok(HttpResponse::Created().json(topic))
+}
+
+#[tracing::instrument(name = "Get topic", skip(pool))]
+pub async fn get_topic(
+    topic_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, TopicError> {
+    let topic = sqlx::query_as!(
+        Topic,
+        r#"
+        SELECT id, name, description
+        FROM topics
+        WHERE id = $1
+        "#,
+        topic_id.into_inner(),
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve topic")?
+    .ok_or(TopicError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(topic))
+}
+
+#[tracing::instrument(name = "Update topic", skip(body, pool))]
+pub async fn update_topic(
+    topic_id: web::Path<Uuid>,
+    body: web::Json<TopicData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, TopicError> {
+    let body = body.into_inner();
+    let name = TopicName::parse(body.name).map_err(TopicError::InvalidName)?;
+
+    let topic = sqlx::query_as!(
+        Topic,
+        r#"
+        UPDATE topics
+        SET name = $1, description = $2
+        WHERE id = $3
+        RETURNING id, name, description
+        "#,
+        name.as_ref(),
+        body.description,
+        topic_id.into_inner(),
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(map_unique_violation)?
+    .ok_or(TopicError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(topic))
+}
+
+#[tracing::instrument(name = "Delete topic", skip(pool))]
+pub async fn delete_topic(
+    topic_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, TopicError> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM topics
+        WHERE id = $1
+        "#,
+        topic_id.into_inner(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to delete topic")?;
+
+    if result.rows_affected() == 0 {
+        return Err(TopicError::NotFound);
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[tracing::instrument(name = "Check if topic exists", skip(pool))]
+async fn topic_exists(topic_id: Uuid, pool: &PgPool) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT 1 as exists
+        FROM topics
+        WHERE id = $1
+        "#,
+        topic_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|r| r.is_some())
+}
+
+#[tracing::instrument(name = "Add subscriber to topic", skip(body, pool))]
+pub async fn add_topic_subscriber(
+    topic_id: web::Path<Uuid>,
+    body: web::Json<TopicSubscriberData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, TopicError> {
+    let topic_id = topic_id.into_inner();
+    let email =
+        SubscriberEmail::parse(body.into_inner().email).map_err(TopicError::InvalidEmail)?;
+
+    if !topic_exists(topic_id, &pool)
+        .await
+        .context("Failed to check topic")?
+    {
+        return Err(TopicError::NotFound);
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    let added: Vec<Uuid> = sqlx::query!(
+        r#"
+        INSERT INTO subscriber_topics (subscriber_id, topic_id)
+        SELECT id, $1
+        FROM subscriptions
+        WHERE email = $2
+        ON CONFLICT DO NOTHING
+        RETURNING subscriber_id
+        "#,
+        topic_id,
+        email.as_ref().as_ref(),
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to add subscriber to topic")?
+    .into_iter()
+    .map(|r| r.subscriber_id)
+    .collect();
+
+    record_events(
+        &mut transaction,
+        &added,
+        SubscriberEventKind::TopicAdded,
+        serde_json::json!({ "topic_id": topic_id }),
+    )
+    .await
+    .context("Failed to record the topics added to a subscriber")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to add subscriber to topic")?;
+
+    // Nothing is returned either when the subscriber doesn't exist or when it
+    // was already assigned to the topic.
+    if added.is_empty()
+        && !subscriber_has_topic(topic_id, &email, &pool)
+            .await
+            .context("Failed to check subscriber topics")?
+    {
+        return Err(TopicError::SubscriberNotFound);
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[tracing::instrument(name = "Check subscriber topic", skip(pool))]
+async fn subscriber_has_topic(
+    topic_id: Uuid,
+    email: &SubscriberEmail,
+    pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT 1 as exists
+        FROM subscriber_topics st
+        JOIN subscriptions s ON s.id = st.subscriber_id
+        WHERE st.topic_id = $1 AND s.email = $2
+        "#,
+        topic_id,
+        email.as_ref().as_ref(),
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|r| r.is_some())
+}
+
+#[tracing::instrument(name = "Remove subscriber from topic", skip(pool))]
+pub async fn remove_topic_subscriber(
+    path: web::Path<(Uuid, String)>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, TopicError> {
+    let (topic_id, email) = path.into_inner();
+    let email = SubscriberEmail::parse(email).map_err(TopicError::InvalidEmail)?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    let removed: Vec<Uuid> = sqlx::query!(
+        r#"
+        DELETE FROM subscriber_topics
+        WHERE topic_id = $1 AND subscriber_id IN (
+            SELECT id FROM subscriptions WHERE email = $2
+        )
+        RETURNING subscriber_id
+        "#,
+        topic_id,
+        email.as_ref().as_ref(),
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to remove subscriber from topic")?
+    .into_iter()
+    .map(|r| r.subscriber_id)
+    .collect();
+
+    if removed.is_empty() {
+        return Err(TopicError::NotFound);
+    }
+
+    record_events(
+        &mut transaction,
+        &removed,
+        SubscriberEventKind::TopicRemoved,
+        serde_json::json!({ "topic_id": topic_id }),
+    )
+    .await
+    .context("Failed to record the topics removed from a subscriber")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to remove subscriber from topic")?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,173 @@
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    domain::{WebhookUrl, WebhookUrlError},
+    routes::error_chain_fmt,
+    token_generator::{generate_webhook_secret, TokenGenerator},
+    webhooks::{get_webhooks, recent_deliveries, remove_webhook, store_webhook},
+};
+
+#[derive(thiserror::Error)]
+pub enum WebhookError {
+    #[error(transparent)]
+    InvalidUrl(WebhookUrlError),
+    #[error("Webhook not found")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for WebhookError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            WebhookError::InvalidUrl(_) => StatusCode::BAD_REQUEST,
+            WebhookError::NotFound => StatusCode::NOT_FOUND,
+            WebhookError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for WebhookError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            WebhookError::InvalidUrl(_) => "invalid-webhook",
+            WebhookError::NotFound => "webhook-not-found",
+            WebhookError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct WebhookData {
+    url: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct BackfillReport {
+    events: u64,
+}
+
+/// Attempts listed by the deliveries endpoint.
+const DELIVERIES_LIMIT: i64 = 50;
+
+#[tracing::instrument(name = "List webhooks", skip(pool))]
+pub async fn list_webhooks(pool: web::Data<PgPool>) -> Result<HttpResponse, WebhookError> {
+    let webhooks = get_webhooks(&pool)
+        .await
+        .context("Failed to retrieve webhooks")?;
+
+    Ok(HttpResponse::Ok().json(webhooks))
+}
+
+/// Registers an endpoint that receives every event recorded from now on.
+/// Past events can be replayed with a backfill. The response holds the
+/// secret payloads are signed with, which can't be retrieved later.
+#[tracing::instrument(name = "Register webhook", skip(body, pool, token_generator))]
+pub async fn register_webhook(
+    body: web::Json<WebhookData>,
+    pool: web::Data<PgPool>,
+    token_generator: web::Data<dyn TokenGenerator>,
+) -> Result<HttpResponse, WebhookError> {
+    let url = WebhookUrl::parse(body.into_inner().url).map_err(WebhookError::InvalidUrl)?;
+
+    let webhook = store_webhook(&pool, &url, &generate_webhook_secret(&**token_generator))
+        .await
+        .context("Failed to store webhook")?;
+
+    Ok(HttpResponse::Created().json(webhook))
+}
+
+#[tracing::instrument(name = "Delete webhook", skip(pool))]
+pub async fn delete_webhook(
+    webhook_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, WebhookError> {
+    let deleted = remove_webhook(&pool, webhook_id.into_inner())
+        .await
+        .context("Failed to delete webhook")?;
+
+    if !deleted {
+        return Err(WebhookError::NotFound);
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Latest attempts to deliver events to the webhook, most recent first.
+#[tracing::instrument(name = "List webhook deliveries", skip(pool))]
+pub async fn list_webhook_deliveries(
+    webhook_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, WebhookError> {
+    let deliveries = recent_deliveries(&pool, Some(webhook_id.into_inner()), DELIVERIES_LIMIT)
+        .await
+        .context("Failed to retrieve webhook deliveries")?;
+
+    Ok(HttpResponse::Ok().json(deliveries))
+}
+
+/// Schedules the delivery of every event recorded so far to the webhook, in
+/// the order they happened. Events still waiting to be delivered to it are
+/// not sent twice.
+#[tracing::instrument(name = "Backfill webhook", skip(pool))]
+pub async fn backfill_webhook(
+    webhook_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, WebhookError> {
+    let webhook_id = webhook_id.into_inner();
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    sqlx::query!(
+        r#"
+        SELECT webhook_id
+        FROM webhooks
+        WHERE webhook_id = $1
+        FOR UPDATE
+        "#,
+        webhook_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve webhook")?
+    .ok_or(WebhookError::NotFound)?;
+
+    let events = sqlx::query!(
+        r#"
+        INSERT INTO webhook_delivery_queue (webhook_id, event_id, occurred_at)
+        SELECT $1, event_id, occurred_at
+        FROM subscriber_events
+        ORDER BY event_id
+        ON CONFLICT DO NOTHING
+        "#,
+        webhook_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to enqueue webhook backfill")?
+    .rows_affected();
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to backfill a webhook")?;
+
+    Ok(HttpResponse::Accepted().json(BackfillReport { events }))
+}
@@ -5,11 +5,14 @@ use actix_web::{
 use actix_web_flash_messages::IncomingFlashMessages;
 use anyhow::Context;
 use sqlx::PgPool;
-use std::fmt::Write;
 
 use crate::{
+    api_error::{ApiError, Problem},
     domain::{InvitationToken, InvitationTokenError},
     routes::error_chain_fmt,
+    session_state::TypedSession,
+    template::{render_collaborator_registration_page, PageLayout},
+    token_generator::hash_token,
 };
 
 #[derive(serde::Deserialize)]
@@ -43,6 +46,20 @@ impl ResponseError for CollaboratorRegistrationFormError {
             }
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for CollaboratorRegistrationFormError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            CollaboratorRegistrationFormError::ValidationError(_) => "invalid-invitation-token",
+            CollaboratorRegistrationFormError::MissingInvitationError => "unknown-invitation",
+            CollaboratorRegistrationFormError::UnexpectedError(_) => "internal-error",
+        }
+    }
 }
 
 impl TryFrom<Parameters> for InvitationToken {
@@ -53,25 +70,33 @@ impl TryFrom<Parameters> for InvitationToken {
     }
 }
 
-pub async fn contains_invitation_token(
+/// An invitation not yet used to register.
+pub struct PendingInvitation {
+    /// Address it was sent to, unless sent before invitations were bound to
+    /// one.
+    pub email: Option<String>,
+}
+
+pub async fn get_invitation(
     token: InvitationToken,
     pool: &PgPool,
-) -> Result<bool, sqlx::Error> {
-    sqlx::query!(
+) -> Result<Option<PendingInvitation>, sqlx::Error> {
+    sqlx::query_as!(
+        PendingInvitation,
         r#"
-        SELECT 1 as contains
+        SELECT email
         FROM invitation_tokens
-        WHERE invitation_token = $1
+        WHERE invitation_token_hash = $1
         "#,
-        token.as_ref()
+        hash_token(token.as_ref())
     )
     .fetch_optional(pool)
     .await
-    .map(|r| r.is_some())
 }
 
 pub async fn register_collaborator_form(
     parameters: web::Query<Parameters>,
+    session: TypedSession,
     pool: web::Data<PgPool>,
     flash_messages: IncomingFlashMessages,
 ) -> Result<HttpResponse, CollaboratorRegistrationFormError> {
@@ -80,54 +105,18 @@ pub async fn register_collaborator_form(
         .try_into()
         .map_err(CollaboratorRegistrationFormError::ValidationError)?;
 
-    if !contains_invitation_token(invitation_token, &pool)
+    let invitation = get_invitation(invitation_token, &pool)
         .await
         .context("Failed to check invitation token")?
-    {
-        return Err(CollaboratorRegistrationFormError::MissingInvitationError);
-    }
+        .ok_or(CollaboratorRegistrationFormError::MissingInvitationError)?;
 
-    let mut error_html = String::new();
-    for m in flash_messages.iter() {
-        writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
+    let csrf_token = session.csrf_token()?;
+    let layout = PageLayout::new(None, &flash_messages);
+    let page =
+        render_collaborator_registration_page(&layout, &csrf_token, invitation.email.as_deref())
+            .context("Failed to render the collaborator registration page")?;
 
-    let response = HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-<html lang="en">
-    <head>
-        <meta http-equiv="content-type" content="text/html; charset=utf-8">
-        <title>Collaborator registration</title>
-    </head>
-    <body>
-        {error_html}
-        <form action="/collaborator/register" method="post">
-            <label>
-                Username
-                <input type="text" placeholder="Enter Username" name="username">
-            </label>
-            <label>
-                Password
-                <input type="password" placeholder="Enter Password" name="password">
-            </label>
-            <label>
-                Validation Code
-                <input type="text" placeholder="Enter Validation Code" name="validation_code" pattern="[0-9]{{6}}" required>
-            </label>
-            <label>
-                <input id="invitation_token" type="hidden" name="invitation_token">
-            </label>
-            <button type="submit">Register</button>
-        </form>
-    </body>
-    <script>
-        const invitation_token = (new URLSearchParams(window.location.search)).get("invitation_token");
-        document.getElementById("invitation_token").value = invitation_token || "";
-    </script>
-</html>"#,
-        ));
-
-    Ok(response)
+        .body(page))
 }
@@ -6,9 +6,13 @@ use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
-    authentication::compute_password_hash,
-    domain::{InvitationToken, InvitationTokenError, ValidationCode, ValidationCodeError},
-    routes::error_chain_fmt,
+    api_error::{ApiError, Problem},
+    authentication::{compute_password_hash, PasswordPeppers},
+    domain::{
+        InvitationToken, InvitationTokenError, Username, ValidationCode, ValidationCodeError,
+    },
+    routes::{error_chain_fmt, PendingInvitation},
+    token_generator::hash_token,
     util::see_other,
 };
 
@@ -47,27 +51,43 @@ impl ResponseError for CollaboratorRegistrationError {
             CollaboratorRegistrationError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for CollaboratorRegistrationError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            CollaboratorRegistrationError::TokenValidationError(_) => "invalid-invitation-token",
+            CollaboratorRegistrationError::CodeValidationError(_) => "invalid-validation-code",
+            CollaboratorRegistrationError::MissingRegistrationError => "unknown-invitation",
+            CollaboratorRegistrationError::UnexpectedError(_) => "internal-error",
+        }
+    }
 }
 
+/// Removes the invitation, returning it if it was pending.
 #[tracing::instrument(name = "Remove invitation token", skip(invitation_token))]
 async fn remove_invitation_token(
     transaction: &mut Transaction<'_, Postgres>,
     invitation_token: InvitationToken,
     validation_code: ValidationCode,
-) -> Result<bool, sqlx::Error> {
-    sqlx::query!(
+) -> Result<Option<PendingInvitation>, sqlx::Error> {
+    sqlx::query_as!(
+        PendingInvitation,
         r#"
         DELETE FROM invitation_tokens
-        WHERE invitation_token = $1 AND
+        WHERE invitation_token_hash = $1 AND
             validation_code = $2
-        RETURNING 1 as contained
+        RETURNING email
         "#,
-        invitation_token.as_ref(),
+        hash_token(invitation_token.as_ref()),
         validation_code.as_ref(),
     )
     .fetch_optional(&mut **transaction)
     .await
-    .map(|r| r.is_some())
 }
 
 #[tracing::instrument(
@@ -77,18 +97,20 @@ async fn remove_invitation_token(
 )]
 async fn insert_collaborator(
     transaction: &mut Transaction<'_, Postgres>,
-    username: &str,
+    username: &Username,
+    email: Option<&str>,
     password_hash: Secret<String>,
 ) -> Result<bool, sqlx::Error> {
     let user_id = Uuid::new_v4();
 
     let result = sqlx::query!(
         r#"
-        INSERT INTO users (user_id, username, password_hash, role)
-        VALUES ($1, $2, $3, 'collaborator')
+        INSERT INTO users (user_id, username, email, password_hash, role)
+        VALUES ($1, $2, $3, $4, 'collaborator')
         "#,
         user_id,
-        username,
+        username.as_ref(),
+        email,
         password_hash.expose_secret()
     )
     .execute(&mut **transaction)
@@ -107,10 +129,11 @@ async fn insert_collaborator(
     }
 }
 
-#[tracing::instrument(name = "Register collaborator", skip(form, pool))]
+#[tracing::instrument(name = "Register collaborator", skip(form, pool, peppers))]
 pub async fn register_collaborator(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
+    peppers: web::Data<PasswordPeppers>,
 ) -> Result<HttpResponse, CollaboratorRegistrationError> {
     let form_data = form.into_inner();
 
@@ -120,36 +143,44 @@ pub async fn register_collaborator(
     let validation_code = ValidationCode::parse(form_data.validation_code)
         .map_err(CollaboratorRegistrationError::CodeValidationError)?;
 
+    let username = match Username::parse(form_data.username) {
+        Ok(username) => username,
+        Err(e) => {
+            FlashMessage::error(e.to_string()).send();
+
+            return Ok(see_other("/collaborator"));
+        }
+    };
+
     if !(8..=64).contains(&form_data.password.expose_secret().len()) {
         FlashMessage::error("New password must contain at least 8 and up to 64 characters.").send();
 
         return Ok(see_other("/collaborator"));
     }
 
-    let password_hash =
-        compute_password_hash(form_data.password).context("Failed to compute password hash")?;
+    let password_hash = compute_password_hash(form_data.password, &peppers)
+        .context("Failed to compute password hash")?;
 
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to aquire a Postgres connection from the pool")?;
 
-    if !remove_invitation_token(&mut transaction, invitation_token, validation_code)
+    let invitation = remove_invitation_token(&mut transaction, invitation_token, validation_code)
         .await
         .context("Failed to remove invitation token")?
-    {
-        return Err(CollaboratorRegistrationError::MissingRegistrationError);
-    }
+        .ok_or(CollaboratorRegistrationError::MissingRegistrationError)?;
 
-    if !insert_collaborator(&mut transaction, &form_data.username, password_hash)
-        .await
-        .context("Failed to insert new collaborator")?
+    if !insert_collaborator(
+        &mut transaction,
+        &username,
+        invitation.email.as_deref(),
+        password_hash,
+    )
+    .await
+    .context("Failed to insert new collaborator")?
     {
-        FlashMessage::error(format!(
-            "Username \"{}\" is already in use.",
-            form_data.username
-        ))
-        .send();
+        FlashMessage::error(format!("Username \"{}\" is already in use.", username)).send();
 
         return Ok(see_other("/collaborator"));
     }
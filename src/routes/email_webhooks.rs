@@ -0,0 +1,95 @@
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, ResponseError};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use sha2::Sha256;
+use sqlx::PgPool;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    configuration::EmailWebhookSettings,
+    deliverability::apply_provider_event,
+    domain::{ProviderEvent, ProviderEventError},
+    routes::error_chain_fmt,
+};
+
+/// Header carrying the base64 HMAC-SHA256 of the body.
+pub const SIGNATURE_HEADER: &str = "X-Postmark-Signature";
+
+#[derive(thiserror::Error)]
+pub enum EmailWebhookError {
+    #[error("Missing or invalid signature")]
+    InvalidSignature,
+    #[error(transparent)]
+    InvalidEvent(#[from] ProviderEventError),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for EmailWebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for EmailWebhookError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            EmailWebhookError::InvalidSignature => StatusCode::UNAUTHORIZED,
+            EmailWebhookError::InvalidEvent(_) => StatusCode::BAD_REQUEST,
+            EmailWebhookError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for EmailWebhookError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            EmailWebhookError::InvalidSignature => "invalid-signature",
+            EmailWebhookError::InvalidEvent(_) => "invalid-event",
+            EmailWebhookError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+fn verify_signature(
+    request: &HttpRequest,
+    body: &[u8],
+    settings: &EmailWebhookSettings,
+) -> Result<(), EmailWebhookError> {
+    let signature = request
+        .headers()
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| base64::engine::general_purpose::STANDARD.decode(value).ok())
+        .ok_or(EmailWebhookError::InvalidSignature)?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(settings.secret.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+
+    mac.verify_slice(&signature)
+        .map_err(|_| EmailWebhookError::InvalidSignature)
+}
+
+/// Receives the bounce and spam complaint webhooks of Postmark. Other
+/// records are acknowledged and ignored.
+#[tracing::instrument(name = "Receive email provider webhook", skip_all)]
+pub async fn receive_email_webhook(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    settings: web::Data<EmailWebhookSettings>,
+) -> Result<HttpResponse, EmailWebhookError> {
+    verify_signature(&request, &body, &settings)?;
+
+    if let Some(event) = ProviderEvent::parse_postmark(&body)? {
+        apply_provider_event(&pool, &event, &settings).await?;
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,18 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+use crate::{feed::get_feed, startup::ApplicationBaseUrl, util::e500};
+
+/// Atom feed of the latest issues, for readers who'd rather not subscribe
+/// by email.
+#[tracing::instrument(name = "Get issues feed", skip(pool, base_url))]
+pub async fn issues_feed(
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let feed = get_feed(&pool).await.map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/atom+xml; charset=utf-8")
+        .body(feed.to_atom(&base_url.0)))
+}
@@ -1,5 +1,76 @@
-use actix_web::{HttpRequest, HttpResponse};
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    web, HttpRequest, HttpResponse, ResponseError,
+};
+use anyhow::Context;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    base_url_check::BaseUrlCheck,
+    config_schema::describe_configuration,
+    routes::error_chain_fmt,
+    user_role::UserRole,
+};
 
 pub async fn health_check(_req: HttpRequest) -> HttpResponse {
     HttpResponse::Ok().finish()
 }
+
+/// Requested through the base url by [`BaseUrlCheck`], which expects the
+/// nonce of this instance back.
+pub async fn base_url_probe(base_url_check: web::Data<BaseUrlCheck>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .body(base_url_check.nonce().to_string())
+}
+
+#[derive(thiserror::Error)]
+pub enum ConfigReportError {
+    #[error("Restricted operation")]
+    NonAdminError,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ConfigReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ConfigReportError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ConfigReportError::NonAdminError => StatusCode::METHOD_NOT_ALLOWED,
+            ConfigReportError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for ConfigReportError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            ConfigReportError::NonAdminError => "restricted-operation",
+            ConfigReportError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+/// The configuration layers as found on disk and in the environment, with
+/// secrets redacted. Same as `newsletter config schema --json`.
+#[tracing::instrument(name = "Report configuration", skip_all)]
+pub async fn config_report(
+    role: web::ReqData<UserRole>,
+) -> Result<HttpResponse, ConfigReportError> {
+    if *role != UserRole::Admin {
+        return Err(ConfigReportError::NonAdminError);
+    }
+
+    let entries = describe_configuration().context("Failed to read the configuration")?;
+
+    Ok(HttpResponse::Ok().json(entries))
+}
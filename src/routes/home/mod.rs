@@ -1,7 +1,57 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web::{
+    http::header::{ContentType, VARY},
+    web, HttpRequest, HttpResponse,
+};
+use anyhow::Context;
 
-pub async fn home() -> HttpResponse {
-    HttpResponse::Ok()
+use crate::{
+    configuration::SignupPageSettings,
+    template::{render_home_page, render_signup_page},
+    util::{accepted_locales, e500},
+};
+
+#[derive(serde::Deserialize)]
+pub struct HomeParameters {
+    /// Set by referral links.
+    referral_code: Option<String>,
+}
+
+pub async fn home(
+    request: HttpRequest,
+    parameters: web::Query<HomeParameters>,
+    signup: web::Data<SignupPageSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let body = render_home_page(
+        &signup.fields,
+        &accepted_locales(&request),
+        parameters.referral_code.as_deref(),
+    )
+    .context("Failed to render the home page")
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header((VARY, "Accept-Language"))
+        .body(body))
+}
+
+/// Standalone subscription form, branded through the `signup_page` settings,
+/// to link to or embed in other sites.
+pub async fn signup_form(
+    request: HttpRequest,
+    parameters: web::Query<HomeParameters>,
+    signup: web::Data<SignupPageSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let body = render_signup_page(
+        &signup,
+        &accepted_locales(&request),
+        parameters.referral_code.as_deref(),
+    )
+    .context("Failed to render the signup page")
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
-        .body(include_str!("home.html"))
+        .insert_header((VARY, "Accept-Language"))
+        .body(body))
 }
@@ -0,0 +1,199 @@
+use actix_web::{
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE, LOCATION, X_CONTENT_TYPE_OPTIONS},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse, ResponseError,
+};
+use uuid::Uuid;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    blob_store::{validate_upload, BlobKey, BlobStore, BlobStoreError, BlobUrlSigner, UploadKind},
+    configuration::BlobStoreSettings,
+    routes::error_chain_fmt,
+    startup::ApplicationBaseUrl,
+    user_role::UserRole,
+};
+
+#[derive(thiserror::Error)]
+pub enum ImageError {
+    #[error("Restricted operation")]
+    NonAdminError,
+    #[error("Only PNG, JPEG, GIF and WebP images can be uploaded")]
+    UnsupportedType,
+    #[error(transparent)]
+    InvalidImage(BlobStoreError),
+    #[error("Image not found")]
+    NotFound,
+    #[error("Invalid or expired link")]
+    InvalidSignature,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl From<BlobStoreError> for ImageError {
+    fn from(e: BlobStoreError) -> Self {
+        match e {
+            BlobStoreError::NotFound => ImageError::NotFound,
+            BlobStoreError::UnexpectedError(e) => ImageError::UnexpectedError(e),
+            e => ImageError::InvalidImage(e),
+        }
+    }
+}
+
+impl ResponseError for ImageError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ImageError::NonAdminError => StatusCode::METHOD_NOT_ALLOWED,
+            ImageError::UnsupportedType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ImageError::InvalidImage(BlobStoreError::TooLarge(_)) => StatusCode::PAYLOAD_TOO_LARGE,
+            ImageError::InvalidImage(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ImageError::NotFound => StatusCode::NOT_FOUND,
+            ImageError::InvalidSignature => StatusCode::FORBIDDEN,
+            ImageError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for ImageError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            ImageError::NonAdminError => "restricted-operation",
+            ImageError::UnsupportedType => "unsupported-image-type",
+            ImageError::InvalidImage(BlobStoreError::TooLarge(_)) => "image-too-large",
+            ImageError::InvalidImage(_) => "invalid-image",
+            ImageError::NotFound => "image-not-found",
+            ImageError::InvalidSignature => "invalid-signature",
+            ImageError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+/// Images are kept under `images/`, named after their id and kind.
+fn image_key(name: &str) -> Result<BlobKey, ImageError> {
+    if name.contains('/') {
+        return Err(ImageError::NotFound);
+    }
+
+    BlobKey::parse(format!("images/{}", name)).map_err(|_| ImageError::NotFound)
+}
+
+#[derive(serde::Serialize)]
+struct UploadedImage {
+    name: String,
+    url: String,
+    size: u64,
+}
+
+/// Stores the image sent as the request body, to be embedded in issues
+/// through the returned url.
+#[tracing::instrument(name = "Upload image", skip(req, body, blob_store, settings, base_url))]
+pub async fn upload_image(
+    req: HttpRequest,
+    body: web::Payload,
+    role: web::ReqData<UserRole>,
+    blob_store: web::Data<dyn BlobStore>,
+    settings: web::Data<BlobStoreSettings>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, ImageError> {
+    if *role != UserRole::Admin {
+        return Err(ImageError::NonAdminError);
+    }
+
+    let kind = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(UploadKind::from_content_type)
+        .ok_or(ImageError::UnsupportedType)?;
+
+    // Refused before reading anything when the client tells the size.
+    let declared_size = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared_size.is_some_and(|size| size > settings.max_upload_bytes) {
+        return Err(BlobStoreError::TooLarge(settings.max_upload_bytes).into());
+    }
+
+    let name = format!("{}.{}", Uuid::new_v4(), kind.extension());
+    let body = validate_upload(body, kind, settings.max_upload_bytes);
+    let size = blob_store
+        .put(&image_key(&name)?, kind.content_type(), body)
+        .await?;
+
+    Ok(HttpResponse::Created().json(UploadedImage {
+        url: format!("{}/images/{}", base_url.0, name),
+        name,
+        size,
+    }))
+}
+
+#[tracing::instrument(name = "Delete image", skip(blob_store))]
+pub async fn delete_image(
+    name: web::Path<String>,
+    role: web::ReqData<UserRole>,
+    blob_store: web::Data<dyn BlobStore>,
+) -> Result<HttpResponse, ImageError> {
+    if *role != UserRole::Admin {
+        return Err(ImageError::NonAdminError);
+    }
+
+    blob_store.delete(&image_key(&name)?).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Stable url of an image, redirecting to a short-lived link to download it
+/// from the store.
+#[tracing::instrument(name = "Get image", skip(blob_store, settings))]
+pub async fn get_image(
+    name: web::Path<String>,
+    blob_store: web::Data<dyn BlobStore>,
+    settings: web::Data<BlobStoreSettings>,
+) -> Result<HttpResponse, ImageError> {
+    let url = blob_store.presigned_url(&image_key(&name)?, settings.url_expiration())?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((LOCATION, url))
+        .finish())
+}
+
+#[derive(serde::Deserialize)]
+pub struct SignedBlobQuery {
+    expires: i64,
+    signature: String,
+}
+
+/// Downloads a blob of the local store through a link it signed.
+#[tracing::instrument(name = "Download blob", skip(query, signer, blob_store))]
+pub async fn download_blob(
+    key: web::Path<String>,
+    query: web::Query<SignedBlobQuery>,
+    signer: web::Data<BlobUrlSigner>,
+    blob_store: web::Data<dyn BlobStore>,
+) -> Result<HttpResponse, ImageError> {
+    let key = BlobKey::parse(key.into_inner()).map_err(|_| ImageError::NotFound)?;
+    if !signer.verify(&key, query.expires, &query.signature) {
+        return Err(ImageError::InvalidSignature);
+    }
+
+    let blob = blob_store.get(&key).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(blob.content_type)
+        .insert_header((X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .streaming(blob.body))
+}
@@ -1,48 +1,30 @@
 use actix_web::{cookie::Cookie, http::header::ContentType, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
-use std::fmt::Write;
+use anyhow::Context;
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
-    let mut error_html = String::new();
-    for m in flash_messages.iter() {
-        writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
+use crate::{
+    session_state::TypedSession,
+    template::{render_login_page, PageLayout},
+    util::e500,
+};
+
+pub async fn login_form(
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.csrf_token().map_err(e500)?;
+    let layout = PageLayout::new(None, &flash_messages);
+    let page = render_login_page(&layout, &csrf_token)
+        .context("Failed to render the login page")
+        .map_err(e500)?;
 
     let mut response = HttpResponse::Ok()
         .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta http-equiv="content-type" content="text/html; charset=utf-8">
-    <title>Login</title>
-</head>
-<body>
-    {error_html}
-    <form action="/login" method="post">
-        <label>Username
-            <input
-                type="text"
-                placeholder="Enter Username"
-                name="username"
-            >
-        </label>
-        <label>Password
-            <input
-                type="password"
-                placeholder="Enter Password"
-                name="password"
-            >
-        </label>
-        <button type="submit">Login</button>
-    </form>
-</body>
-</html>"#,
-        ));
+        .body(page);
 
     response
         .add_removal_cookie(&Cookie::new("_flash", ""))
         .unwrap();
 
-    response
+    Ok(response)
 }
@@ -1,18 +1,26 @@
 use actix_web::{
     error::InternalError,
-    http::{header::LOCATION, StatusCode},
-    web, HttpResponse, ResponseError,
+    http::{
+        header::{LOCATION, USER_AGENT},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse, ResponseError,
 };
 use actix_web_flash_messages::FlashMessage;
 use secrecy::Secret;
 use sqlx::PgPool;
-use uuid::Uuid;
 
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials},
+    authentication::{
+        get_password_changed_at, validate_credentials, AuthError, Credentials, FailedLogin,
+        LoginAlerts, PasswordPeppers,
+    },
+    configuration::{AdminBasePath, PasswordPolicySettings},
+    domain::Username,
     routes::error_chain_fmt,
     session_state::TypedSession,
-    user_role::UserRole,
+    source_allow_list::SourceAllowList,
+    user_role::get_user_role,
 };
 
 #[derive(serde::Deserialize)]
@@ -55,38 +63,39 @@ fn login_redirect(e: LoginError) -> InternalError<LoginError> {
 }
 
 #[tracing::instrument(
-    skip(pool),
-    fields(user_id=tracing::field::Empty)
-)]
-async fn get_user_role(user_id: &Uuid, pool: &PgPool) -> Result<UserRole, sqlx::Error> {
-    sqlx::query!(
-        r#"
-        SELECT role as "role!: UserRole"
-        FROM users
-        WHERE user_id = $1
-        "#,
-        user_id
-    )
-    .fetch_one(pool)
-    .await
-    .map(|record| record.role)
-}
-
-#[tracing::instrument(
-    skip(form, pool, session),
+    skip(
+        request,
+        form,
+        pool,
+        peppers,
+        session,
+        password_policy,
+        admin_base_path,
+        login_alerts,
+        source_allow_list
+    ),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn login(
+    request: HttpRequest,
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
+    peppers: web::Data<PasswordPeppers>,
     session: TypedSession,
+    password_policy: web::Data<PasswordPolicySettings>,
+    admin_base_path: web::Data<AdminBasePath>,
+    login_alerts: web::Data<LoginAlerts>,
+    source_allow_list: web::Data<SourceAllowList>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
+    let username = Username::fold(&form.0.username);
+    tracing::Span::current().record("username", tracing::field::display(&username));
     let credentials = Credentials {
-        username: form.0.username,
+        username: username.clone(),
         password: form.0.password,
     };
 
-    match validate_credentials(credentials, &pool).await {
+    match validate_credentials(credentials, &pool, &peppers).await {
         Ok(user_id) => {
             let user_role = get_user_role(&user_id, &pool)
                 .await
@@ -101,14 +110,45 @@ pub async fn login(
             session
                 .insert_user_role(user_role)
                 .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            session
+                .register(user_id)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+
+            let password_changed_at = get_password_changed_at(user_id, &pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            if password_policy.is_expired(password_changed_at) {
+                session
+                    .mark_password_as_expired()
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+
+                FlashMessage::error("Your password has expired. You must change it.").send();
+
+                return Ok(HttpResponse::SeeOther()
+                    .insert_header((LOCATION, admin_base_path.join("/password")))
+                    .finish());
+            }
 
             Ok(HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/admin/dashboard"))
+                .insert_header((LOCATION, admin_base_path.join("/dashboard")))
                 .finish())
         }
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::InvalidCredentials(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    let failed_login = FailedLogin::new(
+                        &username,
+                        source_allow_list.client_ip_of(&request),
+                        request
+                            .headers()
+                            .get(USER_AGENT)
+                            .and_then(|value| value.to_str().ok()),
+                    );
+                    login_alerts.report(&pool, failed_login).await;
+
+                    LoginError::InvalidCredentials(e.into())
+                }
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
 
@@ -1,20 +1,38 @@
 mod admin;
+mod api;
 mod collaborator;
+mod email_webhooks;
+mod feed;
 mod health_check;
 mod home;
+mod images;
 mod login;
 mod newsletters;
+mod preferences;
+mod shared;
+mod static_files;
+mod stats;
 mod subscriptions;
 mod subscriptions_confirm;
+mod tracking;
 
 pub use admin::*;
+pub use api::*;
 pub use collaborator::*;
+pub use email_webhooks::*;
+pub use feed::*;
 pub use health_check::*;
 pub use home::*;
+pub use images::*;
 pub use login::*;
 pub use newsletters::*;
+pub use preferences::*;
+pub use shared::*;
+pub use static_files::*;
+pub use stats::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use tracking::*;
 
 fn error_chain_fmt(
     e: &impl std::error::Error,
@@ -1,18 +1,30 @@
 use actix_web::{
     http::{
-        header::{self, HeaderMap, HeaderValue},
+        header::{self, ContentType, HeaderValue},
         StatusCode,
     },
     web, HttpRequest, HttpResponse, ResponseError,
 };
 use anyhow::Context;
-use base64::Engine;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
 
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials},
-    domain::SubscriberEmail,
-    email_client::EmailClient,
+    admin_action::{insert_pending_action, AdminAction},
+    api_error::{ApiError, Problem},
+    authentication::{basic_authentication, validate_credentials, AuthError, PasswordPeppers},
+    base_url_check::{BaseUrlCheck, BaseUrlStatus},
+    css_inliner::CssInliner,
+    delivery_queue::{
+        assign_subject_variants, enqueue_delivery_tasks, insert_issue_attachments,
+        insert_newsletter_issue, IssueSender,
+    },
+    domain::{DisplayName, Email, TagName, TagNameError},
+    email_client::{Attachment, EmailClient},
+    link_validator::LinkValidator,
+    newsletter_list::{list_exists, DEFAULT_LIST_ID},
+    subscriber_events::{record_list_event, ListEventKind},
+    template::html_to_text,
 };
 
 use super::error_chain_fmt;
@@ -21,6 +33,18 @@ use super::error_chain_fmt;
 pub enum PublishError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("The issue contains flagged links")]
+    FlaggedLinks(Vec<String>),
+    #[error("List not found")]
+    UnknownList,
+    #[error("Links in emails would not reach this application: {0}")]
+    UnreachableBaseUrl(String),
+    #[error(transparent)]
+    InvalidTag(TagNameError),
+    #[error("The attachments exceed {0} bytes")]
+    AttachmentsTooLarge(u64),
+    #[error("Invalid sender: {0}")]
+    InvalidSender(String),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -32,142 +56,365 @@ impl std::fmt::Debug for PublishError {
 }
 
 impl ResponseError for PublishError {
-    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+    fn status_code(&self) -> StatusCode {
         match self {
-            PublishError::UnexpectedError(_) => {
-                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
-            }
-            PublishError::AuthError(_) => {
-                let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
-                let header_value = HeaderValue::from_str(r#"Basic realm="publish""#).unwrap();
+            PublishError::AuthError(_) => StatusCode::UNAUTHORIZED,
+            PublishError::FlaggedLinks(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            PublishError::UnknownList => StatusCode::NOT_FOUND,
+            PublishError::UnreachableBaseUrl(_) => StatusCode::SERVICE_UNAVAILABLE,
+            PublishError::InvalidTag(_) => StatusCode::BAD_REQUEST,
+            PublishError::AttachmentsTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            PublishError::InvalidSender(_) => StatusCode::BAD_REQUEST,
+            PublishError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = ApiError(self).error_response();
 
-                response
-                    .headers_mut()
-                    .insert(header::WWW_AUTHENTICATE, header_value);
+        if let PublishError::AuthError(_) = self {
+            let header_value = HeaderValue::from_str(r#"Basic realm="publish""#).unwrap();
+
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, header_value);
+        }
+
+        response
+    }
+}
 
-                response
+impl Problem for PublishError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            PublishError::AuthError(_) => "authentication-failed",
+            PublishError::FlaggedLinks(_) => "flagged-links",
+            PublishError::UnknownList => "list-not-found",
+            PublishError::UnreachableBaseUrl(_) => "unreachable-base-url",
+            PublishError::InvalidTag(_) => "invalid-tag",
+            PublishError::AttachmentsTooLarge(_) => "attachments-too-large",
+            PublishError::InvalidSender(_) => "invalid-sender",
+            PublishError::UnexpectedError(_) => "internal-error",
+        }
+    }
+
+    fn extensions(&self) -> Option<serde_json::Map<String, serde_json::Value>> {
+        match self {
+            PublishError::FlaggedLinks(links) => {
+                let mut extensions = serde_json::Map::new();
+                extensions.insert("flagged_links".into(), links.clone().into());
+
+                Some(extensions)
             }
+            _ => None,
         }
     }
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct Content {
-    html: String,
-    text: String,
+    pub html: String,
+    /// Generated from the HTML when missing or blank.
+    pub text: Option<String>,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct BodyData {
-    title: String,
-    content: Content,
+    /// The list whose subscribers get the issue, the default one if missing.
+    pub list_id: Option<Uuid>,
+    /// Restricts the delivery to the subscribers with this tag.
+    pub tag: Option<String>,
+    pub title: String,
+    /// Tests a second subject: half of the recipients, picked at random, get
+    /// it instead of the title.
+    pub alternative_title: Option<String>,
+    pub content: Content,
+    /// Files sent along with every email, their content encoded in base64.
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// Shown as who the emails are from, before the sender address.
+    pub sender_name: Option<String>,
+    /// Sends the emails from this address instead of the default one. It must
+    /// be one of the verified senders.
+    pub sender_email: Option<String>,
+    /// Where replies go, one of the verified senders.
+    pub reply_to: Option<String>,
+    /// Asks an admin to approve the issue instead of refusing it when some of
+    /// its links are flagged.
+    #[serde(default)]
+    pub override_flagged_links: bool,
 }
 
-struct ConfirmedSubscriber {
-    email: SubscriberEmail,
-}
+/// Stores the issue and schedules its delivery to every confirmed subscriber
+/// of the list, or only to those with the given tag. Emails are sent later on by the issue delivery worker.
+#[tracing::instrument(name = "Schedule newsletter issue delivery", skip_all)]
+#[allow(clippy::too_many_arguments)]
+pub async fn schedule_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    tag: Option<&str>,
+    title: &str,
+    alternative_title: Option<&str>,
+    html: &str,
+    text: &str,
+    attachments: &[Attachment],
+    author_id: Uuid,
+    sender: &IssueSender,
+) -> Result<(), anyhow::Error> {
+    let newsletter_issue_id = insert_newsletter_issue(
+        transaction,
+        list_id,
+        tag,
+        title,
+        text,
+        html,
+        alternative_title,
+        author_id,
+        sender,
+    )
+    .await
+    .context("Failed to store newsletter issue details")?;
+
+    insert_issue_attachments(transaction, newsletter_issue_id, attachments)
+        .await
+        .context("Failed to store newsletter issue attachments")?;
 
-fn basic_authentication(headers: &HeaderMap) -> Result<Credentials, anyhow::Error> {
-    let header_value = headers
-        .get("Authorization")
-        .context("The 'Authorization' header is missing")?
-        .to_str()
-        .context("The 'Authorization' header was not a valid string")?;
-    let base64_encoded_segment = header_value
-        .strip_prefix("Basic ")
-        .context("The authorization scheme was not 'Basic'")?;
-    let decoded_bytes = base64::engine::general_purpose::STANDARD
-        .decode(base64_encoded_segment)
-        .context("Failed to base64-decode 'Basic' credentials")?;
-    let decoded_credentials = String::from_utf8(decoded_bytes)
-        .context("The decoded credential string is not valid UTF8")?;
-
-    let mut credentials = decoded_credentials.splitn(2, ':');
-    let username = credentials
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("A username must be providaded in 'Basic' auth"))?
-        .to_string();
-    let password = credentials
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("A password must be providaded in 'Basic' auth"))?
-        .to_string()
-        .into();
-
-    Ok(Credentials { username, password })
+    enqueue_delivery_tasks(transaction, newsletter_issue_id)
+        .await
+        .context("Failed to enqueue delivery tasks")?;
+
+    assign_subject_variants(transaction, newsletter_issue_id)
+        .await
+        .context("Failed to split the recipients between the subjects")?;
+
+    record_list_event(
+        transaction,
+        ListEventKind::IssuePublished,
+        serde_json::json!({
+            "newsletter_issue_id": newsletter_issue_id,
+            "list_id": list_id,
+            "tag": tag,
+            "title": title,
+        }),
+    )
+    .await
+    .context("Failed to record the publication of the issue")?;
+
+    Ok(())
 }
 
-#[tracing::instrument(name = "Get confirmed subscribers", skip(pool))]
-async fn get_confirmed_subscribers(
+async fn authenticate_publisher(
+    request: &HttpRequest,
     pool: &PgPool,
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-    let rows = sqlx::query!(
-        r#"
-        SELECT email
-        FROM subscriptions
-        WHERE status = 'confirmed'
-        "#
-    )
-    .fetch_all(pool)
-    .await?;
+    peppers: &PasswordPeppers,
+) -> Result<Uuid, PublishError> {
+    let credentials = basic_authentication(request.headers()).map_err(PublishError::AuthError)?;
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
+    let user_id = validate_credentials(credentials, pool, peppers)
+        .await
+        .map_err(|e| match e {
+            AuthError::InvalidCredentials(_) => PublishError::AuthError(e.into()),
+            AuthError::UnexpectedError(_) => PublishError::UnexpectedError(e.into()),
+        })?;
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
 
-    let confirmed_subscribers = rows
-        .into_iter()
-        .map(|r| match SubscriberEmail::parse(r.email) {
-            Ok(email) => Ok(ConfirmedSubscriber { email }),
-            Err(error) => Err(anyhow::anyhow!(error)),
-        })
-        .collect();
+    Ok(user_id)
+}
+
+/// Shows the HTML of an issue exactly as it would be delivered.
+#[tracing::instrument(
+    name = "Preview newsletter issue",
+    skip(body, pool, peppers, css_inliner, request),
+    fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
+)]
+pub async fn preview_newsletter(
+    body: web::Json<BodyData>,
+    pool: web::Data<PgPool>,
+    peppers: web::Data<PasswordPeppers>,
+    css_inliner: web::Data<CssInliner>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PublishError> {
+    authenticate_publisher(&request, &pool, &peppers).await?;
 
-    Ok(confirmed_subscribers)
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(css_inliner.inline(&body.content.html)))
 }
 
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(
     name = "Publish newsletter issue",
-    skip(body, pool, email_client, request),
+    skip(body, pool, peppers, link_validator, base_url_check, css_inliner, email_client, request),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn publish_newsletter(
     body: web::Json<BodyData>,
     pool: web::Data<PgPool>,
+    peppers: web::Data<PasswordPeppers>,
+    link_validator: web::Data<LinkValidator>,
+    base_url_check: web::Data<BaseUrlCheck>,
+    css_inliner: web::Data<CssInliner>,
     email_client: web::Data<EmailClient>,
     request: HttpRequest,
 ) -> Result<HttpResponse, PublishError> {
-    let credentials = basic_authentication(request.headers()).map_err(PublishError::AuthError)?;
-    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
-    let user_id = validate_credentials(credentials, &pool)
+    let user_id = authenticate_publisher(&request, &pool, &peppers).await?;
+
+    publish_issue(
+        user_id,
+        body.into_inner(),
+        &pool,
+        &link_validator,
+        &base_url_check,
+        &css_inliner,
+        &email_client,
+    )
+    .await
+}
+
+/// Blank fields keep the defaults.
+fn parse_sender(
+    name: Option<String>,
+    email: Option<String>,
+    reply_to: Option<String>,
+    email_client: &EmailClient,
+) -> Result<IssueSender, PublishError> {
+    let verified_address = |address: Option<String>| -> Result<Option<String>, PublishError> {
+        let Some(address) = address
+            .map(|address| address.trim().to_string())
+            .filter(|address| !address.is_empty())
+        else {
+            return Ok(None);
+        };
+        let address =
+            Email::parse(address).map_err(|e| PublishError::InvalidSender(e.to_string()))?;
+        if !email_client.is_verified_sender(&address) {
+            return Err(PublishError::InvalidSender(format!(
+                "{} is not a verified sender",
+                address
+            )));
+        }
+
+        Ok(Some(address.as_ref().to_string()))
+    };
+
+    Ok(IssueSender {
+        name: name
+            .filter(|name| !name.trim().is_empty())
+            .map(DisplayName::parse)
+            .transpose()
+            .map_err(|e| PublishError::InvalidSender(e.to_string()))?
+            .map(|name| name.as_ref().to_string()),
+        email: verified_address(email)?,
+        reply_to: verified_address(reply_to)?,
+    })
+}
+
+/// Validates the issue and schedules its delivery, or asks an admin to
+/// approve it when its flagged links were explicitly overridden.
+pub async fn publish_issue(
+    user_id: Uuid,
+    body: BodyData,
+    pool: &PgPool,
+    link_validator: &LinkValidator,
+    base_url_check: &BaseUrlCheck,
+    css_inliner: &CssInliner,
+    email_client: &EmailClient,
+) -> Result<HttpResponse, PublishError> {
+    let BodyData {
+        list_id,
+        tag,
+        title,
+        alternative_title,
+        content: Content { html, text },
+        attachments,
+        sender_name,
+        sender_email,
+        reply_to,
+        override_flagged_links,
+    } = body;
+    let list_id = list_id.unwrap_or(DEFAULT_LIST_ID);
+    let text = text
+        .filter(|text| !text.trim().is_empty())
+        .unwrap_or_else(|| html_to_text(&html));
+    let tag = tag
+        .map(TagName::parse)
+        .transpose()
+        .map_err(PublishError::InvalidTag)?
+        .map(|tag| tag.as_ref().to_string());
+    let alternative_title = alternative_title.filter(|title| !title.trim().is_empty());
+    let sender = parse_sender(sender_name, sender_email, reply_to, email_client)?;
+    let max_attachments_bytes = email_client.max_attachments_bytes();
+
+    // Checked upfront, every email of the issue would be refused otherwise.
+    if attachments.iter().map(Attachment::size).sum::<u64>() > max_attachments_bytes {
+        return Err(PublishError::AttachmentsTooLarge(max_attachments_bytes));
+    }
+
+    if !list_exists(pool, list_id)
         .await
-        .map_err(|e| match e {
-            AuthError::InvalidCredentials(_) => PublishError::AuthError(e.into()),
-            AuthError::UnexpectedError(_) => PublishError::UnexpectedError(e.into()),
-        })?;
-    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+        .context("Failed to check newsletter list")?
+    {
+        return Err(PublishError::UnknownList);
+    }
 
-    let subscribers = get_confirmed_subscribers(&pool).await?;
-
-    for subscriber in subscribers {
-        match subscriber {
-            Ok(subscriber) => {
-                email_client
-                    .send_email(
-                        subscriber.email.as_ref(),
-                        &body.title,
-                        &body.content.html,
-                        &body.content.text,
-                    )
-                    .await
-                    .with_context(|| {
-                        format!("Failed to send newsletter issue to {}", subscriber.email)
-                    })?;
-            }
-            Err(error) => {
-                tracing::warn!(
-                    error.cause_chain = ?error,
-                    "Skipping confirmed subscriber. \
-                    Their stored contact details are invalid"
-                );
-            }
+    if let BaseUrlStatus::Unreachable(reason) = base_url_check.status().await {
+        return Err(PublishError::UnreachableBaseUrl(reason));
+    }
+
+    // Inlined once per issue, the stored HTML is what every subscriber gets.
+    let html = css_inliner.inline(&html);
+
+    let flagged_links = link_validator
+        .flagged_links(&[&html, &text])
+        .await
+        .context("Failed to validate newsletter issue links")?;
+
+    if !flagged_links.is_empty() {
+        if !override_flagged_links {
+            return Err(PublishError::FlaggedLinks(flagged_links));
         }
+
+        let action = AdminAction::PublishNewsletter {
+            list_id,
+            tag,
+            title,
+            alternative_title,
+            html,
+            text,
+            attachments,
+            sender,
+            flagged_links,
+        };
+        insert_pending_action(pool, user_id, &action)
+            .await
+            .context("Failed to store pending newsletter issue")?;
+
+        return Ok(HttpResponse::Accepted().finish());
     }
 
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    schedule_newsletter_issue(
+        &mut transaction,
+        list_id,
+        tag.as_deref(),
+        &title,
+        alternative_title.as_deref(),
+        &html,
+        &text,
+        &attachments,
+        user_id,
+        &sender,
+    )
+    .await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a newsletter issue")?;
+
     Ok(HttpResponse::Ok().finish())
 }
@@ -0,0 +1,175 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    domain::Token,
+    subscriber_events::{record_events, SubscriberEventKind},
+};
+
+use super::{PreferencesError, LINK_TTL_HOURS};
+
+#[derive(serde::Deserialize)]
+pub struct EmailChangeConfirmationParameters {
+    change_token: String,
+}
+
+struct PendingEmailChange {
+    subscriber_id: Uuid,
+    old_email: String,
+    new_email: String,
+}
+
+#[tracing::instrument(name = "Get pending email change", skip(transaction, change_token))]
+async fn get_pending_email_change(
+    transaction: &mut Transaction<'_, Postgres>,
+    change_token: &Token,
+) -> Result<Option<PendingEmailChange>, sqlx::Error> {
+    sqlx::query_as!(
+        PendingEmailChange,
+        r#"
+        SELECT subscriber_id, old_email, new_email
+        FROM subscriber_email_changes
+        WHERE change_token = $1
+          AND confirmed_at IS NULL
+          AND requested_at > now() - make_interval(hours => $2)
+        FOR UPDATE
+        "#,
+        change_token.as_ref(),
+        LINK_TTL_HOURS,
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+}
+
+/// Moves the subscription to the new address, as long as it still has the
+/// address the change was requested from.
+#[tracing::instrument(name = "Change subscriber email", skip(transaction, change))]
+async fn change_subscriber_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    change: &PendingEmailChange,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET email = $3
+        WHERE id = $1 AND email = $2
+        "#,
+        change.subscriber_id,
+        change.old_email,
+        change.new_email,
+    )
+    .execute(&mut **transaction)
+    .await
+    .map(|r| r.rows_affected() == 1)
+}
+
+/// Marks the change as confirmed, drops the other pending changes and revokes
+/// the preferences links that were sent to the old address.
+#[tracing::instrument(name = "Settle email change", skip(transaction, change_token, change))]
+async fn settle_email_change(
+    transaction: &mut Transaction<'_, Postgres>,
+    change_token: &Token,
+    change: &PendingEmailChange,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriber_email_changes
+        SET confirmed_at = now()
+        WHERE change_token = $1
+        "#,
+        change_token.as_ref(),
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM subscriber_email_changes
+        WHERE subscriber_id = $1 AND confirmed_at IS NULL
+        "#,
+        change.subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM preferences_tokens
+        WHERE subscriber_id = $1
+        "#,
+        change.subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    // Issues still being delivered go to the new address from now on.
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET subscriber_email = $2
+        WHERE subscriber_email = $1
+        "#,
+        change.old_email,
+        change.new_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Confirm subscriber email change", skip(parameters, pool))]
+pub async fn confirm_email_change(
+    parameters: web::Query<EmailChangeConfirmationParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, PreferencesError> {
+    let change_token =
+        Token::parse(parameters.0.change_token).map_err(PreferencesError::TokenValidationError)?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    let change = get_pending_email_change(&mut transaction, &change_token)
+        .await
+        .context("Failed to retrieve pending email change")?
+        .ok_or(PreferencesError::UnknownTokenError)?;
+
+    match change_subscriber_email(&mut transaction, &change).await {
+        Ok(true) => {}
+        Ok(false) => return Err(PreferencesError::UnknownTokenError),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            return Err(PreferencesError::EmailAlreadySubscribedError)
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context("Failed to change email")
+                .into())
+        }
+    }
+
+    settle_email_change(&mut transaction, &change_token, &change)
+        .await
+        .context("Failed to settle email change")?;
+    record_events(
+        &mut transaction,
+        &[change.subscriber_id],
+        SubscriberEventKind::EmailChanged,
+        serde_json::json!({
+            "old_email": change.old_email,
+            "new_email": change.new_email,
+        }),
+    )
+    .await
+    .context("Failed to record the email change of a subscriber")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to change subscriber email")?;
+
+    Ok(HttpResponse::Ok().finish())
+}
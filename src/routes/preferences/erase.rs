@@ -0,0 +1,58 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{domain::Token, erasure::erase_subscriber};
+
+use super::{get_preferences_subscriber, PreferencesError};
+
+#[derive(serde::Deserialize)]
+pub struct ErasureParameters {
+    token: String,
+}
+
+/// Lets subscribers have their personal data erased from a link of the
+/// preferences center.
+#[tracing::instrument(name = "Erase subscription", skip(parameters, pool))]
+pub async fn erase_subscription(
+    parameters: web::Query<ErasureParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, PreferencesError> {
+    let preferences_token =
+        Token::parse(parameters.0.token).map_err(PreferencesError::TokenValidationError)?;
+
+    let subscriber = get_preferences_subscriber(&pool, &preferences_token)
+        .await
+        .context("Failed to retrieve the subscriber of a preferences token")?
+        .ok_or(PreferencesError::UnknownTokenError)?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+
+    if !erase_subscriber(&mut transaction, subscriber.id)
+        .await
+        .context("Failed to erase subscriber")?
+    {
+        return Err(PreferencesError::UnknownTokenError);
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to erase subscriber")?;
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Data erased</title>
+</head>
+<body>
+    <p>Your personal data was erased, you won't receive any more issues.</p>
+</body>
+</html>"#,
+    ))
+}
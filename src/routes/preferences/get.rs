@@ -0,0 +1,166 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    domain::{DeliveryFrequency, Token},
+    newsletter_list::DEFAULT_LIST_ID,
+    referrals::{count_referrals, ReferralLinks},
+    subscriber_events::get_subscriber_history,
+};
+
+use super::{get_preferences_subscriber, PreferencesError};
+
+// Enough to cover the lifetime of most subscriptions.
+const HISTORY_LENGTH: i64 = 50;
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesParameters {
+    preferences_token: String,
+}
+
+#[tracing::instrument(
+    name = "Show subscriber preferences",
+    skip(parameters, pool, referral_links)
+)]
+pub async fn preferences_form(
+    parameters: web::Query<PreferencesParameters>,
+    pool: web::Data<PgPool>,
+    referral_links: web::Data<ReferralLinks>,
+) -> Result<HttpResponse, PreferencesError> {
+    let preferences_token = Token::parse(parameters.0.preferences_token)
+        .map_err(PreferencesError::TokenValidationError)?;
+
+    let subscriber = get_preferences_subscriber(&pool, &preferences_token)
+        .await
+        .context("Failed to retrieve the subscriber of a preferences token")?
+        .ok_or(PreferencesError::UnknownTokenError)?;
+
+    let history = get_subscriber_history(&pool, subscriber.id, HISTORY_LENGTH)
+        .await
+        .context("Failed to retrieve the history of a subscriber")?;
+    let mut history_html = String::new();
+    for entry in history {
+        writeln!(
+            history_html,
+            "<li>{}: {}</li>",
+            entry.occurred_at.format("%Y-%m-%d %H:%M UTC"),
+            htmlescape::encode_minimal(&entry.description()),
+        )
+        .unwrap();
+    }
+
+    let referral_html = match referral_links.link(&subscriber.referral_code) {
+        Some(link) => {
+            let referrals = count_referrals(&pool, subscriber.id)
+                .await
+                .context("Failed to count the referrals of a subscriber")?;
+            format!(
+                r#"<h2>Refer your friends</h2>
+    <p>Share <a href="{link}">{link}</a> with them. {referrals} subscribed thanks to you so far.</p>"#,
+                link = htmlescape::encode_minimal(&link),
+            )
+        }
+        None => String::new(),
+    };
+    let checked = |frequency| {
+        if subscriber.delivery_frequency == frequency {
+            "checked"
+        } else {
+            ""
+        }
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Preferences</title>
+</head>
+<body>
+    <p>You are subscribed as {email}</p>
+    <form action="/preferences/email" method="post">
+        <input type="hidden" name="preferences_token" value="{preferences_token}">
+        <label>New email
+            <input
+                type="email"
+                placeholder="Enter the new email"
+                name="email"
+            >
+        </label>
+        <br>
+        <button type="submit">Change email</button>
+    </form>
+    <form action="/preferences/frequency" method="post">
+        <input type="hidden" name="preferences_token" value="{preferences_token}">
+        <label>
+            <input type="radio" name="delivery_frequency" value="every_issue" {every_issue}>
+            Every issue, as soon as it's published
+        </label>
+        <br>
+        <label>
+            <input type="radio" name="delivery_frequency" value="weekly_digest" {weekly_digest}>
+            A weekly digest of the issues
+        </label>
+        <br>
+        <button type="submit">Save</button>
+    </form>
+    <p><a href="/subscriptions/erase?token={preferences_token}">Erase my personal data</a></p>
+    {referral_html}
+    <h2>History of your subscription</h2>
+    <ul>
+        {history_html}
+    </ul>
+</body>
+</html>"#,
+            email = htmlescape::encode_minimal(&subscriber.email),
+            preferences_token = preferences_token.as_ref(),
+            every_issue = checked(DeliveryFrequency::EveryIssue),
+            weekly_digest = checked(DeliveryFrequency::WeeklyDigest),
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesLinkParameters {
+    /// The list whose subscription is managed, the default one if missing.
+    list_id: Option<Uuid>,
+}
+
+/// Asks for the email to send a link to the preferences center to, as
+/// linked from the footer of emails.
+pub async fn preferences_link_form(
+    parameters: web::Query<PreferencesLinkParameters>,
+) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Manage your subscription</title>
+</head>
+<body>
+    <p>We will send you a link to manage your subscription.</p>
+    <form action="/preferences/link" method="post">
+        <input type="hidden" name="list_id" value="{list_id}">
+        <label>Email
+            <input
+                type="email"
+                placeholder="Enter your email"
+                name="email"
+            >
+        </label>
+        <br>
+        <button type="submit">Send me a link</button>
+    </form>
+</body>
+</html>"#,
+            list_id = parameters.list_id.unwrap_or(DEFAULT_LIST_ID),
+        ))
+}
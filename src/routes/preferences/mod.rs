@@ -0,0 +1,127 @@
+mod confirm;
+mod erase;
+mod get;
+mod post;
+
+pub use confirm::*;
+pub use erase::*;
+pub use get::*;
+pub use post::*;
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    api_error::{ApiError, Problem},
+    domain::{DeliveryFrequency, EmailError, Token, TokenError},
+    routes::error_chain_fmt,
+};
+
+// Links sent by email stop working after a day.
+const LINK_TTL_HOURS: i32 = 24;
+
+#[derive(thiserror::Error)]
+pub enum PreferencesError {
+    #[error("{0}")]
+    TokenValidationError(TokenError),
+    #[error("{0}")]
+    EmailValidationError(EmailError),
+    #[error("The link is unknown or has expired")]
+    UnknownTokenError,
+    #[error("The email is already subscribed")]
+    EmailAlreadySubscribedError,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PreferencesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PreferencesError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PreferencesError::TokenValidationError(_)
+            | PreferencesError::EmailValidationError(_) => StatusCode::BAD_REQUEST,
+            PreferencesError::UnknownTokenError => StatusCode::UNAUTHORIZED,
+            PreferencesError::EmailAlreadySubscribedError => StatusCode::CONFLICT,
+            PreferencesError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for PreferencesError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            PreferencesError::TokenValidationError(_) => "invalid-preferences-token",
+            PreferencesError::EmailValidationError(_) => "invalid-email",
+            PreferencesError::UnknownTokenError => "unknown-preferences-token",
+            PreferencesError::EmailAlreadySubscribedError => "email-already-subscribed",
+            PreferencesError::UnexpectedError(_) => "internal-error",
+        }
+    }
+}
+
+struct PreferencesSubscriber {
+    id: Uuid,
+    list_id: Uuid,
+    email: String,
+    locale: Option<String>,
+    referral_code: String,
+    delivery_frequency: DeliveryFrequency,
+}
+
+#[tracing::instrument(name = "Get subscriber of preferences token", skip(pool, token))]
+async fn get_preferences_subscriber(
+    pool: &PgPool,
+    token: &Token,
+) -> Result<Option<PreferencesSubscriber>, sqlx::Error> {
+    sqlx::query_as!(
+        PreferencesSubscriber,
+        r#"
+        SELECT
+            subscriptions.id,
+            subscriptions.list_id,
+            subscriptions.email,
+            subscriptions.locale,
+            subscriptions.referral_code,
+            subscriptions.delivery_frequency AS "delivery_frequency: DeliveryFrequency"
+        FROM preferences_tokens
+        JOIN subscriptions ON subscriptions.id = preferences_tokens.subscriber_id
+        WHERE preferences_tokens.preferences_token = $1
+          AND preferences_tokens.created_at > now() - make_interval(hours => $2)
+          AND subscriptions.status = 'confirmed'
+        "#,
+        token.as_ref(),
+        LINK_TTL_HOURS,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Check if email is subscribed", skip(pool))]
+async fn is_email_subscribed(
+    pool: &PgPool,
+    list_id: Uuid,
+    email: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM subscriptions WHERE list_id = $1 AND email = $2
+        ) AS "subscribed!"
+        "#,
+        list_id,
+        email,
+    )
+    .fetch_one(pool)
+    .await
+    .map(|r| r.subscribed)
+}
@@ -0,0 +1,275 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    domain::{DeliveryFrequency, Email, Locale, Token},
+    email_client::EmailClient,
+    email_log::EmailKind,
+    newsletter_list::DEFAULT_LIST_ID,
+    startup::ApplicationBaseUrl,
+    template::{render_email_change_confirmation, render_preferences_link},
+    token_generator::{generate_subscription_token, TokenGenerator},
+    util::see_other,
+};
+
+use super::{get_preferences_subscriber, is_email_subscribed, PreferencesError};
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesLinkFormData {
+    email: String,
+    /// The list whose subscription is managed, the default one if missing.
+    list_id: Option<Uuid>,
+}
+
+struct ConfirmedSubscriber {
+    id: Uuid,
+    locale: Option<String>,
+}
+
+#[tracing::instrument(name = "Get confirmed subscriber by email", skip(pool))]
+async fn get_confirmed_subscriber(
+    pool: &PgPool,
+    list_id: Uuid,
+    email: &Email,
+) -> Result<Option<ConfirmedSubscriber>, sqlx::Error> {
+    sqlx::query_as!(
+        ConfirmedSubscriber,
+        r#"
+        SELECT id, locale
+        FROM subscriptions
+        WHERE list_id = $1 AND email = $2 AND status = 'confirmed'
+        "#,
+        list_id,
+        email.as_ref(),
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Stored locales were parsed before, one failing now only falls back to
+/// the default templates.
+fn stored_locale(locale: Option<&str>) -> Option<Locale> {
+    locale.and_then(|l| Locale::parse(l).ok())
+}
+
+#[tracing::instrument(name = "Store preferences token", skip(pool, preferences_token))]
+pub async fn store_preferences_token(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    preferences_token: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO preferences_tokens (preferences_token, subscriber_id, created_at)
+        VALUES ($1, $2, now())
+        "#,
+        preferences_token,
+        subscriber_id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Emails a link to the preferences center. The response is the same whether
+/// the email is subscribed or not, so that subscribers can't be enumerated.
+#[tracing::instrument(
+    name = "Send preferences link",
+    skip(form, pool, email_client, base_url, token_generator)
+)]
+pub async fn request_preferences_link(
+    form: web::Form<PreferencesLinkFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    token_generator: web::Data<dyn TokenGenerator>,
+) -> Result<HttpResponse, PreferencesError> {
+    let PreferencesLinkFormData { email, list_id } = form.0;
+    let email = Email::parse(email).map_err(PreferencesError::EmailValidationError)?;
+    let list_id = list_id.unwrap_or(DEFAULT_LIST_ID);
+
+    let subscriber = match get_confirmed_subscriber(&pool, list_id, &email)
+        .await
+        .context("Failed to retrieve subscriber by email")?
+    {
+        Some(subscriber) => subscriber,
+        None => return Ok(HttpResponse::Ok().finish()),
+    };
+
+    let preferences_token = generate_subscription_token(token_generator.get_ref());
+    store_preferences_token(&pool, subscriber.id, &preferences_token)
+        .await
+        .context("Failed to store preferences token")?;
+
+    let preferences_link = format!(
+        "{}/preferences?preferences_token={}",
+        base_url.0, preferences_token
+    );
+    let locale = stored_locale(subscriber.locale.as_deref());
+    let template = render_preferences_link(&preferences_link, locale.as_slice())
+        .context("Failed to generate email template for preferences link")?;
+    email_client
+        .send_email(
+            EmailKind::PreferencesLink,
+            &email,
+            "Your preferences",
+            &template.html,
+            &template.text,
+            &[],
+            None,
+        )
+        .await
+        .context("Failed to send preferences link")?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailChangeFormData {
+    preferences_token: String,
+    email: String,
+}
+
+#[tracing::instrument(
+    name = "Store email change request",
+    skip(pool, change_token, old_email, new_email)
+)]
+async fn store_email_change(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    change_token: &str,
+    old_email: &str,
+    new_email: &Email,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_email_changes
+            (change_token, subscriber_id, old_email, new_email, requested_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        change_token,
+        subscriber_id,
+        old_email,
+        new_email.as_ref(),
+        Utc::now(),
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Asks the new address to confirm the change. Until it does, issues keep
+/// being delivered to the current one.
+#[tracing::instrument(
+    name = "Request subscriber email change",
+    skip(form, pool, email_client, base_url, token_generator)
+)]
+pub async fn request_email_change(
+    form: web::Form<EmailChangeFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    token_generator: web::Data<dyn TokenGenerator>,
+) -> Result<HttpResponse, PreferencesError> {
+    let EmailChangeFormData {
+        preferences_token,
+        email,
+    } = form.0;
+    let preferences_token =
+        Token::parse(preferences_token).map_err(PreferencesError::TokenValidationError)?;
+    let new_email = Email::parse(email).map_err(PreferencesError::EmailValidationError)?;
+
+    let subscriber = get_preferences_subscriber(&pool, &preferences_token)
+        .await
+        .context("Failed to retrieve the subscriber of a preferences token")?
+        .ok_or(PreferencesError::UnknownTokenError)?;
+
+    if is_email_subscribed(&pool, subscriber.list_id, new_email.as_ref())
+        .await
+        .context("Failed to check if the new email is subscribed")?
+    {
+        return Err(PreferencesError::EmailAlreadySubscribedError);
+    }
+
+    let change_token = generate_subscription_token(token_generator.get_ref());
+    store_email_change(
+        &pool,
+        subscriber.id,
+        &change_token,
+        &subscriber.email,
+        &new_email,
+    )
+    .await
+    .context("Failed to store email change request")?;
+
+    let confirmation_link = format!(
+        "{}/preferences/email/confirm?change_token={}",
+        base_url.0, change_token
+    );
+    let locale = stored_locale(subscriber.locale.as_deref());
+    let template = render_email_change_confirmation(&confirmation_link, locale.as_slice())
+        .context("Failed to generate email template for email change confirmation")?;
+    email_client
+        .send_email(
+            EmailKind::EmailChangeConfirmation,
+            &new_email,
+            "Confirm your new email",
+            &template.html,
+            &template.text,
+            &[],
+            None,
+        )
+        .await
+        .context("Failed to send email change confirmation")?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeliveryFrequencyFormData {
+    preferences_token: String,
+    delivery_frequency: DeliveryFrequency,
+}
+
+/// Switches between getting every issue and a weekly digest of them. Issues
+/// already queued for the subscriber are still delivered.
+#[tracing::instrument(name = "Change subscriber delivery frequency", skip(form, pool))]
+pub async fn change_delivery_frequency(
+    form: web::Form<DeliveryFrequencyFormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, PreferencesError> {
+    let DeliveryFrequencyFormData {
+        preferences_token,
+        delivery_frequency,
+    } = form.0;
+    let preferences_token =
+        Token::parse(preferences_token).map_err(PreferencesError::TokenValidationError)?;
+
+    let subscriber = get_preferences_subscriber(&pool, &preferences_token)
+        .await
+        .context("Failed to retrieve the subscriber of a preferences token")?
+        .ok_or(PreferencesError::UnknownTokenError)?;
+
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET delivery_frequency = $1
+        WHERE id = $2
+        "#,
+        delivery_frequency as DeliveryFrequency,
+        subscriber.id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to change the delivery frequency")?;
+
+    Ok(see_other(&format!(
+        "/preferences?preferences_token={}",
+        preferences_token.as_ref()
+    )))
+}
@@ -0,0 +1,100 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{delivery_queue::issue_stats, template::render_issue_stats_page, util::e500};
+
+/// The published issues, for the holder of an access link to pick from.
+#[tracing::instrument(name = "Get shared analytics page", skip_all)]
+pub async fn shared_analytics_page(
+    token: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issues = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, title, published_at
+        FROM newsletter_issues
+        ORDER BY published_at DESC
+        "#,
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve the published issues")
+    .map_err(e500)?;
+
+    let mut issues_html = String::new();
+    for issue in issues {
+        writeln!(
+            issues_html,
+            r#"<li><a href="/shared/{token}/issues/{id}/stats">{title}</a> ({published_at})</li>"#,
+            token = token.as_str(),
+            id = issue.newsletter_issue_id,
+            title = htmlescape::encode_minimal(&issue.title),
+            published_at = issue.published_at.format("%Y-%m-%d"),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Issues</title>
+</head>
+<body>
+    <h1>Issues</h1>
+    <ul>
+        {issues_html}
+    </ul>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct SharedIssuePath {
+    token: String,
+    newsletter_issue_id: Uuid,
+}
+
+/// Same stats as the admins see, without their navigation.
+#[tracing::instrument(name = "Get shared issue stats page", skip_all)]
+pub async fn shared_issue_stats_page(
+    path: web::Path<SharedIssuePath>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let back = format!(r#"<p><a href="/shared/{}">All issues</a></p>"#, path.token);
+    let Some(stats) = issue_stats(&pool, path.newsletter_issue_id)
+        .await
+        .context("Failed to retrieve the issue stats")
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound()
+            .content_type(ContentType::html())
+            .body(format!(
+                r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Issue not found</title>
+</head>
+<body>
+    {back}
+    <p>There is no such issue.</p>
+</body>
+</html>"#,
+            )));
+    };
+    let page = render_issue_stats_page(&back, &stats)
+        .context("Failed to render the issue stats page")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(page))
+}
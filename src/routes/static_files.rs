@@ -0,0 +1,11 @@
+use actix_web::{
+    http::header::{CacheControl, CacheDirective},
+    HttpResponse,
+};
+
+/// Missing files aren't cached, they may be deployed later on.
+pub async fn static_file_not_found() -> HttpResponse {
+    HttpResponse::NotFound()
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .finish()
+}
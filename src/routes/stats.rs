@@ -0,0 +1,15 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+use crate::{public_stats::PublicStats, util::e500};
+
+/// Figures about the newsletter for marketing pages, the subscriber count
+/// being fuzzed and rounded.
+pub async fn get_public_stats(
+    pool: web::Data<PgPool>,
+    public_stats: web::Data<PublicStats>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let stats = public_stats.get(&pool).await.map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(stats))
+}
@@ -1,18 +1,38 @@
-use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use std::collections::HashMap;
+
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    web, HttpRequest, HttpResponse, ResponseError,
+};
 use anyhow::Context;
 use chrono::Utc;
-use rand::{thread_rng, Rng};
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
-    domain::{Email, EmailError, NewSubscriber, SubscriberName, SubscriberNameError},
-    email_client::EmailClient,
-    startup::ApplicationBaseUrl,
-    template::{self, render_subscription_confirmation},
+    api_error::{ApiError, Problem, ProblemDetails},
+    configuration::{ReferralSettings, SignupPageSettings},
+    confirmation_tokens::ConfirmationSigner,
+    domain::{
+        Email, EmailError, Locale, LocaleError, NewSubscriber, SignupField, SubscriberAttributes,
+        SubscriberAttributesError, SubscriberName, SubscriberNameError, SubscriptionStatus,
+    },
+    email_client::{EmailClient, SendEmailError},
+    email_log::EmailKind,
+    feature_flags::{Feature, FeatureFlags},
+    mx_check::MxCheck,
+    newsletter_list::{list_exists, DEFAULT_LIST_ID},
+    referrals::{claim_referral_reward, find_referrer, send_referral_reward, ReferralReward},
+    startup::{ApplicationBaseUrl, RequireConfirmation},
+    subscriber_events::{record_events, SubscriberEventKind},
+    template::{
+        self, render_subscribed_page, render_subscription_confirmation, render_subscription_welcome,
+    },
+    token_generator::{generate_subscription_token, hash_token, TokenGenerator},
+    util::accepted_locales,
 };
 
-use super::error_chain_fmt;
+use super::{error_chain_fmt, store_preferences_token};
 
 pub struct StoreSubscriptionTokenError(sqlx::Error);
 
@@ -45,6 +65,10 @@ pub enum SubscriptionParseError {
     InvalidName(SubscriberNameError),
     #[error(transparent)]
     InvalidEmail(EmailError),
+    #[error(transparent)]
+    InvalidLocale(LocaleError),
+    #[error(transparent)]
+    InvalidAttributes(SubscriberAttributesError),
 }
 
 impl std::fmt::Debug for SubscriptionParseError {
@@ -59,6 +83,10 @@ pub enum SubscribeError {
     ValidationError(SubscriptionParseError),
     #[error("Duplicated subscriber")]
     DuplicatedSubscriberError,
+    #[error("The domain of the email can't receive mail")]
+    UndeliverableEmailError,
+    #[error("List not found")]
+    UnknownListError,
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -74,38 +102,70 @@ impl ResponseError for SubscribeError {
         match self {
             SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
             SubscribeError::DuplicatedSubscriberError => StatusCode::NOT_ACCEPTABLE,
+            SubscribeError::UndeliverableEmailError => StatusCode::BAD_REQUEST,
+            SubscribeError::UnknownListError => StatusCode::NOT_FOUND,
             SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for SubscribeError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            SubscribeError::ValidationError(_) => "invalid-subscriber",
+            SubscribeError::DuplicatedSubscriberError => "duplicated-subscriber",
+            SubscribeError::UndeliverableEmailError => "undeliverable-email",
+            SubscribeError::UnknownListError => "list-not-found",
+            SubscribeError::UnexpectedError(_) => "internal-error",
+        }
+    }
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct SubscriptionFormData {
     email: String,
     name: String,
+    /// Picked by the subscriber, otherwise the language they read the most
+    /// according to `Accept-Language`.
+    locale: Option<String>,
+    /// Of the subscriber whose referral link led here.
+    referral_code: Option<String>,
+    /// Answers to the custom fields of the signup page, by field name.
+    #[serde(flatten)]
+    #[schema(ignore)]
+    attributes: HashMap<String, String>,
 }
 
-impl TryFrom<SubscriptionFormData> for NewSubscriber {
-    type Error = SubscriptionParseError;
-
-    fn try_from(value: SubscriptionFormData) -> Result<Self, Self::Error> {
-        let email = Email::parse(value.email).map_err(SubscriptionParseError::InvalidEmail)?;
-        let name =
-            SubscriberName::parse(value.name).map_err(SubscriptionParseError::InvalidName)?;
-
-        Ok(NewSubscriber { email, name })
+impl SubscriptionFormData {
+    fn parse(
+        self,
+        accepted_locales: Vec<Locale>,
+        signup_fields: &[SignupField],
+    ) -> Result<NewSubscriber, SubscriptionParseError> {
+        let email = Email::parse(self.email).map_err(SubscriptionParseError::InvalidEmail)?;
+        let name = SubscriberName::parse(self.name).map_err(SubscriptionParseError::InvalidName)?;
+        let locale = match self.locale {
+            Some(locale) => {
+                Some(Locale::parse(&locale).map_err(SubscriptionParseError::InvalidLocale)?)
+            }
+            None => accepted_locales.into_iter().next(),
+        };
+        let attributes = SubscriberAttributes::parse(signup_fields, self.attributes)
+            .map_err(SubscriptionParseError::InvalidAttributes)?;
+
+        Ok(NewSubscriber {
+            email,
+            name,
+            locale,
+            attributes,
+        })
     }
 }
 
-fn generate_subscription_token() -> String {
-    let mut rng = thread_rng();
-
-    std::iter::repeat_with(|| rng.sample(rand::distributions::Alphanumeric))
-        .map(char::from)
-        .take(30)
-        .collect()
-}
-
 #[tracing::instrument(
     name = "Store subscription token in the database",
     skip(transaction, subscription_token)
@@ -116,9 +176,9 @@ pub async fn store_token(
     subscription_token: &str,
 ) -> Result<(), StoreSubscriptionTokenError> {
     sqlx::query!(
-        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id)
+        r#"INSERT INTO subscription_tokens (subscription_token_hash, subscriber_id)
         VALUES ($1, $2)"#,
-        subscription_token,
+        hash_token(subscription_token),
         subscriber_id,
     )
     .execute(&mut **transaction)
@@ -141,29 +201,56 @@ pub enum SubscriptionState {
 )]
 pub async fn insert_susbscriber(
     transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
     new_subscriber: &NewSubscriber,
+    referred_by: Option<Uuid>,
+    status: SubscriptionStatus,
 ) -> Result<SubscriptionState, sqlx::Error> {
     let subscriber_id = Uuid::new_v4();
+    let subscribed_at = Utc::now();
+    let confirmed_at = (status == SubscriptionStatus::Confirmed).then_some(subscribed_at);
 
     let result = sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions (
+            id, list_id, email, name, subscribed_at, status, locale, referred_by,
+            subscriber_attributes, confirmed_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
         -- idk a better way besides using only one query...
-        ON CONFLICT (email) DO UPDATE SET status = subscriptions.status
-        RETURNING id, status
+        ON CONFLICT (list_id, email) DO UPDATE SET
+            status = subscriptions.status,
+            -- A pending subscriber may pick another language on retrying.
+            locale = CASE
+                WHEN subscriptions.status = 'pending_confirmation'
+                    THEN COALESCE(EXCLUDED.locale, subscriptions.locale)
+                ELSE subscriptions.locale
+            END,
+            -- As may their answers to the signup fields.
+            subscriber_attributes = CASE
+                WHEN subscriptions.status = 'pending_confirmation'
+                    THEN EXCLUDED.subscriber_attributes
+                ELSE subscriptions.subscriber_attributes
+            END
+        RETURNING id, status as "status: SubscriptionStatus"
         "#,
         subscriber_id,
+        list_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
-        Utc::now()
+        subscribed_at,
+        status as SubscriptionStatus,
+        new_subscriber.locale.as_ref().map(|l| l.as_ref()),
+        referred_by,
+        new_subscriber.attributes.to_json(),
+        confirmed_at,
     )
     .fetch_one(&mut **transaction)
     .await?;
 
     let status = if subscriber_id == result.id {
         SubscriptionState::Inserted(subscriber_id)
-    } else if result.status == "pending_confirmation" {
+    } else if result.status == SubscriptionStatus::PendingConfirmation {
         SubscriptionState::Pending(result.id)
     } else {
         SubscriptionState::Confirmed
@@ -172,102 +259,359 @@ pub async fn insert_susbscriber(
     Ok(status)
 }
 
+/// Only the hash of the token sent to a pending subscriber is stored, so a
+/// repeated subscription replaces it with a new one, invalidating the link
+/// sent before. It's stored anew for subscribers who were sent a signed
+/// token instead.
 #[tracing::instrument(
-    name = "Fetch subscription token of pending subscriber",
-    skip(transaction, subscriber_id)
+    name = "Replace subscription token of pending subscriber",
+    skip(transaction, subscription_token)
 )]
-pub async fn get_subscriber_confirmation_token(
+pub async fn replace_subscriber_confirmation_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
-) -> Result<String, sqlx::Error> {
-    sqlx::query!(
+    subscription_token: &str,
+) -> Result<(), StoreSubscriptionTokenError> {
+    let result = sqlx::query!(
         r#"
-        SELECT subscription_token
-        FROM subscription_tokens
-        WHERE subscriber_id = $1
+        UPDATE subscription_tokens
+        SET subscription_token_hash = $1
+        WHERE subscriber_id = $2
         "#,
+        hash_token(subscription_token),
         subscriber_id,
     )
-    .fetch_one(&mut **transaction)
+    .execute(&mut **transaction)
     .await
-    .map(|result| result.subscription_token)
+    .map_err(StoreSubscriptionTokenError)?;
+
+    if result.rows_affected() == 0 {
+        store_token(transaction, subscriber_id, subscription_token).await?;
+    }
+
+    Ok(())
 }
 
 #[tracing::instrument(
     name = "Render subscription confirmation message",
-    skip(base_url, subscription_token)
+    skip(base_url, subscription_token, new_subscriber)
 )]
 fn build_confirmation_email_template(
     base_url: &str,
     subscription_token: &str,
+    new_subscriber: &NewSubscriber,
 ) -> Result<template::SubcriptionConfirmation, tera::Error> {
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         base_url, subscription_token,
     );
 
-    render_subscription_confirmation(&confirmation_link)
+    render_subscription_confirmation(&confirmation_link, new_subscriber.locale.as_slice())
 }
 
+/// The welcome links to the preferences center, the way out of a
+/// subscription that was never confirmed.
 #[tracing::instrument(
-    name = "Send a confirmation email to a new subscriber",
+    name = "Render subscription welcome message",
+    skip(pool, base_url, token_generator, new_subscriber)
+)]
+async fn build_welcome_email_template(
+    pool: &PgPool,
+    base_url: &str,
+    token_generator: &dyn TokenGenerator,
+    subscriber_id: Uuid,
+    new_subscriber: &NewSubscriber,
+) -> Result<template::SubscriptionWelcome, anyhow::Error> {
+    let preferences_token = generate_subscription_token(token_generator);
+    store_preferences_token(pool, subscriber_id, &preferences_token)
+        .await
+        .context("Failed to store preferences token")?;
+    let preferences_link = format!(
+        "{}/preferences?preferences_token={}",
+        base_url, preferences_token
+    );
+
+    render_subscription_welcome(&preferences_link, new_subscriber.locale.as_slice())
+        .context("Failed to render the welcome email")
+}
+
+#[tracing::instrument(
+    name = "Send a confirmation or welcome email to a new subscriber",
     skip(email_client, new_subscriber, template)
 )]
-async fn send_confirmation_email(
+async fn send_subscription_email(
     email_client: &EmailClient,
+    kind: EmailKind,
     new_subscriber: NewSubscriber,
-    template: template::SubcriptionConfirmation,
-) -> Result<(), reqwest::Error> {
+    template: &template::Template,
+) -> Result<(), SendEmailError> {
     email_client
         .send_email(
+            kind,
             &new_subscriber.email,
             "Welcome!",
             &template.html,
             &template.text,
+            &[],
+            None,
         )
         .await
 }
 
+/// Subscribes to the default list.
+#[utoipa::path(
+    post,
+    path = "/subscriptions",
+    tag = "subscriptions",
+    request_body(
+        content = SubscriptionFormData,
+        content_type = "application/x-www-form-urlencoded"
+    ),
+    responses(
+        (status = 200, description = "A confirmation email, or a welcome one if subscriptions need no confirmation, was sent, the body thanks the subscriber", body = String, content_type = "text/html"),
+        (status = 400, description = "Invalid email, name, locale or signup field answer, or an email whose domain can't receive mail", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 406, description = "The email is already subscribed", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
 #[tracing::instrument(
     name = "Adding a new susbscriber",
-    skip(form, pool, email_client, base_url),
+    skip(
+        request,
+        form,
+        pool,
+        email_client,
+        mx_check,
+        base_url,
+        token_generator,
+        signup,
+        require_confirmation,
+        features,
+        referrals,
+        confirmation_signer
+    ),
     fields(
         susbscriber_email = %form.email,
         susbscriber_name = %form.name
     )
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn subscribe(
+    request: HttpRequest,
+    form: web::Form<SubscriptionFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    mx_check: web::Data<MxCheck>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    token_generator: web::Data<dyn TokenGenerator>,
+    signup: web::Data<SignupPageSettings>,
+    require_confirmation: web::Data<RequireConfirmation>,
+    features: web::Data<FeatureFlags>,
+    referrals: web::Data<ReferralSettings>,
+    confirmation_signer: web::Data<ConfirmationSigner>,
+) -> Result<HttpResponse, SubscribeError> {
+    add_subscription(
+        DEFAULT_LIST_ID,
+        form.0,
+        accepted_locales(&request),
+        &signup.fields,
+        require_confirmation.0,
+        &pool,
+        &email_client,
+        &mx_check,
+        &base_url,
+        token_generator.get_ref(),
+        &features,
+        &referrals,
+        &confirmation_signer,
+    )
+    .await
+}
+
+#[tracing::instrument(
+    name = "Adding a new susbscriber to a list",
+    skip(
+        request,
+        form,
+        pool,
+        email_client,
+        mx_check,
+        base_url,
+        token_generator,
+        signup,
+        require_confirmation,
+        features,
+        referrals,
+        confirmation_signer
+    ),
+    fields(
+        susbscriber_email = %form.email,
+        susbscriber_name = %form.name
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn subscribe_to_list(
+    request: HttpRequest,
+    list_id: web::Path<Uuid>,
     form: web::Form<SubscriptionFormData>,
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
+    mx_check: web::Data<MxCheck>,
     base_url: web::Data<ApplicationBaseUrl>,
+    token_generator: web::Data<dyn TokenGenerator>,
+    signup: web::Data<SignupPageSettings>,
+    require_confirmation: web::Data<RequireConfirmation>,
+    features: web::Data<FeatureFlags>,
+    referrals: web::Data<ReferralSettings>,
+    confirmation_signer: web::Data<ConfirmationSigner>,
 ) -> Result<HttpResponse, SubscribeError> {
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    let list_id = list_id.into_inner();
+
+    if !list_exists(&pool, list_id)
+        .await
+        .context("Failed to check newsletter list")?
+    {
+        return Err(SubscribeError::UnknownListError);
+    }
+
+    add_subscription(
+        list_id,
+        form.0,
+        accepted_locales(&request),
+        &signup.fields,
+        require_confirmation.0,
+        &pool,
+        &email_client,
+        &mx_check,
+        &base_url,
+        token_generator.get_ref(),
+        &features,
+        &referrals,
+        &confirmation_signer,
+    )
+    .await
+}
+
+/// What a subscriber is sent once stored.
+enum SubscriptionEmail {
+    /// The link to confirm a pending subscription.
+    Confirmation(String),
+    /// A welcome, the subscription needing no confirmation, along with the
+    /// reward it earned the referrer, if any.
+    Welcome(Uuid, Option<ReferralReward>),
+}
+
+/// Without double opt-in, new subscribers are confirmed right away and
+/// welcomed. Pending ones, who subscribed while it was required, still
+/// confirm through a new link.
+#[allow(clippy::too_many_arguments)]
+async fn add_subscription(
+    list_id: Uuid,
+    mut form: SubscriptionFormData,
+    accepted_locales: Vec<Locale>,
+    signup_fields: &[SignupField],
+    require_confirmation: bool,
+    pool: &PgPool,
+    email_client: &EmailClient,
+    mx_check: &MxCheck,
+    base_url: &ApplicationBaseUrl,
+    token_generator: &dyn TokenGenerator,
+    features: &FeatureFlags,
+    referrals: &ReferralSettings,
+    confirmation_signer: &ConfirmationSigner,
+) -> Result<HttpResponse, SubscribeError> {
+    let referral_code = form.referral_code.take();
+    let new_subscriber = form
+        .parse(accepted_locales, signup_fields)
+        .map_err(SubscribeError::ValidationError)?;
+    if !mx_check.accepts(&new_subscriber.email).await {
+        return Err(SubscribeError::UndeliverableEmailError);
+    }
 
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to aquire a Postgres connection from the pool")?;
 
-    let subscription_state = insert_susbscriber(&mut transaction, &new_subscriber)
-        .await
-        .context("Failed to insert new subscriber in the database")?;
+    let referred_by = match referral_code {
+        Some(referral_code) => find_referrer(&mut transaction, list_id, &referral_code)
+            .await
+            .context("Failed to find the referrer of a new subscriber")?,
+        None => None,
+    };
+    let status = match require_confirmation {
+        true => SubscriptionStatus::PendingConfirmation,
+        false => SubscriptionStatus::Confirmed,
+    };
+    let subscription_state = insert_susbscriber(
+        &mut transaction,
+        list_id,
+        &new_subscriber,
+        referred_by,
+        status,
+    )
+    .await
+    .context("Failed to insert new subscriber in the database")?;
 
-    let subscription_token = match subscription_state {
+    let subscription_email = match subscription_state {
         SubscriptionState::Confirmed => Err(SubscribeError::DuplicatedSubscriberError)?,
         SubscriptionState::Inserted(subscriber_id) => {
-            let subscription_token = generate_subscription_token();
-
-            store_token(&mut transaction, subscriber_id, &subscription_token)
+            record_events(
+                &mut transaction,
+                &[subscriber_id],
+                SubscriberEventKind::Subscribed,
+                serde_json::json!({}),
+            )
+            .await
+            .context("Failed to record the subscription of a new subscriber")?;
+
+            if require_confirmation && confirmation_signer.is_signing() {
+                SubscriptionEmail::Confirmation(confirmation_signer.token(subscriber_id))
+            } else if require_confirmation {
+                let subscription_token = generate_subscription_token(token_generator);
+
+                store_token(&mut transaction, subscriber_id, &subscription_token)
+                    .await
+                    .context("Failed to store the confirmation token for a new subscriber")?;
+
+                SubscriptionEmail::Confirmation(subscription_token)
+            } else {
+                record_events(
+                    &mut transaction,
+                    &[subscriber_id],
+                    SubscriberEventKind::Confirmed,
+                    serde_json::json!({}),
+                )
                 .await
-                .context("Failed to store the confirmation token for a new subscriber")?;
-
-            subscription_token
+                .context("Failed to record the confirmation of a new subscriber")?;
+                let reward = match features.is_enabled(Feature::Referrals) {
+                    true => claim_referral_reward(
+                        &mut transaction,
+                        subscriber_id,
+                        &referrals.reward_milestones,
+                    )
+                    .await
+                    .context("Failed to claim the reward of a referrer")?,
+                    false => None,
+                };
+
+                SubscriptionEmail::Welcome(subscriber_id, reward)
+            }
+        }
+        SubscriptionState::Pending(subscriber_id) if confirmation_signer.is_signing() => {
+            SubscriptionEmail::Confirmation(confirmation_signer.token(subscriber_id))
         }
         SubscriptionState::Pending(subscriber_id) => {
-            get_subscriber_confirmation_token(&mut transaction, subscriber_id)
-                .await
-                .context("Failed to retrieve subscriber confirmation token")?
+            let subscription_token = generate_subscription_token(token_generator);
+
+            replace_subscriber_confirmation_token(
+                &mut transaction,
+                subscriber_id,
+                &subscription_token,
+            )
+            .await
+            .context("Failed to replace the confirmation token of a pending subscriber")?;
+
+            SubscriptionEmail::Confirmation(subscription_token)
         }
     };
 
@@ -276,11 +620,53 @@ pub async fn subscribe(
         .await
         .context("Failed to commit SQL transaction to store new subscriber")?;
 
-    let template = build_confirmation_email_template(&base_url.0, &subscription_token)
-        .context("Failed to generate email template for confirmation email")?;
-    send_confirmation_email(&email_client, new_subscriber, template)
-        .await
-        .context("Failed to send confirmation email")?;
+    let page = render_subscribed_page(
+        matches!(subscription_email, SubscriptionEmail::Confirmation(_)),
+        new_subscriber.locale.as_slice(),
+    )
+    .context("Failed to render the subscribed page")?;
+    match subscription_email {
+        SubscriptionEmail::Confirmation(subscription_token) => {
+            let template = build_confirmation_email_template(
+                &base_url.0,
+                &subscription_token,
+                &new_subscriber,
+            )
+            .context("Failed to generate email template for confirmation email")?;
+            send_subscription_email(
+                email_client,
+                EmailKind::SubscriptionConfirmation,
+                new_subscriber,
+                &template,
+            )
+            .await
+            .context("Failed to send confirmation email")?;
+        }
+        SubscriptionEmail::Welcome(subscriber_id, reward) => {
+            let template = build_welcome_email_template(
+                pool,
+                &base_url.0,
+                token_generator,
+                subscriber_id,
+                &new_subscriber,
+            )
+            .await?;
+            send_subscription_email(
+                email_client,
+                EmailKind::SubscriptionWelcome,
+                new_subscriber,
+                &template,
+            )
+            .await
+            .context("Failed to send welcome email")?;
+
+            if let Some(reward) = reward {
+                send_referral_reward(email_client, &reward).await;
+            }
+        }
+    }
 
-    Ok(HttpResponse::Ok().finish())
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(page))
 }
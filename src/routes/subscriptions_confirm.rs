@@ -1,31 +1,41 @@
 use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
 use anyhow::Context;
+use chrono::Utc;
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-use crate::domain::{SubscriptionToken, SubscriptionTokenError};
+use crate::{
+    api_error::{ApiError, Problem, ProblemDetails},
+    configuration::ReferralSettings,
+    confirmation_tokens::ConfirmationSigner,
+    domain::{IllegalTransition, SubscriptionStatus, SubscriptionToken, SubscriptionTokenError},
+    email_client::EmailClient,
+    feature_flags::{Feature, FeatureFlags},
+    referrals::{claim_referral_reward, send_referral_reward},
+    subscriber_events::{record_events, SubscriberEventKind},
+    token_generator::hash_token,
+};
 
 use super::error_chain_fmt;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct SubscriptionConfirmationParameters {
+    /// Sent to the subscriber in the confirmation email, either stored or
+    /// signed.
     subscription_token: String,
 }
 
-impl TryFrom<SubscriptionConfirmationParameters> for SubscriptionToken {
-    type Error = SubscriptionTokenError;
-
-    fn try_from(value: SubscriptionConfirmationParameters) -> Result<Self, Self::Error> {
-        SubscriptionToken::parse(value.subscription_token)
-    }
-}
-
 #[derive(thiserror::Error)]
 pub enum SubscriptionConfirmationError {
     #[error("{0}")]
     ValidationError(SubscriptionTokenError),
     #[error("Confirmation not authorized")]
     MissingConfirmationError,
+    #[error("Confirmation link expired")]
+    ExpiredConfirmationError,
+    #[error(transparent)]
+    IllegalTransition(#[from] IllegalTransition),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -41,9 +51,27 @@ impl ResponseError for SubscriptionConfirmationError {
         match self {
             SubscriptionConfirmationError::ValidationError(_) => StatusCode::BAD_REQUEST,
             SubscriptionConfirmationError::MissingConfirmationError => StatusCode::UNAUTHORIZED,
+            SubscriptionConfirmationError::ExpiredConfirmationError => StatusCode::GONE,
+            SubscriptionConfirmationError::IllegalTransition(_) => StatusCode::CONFLICT,
             SubscriptionConfirmationError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError(self).error_response()
+    }
+}
+
+impl Problem for SubscriptionConfirmationError {
+    fn problem_type(&self) -> &'static str {
+        match self {
+            SubscriptionConfirmationError::ValidationError(_) => "invalid-subscription-token",
+            SubscriptionConfirmationError::MissingConfirmationError => "unknown-subscription-token",
+            SubscriptionConfirmationError::ExpiredConfirmationError => "expired-subscription-token",
+            SubscriptionConfirmationError::IllegalTransition(_) => "illegal-status-transition",
+            SubscriptionConfirmationError::UnexpectedError(_) => "internal-error",
+        }
+    }
 }
 
 #[tracing::instrument(
@@ -57,10 +85,10 @@ pub async fn delete_possible_pending_subscriber_confirmation(
     let result = sqlx::query!(
         r#"
         DELETE from subscription_tokens
-        WHERE subscription_token = $1
+        WHERE subscription_token_hash = $1
         RETURNING subscriber_id
         "#,
-        subscription_token.as_ref()
+        hash_token(subscription_token.as_ref())
     )
     .fetch_optional(&mut **transaction)
     .await?;
@@ -68,6 +96,9 @@ pub async fn delete_possible_pending_subscriber_confirmation(
     Ok(result.map(|r| r.subscriber_id))
 }
 
+/// Only pending subscribers can be confirmed, e.g. not the ones whose
+/// address bounced since they were sent their link. Signed links may outlive
+/// their subscriber, who is then unknown.
 #[tracing::instrument(
     name = "Mark subscriber as confirmed",
     skip(transaction, subscriber_id)
@@ -75,50 +106,123 @@ pub async fn delete_possible_pending_subscriber_confirmation(
 pub async fn confirm_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
-) -> Result<(), sqlx::Error> {
+) -> Result<(), SubscriptionConfirmationError> {
+    let status = sqlx::query_scalar!(
+        r#"
+        SELECT status AS "status: SubscriptionStatus"
+        FROM subscriptions
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to retrieve the status of the subscriber")?
+    .ok_or(SubscriptionConfirmationError::MissingConfirmationError)?
+    .transition_to(SubscriptionStatus::Confirmed)?;
+
     sqlx::query!(
         r#"
         UPDATE subscriptions
-        SET status = 'confirmed'
-        WHERE id = $1
+        SET status = $1, confirmed_at = now()
+        WHERE id = $2
         "#,
+        status as SubscriptionStatus,
         &subscriber_id
     )
     .execute(&mut **transaction)
-    .await?;
+    .await
+    .context("Failed to confirm new subscriber")?;
 
     Ok(())
 }
 
-#[tracing::instrument(name = "Confirm pending subscriber", skip(parameters, pool))]
+/// Whoever referred the subscriber is thanked by email if the confirmation
+/// makes them reach a reward milestone.
+#[utoipa::path(
+    get,
+    path = "/subscriptions/confirm",
+    tag = "subscriptions",
+    params(SubscriptionConfirmationParameters),
+    responses(
+        (status = 200, description = "The subscription is confirmed"),
+        (status = 400, description = "Malformed token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Unknown or already used token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 410, description = "Expired signed token", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "The subscription can't be confirmed anymore", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+#[tracing::instrument(
+    name = "Confirm pending subscriber",
+    skip(
+        parameters,
+        pool,
+        email_client,
+        features,
+        referrals,
+        confirmation_signer
+    )
+)]
 pub async fn confirm(
     parameters: web::Query<SubscriptionConfirmationParameters>,
     pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    features: web::Data<FeatureFlags>,
+    referrals: web::Data<ReferralSettings>,
+    confirmation_signer: web::Data<ConfirmationSigner>,
 ) -> Result<HttpResponse, SubscriptionConfirmationError> {
-    let subscription_token = parameters
-        .0
-        .try_into()
-        .map_err(SubscriptionConfirmationError::ValidationError)?;
+    let SubscriptionConfirmationParameters { subscription_token } = parameters.into_inner();
 
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to aquire a Postgres connection from the pool")?;
 
-    let subscriber_id =
-        delete_possible_pending_subscriber_confirmation(&mut transaction, subscription_token)
-            .await
-            .context("Failed to delete possible pending subscriber confirmation")?
-            .ok_or(SubscriptionConfirmationError::MissingConfirmationError)?;
-
-    confirm_subscriber(&mut transaction, subscriber_id)
+    let subscriber_id = match confirmation_signer.verify(&subscription_token) {
+        Some(confirmation) if confirmation.is_expired(Utc::now()) => {
+            return Err(SubscriptionConfirmationError::ExpiredConfirmationError)
+        }
+        Some(confirmation) => confirmation.subscriber_id,
+        None => {
+            let subscription_token = SubscriptionToken::parse(subscription_token)
+                .map_err(SubscriptionConfirmationError::ValidationError)?;
+
+            delete_possible_pending_subscriber_confirmation(&mut transaction, subscription_token)
+                .await
+                .context("Failed to delete possible pending subscriber confirmation")?
+                .ok_or(SubscriptionConfirmationError::MissingConfirmationError)?
+        }
+    };
+
+    confirm_subscriber(&mut transaction, subscriber_id).await?;
+    record_events(
+        &mut transaction,
+        &[subscriber_id],
+        SubscriberEventKind::Confirmed,
+        serde_json::json!({}),
+    )
+    .await
+    .context("Failed to record the confirmation of a subscriber")?;
+    let reward = match features.is_enabled(Feature::Referrals) {
+        true => claim_referral_reward(
+            &mut transaction,
+            subscriber_id,
+            &referrals.reward_milestones,
+        )
         .await
-        .context("Failed to confirm new subscriber")?;
+        .context("Failed to claim the reward of a referrer")?,
+        false => None,
+    };
 
     transaction
         .commit()
         .await
         .context("Failed to commit SQL transaction to store new subscriber")?;
 
+    if let Some(reward) = reward {
+        send_referral_reward(&email_client, &reward).await;
+    }
+
     Ok(HttpResponse::Ok().finish())
 }
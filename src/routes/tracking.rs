@@ -0,0 +1,53 @@
+use actix_web::{
+    http::header::{CacheControl, CacheDirective, LOCATION},
+    web, HttpResponse,
+};
+
+use crate::tracking::{EmailEvent, EmailEventKind, EmailEventRecorder, EmailTracker};
+
+/// A transparent 1x1 GIF.
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+#[tracing::instrument(name = "Track email open", skip_all)]
+pub async fn track_open(
+    token: web::Path<String>,
+    recorder: web::Data<EmailEventRecorder>,
+    tracker: web::Data<EmailTracker>,
+) -> HttpResponse {
+    let Some(tracked) = tracker.verify(&token) else {
+        return HttpResponse::NotFound().finish();
+    };
+    recorder
+        .record(EmailEvent::now(EmailEventKind::Open, tracked))
+        .await;
+
+    HttpResponse::Ok()
+        .content_type("image/gif")
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(PIXEL)
+}
+
+#[tracing::instrument(name = "Track email click", skip_all)]
+pub async fn track_click(
+    token: web::Path<String>,
+    recorder: web::Data<EmailEventRecorder>,
+    tracker: web::Data<EmailTracker>,
+) -> HttpResponse {
+    let Some(tracked) = tracker.verify(&token) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let Some(url) = tracked.url.clone() else {
+        return HttpResponse::NotFound().finish();
+    };
+    recorder
+        .record(EmailEvent::now(EmailEventKind::Click, tracked))
+        .await;
+
+    HttpResponse::Found()
+        .insert_header((LOCATION, url))
+        .finish()
+}
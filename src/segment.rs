@@ -0,0 +1,117 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+/// A condition that a confirmed subscriber must satisfy to belong to a segment.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SegmentRule {
+    Topic { name: String },
+    ConfirmedAfter { date: DateTime<Utc> },
+    ConfirmedBefore { date: DateTime<Utc> },
+}
+
+/// Conjunction of rules, compiled into the `WHERE` clause of a query over
+/// the `subscriptions` table aliased as `s`.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct SegmentRules(pub Vec<SegmentRule>);
+
+impl SegmentRules {
+    pub fn push_conditions<'a>(&'a self, builder: &mut QueryBuilder<'a, Postgres>) {
+        builder.push(" WHERE s.status = 'confirmed'");
+
+        for rule in &self.0 {
+            match rule {
+                SegmentRule::Topic { name } => {
+                    builder
+                        .push(
+                            " AND EXISTS (\
+                            SELECT 1 FROM subscriber_topics st \
+                            JOIN topics t ON t.id = st.topic_id \
+                            WHERE st.subscriber_id = s.id AND t.name = ",
+                        )
+                        .push_bind(name)
+                        .push(")");
+                }
+                SegmentRule::ConfirmedAfter { date } => {
+                    builder.push(" AND s.confirmed_at > ").push_bind(date);
+                }
+                SegmentRule::ConfirmedBefore { date } => {
+                    builder.push(" AND s.confirmed_at < ").push_bind(date);
+                }
+            }
+        }
+    }
+
+    #[tracing::instrument(name = "Count segment subscribers", skip(pool))]
+    pub async fn count_subscribers(&self, pool: &PgPool) -> Result<i64, sqlx::Error> {
+        let mut builder = QueryBuilder::new("SELECT count(*) FROM subscriptions s");
+        self.push_conditions(&mut builder);
+
+        builder.build_query_scalar().fetch_one(pool).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use sqlx::QueryBuilder;
+
+    use super::{SegmentRule, SegmentRules};
+
+    #[test]
+    fn empty_rules_select_all_confirmed_subscribers() {
+        let rules = SegmentRules::default();
+        let mut builder = QueryBuilder::new("SELECT s.id FROM subscriptions s");
+        rules.push_conditions(&mut builder);
+
+        assert_eq!(
+            builder.sql(),
+            "SELECT s.id FROM subscriptions s WHERE s.status = 'confirmed'"
+        );
+    }
+
+    #[test]
+    fn rules_are_compiled_into_a_conjunction_of_bound_conditions() {
+        let rules = SegmentRules(vec![
+            SegmentRule::Topic {
+                name: "rust".into(),
+            },
+            SegmentRule::ConfirmedAfter {
+                date: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            },
+        ]);
+        let mut builder = QueryBuilder::new("SELECT s.id FROM subscriptions s");
+        rules.push_conditions(&mut builder);
+
+        assert_eq!(
+            builder.sql(),
+            "SELECT s.id FROM subscriptions s WHERE s.status = 'confirmed' \
+            AND EXISTS (SELECT 1 FROM subscriber_topics st \
+            JOIN topics t ON t.id = st.topic_id \
+            WHERE st.subscriber_id = s.id AND t.name = $1) \
+            AND s.confirmed_at > $2"
+        );
+    }
+
+    #[test]
+    fn rules_are_deserialized_from_tagged_json() {
+        let rules: SegmentRules = serde_json::from_value(serde_json::json!([
+            {"type": "topic", "name": "rust"},
+            {"type": "confirmed_before", "date": "2024-01-01T00:00:00Z"},
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            rules,
+            SegmentRules(vec![
+                SegmentRule::Topic {
+                    name: "rust".into()
+                },
+                SegmentRule::ConfirmedBefore {
+                    date: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+                },
+            ])
+        );
+    }
+}
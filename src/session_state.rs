@@ -1,16 +1,380 @@
-use std::future::{ready, Ready};
+use std::{
+    collections::HashMap,
+    future::{ready, Ready},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use actix_session::{Session, SessionExt, SessionGetError, SessionInsertError};
-use actix_web::FromRequest;
+use actix_session::{
+    storage::{
+        CookieSessionStore, LoadError, RedisSessionStore, SaveError, SessionKey, SessionStore,
+        UpdateError,
+    },
+    Session, SessionExt, SessionGetError, SessionInsertError,
+};
+use actix_web::{cookie::time, web, FromRequest};
+use anyhow::Context;
+use chrono::Utc;
+use redis::{aio::ConnectionManager, AsyncCommands};
+use secrecy::{ExposeSecret, Secret};
 use uuid::Uuid;
 
-use crate::user_role::UserRole;
+use crate::{
+    configuration::SessionBackend,
+    token_generator::{CsprngTokenGenerator, TokenGenerator},
+    user_role::UserRole,
+};
 
-pub struct TypedSession(Session);
+// Same as the default state TTL of actix-session.
+const SESSION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Where the state of the sessions is kept, as configured with
+/// `session_backend`.
+#[derive(Clone)]
+pub enum SessionStorage {
+    Redis(Box<RedisSessionStore>),
+    /// The state is kept, encrypted, in the session cookie itself.
+    Cookie(Arc<CookieSessionStore>),
+    InMemory(InMemorySessionStore),
+}
+
+impl SessionStorage {
+    /// Builds the store of the sessions along with their index, connecting
+    /// to Redis only if it's used.
+    pub async fn build(
+        backend: SessionBackend,
+        redis_uri: &Secret<String>,
+    ) -> Result<(Self, SessionIndex), anyhow::Error> {
+        match backend {
+            SessionBackend::Redis => Ok((
+                Self::Redis(Box::new(
+                    RedisSessionStore::new(redis_uri.expose_secret()).await?,
+                )),
+                SessionIndex::redis(redis_uri).await?,
+            )),
+            SessionBackend::Cookie => Ok((
+                Self::Cookie(Arc::new(CookieSessionStore::default())),
+                SessionIndex::Disabled,
+            )),
+            SessionBackend::InMemory => Ok((
+                Self::InMemory(InMemorySessionStore::default()),
+                SessionIndex::in_memory(),
+            )),
+        }
+    }
+}
+
+impl SessionStore for SessionStorage {
+    async fn load(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<HashMap<String, String>>, LoadError> {
+        match self {
+            Self::Redis(store) => store.load(session_key).await,
+            Self::Cookie(store) => store.load(session_key).await,
+            Self::InMemory(store) => store.load(session_key).await,
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: HashMap<String, String>,
+        ttl: &time::Duration,
+    ) -> Result<SessionKey, SaveError> {
+        match self {
+            Self::Redis(store) => store.save(session_state, ttl).await,
+            Self::Cookie(store) => store.save(session_state, ttl).await,
+            Self::InMemory(store) => store.save(session_state, ttl).await,
+        }
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: HashMap<String, String>,
+        ttl: &time::Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        match self {
+            Self::Redis(store) => store.update(session_key, session_state, ttl).await,
+            Self::Cookie(store) => store.update(session_key, session_state, ttl).await,
+            Self::InMemory(store) => store.update(session_key, session_state, ttl).await,
+        }
+    }
+
+    async fn update_ttl(
+        &self,
+        session_key: &SessionKey,
+        ttl: &time::Duration,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Redis(store) => store.update_ttl(session_key, ttl).await,
+            Self::Cookie(store) => store.update_ttl(session_key, ttl).await,
+            Self::InMemory(store) => store.update_ttl(session_key, ttl).await,
+        }
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Redis(store) => store.delete(session_key).await,
+            Self::Cookie(store) => store.delete(session_key).await,
+            Self::InMemory(store) => store.delete(session_key).await,
+        }
+    }
+}
+
+/// The state of each session by its key, along with when it expires.
+type SessionStates = HashMap<String, (Instant, HashMap<String, String>)>;
+
+/// Keeps the state of the sessions in the memory of the process, so they
+/// are lost on restarts and unknown to other instances. Meant for local
+/// development and single instance deployments without Redis.
+#[derive(Clone, Default)]
+pub struct InMemorySessionStore(Arc<Mutex<SessionStates>>);
+
+impl InMemorySessionStore {
+    fn expires_at(ttl: &time::Duration) -> Instant {
+        Instant::now() + Duration::from_secs(ttl.whole_seconds().max(0) as u64)
+    }
+}
+
+impl SessionStore for InMemorySessionStore {
+    async fn load(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<HashMap<String, String>>, LoadError> {
+        let sessions = self.0.lock().unwrap();
+
+        Ok(sessions
+            .get(session_key.as_ref())
+            .filter(|(expires_at, _)| *expires_at > Instant::now())
+            .map(|(_, state)| state.clone()))
+    }
+
+    async fn save(
+        &self,
+        session_state: HashMap<String, String>,
+        ttl: &time::Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let session_key = CsprngTokenGenerator.alphanumeric(64);
+        let mut sessions = self.0.lock().unwrap();
+
+        // Expired sessions are dropped as new ones come in, so that
+        // abandoned sessions don't pile up.
+        let now = Instant::now();
+        sessions.retain(|_, (expires_at, _)| *expires_at > now);
+        sessions.insert(session_key.clone(), (Self::expires_at(ttl), session_state));
+
+        SessionKey::try_from(session_key).map_err(|e| SaveError::Other(e.into()))
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: HashMap<String, String>,
+        ttl: &time::Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        if let Some(session) = self.0.lock().unwrap().get_mut(session_key.as_ref()) {
+            *session = (Self::expires_at(ttl), session_state);
+
+            return Ok(session_key);
+        }
+
+        // Expired and dropped in the meantime, it's saved anew.
+        self.save(session_state, ttl).await.map_err(|e| match e {
+            SaveError::Serialization(e) => UpdateError::Serialization(e),
+            SaveError::Other(e) => UpdateError::Other(e),
+        })
+    }
+
+    async fn update_ttl(
+        &self,
+        session_key: &SessionKey,
+        ttl: &time::Duration,
+    ) -> Result<(), anyhow::Error> {
+        if let Some((expires_at, _)) = self.0.lock().unwrap().get_mut(session_key.as_ref()) {
+            *expires_at = Self::expires_at(ttl);
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        self.0.lock().unwrap().remove(session_key.as_ref());
+
+        Ok(())
+    }
+}
+
+/// The keys of the sessions of each user, along with when each expires.
+type UserSessions = HashMap<Uuid, HashMap<String, Instant>>;
+
+/// Keeps track of the live sessions of each user, so that all of them can be
+/// invalidated at once (e.g. after a password change).
+///
+/// Each user has a set holding the keys of its sessions, in Redis (a sorted
+/// set scored by expiry) or, without it, in the memory of the process. A
+/// session whose key is no longer in the set, or expired, is considered
+/// revoked. Keys expire [`SESSION_TTL`] after the session was last used.
+#[derive(Clone)]
+pub enum SessionIndex {
+    Redis(Box<ConnectionManager>),
+    /// Only knows of the sessions registered with this instance.
+    InMemory(Arc<Mutex<UserSessions>>),
+    /// Keeps track of nothing, for sessions kept in cookies: those outlive
+    /// restarts and reach any instance, which an index in the memory of one
+    /// wouldn't. Every session is live, so logging out everywhere isn't
+    /// supported; only the current session can be ended.
+    Disabled,
+}
+
+impl SessionIndex {
+    pub async fn redis(redis_uri: &Secret<String>) -> Result<Self, anyhow::Error> {
+        let client = redis::Client::open(redis_uri.expose_secret().as_str())?;
+        let manager = ConnectionManager::new(client).await?;
+
+        Ok(Self::Redis(Box::new(manager)))
+    }
+
+    pub fn in_memory() -> Self {
+        Self::InMemory(Arc::default())
+    }
+
+    fn user_key(user_id: Uuid) -> String {
+        format!("user_sessions:{}", user_id)
+    }
+
+    /// Sets the expiry of the session key, and of the set of the user, to
+    /// [`SESSION_TTL`] from now.
+    async fn touch(
+        connection: &mut ConnectionManager,
+        key: &str,
+        session_key: &str,
+    ) -> Result<(), anyhow::Error> {
+        let expires_at = Utc::now().timestamp() + SESSION_TTL.as_secs() as i64;
+
+        connection
+            .zadd::<_, _, _, ()>(key, session_key, expires_at)
+            .await?;
+        connection
+            .expire::<_, ()>(key, SESSION_TTL.as_secs() as i64)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn add(&self, user_id: Uuid, session_key: &str) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Redis(manager) => {
+                let key = Self::user_key(user_id);
+                let mut connection = manager.as_ref().clone();
+
+                // Keys of sessions that expired without logging out.
+                connection
+                    .zrembyscore::<_, _, _, ()>(&key, "-inf", Utc::now().timestamp())
+                    .await?;
+                Self::touch(&mut connection, &key, session_key).await?;
+            }
+            Self::InMemory(users) => {
+                let mut users = users.lock().unwrap();
+                let now = Instant::now();
+                users.retain(|_, session_keys| {
+                    session_keys.retain(|_, expires_at| *expires_at > now);
+                    !session_keys.is_empty()
+                });
+
+                users
+                    .entry(user_id)
+                    .or_default()
+                    .insert(session_key.to_string(), now + SESSION_TTL);
+            }
+            Self::Disabled => {}
+        }
+
+        Ok(())
+    }
+
+    /// Whether the session is live, extending its expiry if so.
+    async fn contains(&self, user_id: Uuid, session_key: &str) -> Result<bool, anyhow::Error> {
+        match self {
+            Self::Redis(manager) => {
+                let key = Self::user_key(user_id);
+                let mut connection = manager.as_ref().clone();
+
+                let expires_at: Option<i64> = connection.zscore(&key, session_key).await?;
+                let live = expires_at.is_some_and(|expires_at| expires_at > Utc::now().timestamp());
+                if live {
+                    Self::touch(&mut connection, &key, session_key).await?;
+                }
+
+                Ok(live)
+            }
+            Self::InMemory(users) => {
+                let now = Instant::now();
+                let mut users = users.lock().unwrap();
+                let expires_at = users
+                    .get_mut(&user_id)
+                    .and_then(|session_keys| session_keys.get_mut(session_key))
+                    .filter(|expires_at| **expires_at > now);
+
+                Ok(match expires_at {
+                    Some(expires_at) => {
+                        *expires_at = now + SESSION_TTL;
+                        true
+                    }
+                    None => false,
+                })
+            }
+            Self::Disabled => Ok(true),
+        }
+    }
+
+    async fn remove(&self, user_id: Uuid, session_key: &str) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Redis(manager) => {
+                manager
+                    .as_ref()
+                    .clone()
+                    .zrem::<_, _, ()>(Self::user_key(user_id), session_key)
+                    .await?
+            }
+            Self::InMemory(users) => {
+                if let Some(session_keys) = users.lock().unwrap().get_mut(&user_id) {
+                    session_keys.remove(session_key);
+                }
+            }
+            Self::Disabled => {}
+        }
+
+        Ok(())
+    }
+
+    async fn remove_all(&self, user_id: Uuid) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Redis(manager) => {
+                manager
+                    .as_ref()
+                    .clone()
+                    .del::<_, ()>(Self::user_key(user_id))
+                    .await?
+            }
+            Self::InMemory(users) => {
+                users.lock().unwrap().remove(&user_id);
+            }
+            Self::Disabled => {}
+        }
+
+        Ok(())
+    }
+}
+
+pub struct TypedSession(Session, Option<web::Data<SessionIndex>>);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
     const USER_ROLE: &'static str = "user_role";
+    const SESSION_KEY: &'static str = "session_key";
+    const PASSWORD_EXPIRED: &'static str = "password_expired";
+    const CSRF_TOKEN: &'static str = "csrf_token";
 
     pub fn renew(&self) {
         self.0.renew();
@@ -32,11 +396,116 @@ impl TypedSession {
         self.0.get(Self::USER_ROLE)
     }
 
-    pub fn log_out(&self) {
-        self.0.purge()
+    pub fn mark_password_as_expired(&self) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PASSWORD_EXPIRED, true)
+    }
+
+    pub fn is_password_expired(&self) -> Result<bool, SessionGetError> {
+        self.0
+            .get(Self::PASSWORD_EXPIRED)
+            .map(|expired| expired.unwrap_or(false))
+    }
+
+    pub fn clear_password_expiration(&self) {
+        self.0.remove(Self::PASSWORD_EXPIRED);
+    }
+
+    /// Token that forms must send back, so that requests forged by other
+    /// sites are told apart. It's created on first use and lives as long as
+    /// the session.
+    pub fn csrf_token(&self) -> Result<String, anyhow::Error> {
+        if let Some(token) = self.get_csrf_token().context("Failed to get CSRF token")? {
+            return Ok(token);
+        }
+
+        let token = generate_session_key();
+        self.0
+            .insert(Self::CSRF_TOKEN, &token)
+            .context("Failed to insert CSRF token")?;
+
+        Ok(token)
+    }
+
+    pub fn get_csrf_token(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::CSRF_TOKEN)
+    }
+
+    fn index(&self) -> Result<&SessionIndex, anyhow::Error> {
+        self.1
+            .as_ref()
+            .map(|index| index.get_ref())
+            .context("Session index is not registered in the application")
+    }
+
+    /// Registers the current session in the index of the given user.
+    pub async fn register(&self, user_id: Uuid) -> Result<(), anyhow::Error> {
+        let session_key = generate_session_key();
+
+        self.0
+            .insert(Self::SESSION_KEY, &session_key)
+            .context("Failed to insert session key")?;
+        self.index()?
+            .add(user_id, &session_key)
+            .await
+            .context("Failed to register session in the index")
+    }
+
+    /// Checks if the current session wasn't revoked in the meantime.
+    pub async fn is_registered(&self, user_id: Uuid) -> Result<bool, anyhow::Error> {
+        let session_key = match self
+            .0
+            .get::<String>(Self::SESSION_KEY)
+            .context("Failed to get session key")?
+        {
+            Some(session_key) => session_key,
+            None => return Ok(false),
+        };
+
+        self.index()?
+            .contains(user_id, &session_key)
+            .await
+            .context("Failed to check session in the index")
+    }
+
+    /// Revokes every session of the given user, including the current one.
+    pub async fn revoke_all(&self, user_id: Uuid) -> Result<(), anyhow::Error> {
+        self.index()?
+            .remove_all(user_id)
+            .await
+            .context("Failed to revoke user sessions")
+    }
+
+    /// Revokes every session of the given user, except the current one.
+    pub async fn revoke_others(&self, user_id: Uuid) -> Result<(), anyhow::Error> {
+        self.revoke_all(user_id).await?;
+        self.register(user_id).await
+    }
+
+    /// Ends the current session, taking it out of the index of its user.
+    pub async fn log_out(&self) -> Result<(), anyhow::Error> {
+        let user_id = self.get_user_id().context("Failed to get user id")?;
+        let session_key = self
+            .0
+            .get::<String>(Self::SESSION_KEY)
+            .context("Failed to get session key")?;
+        if let (Some(user_id), Some(session_key)) = (user_id, session_key) {
+            self.index()?
+                .remove(user_id, &session_key)
+                .await
+                .context("Failed to remove session from the index")?;
+        }
+        self.0.purge();
+
+        Ok(())
     }
 }
 
+/// Session keys and CSRF tokens are never predicted by tests, so they always
+/// come from the CSPRNG.
+fn generate_session_key() -> String {
+    CsprngTokenGenerator.alphanumeric(30)
+}
+
 impl FromRequest for TypedSession {
     type Error = <Session as FromRequest>::Error;
     type Future = Ready<Result<TypedSession, Self::Error>>;
@@ -45,6 +514,8 @@ impl FromRequest for TypedSession {
         req: &actix_web::HttpRequest,
         _payload: &mut actix_web::dev::Payload,
     ) -> Self::Future {
-        ready(Ok(TypedSession(req.get_session())))
+        let index = req.app_data::<web::Data<SessionIndex>>().cloned();
+
+        ready(Ok(TypedSession(req.get_session(), index)))
     }
 }
@@ -0,0 +1,223 @@
+use std::net::IpAddr;
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, HttpRequest, HttpResponse,
+};
+use anyhow::Context;
+use ipnet::IpNet;
+
+use crate::{configuration::SourceAllowListSettings, util::e500};
+
+const X_FORWARDED_FOR: &str = "X-Forwarded-For";
+
+/// Part of the admin surface a source may be allowed to reach.
+#[derive(Clone, Copy, Debug)]
+enum Surface {
+    Admin,
+    Internal,
+}
+
+/// Restricts the admin UI and the internal endpoints to the configured
+/// networks.
+#[derive(Clone, Debug)]
+pub struct SourceAllowList {
+    admin: Option<Vec<IpNet>>,
+    internal: Option<Vec<IpNet>>,
+    trusted_proxies: Vec<IpNet>,
+}
+
+impl SourceAllowList {
+    pub fn new(settings: SourceAllowListSettings) -> Self {
+        Self {
+            admin: settings.admin,
+            internal: settings.internal,
+            trusted_proxies: settings.trusted_proxies,
+        }
+    }
+
+    fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(&ip))
+    }
+
+    /// The address of the client, as seen by the closest proxy we don't
+    /// trust.
+    ///
+    /// Proxies append the address they got the request from to
+    /// `X-Forwarded-For`, so it's walked from the right while the hops are
+    /// trusted. Anything left of the first untrusted hop could have been
+    /// written by the client itself.
+    fn client_ip(&self, peer: Option<IpAddr>, forwarded_for: &[&str]) -> Option<IpAddr> {
+        let mut client = peer?;
+        let mut hops = forwarded_for
+            .iter()
+            .flat_map(|header| header.split(','))
+            .map(str::trim)
+            .rev();
+
+        while self.is_trusted_proxy(client) {
+            let Some(hop) = hops.next() else {
+                break;
+            };
+            match hop.parse() {
+                Ok(ip) => client = ip,
+                // Garbage sent by an untrusted hop, the last trusted proxy is
+                // the closest we know of.
+                Err(_) => break,
+            }
+        }
+
+        Some(client)
+    }
+
+    /// The address of the client that sent the request, behind the trusted
+    /// proxies.
+    pub fn client_ip_of(&self, request: &HttpRequest) -> Option<IpAddr> {
+        let forwarded_for: Vec<&str> = request
+            .headers()
+            .get_all(X_FORWARDED_FOR)
+            .filter_map(|value| value.to_str().ok())
+            .collect();
+        let peer = request.peer_addr().map(|addr| addr.ip());
+
+        self.client_ip(peer, &forwarded_for)
+    }
+
+    fn allows(&self, surface: Surface, client: Option<IpAddr>) -> bool {
+        let allowed = match surface {
+            Surface::Admin => &self.admin,
+            Surface::Internal => &self.internal,
+        };
+
+        match (allowed, client) {
+            (None, _) => true,
+            (Some(allowed), Some(client)) => allowed.iter().any(|net| net.contains(&client)),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+async fn reject_disallowed_sources(
+    surface: Surface,
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let allow_list = req
+        .app_data::<web::Data<SourceAllowList>>()
+        .context("Source allow list is not registered in the application")
+        .map_err(e500)?;
+
+    let client = allow_list.client_ip_of(req.request());
+
+    if !allow_list.allows(surface, client) {
+        tracing::warn!(
+            client = ?client,
+            surface = ?surface,
+            path = %req.path(),
+            "Rejected a request from a source outside of the allow list"
+        );
+
+        return Ok(req.into_response(HttpResponse::NotFound().finish()));
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_boxed_body)
+}
+
+/// Answers the admin UI with a 404 to sources outside of its allow list.
+pub async fn reject_disallowed_admin_sources(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    reject_disallowed_sources(Surface::Admin, req, next).await
+}
+
+/// Answers the internal endpoints with a 404 to sources outside of their
+/// allow list.
+pub async fn reject_disallowed_internal_sources(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    reject_disallowed_sources(Surface::Internal, req, next).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use crate::configuration::SourceAllowListSettings;
+
+    use super::{SourceAllowList, Surface};
+
+    fn allow_list(admin: Option<&[&str]>, trusted_proxies: &[&str]) -> SourceAllowList {
+        let nets = |nets: &[&str]| nets.iter().map(|net| net.parse().unwrap()).collect();
+
+        SourceAllowList::new(SourceAllowListSettings {
+            admin: admin.map(nets),
+            internal: None,
+            trusted_proxies: nets(trusted_proxies),
+        })
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_from_untrusted_peers() {
+        let allow_list = allow_list(None, &["10.0.0.0/8"]);
+
+        let client = allow_list.client_ip(ip("203.0.113.7"), &["192.168.1.1"]);
+
+        assert_eq!(client, ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn forwarded_for_is_walked_through_trusted_proxies() {
+        let allow_list = allow_list(None, &["10.0.0.0/8"]);
+
+        let client = allow_list.client_ip(ip("10.0.0.2"), &["192.168.1.1, 203.0.113.7, 10.0.0.1"]);
+
+        assert_eq!(client, ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn repeated_forwarded_for_headers_are_joined() {
+        let allow_list = allow_list(None, &["10.0.0.0/8"]);
+
+        let client = allow_list.client_ip(ip("10.0.0.2"), &["203.0.113.7", "10.0.0.1"]);
+
+        assert_eq!(client, ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn the_last_trusted_proxy_is_kept_when_a_hop_is_garbage() {
+        let allow_list = allow_list(None, &["10.0.0.0/8"]);
+
+        let client = allow_list.client_ip(ip("10.0.0.2"), &["not-an-ip"]);
+
+        assert_eq!(client, ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn a_missing_list_allows_any_source() {
+        let allow_list = allow_list(None, &[]);
+
+        assert!(allow_list.allows(Surface::Admin, ip("203.0.113.7")));
+        assert!(allow_list.allows(Surface::Admin, None));
+    }
+
+    #[test]
+    fn only_sources_in_the_list_are_allowed() {
+        let allow_list = allow_list(Some(&["10.0.0.0/8", "::1/128"]), &[]);
+
+        assert!(allow_list.allows(Surface::Admin, ip("10.1.2.3")));
+        assert!(allow_list.allows(Surface::Admin, ip("::1")));
+        assert!(!allow_list.allows(Surface::Admin, ip("203.0.113.7")));
+        assert!(!allow_list.allows(Surface::Admin, None));
+        assert!(allow_list.allows(Surface::Internal, ip("203.0.113.7")));
+    }
+}
@@ -1,137 +1,760 @@
-use std::net::TcpListener;
+use std::{net::TcpListener, sync::Arc};
 
-use actix_session::{storage::RedisSessionStore, SessionMiddleware};
-use actix_web::{cookie::Key, dev::Server, middleware::from_fn, web, App, HttpServer};
+use actix_files::Files;
+use actix_session::SessionMiddleware;
+use actix_web::{
+    dev::Server,
+    guard,
+    http::header::{CacheControl, CacheDirective},
+    middleware::{from_fn, DefaultHeaders},
+    web, App, HttpServer,
+};
 use actix_web_flash_messages::{storage::CookieMessageStore, FlashMessagesFramework};
-use secrecy::{ExposeSecret, Secret};
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::PgPool;
 use tracing_actix_web::TracingLogger;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 use crate::{
-    authentication::reject_anonymous_users,
-    configuration::{DatabaseSettings, Settings},
+    authentication::{
+        ensure_initial_admin, reject_anonymous_users, reject_expired_passwords,
+        reject_invalid_csrf_tokens, reject_unauthenticated_admin_api_clients,
+        reject_unauthenticated_api_clients, LoginAlerts, PasswordPeppers,
+    },
+    base_url_check::{BaseUrlCheck, PROBE_PATH},
+    blob_store::{build_blob_store, BlobUrlSigner},
+    configuration::{
+        ApplicationSettings, BlobStoreSettings, DatabaseSettings, EmailWebhookSettings,
+        PublicStatsSettings, ReferralSettings, SessionBackend, Settings, TrackingSettings,
+    },
+    confirmation_tokens::ConfirmationSigner,
+    cookie_keys::{migrate_rotated_cookies, CookieKeys},
+    css_inliner::CssInliner,
+    delegated_access::{reject_invalid_capability_tokens, CapabilitySigner},
+    domain::SignupField,
+    dynamic_settings::SettingsReloader,
     email_client::EmailClient,
+    error_pages::error_handlers,
+    feature_flags::{
+        reject_disabled_api, reject_disabled_public_archive, reject_disabled_public_stats,
+        reject_disabled_tracking, reject_disabled_webhooks, FeatureFlags,
+    },
+    link_validator::{LinkValidator, SafeBrowsingClient},
+    maintenance_mode::{reject_during_maintenance, MaintenanceMode},
+    mx_check::{DnsMxResolver, MxCheck},
+    openapi::{ApiDoc, OPENAPI_PATH},
+    payload_limits::{form_config, json_config, multipart_form_config},
+    public_stats::PublicStats,
+    referrals::ReferralLinks,
+    request_id::assign_request_id,
     routes::{
-        admin_dashboard, change_password, change_password_form, confirm, health_check, home,
-        invite_collaborator, log_out, login, login_form, publish_newsletter, register_collaborator,
-        register_collaborator_form, subscribe,
+        access_link_log_page, add_topic_subscriber, admin_commands, api_approve_action,
+        api_change_password, api_delete_subscribers, api_import_subscribers,
+        api_invite_collaborator, api_pending_actions, api_publish_newsletter, api_reject_action,
+        api_sync_suppressions, approve_action, backfill_webhook, base_url_probe,
+        change_delivery_frequency, change_password, config_report, confirm, confirm_email_change,
+        create_access_link, create_api_token, create_list, create_segment, create_subscriber,
+        create_topic, create_webhook, delete_image, delete_segment, delete_subscriber,
+        delete_topic, delete_webhook, dismiss_duplicate_subscribers, download_blob,
+        erase_subscriber_data, erase_subscription, export_newsletter, export_subscriber_data,
+        get_dynamic_settings, get_image, get_log_level, get_public_stats, get_segment, get_stats,
+        get_subscriber, get_subscriber_timeline, get_table_maintenance_runs, get_topic,
+        health_check, home, import_subscribers, invite_collaborator, issue_report,
+        issue_stats_page, issues_feed, list_lists, list_segments, list_subscriber_tags,
+        list_subscribers, list_topics, list_webhook_deliveries, list_webhooks, log_out, login,
+        login_form, merge_duplicate_subscribers, newsletter_stats_page, preferences_form,
+        preferences_link_form, preview_import, preview_newsletter, preview_segment,
+        preview_segment_rules, publish_newsletter, publish_newsletter_upload,
+        receive_email_webhook, register_collaborator, register_collaborator_form, register_webhook,
+        reject_action, reload_dynamic_settings, remove_topic_subscriber, request_email_change,
+        request_preferences_link, request_subscribers_deletion, resend_to_failed,
+        revoke_access_link, revoke_all_sessions, revoke_api_token, run_table_maintenance_now,
+        save_invitation_template, set_growth_goal, set_log_level, set_report_subscription,
+        shared_analytics_page, shared_issue_stats_page, signup_form, static_file_not_found,
+        subscribe, subscribe_to_list, subscriber_page, tag_subscriber, track_click, track_open,
+        unregister_webhook, untag_subscriber, update_profile, update_segment, update_subscriber,
+        update_topic, upload_image, ADMIN_PAGES,
+    },
+    session_state::SessionStorage,
+    source_allow_list::{
+        reject_disallowed_admin_sources, reject_disallowed_internal_sources, SourceAllowList,
     },
+    telemetry::{QuietRoutes, SampledRootSpanBuilder},
+    token_generator::{CsprngTokenGenerator, TokenGenerator},
+    tracking::{EmailEventRecorder, EmailTracker},
 };
 
+/// Names the instance that served a response, to debug sticky sessions
+/// behind a load balancer.
+pub const INSTANCE_ID_HEADER: &str = "X-Instance-Id";
+
 pub struct ApplicationBaseUrl(pub String);
 
+/// Whether subscribers confirm their address before being subscribed.
+pub struct RequireConfirmation(pub bool);
+
 #[derive(Clone)]
 pub struct HmacSecret(pub Secret<String>);
 
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     listener: TcpListener,
     db_pool: PgPool,
     email_client: EmailClient,
-    base_url: String,
-    hmac_secret: Secret<String>,
+    link_validator: LinkValidator,
+    base_url_check: BaseUrlCheck,
+    mx_check: MxCheck,
+    login_alerts: LoginAlerts,
+    maintenance_mode: MaintenanceMode,
+    blob_store: BlobStoreSettings,
+    public_stats: PublicStatsSettings,
+    tracking: TrackingSettings,
+    referrals: ReferralSettings,
+    email_webhooks: EmailWebhookSettings,
+    application: ApplicationSettings,
+    features: FeatureFlags,
+    session_backend: SessionBackend,
     redis_uri: Secret<String>,
+    token_generator: Arc<dyn TokenGenerator>,
+    settings_reloader: SettingsReloader,
 ) -> Result<Server, anyhow::Error> {
-    let secret_key = Key::try_from(hmac_secret.expose_secret().as_bytes())?;
+    let ApplicationSettings {
+        base_url,
+        hmac_secret,
+        previous_hmac_secrets,
+        two_person_rule,
+        password_policy,
+        cors,
+        inline_css,
+        tls,
+        quiet_routes,
+        admin_base_path,
+        password_pepper,
+        source_allow_list,
+        instance_id,
+        static_files,
+        signup_page,
+        payload_limits,
+        require_confirmation,
+        confirmation_tokens,
+        ..
+    } = application;
+    let tls_config = tls.map(|tls| tls.server_config()).transpose()?;
+    let cookie_keys = CookieKeys::new(&hmac_secret, &previous_hmac_secrets)?;
+    let secret_key = cookie_keys.current().clone();
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
-    let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    let (session_storage, session_index) =
+        SessionStorage::build(session_backend, &redis_uri).await?;
+    let session_index = web::Data::new(session_index);
 
     let db_pool = web::Data::new(db_pool);
     let email_client = web::Data::new(email_client);
+    let link_validator = web::Data::new(link_validator);
+    let base_url_check = web::Data::new(base_url_check);
+    let mx_check = web::Data::new(mx_check);
+    let login_alerts = web::Data::new(login_alerts);
+    let maintenance_mode = web::Data::new(maintenance_mode);
+    let blob_url_signer = web::Data::new(BlobUrlSigner::new(&base_url, &hmac_secret));
+    let blob_store_settings = web::Data::new(blob_store.clone());
+    let blob_store = web::Data::from(build_blob_store(&blob_store, &base_url, &hmac_secret)?);
+    let public_stats = web::Data::new(PublicStats::new(&public_stats));
+    let email_webhooks = web::Data::new(email_webhooks);
+    let css_inliner = web::Data::new(CssInliner::new(inline_css));
+    let quiet_routes = web::Data::new(QuietRoutes::new(quiet_routes));
+    let admin_base_path = web::Data::new(admin_base_path);
+    let source_allow_list = web::Data::new(SourceAllowList::new(source_allow_list));
+    let email_tracker = web::Data::new(EmailTracker::new(
+        features.tracking,
+        &base_url,
+        &hmac_secret,
+    ));
+    let email_event_recorder = web::Data::new(EmailEventRecorder::spawn(
+        db_pool.get_ref().clone(),
+        &tracking,
+    ));
+    let capability_signer = web::Data::new(CapabilitySigner::new(&hmac_secret));
+    let confirmation_signer =
+        web::Data::new(ConfirmationSigner::new(&hmac_secret, &confirmation_tokens));
+    let referral_links = web::Data::new(ReferralLinks::new(features.referrals, &base_url));
+    let referrals = web::Data::new(referrals);
+    let signup_page = web::Data::new(signup_page);
+    let require_confirmation = web::Data::new(RequireConfirmation(require_confirmation));
+    let features = web::Data::new(features);
     let base_url = web::Data::new(ApplicationBaseUrl(base_url));
     let hmac_secret = web::Data::new(HmacSecret(hmac_secret.clone()));
+    let cookie_keys = web::Data::new(cookie_keys);
+    let two_person_rule = web::Data::new(two_person_rule);
+    let password_policy = web::Data::new(password_policy);
+    let password_peppers = web::Data::new(PasswordPeppers::new(password_pepper)?);
+    let token_generator = web::Data::from(token_generator);
+    let dynamic_settings = web::Data::from(settings_reloader.settings());
+    let settings_reloader = web::Data::new(settings_reloader);
+    let payload_limits = web::Data::new(payload_limits);
+    let openapi = ApiDoc::openapi();
 
     let server = HttpServer::new(move || {
         App::new()
-            .wrap(TracingLogger::default())
+            .wrap(error_handlers())
+            .wrap(DefaultHeaders::new().add((INSTANCE_ID_HEADER, instance_id.as_str())))
+            .wrap(cors.cors())
+            .wrap(TracingLogger::<SampledRootSpanBuilder>::new())
+            .wrap(from_fn(assign_request_id))
             .wrap(message_framework.clone())
             .wrap(SessionMiddleware::new(
-                redis_store.clone(),
+                session_storage.clone(),
                 secret_key.clone(),
             ))
+            .wrap(from_fn(migrate_rotated_cookies))
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
+            .app_data(link_validator.clone())
+            .app_data(base_url_check.clone())
+            .app_data(mx_check.clone())
+            .app_data(login_alerts.clone())
+            .app_data(maintenance_mode.clone())
+            .app_data(blob_url_signer.clone())
+            .app_data(blob_store_settings.clone())
+            .app_data(blob_store.clone())
+            .app_data(public_stats.clone())
+            .app_data(email_webhooks.clone())
+            .app_data(css_inliner.clone())
+            .app_data(quiet_routes.clone())
+            .app_data(admin_base_path.clone())
+            .app_data(source_allow_list.clone())
+            .app_data(email_tracker.clone())
+            .app_data(email_event_recorder.clone())
+            .app_data(capability_signer.clone())
+            .app_data(referral_links.clone())
+            .app_data(referrals.clone())
+            .app_data(signup_page.clone())
+            .app_data(require_confirmation.clone())
+            .app_data(confirmation_signer.clone())
+            .app_data(features.clone())
             .app_data(base_url.clone())
             .app_data(hmac_secret.clone())
+            .app_data(cookie_keys.clone())
+            .app_data(session_index.clone())
+            .app_data(two_person_rule.clone())
+            .app_data(password_policy.clone())
+            .app_data(password_peppers.clone())
+            .app_data(token_generator.clone())
+            .app_data(dynamic_settings.clone())
+            .app_data(settings_reloader.clone())
+            .app_data(payload_limits.clone())
+            .app_data(form_config(payload_limits.default_bytes))
+            .app_data(json_config(payload_limits.default_bytes))
             .route("/", web::get().to(home))
-            .route("/login", web::get().to(login_form))
-            .route("/login", web::post().to(login))
+            .route("/subscribe", web::get().to(signup_form))
+            .service(
+                web::resource("/login")
+                    .app_data(form_config(payload_limits.public_form_bytes))
+                    .wrap(from_fn(reject_invalid_csrf_tokens))
+                    .route(web::get().to(login_form))
+                    .route(web::post().to(login)),
+            )
             .route("/health_check", web::get().to(health_check))
-            .route("/subscriptions", web::post().to(subscribe))
-            .route("/subscriptions/confirm", web::get().to(confirm))
-            .route("/newsletters", web::post().to(publish_newsletter))
             .service(
-                web::scope("/admin")
+                web::scope("/static")
+                    .wrap(DefaultHeaders::new().add(CacheControl(vec![
+                        CacheDirective::Public,
+                        CacheDirective::MaxAge(static_files.max_age_seconds),
+                    ])))
+                    .service(
+                        Files::new("", &static_files.directory)
+                            .default_handler(web::to(static_file_not_found)),
+                    ),
+            )
+            .service(
+                web::resource("/health/config")
+                    .wrap(from_fn(reject_unauthenticated_admin_api_clients))
+                    .wrap(from_fn(reject_disallowed_internal_sources))
+                    .route(web::get().to(config_report)),
+            )
+            .route(PROBE_PATH, web::get().to(base_url_probe))
+            .service(SwaggerUi::new("/api/docs/{_:.*}").url(OPENAPI_PATH, openapi.clone()))
+            .service(
+                web::resource("/subscriptions")
+                    .app_data(form_config(payload_limits.public_form_bytes))
+                    .wrap(from_fn(reject_during_maintenance))
+                    .route(web::post().to(subscribe)),
+            )
+            .service(
+                web::resource("/subscriptions/confirm")
+                    .wrap(from_fn(reject_during_maintenance))
+                    .route(web::get().to(confirm)),
+            )
+            .service(
+                web::resource("/subscriptions/erase")
+                    .wrap(from_fn(reject_during_maintenance))
+                    .route(web::get().to(erase_subscription)),
+            )
+            .service(
+                web::resource("/lists/{list_id}/subscriptions")
+                    .app_data(form_config(payload_limits.public_form_bytes))
+                    .wrap(from_fn(reject_during_maintenance))
+                    .route(web::post().to(subscribe_to_list)),
+            )
+            .service(
+                web::resource("/preferences")
+                    .wrap(from_fn(reject_during_maintenance))
+                    .route(web::get().to(preferences_form)),
+            )
+            .service(
+                web::resource("/preferences/link")
+                    .wrap(from_fn(reject_during_maintenance))
+                    .route(web::get().to(preferences_link_form))
+                    .route(web::post().to(request_preferences_link)),
+            )
+            .service(
+                web::resource("/preferences/frequency")
+                    .wrap(from_fn(reject_during_maintenance))
+                    .route(web::post().to(change_delivery_frequency)),
+            )
+            .service(
+                web::resource("/preferences/email")
+                    .wrap(from_fn(reject_during_maintenance))
+                    .route(web::post().to(request_email_change)),
+            )
+            .service(
+                web::resource("/preferences/email/confirm")
+                    .wrap(from_fn(reject_during_maintenance))
+                    .route(web::get().to(confirm_email_change)),
+            )
+            .service(
+                web::resource("/feed.xml")
+                    .wrap(from_fn(reject_disabled_public_archive))
+                    .route(web::get().to(issues_feed)),
+            )
+            .service(
+                web::resource("/stats")
+                    .wrap(from_fn(reject_disabled_public_stats))
+                    .route(web::get().to(get_public_stats)),
+            )
+            .service(
+                web::scope("/t")
+                    .wrap(from_fn(reject_disabled_tracking))
+                    .route("/open/{token}", web::get().to(track_open))
+                    .route("/click/{token}", web::get().to(track_click)),
+            )
+            .service(
+                web::scope("/shared/{token}")
+                    .wrap(from_fn(reject_invalid_capability_tokens))
+                    .route("", web::get().to(shared_analytics_page))
+                    .route(
+                        "/issues/{newsletter_issue_id}/stats",
+                        web::get().to(shared_issue_stats_page),
+                    ),
+            )
+            .route("/webhooks/email", web::post().to(receive_email_webhook))
+            .route("/images/{name}", web::get().to(get_image))
+            .route("/blobs/{key:.*}", web::get().to(download_blob))
+            .service(
+                web::resource("/newsletters")
+                    .app_data(json_config(payload_limits.publish_bytes))
+                    .route(web::post().to(publish_newsletter)),
+            )
+            .service(
+                web::resource("/newsletters/preview")
+                    .app_data(json_config(payload_limits.publish_bytes))
+                    .route(web::post().to(preview_newsletter)),
+            )
+            .service(
+                ADMIN_PAGES
+                    .iter()
+                    .fold(
+                        web::scope(admin_base_path.get_ref().as_ref()),
+                        |scope, page| scope.route(page.path, page.route()),
+                    )
+                    .wrap(from_fn(reject_invalid_csrf_tokens))
+                    .wrap(from_fn(reject_expired_passwords))
                     .wrap(from_fn(reject_anonymous_users))
-                    .route("/dashboard", web::get().to(admin_dashboard))
-                    .route("/password", web::get().to(change_password_form))
+                    .wrap(from_fn(reject_disallowed_admin_sources))
+                    .route("/commands", web::get().to(admin_commands))
+                    .service(
+                        web::resource("/newsletters")
+                            .guard(guard::Post())
+                            .app_data(multipart_form_config(payload_limits.publish_bytes))
+                            .route(web::post().to(publish_newsletter_upload)),
+                    )
                     .route("/password", web::post().to(change_password))
+                    .route("/profile", web::post().to(update_profile))
                     .route("/logout", web::post().to(log_out))
-                    .route("/collaborator", web::post().to(invite_collaborator)),
+                    .route("/sessions/revoke_all", web::post().to(revoke_all_sessions))
+                    .route("/collaborator", web::post().to(invite_collaborator))
+                    .route("/access_links", web::post().to(create_access_link))
+                    .route("/api_tokens", web::post().to(create_api_token))
+                    .route(
+                        "/api_tokens/{token_id}/revoke",
+                        web::post().to(revoke_api_token),
+                    )
+                    .route(
+                        "/access_links/{grant_id}",
+                        web::get().to(access_link_log_page),
+                    )
+                    .route(
+                        "/access_links/{grant_id}/revoke",
+                        web::post().to(revoke_access_link),
+                    )
+                    .route(
+                        "/subscribers/delete",
+                        web::post().to(request_subscribers_deletion),
+                    )
+                    .route("/subscribers/import", web::post().to(import_subscribers))
+                    .route(
+                        "/subscribers/duplicates/merge",
+                        web::post().to(merge_duplicate_subscribers),
+                    )
+                    .route(
+                        "/subscribers/duplicates/dismiss",
+                        web::post().to(dismiss_duplicate_subscribers),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::get().to(subscriber_page),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/export",
+                        web::get().to(export_subscriber_data),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/erase",
+                        web::post().to(erase_subscriber_data),
+                    )
+                    .route(
+                        "/issues/{newsletter_issue_id}/stats",
+                        web::get().to(issue_stats_page),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/stats",
+                        web::get().to(newsletter_stats_page),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/export",
+                        web::get().to(export_newsletter),
+                    )
+                    .route(
+                        "/subscribers/import/preview",
+                        web::post().to(preview_import),
+                    )
+                    .route(
+                        "/actions/{action_id}/approve",
+                        web::post().to(approve_action),
+                    )
+                    .route("/actions/{action_id}/reject", web::post().to(reject_action))
+                    .route("/webhooks", web::post().to(create_webhook))
+                    .route(
+                        "/webhooks/{webhook_id}/delete",
+                        web::post().to(unregister_webhook),
+                    )
+                    .route(
+                        "/templates/invitation",
+                        web::post().to(save_invitation_template),
+                    ),
+            )
+            .service(
+                web::scope("/api/v1/admin")
+                    .wrap(from_fn(reject_unauthenticated_admin_api_clients))
+                    .wrap(from_fn(reject_disabled_api))
+                    .wrap(from_fn(reject_disallowed_internal_sources))
+                    .service(
+                        web::resource("/newsletters")
+                            .app_data(json_config(payload_limits.publish_bytes))
+                            .route(web::post().to(api_publish_newsletter)),
+                    )
+                    .route("/collaborators", web::post().to(api_invite_collaborator))
+                    .route("/password", web::post().to(api_change_password))
+                    .route("/log_level", web::get().to(get_log_level))
+                    .route("/log_level", web::put().to(set_log_level))
+                    .route("/settings", web::get().to(get_dynamic_settings))
+                    .route("/settings/reload", web::post().to(reload_dynamic_settings))
+                    .route("/suppressions/sync", web::post().to(api_sync_suppressions))
+                    .route(
+                        "/maintenance/tables",
+                        web::get().to(get_table_maintenance_runs),
+                    )
+                    .route(
+                        "/maintenance/tables/run",
+                        web::post().to(run_table_maintenance_now),
+                    )
+                    .route("/images", web::post().to(upload_image))
+                    .route("/images/{name}", web::delete().to(delete_image))
+                    .route(
+                        "/issues/{newsletter_issue_id}/report",
+                        web::get().to(issue_report),
+                    )
+                    .route(
+                        "/issues/{newsletter_issue_id}/resend_failed",
+                        web::post().to(resend_to_failed),
+                    )
+                    .route(
+                        "/subscribers/delete",
+                        web::post().to(api_delete_subscribers),
+                    )
+                    .route(
+                        "/subscribers/import",
+                        web::post().to(api_import_subscribers),
+                    )
+                    .route("/stats", web::get().to(get_stats))
+                    .route("/subscribers", web::get().to(list_subscribers))
+                    .route("/subscribers", web::post().to(create_subscriber))
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::get().to(get_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::patch().to(update_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::delete().to(delete_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/tags",
+                        web::get().to(list_subscriber_tags),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/tags/{tag}",
+                        web::put().to(tag_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/tags/{tag}",
+                        web::delete().to(untag_subscriber),
+                    )
+                    .route("/lists/{list_id}/goal", web::put().to(set_growth_goal))
+                    .route("/report", web::put().to(set_report_subscription))
+                    .route("/actions", web::get().to(api_pending_actions))
+                    .route(
+                        "/actions/{action_id}/approve",
+                        web::post().to(api_approve_action),
+                    )
+                    .route(
+                        "/actions/{action_id}/reject",
+                        web::post().to(api_reject_action),
+                    ),
+            )
+            .service(
+                web::scope("/api/v1")
+                    .wrap(from_fn(reject_unauthenticated_api_clients))
+                    .wrap(from_fn(reject_disabled_api))
+                    .route("/lists", web::get().to(list_lists))
+                    .route("/lists", web::post().to(create_list))
+                    .route("/topics", web::get().to(list_topics))
+                    .route("/topics", web::post().to(create_topic))
+                    .route("/topics/{topic_id}", web::get().to(get_topic))
+                    .route("/topics/{topic_id}", web::put().to(update_topic))
+                    .route("/topics/{topic_id}", web::delete().to(delete_topic))
+                    .route(
+                        "/topics/{topic_id}/subscribers",
+                        web::post().to(add_topic_subscriber),
+                    )
+                    .route(
+                        "/topics/{topic_id}/subscribers/{email}",
+                        web::delete().to(remove_topic_subscriber),
+                    )
+                    .route("/segments", web::get().to(list_segments))
+                    .route("/segments", web::post().to(create_segment))
+                    .route("/segments/preview", web::post().to(preview_segment_rules))
+                    .route("/segments/{segment_id}", web::get().to(get_segment))
+                    .route("/segments/{segment_id}", web::put().to(update_segment))
+                    .route("/segments/{segment_id}", web::delete().to(delete_segment))
+                    .route(
+                        "/segments/{segment_id}/preview",
+                        web::get().to(preview_segment),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/events",
+                        web::get().to(get_subscriber_timeline),
+                    )
+                    .service(
+                        web::scope("/webhooks")
+                            .wrap(from_fn(reject_disabled_webhooks))
+                            .route("", web::get().to(list_webhooks))
+                            .route("", web::post().to(register_webhook))
+                            .route("/{webhook_id}", web::delete().to(delete_webhook))
+                            .route("/{webhook_id}/backfill", web::post().to(backfill_webhook))
+                            .route(
+                                "/{webhook_id}/deliveries",
+                                web::get().to(list_webhook_deliveries),
+                            ),
+                    ),
             )
             .route("/collaborator", web::get().to(register_collaborator_form))
-            .route(
-                "/collaborator/register",
-                web::post().to(register_collaborator),
+            .service(
+                web::resource("/collaborator/register")
+                    .wrap(from_fn(reject_invalid_csrf_tokens))
+                    .route(web::post().to(register_collaborator)),
             )
-    })
-    .listen(listener)?
+    });
+    let server = match tls_config {
+        Some(tls_config) => server.listen_rustls_0_23(listener, tls_config)?,
+        None => server.listen(listener)?,
+    }
     .run();
 
     Ok(server)
 }
 
 pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
-    PgPoolOptions::new().connect_lazy_with(configuration.with_db())
+    configuration
+        .pool_options()
+        .connect_lazy_with(configuration.with_db())
+}
+
+/// Runs the migrations not applied to the database yet.
+pub async fn migrate_database(pool: &PgPool) -> Result<(), anyhow::Error> {
+    sqlx::migrate!("./migrations")
+        .run(pool)
+        .await
+        .context("Failed to migrate the database")?;
+
+    Ok(())
 }
 
 pub struct Application {
     port: u16,
     server: Server,
+    maintenance_mode: MaintenanceMode,
+    settings_reloader: SettingsReloader,
 }
 
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
-        let connection_pool =
-            PgPoolOptions::new().connect_lazy_with(configuration.database.with_db());
-        let sender_email = configuration
-            .email_client
-            .sender()
-            .expect("Invalid sender email address.");
-        let base_url = configuration
+        Self::build_with_token_generator(configuration, Arc::new(CsprngTokenGenerator)).await
+    }
+
+    /// Same as [`Application::build`], but emails are sent with the given
+    /// client, e.g. to share its connections with the delivery worker.
+    pub async fn build_with_email_client(
+        configuration: Settings,
+        email_client: EmailClient,
+    ) -> Result<Self, anyhow::Error> {
+        Self::build_with(configuration, email_client, Arc::new(CsprngTokenGenerator)).await
+    }
+
+    /// Same as [`Application::build`], but tokens and codes are drawn from
+    /// the given generator, so that tests can predict them.
+    pub async fn build_with_token_generator(
+        configuration: Settings,
+        token_generator: Arc<dyn TokenGenerator>,
+    ) -> Result<Self, anyhow::Error> {
+        let email_client = configuration
             .email_client
-            .url()
-            .expect("Invalid email base url.");
-        let timeout = configuration.email_client.timeout();
-        let email_client = EmailClient::new(
-            base_url,
-            sender_email,
-            configuration.email_client.authorization_token,
-            timeout,
+            .clone()
+            .client()
+            .with_email_log(get_connection_pool(&configuration.database));
+
+        Self::build_with(configuration, email_client, token_generator).await
+    }
+
+    async fn build_with(
+        configuration: Settings,
+        email_client: EmailClient,
+        token_generator: Arc<dyn TokenGenerator>,
+    ) -> Result<Self, anyhow::Error> {
+        SignupField::check_all(&configuration.application.signup_page.fields)
+            .context("Invalid signup page fields")?;
+        let settings_reloader =
+            SettingsReloader::new(configuration.dynamic.clone(), email_client.clone())?;
+        let connection_pool = get_connection_pool(&configuration.database);
+        let safe_browsing = configuration
+            .link_validation
+            .safe_browsing
+            .map(|settings| {
+                let base_url = settings.url().context("Invalid Safe Browsing base url")?;
+                let timeout = settings.timeout();
+
+                SafeBrowsingClient::new(base_url, settings.api_key, timeout)
+            })
+            .transpose()?;
+        let link_validator = LinkValidator::new(
+            configuration.link_validation.enabled,
+            configuration.link_validation.blocklist,
+            safe_browsing,
+        );
+        let base_url_check = BaseUrlCheck::new(
+            configuration.base_url_check.enabled,
+            configuration
+                .base_url_check
+                .url()
+                .expect("Invalid base url checker url."),
+            &configuration.application.base_url,
+            CsprngTokenGenerator.alphanumeric(32),
+            configuration.base_url_check.timeout(),
+            configuration.base_url_check.cache_ttl(),
+        );
+        let mx_check = MxCheck::new(
+            configuration.features.mx_validation,
+            Box::new(DnsMxResolver::from_system_conf()),
+            configuration.mx_check.timeout(),
+            configuration.mx_check.cache_ttl(),
+        );
+        let login_alerts = LoginAlerts::new(
+            configuration
+                .login_alerts
+                .url()
+                .expect("Invalid login alerts webhook url."),
+            configuration.login_alerts.timeout(),
         );
+        let maintenance_mode = MaintenanceMode::new(&configuration.maintenance_mode);
+        if configuration.application.migrate_on_startup {
+            migrate_database(&connection_pool).await?;
+        }
+        if let Some(initial_admin) = &configuration.application.initial_admin {
+            let peppers = PasswordPeppers::new(configuration.application.password_pepper.clone())?;
+            ensure_initial_admin(&connection_pool, initial_admin, &peppers).await?;
+        }
         let listener = TcpListener::bind(configuration.application.address())?;
         let port = listener.local_addr().unwrap().port();
-        let base_url = configuration.application.base_url;
-        let hmac_secret = configuration.application.hmac_secret;
-        let redis_uri = configuration.redis_uri;
 
         let server = run(
             listener,
             connection_pool,
             email_client,
-            base_url,
-            hmac_secret,
-            redis_uri,
+            link_validator,
+            base_url_check,
+            mx_check,
+            login_alerts,
+            maintenance_mode.clone(),
+            configuration.blob_store,
+            configuration.public_stats,
+            configuration.tracking,
+            configuration.referrals,
+            configuration.email_webhooks,
+            configuration.application,
+            configuration.features,
+            configuration.session_backend,
+            configuration.redis_uri,
+            token_generator,
+            settings_reloader.clone(),
         )
         .await?;
 
-        Ok(Self { port, server })
+        Ok(Self {
+            port,
+            server,
+            maintenance_mode,
+            settings_reloader,
+        })
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
 
+    /// Maintenance state of the application, to share with the workers.
+    pub fn maintenance_mode(&self) -> MaintenanceMode {
+        self.maintenance_mode.clone()
+    }
+
+    /// Reloads the dynamic settings of the running application.
+    pub fn settings_reloader(&self) -> SettingsReloader {
+        self.settings_reloader.clone()
+    }
+
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
         self.server.await
     }
@@ -0,0 +1,146 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::SubscriptionStatus;
+
+/// How many days of signups the dashboard sparkline spans.
+pub const RECENT_SIGNUP_DAYS: i32 = 30;
+
+const SPARKLINE_WIDTH: f64 = 120.0;
+const SPARKLINE_HEIGHT: f64 = 24.0;
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct LastIssue {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub published_at: DateTime<Utc>,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct DailySignups {
+    pub day: DateTime<Utc>,
+    pub signups: i64,
+}
+
+/// Figures shown on the admin dashboard.
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct DashboardStats {
+    /// Distinct addresses, someone subscribed to several lists counts once.
+    pub confirmed_subscribers: i64,
+    pub pending_confirmations: i64,
+    pub last_issue: Option<LastIssue>,
+    /// One entry per day, oldest first, days without signups included.
+    pub recent_signups: Vec<DailySignups>,
+    /// Points of an SVG polyline drawing `recent_signups`.
+    pub sparkline: String,
+}
+
+#[tracing::instrument(name = "Compute dashboard stats", skip(pool))]
+pub async fn dashboard_stats(pool: &PgPool) -> Result<DashboardStats, sqlx::Error> {
+    let counts = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT count(DISTINCT email) FROM subscriptions WHERE status = $1) AS "confirmed!",
+            (SELECT count(*) FROM subscriptions WHERE status = $2) AS "pending!"
+        "#,
+        SubscriptionStatus::Confirmed as SubscriptionStatus,
+        SubscriptionStatus::PendingConfirmation as SubscriptionStatus,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let last_issue = sqlx::query_as!(
+        LastIssue,
+        r#"
+        SELECT newsletter_issue_id, title, published_at
+        FROM newsletter_issues
+        ORDER BY published_at DESC
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let recent_signups = sqlx::query_as!(
+        DailySignups,
+        r#"
+        SELECT days.day AS "day!", count(s.id) AS "signups!"
+        FROM generate_series(
+            date_trunc('day', now()) - make_interval(days => $1 - 1),
+            date_trunc('day', now()),
+            interval '1 day'
+        ) AS days (day)
+        LEFT JOIN subscriptions s
+            ON s.subscribed_at >= days.day AND s.subscribed_at < days.day + interval '1 day'
+        GROUP BY days.day
+        ORDER BY days.day
+        "#,
+        RECENT_SIGNUP_DAYS,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let sparkline = sparkline_points(
+        &recent_signups.iter().map(|d| d.signups).collect::<Vec<_>>(),
+        SPARKLINE_WIDTH,
+        SPARKLINE_HEIGHT,
+    );
+
+    Ok(DashboardStats {
+        confirmed_subscribers: counts.confirmed,
+        pending_confirmations: counts.pending,
+        last_issue,
+        recent_signups,
+        sparkline,
+    })
+}
+
+/// Points spreading `values` over `width`, the highest one at the top of
+/// `height`. A flat series lies on the bottom edge.
+fn sparkline_points(values: &[i64], width: f64, height: f64) -> String {
+    let max = values.iter().copied().max().unwrap_or(0).max(1) as f64;
+    let step = if values.len() > 1 {
+        width / (values.len() - 1) as f64
+    } else {
+        0.0
+    };
+
+    let mut points = String::new();
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            points.push(' ');
+        }
+        let x = step * i as f64;
+        let y = height - *value as f64 / max * height;
+        write!(points, "{:.1},{:.1}", x, y).unwrap();
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sparkline_points;
+
+    #[test]
+    fn the_highest_value_reaches_the_top() {
+        let points = sparkline_points(&[0, 2, 4], 10.0, 8.0);
+
+        assert_eq!(points, "0.0,8.0 5.0,4.0 10.0,0.0");
+    }
+
+    #[test]
+    fn a_series_without_signups_lies_on_the_bottom() {
+        let points = sparkline_points(&[0, 0], 10.0, 8.0);
+
+        assert_eq!(points, "0.0,8.0 10.0,8.0");
+    }
+
+    #[test]
+    fn no_values_draw_nothing() {
+        assert_eq!(sparkline_points(&[], 10.0, 8.0), "");
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Name the collaborator invitation is stored under.
+pub const INVITATION_TEMPLATE: &str = "invitation";
+
+/// A version of an email edited from the admin UI. Its bodies are Tera
+/// templates, rendered like the ones on disk.
+#[derive(Debug, serde::Serialize)]
+pub struct StoredTemplate {
+    pub version: i32,
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+    pub created_at: DateTime<Utc>,
+    /// Username of whoever saved it, unless they were removed since.
+    pub created_by: Option<String>,
+}
+
+/// What saving an email adds as its next version.
+#[derive(Debug)]
+pub struct NewTemplateVersion<'a> {
+    pub subject: &'a str,
+    pub html_body: &'a str,
+    pub text_body: &'a str,
+}
+
+/// The given version of an email, its latest one if `None`.
+#[tracing::instrument(name = "Get stored template", skip(pool))]
+pub async fn get_template(
+    pool: &PgPool,
+    name: &str,
+    version: Option<i32>,
+) -> Result<Option<StoredTemplate>, sqlx::Error> {
+    sqlx::query_as!(
+        StoredTemplate,
+        r#"
+        SELECT t.version, t.subject, t.html_body, t.text_body, t.created_at,
+            u.username AS "created_by?"
+        FROM templates t
+        LEFT JOIN users u ON u.user_id = t.created_by
+        WHERE t.name = $1 AND ($2::int IS NULL OR t.version = $2)
+        ORDER BY t.version DESC
+        LIMIT 1
+        "#,
+        name,
+        version,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Every version of an email, the latest first.
+#[tracing::instrument(name = "Get stored template history", skip(pool))]
+pub async fn get_template_history(
+    pool: &PgPool,
+    name: &str,
+) -> Result<Vec<StoredTemplate>, sqlx::Error> {
+    sqlx::query_as!(
+        StoredTemplate,
+        r#"
+        SELECT t.version, t.subject, t.html_body, t.text_body, t.created_at,
+            u.username AS "created_by?"
+        FROM templates t
+        LEFT JOIN users u ON u.user_id = t.created_by
+        WHERE t.name = $1
+        ORDER BY t.version DESC
+        "#,
+        name,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Saves the email as its next version, which is returned. Earlier versions
+/// are kept.
+#[tracing::instrument(name = "Store template version", skip(pool, template))]
+pub async fn store_template_version(
+    pool: &PgPool,
+    name: &str,
+    template: &NewTemplateVersion<'_>,
+    created_by: Uuid,
+) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO templates (
+            name, version, subject, html_body, text_body, created_by, created_at
+        )
+        SELECT $1, COALESCE(MAX(version), 0) + 1, $2, $3, $4, $5, now()
+        FROM templates
+        WHERE name = $1
+        RETURNING version
+        "#,
+        name,
+        template.subject,
+        template.html_body,
+        template.text_body,
+        created_by,
+    )
+    .fetch_one(pool)
+    .await
+}
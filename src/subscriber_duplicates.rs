@@ -0,0 +1,436 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    domain::SubscriptionStatus,
+    subscriber_events::{record_events, SubscriberEventKind},
+};
+
+/// Why two subscribers of a list look like the same person.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicateReason {
+    /// The addresses only differ by their case or surrounding spaces.
+    SameEmail,
+    /// The addresses only differ by a `+tag` in their local part.
+    PlusAlias,
+    /// Same name, and addresses close enough to be a typo.
+    SameNameSimilarEmail,
+}
+
+impl DuplicateReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DuplicateReason::SameEmail => "same_email",
+            DuplicateReason::PlusAlias => "plus_alias",
+            DuplicateReason::SameNameSimilarEmail => "same_name_similar_email",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            DuplicateReason::SameEmail => "Same email",
+            DuplicateReason::PlusAlias => "Same email but for a +alias",
+            DuplicateReason::SameNameSimilarEmail => "Same name and a similar email",
+        }
+    }
+}
+
+impl TryFrom<String> for DuplicateReason {
+    type Error = String;
+
+    fn try_from(reason: String) -> Result<Self, Self::Error> {
+        match reason.as_str() {
+            "same_email" => Ok(DuplicateReason::SameEmail),
+            "plus_alias" => Ok(DuplicateReason::PlusAlias),
+            "same_name_similar_email" => Ok(DuplicateReason::SameNameSimilarEmail),
+            other => Err(format!("{} is not a duplicate reason", other)),
+        }
+    }
+}
+
+/// Two subscribers found to be alike, the lowest id first.
+#[derive(Debug)]
+pub struct DuplicateEvidence {
+    pub first_id: Uuid,
+    pub second_id: Uuid,
+    pub reason: DuplicateReason,
+    /// Trigram similarity of the addresses, from 0 to 1.
+    pub similarity: f32,
+}
+
+#[derive(Debug)]
+pub struct DuplicateCandidate {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: SubscriptionStatus,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+/// Subscribers of a list probably being the same person, along with what
+/// links them.
+#[derive(Debug)]
+pub struct DuplicateGroup {
+    pub members: Vec<DuplicateCandidate>,
+    pub evidence: Vec<DuplicateEvidence>,
+}
+
+impl DuplicateGroup {
+    pub fn member_ids(&self) -> Vec<Uuid> {
+        self.members.iter().map(|m| m.id).collect()
+    }
+}
+
+/// The lowest id of the group of a subscriber, flattening the path to it.
+fn root(parents: &mut BTreeMap<Uuid, Uuid>, id: Uuid) -> Uuid {
+    let parent = *parents.entry(id).or_insert(id);
+    if parent == id {
+        return id;
+    }
+    let root = root(parents, parent);
+    parents.insert(id, root);
+
+    root
+}
+
+/// Joins pairs sharing a subscriber into groups, e.g. `a+news@x` and
+/// `A@x` both end up with `a@x`. Groups are sorted, as well as their ids.
+fn group_pairs(pairs: &[(Uuid, Uuid)]) -> Vec<Vec<Uuid>> {
+    let mut parents: BTreeMap<Uuid, Uuid> = BTreeMap::new();
+    for &(first, second) in pairs {
+        let (first, second) = (root(&mut parents, first), root(&mut parents, second));
+        if first != second {
+            parents.insert(first.max(second), first.min(second));
+        }
+    }
+
+    let ids: Vec<Uuid> = parents.keys().copied().collect();
+    let mut groups: BTreeMap<Uuid, BTreeSet<Uuid>> = BTreeMap::new();
+    for id in ids {
+        let root = root(&mut parents, id);
+        groups.entry(root).or_default().insert(id);
+    }
+
+    groups
+        .into_values()
+        .map(|group| group.into_iter().collect())
+        .collect()
+}
+
+/// Scans every list for probable duplicate subscribers, leaving out the
+/// erased ones and the pairs an admin already dismissed.
+#[tracing::instrument(name = "Find duplicate subscribers", skip(pool))]
+pub async fn find_duplicate_groups(pool: &PgPool) -> Result<Vec<DuplicateGroup>, sqlx::Error> {
+    let evidence: Vec<DuplicateEvidence> = sqlx::query!(
+        r#"
+        WITH candidates AS (
+            SELECT
+                id,
+                list_id,
+                lower(trim(email)) AS email,
+                regexp_replace(lower(trim(email)), '\+[^@]*@', '@') AS unaliased_email,
+                lower(trim(name)) AS name
+            FROM subscriptions
+            WHERE erased_at IS NULL
+        )
+        SELECT
+            a.id AS "first_id!",
+            b.id AS "second_id!",
+            CASE
+                WHEN a.email = b.email THEN 'same_email'
+                WHEN a.unaliased_email = b.unaliased_email THEN 'plus_alias'
+                ELSE 'same_name_similar_email'
+            END AS "reason!",
+            similarity(a.email, b.email) AS "similarity!"
+        FROM candidates a
+        JOIN candidates b ON b.list_id = a.list_id AND a.id < b.id
+        WHERE (
+            a.unaliased_email = b.unaliased_email
+            OR (a.name <> '' AND a.name = b.name AND a.email % b.email)
+        )
+        AND NOT EXISTS (
+            SELECT 1 FROM subscriber_duplicate_dismissals d
+            WHERE d.first_id = a.id AND d.second_id = b.id
+        )
+        "#,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .filter_map(|r| {
+        Some(DuplicateEvidence {
+            first_id: r.first_id,
+            second_id: r.second_id,
+            reason: r.reason.try_into().ok()?,
+            similarity: r.similarity,
+        })
+    })
+    .collect();
+    if evidence.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let pairs: Vec<(Uuid, Uuid)> = evidence.iter().map(|e| (e.first_id, e.second_id)).collect();
+    let ids: Vec<Uuid> = pairs.iter().flat_map(|&(a, b)| [a, b]).collect();
+    let mut candidates: BTreeMap<Uuid, DuplicateCandidate> = sqlx::query_as!(
+        DuplicateCandidate,
+        r#"
+        SELECT
+            id,
+            email,
+            name,
+            status AS "status: SubscriptionStatus",
+            subscribed_at
+        FROM subscriptions
+        WHERE id = ANY($1)
+        "#,
+        &ids[..],
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|c| (c.id, c))
+    .collect();
+
+    let mut evidence_by_id: BTreeMap<Uuid, Vec<DuplicateEvidence>> = BTreeMap::new();
+    for e in evidence {
+        evidence_by_id.entry(e.first_id).or_default().push(e);
+    }
+
+    Ok(group_pairs(&pairs)
+        .into_iter()
+        .map(|ids| DuplicateGroup {
+            evidence: ids
+                .iter()
+                .flat_map(|id| evidence_by_id.remove(id).unwrap_or_default())
+                .collect(),
+            members: ids.iter().filter_map(|id| candidates.remove(id)).collect(),
+        })
+        .collect())
+}
+
+/// Stops reporting the subscribers as duplicates of each other.
+#[tracing::instrument(name = "Dismiss duplicate subscribers", skip(pool))]
+pub async fn dismiss_duplicates(pool: &PgPool, subscriber_ids: &[Uuid]) -> Result<(), sqlx::Error> {
+    let mut ids = subscriber_ids.to_vec();
+    ids.sort();
+    ids.dedup();
+    let (first_ids, second_ids): (Vec<Uuid>, Vec<Uuid>) = ids
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &first)| ids[i + 1..].iter().map(move |&second| (first, second)))
+        .unzip();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_duplicate_dismissals (first_id, second_id, dismissed_at)
+        SELECT t.first_id, t.second_id, now()
+        FROM UNNEST($1::uuid[], $2::uuid[]) AS t(first_id, second_id)
+        WHERE EXISTS (SELECT 1 FROM subscriptions WHERE id = t.first_id)
+            AND EXISTS (SELECT 1 FROM subscriptions WHERE id = t.second_id)
+        ON CONFLICT DO NOTHING
+        "#,
+        &first_ids[..],
+        &second_ids[..],
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Folds duplicates into the subscriber kept: its tags and topics are
+/// added to, its history gets theirs, and they are deleted along with the
+/// deliveries still pending for them. The status of the subscriber kept is
+/// left as is.
+///
+/// Gives back `false` if a subscriber doesn't exist, was erased, or isn't on
+/// the list of the one kept.
+#[tracing::instrument(name = "Merge duplicate subscribers", skip(transaction))]
+pub async fn merge_subscribers(
+    transaction: &mut Transaction<'_, Postgres>,
+    kept_id: Uuid,
+    merged_ids: &[Uuid],
+) -> Result<bool, sqlx::Error> {
+    let merged_ids: Vec<Uuid> = merged_ids
+        .iter()
+        .copied()
+        .filter(|&id| id != kept_id)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    if merged_ids.is_empty() {
+        return Ok(false);
+    }
+
+    let Some(kept) = sqlx::query!(
+        r#"
+        SELECT list_id
+        FROM subscriptions
+        WHERE id = $1 AND erased_at IS NULL
+        FOR UPDATE
+        "#,
+        kept_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    else {
+        return Ok(false);
+    };
+    let merged_emails: Vec<String> = sqlx::query!(
+        r#"
+        SELECT email
+        FROM subscriptions
+        WHERE id = ANY($1) AND list_id = $2 AND erased_at IS NULL
+        FOR UPDATE
+        "#,
+        &merged_ids[..],
+        kept.list_id,
+    )
+    .fetch_all(&mut **transaction)
+    .await?
+    .into_iter()
+    .map(|r| r.email)
+    .collect();
+    if merged_emails.len() != merged_ids.len() {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag, tagged_at)
+        SELECT $1, tag, min(tagged_at)
+        FROM subscriber_tags
+        WHERE subscriber_id = ANY($2)
+        GROUP BY tag
+        ON CONFLICT DO NOTHING
+        "#,
+        kept_id,
+        &merged_ids[..],
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_topics (subscriber_id, topic_id)
+        SELECT DISTINCT $1::uuid, topic_id
+        FROM subscriber_topics
+        WHERE subscriber_id = ANY($2)
+        ON CONFLICT DO NOTHING
+        "#,
+        kept_id,
+        &merged_ids[..],
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE subscriber_events
+        SET subscriber_id = $1
+        WHERE subscriber_id = ANY($2)
+        "#,
+        kept_id,
+        &merged_ids[..],
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    // Issues delivered to a merged subscriber aren't delivered again to the
+    // one kept.
+    sqlx::query!(
+        r#"
+        UPDATE issue_deliveries d
+        SET subscriber_id = $1
+        FROM (
+            SELECT DISTINCT ON (newsletter_issue_id) newsletter_issue_id, subscriber_email
+            FROM issue_deliveries
+            WHERE subscriber_id = ANY($2)
+            ORDER BY newsletter_issue_id, delivered_at
+        ) first
+        WHERE d.newsletter_issue_id = first.newsletter_issue_id
+            AND d.subscriber_email = first.subscriber_email
+            AND NOT EXISTS (
+                SELECT 1 FROM issue_deliveries k
+                WHERE k.newsletter_issue_id = d.newsletter_issue_id AND k.subscriber_id = $1
+            )
+        "#,
+        kept_id,
+        &merged_ids[..],
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    // The subscriber kept is sent the issues on its own.
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE subscriber_email = ANY($1) AND newsletter_issue_id IN (
+            SELECT newsletter_issue_id FROM newsletter_issues WHERE list_id = $2
+        )
+        "#,
+        &merged_emails[..],
+        kept.list_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM subscription_tokens
+        WHERE subscriber_id = ANY($1)
+        "#,
+        &merged_ids[..],
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM subscriptions
+        WHERE id = ANY($1)
+        "#,
+        &merged_ids[..],
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    record_events(
+        transaction,
+        &[kept_id],
+        SubscriberEventKind::Merged,
+        serde_json::json!({ "merged_subscriber_ids": merged_ids }),
+    )
+    .await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::group_pairs;
+
+    #[test]
+    fn pairs_sharing_a_subscriber_end_up_in_the_same_group() {
+        let mut ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        ids.sort();
+        let [a, b, c, d, e] = ids[..] else {
+            unreachable!()
+        };
+
+        let groups = group_pairs(&[(a, c), (b, c), (d, e)]);
+
+        assert_eq!(groups, vec![vec![a, b, c], vec![d, e]]);
+    }
+
+    #[test]
+    fn no_pairs_make_no_groups() {
+        assert!(group_pairs(&[]).is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// What happened to a subscriber, as recorded in their timeline.
+#[derive(Clone, Copy, Debug)]
+pub enum SubscriberEventKind {
+    Subscribed,
+    Confirmed,
+    Unsubscribed,
+    Delivered,
+    /// The email provider refused to deliver to the subscriber anymore.
+    Suppressed,
+    EmailChanged,
+    /// The email provider reported a bounce.
+    Bounced,
+    /// The subscriber marked an email as spam.
+    Complained,
+    /// The personal data of the subscriber was erased on request.
+    Erased,
+    /// Duplicates of the subscriber were merged into it.
+    Merged,
+    TopicAdded,
+    TopicRemoved,
+}
+
+impl SubscriberEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubscriberEventKind::Subscribed => "subscribed",
+            SubscriberEventKind::Confirmed => "confirmed",
+            SubscriberEventKind::Unsubscribed => "unsubscribed",
+            SubscriberEventKind::Delivered => "delivered",
+            SubscriberEventKind::Suppressed => "suppressed",
+            SubscriberEventKind::EmailChanged => "email_changed",
+            SubscriberEventKind::Bounced => "bounced",
+            SubscriberEventKind::Complained => "complained",
+            SubscriberEventKind::Erased => "erased",
+            SubscriberEventKind::Merged => "merged",
+            SubscriberEventKind::TopicAdded => "topic_added",
+            SubscriberEventKind::TopicRemoved => "topic_removed",
+        }
+    }
+}
+
+/// Appends the event to the timeline of each subscriber and schedules its
+/// delivery to every registered webhook.
+#[tracing::instrument(name = "Record subscriber events", skip(transaction, details))]
+pub async fn record_events(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_ids: &[Uuid],
+    kind: SubscriberEventKind,
+    details: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        WITH events AS (
+            INSERT INTO subscriber_events (subscriber_id, event_type, details, occurred_at)
+            SELECT subscriber_id, $2, $3, now()
+            FROM UNNEST($1::uuid[]) AS t(subscriber_id)
+            RETURNING event_id, occurred_at
+        )
+        INSERT INTO webhook_delivery_queue (webhook_id, event_id, occurred_at)
+        SELECT webhooks.webhook_id, events.event_id, events.occurred_at
+        FROM webhooks CROSS JOIN events
+        "#,
+        subscriber_ids,
+        kind.as_str(),
+        details,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// What happened to the list as a whole. These events concern no subscriber
+/// in particular and only reach webhooks, under their full name.
+#[derive(Clone, Copy, Debug)]
+pub enum ListEventKind {
+    IssuePublished,
+}
+
+impl ListEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListEventKind::IssuePublished => "issue.published",
+        }
+    }
+}
+
+/// Records an event about the list as a whole and schedules its delivery to
+/// every registered webhook.
+#[tracing::instrument(name = "Record list event", skip(transaction, details))]
+pub async fn record_list_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    kind: ListEventKind,
+    details: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        WITH event AS (
+            INSERT INTO subscriber_events (subscriber_id, event_type, details, occurred_at)
+            VALUES (NULL, $1, $2, now())
+            RETURNING event_id, occurred_at
+        )
+        INSERT INTO webhook_delivery_queue (webhook_id, event_id, occurred_at)
+        SELECT webhooks.webhook_id, event.event_id, event.occurred_at
+        FROM webhooks CROSS JOIN event
+        "#,
+        kind.as_str(),
+        details,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct SubscriberEvent {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub details: serde_json::Value,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// A page of the timeline of a subscriber, oldest events first, starting
+/// right after the event identified by `after`.
+#[tracing::instrument(name = "Fetch subscriber events", skip(pool))]
+pub async fn get_subscriber_events(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    after: Option<i64>,
+    limit: i64,
+) -> Result<Vec<SubscriberEvent>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriberEvent,
+        r#"
+        SELECT event_id as id, event_type, details, occurred_at
+        FROM subscriber_events
+        WHERE subscriber_id = $1 AND event_id > $2
+        ORDER BY event_id
+        LIMIT $3
+        "#,
+        subscriber_id,
+        after.unwrap_or(0),
+        limit,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// A change to a subscription, as told to the subscriber themselves.
+#[derive(Debug)]
+pub struct HistoryEntry {
+    pub event_type: String,
+    /// Name of the topic of `topic_added` and `topic_removed` events, unless
+    /// the topic was deleted since.
+    pub topic: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl HistoryEntry {
+    pub fn description(&self) -> String {
+        let topic = self.topic.as_deref().unwrap_or("a removed topic");
+
+        match self.event_type.as_str() {
+            "subscribed" => "You subscribed".into(),
+            "confirmed" => "You confirmed your subscription".into(),
+            "unsubscribed" => "You were unsubscribed".into(),
+            "email_changed" => "Your email address was changed".into(),
+            "topic_added" => format!("You were added to {}", topic),
+            "topic_removed" => format!("You were removed from {}", topic),
+            "merged" => "Duplicate subscriptions of yours were merged into this one".into(),
+            "bounced" => "Emails to your address bounced".into(),
+            "complained" => "An email was marked as spam".into(),
+            "suppressed" => "Emails to your address were stopped by our provider".into(),
+            other => other.replace('_', " "),
+        }
+    }
+}
+
+/// Changes to a subscription, newest first. Deliveries of issues are left
+/// out, they would drown everything else.
+#[tracing::instrument(name = "Fetch subscriber history", skip(pool))]
+pub async fn get_subscriber_history(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    limit: i64,
+) -> Result<Vec<HistoryEntry>, sqlx::Error> {
+    sqlx::query_as!(
+        HistoryEntry,
+        r#"
+        SELECT e.event_type, t.name AS "topic?", e.occurred_at
+        FROM subscriber_events e
+        LEFT JOIN topics t ON t.id::text = e.details ->> 'topic_id'
+        WHERE e.subscriber_id = $1 AND e.event_type <> $2
+        ORDER BY e.event_id DESC
+        LIMIT $3
+        "#,
+        subscriber_id,
+        SubscriberEventKind::Delivered.as_str(),
+        limit,
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,201 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    configuration::Settings,
+    domain::SubscriptionStatus,
+    email_client::{EmailClient, Suppression, SuppressionReason},
+    leader_election::{LeaderElection, SingletonJob},
+    startup::get_connection_pool,
+    subscriber_events::{record_events, SubscriberEventKind},
+};
+
+/// An email provider keeping a list of the recipients it doesn't deliver to.
+#[async_trait::async_trait]
+pub trait SuppressionProvider: Send + Sync {
+    async fn suppressions(&self) -> Result<Vec<Suppression>, anyhow::Error>;
+
+    async fn suppress(&self, emails: &[String]) -> Result<(), anyhow::Error>;
+}
+
+#[async_trait::async_trait]
+impl SuppressionProvider for EmailClient {
+    async fn suppressions(&self) -> Result<Vec<Suppression>, anyhow::Error> {
+        Ok(self.list_suppressions().await?)
+    }
+
+    async fn suppress(&self, emails: &[String]) -> Result<(), anyhow::Error> {
+        Ok(self.suppress_recipients(emails).await?)
+    }
+}
+
+/// Addresses suppressed on one side only before the sync.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct SuppressionSyncReport {
+    /// Suppressed by the provider, whose subscriptions were still active.
+    pub suppressed_locally: Vec<String>,
+    /// Suppressed here, unknown to the provider.
+    pub suppressed_at_provider: Vec<String>,
+}
+
+fn local_status(reason: SuppressionReason) -> (SubscriptionStatus, SubscriberEventKind) {
+    match reason {
+        SuppressionReason::HardBounce => {
+            (SubscriptionStatus::Bounced, SubscriberEventKind::Bounced)
+        }
+        SuppressionReason::SpamComplaint => (
+            SubscriptionStatus::Complained,
+            SubscriberEventKind::Complained,
+        ),
+        SuppressionReason::Manual => (
+            SubscriptionStatus::Suppressed,
+            SubscriberEventKind::Suppressed,
+        ),
+    }
+}
+
+/// Reconciles the suppressions of the provider with the suppressed,
+/// bounced and complained subscriptions, in both directions.
+///
+/// Suppressions are only ever added: an address reactivated on one side is
+/// suppressed again from the other, so it has to be reactivated on both.
+#[tracing::instrument(name = "Sync suppressions", skip_all)]
+pub async fn sync_suppressions(
+    pool: &PgPool,
+    provider: &dyn SuppressionProvider,
+) -> Result<SuppressionSyncReport, anyhow::Error> {
+    let mut by_reason: HashMap<SuppressionReason, Vec<String>> = HashMap::new();
+    let mut provider_emails = BTreeSet::new();
+    for suppression in provider
+        .suppressions()
+        .await
+        .context("Failed to list the suppressions of the provider")?
+    {
+        let email = suppression.email_address.to_lowercase();
+        provider_emails.insert(email.clone());
+        by_reason.entry(suppression.reason).or_default().push(email);
+    }
+
+    let local_emails: BTreeSet<String> = sqlx::query!(
+        r#"
+        SELECT DISTINCT lower(email) as "email!"
+        FROM subscriptions
+        WHERE status IN ('suppressed', 'bounced', 'complained') AND erased_at IS NULL
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch the suppressed subscriptions")?
+    .into_iter()
+    .map(|r| r.email)
+    .collect();
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool")?;
+    let mut suppressed_locally = BTreeSet::new();
+    for (reason, emails) in by_reason {
+        let (status, kind) = local_status(reason);
+        // Subscriptions already suppressed here are left with their reason.
+        let active: Vec<SubscriptionStatus> = SubscriptionStatus::sources_of(status)
+            .into_iter()
+            .filter(|source| !source.is_undeliverable())
+            .collect();
+        let rows = sqlx::query!(
+            r#"
+            UPDATE subscriptions
+            SET status = $1
+            WHERE lower(email) = ANY($2) AND status = ANY($3)
+            RETURNING id, email
+            "#,
+            status as SubscriptionStatus,
+            &emails[..],
+            &active as &[SubscriptionStatus],
+        )
+        .fetch_all(&mut *transaction)
+        .await
+        .context("Failed to suppress the subscriptions")?;
+        if rows.is_empty() {
+            continue;
+        }
+
+        let subscriber_ids: Vec<_> = rows.iter().map(|r| r.id).collect();
+        let emails: Vec<_> = rows.into_iter().map(|r| r.email).collect();
+        sqlx::query!(
+            r#"
+            DELETE FROM issue_delivery_queue
+            WHERE subscriber_email = ANY($1)
+            "#,
+            &emails[..],
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to cancel the pending deliveries to the suppressed recipients")?;
+        record_events(
+            &mut transaction,
+            &subscriber_ids,
+            kind,
+            serde_json::json!({ "source": "suppression_sync" }),
+        )
+        .await
+        .context("Failed to record the suppressions")?;
+
+        suppressed_locally.extend(emails.into_iter().map(|email| email.to_lowercase()));
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to sync suppressions")?;
+
+    let suppressed_at_provider: Vec<String> =
+        local_emails.difference(&provider_emails).cloned().collect();
+    if !suppressed_at_provider.is_empty() {
+        provider
+            .suppress(&suppressed_at_provider)
+            .await
+            .context("Failed to suppress the recipients at the provider")?;
+    }
+
+    let report = SuppressionSyncReport {
+        suppressed_locally: suppressed_locally.into_iter().collect(),
+        suppressed_at_provider,
+    };
+    if !report.suppressed_locally.is_empty() || !report.suppressed_at_provider.is_empty() {
+        tracing::warn!(
+            suppressed_locally = ?report.suppressed_locally,
+            suppressed_at_provider = ?report.suppressed_at_provider,
+            "Suppressions differed between the provider and the subscriptions"
+        );
+    }
+
+    Ok(report)
+}
+
+/// Syncs the suppressions once, if enabled. Failures are only logged, the
+/// sync can be run again on demand.
+pub async fn sync_on_startup(configuration: Settings, email_client: EmailClient) {
+    if !configuration.email_client.sync_suppressions_on_startup {
+        return;
+    }
+
+    let pool = get_connection_pool(&configuration.database);
+    let mut election = LeaderElection::new(
+        pool.clone(),
+        SingletonJob::SuppressionSync,
+        &configuration.application.instance_id,
+    );
+    // Another instance starting at the same time syncs them already.
+    if !election.is_leader().await {
+        return;
+    }
+    if let Err(e) = sync_suppressions(&pool, &email_client).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to sync the suppressions on startup",
+        );
+    }
+}
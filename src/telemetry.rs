@@ -1,12 +1,28 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    web, HttpMessage,
+};
+use rand::Rng;
 use tokio::task::JoinHandle;
-use tracing::{subscriber::set_global_default, Subscriber};
+use tracing::{subscriber::set_global_default, Span, Subscriber};
+use tracing_actix_web::{root_span, DefaultRootSpanBuilder, Level, RootSpanBuilder};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
-use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, reload, EnvFilter, Registry};
+
+use crate::{configuration::QuietRouteSettings, request_id::RequestId};
 
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Logs as Bunyan JSON records, each tagged with the id of the instance
+/// when there is one.
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
+    instance_id: Option<String>,
     sink: Sink,
 ) -> impl Subscriber + Send + Sync
 where
@@ -14,7 +30,13 @@ where
 {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
-    let formatting_layer = BunyanFormattingLayer::new(name, sink);
+    let (env_filter, handle) = reload::Layer::new(env_filter);
+    // Only the first subscriber built can become the global default.
+    let _ = LOG_FILTER.set(handle);
+    let default_fields = instance_id
+        .map(|id| HashMap::from([("instance_id".to_string(), id.into())]))
+        .unwrap_or_default();
+    let formatting_layer = BunyanFormattingLayer::with_default_fields(name, sink, default_fields);
 
     Registry::default()
         .with(env_filter)
@@ -27,6 +49,99 @@ pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     set_global_default(subscriber).expect("Failed to set subscriber");
 }
 
+/// The directives currently filtering the logs, if they can be changed.
+pub fn current_log_filter() -> Option<String> {
+    LOG_FILTER
+        .get()?
+        .with_current(|filter| filter.to_string())
+        .ok()
+}
+
+/// Replaces the filter of the logs until the application restarts.
+pub fn set_log_filter(filter: EnvFilter) -> Result<(), anyhow::Error> {
+    let handle = LOG_FILTER
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("No reloadable log filter was installed"))?;
+    handle.reload(filter)?;
+
+    Ok(())
+}
+
+/// Routes whose successful requests are logged for a sample of them only,
+/// e.g. health checks polled every few seconds.
+pub struct QuietRoutes(Vec<QuietRouteSettings>);
+
+impl QuietRoutes {
+    pub fn new(routes: Vec<QuietRouteSettings>) -> Self {
+        Self(routes)
+    }
+
+    fn sample_rate(&self, path: &str) -> Option<f64> {
+        self.0
+            .iter()
+            .find(|route| route.path == path)
+            .map(|route| route.sample_rate)
+    }
+
+    pub fn is_sampled(&self, path: &str) -> bool {
+        match self.sample_rate(path) {
+            Some(rate) => rate > 0.0 && (rate >= 1.0 || rand::thread_rng().gen_bool(rate)),
+            None => true,
+        }
+    }
+}
+
+/// Opens the root span of requests to quiet routes at the `DEBUG` level when
+/// they are not sampled, so that they are dropped unless debugging. Failed
+/// requests are always reported.
+pub struct SampledRootSpanBuilder;
+
+impl RootSpanBuilder for SampledRootSpanBuilder {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        let sampled = match request.app_data::<web::Data<QuietRoutes>>() {
+            Some(routes) => routes.is_sampled(request.path()),
+            None => true,
+        };
+
+        // `request_id` is the one of `TracingLogger`, `http.request_id` the
+        // `X-Request-Id` users are shown.
+        let request_id = request
+            .extensions()
+            .get::<RequestId>()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+
+        if sampled {
+            root_span!(level = Level::INFO, request, http.request_id = %request_id)
+        } else {
+            root_span!(level = Level::DEBUG, request, http.request_id = %request_id)
+        }
+    }
+
+    fn on_request_end<B: MessageBody>(
+        span: Span,
+        outcome: &Result<ServiceResponse<B>, actix_web::Error>,
+    ) {
+        if span.is_disabled() {
+            match outcome {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => tracing::warn!(
+                    http.method = %response.request().method(),
+                    http.target = %response.request().path(),
+                    http.status_code = response.status().as_u16(),
+                    "Request to a quiet route failed",
+                ),
+                Err(e) => tracing::warn!(
+                    error.message = %e,
+                    "Request to a quiet route failed",
+                ),
+            }
+        }
+
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
+
 pub fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
 where
     F: FnOnce() -> R + Send + 'static,
@@ -36,3 +151,32 @@ where
 
     tokio::task::spawn_blocking(move || current_span.in_scope(f))
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::QuietRouteSettings;
+
+    use super::QuietRoutes;
+
+    fn quiet_routes(sample_rate: f64) -> QuietRoutes {
+        QuietRoutes::new(vec![QuietRouteSettings {
+            path: "/health_check".into(),
+            sample_rate,
+        }])
+    }
+
+    #[test]
+    fn excluded_routes_are_never_sampled() {
+        assert!(!quiet_routes(0.0).is_sampled("/health_check"));
+    }
+
+    #[test]
+    fn fully_sampled_routes_are_always_sampled() {
+        assert!(quiet_routes(1.0).is_sampled("/health_check"));
+    }
+
+    #[test]
+    fn other_routes_are_always_sampled() {
+        assert!(quiet_routes(0.0).is_sampled("/subscriptions"));
+    }
+}
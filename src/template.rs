@@ -1,22 +1,334 @@
-use std::ops::Deref;
+use std::{collections::HashMap, ops::Deref, sync::Arc};
 
+use actix_web_flash_messages::IncomingFlashMessages;
+use arc_swap::ArcSwap;
+use kuchikiki::{traits::TendrilSink, NodeRef};
 use lazy_static::lazy_static;
-use tera::{self, Context, Tera};
+use tera::{self, Context, Tera, Value};
+
+use crate::{
+    authentication::{ApiToken, FailedLogin},
+    configuration::SignupPageSettings,
+    delivery_queue::IssueStats,
+    domain::{Locale, SignupField},
+    email_log::{EmailKind, EmailLogFilter, EmailStatus, SentEmail},
+    growth_report::GrowthReport,
+    referrals::ReferralStats,
+    stats::DashboardStats,
+    stored_templates::StoredTemplate,
+    webhooks::{Webhook, WebhookDelivery},
+};
+
+/// Where templates are read from unless configured otherwise.
+pub const DEFAULT_TEMPLATE_DIRECTORY: &str = "templates";
+
+fn load_templates(directory: &str) -> Result<Tera, tera::Error> {
+    let mut tera = Tera::new(&format!("{}/**/*", directory))?;
+    if tera.get_template_names().next().is_none() {
+        return Err(tera::Error::msg(format!("No templates in {}", directory)));
+    }
+
+    register_filters(&mut tera);
+
+    Ok(tera)
+}
+
+fn register_filters(tera: &mut Tera) {
+    tera.autoescape_on(vec![".html"]);
+    tera.register_filter("escape_attribute", escape_attribute);
+    tera.register_filter("text", text);
+}
 
 lazy_static! {
-    pub static ref TEMPLATES: Tera = {
-        let mut tera = match Tera::new("templates/**/*") {
-            Ok(t) => t,
-            Err(e) => {
-                eprintln!("Tera failed to parse templates: {}", e);
-                ::std::process::exit(1);
-            }
-        };
+    /// Every email is rendered from an `.html` and a `.txt` template, pages
+    /// from an `.html` one extending `layout.html`.
+    ///
+    /// Values are escaped by the templates themselves: `.html` templates are
+    /// autoescaped, links put in attributes go through `escape_attribute`
+    /// (which leaves their slashes alone, unlike the autoescape) and every
+    /// value of a `.txt` template goes through `text`, which drops control
+    /// characters so that a value can't add lines of its own.
+    ///
+    /// Replaced as a whole by [`use_templates`].
+    pub static ref TEMPLATES: ArcSwap<Tera> = match load_templates(DEFAULT_TEMPLATE_DIRECTORY) {
+        Ok(tera) => ArcSwap::from_pointee(tera),
+        Err(e) => {
+            eprintln!("Tera failed to parse templates: {}", e);
+            ::std::process::exit(1);
+        }
+    };
+}
+
+/// Reads the templates again from `directory`, e.g. after they were edited.
+/// The templates in use are kept if the new ones can't be parsed.
+pub fn use_templates(directory: &str) -> Result<(), tera::Error> {
+    TEMPLATES.store(Arc::new(load_templates(directory)?));
+    STATIC_EMAILS.store(Arc::new(StaticEmails::prerender()));
+
+    Ok(())
+}
+
+fn encode_attribute(value: &str) -> String {
+    htmlescape::encode_minimal(value).replace('\'', "&#x27;")
+}
+
+fn strip_control_characters(value: &str) -> String {
+    value.chars().filter(|c| !c.is_control()).collect()
+}
 
-        tera.autoescape_on(vec![".html"]);
+fn escape_attribute(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let value = tera::try_get_value!("escape_attribute", "value", String, value);
 
-        tera
+    Ok(Value::String(encode_attribute(&value)))
+}
+
+fn text(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let value = tera::try_get_value!("text", "value", String, value);
+
+    Ok(Value::String(strip_control_characters(&value)))
+}
+
+/// Renders an email whose templates aren't on disk, escaping its values like
+/// the ones that are.
+fn render_raw(html: &str, text: &str, context: &Context) -> Result<Template, tera::Error> {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![("email.html", html), ("email.txt", text)])?;
+    register_filters(&mut tera);
+
+    Ok(Template {
+        html: tera.render("email.html", context)?,
+        text: tera.render("email.txt", context)?,
+    })
+}
+
+/// What the HTML and the text templates of an email on disk are made of.
+fn source(name: &str) -> Option<(String, String)> {
+    let templates = TEMPLATES.load();
+    let read = |extension: &str| {
+        let template = templates
+            .get_template(&format!("{}.{}", name, extension))
+            .ok()?;
+        std::fs::read_to_string(template.path.as_ref()?).ok()
     };
+
+    Some((read("html")?, read("txt")?))
+}
+
+fn render(name: &str, context: &Context) -> Result<Template, tera::Error> {
+    let templates = TEMPLATES.load();
+    let html = templates.render(&format!("{}.html", name), context)?;
+    let text = templates.render(&format!("{}.txt", name), context)?;
+
+    Ok(Template { html, text })
+}
+
+/// Renders the translation of an email to the first of `locales` there is
+/// one for, the default templates otherwise.
+fn render_localized(
+    name: &str,
+    context: &Context,
+    locales: &[Locale],
+) -> Result<Template, tera::Error> {
+    let translated = translations(&TEMPLATES.load(), name);
+
+    match locales
+        .iter()
+        .find(|l| translated.iter().any(|t| t == l.as_ref()))
+    {
+        Some(locale) => render(&format!("{}.{}", name, locale.as_ref()), context),
+        None => render(name, context),
+    }
+}
+
+/// Stands for the link while pre-rendering a [`StaticEmail`]. Private use
+/// characters are left alone by both `escape_attribute` and `text`.
+const LINK_PLACEHOLDER: &str = "\u{e000}link\u{e000}";
+
+/// A link going through every escape, to check that splicing renders the
+/// same as Tera does.
+const PROBE_LINK: &str = "https://example.com/probe?a=1&b=<'\">\n";
+
+/// The parts of an email around each occurrence of its link.
+struct SplicedTemplate {
+    html: Vec<String>,
+    text: Vec<String>,
+}
+
+impl SplicedTemplate {
+    fn splice(&self, link: &str) -> Template {
+        Template {
+            html: self.html.join(&encode_attribute(link)),
+            text: self.text.join(&strip_control_characters(link)),
+        }
+    }
+}
+
+/// An email whose templates only take a link, e.g. a confirmation. Its static
+/// parts are rendered once and each message only splices its link between
+/// them, unless the templates escape the link in some other way than
+/// `escape_attribute` and `text`, in which case Tera renders every message.
+struct StaticEmail {
+    /// Name of the templates, language included for a translation.
+    name: String,
+    variable: &'static str,
+    spliced: Option<SplicedTemplate>,
+}
+
+impl StaticEmail {
+    fn new(name: String, variable: &'static str) -> Self {
+        let mut email = Self {
+            name,
+            variable,
+            spliced: None,
+        };
+        email.spliced = email.prerender();
+
+        email
+    }
+
+    fn prerender(&self) -> Option<SplicedTemplate> {
+        let template = self.render_with_tera(LINK_PLACEHOLDER).ok()?;
+        let spliced = SplicedTemplate {
+            html: template
+                .html
+                .split(LINK_PLACEHOLDER)
+                .map(Into::into)
+                .collect(),
+            text: template
+                .text
+                .split(LINK_PLACEHOLDER)
+                .map(Into::into)
+                .collect(),
+        };
+
+        let expected = self.render_with_tera(PROBE_LINK).ok()?;
+        let actual = spliced.splice(PROBE_LINK);
+        if actual.html != expected.html || actual.text != expected.text {
+            tracing::warn!(
+                template = %self.name,
+                "The link of the email can't be spliced, it is rendered on each message"
+            );
+            return None;
+        }
+
+        Some(spliced)
+    }
+
+    fn render_with_tera(&self, link: &str) -> Result<Template, tera::Error> {
+        let mut context = Context::new();
+        context.insert(self.variable, link);
+
+        render(&self.name, &context)
+    }
+
+    fn render(&self, link: &str) -> Result<Template, tera::Error> {
+        match &self.spliced {
+            Some(spliced) => Ok(spliced.splice(link)),
+            None => self.render_with_tera(link),
+        }
+    }
+}
+
+/// A [`StaticEmail`] along with its translations, e.g.
+/// `subscription_confirmation.pt.html` and `subscription_confirmation.pt.txt`
+/// for the Portuguese one.
+struct LocalizedEmail {
+    default: StaticEmail,
+    translations: HashMap<String, StaticEmail>,
+}
+
+impl LocalizedEmail {
+    fn new(name: &'static str, variable: &'static str) -> Self {
+        let translations = translations(&TEMPLATES.load(), name)
+            .into_iter()
+            .map(|language| {
+                let email = StaticEmail::new(format!("{}.{}", name, language), variable);
+                (language, email)
+            })
+            .collect();
+
+        Self {
+            default: StaticEmail::new(name.to_string(), variable),
+            translations,
+        }
+    }
+
+    /// Renders the translation to the first of `locales` there is one for,
+    /// the default templates otherwise.
+    fn render(&self, link: &str, locales: &[Locale]) -> Result<Template, tera::Error> {
+        locales
+            .iter()
+            .find_map(|l| self.translations.get(l.as_ref()))
+            .unwrap_or(&self.default)
+            .render(link)
+    }
+}
+
+/// Languages an email has both templates translated to.
+fn translations(templates: &Tera, name: &str) -> Vec<String> {
+    let prefix = format!("{}.", name);
+    let names: Vec<_> = templates.get_template_names().collect();
+
+    names
+        .iter()
+        .filter_map(|n| n.strip_prefix(&prefix)?.strip_suffix(".html"))
+        .filter(|language| Locale::parse(language).is_ok_and(|l| l.as_ref() == *language))
+        .filter(|language| names.contains(&format!("{}{}.txt", prefix, language).as_str()))
+        .map(|language| language.to_string())
+        .collect()
+}
+
+/// Name of the translation of a template to the first of `locales` there is
+/// one for, e.g. `pages/home.pt.html` for `pages/home.html`, along with its
+/// language. The name itself if there is none.
+fn localized_name(templates: &Tera, name: &str, locales: &[Locale]) -> (String, Option<String>) {
+    let (stem, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+
+    locales
+        .iter()
+        .map(|l| {
+            let language = l.as_ref();
+            (format!("{}.{}.{}", stem, language, extension), language)
+        })
+        .find(|(n, _)| templates.get_template(n).is_ok())
+        .map_or((name.to_string(), None), |(n, language)| {
+            (n, Some(language.to_string()))
+        })
+}
+
+struct StaticEmails {
+    subscription_confirmation: LocalizedEmail,
+    subscription_welcome: LocalizedEmail,
+    collaborator_invitation: StaticEmail,
+    preferences_link: LocalizedEmail,
+    email_change_confirmation: LocalizedEmail,
+}
+
+impl StaticEmails {
+    fn prerender() -> Self {
+        Self {
+            subscription_confirmation: LocalizedEmail::new(
+                "subscription_confirmation",
+                "confirmation_link",
+            ),
+            subscription_welcome: LocalizedEmail::new("subscription_welcome", "preferences_link"),
+            // Sent to collaborators, whose language isn't known.
+            collaborator_invitation: StaticEmail::new(
+                "collaborator_invitation".to_string(),
+                "registration_link",
+            ),
+            preferences_link: LocalizedEmail::new("preferences_link", "preferences_link"),
+            email_change_confirmation: LocalizedEmail::new(
+                "email_change_confirmation",
+                "confirmation_link",
+            ),
+        }
+    }
+}
+
+lazy_static! {
+    static ref STATIC_EMAILS: ArcSwap<StaticEmails> =
+        ArcSwap::from_pointee(StaticEmails::prerender());
 }
 
 #[derive(Debug)]
@@ -36,22 +348,42 @@ impl Deref for SubcriptionConfirmation {
     }
 }
 
+/// In the first of `locales` there is a translation for.
 pub fn render_subscription_confirmation(
     confirmation_link: &str,
+    locales: &[Locale],
 ) -> Result<SubcriptionConfirmation, tera::Error> {
-    let mut context = Context::new();
-    context.insert("confirmation_link", confirmation_link);
-    let html = TEMPLATES.render("subscription_confirmation.html", &context)?;
+    let template = STATIC_EMAILS
+        .load()
+        .subscription_confirmation
+        .render(confirmation_link, locales)?;
+
+    Ok(SubcriptionConfirmation(template))
+}
 
-    let text = format!(
-        "Welcome to our newsletter!\n\
-                Visit {} to confirm your subscription.",
-        confirmation_link
-    );
+#[derive(Debug)]
+pub struct SubscriptionWelcome(Template);
 
-    let template = Template { html, text };
+impl Deref for SubscriptionWelcome {
+    type Target = Template;
 
-    Ok(SubcriptionConfirmation(template))
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Sent instead of the confirmation when subscribing needs no confirmation,
+/// in the first of `locales` there is a translation for.
+pub fn render_subscription_welcome(
+    preferences_link: &str,
+    locales: &[Locale],
+) -> Result<SubscriptionWelcome, tera::Error> {
+    let template = STATIC_EMAILS
+        .load()
+        .subscription_welcome
+        .render(preferences_link, locales)?;
+
+    Ok(SubscriptionWelcome(template))
 }
 
 #[derive(Debug)]
@@ -67,18 +399,789 @@ impl Deref for CollaboratorInvitation {
 
 pub fn render_collaborator_invitation(
     registration_link: &str,
+) -> Result<CollaboratorInvitation, tera::Error> {
+    let template = STATIC_EMAILS
+        .load()
+        .collaborator_invitation
+        .render(registration_link)?;
+
+    Ok(CollaboratorInvitation(template))
+}
+
+/// Renders an invitation edited from the admin UI, see
+/// [`stored_templates`](crate::stored_templates).
+pub fn render_stored_collaborator_invitation(
+    html_body: &str,
+    text_body: &str,
+    registration_link: &str,
 ) -> Result<CollaboratorInvitation, tera::Error> {
     let mut context = Context::new();
     context.insert("registration_link", registration_link);
-    let html = TEMPLATES.render("collaborator_invitation.html", &context)?;
 
-    let text = format!(
-        "Welcome to our newsletter!\n\
-                Visit {} to register as collaborator.",
-        registration_link
-    );
+    Ok(CollaboratorInvitation(render_raw(
+        html_body, text_body, &context,
+    )?))
+}
 
-    let template = Template { html, text };
+/// The HTML and text templates of the invitation on disk, which is sent
+/// until one is edited from the admin UI.
+pub fn collaborator_invitation_source() -> Option<(String, String)> {
+    source("collaborator_invitation")
+}
 
-    Ok(CollaboratorInvitation(template))
+#[derive(Debug)]
+pub struct PreferencesLink(Template);
+
+impl Deref for PreferencesLink {
+    type Target = Template;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub fn render_preferences_link(
+    preferences_link: &str,
+    locales: &[Locale],
+) -> Result<PreferencesLink, tera::Error> {
+    let template = STATIC_EMAILS
+        .load()
+        .preferences_link
+        .render(preferences_link, locales)?;
+
+    Ok(PreferencesLink(template))
+}
+
+#[derive(Debug)]
+pub struct EmailChangeConfirmation(Template);
+
+impl Deref for EmailChangeConfirmation {
+    type Target = Template;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub fn render_email_change_confirmation(
+    confirmation_link: &str,
+    locales: &[Locale],
+) -> Result<EmailChangeConfirmation, tera::Error> {
+    let template = STATIC_EMAILS
+        .load()
+        .email_change_confirmation
+        .render(confirmation_link, locales)?;
+
+    Ok(EmailChangeConfirmation(template))
+}
+
+/// Appended to issues, see [`ReferralLinks`](crate::referrals::ReferralLinks).
+pub fn render_issue_footer(referral_link: &str) -> Result<Template, tera::Error> {
+    let mut context = Context::new();
+    context.insert("referral_link", referral_link);
+
+    render("issue_footer", &context)
+}
+
+/// Thanks a subscriber for the confirmed referrals they reached.
+pub fn render_referral_reward(
+    name: &str,
+    referrals: i64,
+    locales: &[Locale],
+) -> Result<Template, tera::Error> {
+    let mut context = Context::new();
+    context.insert("name", name);
+    context.insert("referrals", &referrals);
+
+    render_localized("referral_reward", &context, locales)
+}
+
+/// What every page shares, see `templates/layout.html`.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct PageLayout {
+    /// Navigation menu, already rendered, shown at the top of the page.
+    pub navigation: Option<String>,
+    /// Shown below the navigation, escaped by the layout.
+    pub flash_messages: Vec<String>,
+    /// Language of the page, English unless it was translated.
+    pub language: Option<String>,
+}
+
+impl PageLayout {
+    pub fn new(navigation: Option<String>, flash_messages: &IncomingFlashMessages) -> Self {
+        Self {
+            navigation,
+            flash_messages: flash_messages
+                .iter()
+                .map(|m| m.content().to_string())
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+fn render_page(
+    name: &str,
+    layout: &PageLayout,
+    mut context: Context,
+) -> Result<String, tera::Error> {
+    context.insert("layout", layout);
+
+    TEMPLATES.load().render(name, &context)
+}
+
+/// Renders the translation of a page to the first of `locales` there is one
+/// for, the page itself otherwise.
+fn render_localized_page(
+    name: &str,
+    mut layout: PageLayout,
+    locales: &[Locale],
+    context: Context,
+) -> Result<String, tera::Error> {
+    let (name, language) = localized_name(&TEMPLATES.load(), name, locales);
+    layout.language = language;
+
+    render_page(&name, &layout, context)
+}
+
+/// Public landing page, with a subscription form carrying the referral code
+/// of whoever shared the link to it.
+pub fn render_home_page(
+    fields: &[SignupField],
+    locales: &[Locale],
+    referral_code: Option<&str>,
+) -> Result<String, tera::Error> {
+    let mut context = Context::new();
+    context.insert("fields", fields);
+    context.insert("referral_code", &referral_code);
+
+    render_localized_page("pages/home.html", PageLayout::default(), locales, context)
+}
+
+/// Public signup page, branded as configured, with the same form as the
+/// home page.
+pub fn render_signup_page(
+    signup: &SignupPageSettings,
+    locales: &[Locale],
+    referral_code: Option<&str>,
+) -> Result<String, tera::Error> {
+    let mut context = Context::new();
+    context.insert("signup", signup);
+    context.insert("fields", &signup.fields);
+    context.insert("referral_code", &referral_code);
+
+    render_localized_page(
+        "pages/subscribe.html",
+        PageLayout::default(),
+        locales,
+        context,
+    )
+}
+
+/// Shown once the subscription form is sent, while the confirmation or,
+/// without double opt-in, the welcome email is on its way.
+pub fn render_subscribed_page(
+    require_confirmation: bool,
+    locales: &[Locale],
+) -> Result<String, tera::Error> {
+    let mut context = Context::new();
+    context.insert("require_confirmation", &require_confirmation);
+
+    render_localized_page(
+        "pages/subscribed.html",
+        PageLayout::default(),
+        locales,
+        context,
+    )
+}
+
+/// Delivery stats page of an issue in the admin UI, below the given
+/// navigation menu.
+pub fn render_issue_stats_page(
+    navigation: &str,
+    stats: &IssueStats,
+) -> Result<String, tera::Error> {
+    let layout = PageLayout {
+        navigation: Some(navigation.to_string()),
+        ..Default::default()
+    };
+    let mut context = Context::new();
+    context.insert("stats", stats);
+
+    render_page("admin/issue_stats.html", &layout, context)
+}
+
+/// Referral leaderboard and milestones in the admin UI, below the given
+/// navigation menu.
+pub fn render_referrals_page(
+    navigation: &str,
+    stats: &ReferralStats,
+) -> Result<String, tera::Error> {
+    let layout = PageLayout {
+        navigation: Some(navigation.to_string()),
+        ..Default::default()
+    };
+    let mut context = Context::new();
+    context.insert("stats", stats);
+
+    render_page("admin/referrals.html", &layout, context)
+}
+
+/// Latest failed logins in the admin UI, below the given navigation menu.
+pub fn render_failed_logins_page(
+    navigation: &str,
+    failed_logins: &[FailedLogin],
+) -> Result<String, tera::Error> {
+    let layout = PageLayout {
+        navigation: Some(navigation.to_string()),
+        ..Default::default()
+    };
+    let mut context = Context::new();
+    context.insert("failed_logins", failed_logins);
+
+    render_page("admin/failed_logins.html", &layout, context)
+}
+
+/// Latest emails sent, narrowed down by the filter, in the admin UI.
+pub fn render_emails_page(
+    navigation: &str,
+    filter: &EmailLogFilter,
+    emails: &[SentEmail],
+) -> Result<String, tera::Error> {
+    let layout = PageLayout {
+        navigation: Some(navigation.to_string()),
+        ..Default::default()
+    };
+    let kinds: Vec<&str> = EmailKind::ALL.iter().map(EmailKind::as_str).collect();
+    let statuses: Vec<&str> = EmailStatus::ALL.iter().map(EmailStatus::as_str).collect();
+    let mut context = Context::new();
+    context.insert("filter", filter);
+    context.insert("kinds", &kinds);
+    context.insert("statuses", &statuses);
+    context.insert("emails", emails);
+
+    render_page("admin/emails.html", &layout, context)
+}
+
+/// API tokens of the user, with a form to create another.
+pub fn render_api_tokens_page(
+    layout: &PageLayout,
+    admin: &str,
+    csrf_token: &str,
+    tokens: &[ApiToken],
+) -> Result<String, tera::Error> {
+    let mut context = Context::new();
+    context.insert("admin", admin);
+    context.insert("csrf_token", csrf_token);
+    context.insert("tokens", tokens);
+
+    render_page("admin/api_tokens.html", layout, context)
+}
+
+/// What the invitation editor is made of.
+#[derive(Debug, serde::Serialize)]
+pub struct InvitationTemplatePage<'a> {
+    pub admin: &'a str,
+    pub csrf_token: &'a str,
+    pub subject: &'a str,
+    pub html_body: &'a str,
+    pub text_body: &'a str,
+    /// Version shown in the form, `None` for the default email.
+    pub version: Option<i32>,
+    pub history: &'a [StoredTemplate],
+}
+
+/// Form to edit the collaborator invitation, along with its earlier
+/// versions.
+pub fn render_invitation_template_page(
+    layout: &PageLayout,
+    page: &InvitationTemplatePage,
+) -> Result<String, tera::Error> {
+    render_page(
+        "admin/invitation_template.html",
+        layout,
+        Context::from_serialize(page)?,
+    )
+}
+
+/// Registered webhooks, with a form to register another, and their latest
+/// deliveries.
+pub fn render_webhooks_page(
+    layout: &PageLayout,
+    admin: &str,
+    csrf_token: &str,
+    webhooks: &[Webhook],
+    deliveries: &[WebhookDelivery],
+) -> Result<String, tera::Error> {
+    let mut context = Context::new();
+    context.insert("admin", admin);
+    context.insert("csrf_token", csrf_token);
+    context.insert("webhooks", webhooks);
+    context.insert("deliveries", deliveries);
+
+    render_page("admin/webhooks.html", layout, context)
+}
+
+/// What the admin dashboard is made of.
+#[derive(Debug, serde::Serialize)]
+pub struct DashboardPage<'a> {
+    pub admin: &'a str,
+    pub username: &'a str,
+    pub display_name: Option<&'a str>,
+    pub csrf_token: &'a str,
+    pub base_url: &'a str,
+    /// Why links in emails can't reach the app, if they can't.
+    pub base_url_unreachable: Option<&'a str>,
+    pub stats: &'a DashboardStats,
+}
+
+/// Landing page of the admin UI.
+pub fn render_admin_dashboard(
+    layout: &PageLayout,
+    page: &DashboardPage,
+) -> Result<String, tera::Error> {
+    render_page(
+        "admin/dashboard.html",
+        layout,
+        Context::from_serialize(page)?,
+    )
+}
+
+pub fn render_change_password_page(
+    layout: &PageLayout,
+    admin: &str,
+    csrf_token: &str,
+) -> Result<String, tera::Error> {
+    let mut context = Context::new();
+    context.insert("admin", admin);
+    context.insert("csrf_token", csrf_token);
+
+    render_page("admin/change_password.html", layout, context)
+}
+
+/// What the profile page of a user is made of.
+#[derive(Debug, serde::Serialize)]
+pub struct ProfilePage<'a> {
+    pub admin: &'a str,
+    pub csrf_token: &'a str,
+    pub username: &'a str,
+    pub display_name: Option<&'a str>,
+    pub contact_email: Option<&'a str>,
+}
+
+pub fn render_profile_page(layout: &PageLayout, page: &ProfilePage) -> Result<String, tera::Error> {
+    render_page("admin/profile.html", layout, Context::from_serialize(page)?)
+}
+
+pub fn render_login_page(layout: &PageLayout, csrf_token: &str) -> Result<String, tera::Error> {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token);
+
+    render_page("pages/login.html", layout, context)
+}
+
+/// The invitation token is read from the URL by the page itself.
+pub fn render_collaborator_registration_page(
+    layout: &PageLayout,
+    csrf_token: &str,
+    email: Option<&str>,
+) -> Result<String, tera::Error> {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token);
+    context.insert("email", &email);
+
+    render_page("pages/collaborator_registration.html", layout, context)
+}
+
+/// What an error page is made of.
+#[derive(Debug, serde::Serialize)]
+pub struct ErrorPage<'a> {
+    pub status: u16,
+    pub title: &'a str,
+    pub message: &'a str,
+    pub request_id: Option<String>,
+}
+
+pub fn render_error_page(page: &ErrorPage) -> Result<String, tera::Error> {
+    render_page(
+        "error.html",
+        &PageLayout::default(),
+        Context::from_serialize(page)?,
+    )
+}
+
+/// Weekly growth report emailed to admins.
+pub fn render_weekly_report(report: &GrowthReport) -> Result<Template, tera::Error> {
+    let mut context = Context::new();
+    context.insert("report", report);
+
+    render("weekly_report", &context)
+}
+
+/// An issue of a weekly digest, already rendered for its recipient.
+#[derive(Debug, serde::Serialize)]
+pub struct DigestIssue {
+    pub title: String,
+    pub html: String,
+    pub text: String,
+}
+
+/// Issues of the week gathered for a subscriber asking for a digest, with a
+/// link to change their mind.
+pub fn render_weekly_digest(
+    issues: &[DigestIssue],
+    preferences_link: &str,
+) -> Result<Template, tera::Error> {
+    let mut context = Context::new();
+    context.insert("issues", issues);
+    context.insert("preferences_link", preferences_link);
+
+    render("weekly_digest", &context)
+}
+
+/// Who an issue is rendered for. Issues may refer to them, e.g. with
+/// `{{ subscriber.name }}`.
+#[derive(Debug, serde::Serialize)]
+pub struct IssueRecipient<'a> {
+    pub email: &'a str,
+    pub name: &'a str,
+}
+
+/// Renders the content of an issue for one of its recipients. Values are
+/// escaped in the HTML version only.
+pub fn render_issue(
+    html: &str,
+    text: &str,
+    recipient: &IssueRecipient,
+) -> Result<Template, tera::Error> {
+    let mut context = Context::new();
+    context.insert("subscriber", recipient);
+
+    Ok(Template {
+        html: Tera::one_off(html, &context, true)?,
+        text: Tera::one_off(text, &context, false)?,
+    })
+}
+
+/// Plain-text version of an HTML body, so that authors only have to write
+/// the HTML one. Blocks are separated by blank lines, list items are bulleted
+/// and links keep their target next to their text.
+pub fn html_to_text(html: &str) -> String {
+    let document = kuchikiki::parse_html().one(html);
+    let mut text = PlainText::default();
+    text.push_children(&document, false);
+
+    text.finish()
+}
+
+#[derive(Default)]
+struct PlainText {
+    out: String,
+    pending_breaks: usize,
+    pending_space: bool,
+}
+
+impl PlainText {
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    fn break_line(&mut self, breaks: usize) {
+        self.pending_breaks = self.pending_breaks.max(breaks);
+        self.pending_space = false;
+    }
+
+    fn push_str(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        // Breaks before the first line would only add leading blank lines.
+        if !self.out.is_empty() {
+            self.out.push_str(&"\n".repeat(self.pending_breaks));
+        }
+        self.pending_breaks = 0;
+        self.out.push_str(s);
+    }
+
+    /// Pushes text the way browsers render it, with whitespace collapsed.
+    fn push_words(&mut self, s: &str) {
+        let mut words = s.split_whitespace().peekable();
+        if words.peek().is_none() {
+            self.pending_space |= !s.is_empty();
+            return;
+        }
+
+        let spaced = self.pending_space || s.starts_with(char::is_whitespace);
+        if spaced && self.pending_breaks == 0 && !self.at_line_start() {
+            self.out.push(' ');
+        }
+        self.push_str(&words.collect::<Vec<_>>().join(" "));
+        self.pending_space = s.ends_with(char::is_whitespace);
+    }
+
+    fn push_children(&mut self, node: &NodeRef, preformatted: bool) {
+        for child in node.children() {
+            self.push_node(&child, preformatted);
+        }
+    }
+
+    fn push_node(&mut self, node: &NodeRef, preformatted: bool) {
+        if let Some(text) = node.as_text() {
+            match preformatted {
+                true => self.push_str(&text.borrow()),
+                false => self.push_words(&text.borrow()),
+            }
+            return;
+        }
+        let Some(element) = node.as_element() else {
+            self.push_children(node, preformatted);
+            return;
+        };
+
+        match &*element.name.local {
+            "head" | "script" | "style" | "template" => {}
+            "br" => {
+                self.pending_breaks += 1;
+                self.pending_space = false;
+            }
+            "hr" => {
+                self.break_line(2);
+                self.push_str("---");
+                self.break_line(2);
+            }
+            "img" => {
+                if let Some(alt) = element.attributes.borrow().get("alt") {
+                    self.push_words(alt);
+                }
+            }
+            "a" => {
+                let mut label = PlainText::default();
+                label.push_children(node, preformatted);
+                let label = label.finish();
+                self.push_words(&label);
+
+                let attributes = element.attributes.borrow();
+                match attributes.get("href").map(str::trim) {
+                    Some(href) if !href.is_empty() && !href.starts_with('#') && href != label => {
+                        self.push_words(&format!(" ({})", href));
+                    }
+                    _ => {}
+                }
+            }
+            "li" => {
+                self.break_line(1);
+                self.push_str("- ");
+                self.push_children(node, preformatted);
+                self.break_line(1);
+            }
+            "td" | "th" => {
+                self.push_children(node, preformatted);
+                self.pending_space = true;
+            }
+            "pre" => {
+                self.break_line(2);
+                self.push_children(node, true);
+                self.break_line(2);
+            }
+            "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "ul" | "ol" | "table"
+            | "blockquote" => {
+                self.break_line(2);
+                self.push_children(node, preformatted);
+                self.break_line(2);
+            }
+            "div" | "section" | "article" | "header" | "footer" | "nav" | "main" | "aside"
+            | "figure" | "tr" | "dt" | "dd" => {
+                self.break_line(1);
+                self.push_children(node, preformatted);
+                self.break_line(1);
+            }
+            _ => self.push_children(node, preformatted),
+        }
+    }
+
+    fn finish(self) -> String {
+        self.out
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        collaborator_invitation_source, html_to_text, localized_name,
+        render_collaborator_invitation, render_email_change_confirmation, render_issue,
+        render_preferences_link, render_stored_collaborator_invitation,
+        render_subscription_confirmation, render_subscription_welcome, IssueRecipient, StaticEmail,
+        Template, TEMPLATES,
+    };
+    use crate::domain::Locale;
+
+    fn locales(languages: &[&str]) -> Vec<Locale> {
+        languages
+            .iter()
+            .map(|l| Locale::parse(l).unwrap())
+            .collect()
+    }
+
+    fn render_every_email(link: &str) -> Vec<Template> {
+        let mut emails = vec![render_collaborator_invitation(link).unwrap().0];
+        for locales in [vec![], locales(&["pt"])] {
+            emails.push(render_subscription_confirmation(link, &locales).unwrap().0);
+            emails.push(render_subscription_welcome(link, &locales).unwrap().0);
+            emails.push(render_preferences_link(link, &locales).unwrap().0);
+            emails.push(render_email_change_confirmation(link, &locales).unwrap().0);
+        }
+
+        emails
+    }
+
+    /// Whatever ends up in a link, it can't add markup or attributes to the
+    /// HTML part, nor lines to the text part.
+    #[quickcheck_macros::quickcheck]
+    fn user_influenced_values_cannot_break_out_of_emails(link: String) -> bool {
+        let expected = render_every_email("https://example.com/link");
+        let rendered = render_every_email(&link);
+
+        expected.iter().zip(&rendered).all(|(expected, rendered)| {
+            ['<', '>', '"', '\'']
+                .iter()
+                .all(|c| rendered.html.matches(*c).count() == expected.html.matches(*c).count())
+                && rendered.text.lines().count() == expected.text.lines().count()
+        })
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn spliced_emails_are_rendered_like_tera_does(link: String) -> bool {
+        ["subscription_confirmation", "subscription_confirmation.pt"]
+            .into_iter()
+            .all(|name| {
+                let email = StaticEmail::new(name.to_string(), "confirmation_link");
+                let spliced = email.render(&link).unwrap();
+                let rendered = email.render_with_tera(&link).unwrap();
+
+                email.spliced.is_some()
+                    && spliced.html == rendered.html
+                    && spliced.text == rendered.text
+            })
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn stored_emails_are_escaped_like_the_ones_on_disk(link: String) -> bool {
+        let (html, text) = collaborator_invitation_source().unwrap();
+        let stored = render_stored_collaborator_invitation(&html, &text, &link).unwrap();
+        let default = render_collaborator_invitation(&link).unwrap();
+
+        stored.html == default.html && stored.text == default.text
+    }
+
+    #[test]
+    fn emails_are_rendered_in_the_first_language_translated_to() {
+        let link = "https://example.com/confirm";
+
+        let translated = render_subscription_confirmation(link, &locales(&["de", "pt", "en"]));
+        let untranslated = render_subscription_confirmation(link, &locales(&["de"]));
+
+        assert!(translated.unwrap().text.starts_with("Bem-vindo"));
+        assert!(untranslated.unwrap().text.starts_with("Welcome"));
+    }
+
+    #[test]
+    fn pages_fall_back_to_their_untranslated_template() {
+        let templates = TEMPLATES.load();
+
+        assert_eq!(
+            localized_name(&templates, "pages/home.html", &locales(&["de", "pt"])),
+            ("pages/home.pt.html".to_string(), Some("pt".to_string()))
+        );
+        assert_eq!(
+            localized_name(&templates, "pages/home.html", &locales(&["de"])),
+            ("pages/home.html".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn links_are_kept_readable() {
+        let link = "https://example.com/subscriptions/confirm?subscription_token=abc&x=1";
+
+        for template in render_every_email(link) {
+            assert!(template.html.contains(
+                r#"href="https://example.com/subscriptions/confirm?subscription_token=abc&amp;x=1""#
+            ));
+            assert!(template.text.contains(link));
+        }
+    }
+
+    #[test]
+    fn blocks_are_separated_by_blank_lines() {
+        let html = "<html><head><title>Issue</title><style>p { color: red }</style></head>
+            <body><h1>Hello</h1>
+            <p>First   paragraph,
+            on two lines.</p><p>Second<br>line</p></body></html>";
+
+        assert_eq!(
+            html_to_text(html),
+            "Hello\n\nFirst paragraph, on two lines.\n\nSecond\nline"
+        );
+    }
+
+    #[test]
+    fn links_keep_their_target() {
+        let html = r#"<p>Read <a href="https://example.com/post">the post</a> or
+            <a href="https://example.com">https://example.com</a>.</p>"#;
+
+        assert_eq!(
+            html_to_text(html),
+            "Read the post (https://example.com/post) or https://example.com."
+        );
+    }
+
+    #[test]
+    fn list_items_are_bulleted() {
+        let html = "<p>Topics:</p><ul><li>Rust</li><li><b>Web</b> services</li></ul><p>Bye</p>";
+
+        assert_eq!(
+            html_to_text(html),
+            "Topics:\n\n- Rust\n- Web services\n\nBye"
+        );
+    }
+
+    #[test]
+    fn preformatted_text_is_kept_as_is() {
+        let html = "<pre>fn main() {\n    println!();\n}</pre>";
+
+        assert_eq!(html_to_text(html), "fn main() {\n    println!();\n}");
+    }
+
+    #[test]
+    fn issues_are_rendered_for_their_recipient() {
+        let recipient = IssueRecipient {
+            email: "ursula@example.com",
+            name: "<Ursula>",
+        };
+
+        let rendered = render_issue(
+            "<p>Hi {{ subscriber.name }}</p>",
+            "Hi {{ subscriber.name }}",
+            &recipient,
+        )
+        .unwrap();
+
+        assert_eq!(rendered.html, "<p>Hi &lt;Ursula&gt;</p>");
+        assert_eq!(rendered.text, "Hi <Ursula>");
+    }
+
+    #[test]
+    fn broken_issues_fail_to_render() {
+        let recipient = IssueRecipient {
+            email: "ursula@example.com",
+            name: "Ursula",
+        };
+
+        assert!(render_issue("<p>{{ subscriber.age }}</p>", "", &recipient).is_err());
+    }
 }
@@ -0,0 +1,116 @@
+use std::sync::Mutex;
+
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use sha2::{Digest, Sha256};
+
+/// Source of every random token and code handed out to users.
+///
+/// The server uses [`CsprngTokenGenerator`]; tests can inject a
+/// [`SeededTokenGenerator`] to know in advance what will be generated.
+pub trait TokenGenerator: Send + Sync {
+    fn alphanumeric(&self, length: usize) -> String;
+
+    fn digits(&self, length: usize) -> String;
+}
+
+/// Draws from the thread-local CSPRNG, seeded by the operating system.
+pub struct CsprngTokenGenerator;
+
+impl TokenGenerator for CsprngTokenGenerator {
+    fn alphanumeric(&self, length: usize) -> String {
+        sample_alphanumeric(&mut thread_rng(), length)
+    }
+
+    fn digits(&self, length: usize) -> String {
+        sample_digits(&mut thread_rng(), length)
+    }
+}
+
+/// Deterministic generator: two instances built from the same seed yield the
+/// same sequence. Never use it outside of tests.
+pub struct SeededTokenGenerator(Mutex<StdRng>);
+
+impl SeededTokenGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self(Mutex::new(StdRng::seed_from_u64(seed)))
+    }
+}
+
+impl TokenGenerator for SeededTokenGenerator {
+    fn alphanumeric(&self, length: usize) -> String {
+        sample_alphanumeric(&mut *self.0.lock().unwrap(), length)
+    }
+
+    fn digits(&self, length: usize) -> String {
+        sample_digits(&mut *self.0.lock().unwrap(), length)
+    }
+}
+
+fn sample_alphanumeric(rng: &mut impl Rng, length: usize) -> String {
+    std::iter::repeat_with(|| rng.sample(rand::distributions::Alphanumeric))
+        .map(char::from)
+        .take(length)
+        .collect()
+}
+
+fn sample_digits(rng: &mut impl Rng, length: usize) -> String {
+    std::iter::repeat_with(|| rng.sample(rand::distributions::Uniform::new_inclusive(0, 9)))
+        .map(|d| char::from_digit(d, 10).unwrap())
+        .take(length)
+        .collect()
+}
+
+pub fn generate_subscription_token(generator: &dyn TokenGenerator) -> String {
+    generator.alphanumeric(30)
+}
+
+pub fn generate_invitation_token(generator: &dyn TokenGenerator) -> String {
+    generator.alphanumeric(30)
+}
+
+/// Key the payloads delivered to a webhook are signed with.
+pub fn generate_webhook_secret(generator: &dyn TokenGenerator) -> String {
+    generator.alphanumeric(32)
+}
+
+pub fn generate_validation_code(generator: &dyn TokenGenerator) -> String {
+    generator.digits(6)
+}
+
+/// Hex encoded SHA-256 digest of a token, which is what gets stored in
+/// Postgres instead of the token itself.
+///
+/// Tokens are long and random, so an unsalted fast hash is enough to make a
+/// leaked table useless for confirming or registering on someone's behalf.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_token, SeededTokenGenerator, TokenGenerator};
+
+    #[test]
+    fn generators_with_the_same_seed_yield_the_same_tokens() {
+        let (a, b) = (SeededTokenGenerator::new(42), SeededTokenGenerator::new(42));
+
+        assert_eq!(a.alphanumeric(30), b.alphanumeric(30));
+        assert_eq!(a.digits(6), b.digits(6));
+    }
+
+    #[test]
+    fn digits_are_decimal() {
+        let code = SeededTokenGenerator::new(7).digits(6);
+
+        assert_eq!(code.len(), 6);
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn tokens_are_hashed_as_hex_encoded_sha256() {
+        assert_eq!(
+            hash_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
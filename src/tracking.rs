@@ -0,0 +1,433 @@
+use std::time::Duration;
+
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use kuchikiki::traits::TendrilSink;
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::PgPool;
+use tokio::{sync::mpsc, time::Instant};
+use uuid::Uuid;
+
+use crate::configuration::TrackingSettings;
+
+/// An issue as sent to one of its recipients, along with the link they
+/// followed for clicks.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TrackedEmail {
+    #[serde(rename = "i")]
+    pub newsletter_issue_id: Uuid,
+    #[serde(rename = "e")]
+    pub subscriber_email: String,
+    #[serde(rename = "u", default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// Rewrites issues so that opening them and following their links go through
+/// the tracking routes.
+///
+/// Tokens are signed rather than stored: they carry the issue, the recipient
+/// and the link itself, so that a click can't be turned into a redirect to
+/// anywhere else.
+#[derive(Clone)]
+pub struct EmailTracker {
+    enabled: bool,
+    base_url: String,
+    secret: Secret<String>,
+}
+
+impl EmailTracker {
+    pub fn new(enabled: bool, base_url: &str, secret: &Secret<String>) -> Self {
+        Self {
+            enabled,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            secret: secret.clone(),
+        }
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(payload.as_bytes());
+
+        mac
+    }
+
+    pub fn token(&self, tracked: &TrackedEmail) -> String {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let payload =
+            engine.encode(serde_json::to_vec(tracked).expect("Tracked emails are serializable"));
+        let signature = engine.encode(self.mac(&payload).finalize().into_bytes());
+
+        format!("{}.{}", payload, signature)
+    }
+
+    /// Gives back what the token was created for, if it was signed by us.
+    pub fn verify(&self, token: &str) -> Option<TrackedEmail> {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let (payload, signature) = token.split_once('.')?;
+        let signature = engine.decode(signature).ok()?;
+        self.mac(payload).verify_slice(&signature).ok()?;
+
+        serde_json::from_slice(&engine.decode(payload).ok()?).ok()
+    }
+
+    /// Embeds the tracking pixel of the recipient and points the web links
+    /// of the issue to the click-through route. Left untouched when tracking
+    /// is disabled.
+    pub fn track(&self, html: &str, newsletter_issue_id: Uuid, subscriber_email: &str) -> String {
+        if !self.enabled {
+            return html.to_string();
+        }
+
+        let tracked = |url: Option<String>| TrackedEmail {
+            newsletter_issue_id,
+            subscriber_email: subscriber_email.to_string(),
+            url,
+        };
+        let document = kuchikiki::parse_html().one(html);
+
+        for link in document.select("a[href]").expect("Valid selector") {
+            let mut attributes = link.attributes.borrow_mut();
+            let Some(href) = attributes.get("href").map(|href| href.trim().to_string()) else {
+                continue;
+            };
+            if !(href.starts_with("http://") || href.starts_with("https://")) {
+                continue;
+            }
+            let token = self.token(&tracked(Some(href)));
+            attributes.insert("href", format!("{}/t/click/{}", self.base_url, token));
+        }
+
+        let pixel_url = format!("{}/t/open/{}", self.base_url, self.token(&tracked(None)));
+        let pixel = kuchikiki::parse_html()
+            .one(format!(
+                r#"<img src="{}" width="1" height="1" alt="">"#,
+                pixel_url
+            ))
+            .select_first("img")
+            .expect("The pixel is an image")
+            .as_node()
+            .clone();
+        pixel.detach();
+        if let Ok(body) = document.select_first("body") {
+            body.as_node().append(pixel);
+        }
+
+        document.to_string()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmailEventKind {
+    Open,
+    Click,
+}
+
+impl EmailEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailEventKind::Open => "open",
+            EmailEventKind::Click => "click",
+        }
+    }
+}
+
+/// An open or a click, as it happened.
+#[derive(Debug)]
+pub struct EmailEvent {
+    pub kind: EmailEventKind,
+    pub tracked: TrackedEmail,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl EmailEvent {
+    pub fn now(kind: EmailEventKind, tracked: TrackedEmail) -> Self {
+        Self {
+            kind,
+            tracked,
+            occurred_at: Utc::now(),
+        }
+    }
+}
+
+#[tracing::instrument(name = "Record email events", skip_all, fields(events = events.len()))]
+pub async fn record_events(pool: &PgPool, events: &[EmailEvent]) -> Result<(), sqlx::Error> {
+    let mut newsletter_issue_ids = Vec::with_capacity(events.len());
+    let mut subscriber_emails = Vec::with_capacity(events.len());
+    let mut event_types = Vec::with_capacity(events.len());
+    let mut urls = Vec::with_capacity(events.len());
+    let mut occurred_ats = Vec::with_capacity(events.len());
+    for event in events {
+        newsletter_issue_ids.push(event.tracked.newsletter_issue_id);
+        subscriber_emails.push(event.tracked.subscriber_email.clone());
+        event_types.push(event.kind.as_str().to_string());
+        urls.push(event.tracked.url.clone());
+        occurred_ats.push(event.occurred_at);
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO email_events (newsletter_issue_id, subscriber_email, event_type, url, occurred_at)
+        SELECT * FROM UNNEST($1::uuid[], $2::text[], $3::text[], $4::text[], $5::timestamptz[])
+        "#,
+        &newsletter_issue_ids,
+        &subscriber_emails,
+        &event_types,
+        &urls as _,
+        &occurred_ats,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Events buffered per event of a batch before the tracking routes wait on
+// the writer, which only happens when the database can't keep up.
+const BUFFERED_BATCHES: usize = 16;
+
+/// Writes opens and clicks behind the tracking routes, so that they answer
+/// without waiting on the database during the spike following a send.
+///
+/// Events are written in batches, once `event_batch_size` of them are
+/// buffered or the oldest waited `event_flush_milliseconds`. Those still
+/// buffered when the application stops are lost.
+#[derive(Clone)]
+pub struct EmailEventRecorder {
+    sender: mpsc::Sender<EmailEvent>,
+}
+
+impl EmailEventRecorder {
+    /// Starts writing events in the background.
+    pub fn spawn(pool: PgPool, settings: &TrackingSettings) -> Self {
+        let batch_size = settings.event_batch_size.max(1);
+        let (sender, receiver) = mpsc::channel(batch_size * BUFFERED_BATCHES);
+        tokio::spawn(write_events(
+            pool,
+            receiver,
+            batch_size,
+            settings.flush_interval(),
+        ));
+
+        Self { sender }
+    }
+
+    pub async fn record(&self, event: EmailEvent) {
+        if self.sender.send(event).await.is_err() {
+            tracing::error!("The email event writer has stopped, an event was dropped");
+        }
+    }
+}
+
+async fn write_events(
+    pool: PgPool,
+    mut receiver: mpsc::Receiver<EmailEvent>,
+    batch_size: usize,
+    flush_interval: Duration,
+) {
+    let mut batch = Vec::with_capacity(batch_size);
+    // Waits for a first event, then lets others pile up for the flush interval.
+    while receiver.recv_many(&mut batch, batch_size).await > 0 {
+        let deadline = Instant::now() + flush_interval;
+        while batch.len() < batch_size {
+            let limit = batch_size - batch.len();
+            match tokio::time::timeout_at(deadline, receiver.recv_many(&mut batch, limit)).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+
+        // Failing to record events must not get in the way of the recipients.
+        if let Err(e) = record_events(&pool, &batch).await {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                events = batch.len(),
+                "Failed to record email events",
+            );
+        }
+        batch.clear();
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct LinkClicks {
+    pub url: String,
+    pub clicks: i64,
+}
+
+/// How recipients engaged with an issue. Opens are a lower bound, as many
+/// clients don't load images.
+#[derive(Debug, serde::Serialize)]
+pub struct IssueEngagement {
+    pub opens: i64,
+    pub unique_opens: i64,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+    /// Most followed first.
+    pub links: Vec<LinkClicks>,
+}
+
+#[tracing::instrument(name = "Get issue engagement", skip(pool))]
+pub async fn issue_engagement(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<IssueEngagement, sqlx::Error> {
+    let totals = sqlx::query!(
+        r#"
+        SELECT
+            count(*) FILTER (WHERE event_type = 'open') AS "opens!",
+            count(DISTINCT subscriber_email) FILTER (WHERE event_type = 'open') AS "unique_opens!",
+            count(*) FILTER (WHERE event_type = 'click') AS "clicks!",
+            count(DISTINCT subscriber_email) FILTER (WHERE event_type = 'click') AS "unique_clicks!"
+        FROM email_events
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let links = sqlx::query_as!(
+        LinkClicks,
+        r#"
+        SELECT url AS "url!", count(*) AS "clicks!"
+        FROM email_events
+        WHERE newsletter_issue_id = $1 AND event_type = 'click'
+        GROUP BY url
+        ORDER BY count(*) DESC, url
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(IssueEngagement {
+        opens: totals.opens,
+        unique_opens: totals.unique_opens,
+        clicks: totals.clicks,
+        unique_clicks: totals.unique_clicks,
+        links,
+    })
+}
+
+/// How the recipients of one of the subjects of an A/B tested issue opened
+/// it.
+#[derive(Debug, serde::Serialize)]
+pub struct SubjectVariantEngagement {
+    pub variant: String,
+    pub subject: String,
+    pub delivered: i64,
+    pub unique_opens: i64,
+}
+
+/// One entry per subject, none when the issue doesn't test two of them.
+#[tracing::instrument(name = "Get subject variant engagement", skip(pool))]
+pub async fn subject_variant_engagement(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Vec<SubjectVariantEngagement>, sqlx::Error> {
+    sqlx::query_as!(
+        SubjectVariantEngagement,
+        r#"
+        SELECT
+            v.variant,
+            COALESCE(CASE WHEN v.variant = 'b' THEN i.alternative_title END, i.title) AS "subject!",
+            count(DISTINCT d.subscriber_email) AS "delivered!",
+            count(DISTINCT e.subscriber_email) AS "unique_opens!"
+        FROM issue_subject_variants v
+        JOIN newsletter_issues i ON i.newsletter_issue_id = v.newsletter_issue_id
+        LEFT JOIN issue_deliveries d ON d.newsletter_issue_id = v.newsletter_issue_id
+            AND d.subscriber_email = v.subscriber_email
+        LEFT JOIN email_events e ON e.newsletter_issue_id = v.newsletter_issue_id
+            AND e.subscriber_email = v.subscriber_email
+            AND e.event_type = 'open'
+        WHERE v.newsletter_issue_id = $1
+        GROUP BY v.variant, i.title, i.alternative_title
+        ORDER BY v.variant
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_none, assert_some};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::{EmailTracker, TrackedEmail};
+
+    fn tracker(enabled: bool) -> EmailTracker {
+        EmailTracker::new(
+            enabled,
+            "https://example.com/",
+            &Secret::new("secret".into()),
+        )
+    }
+
+    fn tracked(url: Option<&str>) -> TrackedEmail {
+        TrackedEmail {
+            newsletter_issue_id: Uuid::new_v4(),
+            subscriber_email: "ursula@example.com".into(),
+            url: url.map(String::from),
+        }
+    }
+
+    #[test]
+    fn tokens_are_verified() {
+        let tracker = tracker(true);
+        let tracked = tracked(Some("https://example.org"));
+
+        let token = tracker.token(&tracked);
+
+        assert_eq!(assert_some!(tracker.verify(&token)), tracked);
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        let tracker = tracker(true);
+        let token = tracker.token(&tracked(Some("https://example.org")));
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = tracker.token(&tracked(Some("https://evil.example.com")));
+        let (payload, _) = forged.split_once('.').unwrap();
+
+        assert_none!(tracker.verify(&format!("{}.{}", payload, signature)));
+        assert_none!(tracker.verify("not-a-token"));
+    }
+
+    #[test]
+    fn tokens_of_another_secret_are_rejected() {
+        let other = EmailTracker::new(true, "https://example.com", &Secret::new("other".into()));
+
+        let token = other.token(&tracked(None));
+
+        assert_none!(tracker(true).verify(&token));
+    }
+
+    #[test]
+    fn web_links_are_rewritten_and_a_pixel_is_added() {
+        let tracker = tracker(true);
+        let html = r#"<p><a href="https://example.org/post">Post</a> <a href="mailto:a@example.com">Mail</a></p>"#;
+
+        let tracked = tracker.track(html, Uuid::new_v4(), "ursula@example.com");
+
+        assert!(tracked.contains(r#"href="https://example.com/t/click/"#));
+        assert!(!tracked.contains(r#"href="https://example.org/post""#));
+        assert!(tracked.contains(r#"href="mailto:a@example.com""#));
+        assert!(tracked.contains(r#"src="https://example.com/t/open/"#));
+    }
+
+    #[test]
+    fn issues_are_left_untouched_when_disabled() {
+        let html = r#"<p><a href="https://example.org/post">Post</a></p>"#;
+
+        let tracked = tracker(false).track(html, Uuid::new_v4(), "ursula@example.com");
+
+        assert_eq!(tracked, html);
+    }
+}
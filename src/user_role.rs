@@ -1,6 +1,24 @@
-#[derive(Debug, serde::Serialize, serde::Deserialize, sqlx::Type, PartialEq)]
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize, sqlx::Type, PartialEq)]
 #[sqlx(type_name = "user_role", rename_all = "lowercase")]
 pub enum UserRole {
     Admin,
     Collaborator,
 }
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_user_role(user_id: &Uuid, pool: &PgPool) -> Result<UserRole, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        SELECT role as "role!: UserRole"
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .map(|record| record.role)
+}
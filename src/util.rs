@@ -1,4 +1,9 @@
-use actix_web::{http::header::LOCATION, HttpResponse};
+use actix_web::{
+    http::header::{ACCEPT_LANGUAGE, LOCATION},
+    HttpRequest, HttpResponse,
+};
+
+use crate::domain::Locale;
 
 pub fn e500<T>(e: T) -> actix_web::Error
 where
@@ -12,3 +17,14 @@ pub fn see_other(location: &str) -> HttpResponse {
         .insert_header((LOCATION, location))
         .finish()
 }
+
+/// Languages the client reads, most preferred first, from its
+/// `Accept-Language` header.
+pub fn accepted_locales(request: &HttpRequest) -> Vec<Locale> {
+    request
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|h| h.to_str().ok())
+        .map(Locale::from_accept_language)
+        .unwrap_or_default()
+}
@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use reqwest::header::CONTENT_TYPE;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{field::display, Span};
+use uuid::Uuid;
+
+use crate::{
+    configuration::{DeliveryQueueSettings, Settings},
+    issue_delivery_worker::ExecutionOutcome,
+    startup::get_connection_pool,
+    webhooks::{log_delivery, signature_header, DeliveryAttempt, SIGNATURE_HEADER},
+};
+
+struct Task {
+    webhook_id: Uuid,
+    url: String,
+    secret: String,
+    event_id: i64,
+    occurred_at: DateTime<Utc>,
+    n_retries: i16,
+}
+
+#[derive(serde::Serialize)]
+struct EventPayload {
+    id: i64,
+    #[serde(rename = "type")]
+    event_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subscriber_id: Option<Uuid>,
+    details: serde_json::Value,
+    occurred_at: DateTime<Utc>,
+}
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_task(pool: &PgPool) -> Result<Option<(PgTransaction, Task)>, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+
+    let task = sqlx::query_as!(
+        Task,
+        r#"
+        SELECT q.webhook_id, w.url, w.secret, q.event_id, q.occurred_at, q.n_retries
+        FROM webhook_delivery_queue q
+        JOIN webhooks w ON w.webhook_id = q.webhook_id
+        WHERE q.execute_after <= now()
+        ORDER BY q.event_id
+        FOR UPDATE OF q
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    Ok(task.map(|task| (transaction, task)))
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_task(mut transaction: PgTransaction, task: &Task) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM webhook_delivery_queue
+        WHERE webhook_id = $1 AND event_id = $2
+        "#,
+        task.webhook_id,
+        task.event_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Schedules another attempt with an exponential backoff or, once the
+/// retries are exhausted, gives up on the event.
+#[tracing::instrument(skip_all)]
+async fn handle_failed_task(
+    mut transaction: PgTransaction,
+    task: &Task,
+    settings: &DeliveryQueueSettings,
+) -> Result<(), anyhow::Error> {
+    let n_retries = task.n_retries + 1;
+
+    if n_retries >= settings.max_retries {
+        tracing::error!("Giving up on the delivery of an event to a webhook");
+
+        return delete_task(transaction, task).await;
+    }
+
+    let backoff = settings.retry_backoff() * 2i32.saturating_pow(task.n_retries as u32);
+    sqlx::query!(
+        r#"
+        UPDATE webhook_delivery_queue
+        SET n_retries = $1, execute_after = $2
+        WHERE webhook_id = $3 AND event_id = $4
+        "#,
+        n_retries,
+        Utc::now() + backoff,
+        task.webhook_id,
+        task.event_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Events of a subscriber are named after them, e.g. `subscriber.confirmed`,
+/// while events about the whole list are recorded under their full name.
+fn webhook_event_type(event_type: &str, subscriber_id: Option<Uuid>) -> String {
+    match subscriber_id {
+        Some(_) => format!("subscriber.{}", event_type),
+        None => event_type.to_string(),
+    }
+}
+
+/// `None` if the event was purged since it was enqueued.
+#[tracing::instrument(skip_all)]
+async fn get_event(pool: &PgPool, task: &Task) -> Result<Option<EventPayload>, anyhow::Error> {
+    // The timestamp restricts the lookup to the partition holding the event.
+    let event = sqlx::query!(
+        r#"
+        SELECT event_id, event_type, subscriber_id, details, occurred_at
+        FROM subscriber_events
+        WHERE event_id = $1 AND occurred_at = $2
+        "#,
+        task.event_id,
+        task.occurred_at,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(event.map(|event| EventPayload {
+        id: event.event_id,
+        event_type: webhook_event_type(&event.event_type, event.subscriber_id),
+        subscriber_id: event.subscriber_id,
+        details: event.details,
+        occurred_at: event.occurred_at,
+    }))
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        webhook_id=tracing::field::Empty,
+        event_id=tracing::field::Empty
+    ),
+    err
+)]
+pub async fn try_execute_task(
+    pool: &PgPool,
+    http_client: &reqwest::Client,
+    settings: &DeliveryQueueSettings,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let (mut transaction, task) = match dequeue_task(pool).await? {
+        Some(task) => task,
+        None => return Ok(ExecutionOutcome::EmptyQueue),
+    };
+
+    Span::current()
+        .record("webhook_id", display(task.webhook_id))
+        .record("event_id", display(task.event_id));
+
+    let event = match get_event(pool, &task).await? {
+        Some(event) => event,
+        None => {
+            delete_task(transaction, &task).await?;
+
+            return Ok(ExecutionOutcome::TaskCompleted);
+        }
+    };
+
+    let body = serde_json::to_vec(&event).context("Failed to serialize the event")?;
+    let signature = signature_header(&task.secret, Utc::now().timestamp(), &body);
+    let outcome = http_client
+        .post(&task.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .body(body)
+        .send()
+        .await;
+    let status_code = outcome
+        .as_ref()
+        .ok()
+        .map(|response| response.status().as_u16());
+    let outcome = outcome.and_then(|response| response.error_for_status());
+
+    log_delivery(
+        &mut transaction,
+        &DeliveryAttempt {
+            webhook_id: task.webhook_id,
+            event_id: task.event_id,
+            event_type: &event.event_type,
+            attempt: task.n_retries + 1,
+            status_code,
+            error: outcome.as_ref().err().map(|e| e.to_string()),
+        },
+    )
+    .await?;
+
+    if let Err(e) = outcome {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to deliver event to a webhook. Retrying later.",
+        );
+
+        handle_failed_task(transaction, &task, settings).await?;
+
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+
+    delete_task(transaction, &task).await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    http_client: reqwest::Client,
+    settings: DeliveryQueueSettings,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_task(&pool, &http_client, &settings).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    if !configuration.features.webhooks {
+        // Nothing will ever be delivered, but the application must not stop
+        // because one of its workers did.
+        return std::future::pending().await;
+    }
+
+    let connection_pool = get_connection_pool(&configuration.database);
+    let http_client = configuration.webhooks.client();
+
+    worker_loop(connection_pool, http_client, configuration.delivery_queue).await
+}
@@ -0,0 +1,232 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::domain::WebhookUrl;
+
+/// Header of every delivery, with the signature of its payload.
+pub const SIGNATURE_HEADER: &str = "Webhook-Signature";
+
+/// Attempts kept in the delivery log of each webhook, the oldest ones are
+/// dropped first.
+const DELIVERY_LOG_LENGTH: i64 = 100;
+
+#[derive(Debug, serde::Serialize)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A webhook as it was just registered. Its secret isn't shown again.
+#[derive(Debug, serde::Serialize)]
+pub struct RegisteredWebhook {
+    pub id: Uuid,
+    pub url: String,
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Store webhook", skip(pool, secret))]
+pub async fn store_webhook(
+    pool: &PgPool,
+    url: &WebhookUrl,
+    secret: &str,
+) -> Result<RegisteredWebhook, sqlx::Error> {
+    sqlx::query_as!(
+        RegisteredWebhook,
+        r#"
+        INSERT INTO webhooks (webhook_id, url, secret, created_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING webhook_id as id, url, secret, created_at
+        "#,
+        Uuid::new_v4(),
+        url.as_ref(),
+        secret,
+        Utc::now(),
+    )
+    .fetch_one(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get webhooks", skip(pool))]
+pub async fn get_webhooks(pool: &PgPool) -> Result<Vec<Webhook>, sqlx::Error> {
+    sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT webhook_id as id, url, created_at
+        FROM webhooks
+        ORDER BY created_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// `false` if there was no such webhook.
+#[tracing::instrument(name = "Remove webhook", skip(pool))]
+pub async fn remove_webhook(pool: &PgPool, webhook_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM webhooks
+        WHERE webhook_id = $1
+        "#,
+        webhook_id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// An attempt to deliver an event to a webhook.
+#[derive(Debug, serde::Serialize)]
+pub struct WebhookDelivery {
+    pub webhook_id: Uuid,
+    pub url: String,
+    pub event_id: i64,
+    pub event_type: String,
+    /// Starts at 1.
+    pub attempt: i16,
+    /// Missing if the endpoint couldn't be reached at all.
+    pub status_code: Option<i16>,
+    pub error: Option<String>,
+    pub attempted_at: DateTime<Utc>,
+}
+
+/// What came of an attempt, as logged by the webhook delivery worker.
+pub struct DeliveryAttempt<'a> {
+    pub webhook_id: Uuid,
+    pub event_id: i64,
+    pub event_type: &'a str,
+    pub attempt: i16,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// Appends the attempt to the delivery log of its webhook, dropping the
+/// oldest attempts beyond the last [`DELIVERY_LOG_LENGTH`].
+#[tracing::instrument(name = "Log webhook delivery", skip_all)]
+pub async fn log_delivery(
+    transaction: &mut Transaction<'_, Postgres>,
+    attempt: &DeliveryAttempt<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_deliveries (
+            webhook_id, event_id, event_type, attempt, status_code, error, attempted_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, now())
+        "#,
+        attempt.webhook_id,
+        attempt.event_id,
+        attempt.event_type,
+        attempt.attempt,
+        attempt.status_code.map(|code| code as i16),
+        attempt.error,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM webhook_deliveries
+        WHERE webhook_id = $1 AND delivery_id <= (
+            SELECT delivery_id
+            FROM webhook_deliveries
+            WHERE webhook_id = $1
+            ORDER BY delivery_id DESC
+            OFFSET $2
+            LIMIT 1
+        )
+        "#,
+        attempt.webhook_id,
+        DELIVERY_LOG_LENGTH,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Latest attempts to deliver to the webhook, or to any webhook if none is
+/// given, most recent first.
+#[tracing::instrument(name = "Get recent webhook deliveries", skip(pool))]
+pub async fn recent_deliveries(
+    pool: &PgPool,
+    webhook_id: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        WebhookDelivery,
+        r#"
+        SELECT
+            d.webhook_id, w.url, d.event_id, d.event_type, d.attempt, d.status_code,
+            d.error, d.attempted_at
+        FROM webhook_deliveries d
+        JOIN webhooks w ON w.webhook_id = d.webhook_id
+        WHERE $1::uuid IS NULL OR d.webhook_id = $1
+        ORDER BY d.delivery_id DESC
+        LIMIT $2
+        "#,
+        webhook_id,
+        limit,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Value of the [`SIGNATURE_HEADER`]: `t=<timestamp>,v1=<signature>`, where
+/// the signature is the hex encoded HMAC-SHA256 of `<timestamp>.<body>` keyed
+/// with the secret of the webhook. Endpoints can refuse deliveries whose
+/// timestamp is too old to be anything but a replay.
+pub fn signature_header(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    format!(
+        "t={},v1={}",
+        timestamp,
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    use super::signature_header;
+
+    fn signature(header: &str) -> &str {
+        header.split_once(",v1=").unwrap().1
+    }
+
+    #[test]
+    fn the_signature_covers_the_timestamp_and_the_body() {
+        let header = signature_header("secret", 1700000000, br#"{"id":1}"#);
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(br#"1700000000.{"id":1}"#);
+        let expected = hex::encode(mac.finalize().into_bytes());
+        assert_eq!(header, format!("t=1700000000,v1={}", expected));
+    }
+
+    #[test]
+    fn other_secrets_timestamps_or_bodies_give_other_signatures() {
+        let header = signature_header("secret", 1700000000, b"{}");
+
+        for other in [
+            signature_header("other", 1700000000, b"{}"),
+            signature_header("secret", 1700000001, b"{}"),
+            signature_header("secret", 1700000000, b"[]"),
+        ] {
+            assert_ne!(signature(&other), signature(&header));
+        }
+    }
+}
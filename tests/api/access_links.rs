@@ -0,0 +1,204 @@
+use chrono::Duration;
+use newsletter::delegated_access::{grant_access, DelegatedScope};
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn login(app: &TestApp) {
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+}
+
+async fn create_access_link(app: &TestApp, recipient: &str, days: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/access_links", app.address))
+        .form(&[
+            ("recipient", recipient),
+            ("scope", "view_analytics"),
+            ("days", days),
+            ("csrf_token", &app.csrf_token().await),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn get_access_links_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/admin/access_links", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+/// The token of the link shown once the access link is created.
+async fn create_access_token(app: &TestApp) -> String {
+    let response = create_access_link(app, "Sponsor", "7").await;
+    assert_is_redirect_to(&response, "/admin/access_links");
+
+    let html = get_access_links_html(app).await;
+    let (_, rest) = html.split_once("/shared/").expect("No access link shown");
+
+    rest.split(|c: char| c.is_whitespace() || c == '<')
+        .next()
+        .unwrap()
+        .to_string()
+}
+
+async fn get_shared(app: &TestApp, token: &str, path: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/shared/{}{}", app.address, token, path))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn publish_issue(app: &TestApp) -> String {
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        },
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+        .to_string()
+}
+
+#[tokio::test]
+async fn access_links_show_the_stats_of_the_issues() {
+    let app = spawn_app().await;
+    let newsletter_issue_id = publish_issue(&app).await;
+    login(&app).await;
+    let token = create_access_token(&app).await;
+
+    let response = get_shared(&app, &token, "").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("Newsletter title"));
+
+    let response = get_shared(
+        &app,
+        &token,
+        &format!("/issues/{}/stats", newsletter_issue_id),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("Newsletter title"));
+    assert!(!html.contains("/admin/dashboard"));
+}
+
+#[tokio::test]
+async fn every_request_made_with_an_access_link_is_logged() {
+    let app = spawn_app().await;
+    login(&app).await;
+    let token = create_access_token(&app).await;
+    get_shared(&app, &token, "").await;
+    app.api_client
+        .post(format!("{}/shared/{}", app.address, token))
+        .send()
+        .await
+        .unwrap();
+
+    let log = sqlx::query!("SELECT method, path, status FROM delegated_access_log ORDER BY id")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+
+    assert_eq!(log.len(), 2);
+    assert_eq!((log[0].method.as_str(), log[0].path.as_str()), ("GET", "/"));
+    assert_eq!(log[0].status, 200);
+    assert_eq!(log[1].method, "POST");
+    assert_eq!(log[1].status, 403);
+}
+
+#[tokio::test]
+async fn revoked_access_links_no_longer_work() {
+    let app = spawn_app().await;
+    login(&app).await;
+    let token = create_access_token(&app).await;
+    let grant_id = sqlx::query!("SELECT grant_id FROM delegated_access_grants")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .grant_id;
+
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/access_links/{}/revoke",
+            app.address, grant_id
+        ))
+        .header("X-CSRF-Token", app.csrf_token().await)
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/access_links");
+
+    let response = get_shared(&app, &token, "").await;
+    assert_eq!(response.status().as_u16(), 410);
+    let revoked_by = sqlx::query!("SELECT revoked_by FROM delegated_access_grants")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .revoked_by;
+    assert_eq!(revoked_by, Some(app.test_user.user_id));
+}
+
+#[tokio::test]
+async fn expired_access_links_no_longer_work() {
+    let app = spawn_app().await;
+    let capability = grant_access(
+        &app.db_pool,
+        app.test_user.user_id,
+        "Sponsor",
+        DelegatedScope::ViewAnalytics,
+        Duration::days(-1),
+    )
+    .await
+    .unwrap();
+    let token = app.capability_signer.token(&capability);
+
+    let response = get_shared(&app, &token, "").await;
+
+    assert_eq!(response.status().as_u16(), 410);
+}
+
+#[tokio::test]
+async fn forged_access_links_are_not_found() {
+    let app = spawn_app().await;
+
+    let response = get_shared(&app, "eyJnIjoiMSJ9.c2lnbmF0dXJl", "").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn access_links_must_say_who_they_are_for() {
+    let app = spawn_app().await;
+    login(&app).await;
+
+    let response = create_access_link(&app, " ", "7").await;
+    assert_is_redirect_to(&response, "/admin/access_links");
+
+    let html = get_access_links_html(&app).await;
+    assert!(html.contains("Tell who the access link is for."));
+}
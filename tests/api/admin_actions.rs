@@ -0,0 +1,164 @@
+use newsletter::newsletter_list::DEFAULT_LIST_ID;
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with_configuration, TestApp};
+
+async fn insert_subscriber(app: &TestApp, email: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, 'le guin', now(), 'confirmed')
+        "#,
+        Uuid::new_v4(),
+        DEFAULT_LIST_ID,
+        email,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+}
+
+async fn count_subscribers(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT count(*) as "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count subscribers.")
+        .count
+}
+
+async fn get_pending_action_id(app: &TestApp) -> Uuid {
+    sqlx::query!("SELECT id FROM admin_actions WHERE status = 'pending'")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch pending action.")
+        .id
+}
+
+async fn login(app: &TestApp, username: &str, password: &str) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": username,
+            "password": password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+async fn spawn_app_requiring_approval() -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.application.two_person_rule.enabled = true;
+        c.application.two_person_rule.threshold = 1;
+    })
+    .await
+}
+
+const EMAILS: &str = "ursula@gmail.com\nle_guin@gmail.com";
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_delete_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_delete_subscribers(&serde_json::json!({"emails": EMAILS}))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn you_must_be_admin_to_delete_subscribers() {
+    let app = spawn_app().await;
+    let collaborator = app.create_collaborator().await;
+    login(&app, &collaborator.username, &collaborator.password).await;
+
+    let response = app
+        .post_delete_subscribers(&serde_json::json!({"emails": EMAILS}))
+        .await;
+
+    assert_eq!(405, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribers_are_deleted_right_away_when_approval_is_not_required() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "ursula@gmail.com").await;
+    insert_subscriber(&app, "le_guin@gmail.com").await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+
+    let response = app
+        .post_delete_subscribers(&serde_json::json!({"emails": EMAILS}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/actions");
+
+    let html_page = app.get_admin_actions_html().await;
+    assert!(html_page.contains("<p><i>2 subscribers were deleted.</i></p>"));
+    assert_eq!(count_subscribers(&app).await, 0);
+}
+
+#[tokio::test]
+async fn bulk_deletion_waits_for_the_approval_of_another_admin() {
+    let app = spawn_app_requiring_approval().await;
+    insert_subscriber(&app, "ursula@gmail.com").await;
+    insert_subscriber(&app, "le_guin@gmail.com").await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+
+    let response = app
+        .post_delete_subscribers(&serde_json::json!({"emails": EMAILS}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/actions");
+
+    let html_page = app.get_admin_actions_html().await;
+    assert!(html_page
+        .contains("<p><i>The deletion is waiting for the approval of another admin.</i></p>"));
+    assert_eq!(count_subscribers(&app).await, 2);
+
+    let action_id = get_pending_action_id(&app).await;
+    let response = app.post_review_admin_action(action_id, "approve").await;
+    assert_is_redirect_to(&response, "/admin/actions");
+
+    let html_page = app.get_admin_actions_html().await;
+    assert!(html_page.contains("<p><i>The action must be reviewed by another admin.</i></p>"));
+    assert_eq!(count_subscribers(&app).await, 2);
+
+    app.post_logout().await;
+    let other_admin = app.create_admin().await;
+    login(&app, &other_admin.username, &other_admin.password).await;
+
+    let response = app.post_review_admin_action(action_id, "approve").await;
+    assert_is_redirect_to(&response, "/admin/actions");
+
+    let html_page = app.get_admin_actions_html().await;
+    assert!(html_page.contains("<p><i>The action was approved and executed.</i></p>"));
+    assert_eq!(count_subscribers(&app).await, 0);
+}
+
+#[tokio::test]
+async fn rejected_bulk_deletion_is_not_executed() {
+    let app = spawn_app_requiring_approval().await;
+    insert_subscriber(&app, "ursula@gmail.com").await;
+    insert_subscriber(&app, "le_guin@gmail.com").await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+
+    app.post_delete_subscribers(&serde_json::json!({"emails": EMAILS}))
+        .await;
+    let action_id = get_pending_action_id(&app).await;
+
+    app.post_logout().await;
+    let other_admin = app.create_admin().await;
+    login(&app, &other_admin.username, &other_admin.password).await;
+
+    let response = app.post_review_admin_action(action_id, "reject").await;
+    assert_is_redirect_to(&response, "/admin/actions");
+
+    let html_page = app.get_admin_actions_html().await;
+    assert!(html_page.contains("<p><i>The action was rejected.</i></p>"));
+    assert_eq!(count_subscribers(&app).await, 2);
+
+    let response = app.post_review_admin_action(action_id, "approve").await;
+    assert_is_redirect_to(&response, "/admin/actions");
+
+    let html_page = app.get_admin_actions_html().await;
+    assert!(html_page.contains("<p><i>The action is no longer pending.</i></p>"));
+    assert_eq!(count_subscribers(&app).await, 2);
+}
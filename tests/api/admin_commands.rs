@@ -0,0 +1,138 @@
+use newsletter::newsletter_list::DEFAULT_LIST_ID;
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn login(app: &TestApp, username: &str, password: &str) {
+    app.post_login(&serde_json::json!({
+        "username": username,
+        "password": password,
+    }))
+    .await;
+}
+
+async fn get_commands(app: &TestApp, query: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/admin/commands?{}", &app.address, query))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn insert_subscriber(app: &TestApp, email: &str, name: &str) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, $4, now(), 'confirmed')
+        "#,
+        subscriber_id,
+        DEFAULT_LIST_ID,
+        email,
+        name,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+
+    subscriber_id
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_list_commands() {
+    let app = spawn_app().await;
+
+    let response = get_commands(&app, "").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn commands_are_filtered_by_the_query() {
+    let app = spawn_app().await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+
+    let commands: serde_json::Value = get_commands(&app, "q=INVITE").await.json().await.unwrap();
+
+    assert_eq!(
+        commands["commands"],
+        serde_json::json!([{
+            "kind": "form",
+            "title": "Invite collaborator",
+            "url": "/admin/collaborator",
+            "fields": ["email"],
+        }])
+    );
+}
+
+#[tokio::test]
+async fn collaborators_only_get_the_commands_they_may_run() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "ursula@gmail.com", "le guin").await;
+    let collaborator = app.create_collaborator().await;
+    login(&app, &collaborator.username, &collaborator.password).await;
+
+    let commands: serde_json::Value = get_commands(&app, "").await.json().await.unwrap();
+    let titles: Vec<&str> = commands["commands"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|command| command["title"].as_str().unwrap())
+        .collect();
+
+    assert!(titles.contains(&"Dashboard"));
+    assert!(!titles.contains(&"Pending actions"));
+    assert!(!titles.contains(&"Invite collaborator"));
+    assert_eq!(commands["subscribers"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn admins_can_jump_to_matching_subscribers() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(&app, "ursula@gmail.com", "Ursula <b>Le Guin</b>").await;
+    insert_subscriber(&app, "octavia@gmail.com", "Octavia Butler").await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+
+    let commands: serde_json::Value = get_commands(&app, "q=le%20guin")
+        .await
+        .json()
+        .await
+        .unwrap();
+    let subscribers = commands["subscribers"].as_array().unwrap();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0]["email"], "ursula@gmail.com");
+
+    let url = subscribers[0]["url"].as_str().unwrap();
+    assert_eq!(url, format!("/admin/subscribers/{}", subscriber_id));
+    let html_page = app
+        .api_client
+        .get(format!("{}{}", &app.address, url))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("ursula@gmail.com"));
+    assert!(html_page.contains("Ursula &lt;b&gt;Le Guin&lt;/b&gt;"));
+}
+
+#[tokio::test]
+async fn commands_can_be_rendered_as_an_html_fragment() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "ursula@gmail.com", "<script>").await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+    let csrf_token = app.csrf_token().await;
+
+    let response = get_commands(&app, "format=html").await;
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/html; charset=utf-8"
+    );
+    let fragment = response.text().await.unwrap();
+
+    assert!(fragment.contains(r#"<a href="/admin/actions">Pending actions</a>"#));
+    assert!(fragment.contains(r#"<form action="/admin/logout" method="post">"#));
+    assert!(fragment.contains(&format!(r#"value="{}""#, csrf_token)));
+    assert!(fragment.contains("ursula@gmail.com (&lt;script&gt;)"));
+}
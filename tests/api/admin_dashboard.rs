@@ -1,4 +1,8 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use newsletter::newsletter_list::DEFAULT_LIST_ID;
+use uuid::Uuid;
+use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with_configuration};
 
 #[tokio::test]
 async fn you_must_be_logged_in_to_access_the_admin_dashboard() {
@@ -38,3 +42,138 @@ async fn logout_clears_session_state() {
 
     assert_is_redirect_to(&response, "/login");
 }
+
+#[tokio::test]
+async fn the_admin_ui_can_be_mounted_at_another_path() {
+    let app = spawn_app_with_configuration(|c| {
+        c.application.admin_base_path = "/backstage".to_string().try_into().unwrap();
+    })
+    .await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/backstage/dashboard");
+
+    let html_page = app
+        .api_client
+        .get(format!("{}/backstage/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(r#"href="/backstage/password""#));
+    assert!(html_page.contains(r#"action="/backstage/logout""#));
+
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn the_dashboard_warns_when_links_in_emails_would_not_reach_the_app() {
+    let checker = MockServer::start().await;
+    let app = spawn_app_with_configuration(|c| {
+        c.base_url_check.enabled = true;
+        c.base_url_check.checker_url = checker.uri();
+    })
+    .await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(502))
+        .expect(1)
+        .mount(&checker)
+        .await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("which is not reachable"));
+    assert!(html_page.contains("/health_check/probe"));
+}
+
+#[tokio::test]
+async fn the_navigation_menu_only_lists_the_pages_a_user_can_use() {
+    let app = spawn_app().await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(r#"<a href="/admin/password">Change password</a>"#));
+    assert!(html_page.contains(r#"<a href="/admin/actions">Pending actions</a>"#));
+
+    app.post_logout().await;
+    let collaborator = app.create_collaborator().await;
+    app.post_login(&serde_json::json!({
+        "username": &collaborator.username,
+        "password": &collaborator.password,
+    }))
+    .await;
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(r#"<a href="/admin/password">Change password</a>"#));
+    assert!(!html_page.contains("/admin/actions"));
+}
+
+#[tokio::test]
+async fn the_dashboard_shows_the_key_stats_of_the_newsletter() {
+    let app = spawn_app().await;
+
+    for (email, status) in [
+        ("ursula@example.com", "confirmed"),
+        ("octavia@example.com", "confirmed"),
+        ("nk@example.com", "pending_confirmation"),
+    ] {
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+            VALUES ($1, $2, $3, 'name', now(), $4::text::subscription_status)
+            "#,
+            Uuid::new_v4(),
+            DEFAULT_LIST_ID,
+            email,
+            status,
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues
+            (newsletter_issue_id, list_id, title, text_content, html_content, published_at)
+        VALUES ($1, $2, 'The dispossessed', 'text', '<p>html</p>', now())
+        "#,
+        newsletter_issue_id,
+        DEFAULT_LIST_ID,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let html_page = app.get_admin_dashboard_html().await;
+
+    assert!(html_page.contains("<tr><th>Confirmed subscribers</th><td>2</td></tr>"));
+    assert!(html_page.contains("<tr><th>Pending confirmations</th><td>1</td></tr>"));
+    assert!(html_page.contains(&format!(
+        r#"<a href="/admin/issues/{}/stats">The dispossessed</a>"#,
+        newsletter_issue_id
+    )));
+    assert!(html_page.contains("<polyline"));
+}
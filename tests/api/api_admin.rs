@@ -0,0 +1,347 @@
+use newsletter::newsletter_list::DEFAULT_LIST_ID;
+use reqwest::Method;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with_configuration, TestApp};
+
+async fn insert_subscriber(app: &TestApp, email: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, 'le guin', now(), 'confirmed')
+        "#,
+        Uuid::new_v4(),
+        DEFAULT_LIST_ID,
+        email,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+}
+
+fn admin_api_request(
+    app: &TestApp,
+    method: Method,
+    path: &str,
+    username: &str,
+    password: &str,
+) -> reqwest::RequestBuilder {
+    app.api_client
+        .request(method, format!("{}/api/v1/admin{}", &app.address, path))
+        .basic_auth(username, Some(password))
+}
+
+async fn login(app: &TestApp, username: &str, password: &str) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": username,
+            "password": password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn requests_without_credentials_or_session_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/api/v1/admin/actions", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        r#"Basic realm="api""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[tokio::test]
+async fn admins_can_delete_subscribers_with_their_credentials() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "ursula@gmail.com").await;
+
+    let response = admin_api_request(
+        &app,
+        Method::POST,
+        "/subscribers/delete",
+        &app.test_user.username,
+        &app.test_user.password,
+    )
+    .json(&serde_json::json!({ "emails": ["ursula@gmail.com"] }))
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["deleted"], 1);
+}
+
+#[tokio::test]
+async fn invalid_emails_are_reported_as_problems() {
+    let app = spawn_app().await;
+
+    let response = admin_api_request(
+        &app,
+        Method::POST,
+        "/subscribers/delete",
+        &app.test_user.username,
+        &app.test_user.password,
+    )
+    .json(&serde_json::json!({ "emails": ["not-an-email"] }))
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["type"], "/problems/invalid-emails");
+}
+
+#[tokio::test]
+async fn collaborators_cannot_manage_subscribers() {
+    let app = spawn_app().await;
+    let collaborator = app.create_collaborator().await;
+
+    let response = admin_api_request(
+        &app,
+        Method::POST,
+        "/subscribers/delete",
+        &collaborator.username,
+        &collaborator.password,
+    )
+    .json(&serde_json::json!({ "emails": ["ursula@gmail.com"] }))
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 405);
+}
+
+#[tokio::test]
+async fn logged_in_users_must_send_the_csrf_token_to_change_state() {
+    let app = spawn_app().await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+    insert_subscriber(&app, "ursula@gmail.com").await;
+    let url = format!("{}/api/v1/admin/subscribers/delete", &app.address);
+    let body = serde_json::json!({ "emails": ["ursula@gmail.com"] });
+
+    let response = app.api_client.post(&url).json(&body).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app
+        .api_client
+        .post(&url)
+        .header("X-CSRF-Token", app.csrf_token().await)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn admins_can_invite_collaborators() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = admin_api_request(
+        &app,
+        Method::POST,
+        "/collaborators",
+        &app.test_user.username,
+        &app.test_user.password,
+    )
+    .json(&serde_json::json!({ "email": "ursula_le_guin@gmail.com" }))
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["validation_code"].as_str().unwrap().len(), 6);
+}
+
+#[tokio::test]
+async fn password_changes_are_validated() {
+    let app = spawn_app().await;
+    let new_password = Uuid::new_v4().to_string();
+
+    let response = admin_api_request(
+        &app,
+        Method::POST,
+        "/password",
+        &app.test_user.username,
+        &app.test_user.password,
+    )
+    .json(&serde_json::json!({
+        "current_password": Uuid::new_v4().to_string(),
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    }))
+    .send()
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["type"], "/problems/incorrect-password");
+
+    let response = admin_api_request(
+        &app,
+        Method::POST,
+        "/password",
+        &app.test_user.username,
+        &app.test_user.password,
+    )
+    .json(&serde_json::json!({
+        "current_password": &app.test_user.password,
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    }))
+    .send()
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = admin_api_request(
+        &app,
+        Method::GET,
+        "/actions",
+        &app.test_user.username,
+        &new_password,
+    )
+    .send()
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn pending_actions_can_be_listed_and_approved_by_another_admin() {
+    let app = spawn_app_with_configuration(|c| {
+        c.application.two_person_rule.enabled = true;
+        c.application.two_person_rule.threshold = 0;
+    })
+    .await;
+    let reviewer = app.create_admin().await;
+    insert_subscriber(&app, "ursula@gmail.com").await;
+
+    let response = admin_api_request(
+        &app,
+        Method::POST,
+        "/subscribers/delete",
+        &app.test_user.username,
+        &app.test_user.password,
+    )
+    .json(&serde_json::json!({ "emails": ["ursula@gmail.com"] }))
+    .send()
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 202);
+
+    let actions: serde_json::Value = admin_api_request(
+        &app,
+        Method::GET,
+        "/actions",
+        &reviewer.username,
+        &reviewer.password,
+    )
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(actions.as_array().unwrap().len(), 1);
+    assert_eq!(actions[0]["payload"]["kind"], "delete_subscribers");
+    let approve_path = format!("/actions/{}/approve", actions[0]["id"].as_str().unwrap());
+
+    let response = admin_api_request(
+        &app,
+        Method::POST,
+        &approve_path,
+        &app.test_user.username,
+        &app.test_user.password,
+    )
+    .send()
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = admin_api_request(
+        &app,
+        Method::POST,
+        &approve_path,
+        &reviewer.username,
+        &reviewer.password,
+    )
+    .send()
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = admin_api_request(
+        &app,
+        Method::POST,
+        &approve_path,
+        &reviewer.username,
+        &reviewer.password,
+    )
+    .send()
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 409);
+
+    let remaining = sqlx::query!(r#"SELECT count(*) as "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(remaining, 0);
+}
+
+#[tokio::test]
+async fn issues_can_be_published() {
+    let app = spawn_app().await;
+
+    let response = admin_api_request(
+        &app,
+        Method::POST,
+        "/newsletters",
+        &app.test_user.username,
+        &app.test_user.password,
+    )
+    .json(&serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    }))
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let issues = sqlx::query!(r#"SELECT count(*) as "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(issues, 1);
+}
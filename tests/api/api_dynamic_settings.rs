@@ -0,0 +1,45 @@
+use reqwest::Method;
+
+use crate::helpers::spawn_app_with_configuration;
+
+#[tokio::test]
+async fn dynamic_settings_reflect_the_configuration() {
+    let app = spawn_app_with_configuration(|c| c.dynamic.max_emails_per_second = Some(1000)).await;
+
+    let body: serde_json::Value = app
+        .api_request(Method::GET, "/admin/settings")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(body["max_emails_per_second"], 1000);
+    assert_eq!(body["template_directory"], "templates");
+}
+
+#[tokio::test]
+async fn reloading_applies_the_settings_of_the_configuration_files() {
+    let app = spawn_app_with_configuration(|c| c.dynamic.max_emails_per_second = Some(1000)).await;
+
+    let response = app
+        .api_request(Method::POST, "/admin/settings/reload")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["max_emails_per_second"].is_null());
+
+    let body: serde_json::Value = app
+        .api_request(Method::GET, "/admin/settings")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(body["max_emails_per_second"].is_null());
+}
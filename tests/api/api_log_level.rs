@@ -0,0 +1,60 @@
+use reqwest::Method;
+
+use crate::helpers::{spawn_app, TestApp};
+
+fn log_level_request(app: &TestApp, method: Method) -> reqwest::RequestBuilder {
+    app.api_client
+        .request(method, format!("{}/api/v1/admin/log_level", &app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+}
+
+#[tokio::test]
+async fn the_log_filter_can_be_changed_at_runtime() {
+    let app = spawn_app().await;
+
+    // Tests log at the debug level already, so this leaves them untouched.
+    let response = log_level_request(&app, Method::PUT)
+        .json(&serde_json::json!({ "filter": "debug" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+
+    let body: serde_json::Value = log_level_request(&app, Method::GET)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["filter"], "debug");
+}
+
+#[tokio::test]
+async fn invalid_log_filters_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = log_level_request(&app, Method::PUT)
+        .json(&serde_json::json!({ "filter": "newsletter=loud" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["type"], "/problems/invalid-log-filter");
+}
+
+#[tokio::test]
+async fn health_checks_are_still_answered_when_quiet() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/health_check", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert!(response.status().is_success());
+}
@@ -0,0 +1,224 @@
+use reqwest::Method;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn create_confirmed_subscriber(app: &TestApp, email: &str) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let body = format!("name=le%20guin&email={}", urlencoding::encode(email));
+    app.post_subscription(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+async fn assign_topic(app: &TestApp, topic: &str, email: &str) {
+    let topic: serde_json::Value = app
+        .api_request(Method::POST, "/topics")
+        .json(&serde_json::json!({ "name": topic }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    app.api_request(
+        Method::POST,
+        &format!("/topics/{}/subscribers", topic["id"].as_str().unwrap()),
+    )
+    .json(&serde_json::json!({ "email": email }))
+    .send()
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+}
+
+async fn preview_rules(app: &TestApp, rules: serde_json::Value) -> i64 {
+    let preview: serde_json::Value = app
+        .api_request(Method::POST, "/segments/preview")
+        .json(&rules)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    preview["subscribers"].as_i64().unwrap()
+}
+
+#[tokio::test]
+async fn segments_can_be_created_updated_and_deleted() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_request(Method::POST, "/segments")
+        .json(&serde_json::json!({
+            "name": "Rustaceans",
+            "rules": [{ "type": "topic", "name": "Rust" }]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    let segment: serde_json::Value = response.json().await.unwrap();
+    let segment_id = segment["id"].as_str().unwrap();
+    assert_eq!(segment["rules"][0]["name"], "Rust");
+
+    let response = app
+        .api_request(Method::PUT, &format!("/segments/{}", segment_id))
+        .json(&serde_json::json!({ "name": "Everyone", "rules": [] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let segments: serde_json::Value = app
+        .api_request(Method::GET, "/segments")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(segments.as_array().unwrap().len(), 1);
+    assert_eq!(segments[0]["name"], "Everyone");
+
+    let response = app
+        .api_request(Method::DELETE, &format!("/segments/{}", segment_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app
+        .api_request(Method::GET, &format!("/segments/{}", segment_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn creating_a_segment_with_invalid_rules_returns_400() {
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({ "name": "Segment", "rules": [{ "type": "unknown" }] }),
+            "unknown rule",
+        ),
+        (
+            serde_json::json!({ "name": "Segment", "rules": [{ "type": "topic", "name": "" }] }),
+            "empty topic name",
+        ),
+        (
+            serde_json::json!({ "name": "", "rules": [] }),
+            "empty segment name",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        let response = app
+            .api_request(Method::POST, "/segments")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when the payload had an {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn preview_counts_only_confirmed_subscribers_matching_all_rules() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app, "ursula_le_guin@gmail.com").await;
+    create_confirmed_subscriber(&app, "terry_pratchett@gmail.com").await;
+    assign_topic(&app, "Rust", "ursula_le_guin@gmail.com").await;
+
+    assert_eq!(preview_rules(&app, serde_json::json!([])).await, 2);
+    assert_eq!(
+        preview_rules(
+            &app,
+            serde_json::json!([{ "type": "topic", "name": "Rust" }])
+        )
+        .await,
+        1
+    );
+    assert_eq!(
+        preview_rules(
+            &app,
+            serde_json::json!([
+                { "type": "topic", "name": "Rust" },
+                { "type": "confirmed_before", "date": "2000-01-01T00:00:00Z" }
+            ])
+        )
+        .await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn stored_segments_can_be_previewed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app, "ursula_le_guin@gmail.com").await;
+
+    let segment: serde_json::Value = app
+        .api_request(Method::POST, "/segments")
+        .json(&serde_json::json!({
+            "name": "Recent",
+            "rules": [{ "type": "confirmed_after", "date": "2000-01-01T00:00:00Z" }]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let preview: serde_json::Value = app
+        .api_request(
+            Method::GET,
+            &format!("/segments/{}/preview", segment["id"].as_str().unwrap()),
+        )
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(preview["subscribers"], 1);
+}
@@ -0,0 +1,212 @@
+use reqwest::Method;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::helpers::{accept_email_batch, spawn_app, spawn_app_with_configuration, TestApp};
+
+async fn spawn_app_with_webhooks() -> TestApp {
+    spawn_app_with_configuration(|c| c.features.webhooks = true).await
+}
+
+async fn create_confirmed_subscriber(app: &TestApp) -> Uuid {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    reqwest::get(app.get_links(email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch subscriber.")
+        .id
+}
+
+async fn get_events(app: &TestApp, subscriber_id: Uuid, query: &str) -> reqwest::Response {
+    app.api_request(
+        Method::GET,
+        &format!("/subscribers/{}/events?{}", subscriber_id, query),
+    )
+    .send()
+    .await
+    .expect("Failed to execute request.")
+}
+
+async fn register_webhook(app: &TestApp, url: &str) -> serde_json::Value {
+    let response = app
+        .api_request(Method::POST, "/webhooks")
+        .json(&serde_json::json!({ "url": url }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 201);
+
+    response.json().await.unwrap()
+}
+
+fn event_types(events: &serde_json::Value) -> Vec<&str> {
+    events
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["type"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn subscriber_timeline_is_paginated_with_a_cursor() {
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    let first_page: serde_json::Value = get_events(&app, subscriber_id, "limit=2")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        event_types(&first_page["events"]),
+        vec!["subscribed", "confirmed"]
+    );
+    let cursor = first_page["next_cursor"].as_i64().unwrap();
+
+    let second_page: serde_json::Value =
+        get_events(&app, subscriber_id, &format!("limit=2&after={}", cursor))
+            .await
+            .json()
+            .await
+            .unwrap();
+    assert_eq!(event_types(&second_page["events"]), vec!["delivered"]);
+    assert!(second_page["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn timeline_of_an_unknown_subscriber_is_not_found() {
+    let app = spawn_app().await;
+
+    let response = get_events(&app, Uuid::new_v4(), "").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn webhooks_are_not_found_when_the_feature_is_disabled() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_request(Method::GET, "/webhooks")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn webhooks_with_invalid_urls_are_rejected() {
+    let app = spawn_app_with_webhooks().await;
+
+    let response = app
+        .api_request(Method::POST, "/webhooks")
+        .json(&serde_json::json!({ "url": "ftp://crm.example.com" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn backfill_replays_past_events_to_a_new_webhook() {
+    let app = spawn_app_with_webhooks().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let webhook_server = MockServer::start().await;
+    let webhook = register_webhook(&app, &format!("{}/hooks", webhook_server.uri())).await;
+
+    Mock::given(path("/hooks"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&webhook_server)
+        .await;
+
+    let response = app
+        .api_request(
+            Method::POST,
+            &format!("/webhooks/{}/backfill", webhook["id"].as_str().unwrap()),
+        )
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 202);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["events"], 2);
+
+    app.dispatch_all_pending_webhook_events().await;
+
+    let payloads: Vec<serde_json::Value> = webhook_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| request.body_json().unwrap())
+        .collect();
+    assert_eq!(payloads[0]["type"], "subscriber.subscribed");
+    assert_eq!(payloads[1]["type"], "subscriber.confirmed");
+    assert_eq!(payloads[1]["subscriber_id"], subscriber_id.to_string());
+}
+
+#[tokio::test]
+async fn new_events_are_delivered_to_registered_webhooks() {
+    let app = spawn_app_with_webhooks().await;
+    let webhook_server = MockServer::start().await;
+    register_webhook(&app, &format!("{}/hooks", webhook_server.uri())).await;
+
+    Mock::given(path("/hooks"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&webhook_server)
+        .await;
+
+    create_confirmed_subscriber(&app).await;
+    app.dispatch_all_pending_webhook_events().await;
+}
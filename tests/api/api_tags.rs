@@ -0,0 +1,126 @@
+use newsletter::newsletter_list::DEFAULT_LIST_ID;
+use reqwest::Method;
+use uuid::Uuid;
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn insert_subscriber(app: &TestApp, email: &str) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, 'le guin', now(), 'confirmed')
+        "#,
+        subscriber_id,
+        DEFAULT_LIST_ID,
+        email,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+
+    subscriber_id
+}
+
+fn tags_request(app: &TestApp, method: Method, path: &str) -> reqwest::RequestBuilder {
+    app.api_client
+        .request(method, format!("{}/api/v1/admin{}", &app.address, path))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+}
+
+async fn tag(app: &TestApp, subscriber_id: Uuid, tag: &str) -> reqwest::Response {
+    tags_request(
+        app,
+        Method::PUT,
+        &format!("/subscribers/{}/tags/{}", subscriber_id, tag),
+    )
+    .send()
+    .await
+    .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn tags_can_be_assigned_listed_and_removed() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(&app, "ursula@gmail.com").await;
+
+    assert_eq!(
+        tag(&app, subscriber_id, "Early-Adopters").await.status(),
+        204
+    );
+    assert_eq!(
+        tag(&app, subscriber_id, "early-adopters").await.status(),
+        204
+    );
+    assert_eq!(tag(&app, subscriber_id, "beta").await.status(), 204);
+
+    let tags_path = format!("/subscribers/{}/tags", subscriber_id);
+    let tags: Vec<String> = tags_request(&app, Method::GET, &tags_path)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(tags, vec!["beta", "early-adopters"]);
+
+    let response = tags_request(&app, Method::DELETE, &format!("{}/beta", tags_path))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = tags_request(&app, Method::DELETE, &format!("{}/beta", tags_path))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn tagging_an_unknown_subscriber_returns_404() {
+    let app = spawn_app().await;
+
+    let response = tag(&app, Uuid::new_v4(), "beta").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn invalid_tags_are_rejected() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(&app, "ursula@gmail.com").await;
+
+    let response = tag(&app, subscriber_id, "beta!").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn issues_published_to_a_tag_only_target_tagged_subscribers() {
+    let app = spawn_app().await;
+    let tagged = insert_subscriber(&app, "ursula@gmail.com").await;
+    insert_subscriber(&app, "octavia@gmail.com").await;
+    tag(&app, tagged, "beta").await.error_for_status().unwrap();
+
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "tag": "Beta",
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let recipients: Vec<String> = sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.subscriber_email)
+        .collect();
+    assert_eq!(recipients, vec!["ursula@gmail.com"]);
+}
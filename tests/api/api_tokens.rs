@@ -0,0 +1,365 @@
+use reqwest::Method;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn login(app: &TestApp) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+async fn get_api_tokens_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/admin/api_tokens", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+/// Creates a token through the admin UI and reads it from the flash message
+/// it's shown in.
+async fn create_api_token(app: &TestApp, name: &str) -> String {
+    let response = app
+        .api_client
+        .post(format!("{}/admin/api_tokens", app.address))
+        .form(&[("name", name), ("csrf_token", &app.csrf_token().await)])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/admin/api_tokens");
+
+    let html = get_api_tokens_html(app).await;
+    let (_, rest) = html
+        .split_once("only shown now: ")
+        .expect("The token wasn't shown");
+
+    rest.chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect()
+}
+
+fn bearer_request(
+    app: &TestApp,
+    method: Method,
+    path: &str,
+    token: &str,
+) -> reqwest::RequestBuilder {
+    reqwest::Client::new()
+        .request(method, format!("{}/api/v1{}", &app.address, path))
+        .bearer_auth(token)
+}
+
+async fn create_subscriber(app: &TestApp, body: serde_json::Value) -> reqwest::Response {
+    app.api_request(Method::POST, "/admin/subscribers")
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn update_subscriber(
+    app: &TestApp,
+    subscriber_id: &str,
+    body: serde_json::Value,
+) -> reqwest::Response {
+    app.api_request(
+        Method::PATCH,
+        &format!("/admin/subscribers/{}", subscriber_id),
+    )
+    .json(&body)
+    .send()
+    .await
+    .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn api_tokens_authenticate_api_requests() {
+    let app = spawn_app().await;
+    login(&app).await;
+    let token = create_api_token(&app, "ci").await;
+    assert!(token.starts_with("z2p_"));
+
+    let response = bearer_request(&app, Method::GET, "/admin/stats", &token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = bearer_request(&app, Method::GET, "/lists", &token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let last_used_at = sqlx::query!("SELECT last_used_at FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .last_used_at;
+    assert!(last_used_at.is_some());
+}
+
+#[tokio::test]
+async fn revoked_api_tokens_are_rejected() {
+    let app = spawn_app().await;
+    login(&app).await;
+    let token = create_api_token(&app, "ci").await;
+
+    let token_id = sqlx::query!("SELECT token_id FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .token_id;
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/api_tokens/{}/revoke",
+            app.address, token_id
+        ))
+        .form(&[("csrf_token", &app.csrf_token().await)])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/admin/api_tokens");
+    assert!(get_api_tokens_html(&app)
+        .await
+        .contains("The API token was revoked."));
+
+    let response = bearer_request(&app, Method::GET, "/admin/stats", &token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn unknown_api_tokens_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = bearer_request(&app, Method::GET, "/lists", "z2p_unknown")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn api_tokens_are_stored_hashed() {
+    let app = spawn_app().await;
+    login(&app).await;
+    let token = create_api_token(&app, "ci").await;
+
+    let token_hash = sqlx::query!("SELECT token_hash FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .token_hash;
+
+    assert_ne!(token_hash, token);
+}
+
+#[tokio::test]
+async fn unnamed_api_tokens_are_refused() {
+    let app = spawn_app().await;
+    login(&app).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/api_tokens", app.address))
+        .form(&[("name", "  "), ("csrf_token", &app.csrf_token().await)])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/admin/api_tokens");
+
+    let html = get_api_tokens_html(&app).await;
+    assert!(html.contains("Give the token a name of at most 100 characters."));
+    assert!(html.contains("You have no API tokens yet."));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_created_read_updated_and_deleted() {
+    let app = spawn_app().await;
+
+    let response = create_subscriber(
+        &app,
+        serde_json::json!({ "email": "ursula@gmail.com", "name": "le guin" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["status"], "confirmed");
+    let subscriber_id = subscriber["id"].as_str().unwrap();
+
+    let subscribers: serde_json::Value = app
+        .api_request(Method::GET, "/admin/subscribers?status=confirmed")
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(subscribers.as_array().unwrap().len(), 1);
+    assert_eq!(subscribers[0]["email"], "ursula@gmail.com");
+
+    let response = update_subscriber(
+        &app,
+        subscriber_id,
+        serde_json::json!({ "name": "ursula", "status": "unsubscribed" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["name"], "ursula");
+    assert_eq!(subscriber["status"], "unsubscribed");
+
+    let event_types: Vec<String> = sqlx::query!(
+        "SELECT event_type FROM subscriber_events WHERE subscriber_id = $1 ORDER BY event_id",
+        uuid::Uuid::parse_str(subscriber_id).unwrap(),
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| r.event_type)
+    .collect();
+    assert_eq!(event_types, ["subscribed", "confirmed", "unsubscribed"]);
+
+    let response = app
+        .api_request(
+            Method::DELETE,
+            &format!("/admin/subscribers/{}", subscriber_id),
+        )
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .api_request(
+            Method::GET,
+            &format!("/admin/subscribers/{}", subscriber_id),
+        )
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn subscribing_an_email_twice_to_a_list_is_a_conflict() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({ "email": "ursula@gmail.com", "name": "le guin" });
+
+    assert_eq!(create_subscriber(&app, body.clone()).await.status(), 201);
+    assert_eq!(create_subscriber(&app, body).await.status(), 409);
+}
+
+#[tokio::test]
+async fn invalid_subscribers_are_refused() {
+    let app = spawn_app().await;
+
+    for body in [
+        serde_json::json!({ "email": "not-an-email", "name": "le guin" }),
+        serde_json::json!({ "email": "ursula@gmail.com", "name": "" }),
+    ] {
+        assert_eq!(create_subscriber(&app, body).await.status(), 400);
+    }
+
+    let response = create_subscriber(
+        &app,
+        serde_json::json!({
+            "email": "ursula@gmail.com",
+            "name": "le guin",
+            "list_id": uuid::Uuid::new_v4(),
+        }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn subscribers_only_move_along_allowed_transitions() {
+    let app = spawn_app().await;
+    let subscriber: serde_json::Value = create_subscriber(
+        &app,
+        serde_json::json!({ "email": "ursula@gmail.com", "name": "le guin" }),
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    let subscriber_id = subscriber["id"].as_str().unwrap();
+
+    let response = update_subscriber(
+        &app,
+        subscriber_id,
+        serde_json::json!({ "status": "pending_confirmation" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    update_subscriber(
+        &app,
+        subscriber_id,
+        serde_json::json!({ "status": "unsubscribed" }),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+    let response = update_subscriber(
+        &app,
+        subscriber_id,
+        serde_json::json!({ "status": "confirmed" }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn collaborators_cannot_manage_subscribers_through_the_api() {
+    let app = spawn_app().await;
+    let collaborator = app.create_collaborator().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/api/v1/admin/subscribers", &app.address))
+        .basic_auth(&collaborator.username, Some(&collaborator.password))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 405);
+}
+
+#[tokio::test]
+async fn stats_count_confirmed_subscribers() {
+    let app = spawn_app().await;
+    create_subscriber(
+        &app,
+        serde_json::json!({ "email": "ursula@gmail.com", "name": "le guin" }),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+
+    let stats: serde_json::Value = app
+        .api_request(Method::GET, "/admin/stats")
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(stats["confirmed_subscribers"], 1);
+    assert_eq!(stats["pending_confirmations"], 0);
+}
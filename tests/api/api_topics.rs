@@ -0,0 +1,205 @@
+use reqwest::Method;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn create_subscriber(app: &TestApp, email: &str) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let body = format!("name=le%20guin&email={}", urlencoding::encode(email));
+    app.post_subscription(body)
+        .await
+        .error_for_status()
+        .unwrap();
+}
+
+async fn create_topic(app: &TestApp, name: &str) -> serde_json::Value {
+    let response = app
+        .api_request(Method::POST, "/topics")
+        .json(&serde_json::json!({ "name": name, "description": "A topic" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 201);
+
+    response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn requests_without_credentials_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/api/v1/topics", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        r#"Basic realm="api""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[tokio::test]
+async fn topics_can_be_created_updated_and_deleted() {
+    let app = spawn_app().await;
+
+    let topic = create_topic(&app, "Rust").await;
+    let topic_id = topic["id"].as_str().unwrap();
+    assert_eq!(topic["name"], "Rust");
+
+    let response = app
+        .api_request(Method::PUT, &format!("/topics/{}", topic_id))
+        .json(&serde_json::json!({ "name": "Rust lang", "description": "Updated" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let topics: serde_json::Value = app
+        .api_request(Method::GET, "/topics")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(topics.as_array().unwrap().len(), 1);
+    assert_eq!(topics[0]["name"], "Rust lang");
+    assert_eq!(topics[0]["description"], "Updated");
+
+    let response = app
+        .api_request(Method::DELETE, &format!("/topics/{}", topic_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app
+        .api_request(Method::GET, &format!("/topics/{}", topic_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn creating_a_duplicated_topic_returns_409() {
+    let app = spawn_app().await;
+    create_topic(&app, "Rust").await;
+
+    let response = app
+        .api_request(Method::POST, "/topics")
+        .json(&serde_json::json!({ "name": "Rust" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn creating_a_topic_with_invalid_name_returns_400() {
+    let app = spawn_app().await;
+    let test_cases = vec![("", "empty name"), ("rust{}", "forbidden characters")];
+
+    for (name, description) in test_cases {
+        let response = app
+            .api_request(Method::POST, "/topics")
+            .json(&serde_json::json!({ "name": name }))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when the payload was {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn subscribers_can_be_assigned_to_and_removed_from_topics() {
+    let app = spawn_app().await;
+    create_subscriber(&app, "ursula_le_guin@gmail.com").await;
+    let topic = create_topic(&app, "Rust").await;
+    let topic_id = topic["id"].as_str().unwrap();
+
+    for _ in 0..2 {
+        let response = app
+            .api_request(Method::POST, &format!("/topics/{}/subscribers", topic_id))
+            .json(&serde_json::json!({ "email": "ursula_le_guin@gmail.com" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 204);
+    }
+
+    let assigned = sqlx::query!(r#"SELECT count(*) as "count!" FROM subscriber_topics"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(assigned.count, 1);
+
+    let response = app
+        .api_request(
+            Method::DELETE,
+            &format!("/topics/{}/subscribers/ursula_le_guin@gmail.com", topic_id),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+
+    let assigned = sqlx::query!(r#"SELECT count(*) as "count!" FROM subscriber_topics"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(assigned.count, 0);
+}
+
+#[tokio::test]
+async fn assigning_an_unknown_subscriber_or_topic_returns_404() {
+    let app = spawn_app().await;
+    create_subscriber(&app, "ursula_le_guin@gmail.com").await;
+    let topic = create_topic(&app, "Rust").await;
+    let topic_id = topic["id"].as_str().unwrap().to_string();
+
+    let test_cases = vec![
+        (topic_id, "unknown@gmail.com", "unknown subscriber"),
+        (
+            Uuid::new_v4().to_string(),
+            "ursula_le_guin@gmail.com",
+            "unknown topic",
+        ),
+    ];
+
+    for (topic_id, email, description) in test_cases {
+        let response = app
+            .api_request(Method::POST, &format!("/topics/{}/subscribers", topic_id))
+            .json(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.status().as_u16(),
+            404,
+            "The API did not fail with 404 Not Found when the payload had an {}.",
+            description
+        );
+    }
+}
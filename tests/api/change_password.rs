@@ -1,6 +1,29 @@
+use newsletter::configuration::{PasswordPepperSettings, PepperSecretSettings};
+use secrecy::Secret;
 use uuid::Uuid;
 
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with_configuration, TestApp};
+
+async fn spawn_app_with_password_policy() -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.application.password_policy.enabled = true;
+        c.application.password_policy.max_age_days = 30;
+        c.application.password_policy.history_size = 2;
+    })
+    .await
+}
+
+async fn change_password(app: &TestApp, current_password: &str, new_password: &str) {
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": current_password,
+            "new_password": new_password,
+            "new_password_check": new_password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+}
 
 #[tokio::test]
 async fn you_must_be_logged_in_to_see_the_change_password_form() {
@@ -188,3 +211,100 @@ async fn change_password_works() {
 
     assert_is_redirect_to(&response, "/admin/dashboard");
 }
+
+#[tokio::test]
+async fn users_with_expired_passwords_must_change_them_after_login() {
+    let app = spawn_app_with_password_policy().await;
+    sqlx::query!(
+        "UPDATE users SET password_changed_at = now() - interval '31 days' WHERE username = $1",
+        &app.test_user.username,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to age the user password.");
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Your password has expired. You must change it.</i></p>"));
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let new_password = Uuid::new_v4().to_string();
+    change_password(&app, &app.test_user.password, &new_password).await;
+
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn recent_passwords_cannot_be_reused() {
+    let app = spawn_app_with_password_policy().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let second_password = Uuid::new_v4().to_string();
+    let third_password = Uuid::new_v4().to_string();
+    change_password(&app, &app.test_user.password, &second_password).await;
+    change_password(&app, &second_password, &third_password).await;
+    app.get_change_password_html().await;
+
+    change_password(&app, &third_password, &second_password).await;
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page
+        .contains("<p><i>The new password must differ from your last 2 passwords.</i></p>"));
+
+    change_password(&app, &third_password, &app.test_user.password).await;
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Your password has been changed.</i></p>"));
+}
+
+#[tokio::test]
+async fn changed_passwords_are_hashed_with_the_current_pepper() {
+    let app = spawn_app_with_configuration(|c| {
+        c.application.password_pepper = Some(PasswordPepperSettings {
+            current_version: 1,
+            secrets: vec![PepperSecretSettings {
+                version: 1,
+                secret: Secret::new("pepper".into()),
+            }],
+        });
+    })
+    .await;
+    let new_password = Uuid::new_v4().to_string();
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    change_password(&app, &app.test_user.password, &new_password).await;
+
+    let saved = sqlx::query!(
+        "SELECT password_hash FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(saved.password_hash.contains("keyid="));
+
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
@@ -1,3 +1,6 @@
+use newsletter::token_generator::{
+    generate_invitation_token, generate_validation_code, hash_token,
+};
 use wiremock::{
     matchers::{method, path},
     Mock, ResponseTemplate,
@@ -129,14 +132,48 @@ async fn invite_persists_invitation_token() {
 
     let validation_code = extract_validation_code(response).await;
 
-    let saved = sqlx::query!("SELECT invitation_token, validation_code from invitation_tokens")
-        .fetch_one(&test_app.db_pool)
-        .await
-        .expect("Failed to retrieve stored token");
+    let invitation_token = test_app.extract_invitation_token().await;
+
+    let saved =
+        sqlx::query!("SELECT invitation_token_hash, validation_code from invitation_tokens")
+            .fetch_one(&test_app.db_pool)
+            .await
+            .expect("Failed to retrieve stored token");
 
+    assert_eq!(hash_token(&invitation_token), saved.invitation_token_hash);
     assert_eq!(validation_code, saved.validation_code);
 }
 
+#[tokio::test]
+async fn invite_hands_out_the_generated_token_and_code() {
+    let test_app = spawn_app().await;
+    let invitation_token = generate_invitation_token(&test_app.tokens);
+    let validation_code = generate_validation_code(&test_app.tokens);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+
+    let body = serde_json::json!({
+        "email": "ursula_le_guin@gmail.com",
+    });
+
+    let response = test_app.invite_collaborator(&body).await;
+
+    assert_eq!(extract_validation_code(response).await, validation_code);
+    assert_eq!(test_app.extract_invitation_token().await, invitation_token);
+}
+
 #[tokio::test]
 async fn invite_returns_400_if_email_is_missing() {
     let test_app = spawn_app().await;
@@ -204,7 +204,7 @@ async fn new_collaborator_must_contain_a_unique_username() {
         .await;
 
     assert!(html_page.contains(&format!(
-        "<p><i>Username \"{}\" is already in use.</i></p>",
+        "<p><i>Username &quot;{}&quot; is already in use.</i></p>",
         collaborator.username
     )))
 }
@@ -268,3 +268,193 @@ async fn new_collaborator_is_registered_with_success() {
     let html_page = test_app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", collaborator_username)));
 }
+
+#[tokio::test]
+async fn registration_form_shows_the_invited_email() {
+    let test_app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+
+    let body = serde_json::json!({
+        "email": "ursula_le_guin@gmail.com",
+    });
+
+    test_app.invite_collaborator(&body).await;
+
+    let invitation_token = test_app.extract_invitation_token().await;
+
+    let html_page = test_app
+        .get_collaborator_registration_html(&invitation_token)
+        .await;
+
+    assert!(html_page.contains(r#"value="ursula_le_guin@gmail.com" readonly"#));
+}
+
+#[tokio::test]
+async fn new_collaborator_is_registered_with_the_invited_email() {
+    let test_app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+
+    let body = serde_json::json!({
+        "email": "ursula_le_guin@gmail.com",
+    });
+
+    let response = test_app.invite_collaborator(&body).await;
+
+    let invitation_token = test_app.extract_invitation_token().await;
+    let validation_code = extract_validation_code(response).await;
+
+    let collaborator_username = "collaborator";
+
+    // Another address sent along with the form is ignored.
+    let body = serde_json::json!({
+        "invitation_token": invitation_token,
+        "validation_code": validation_code,
+        "username": collaborator_username,
+        "password": Uuid::new_v4().to_string(),
+        "email": "someone_else@gmail.com",
+    });
+
+    let response = test_app.register_collaborator(&body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let saved = sqlx::query!(
+        r#"SELECT email FROM users WHERE username = $1"#,
+        collaborator_username
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .expect("Failed to fetch collaborator");
+
+    assert_eq!(saved.email.as_deref(), Some("ursula_le_guin@gmail.com"));
+}
+
+#[tokio::test]
+async fn new_collaborator_must_contain_a_valid_username() {
+    let test_app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+
+    let body = serde_json::json!({
+        "email": "ursula_le_guin@gmail.com",
+    });
+
+    let response = test_app.invite_collaborator(&body).await;
+
+    let invitation_token = test_app.extract_invitation_token().await;
+    let validation_code = extract_validation_code(response).await;
+
+    let test_cases = [
+        (
+            "ul",
+            "Username must contain at least 3 and up to 64 characters.",
+        ),
+        (
+            "ursula le guin",
+            "Username may only contain letters, digits, dots, hyphens and underscores.",
+        ),
+    ];
+
+    for (username, error_message) in test_cases {
+        let invalid_body = serde_json::json!({
+            "invitation_token": invitation_token,
+            "validation_code": validation_code,
+            "username": username,
+            "password": Uuid::new_v4().to_string(),
+        });
+
+        let response = test_app.register_collaborator(&invalid_body).await;
+
+        assert_is_redirect_to(&response, "/collaborator");
+
+        let html_page = test_app
+            .get_collaborator_registration_html(&invitation_token)
+            .await;
+
+        assert!(html_page.contains(&format!("<p><i>{}</i></p>", error_message)));
+    }
+}
+
+#[tokio::test]
+async fn usernames_differing_only_by_case_are_the_same() {
+    let test_app = spawn_app().await;
+
+    let collaborator = test_app.create_collaborator().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+
+    let body = serde_json::json!({
+        "email": "ursula_le_guin@gmail.com",
+    });
+
+    let response = test_app.invite_collaborator(&body).await;
+
+    let invitation_token = test_app.extract_invitation_token().await;
+    let validation_code = extract_validation_code(response).await;
+
+    let invalid_body = serde_json::json!({
+        "invitation_token": invitation_token,
+        "validation_code": validation_code,
+        "username": collaborator.username.to_uppercase(),
+        "password": Uuid::new_v4().to_string(),
+    });
+
+    let response = test_app.register_collaborator(&invalid_body).await;
+
+    assert_is_redirect_to(&response, "/collaborator");
+
+    let html_page = test_app
+        .get_collaborator_registration_html(&invitation_token)
+        .await;
+
+    assert!(html_page.contains(&format!(
+        "<p><i>Username &quot;{}&quot; is already in use.</i></p>",
+        collaborator.username
+    )))
+}
@@ -0,0 +1,46 @@
+use crate::helpers::{spawn_app_with_configuration, TestApp};
+
+async fn spawn_app_allowing(origin: &str) -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.application.cors.allowed_origins = vec![origin.into()];
+    })
+    .await
+}
+
+async fn preflight_subscription(app: &TestApp, origin: &str) -> reqwest::Response {
+    app.api_client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}/subscriptions", app.address),
+        )
+        .header("Origin", origin)
+        .header("Access-Control-Request-Method", "POST")
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn allowed_origins_can_subscribe_from_the_browser() {
+    let app = spawn_app_allowing("https://widget.example.com").await;
+
+    let response = preflight_subscription(&app, "https://widget.example.com").await;
+
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.headers()["Access-Control-Allow-Origin"],
+        "https://widget.example.com"
+    );
+}
+
+#[tokio::test]
+async fn other_origins_are_not_allowed() {
+    let app = spawn_app_allowing("https://widget.example.com").await;
+
+    let response = preflight_subscription(&app, "https://evil.example.com").await;
+
+    assert!(response
+        .headers()
+        .get("Access-Control-Allow-Origin")
+        .is_none());
+}
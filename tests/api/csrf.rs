@@ -0,0 +1,88 @@
+use crate::helpers::{assert_is_redirect_to, extract_csrf_token, spawn_app};
+
+#[tokio::test]
+async fn login_form_embeds_the_csrf_token_of_the_session() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_login_html().await;
+    let csrf_token = extract_csrf_token(&html_page);
+
+    let response = app
+        .api_client
+        .post(&format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+            "csrf_token": csrf_token,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn forms_without_a_csrf_token_are_rejected() {
+    let app = spawn_app().await;
+    app.get_login_html().await;
+
+    let response = app
+        .api_client
+        .post(&format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn forms_with_the_token_of_another_session_are_rejected() {
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let csrf_token = app.csrf_token().await;
+    app.post_logout().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app
+        .api_client
+        .post(&format!("{}/admin/sessions/revoke_all", &app.address))
+        .header("X-CSRF-Token", csrf_token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn admin_pages_embed_the_csrf_token_in_their_forms() {
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let csrf_token = app.csrf_token().await;
+
+    let dashboard = app.get_admin_dashboard_html().await;
+    let change_password = app.get_change_password_html().await;
+
+    assert_eq!(extract_csrf_token(&dashboard), csrf_token);
+    assert_eq!(extract_csrf_token(&change_password), csrf_token);
+}
@@ -0,0 +1,799 @@
+use std::time::Duration;
+
+use newsletter::{
+    delivery_queue::{inspect_issue, purge_deliveries, requeue_dead_letters, summarize_queue},
+    domain::SubscriptionStatus,
+    issue_delivery_worker::try_execute_task,
+    newsletter_list::DEFAULT_LIST_ID,
+};
+use uuid::Uuid;
+use wiremock::{
+    matchers::{any, body_string_contains, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{accept_email_batch, spawn_app, spawn_app_with_configuration, TestApp};
+
+async fn spawn_app_without_backoff() -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.delivery_queue.max_retries = 2;
+        c.delivery_queue.retry_backoff_seconds = 0;
+    })
+    .await
+}
+
+/// Answers batches of emails as Postmark does, refusing the emails to anyone
+/// but `recipient`.
+fn deliver_only_to(
+    recipient: &'static str,
+) -> impl Fn(&wiremock::Request) -> ResponseTemplate + Send + Sync {
+    move |request| {
+        let emails: Vec<serde_json::Value> = request.body_json().unwrap();
+        let results: Vec<serde_json::Value> = emails
+            .iter()
+            .map(|email| match email["To"].as_str() {
+                Some(to) if to == recipient => {
+                    serde_json::json!({ "ErrorCode": 0, "Message": "OK" })
+                }
+                _ => serde_json::json!({ "ErrorCode": 300, "Message": "Invalid email request" }),
+            })
+            .collect();
+
+        ResponseTemplate::new(200).set_body_json(results)
+    }
+}
+
+async fn insert_confirmed_subscriber(app: &TestApp, email: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, 'le guin', now(), 'confirmed')
+        "#,
+        Uuid::new_v4(),
+        DEFAULT_LIST_ID,
+        email,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+}
+
+async fn publish_newsletter(app: &TestApp) -> Uuid {
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    published_issue_id(&app).await
+}
+
+async fn published_issue_id(app: &TestApp) -> Uuid {
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch newsletter issue.")
+        .newsletter_issue_id
+}
+
+#[tokio::test]
+async fn deliveries_are_moved_to_dead_letters_after_exhausting_retries() {
+    let app = spawn_app_without_backoff().await;
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    let issue_id = publish_newsletter(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    app.dispatch_all_pending_emails().await;
+
+    let issue = inspect_issue(&app.db_pool, issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(issue.pending.is_empty());
+    assert_eq!(issue.dead.len(), 1);
+    assert_eq!(issue.dead[0].subscriber_email, "ursula@gmail.com");
+    assert_eq!(issue.dead[0].n_retries, 2);
+}
+
+#[tokio::test]
+async fn requeued_dead_letters_are_delivered() {
+    let app = spawn_app_without_backoff().await;
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    let issue_id = publish_newsletter(&app).await;
+
+    {
+        let _mock_guard = Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .mount_as_scoped(&app.email_server)
+            .await;
+        app.dispatch_all_pending_emails().await;
+    }
+
+    let requeued = requeue_dead_letters(&app.db_pool, issue_id, None)
+        .await
+        .unwrap();
+    assert_eq!(requeued, 1);
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.dispatch_all_pending_emails().await;
+
+    assert!(summarize_queue(&app.db_pool).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn purged_deliveries_are_not_sent() {
+    let app = spawn_app_without_backoff().await;
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    insert_confirmed_subscriber(&app, "le_guin@gmail.com").await;
+    let issue_id = publish_newsletter(&app).await;
+
+    let summary = summarize_queue(&app.db_pool).await.unwrap();
+    assert_eq!(summary.len(), 1);
+    assert_eq!(summary[0].pending, 2);
+
+    let purged = purge_deliveries(&app.db_pool, issue_id, Some("ursula@gmail.com"), false)
+        .await
+        .unwrap();
+    assert_eq!(purged, 1);
+
+    Mock::given(path("/email/batch"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn inactive_recipients_are_suppressed_without_retries() {
+    let app = spawn_app_without_backoff().await;
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    let issue_id = publish_newsletter(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(serde_json::json!([{
+                "ErrorCode": 406,
+                "Message": "You tried to send to a recipient that has been marked as inactive."
+            }])),
+        )
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.dispatch_all_pending_emails().await;
+
+    let issue = inspect_issue(&app.db_pool, issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(issue.pending.is_empty());
+    assert!(issue.dead.is_empty());
+    assert_eq!(issue.suppressed.len(), 1);
+    assert_eq!(issue.suppressed[0].error_code, 406);
+
+    let summary = summarize_queue(&app.db_pool).await.unwrap();
+    assert_eq!(summary[0].suppressed, 1);
+
+    let subscriber =
+        sqlx::query!(r#"SELECT status as "status: SubscriptionStatus" FROM subscriptions"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(subscriber.status, SubscriptionStatus::Suppressed);
+}
+
+fn issue_request(
+    app: &TestApp,
+    method: reqwest::Method,
+    issue_id: Uuid,
+    action: &str,
+) -> reqwest::RequestBuilder {
+    app.api_client
+        .request(
+            method,
+            format!(
+                "{}/api/v1/admin/issues/{}/{}",
+                &app.address, issue_id, action
+            ),
+        )
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+}
+
+#[tokio::test]
+async fn resending_to_failed_recipients_skips_unsubscribed_and_delivered_ones() {
+    let app = spawn_app_without_backoff().await;
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    insert_confirmed_subscriber(&app, "octavia@gmail.com").await;
+    insert_confirmed_subscriber(&app, "le_guin@gmail.com").await;
+    let issue_id = publish_newsletter(&app).await;
+
+    {
+        let _mock_guard = Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .mount_as_scoped(&app.email_server)
+            .await;
+        app.dispatch_all_pending_emails().await;
+    }
+
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'suppressed' WHERE email = 'octavia@gmail.com'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_events (subscriber_id, event_type, details, occurred_at)
+        SELECT id, 'delivered', $1, now()
+        FROM subscriptions
+        WHERE email = 'le_guin@gmail.com'
+        "#,
+        serde_json::json!({ "newsletter_issue_id": issue_id }),
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = issue_request(&app, reqwest::Method::POST, issue_id, "resend_failed")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let outcome: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(outcome["requeued"], 1);
+    assert_eq!(outcome["skipped"], 2);
+
+    let report: serde_json::Value = issue_request(&app, reqwest::Method::GET, issue_id, "report")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["pending"][0]["subscriber_email"], "ursula@gmail.com");
+    assert_eq!(report["pending"][0]["n_retries"], 0);
+    assert_eq!(report["dead"].as_array().unwrap().len(), 2);
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn resending_an_unknown_issue_returns_404() {
+    let app = spawn_app_without_backoff().await;
+
+    let response = issue_request(&app, reqwest::Method::POST, Uuid::new_v4(), "resend_failed")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn collaborators_cannot_resend_issues() {
+    let app = spawn_app_without_backoff().await;
+    let collaborator = app.create_collaborator().await;
+
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/api/v1/admin/issues/{}/resend_failed",
+            &app.address,
+            Uuid::new_v4()
+        ))
+        .basic_auth(&collaborator.username, Some(&collaborator.password))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 405);
+}
+
+#[tokio::test]
+async fn deliveries_of_a_crashed_worker_are_resumed_once_their_lease_expires() {
+    let app = spawn_app_with_configuration(|c| {
+        c.delivery_queue.lease_seconds = 1;
+    })
+    .await;
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    let issue_id = publish_newsletter(&app).await;
+
+    {
+        let _mock_guard = Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(30)))
+            .mount_as_scoped(&app.email_server)
+            .await;
+
+        // The worker dies while waiting for the email provider.
+        let attempt = try_execute_task(
+            &app.db_pool,
+            &app.email_client,
+            &app.email_tracker,
+            &app.referral_links,
+            &app.delivery_queue,
+        );
+        assert!(tokio::time::timeout(Duration::from_millis(500), attempt)
+            .await
+            .is_err());
+    }
+
+    let issue = inspect_issue(&app.db_pool, issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.delivered, 0);
+    assert_eq!(issue.pending.len(), 1);
+    assert!(issue.pending[0].leased_until.is_some());
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Other workers leave the delivery alone while it is leased...
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(summarize_queue(&app.db_pool).await.unwrap()[0].pending, 1);
+
+    // ...and resume it once the lease expired.
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    app.dispatch_all_pending_emails().await;
+
+    let issue = inspect_issue(&app.db_pool, issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.delivered, 1);
+    assert!(issue.pending.is_empty());
+}
+
+#[tokio::test]
+async fn a_crashed_worker_is_resumed_after_the_last_recipient_it_sent_to() {
+    let app = spawn_app_with_configuration(|c| {
+        c.delivery_queue.batch_size = 600;
+        c.delivery_queue.lease_seconds = 1;
+    })
+    .await;
+    // More recipients than the provider takes in a single request.
+    sqlx::query(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        SELECT gen_random_uuid(), $1, 'reader' || n || '@gmail.com', 'le guin', now(), 'confirmed'
+        FROM generate_series(1, 501) n
+        "#,
+    )
+    .bind(DEFAULT_LIST_ID)
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscribers.");
+    let issue_id = publish_newsletter(&app).await;
+
+    {
+        let _accepted_guard = Mock::given(path("/email/batch"))
+            .respond_with(accept_email_batch)
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount_as_scoped(&app.email_server)
+            .await;
+        let _hanging_guard = Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(30)))
+            .mount_as_scoped(&app.email_server)
+            .await;
+
+        // The worker dies after the provider accepted the first request.
+        let attempt = try_execute_task(
+            &app.db_pool,
+            &app.email_client,
+            &app.email_tracker,
+            &app.referral_links,
+            &app.delivery_queue,
+        );
+        assert!(tokio::time::timeout(Duration::from_secs(10), attempt)
+            .await
+            .is_err());
+    }
+
+    let issue = inspect_issue(&app.db_pool, issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.delivered, 0);
+    assert_eq!(issue.pending.len(), 501);
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Only the recipient the dead worker didn't get to is sent the issue.
+    let requests = app.email_server.received_requests().await.unwrap();
+    let emails: Vec<serde_json::Value> = requests.last().unwrap().body_json().unwrap();
+    assert_eq!(emails.len(), 1);
+    let issue = inspect_issue(&app.db_pool, issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.delivered, 501);
+    assert!(issue.pending.is_empty());
+}
+
+#[tokio::test]
+async fn crashed_workers_sending_the_same_issue_are_resumed_after_their_own_last_recipient() {
+    let app = spawn_app_with_configuration(|c| {
+        c.delivery_queue.batch_size = 501;
+        c.delivery_queue.lease_seconds = 1;
+        // For the workers to still be waiting on the provider when they die.
+        c.email_client.timeout_milliseconds = 60_000;
+    })
+    .await;
+    // Two batches, each one more than the provider takes in a single request.
+    sqlx::query(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        SELECT gen_random_uuid(), $1, 'reader' || n || '@gmail.com', 'le guin', now(), 'confirmed'
+        FROM generate_series(1, 1002) n
+        "#,
+    )
+    .bind(DEFAULT_LIST_ID)
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscribers.");
+    let issue_id = publish_newsletter(&app).await;
+
+    {
+        // Only the first, full, request of each worker is answered.
+        let _accepted_guard = Mock::given(path("/email/batch"))
+            .and(|request: &wiremock::Request| {
+                request
+                    .body_json::<Vec<serde_json::Value>>()
+                    .is_ok_and(|emails| emails.len() == 500)
+            })
+            .respond_with(accept_email_batch)
+            .expect(2)
+            .with_priority(1)
+            .mount_as_scoped(&app.email_server)
+            .await;
+        let _hanging_guard = Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(60)))
+            .mount_as_scoped(&app.email_server)
+            .await;
+
+        // Both workers die after the provider accepted their first request.
+        let attempt = || {
+            try_execute_task(
+                &app.db_pool,
+                &app.email_client,
+                &app.email_tracker,
+                &app.referral_links,
+                &app.delivery_queue,
+            )
+        };
+        let attempts = async { tokio::join!(attempt(), attempt()) };
+        assert!(tokio::time::timeout(Duration::from_secs(20), attempts)
+            .await
+            .is_err());
+    }
+
+    let progress = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_dispatch_progress")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(progress.count, 2);
+    let n_requests = app.email_server.received_requests().await.unwrap().len();
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .mount(&app.email_server)
+        .await;
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Only the recipient each dead worker didn't get to is sent the issue.
+    let requests = app.email_server.received_requests().await.unwrap();
+    let n_sent: usize = requests[n_requests..]
+        .iter()
+        .map(|request| request.body_json::<Vec<serde_json::Value>>().unwrap().len())
+        .sum();
+    assert_eq!(n_sent, 2);
+    let issue = inspect_issue(&app.db_pool, issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.delivered, 1002);
+    assert!(issue.pending.is_empty());
+    let progress = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_dispatch_progress")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(progress.count, 0);
+}
+
+#[tokio::test]
+async fn progress_of_abandoned_leases_is_cleared_by_batches_of_other_issues() {
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .mount(&app.email_server)
+        .await;
+    let issue_id = publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+    // Left behind by a worker that died, once every task was taken over.
+    sqlx::query(
+        r#"
+        INSERT INTO issue_dispatch_progress (
+            newsletter_issue_id, lease_id, last_subscriber_email, leased_until, updated_at
+        )
+        VALUES ($1, $2, 'ursula@gmail.com', now() - interval '1 hour', now() - interval '1 hour')
+        "#,
+    )
+    .bind(issue_id)
+    .bind(Uuid::new_v4())
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert dispatch progress.");
+
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Another newsletter title",
+            "content": {
+                "text": "Another newsletter body as plain text",
+                "html": "<p>Another newsletter body as HTML</p>",
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
+
+    let progress = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_dispatch_progress")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(progress.count, 0);
+}
+
+async fn queue_delivery(app: &TestApp, newsletter_issue_id: Uuid, email: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        VALUES ($1, $2)
+        "#,
+        newsletter_issue_id,
+        email,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to queue delivery.");
+}
+
+#[tokio::test]
+async fn issues_are_not_delivered_twice_to_the_same_address() {
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    Mock::given(path("/email/batch"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let issue_id = publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    queue_delivery(&app, issue_id, "ursula@gmail.com").await;
+    app.dispatch_all_pending_emails().await;
+
+    let issue = inspect_issue(&app.db_pool, issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.delivered, 1);
+    assert!(issue.pending.is_empty());
+}
+
+#[tokio::test]
+async fn issues_are_not_delivered_again_to_a_subscriber_whose_address_changed() {
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    Mock::given(path("/email/batch"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let issue_id = publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET email = 'le_guin@gmail.com'
+        WHERE email = 'ursula@gmail.com'
+        "#,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    queue_delivery(&app, issue_id, "le_guin@gmail.com").await;
+    app.dispatch_all_pending_emails().await;
+
+    let issue = inspect_issue(&app.db_pool, issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.delivered, 1);
+    assert!(issue.pending.is_empty());
+}
+
+#[tokio::test]
+async fn the_outcomes_of_a_batch_are_saved_together() {
+    let app = spawn_app_with_configuration(|c| {
+        c.delivery_queue.batch_size = 10;
+        c.delivery_queue.retry_backoff_seconds = 60;
+    })
+    .await;
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    insert_confirmed_subscriber(&app, "le_guin@gmail.com").await;
+    let issue_id = publish_newsletter(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .respond_with(deliver_only_to("ursula@gmail.com"))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    try_execute_task(
+        &app.db_pool,
+        &app.email_client,
+        &app.email_tracker,
+        &app.referral_links,
+        &app.delivery_queue,
+    )
+    .await
+    .unwrap();
+
+    let issue = inspect_issue(&app.db_pool, issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.delivered, 1);
+    assert_eq!(issue.pending.len(), 1);
+    assert_eq!(issue.pending[0].subscriber_email, "le_guin@gmail.com");
+    assert_eq!(issue.pending[0].n_retries, 1);
+    assert!(issue.pending[0].leased_until.is_none());
+}
+
+#[tokio::test]
+async fn render_failures_are_recorded_without_stopping_the_batch() {
+    let app = spawn_app_without_backoff().await;
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    insert_confirmed_subscriber(&app, "le_guin@gmail.com").await;
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Hi {{ subscriber.email }}",
+                "html": r#"<p>Hi {% if subscriber.email == "le_guin@gmail.com" %}{{ missing }}{% endif %}</p>"#,
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let issue_id = published_issue_id(&app).await;
+
+    Mock::given(body_string_contains("ursula@gmail.com"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(body_string_contains("le_guin@gmail.com"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    app.dispatch_all_pending_emails().await;
+
+    let issue = inspect_issue(&app.db_pool, issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.delivered, 1);
+    assert!(issue.pending.is_empty());
+    assert!(issue.dead.is_empty());
+    assert_eq!(issue.render_failures.len(), 1);
+    assert_eq!(
+        issue.render_failures[0].subscriber_email,
+        "le_guin@gmail.com"
+    );
+    assert!(issue.render_failures[0].error.contains("missing"));
+}
+
+#[tokio::test]
+async fn the_stats_page_of_an_issue_counts_recipients_by_outcome() {
+    let app = spawn_app_without_backoff().await;
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    insert_confirmed_subscriber(&app, "le_guin@gmail.com").await;
+    let issue_id = publish_newsletter(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .respond_with(deliver_only_to("ursula@gmail.com"))
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/newsletters/{}/stats",
+            app.address, issue_id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>Newsletter title</h1>"));
+    assert!(html_page.contains("<tr><th>Sent</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Pending</th><td>0</td></tr>"));
+    assert!(html_page.contains("<tr><th>Failed</th><td>1</td></tr>"));
+}
+
+#[tokio::test]
+async fn the_stats_page_of_an_unknown_issue_returns_404() {
+    let app = spawn_app_without_backoff().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/newsletters/{}/stats",
+            app.address,
+            Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 404);
+}
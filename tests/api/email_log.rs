@@ -0,0 +1,151 @@
+use wiremock::{matchers::path, Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn login(app: &TestApp) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+async fn get_emails_html(app: &TestApp, query: &str) -> String {
+    app.api_client
+        .get(format!("{}/admin/emails?{}", app.address, query))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn sent_emails_are_logged_with_their_provider_message_id() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "To": "ursula_le_guin@gmail.com",
+            "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+            "ErrorCode": 0,
+            "Message": "OK",
+        })))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email = sqlx::query!(
+        r#"SELECT kind, recipient, provider_message_id, status, error FROM emails_sent"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(email.kind, "subscription_confirmation");
+    assert_eq!(email.recipient, "ursula_le_guin@gmail.com");
+    assert_eq!(
+        email.provider_message_id.as_deref(),
+        Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+    );
+    assert_eq!(email.status, "sent");
+    assert_eq!(email.error, None);
+}
+
+#[tokio::test]
+async fn issue_emails_refused_by_the_provider_are_logged_as_rejected() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email/batch"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            { "ErrorCode": 406, "Message": "Inactive recipient" },
+        ])))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let confirmation_request = app.email_server.received_requests().await.unwrap().pop();
+    reqwest::get(app.get_links(&confirmation_request.unwrap()).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let email = sqlx::query!(
+        r#"
+        SELECT status, error, newsletter_issue_id
+        FROM emails_sent
+        WHERE kind = 'issue'
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(email.status, "rejected");
+    assert!(email.error.unwrap().contains("Inactive recipient"));
+    assert!(email.newsletter_issue_id.is_some());
+}
+
+#[tokio::test]
+async fn admins_can_filter_the_emails_sent_by_recipient() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    for body in [
+        "name=le%20guin&email=ursula_le_guin%40gmail.com",
+        "name=ged&email=ged%40earthsea.org",
+    ] {
+        app.post_subscription(body.into())
+            .await
+            .error_for_status()
+            .unwrap();
+    }
+    login(&app).await;
+
+    let html_page = get_emails_html(&app, "recipient=Ursula_Le_Guin%40gmail.com").await;
+
+    assert!(html_page.contains("<td>ursula_le_guin@gmail.com</td>"));
+    assert!(html_page.contains("<td>subscription_confirmation</td>"));
+    assert!(!html_page.contains("ged@earthsea.org"));
+
+    let html_page = get_emails_html(&app, "recipient=&kind=issue&status=").await;
+    assert!(html_page.contains("No email matches."));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_emails_sent() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/emails", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,235 @@
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use newsletter::newsletter_list::DEFAULT_LIST_ID;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::helpers::{spawn_app, spawn_app_with_configuration, TestApp};
+
+async fn insert_subscriber(app: &TestApp, email: &str, name: &str) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, $4, now(), 'confirmed')
+        "#,
+        subscriber_id,
+        DEFAULT_LIST_ID,
+        email,
+        name,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+
+    subscriber_id
+}
+
+async fn subscriber_status(app: &TestApp, subscriber_id: Uuid) -> String {
+    sqlx::query!(
+        r#"SELECT status::text as "status!" FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .status
+}
+
+fn sign(body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"email-webhooks-secret").unwrap();
+    mac.update(body.as_bytes());
+
+    base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+async fn post_email_webhook(app: &TestApp, body: &str, signature: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/webhooks/email", &app.address))
+        .header("Content-Type", "application/json")
+        .header("X-Postmark-Signature", signature)
+        .body(body.to_string())
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn bounces_mark_the_subscriber_as_bounced() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "ursula").await;
+    let body = r#"{"RecordType": "Bounce", "Type": "HardBounce", "Email": "ursula@example.com"}"#;
+
+    let response = post_email_webhook(&app, body, &sign(body)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app, subscriber_id).await, "bounced");
+}
+
+#[tokio::test]
+async fn spam_complaints_mark_the_subscriber_as_complained() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "ursula").await;
+    let body = r#"{"RecordType": "SpamComplaint", "Email": "ursula@example.com"}"#;
+
+    let response = post_email_webhook(&app, body, &sign(body)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app, subscriber_id).await, "complained");
+}
+
+#[tokio::test]
+async fn other_records_are_acknowledged_and_ignored() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "ursula").await;
+    let body = r#"{"RecordType": "Delivery", "Recipient": "ursula@example.com"}"#;
+
+    let response = post_email_webhook(&app, body, &sign(body)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app, subscriber_id).await, "confirmed");
+}
+
+#[tokio::test]
+async fn events_with_an_invalid_signature_are_rejected() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "ursula").await;
+    let body = r#"{"RecordType": "Bounce", "Email": "ursula@example.com"}"#;
+
+    for signature in ["", "not base64", &sign("another body")] {
+        let response = post_email_webhook(&app, body, signature).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    assert_eq!(subscriber_status(&app, subscriber_id).await, "confirmed");
+}
+
+#[tokio::test]
+async fn bounced_subscribers_do_not_receive_future_issues() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "ursula@example.com", "ursula").await;
+    let body = r#"{"RecordType": "Bounce", "Type": "HardBounce", "Email": "ursula@example.com"}"#;
+    post_email_webhook(&app, body, &sign(body)).await;
+
+    wiremock::Mock::given(wiremock::matchers::any())
+        .respond_with(wiremock::ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": { "html": "<p>Newsletter body as HTML</p>" },
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
+}
+
+async fn publish_newsletter(app: &TestApp) -> Uuid {
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": { "html": "<p>Newsletter body as HTML</p>" },
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch newsletter issue.")
+        .newsletter_issue_id
+}
+
+fn issue_bounce(bounce_type: &str, newsletter_issue_id: Uuid) -> String {
+    serde_json::json!({
+        "RecordType": "Bounce",
+        "Type": bounce_type,
+        "Email": "ursula@example.com",
+        "Metadata": { "newsletter_issue_id": newsletter_issue_id },
+    })
+    .to_string()
+}
+
+async fn queued_retries(app: &TestApp) -> Vec<i16> {
+    sqlx::query!("SELECT n_retries FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.n_retries)
+        .collect()
+}
+
+#[tokio::test]
+async fn soft_bounces_send_the_issue_again_until_out_of_retries() {
+    let app = spawn_app_with_configuration(|c| c.email_webhooks.max_bounce_retries = 2).await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "ursula").await;
+    let issue_id = publish_newsletter(&app).await;
+    sqlx::query!("DELETE FROM issue_delivery_queue")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let body = issue_bounce("SoftBounce", issue_id);
+
+    post_email_webhook(&app, &body, &sign(&body)).await;
+    assert_eq!(queued_retries(&app).await, vec![0]);
+
+    sqlx::query!("DELETE FROM issue_delivery_queue")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    post_email_webhook(&app, &body, &sign(&body)).await;
+    assert_eq!(queued_retries(&app).await, vec![1]);
+
+    sqlx::query!("DELETE FROM issue_delivery_queue")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    post_email_webhook(&app, &body, &sign(&body)).await;
+    assert!(queued_retries(&app).await.is_empty());
+    assert_eq!(subscriber_status(&app, subscriber_id).await, "confirmed");
+}
+
+#[tokio::test]
+async fn auto_replies_are_ignored() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "ursula").await;
+    let issue_id = publish_newsletter(&app).await;
+    let body = issue_bounce("AutoResponder", issue_id);
+
+    let response = post_email_webhook(&app, &body, &sign(&body)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app, subscriber_id).await, "confirmed");
+}
+
+#[tokio::test]
+async fn issue_reports_break_bounces_down_by_category() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "ursula@example.com", "ursula").await;
+    let issue_id = publish_newsletter(&app).await;
+    for bounce_type in ["AutoResponder", "Blocked", "HardBounce"] {
+        let body = issue_bounce(bounce_type, issue_id);
+        post_email_webhook(&app, &body, &sign(&body)).await;
+    }
+
+    let report: serde_json::Value = app
+        .api_request(
+            reqwest::Method::GET,
+            &format!("/admin/issues/{}/report", issue_id),
+        )
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report["bounces"],
+        serde_json::json!({ "hard": 1, "soft": 0, "block": 1, "auto_reply": 1 })
+    );
+}
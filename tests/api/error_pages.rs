@@ -0,0 +1,145 @@
+use reqwest::Method;
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn unknown_pages_get_an_error_page_with_the_request_id() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/no/such/page", app.address))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>404 Not Found</h1>"));
+    assert!(html_page.contains("request id <code>"));
+}
+
+#[tokio::test]
+async fn unknown_api_routes_get_problem_details() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_request(Method::GET, "/no/such/route")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["type"], "/problems/not-found");
+    assert_eq!(problem["status"], 404);
+    assert!(problem["trace_id"].is_string());
+}
+
+#[tokio::test]
+async fn unsupported_methods_get_an_error_page() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .delete(format!("{}/login", app.address))
+        .header("X-CSRF-Token", app.csrf_token().await)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 405);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("<h1>405 Method Not Allowed</h1>"));
+}
+
+#[tokio::test]
+async fn payloads_too_large_get_an_error_page() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscription(format!(
+            "name={}&email=ursula%40example.com",
+            "a".repeat(64 * 1024)
+        ))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 413);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("<h1>413 Payload Too Large</h1>"));
+}
+
+#[tokio::test]
+async fn pages_rendering_their_own_errors_are_left_alone() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_request(Method::GET, &format!("/topics/{}", uuid::Uuid::new_v4()))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["detail"], "Topic not found");
+}
+
+#[tokio::test]
+async fn request_ids_of_clients_are_returned_and_shown_on_error_pages() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/no/such/page", app.address))
+        .header("X-Request-Id", "support-ticket-42")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.headers()["X-Request-Id"], "support-ticket-42");
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("request id <code>support-ticket-42</code>"));
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_id_are_given_one_matching_the_trace_id() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_request(Method::GET, "/no/such/route")
+        .header("X-Request-Id", "<script>alert(1)</script>")
+        .send()
+        .await
+        .unwrap();
+
+    let request_id = response.headers()["X-Request-Id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(uuid::Uuid::parse_str(&request_id).is_ok());
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["trace_id"], request_id.as_str());
+}
+
+#[tokio::test]
+async fn successful_responses_carry_a_request_id() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/health_check", app.address))
+        .await
+        .unwrap();
+
+    assert!(response.status().is_success());
+    assert!(response.headers().contains_key("X-Request-Id"));
+}
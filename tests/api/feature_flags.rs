@@ -0,0 +1,29 @@
+use reqwest::Method;
+
+use crate::helpers::{spawn_app, spawn_app_with_configuration};
+
+#[tokio::test]
+async fn api_is_available_when_enabled() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_request(Method::GET, "/topics")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn api_returns_404_when_disabled() {
+    let app = spawn_app_with_configuration(|c| c.features.api = false).await;
+
+    let response = app
+        .api_request(Method::GET, "/topics")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 404);
+}
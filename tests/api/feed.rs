@@ -0,0 +1,70 @@
+use crate::helpers::{spawn_app, spawn_app_with_configuration, TestApp};
+
+async fn spawn_app_with_public_archive() -> TestApp {
+    spawn_app_with_configuration(|c| c.features.public_archive = true).await
+}
+
+async fn publish_issue(app: &TestApp, body: serde_json::Value) {
+    app.post_newsletters(body).await.error_for_status().unwrap();
+}
+
+async fn get_feed(app: &TestApp) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/feed.xml", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn published_issues_are_listed_in_the_feed() {
+    let app = spawn_app_with_public_archive().await;
+    publish_issue(
+        &app,
+        serde_json::json!({
+            "title": "Newsletter title",
+            "content": { "html": "<p>Newsletter body as HTML</p>" }
+        }),
+    )
+    .await;
+
+    let response = get_feed(&app).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/atom+xml; charset=utf-8"
+    );
+
+    let atom = response.text().await.unwrap();
+    assert!(atom.contains("<title>Newsletter</title>"));
+    assert!(atom.contains("<title>Newsletter title</title>"));
+    assert!(atom.contains("&lt;p&gt;Newsletter body as HTML&lt;/p&gt;"));
+}
+
+#[tokio::test]
+async fn issues_published_to_a_tag_are_left_out_of_the_feed() {
+    let app = spawn_app_with_public_archive().await;
+    publish_issue(
+        &app,
+        serde_json::json!({
+            "tag": "beta",
+            "title": "Only for beta testers",
+            "content": { "html": "<p>Newsletter body as HTML</p>" }
+        }),
+    )
+    .await;
+
+    let atom = get_feed(&app).await.text().await.unwrap();
+
+    assert!(!atom.contains("Only for beta testers"));
+    assert!(!atom.contains("<entry>"));
+}
+
+#[tokio::test]
+async fn the_feed_is_not_served_unless_the_public_archive_is_enabled() {
+    let app = spawn_app().await;
+
+    let response = get_feed(&app).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
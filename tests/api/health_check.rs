@@ -1,4 +1,4 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with_configuration};
 
 #[tokio::test]
 async fn health_check_works() {
@@ -14,3 +14,77 @@ async fn health_check_works() {
     assert!(response.status().is_success());
     assert_eq!(Some(0), response.content_length());
 }
+
+#[tokio::test]
+async fn the_base_url_probe_answers_with_the_nonce_of_the_instance() {
+    let (first_app, second_app) = (spawn_app().await, spawn_app().await);
+
+    let get_nonce = |address: String| async move {
+        let response = reqwest::get(format!("{}/health_check/probe", address))
+            .await
+            .expect("Failed to execute request.");
+        assert!(response.status().is_success());
+        response.text().await.unwrap()
+    };
+    let first_nonce = get_nonce(first_app.address.clone()).await;
+    let second_nonce = get_nonce(second_app.address.clone()).await;
+
+    assert!(!first_nonce.is_empty());
+    assert_ne!(first_nonce, second_nonce);
+}
+
+#[tokio::test]
+async fn the_configuration_report_lists_settings_with_secrets_redacted() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/health/config", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let entries: Vec<serde_json::Value> = response.json().await.unwrap();
+    let entry = |key: &str| {
+        entries
+            .iter()
+            .find(|entry| entry["key"] == key)
+            .unwrap_or_else(|| panic!("{} is missing", key))
+            .clone()
+    };
+    assert_eq!(entry("application.admin_base_path")["value"], "/admin");
+    assert_eq!(entry("application.admin_base_path")["type"], "string");
+    assert_eq!(entry("application.admin_base_path")["source"], "default");
+    assert_eq!(entry("application.hmac_secret")["value"], "[redacted]");
+    assert_eq!(entry("database.password")["value"], "[redacted]");
+}
+
+#[tokio::test]
+async fn the_configuration_report_is_not_public() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/health/config", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn responses_tell_which_instance_served_them() {
+    let test_app = spawn_app_with_configuration(|c| {
+        c.application.instance_id = "newsletter-eu-1".into();
+    })
+    .await;
+
+    let response = reqwest::get(format!("{}/health_check", test_app.address))
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.headers()["X-Instance-Id"], "newsletter-eu-1");
+}
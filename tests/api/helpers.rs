@@ -1,23 +1,32 @@
+use std::sync::Arc;
+
 use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
 use linkify::{LinkFinder, LinkKind};
 use newsletter::{
-    configuration::{get_configuration, DatabaseSettings},
+    configuration::{get_configuration, DatabaseSettings, DeliveryQueueSettings, Settings},
+    delegated_access::CapabilitySigner,
+    email_client::EmailClient,
+    issue_delivery_worker::{self, ExecutionOutcome},
+    referrals::ReferralLinks,
     startup::{get_connection_pool, Application},
     telemetry::{get_subscriber, init_subscriber},
+    token_generator::SeededTokenGenerator,
+    tracking::EmailTracker,
     user_role::UserRole,
+    webhook_delivery_worker,
 };
 use once_cell::sync::Lazy;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use url::Url;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::{MockServer, ResponseTemplate};
 
 static TRACING: Lazy<()> = Lazy::new(|| {
     if std::env::var("TEST_LOG").is_ok() {
-        let subscriber = get_subscriber("test".into(), "debug".into(), std::io::stdout);
+        let subscriber = get_subscriber("test".into(), "debug".into(), None, std::io::stdout);
         init_subscriber(subscriber);
     } else {
-        let subscriber = get_subscriber("test".into(), "debug".into(), std::io::sink);
+        let subscriber = get_subscriber("test".into(), "debug".into(), None, std::io::sink);
         init_subscriber(subscriber);
     }
 });
@@ -96,9 +105,64 @@ pub struct TestApp {
     pub email_server: MockServer,
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
+    pub email_client: EmailClient,
+    pub delivery_queue: DeliveryQueueSettings,
+    pub email_tracker: EmailTracker,
+    pub referral_links: ReferralLinks,
+    pub capability_signer: CapabilitySigner,
+    pub webhook_client: reqwest::Client,
+    /// Replays the tokens and codes handed out by the application, in order.
+    pub tokens: SeededTokenGenerator,
 }
 
 impl TestApp {
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = issue_delivery_worker::try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.email_tracker,
+                &self.referral_links,
+                &self.delivery_queue,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    /// Issue emails sent through `/email/batch`, in the order they were sent.
+    pub async fn delivered_issue_emails(&self) -> Vec<serde_json::Value> {
+        self.email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|request| request.url.path() == "/email/batch")
+            .flat_map(|request| {
+                let emails: Vec<serde_json::Value> = request.body_json().unwrap();
+                emails
+            })
+            .collect()
+    }
+
+    pub async fn dispatch_all_pending_webhook_events(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = webhook_delivery_worker::try_execute_task(
+                &self.db_pool,
+                &self.webhook_client,
+                &self.delivery_queue,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
     pub async fn post_subscription(&self, body: String) -> reqwest::Response {
         self.api_client
             .post(&format!("{}/subscriptions", self.address))
@@ -119,6 +183,16 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_newsletter_preview(&self, body: serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/newsletters/preview", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub fn get_links(&self, email_request: &wiremock::Request) -> Links {
         let body = email_request.body_json::<serde_json::Value>().unwrap();
 
@@ -152,12 +226,18 @@ impl TestApp {
     {
         self.api_client
             .post(&format!("{}/login", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    /// The CSRF token of the current session, as embedded in its forms.
+    pub async fn csrf_token(&self) -> String {
+        fetch_csrf_token(&self.api_client, &self.address).await
+    }
+
     pub async fn get_login_html(&self) -> String {
         self.api_client
             .get(&format!("{}/login", &self.address))
@@ -195,6 +275,7 @@ impl TestApp {
     {
         self.api_client
             .post(&format!("{}/admin/password", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
             .form(body)
             .send()
             .await
@@ -208,6 +289,89 @@ impl TestApp {
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
             .post(&format!("{}/admin/logout", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_all_sessions(&self) -> reqwest::Response {
+        self.api_client
+            .post(&format!("{}/admin/sessions/revoke_all", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_delete_subscribers<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(&format!("{}/admin/subscribers/delete", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_import_subscribers(&self, csv: &str) -> reqwest::Response {
+        self.post_subscribers_export("/admin/subscribers/import", "csv", csv)
+            .await
+    }
+
+    pub async fn post_import_preview(&self, source: &str, content: &str) -> reqwest::Response {
+        self.post_subscribers_export("/admin/subscribers/import/preview", source, content)
+            .await
+    }
+
+    pub async fn post_subscribers_export(
+        &self,
+        path: &str,
+        source: &str,
+        content: &str,
+    ) -> reqwest::Response {
+        let part = reqwest::multipart::Part::text(content.to_string())
+            .file_name("subscribers")
+            .mime_str("text/plain")
+            .unwrap();
+        let form = reqwest::multipart::Form::new()
+            .text("source", source.to_string())
+            .part("file", part);
+
+        self.api_client
+            .post(&format!("{}{}", &self.address, path))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .multipart(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_actions_html(&self) -> String {
+        self.api_client
+            .get(&format!("{}/admin/actions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_review_admin_action(
+        &self,
+        action_id: Uuid,
+        review: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(&format!(
+                "{}/admin/actions/{}/{}",
+                &self.address, action_id, review
+            ))
+            .header("X-CSRF-Token", self.csrf_token().await)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -219,6 +383,7 @@ impl TestApp {
     {
         self.api_client
             .post(&format!("{}/admin/collaborator", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
             .form(body)
             .send()
             .await
@@ -256,12 +421,27 @@ impl TestApp {
     {
         self.api_client
             .post(&format!("{}/collaborator/register", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub fn api_request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.api_client
+            .request(method, format!("{}/api/v1{}", &self.address, path))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+    }
+
+    pub async fn create_admin(&self) -> TestUser {
+        let admin = TestUser::generate();
+
+        admin.store(&self.db_pool, UserRole::Admin).await;
+
+        admin
+    }
+
     pub async fn create_collaborator(&self) -> TestUser {
         let collaborator = TestUser::generate();
 
@@ -274,6 +454,10 @@ impl TestApp {
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with_configuration(|_| {}).await
+}
+
+pub async fn spawn_app_with_configuration(customize: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
@@ -283,15 +467,21 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        c.blob_store.local.path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        customize(&mut c);
 
         c
     };
 
     configure_database(&configuration.database).await;
 
-    let application = Application::build(configuration.clone())
-        .await
-        .expect("Fail to build application");
+    let token_seed = rand::random();
+    let application = Application::build_with_token_generator(
+        configuration.clone(),
+        Arc::new(SeededTokenGenerator::new(token_seed)),
+    )
+    .await
+    .expect("Fail to build application");
     let port = application.port();
     let address = format!("http://127.0.0.1:{}", port);
 
@@ -302,19 +492,32 @@ pub async fn spawn_app() -> TestApp {
 
     let test_user = TestUser::generate();
 
-    let api_client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .cookie_store(true)
-        .build()
-        .unwrap();
+    let api_client = build_api_client();
 
     let test_app = TestApp {
         address,
         port,
+        email_client: configuration
+            .email_client
+            .client()
+            .with_email_log(db_pool.clone()),
         db_pool,
         email_server,
         test_user,
         api_client,
+        delivery_queue: configuration.delivery_queue,
+        email_tracker: EmailTracker::new(
+            configuration.features.tracking,
+            &configuration.application.base_url,
+            &configuration.application.hmac_secret,
+        ),
+        referral_links: ReferralLinks::new(
+            configuration.features.referrals,
+            &configuration.application.base_url,
+        ),
+        capability_signer: CapabilitySigner::new(&configuration.application.hmac_secret),
+        webhook_client: configuration.webhooks.client(),
+        tokens: SeededTokenGenerator::new(token_seed),
     };
 
     test_app
@@ -325,6 +528,26 @@ pub async fn spawn_app() -> TestApp {
     test_app
 }
 
+/// Answers a batch of emails the way Postmark does when it accepts all of
+/// them, to be mounted on `/email/batch`.
+pub fn accept_email_batch(request: &wiremock::Request) -> ResponseTemplate {
+    let emails: Vec<serde_json::Value> = request.body_json().unwrap();
+    let results: Vec<serde_json::Value> = emails
+        .iter()
+        .map(|email| serde_json::json!({ "ErrorCode": 0, "Message": "OK", "To": email["To"] }))
+        .collect();
+
+    ResponseTemplate::new(200).set_body_json(results)
+}
+
+pub fn build_api_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap()
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("location").unwrap(), location);
@@ -335,6 +558,27 @@ pub struct InvitationResponse {
     pub validation_code: String,
 }
 
+pub async fn fetch_csrf_token(client: &reqwest::Client, address: &str) -> String {
+    let html = client
+        .get(&format!("{}/login", address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+
+    extract_csrf_token(&html)
+}
+
+pub fn extract_csrf_token(html: &str) -> String {
+    let (_, rest) = html
+        .split_once(r#"name="csrf_token" value=""#)
+        .expect("The page has no CSRF token");
+
+    rest.split('"').next().unwrap().to_string()
+}
+
 pub async fn extract_validation_code(response: reqwest::Response) -> String {
     response
         .json::<InvitationResponse>()
@@ -0,0 +1,38 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn home_is_rendered_in_the_preferred_language_translated_to() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(&format!("{}/", app.address))
+        .header("Accept-Language", "de, pt-PT;q=0.9, en;q=0.8")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let vary = response.headers()["Vary"].to_str().unwrap();
+    assert!(vary.split(", ").any(|header| header == "Accept-Language"));
+    let html = response.text().await.unwrap();
+    assert!(html.contains(r#"<html lang="pt">"#));
+    assert!(html.contains("Bem-vindo à nossa newsletter!"));
+}
+
+#[tokio::test]
+async fn home_falls_back_to_english() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(&format!("{}/", app.address))
+        .header("Accept-Language", "de")
+        .send()
+        .await
+        .unwrap();
+
+    let html = response.text().await.unwrap();
+    assert!(html.contains(r#"<html lang="en">"#));
+    assert!(html.contains("Welcome to our newsletter!"));
+}
@@ -0,0 +1,143 @@
+use reqwest::Method;
+
+use crate::helpers::{spawn_app, spawn_app_with_configuration, TestApp};
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01";
+
+fn images_request(app: &TestApp, method: Method, path: &str) -> reqwest::RequestBuilder {
+    app.api_client
+        .request(
+            method,
+            format!("{}/api/v1/admin/images{}", &app.address, path),
+        )
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+}
+
+async fn upload_image(app: &TestApp, content_type: &str, body: &'static [u8]) -> reqwest::Response {
+    images_request(app, Method::POST, "")
+        .header("Content-Type", content_type)
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn uploaded_images_can_be_downloaded_through_their_url() {
+    let app = spawn_app().await;
+
+    let response = upload_image(&app, "image/png", PNG).await;
+    assert_eq!(response.status(), 201);
+    let uploaded: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(uploaded["size"], PNG.len());
+
+    let name = uploaded["name"].as_str().unwrap();
+    assert!(name.ends_with(".png"));
+    assert!(uploaded["url"]
+        .as_str()
+        .unwrap()
+        .ends_with(&format!("/images/{}", name)));
+
+    let response = app
+        .api_client
+        .get(format!("{}/images/{}", app.address, name))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 302);
+    let location = response.headers()["Location"].to_str().unwrap();
+    assert!(location.contains("/blobs/images/"));
+
+    let blob_path = &location[location.find("/blobs/").unwrap()..];
+    let response = app
+        .api_client
+        .get(format!("{}{}", app.address, blob_path))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["Content-Type"], "image/png");
+    assert_eq!(response.bytes().await.unwrap(), PNG);
+}
+
+#[tokio::test]
+async fn blobs_cannot_be_downloaded_without_a_valid_signature() {
+    let app = spawn_app().await;
+    let uploaded: serde_json::Value = upload_image(&app, "image/png", PNG)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let name = uploaded["name"].as_str().unwrap();
+
+    for query in [
+        "",
+        "?expires=4102444800&signature=00",
+        "?expires=1&signature=zz",
+    ] {
+        let response = app
+            .api_client
+            .get(format!("{}/blobs/images/{}{}", app.address, name, query))
+            .send()
+            .await
+            .unwrap();
+
+        assert!(response.status().is_client_error(), "{} was served", query);
+    }
+}
+
+#[tokio::test]
+async fn images_not_matching_their_content_type_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = upload_image(&app, "image/png", b"GIF89a\x01\0\x01\0\0\0").await;
+    assert_eq!(response.status(), 422);
+
+    let response = upload_image(&app, "text/html", b"<script></script>").await;
+    assert_eq!(response.status(), 415);
+}
+
+#[tokio::test]
+async fn images_over_the_size_limit_are_rejected() {
+    let app = spawn_app_with_configuration(|c| c.blob_store.max_upload_bytes = 16).await;
+
+    let response = upload_image(&app, "image/png", PNG).await;
+
+    assert_eq!(response.status(), 413);
+}
+
+#[tokio::test]
+async fn deleted_images_are_gone() {
+    let app = spawn_app().await;
+    let uploaded: serde_json::Value = upload_image(&app, "image/png", PNG)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let name = uploaded["name"].as_str().unwrap();
+
+    let response = images_request(&app, Method::DELETE, &format!("/{}", name))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+
+    let location = app
+        .api_client
+        .get(format!("{}/images/{}", app.address, name))
+        .send()
+        .await
+        .unwrap()
+        .headers()["Location"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let blob_path = &location[location.find("/blobs/").unwrap()..];
+    let response = app
+        .api_client
+        .get(format!("{}{}", app.address, blob_path))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
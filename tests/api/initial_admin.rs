@@ -0,0 +1,51 @@
+use newsletter::configuration::InitialAdminSettings;
+use secrecy::Secret;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app_with_configuration};
+
+fn initial_admin(username: &str, password: &str) -> InitialAdminSettings {
+    InitialAdminSettings {
+        username: username.into(),
+        password_hash: None,
+        password: Some(Secret::new(password.into())),
+    }
+}
+
+#[tokio::test]
+async fn the_initial_admin_can_log_in_right_away() {
+    let app = spawn_app_with_configuration(|c| {
+        c.application.initial_admin = Some(initial_admin("bootstrap", "a-long-bootstrap-password"));
+    })
+    .await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "bootstrap",
+            "password": "a-long-bootstrap-password",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let role =
+        sqlx::query!(r#"SELECT role::text AS "role!" FROM users WHERE username = 'bootstrap'"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap()
+            .role;
+    assert_eq!(role, "admin");
+}
+
+#[tokio::test]
+async fn the_initial_admin_needs_a_password_or_a_hash() {
+    let mut configuration = newsletter::configuration::get_configuration().unwrap();
+    configuration.application.port = 0;
+    configuration.application.initial_admin = Some(InitialAdminSettings {
+        username: "bootstrap".into(),
+        password_hash: None,
+        password: None,
+    });
+
+    let outcome = newsletter::startup::Application::build(configuration).await;
+
+    assert!(outcome.is_err());
+}
@@ -0,0 +1,135 @@
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn login(app: &TestApp) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+async fn get_invitation_template_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/admin/templates/invitation", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+async fn post_invitation_template(
+    app: &TestApp,
+    subject: &str,
+    html_body: &str,
+    text_body: &str,
+) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/templates/invitation", app.address))
+        .form(&[
+            ("subject", subject),
+            ("html_body", html_body),
+            ("text_body", text_body),
+            ("csrf_token", &app.csrf_token().await),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn the_invitation_editor_starts_from_the_default_email() {
+    let app = spawn_app().await;
+    login(&app).await;
+
+    let html = get_invitation_template_html(&app).await;
+
+    assert!(html.contains(r#"value="Welcome!""#));
+    assert!(html.contains("to register as collaborator."));
+    assert!(html.contains("No version was saved yet."));
+}
+
+#[tokio::test]
+async fn saved_invitations_are_sent_to_new_collaborators() {
+    let app = spawn_app().await;
+    login(&app).await;
+
+    let response = post_invitation_template(
+        &app,
+        "Join the team",
+        r#"<a href="{{ registration_link | escape_attribute | safe }}">Join us</a>"#,
+        "Join us at {{ registration_link | text }}",
+    )
+    .await;
+    assert_is_redirect_to(&response, "/admin/templates/invitation");
+
+    let html = get_invitation_template_html(&app).await;
+    assert!(html.contains("Saved version 1 of the invitation"));
+    assert!(html.contains(r#"value="Join the team""#));
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .invite_collaborator(&serde_json::json!({ "email": "ursula_le_guin@gmail.com" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["Subject"], "Join the team");
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains("/collaborator?invitation_token="));
+    assert!(body["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("Join us at "));
+}
+
+#[tokio::test]
+async fn invitations_without_the_registration_link_are_rejected() {
+    let app = spawn_app().await;
+    login(&app).await;
+
+    let response =
+        post_invitation_template(&app, "Join the team", "<p>Join us</p>", "Join us").await;
+    assert_is_redirect_to(&response, "/admin/templates/invitation");
+
+    let html = get_invitation_template_html(&app).await;
+    assert!(html.contains("Both bodies must use the link"));
+    assert!(html.contains("No version was saved yet."));
+}
+
+#[tokio::test]
+async fn collaborators_cannot_edit_the_invitation() {
+    let app = spawn_app().await;
+    let collaborator = app.create_collaborator().await;
+    app.post_login(&serde_json::json!({
+        "username": &collaborator.username,
+        "password": &collaborator.password,
+    }))
+    .await;
+
+    let response = post_invitation_template(
+        &app,
+        "Join the team",
+        "{{ registration_link }}",
+        "{{ registration_link }}",
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 405);
+}
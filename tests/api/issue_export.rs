@@ -0,0 +1,105 @@
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn publish_newsletter(app: &TestApp) -> Uuid {
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch newsletter issue.")
+        .newsletter_issue_id
+}
+
+async fn login(app: &TestApp) {
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+}
+
+async fn export(app: &TestApp, newsletter_issue_id: Uuid, format: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!(
+            "{}/admin/newsletters/{}/export?format={}",
+            app.address, newsletter_issue_id, format
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn issues_are_exported_to_pdf() {
+    let app = spawn_app().await;
+    let newsletter_issue_id = publish_newsletter(&app).await;
+    login(&app).await;
+
+    let response = export(&app, newsletter_issue_id, "pdf").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "application/pdf");
+    assert_eq!(
+        response.headers()["Content-Disposition"],
+        r#"attachment; filename="newsletter-title.pdf""#
+    );
+    let document = response.bytes().await.unwrap();
+    assert!(document.starts_with(b"%PDF-"));
+}
+
+#[tokio::test]
+async fn issues_are_exported_to_epub() {
+    let app = spawn_app().await;
+    let newsletter_issue_id = publish_newsletter(&app).await;
+    login(&app).await;
+
+    let response = export(&app, newsletter_issue_id, "epub").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "application/epub+zip");
+    let document = response.bytes().await.unwrap();
+    assert!(document.starts_with(b"PK"));
+    assert_eq!(&document[30..38], b"mimetype");
+}
+
+#[tokio::test]
+async fn unknown_formats_are_rejected() {
+    let app = spawn_app().await;
+    let newsletter_issue_id = publish_newsletter(&app).await;
+    login(&app).await;
+
+    let response = export(&app, newsletter_issue_id, "docx").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn exporting_an_unknown_issue_returns_404() {
+    let app = spawn_app().await;
+    login(&app).await;
+
+    let response = export(&app, Uuid::new_v4(), "pdf").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_issues() {
+    let app = spawn_app().await;
+    let newsletter_issue_id = publish_newsletter(&app).await;
+
+    let response = export(&app, newsletter_issue_id, "pdf").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
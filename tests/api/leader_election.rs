@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use newsletter::leader_election::{LeaderElection, SingletonJob};
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn a_single_instance_leads_a_job_until_it_steps_down() {
+    let app = spawn_app().await;
+    let mut first = LeaderElection::new(app.db_pool.clone(), SingletonJob::Maintenance, "first");
+    let mut second = LeaderElection::new(app.db_pool.clone(), SingletonJob::Maintenance, "second");
+
+    assert!(first.is_leader().await);
+    assert!(first.is_leader().await);
+    assert!(!second.is_leader().await);
+
+    first.step_down();
+
+    // The lock goes away once Postgres notices the connection was closed.
+    let mut took_over = false;
+    for _ in 0..50 {
+        if second.is_leader().await {
+            took_over = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(took_over);
+}
+
+#[tokio::test]
+async fn jobs_are_led_independently() {
+    let app = spawn_app().await;
+    let mut maintenance =
+        LeaderElection::new(app.db_pool.clone(), SingletonJob::Maintenance, "first");
+    let mut weekly_report =
+        LeaderElection::new(app.db_pool.clone(), SingletonJob::WeeklyReport, "second");
+
+    assert!(maintenance.is_leader().await);
+    assert!(weekly_report.is_leader().await);
+}
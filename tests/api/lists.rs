@@ -0,0 +1,187 @@
+use newsletter::{domain::SubscriptionStatus, newsletter_list::DEFAULT_LIST_ID};
+use reqwest::Method;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{accept_email_batch, spawn_app, TestApp};
+
+async fn create_list(app: &TestApp, name: &str) -> Uuid {
+    let response = app
+        .api_request(Method::POST, "/lists")
+        .json(&serde_json::json!({ "name": name }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 201);
+
+    let list: serde_json::Value = response.json().await.unwrap();
+    list["id"].as_str().unwrap().parse().unwrap()
+}
+
+async fn post_list_subscription(app: &TestApp, list_id: Uuid, body: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/lists/{}/subscriptions", app.address, list_id))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body.to_string())
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn create_confirmed_list_subscriber(app: &TestApp, list_id: Uuid, email: &str) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let body = format!("name=le%20guin&email={}", urlencoding::encode(email));
+    post_list_subscription(app, list_id, &body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    reqwest::get(app.get_links(&email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn lists_can_be_created_and_listed() {
+    let app = spawn_app().await;
+
+    create_list(&app, "Release notes").await;
+
+    let lists: serde_json::Value = app
+        .api_request(Method::GET, "/lists")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let names: Vec<_> = lists
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|list| list["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["Newsletter", "Release notes"]);
+}
+
+#[tokio::test]
+async fn duplicated_list_names_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_request(Method::POST, "/lists")
+        .json(&serde_json::json!({ "name": "Newsletter" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_list_returns_404() {
+    let app = spawn_app().await;
+
+    let response = post_list_subscription(
+        &app,
+        Uuid::new_v4(),
+        "name=le%20guin&email=ursula_le_guin%40gmail.com",
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn the_same_email_can_subscribe_to_several_lists() {
+    let app = spawn_app().await;
+    let list_id = create_list(&app, "Release notes").await;
+
+    create_confirmed_list_subscriber(&app, list_id, "ursula_le_guin@gmail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let subscriptions = sqlx::query!(
+        r#"SELECT list_id, status as "status: SubscriptionStatus" FROM subscriptions ORDER BY status DESC"#
+    )
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriptions.len(), 2);
+    assert_eq!(subscriptions[0].list_id, list_id);
+    assert_eq!(subscriptions[0].status, SubscriptionStatus::Confirmed);
+    assert_eq!(
+        subscriptions[1].status,
+        SubscriptionStatus::PendingConfirmation
+    );
+}
+
+#[tokio::test]
+async fn issues_are_only_delivered_to_the_subscribers_of_their_list() {
+    let app = spawn_app().await;
+    let list_id = create_list(&app, "Release notes").await;
+    create_confirmed_list_subscriber(&app, list_id, "ursula_le_guin@gmail.com").await;
+    create_confirmed_list_subscriber(&app, DEFAULT_LIST_ID, "octavia_butler@gmail.com").await;
+
+    Mock::given(path("/email/batch"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "list_id": list_id,
+            "title": "Newsletter title",
+            "content": {
+                "text": "New body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn publishing_to_an_unknown_list_returns_404() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "list_id": Uuid::new_v4(),
+            "title": "Newsletter title",
+            "content": {
+                "text": "New body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
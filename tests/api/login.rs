@@ -1,4 +1,13 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use std::time::Duration;
+
+use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
+use secrecy::Secret;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with_configuration, TestApp};
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -34,3 +43,212 @@ async fn redirect_to_admin_dashboard_after_login_success() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
 }
+
+#[tokio::test]
+async fn usernames_are_case_insensitive_at_login() {
+    let app = spawn_app().await;
+
+    let login_body = serde_json::json!({
+        "username": app.test_user.username.to_uppercase(),
+        "password": app.test_user.password,
+    });
+    let response = app.post_login(&login_body).await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn users_registered_before_the_username_rules_can_still_log_in() {
+    let app = spawn_app().await;
+    sqlx::query!(
+        "UPDATE users SET username = 'le guin' WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let login_body = serde_json::json!({
+        "username": "Le Guin",
+        "password": app.test_user.password,
+    });
+    let response = app.post_login(&login_body).await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn failed_logins_with_an_invalid_username_are_recorded() {
+    let app = spawn_app().await;
+
+    let login_body = serde_json::json!({
+        "username": "No Spaces Allowed",
+        "password": "random-password",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Authentication failed</i></p>"));
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let html_page = app
+        .api_client
+        .get(&format!("{}/admin/failed_logins", app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<td>no spaces allowed</td>"));
+}
+
+#[tokio::test]
+async fn sessions_work_with_a_short_hmac_secret() {
+    let app = spawn_app_with_configuration(|c| {
+        c.application.hmac_secret = Secret::new("short-secret".to_string());
+    })
+    .await;
+
+    let login_body = serde_json::json!({
+        "username": app.test_user.username,
+        "password": app.test_user.password,
+    });
+    let response = app.post_login(&login_body).await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn outdated_password_hashes_are_upgraded_on_login() {
+    let app = spawn_app().await;
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let outdated_hash = Argon2::new(
+        Algorithm::Argon2i,
+        Version::V0x13,
+        Params::new(4096, 2, 1, None).unwrap(),
+    )
+    .hash_password(app.test_user.password.as_bytes(), &salt)
+    .unwrap()
+    .to_string();
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_id = $2",
+        outdated_hash,
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let saved = sqlx::query!(
+        "SELECT password_hash FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(saved
+        .password_hash
+        .starts_with("$argon2id$v=19$m=12288,t=3,p=1$"));
+
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+async fn post_failed_login(app: &TestApp, user_agent: &str) {
+    let response = app
+        .api_client
+        .post(&format!("{}/login", &app.address))
+        .header("X-CSRF-Token", app.csrf_token().await)
+        .header("User-Agent", user_agent)
+        .form(&serde_json::json!({
+            "username": "mallory",
+            "password": "hunter2",
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn admins_see_the_failed_logins() {
+    let app = spawn_app().await;
+
+    post_failed_login(&app, "curl/8.5.0").await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app
+        .api_client
+        .get(&format!("{}/admin/failed_logins", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<td>mallory</td>"));
+    assert!(html_page.contains("<td>127.0.0.1</td>"));
+    assert!(html_page.contains("<td>curl&#x2F;8.5.0</td>"));
+    assert!(!html_page.contains(&format!("<td>{}</td>", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn failed_logins_are_sent_to_the_alerts_webhook() {
+    let alerts = MockServer::start().await;
+    let app = spawn_app_with_configuration(|c| {
+        c.login_alerts.webhook_url = Some(format!("{}/alerts", alerts.uri()));
+    })
+    .await;
+
+    Mock::given(path("/alerts"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&alerts)
+        .await;
+
+    post_failed_login(&app, "curl/8.5.0").await;
+
+    // Alerts are sent in the background.
+    let mut received = Vec::new();
+    for _ in 0..100 {
+        received = alerts.received_requests().await.unwrap();
+        if !received.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(received.len(), 1);
+    let alert: serde_json::Value = received[0].body_json().unwrap();
+    assert_eq!(alert["type"], "login.failed");
+    assert_eq!(alert["username"], "mallory");
+    assert_eq!(alert["client_ip"], "127.0.0.1");
+    assert_eq!(alert["user_agent"], "curl/8.5.0");
+}
@@ -1,10 +1,60 @@
+mod access_links;
+mod admin_actions;
+mod admin_commands;
 mod admin_dashboard;
+mod api_admin;
+mod api_dynamic_settings;
+mod api_log_level;
+mod api_segments;
+mod api_subscriber_events;
+mod api_tags;
+mod api_tokens;
+mod api_topics;
 mod change_password;
 mod collaborators;
 mod collaborators_registration;
+mod cors;
+mod csrf;
+mod delivery_queue;
+mod email_log;
+mod email_webhooks;
+mod error_pages;
+mod feature_flags;
+mod feed;
 mod health_check;
 mod helpers;
+mod home;
+mod images;
+mod initial_admin;
+mod invitation_template;
+mod issue_export;
+mod leader_election;
+mod lists;
 mod login;
+mod maintenance;
+mod maintenance_mode;
+mod migrations;
 mod newsletter;
+mod openapi;
+mod output_encoding;
+mod payload_limits;
+mod preferences;
+mod profile;
+mod public_stats;
+mod referrals;
+mod sessions;
+mod signup_page;
+mod source_allow_list;
+mod static_files;
+mod subscriber_duplicates;
+mod subscribers_data_export;
+mod subscribers_erasure;
+mod subscribers_import;
 mod subscriptions;
 mod subscriptions_confirm;
+mod suppression_sync;
+mod tls;
+mod tracking;
+mod webhooks;
+mod weekly_digest;
+mod weekly_report;
@@ -0,0 +1,154 @@
+use chrono::{Months, Utc};
+use newsletter::{
+    configuration::{MaintenanceSettings, TableMaintenanceSettings},
+    maintenance::{run_maintenance, HOT_TABLES},
+    newsletter_list::DEFAULT_LIST_ID,
+};
+use uuid::Uuid;
+
+use crate::helpers::{spawn_app, TestApp};
+
+fn maintenance_settings(retention_months: i32) -> MaintenanceSettings {
+    MaintenanceSettings {
+        interval_seconds: 3600,
+        partitions_ahead_months: 2,
+        retention_months,
+        tables: TableMaintenanceSettings {
+            enabled: false,
+            window_start_hour: 3,
+            window_end_hour: 5,
+            reindex: false,
+        },
+    }
+}
+
+async fn partition_exists(app: &TestApp, name: &str) -> bool {
+    sqlx::query!(r#"SELECT to_regclass($1) IS NOT NULL AS "exists!""#, name)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .exists
+}
+
+fn partition_name(months_from_now: i32) -> String {
+    let now = Utc::now();
+    let month = if months_from_now >= 0 {
+        now.checked_add_months(Months::new(months_from_now as u32))
+    } else {
+        now.checked_sub_months(Months::new(months_from_now.unsigned_abs()))
+    };
+
+    month.unwrap().format("subscriber_events_%Y_%m").to_string()
+}
+
+#[tokio::test]
+async fn partitions_of_the_upcoming_months_are_created_in_advance() {
+    let app = spawn_app().await;
+
+    run_maintenance(&app.db_pool, &maintenance_settings(0))
+        .await
+        .unwrap();
+
+    for months_from_now in 0..=2 {
+        assert!(partition_exists(&app, &partition_name(months_from_now)).await);
+    }
+}
+
+#[tokio::test]
+async fn partitions_past_the_retention_period_are_dropped_with_their_events() {
+    let app = spawn_app().await;
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'ursula_le_guin@gmail.com', 'le guin', now(), 'confirmed')
+        "#,
+        subscriber_id,
+        DEFAULT_LIST_ID,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    for months_ago in [24, 6] {
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriber_events (subscriber_id, event_type, occurred_at)
+            VALUES ($1, 'subscribed', now() - make_interval(months => $2))
+            "#,
+            subscriber_id,
+            months_ago,
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "SELECT create_subscriber_events_partition(now() - make_interval(months => $1))",
+            months_ago,
+        )
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    }
+
+    run_maintenance(&app.db_pool, &maintenance_settings(12))
+        .await
+        .unwrap();
+
+    assert!(!partition_exists(&app, &partition_name(-24)).await);
+    assert!(partition_exists(&app, &partition_name(-6)).await);
+    let events = sqlx::query!("SELECT occurred_at FROM subscriber_events")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 1);
+}
+
+#[tokio::test]
+async fn hot_tables_are_analyzed_once_per_window() {
+    let app = spawn_app().await;
+    let mut settings = maintenance_settings(0);
+    settings.tables = TableMaintenanceSettings {
+        enabled: true,
+        window_start_hour: 0,
+        window_end_hour: 24,
+        reindex: false,
+    };
+
+    run_maintenance(&app.db_pool, &settings).await.unwrap();
+    run_maintenance(&app.db_pool, &settings).await.unwrap();
+
+    let runs = sqlx::query!("SELECT table_name, operation, succeeded FROM table_maintenance_runs")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(runs.len(), HOT_TABLES.len());
+    assert!(runs
+        .iter()
+        .all(|run| run.operation == "analyze" && run.succeeded));
+}
+
+#[tokio::test]
+async fn admins_can_run_the_table_maintenance_on_demand() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_request(reqwest::Method::POST, "/admin/maintenance/tables/run")
+        .json(&serde_json::json!({ "reindex": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let runs: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert_eq!(runs.len(), 2 * HOT_TABLES.len());
+    assert!(runs.iter().all(|run| run["succeeded"] == true));
+
+    let response = app
+        .api_request(reqwest::Method::GET, "/admin/maintenance/tables")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let runs: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert_eq!(runs.len(), 2 * HOT_TABLES.len());
+    assert_eq!(runs[0]["operation"], "reindex");
+}
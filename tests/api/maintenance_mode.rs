@@ -0,0 +1,61 @@
+use chrono::{Duration, Utc};
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app_with_configuration};
+
+#[tokio::test]
+async fn public_endpoints_are_unavailable_during_maintenance() {
+    let app = spawn_app_with_configuration(|c| {
+        c.maintenance_mode.enabled = true;
+        c.maintenance_mode.retry_after_seconds = 120;
+    })
+    .await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let response = app.post_subscription(body.into()).await;
+
+    assert_eq!(response.status().as_u16(), 503);
+    assert_eq!(response.headers()["Retry-After"], "120");
+    assert!(response.text().await.unwrap().contains("maintenance"));
+}
+
+#[tokio::test]
+async fn admin_ui_stays_reachable_during_maintenance() {
+    let app = spawn_app_with_configuration(|c| c.maintenance_mode.enabled = true).await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn maintenance_is_left_at_the_configured_time() {
+    let app = spawn_app_with_configuration(|c| {
+        c.maintenance_mode.enabled = true;
+        c.maintenance_mode.until = Some(Utc::now() - Duration::seconds(1));
+    })
+    .await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let response = app.post_subscription(body.into()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
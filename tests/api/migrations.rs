@@ -0,0 +1,43 @@
+use newsletter::{
+    configuration::get_configuration,
+    startup::{get_connection_pool, Application},
+};
+use sqlx::{Connection, Executor, PgConnection};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn pending_migrations_are_run_on_startup_when_enabled() {
+    let configuration = {
+        let mut c = get_configuration().expect("Failed to read configuration.");
+        c.database.database_name = Uuid::new_v4().to_string();
+        c.application.port = 0;
+        c.application.migrate_on_startup = true;
+
+        c
+    };
+    // An empty database, left for the application to migrate.
+    let mut connection = PgConnection::connect_with(&configuration.database.without_db())
+        .await
+        .expect("Failed to connect to Postgres.");
+    connection
+        .execute(
+            format!(
+                r#"CREATE DATABASE "{}";"#,
+                configuration.database.database_name
+            )
+            .as_str(),
+        )
+        .await
+        .expect("Failed to create database.");
+
+    Application::build(configuration.clone())
+        .await
+        .expect("Fail to build application");
+
+    let pool = get_connection_pool(&configuration.database);
+    let subscriptions: i64 = sqlx::query_scalar("SELECT count(*) FROM subscriptions")
+        .fetch_one(&pool)
+        .await
+        .expect("The subscriptions table should have been created");
+    assert_eq!(subscriptions, 0);
+}
@@ -1,10 +1,15 @@
+use newsletter::configuration::SafeBrowsingSettings;
+use secrecy::Secret;
 use uuid::Uuid;
 use wiremock::{
     matchers::{any, method, path},
-    Mock, ResponseTemplate,
+    Mock, MockServer, ResponseTemplate,
 };
 
-use crate::helpers::{spawn_app, Links, TestApp};
+use crate::helpers::{
+    accept_email_batch, assert_is_redirect_to, spawn_app, spawn_app_with_configuration, Links,
+    TestApp,
+};
 
 async fn create_unconfirmed_subscriber(app: &TestApp) -> Links {
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
@@ -61,6 +66,7 @@ async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
     let response = app.post_newsletters(newsletter_request_body).await;
 
     assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
 }
 
 #[tokio::test]
@@ -68,8 +74,8 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
     let app = spawn_app().await;
     create_confirmed_subscriber(&app).await;
 
-    Mock::given(any())
-        .respond_with(ResponseTemplate::new(200))
+    Mock::given(path("/email/batch"))
+        .respond_with(accept_email_batch)
         .expect(1)
         .mount(&app.email_server)
         .await;
@@ -84,6 +90,62 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
     let response = app.post_newsletters(newsletter_request_body).await;
 
     assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
+}
+
+async fn delivered_text_body(app: &TestApp, content: serde_json::Value) -> String {
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content": content,
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let body = app.delivered_issue_emails().await.pop().unwrap();
+
+    body["TextBody"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn text_body_is_generated_from_the_html_when_missing() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let text = delivered_text_body(
+        &app,
+        serde_json::json!({
+            "html": r#"<h1>News</h1><p>Read <a href="https://example.com">more</a></p>"#,
+        }),
+    )
+    .await;
+
+    assert_eq!(text, "News\n\nRead more (https://example.com)");
+}
+
+#[tokio::test]
+async fn explicit_text_body_overrides_the_generated_one() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let text = delivered_text_body(
+        &app,
+        serde_json::json!({
+            "html": "<p>Newsletter body as HTML</p>",
+            "text": "Hand written body",
+        }),
+    )
+    .await;
+
+    assert_eq!(text, "Hand written body");
 }
 
 #[tokio::test]
@@ -225,3 +287,513 @@ async fn default_hashed_password_matches_non_existing_user() {
         response.headers()["WWW-Authenticate"]
     );
 }
+
+async fn spawn_app_validating_links() -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.link_validation.enabled = true;
+        c.link_validation.blocklist = vec!["evil.com".into()];
+    })
+    .await
+}
+
+fn newsletter_with_link(link: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": format!("Check {}", link),
+            "html": format!(r#"<p>Check <a href="{0}">{0}</a></p>"#, link),
+        }
+    })
+}
+
+#[tokio::test]
+async fn newsletters_with_blocked_links_are_refused() {
+    let app = spawn_app_validating_links().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters(newsletter_with_link("https://www.evil.com/win"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["type"], "/problems/flagged-links");
+    assert_eq!(
+        problem["flagged_links"],
+        serde_json::json!(["https://www.evil.com/win"])
+    );
+}
+
+async fn spawn_app_checking_base_url(checker: &MockServer) -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.base_url_check.enabled = true;
+        c.base_url_check.checker_url = checker.uri();
+        c.base_url_check.cache_seconds = 0;
+    })
+    .await
+}
+
+#[tokio::test]
+async fn newsletters_are_refused_when_links_would_not_reach_the_app() {
+    let checker = MockServer::start().await;
+    let app = spawn_app_checking_base_url(&checker).await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(502))
+        .expect(1)
+        .mount(&checker)
+        .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters(newsletter_with_link("https://example.com"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 503);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["type"], "/problems/unreachable-base-url");
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn newsletters_are_published_when_links_reach_the_app() {
+    let checker = MockServer::start().await;
+    let app = spawn_app_checking_base_url(&checker).await;
+    create_confirmed_subscriber(&app).await;
+    let nonce = reqwest::get(format!("{}/health_check/probe", app.address))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200).set_body_string(nonce))
+        .expect(1)
+        .mount(&checker)
+        .await;
+    Mock::given(path("/email/batch"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters(newsletter_with_link("https://example.com"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn newsletters_with_links_flagged_by_safe_browsing_are_refused() {
+    let app = spawn_app_with_configuration(|c| {
+        c.link_validation.enabled = true;
+        c.link_validation.safe_browsing = Some(SafeBrowsingSettings {
+            base_url: c.email_client.base_url.clone(),
+            api_key: Secret::new("api-key".into()),
+            timeout_milliseconds: 1000,
+        });
+    })
+    .await;
+
+    Mock::given(path("/v4/threatMatches:find"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "matches": [{ "threat": { "url": "https://malware.test/" } }]
+        })))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters(newsletter_with_link("https://malware.test/"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[tokio::test]
+async fn overriding_flagged_links_requires_the_approval_of_an_admin() {
+    let app = spawn_app_validating_links().await;
+    create_confirmed_subscriber(&app).await;
+
+    let mut newsletter = newsletter_with_link("https://evil.com");
+    newsletter["override_flagged_links"] = true.into();
+
+    {
+        let _mock_guard = Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount_as_scoped(&app.email_server)
+            .await;
+
+        let response = app.post_newsletters(newsletter).await;
+
+        assert_eq!(response.status().as_u16(), 202);
+    }
+
+    let action_id = sqlx::query!("SELECT id FROM admin_actions WHERE status = 'pending'")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch pending action.")
+        .id;
+
+    let reviewer = app.create_admin().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": reviewer.username,
+            "password": reviewer.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let html_page = app.get_admin_actions_html().await;
+    assert!(html_page.contains("flagged links: https://evil.com"));
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_review_admin_action(action_id, "approve").await;
+    assert_is_redirect_to(&response, "/admin/actions");
+    app.dispatch_all_pending_emails().await;
+
+    let reviewed = sqlx::query!(
+        "SELECT reviewed_by FROM admin_actions WHERE id = $1",
+        action_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch reviewed action.");
+    assert!(reviewed.reviewed_by.is_some());
+}
+
+fn styled_newsletter() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<style>p { color: red }</style><p>Newsletter body as HTML</p>",
+        }
+    })
+}
+
+#[tokio::test]
+async fn preview_shows_the_issue_with_inlined_css() {
+    let app = spawn_app().await;
+
+    let response = app.post_newsletter_preview(styled_newsletter()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(!html.contains("<style>"));
+    assert!(html.contains(r#"<p style="color: red">Newsletter body as HTML</p>"#));
+}
+
+#[tokio::test]
+async fn preview_requires_authentication() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .post(&format!("{}/newsletters/preview", &app.address))
+        .json(&styled_newsletter())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn delivered_issues_match_their_preview() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let preview = app
+        .post_newsletter_preview(styled_newsletter())
+        .await
+        .text()
+        .await
+        .unwrap();
+    app.post_newsletters(styled_newsletter())
+        .await
+        .error_for_status()
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let body = app.delivered_issue_emails().await.pop().unwrap();
+    assert_eq!(body["HtmlBody"], preview);
+}
+
+#[tokio::test]
+async fn css_is_not_inlined_when_disabled() {
+    let app = spawn_app_with_configuration(|c| c.application.inline_css = false).await;
+
+    let response = app.post_newsletter_preview(styled_newsletter()).await;
+
+    assert_eq!(
+        response.text().await.unwrap(),
+        "<style>p { color: red }</style><p>Newsletter body as HTML</p>"
+    );
+}
+
+#[tokio::test]
+async fn attachments_are_sent_with_every_email() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": { "html": "<p>Newsletter body as HTML</p>" },
+            "attachments": [{
+                "filename": "issue.pdf",
+                "content_type": "application/pdf",
+                "content": "JVBERi0xLjc=",
+            }],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
+
+    let body = app.delivered_issue_emails().await.pop().unwrap();
+    assert_eq!(
+        body["Attachments"],
+        serde_json::json!([{
+            "Name": "issue.pdf",
+            "Content": "JVBERi0xLjc=",
+            "ContentType": "application/pdf",
+        }])
+    );
+}
+
+#[tokio::test]
+async fn newsletters_with_attachments_over_the_limit_are_refused() {
+    let app = spawn_app_with_configuration(|c| c.email_client.max_attachments_bytes = 7).await;
+
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": { "html": "<p>Newsletter body as HTML</p>" },
+            "attachments": [{
+                "filename": "issue.pdf",
+                "content_type": "application/pdf",
+                "content": "JVBERi0xLjc=",
+            }],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 413);
+}
+
+#[tokio::test]
+async fn issues_published_from_the_admin_ui_carry_the_uploaded_files() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let pdf = reqwest::multipart::Part::bytes(b"%PDF-1.7".to_vec())
+        .file_name("issue.pdf")
+        .mime_str("application/pdf")
+        .unwrap();
+    let form = reqwest::multipart::Form::new()
+        .text("title", "Newsletter title")
+        .text("html", "<p>Newsletter body as HTML</p>")
+        .part("attachments", pdf);
+    let response = app
+        .api_client
+        .post(&format!("{}/admin/newsletters", &app.address))
+        .header("X-CSRF-Token", app.csrf_token().await)
+        .multipart(form)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let body = app.delivered_issue_emails().await.pop().unwrap();
+    assert_eq!(body["Attachments"][0]["Name"], "issue.pdf");
+    assert_eq!(body["Attachments"][0]["Content"], "JVBERi0xLjc=");
+}
+
+#[tokio::test]
+async fn issues_record_who_published_them() {
+    let app = spawn_app().await;
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content": { "html": "<p>Newsletter body as HTML</p>" }
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    let issue = sqlx::query!(r#"SELECT newsletter_issue_id, published_by FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.published_by, Some(app.test_user.user_id));
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let html_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/issues/{}/stats",
+            app.address, issue.newsletter_issue_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(&format!("Published by {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn issues_can_be_sent_in_the_name_of_their_author() {
+    let app = spawn_app_with_configuration(|c| c.delivery_queue.author_as_sender_name = true).await;
+    sqlx::query!(
+        r#"UPDATE users SET display_name = 'Ursula K. Le Guin' WHERE user_id = $1"#,
+        app.test_user.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content": { "html": "<p>Newsletter body as HTML</p>" }
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let body = app.delivered_issue_emails().await.pop().unwrap();
+    assert!(body["From"]
+        .as_str()
+        .unwrap()
+        .starts_with(r#""Ursula K. Le Guin" <"#));
+}
+
+async fn spawn_app_with_verified_sender() -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.email_client.verified_senders = vec!["editor@example.com".into()];
+    })
+    .await
+}
+
+#[tokio::test]
+async fn issues_can_be_sent_from_a_verified_sender() {
+    let app = spawn_app_with_verified_sender().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(accept_email_batch)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content": { "html": "<p>Newsletter body as HTML</p>" },
+        "sender_name": "The editor",
+        "sender_email": "editor@example.com",
+        "reply_to": "Editor@Example.com",
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let body = app.delivered_issue_emails().await.pop().unwrap();
+    assert_eq!(body["From"], r#""The editor" <editor@example.com>"#);
+    assert_eq!(body["ReplyTo"], "Editor@Example.com");
+}
+
+#[tokio::test]
+async fn issues_from_unverified_senders_are_refused() {
+    let app = spawn_app_with_verified_sender().await;
+
+    for (field, address) in [
+        ("sender_email", "eve@example.com"),
+        ("reply_to", "eve@example.com"),
+        ("reply_to", "not-an-email"),
+    ] {
+        let response = app
+            .post_newsletters(serde_json::json!({
+                "title": "Newsletter title",
+                "content": { "html": "<p>Newsletter body as HTML</p>" },
+                field: address,
+            }))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not refuse {} as {}",
+            address,
+            field
+        );
+    }
+    let issues = sqlx::query_scalar!(r#"SELECT COUNT(*) FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issues, Some(0));
+}
@@ -0,0 +1,43 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn the_openapi_spec_documents_the_json_endpoints() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/api/docs/openapi.json", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let spec: serde_json::Value = response.json().await.unwrap();
+    let paths = spec["paths"].as_object().unwrap();
+    for path in [
+        "/subscriptions",
+        "/subscriptions/confirm",
+        "/api/v1/admin/newsletters",
+        "/api/v1/admin/collaborators",
+        "/api/v1/admin/subscribers",
+        "/api/v1/admin/subscribers/{subscriber_id}",
+    ] {
+        assert!(paths.contains_key(path), "{} isn't documented", path);
+    }
+    assert!(spec["components"]["schemas"]["ProblemDetails"].is_object());
+}
+
+#[tokio::test]
+async fn swagger_ui_is_served_next_to_the_spec() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/api/docs/", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("swagger"));
+}
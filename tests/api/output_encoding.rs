@@ -0,0 +1,68 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+/// Values trying to break out of the HTML they are interpolated in, without
+/// whitespace so that they survive the parsing of lists of emails.
+const PAYLOADS: &[&str] = &[
+    "<script>alert(1)</script>",
+    "\"><img/src=x/onerror=alert(1)>",
+    "'><svg/onload=alert(1)>",
+];
+
+fn assert_is_escaped(html_page: &str, payload: &str) {
+    assert!(
+        !html_page.contains(payload),
+        "{} was rendered unescaped",
+        payload
+    );
+    // Pages rendered with Tera escape slashes as well.
+    let html_page = html_page.replace("&#x2F;", "/");
+    assert!(html_page.contains(&htmlescape::encode_minimal(payload)));
+}
+
+async fn login(app: &TestApp, username: &str, password: &str) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": username,
+            "password": password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn usernames_are_escaped_in_the_admin_dashboard() {
+    for payload in PAYLOADS {
+        let app = spawn_app().await;
+        sqlx::query!(
+            "UPDATE users SET username = $1 WHERE user_id = $2",
+            payload,
+            app.test_user.user_id,
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+        login(&app, payload, &app.test_user.password).await;
+
+        let html_page = app.get_admin_dashboard_html().await;
+
+        assert_is_escaped(&html_page, payload);
+    }
+}
+
+#[tokio::test]
+async fn flash_messages_are_escaped() {
+    let app = spawn_app().await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+
+    for payload in PAYLOADS {
+        let response = app
+            .post_delete_subscribers(&serde_json::json!({ "emails": payload }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/actions");
+
+        let html_page = app.get_admin_actions_html().await;
+
+        assert_is_escaped(&html_page, payload);
+    }
+}
@@ -0,0 +1,66 @@
+use reqwest::Method;
+
+use crate::helpers::{spawn_app, spawn_app_with_configuration};
+
+#[tokio::test]
+async fn public_forms_over_their_limit_are_refused_with_the_limit() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": app.test_user.username,
+            "password": "a".repeat(8 * 1024),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 413);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>413 Payload Too Large</h1>"));
+    assert!(html_page.contains("larger than the 4 KiB allowed"));
+}
+
+#[tokio::test]
+async fn issues_may_be_larger_than_other_bodies() {
+    let app = spawn_app_with_configuration(|c| {
+        c.application.payload_limits.default_bytes = 1024;
+        c.application.payload_limits.publish_bytes = 64 * 1024;
+    })
+    .await;
+    let html = format!("<p>{}</p>", "a".repeat(4 * 1024));
+
+    let response = app
+        .api_request(Method::POST, "/admin/newsletters")
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": html,
+            }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let response = app
+        .api_request(Method::POST, "/admin/password")
+        .json(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": "a".repeat(2 * 1024),
+            "new_password_check": "a".repeat(2 * 1024),
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 413);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["type"], "/problems/payload-too-large");
+    assert_eq!(
+        problem["detail"],
+        "What was sent is larger than the 1 KiB allowed."
+    );
+}
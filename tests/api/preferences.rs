@@ -0,0 +1,313 @@
+use reqwest::{Method, Url};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app, TestApp};
+
+const OLD_EMAIL: &str = "ursula_le_guin@gmail.com";
+const NEW_EMAIL: &str = "ursula@earthsea.org";
+
+async fn create_confirmed_subscriber(app: &TestApp) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    reqwest::get(app.get_links(email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+/// Sends the form of the preferences center and returns the link emailed back.
+async fn post_and_follow_email(
+    app: &TestApp,
+    endpoint: &str,
+    body: &serde_json::Value,
+) -> (reqwest::Response, Option<Url>) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let n_sent = app.email_server.received_requests().await.unwrap().len();
+
+    let response = app
+        .api_client
+        .post(&format!("{}{}", &app.address, endpoint))
+        .form(body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let link = requests
+        .get(n_sent)
+        .map(|email_request| app.get_links(email_request).html);
+
+    (response, link)
+}
+
+async fn get_preferences_link(app: &TestApp) -> Url {
+    let (response, link) = post_and_follow_email(
+        app,
+        "/preferences/link",
+        &serde_json::json!({ "email": OLD_EMAIL }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    link.expect("No preferences link was sent")
+}
+
+async fn request_email_change(app: &TestApp, preferences_link: &Url) -> Url {
+    let (_, preferences_token) = preferences_link.query_pairs().next().unwrap();
+    let (response, link) = post_and_follow_email(
+        app,
+        "/preferences/email",
+        &serde_json::json!({
+            "preferences_token": preferences_token,
+            "email": NEW_EMAIL,
+        }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    link.expect("No confirmation link was sent")
+}
+
+async fn subscription_email(app: &TestApp) -> String {
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email
+}
+
+#[tokio::test]
+async fn the_preferences_link_opens_the_preferences_center() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let preferences_link = get_preferences_link(&app).await;
+    let response = reqwest::get(preferences_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(OLD_EMAIL));
+    assert!(html_page.contains(r#"action="/preferences/email""#));
+}
+
+#[tokio::test]
+async fn unknown_emails_get_no_preferences_link() {
+    let app = spawn_app().await;
+
+    let (response, link) = post_and_follow_email(
+        &app,
+        "/preferences/link",
+        &serde_json::json!({ "email": OLD_EMAIL }),
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(link.is_none());
+}
+
+#[tokio::test]
+async fn the_old_email_is_kept_until_the_new_one_is_confirmed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let preferences_link = get_preferences_link(&app).await;
+
+    let confirmation_link = request_email_change(&app, &preferences_link).await;
+    let email_request = app.email_server.received_requests().await.unwrap().pop();
+    let body: serde_json::Value = email_request.unwrap().body_json().unwrap();
+    assert_eq!(body["To"], NEW_EMAIL);
+    assert_eq!(subscription_email(&app).await, OLD_EMAIL);
+
+    let response = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscription_email(&app).await, NEW_EMAIL);
+}
+
+#[tokio::test]
+async fn confirming_an_email_change_is_recorded_and_revokes_old_links() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let preferences_link = get_preferences_link(&app).await;
+    let confirmation_link = request_email_change(&app, &preferences_link).await;
+
+    reqwest::get(confirmation_link.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let event =
+        sqlx::query!("SELECT details FROM subscriber_events WHERE event_type = 'email_changed'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(event.details["old_email"], OLD_EMAIL);
+    assert_eq!(event.details["new_email"], NEW_EMAIL);
+
+    let response = reqwest::get(preferences_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let response = reqwest::get(confirmation_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn changing_to_an_already_subscribed_email_is_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let preferences_link = get_preferences_link(&app).await;
+    let (_, preferences_token) = preferences_link.query_pairs().next().unwrap();
+
+    let (response, link) = post_and_follow_email(
+        &app,
+        "/preferences/email",
+        &serde_json::json!({
+            "preferences_token": preferences_token,
+            "email": OLD_EMAIL,
+        }),
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    assert!(link.is_none());
+}
+
+#[tokio::test]
+async fn subscribers_can_erase_their_personal_data() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let preferences_link = get_preferences_link(&app).await;
+    let (_, preferences_token) = preferences_link.query_pairs().next().unwrap();
+    let erasure_link = format!(
+        "{}/subscriptions/erase?token={}",
+        app.address, preferences_token
+    );
+
+    let response = reqwest::get(&erasure_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber = sqlx::query!(
+        r#"SELECT email, name, status::TEXT AS "status!", erased_at FROM subscriptions"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(subscriber.email.ends_with("@erased.invalid"));
+    assert_eq!(subscriber.name, "");
+    assert_eq!(subscriber.status, "unsubscribed");
+    assert!(subscriber.erased_at.is_some());
+
+    // The preferences link went away along with the data.
+    let response = reqwest::get(&erasure_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn the_preferences_center_shows_the_history_of_the_subscription() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let topic: serde_json::Value = app
+        .api_request(Method::POST, "/topics")
+        .json(&serde_json::json!({ "name": "Earthsea", "description": "A topic" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    app.api_request(
+        Method::POST,
+        &format!("/topics/{}/subscribers", topic["id"].as_str().unwrap()),
+    )
+    .json(&serde_json::json!({ "email": OLD_EMAIL }))
+    .send()
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+
+    let preferences_link = get_preferences_link(&app).await;
+    let html_page = reqwest::get(preferences_link)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let added = html_page.find("You were added to Earthsea").unwrap();
+    let confirmed = html_page.find("You confirmed your subscription").unwrap();
+    let subscribed = html_page.find("You subscribed").unwrap();
+    assert!(added < confirmed && confirmed < subscribed);
+    assert!(!html_page.contains("delivered"));
+}
+
+#[tokio::test]
+async fn subscribers_can_switch_to_a_weekly_digest() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let preferences_link = get_preferences_link(&app).await;
+    let (_, preferences_token) = preferences_link.query_pairs().next().unwrap();
+
+    let response = app
+        .api_client
+        .post(&format!("{}/preferences/frequency", &app.address))
+        .form(&serde_json::json!({
+            "preferences_token": preferences_token,
+            "delivery_frequency": "weekly_digest",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 303);
+    let html_page = reqwest::get(preferences_link)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(r#"value="weekly_digest" checked"#));
+    let frequency = sqlx::query_scalar!(
+        r#"SELECT delivery_frequency::TEXT AS "delivery_frequency!" FROM subscriptions"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(frequency, "weekly_digest");
+}
+
+#[tokio::test]
+async fn email_footers_lead_to_a_form_asking_for_a_preferences_link() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/preferences/link", app.address))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"action="/preferences/link""#));
+}
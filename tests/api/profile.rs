@@ -0,0 +1,164 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn login(app: &TestApp) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+async fn get_profile_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/admin/profile", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+async fn post_profile(app: &TestApp, display_name: &str, contact_email: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/profile", app.address))
+        .form(&[
+            ("display_name", display_name),
+            ("contact_email", contact_email),
+            ("csrf_token", &app.csrf_token().await),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_your_profile() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/profile", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_display_name_is_shown_on_the_dashboard() {
+    let app = spawn_app().await;
+    login(&app).await;
+
+    let response = post_profile(&app, "Ursula K. Le Guin", "ursula@example.com").await;
+    assert_is_redirect_to(&response, "/admin/profile");
+
+    let html_page = get_profile_html(&app).await;
+    assert!(html_page.contains("<p><i>Your profile has been updated.</i></p>"));
+    assert!(html_page.contains(r#"value="Ursula K. Le Guin""#));
+    assert!(html_page.contains(r#"value="ursula@example.com""#));
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("Welcome Ursula K. Le Guin"));
+}
+
+#[tokio::test]
+async fn blank_fields_clear_the_profile() {
+    let app = spawn_app().await;
+    login(&app).await;
+    post_profile(&app, "Ursula K. Le Guin", "ursula@example.com").await;
+
+    let response = post_profile(&app, " ", "").await;
+    assert_is_redirect_to(&response, "/admin/profile");
+
+    let profile = sqlx::query!(
+        r#"SELECT display_name, contact_email FROM users WHERE user_id = $1"#,
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(profile.display_name, None);
+    assert_eq!(profile.contact_email, None);
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn the_contact_email_leaves_the_email_users_were_invited_at_untouched() {
+    let app = spawn_app().await;
+    sqlx::query!(
+        "UPDATE users SET email = 'ursula@example.com' WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    login(&app).await;
+
+    let html_page = get_profile_html(&app).await;
+    assert!(!html_page.contains("ursula@example.com"));
+
+    let response = post_profile(&app, "", "ursula@gmail.com").await;
+    assert_is_redirect_to(&response, "/admin/profile");
+
+    let user = sqlx::query!(
+        r#"SELECT email, contact_email FROM users WHERE user_id = $1"#,
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(user.email.as_deref(), Some("ursula@example.com"));
+    assert_eq!(user.contact_email.as_deref(), Some("ursula@gmail.com"));
+    let html_page = get_profile_html(&app).await;
+    assert!(html_page.contains(r#"value="ursula@gmail.com""#));
+}
+
+#[tokio::test]
+async fn an_invalid_contact_email_is_rejected() {
+    let app = spawn_app().await;
+    login(&app).await;
+
+    let response = post_profile(&app, "Ursula K. Le Guin", "not-an-email").await;
+    assert_is_redirect_to(&response, "/admin/profile");
+
+    let html_page = get_profile_html(&app).await;
+    assert!(html_page.contains("<p><i>Invalid contact email: Invalid email format</i></p>"));
+    let display_name = sqlx::query_scalar!(
+        r#"SELECT display_name FROM users WHERE user_id = $1"#,
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(display_name, None);
+}
+
+#[tokio::test]
+async fn published_issues_are_authored_by_the_display_name() {
+    let app = spawn_app().await;
+    login(&app).await;
+    post_profile(&app, "Ursula K. Le Guin", "").await;
+
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let author = sqlx::query_scalar!(r#"SELECT author FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(author.as_deref(), Some("Ursula K. Le Guin"));
+}
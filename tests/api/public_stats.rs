@@ -0,0 +1,93 @@
+use newsletter::newsletter_list::DEFAULT_LIST_ID;
+use uuid::Uuid;
+
+use crate::helpers::{spawn_app, spawn_app_with_configuration, TestApp};
+
+async fn insert_subscriber(app: &TestApp, email: &str, name: &str) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, $4, now(), 'confirmed')
+        "#,
+        subscriber_id,
+        DEFAULT_LIST_ID,
+        email,
+        name,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+
+    subscriber_id
+}
+
+async fn get_stats(app: &TestApp) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/stats", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn spawn_app_with_exact_stats() -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.features.public_stats = true;
+        c.public_stats.rounding = 1;
+        c.public_stats.noise_scale = 0.;
+    })
+    .await
+}
+
+#[tokio::test]
+async fn stats_return_404_when_disabled() {
+    let app = spawn_app().await;
+
+    let response = get_stats(&app).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn stats_count_confirmed_subscribers_and_published_issues() {
+    let app = spawn_app_with_exact_stats().await;
+    insert_subscriber(&app, "ursula@example.com", "ursula").await;
+    insert_subscriber(&app, "octavia@example.com", "octavia").await;
+
+    let response = get_stats(&app).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let stats: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(stats["subscribers"], 2);
+    assert_eq!(stats["issues_published"], 0);
+    assert!(stats["last_issue_published_at"].is_null());
+}
+
+#[tokio::test]
+async fn subscriber_count_is_rounded() {
+    let app = spawn_app_with_configuration(|c| {
+        c.features.public_stats = true;
+        c.public_stats.rounding = 10;
+        c.public_stats.noise_scale = 0.;
+    })
+    .await;
+    for i in 0..3 {
+        insert_subscriber(&app, &format!("reader{}@example.com", i), "reader").await;
+    }
+
+    let stats: serde_json::Value = get_stats(&app).await.json().await.unwrap();
+
+    assert_eq!(stats["subscribers"], 0);
+}
+
+#[tokio::test]
+async fn stats_are_cached() {
+    let app = spawn_app_with_exact_stats().await;
+    insert_subscriber(&app, "ursula@example.com", "ursula").await;
+    let first: serde_json::Value = get_stats(&app).await.json().await.unwrap();
+
+    insert_subscriber(&app, "octavia@example.com", "octavia").await;
+    let second: serde_json::Value = get_stats(&app).await.json().await.unwrap();
+
+    assert_eq!(first, second);
+}
@@ -0,0 +1,209 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{
+    accept_email_batch, assert_is_redirect_to, spawn_app_with_configuration, TestApp,
+};
+
+async fn spawn_app_with_referrals(reward_milestones: Vec<u32>) -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.features.referrals = true;
+        c.referrals.reward_milestones = reward_milestones;
+    })
+    .await
+}
+
+/// Subscribes and confirms, giving back the id and referral code of the new
+/// subscriber.
+async fn create_confirmed_subscriber(app: &TestApp, body: &str) -> (Uuid, String) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscription(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    reqwest::get(app.get_links(&email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let email = body
+        .split('&')
+        .find_map(|p| p.strip_prefix("email="))
+        .unwrap()
+        .replace("%40", "@");
+    let subscriber = sqlx::query!(
+        "SELECT id, referral_code FROM subscriptions WHERE email = $1",
+        email,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+
+    (subscriber.id, subscriber.referral_code)
+}
+
+async fn referrer_of(app: &TestApp, email: &str) -> Option<Uuid> {
+    sqlx::query!(
+        "SELECT referred_by FROM subscriptions WHERE email = $1",
+        email,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .referred_by
+}
+
+#[tokio::test]
+async fn subscribers_signing_up_through_a_referral_link_are_tracked() {
+    let app = spawn_app_with_referrals(vec![]).await;
+    let (referrer_id, referral_code) =
+        create_confirmed_subscriber(&app, "name=Ursula&email=ursula%40example.com").await;
+
+    let body = format!("name=Ged&email=ged%40example.com&referral_code={referral_code}");
+    create_confirmed_subscriber(&app, &body).await;
+
+    assert_eq!(
+        referrer_of(&app, "ged@example.com").await,
+        Some(referrer_id)
+    );
+}
+
+#[tokio::test]
+async fn unknown_referral_codes_do_not_keep_anyone_from_subscribing() {
+    let app = spawn_app_with_referrals(vec![]).await;
+
+    create_confirmed_subscriber(&app, "name=Ged&email=ged%40example.com&referral_code=nope").await;
+
+    assert_eq!(referrer_of(&app, "ged@example.com").await, None);
+}
+
+#[tokio::test]
+async fn referrers_are_rewarded_once_per_milestone() {
+    let app = spawn_app_with_referrals(vec![1]).await;
+    let (referrer_id, referral_code) =
+        create_confirmed_subscriber(&app, "name=Ursula&email=ursula%40example.com").await;
+
+    let body = format!("name=Ged&email=ged%40example.com&referral_code={referral_code}");
+    create_confirmed_subscriber(&app, &body).await;
+
+    let sent_to_referrer = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.body_json::<serde_json::Value>().unwrap())
+        .filter(|b| b["To"] == "ursula@example.com")
+        .collect::<Vec<_>>();
+    // The confirmation email, then the reward.
+    assert_eq!(sent_to_referrer.len(), 2);
+    assert!(sent_to_referrer[1]["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("Thank you, Ursula!"));
+
+    let body = format!("name=Tenar&email=tenar%40example.com&referral_code={referral_code}");
+    create_confirmed_subscriber(&app, &body).await;
+
+    let milestones = sqlx::query!(
+        "SELECT milestone FROM referral_rewards WHERE subscriber_id = $1",
+        referrer_id,
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(milestones.len(), 1);
+    assert_eq!(milestones[0].milestone, 1);
+}
+
+#[tokio::test]
+async fn issues_end_with_the_referral_link_of_their_recipient() {
+    let app = spawn_app_with_referrals(vec![]).await;
+    let (_, referral_code) =
+        create_confirmed_subscriber(&app, "name=Ursula&email=ursula%40example.com").await;
+
+    Mock::given(path("/email/batch"))
+        .respond_with(accept_email_batch)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "New body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let issue = app.delivered_issue_emails().await.pop().unwrap();
+    let link = format!("/?referral_code={referral_code}");
+    assert!(issue["TextBody"].as_str().unwrap().contains(&link));
+    assert!(issue["HtmlBody"].as_str().unwrap().contains(&link));
+}
+
+#[tokio::test]
+async fn the_home_page_carries_the_referral_code_to_the_subscription_form() {
+    let app = spawn_app_with_referrals(vec![]).await;
+
+    let html = app
+        .api_client
+        .get(&format!("{}/?referral_code=abc123", app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html.contains(r#"<input type="hidden" name="referral_code" value="abc123">"#));
+}
+
+#[tokio::test]
+async fn admins_see_who_referred_the_most_subscribers() {
+    let app = spawn_app_with_referrals(vec![1, 10]).await;
+    let (_, referral_code) =
+        create_confirmed_subscriber(&app, "name=Ursula&email=ursula%40example.com").await;
+    let body = format!("name=Ged&email=ged%40example.com&referral_code={referral_code}");
+    create_confirmed_subscriber(&app, &body).await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let html = app
+        .api_client
+        .get(&format!("{}/admin/referrals", app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html.contains("1 confirmed subscribers joined through a referral link."));
+    assert!(html.contains("<td>ursula@example.com</td>"));
+    assert!(html.contains("<td>1</td>\n            <td>1</td>\n            <td>1</td>"));
+}
@@ -0,0 +1,148 @@
+use newsletter::configuration::SessionBackend;
+use secrecy::Secret;
+
+use crate::helpers::{
+    assert_is_redirect_to, build_api_client, fetch_csrf_token, spawn_app,
+    spawn_app_with_configuration, TestApp,
+};
+
+async fn spawn_app_without_redis(session_backend: SessionBackend) -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.session_backend = session_backend;
+        c.redis_uri = Secret::new("redis://127.0.0.1:1".to_string());
+    })
+    .await
+}
+
+async fn log_in_with_another_client(app: &TestApp) -> reqwest::Client {
+    let client = build_api_client();
+
+    let response = client
+        .post(&format!("{}/login", &app.address))
+        .header(
+            "X-CSRF-Token",
+            fetch_csrf_token(&client, &app.address).await,
+        )
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    client
+}
+
+async fn get_admin_dashboard(app: &TestApp, client: &reqwest::Client) -> reqwest::Response {
+    client
+        .get(&format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_revoke_all_sessions() {
+    let app = spawn_app().await;
+
+    let response = app.post_revoke_all_sessions().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+async fn assert_revoking_all_sessions_logs_out_every_session_of_the_user(app: &TestApp) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let other_client = log_in_with_another_client(app).await;
+    let response = get_admin_dashboard(app, &other_client).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_revoke_all_sessions().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>You have been logged out of all sessions.</i></p>"));
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = get_admin_dashboard(app, &other_client).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn revoking_all_sessions_logs_out_every_session_of_the_user() {
+    let app = spawn_app().await;
+
+    assert_revoking_all_sessions_logs_out_every_session_of_the_user(&app).await;
+}
+
+#[tokio::test]
+async fn sessions_can_be_kept_in_memory_without_redis() {
+    let app = spawn_app_without_redis(SessionBackend::InMemory).await;
+
+    assert_revoking_all_sessions_logs_out_every_session_of_the_user(&app).await;
+}
+
+#[tokio::test]
+async fn sessions_kept_in_cookies_are_only_logged_out_of_the_current_one() {
+    let app = spawn_app_without_redis(SessionBackend::Cookie).await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let other_client = log_in_with_another_client(&app).await;
+
+    let response = app.post_revoke_all_sessions().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = get_admin_dashboard(&app, &other_client).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn changing_password_logs_out_other_sessions() {
+    let app = spawn_app().await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let other_client = log_in_with_another_client(&app).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = get_admin_dashboard(&app, &other_client).await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,124 @@
+use newsletter::domain::SignupField;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app, spawn_app_with_configuration, TestApp};
+
+async fn get_signup_page_html(app: &TestApp) -> String {
+    let response = app
+        .api_client
+        .get(&format!("{}/subscribe", app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.text().await.unwrap()
+}
+
+#[tokio::test]
+async fn the_signup_page_posts_to_the_subscriptions_endpoint() {
+    let app = spawn_app().await;
+
+    let html = get_signup_page_html(&app).await;
+
+    assert!(html.contains("<h1>Subscribe to our newsletter</h1>"));
+    assert!(html.contains(r#"<form action="/subscriptions" method="post">"#));
+}
+
+#[tokio::test]
+async fn the_signup_page_is_branded_as_configured() {
+    let app = spawn_app_with_configuration(|c| {
+        let signup_page = &mut c.application.signup_page;
+        signup_page.title = "Rust & friends".into();
+        signup_page.description = Some("Weekly news about Rust".into());
+        signup_page.logo_url = Some("/static/favicon.svg".into());
+        signup_page.accent_color = Some("#b7410e".into());
+    })
+    .await;
+
+    let html = get_signup_page_html(&app).await;
+
+    assert!(html.contains("<h1>Rust &amp; friends</h1>"));
+    assert!(html.contains("<p>Weekly news about Rust</p>"));
+    assert!(html.contains(r#"<img src="/static/favicon.svg" alt="">"#));
+    assert!(html.contains(r#"style="background-color: #b7410e""#));
+}
+
+#[tokio::test]
+async fn the_signup_page_keeps_the_referral_code() {
+    let app = spawn_app().await;
+
+    let html = app
+        .api_client
+        .get(&format!("{}/subscribe?referral_code=abc123", app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html.contains(r#"<input type="hidden" name="referral_code" value="abc123">"#));
+}
+
+#[tokio::test]
+async fn the_signup_page_asks_the_custom_fields() {
+    let app = spawn_app_with_configuration(|c| {
+        c.application.signup_page.fields = vec![
+            SignupField {
+                name: "company".into(),
+                label: "Company".into(),
+                required: true,
+                max_length: 50,
+                options: vec![],
+            },
+            SignupField {
+                name: "heard_from".into(),
+                label: "How did you hear about us?".into(),
+                required: false,
+                max_length: 200,
+                options: vec!["Search".into(), "A friend".into()],
+            },
+        ];
+    })
+    .await;
+
+    let html = get_signup_page_html(&app).await;
+
+    assert!(html.contains(r#"<input type="text" name="company" maxlength="50" required>"#));
+    assert!(html.contains(r#"<select name="heard_from">"#));
+    assert!(html.contains(">A friend</option>"));
+}
+
+#[tokio::test]
+async fn subscribers_are_thanked_in_their_language() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .api_client
+        .post(&format!("{}/subscriptions", app.address))
+        .header("Accept-Language", "pt-PT")
+        .form(&[("name", "le guin"), ("email", "ursula_le_guin@gmail.com")])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Obrigado pela subscrição!"));
+}
@@ -0,0 +1,75 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app_with_configuration};
+
+#[tokio::test]
+async fn admin_ui_is_not_found_from_sources_outside_of_the_allow_list() {
+    let app = spawn_app_with_configuration(|c| {
+        c.application.source_allow_list.admin = Some(vec!["10.0.0.0/8".parse().unwrap()]);
+    })
+    .await;
+
+    let response = app.get_admin_dashboard().await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn admin_api_is_not_found_from_sources_outside_of_the_allow_list() {
+    let app = spawn_app_with_configuration(|c| {
+        c.application.source_allow_list.internal = Some(vec!["10.0.0.0/8".parse().unwrap()]);
+    })
+    .await;
+
+    let response = app
+        .api_request(reqwest::Method::GET, "/admin/settings")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn admin_ui_is_reachable_from_sources_in_the_allow_list() {
+    let app = spawn_app_with_configuration(|c| {
+        c.application.source_allow_list.admin = Some(vec!["127.0.0.0/8".parse().unwrap()]);
+    })
+    .await;
+
+    let response = app.get_admin_dashboard().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn forwarded_for_is_honoured_from_trusted_proxies_only() {
+    let app = spawn_app_with_configuration(|c| {
+        c.application.source_allow_list.admin = Some(vec!["203.0.113.0/24".parse().unwrap()]);
+    })
+    .await;
+    let dashboard = format!("{}/admin/dashboard", app.address);
+
+    let response = app
+        .api_client
+        .get(&dashboard)
+        .header("X-Forwarded-For", "203.0.113.7")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+
+    let app = spawn_app_with_configuration(|c| {
+        c.application.source_allow_list.admin = Some(vec!["203.0.113.0/24".parse().unwrap()]);
+        c.application.source_allow_list.trusted_proxies = vec!["127.0.0.0/8".parse().unwrap()];
+    })
+    .await;
+    let dashboard = format!("{}/admin/dashboard", app.address);
+
+    let response = app
+        .api_client
+        .get(&dashboard)
+        .header("X-Forwarded-For", "203.0.113.7")
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,39 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn static_files_are_served_with_cache_headers() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/static/admin.css", app.address))
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/css"));
+    assert_eq!(response.headers()["Cache-Control"], "public, max-age=86400");
+    assert!(response.headers().contains_key("ETag"));
+}
+
+#[tokio::test]
+async fn missing_static_files_are_not_cached() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/static/missing.css", app.address))
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(response.headers()["Cache-Control"], "no-store");
+}
+
+#[tokio::test]
+async fn pages_link_the_stylesheet() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_login_html().await;
+
+    assert!(html_page.contains(r#"<link rel="stylesheet" href="/static/admin.css">"#));
+}
@@ -0,0 +1,174 @@
+use newsletter::newsletter_list::DEFAULT_LIST_ID;
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn insert_subscriber(app: &TestApp, email: &str, name: &str) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, $4, now(), 'confirmed')
+        "#,
+        subscriber_id,
+        DEFAULT_LIST_ID,
+        email,
+        name,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+
+    subscriber_id
+}
+
+async fn login(app: &TestApp) {
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+}
+
+async fn get_duplicates_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/admin/subscribers/duplicates", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+async fn post_duplicates_form(
+    app: &TestApp,
+    action: &str,
+    form: serde_json::Value,
+) -> reqwest::Response {
+    app.api_client
+        .post(format!(
+            "{}/admin/subscribers/duplicates/{}",
+            app.address, action
+        ))
+        .header("X-CSRF-Token", app.csrf_token().await)
+        .form(&form)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+fn members(ids: &[Uuid]) -> String {
+    ids.iter()
+        .map(Uuid::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[tokio::test]
+async fn probable_duplicates_are_grouped_with_their_evidence() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "ursula@gmail.com", "Ursula").await;
+    insert_subscriber(&app, "Ursula@gmail.com", "Ursula").await;
+    insert_subscriber(&app, "ursula+news@gmail.com", "U").await;
+    insert_subscriber(&app, "leguin@example.com", "Le Guin").await;
+    insert_subscriber(&app, "leguinn@example.com", "le guin").await;
+    insert_subscriber(&app, "someone@example.com", "Someone").await;
+    login(&app).await;
+
+    let html_page = get_duplicates_html(&app).await;
+
+    assert_eq!(html_page.matches("<section>").count(), 2);
+    assert!(html_page.contains("Same email but for a +alias"));
+    assert!(html_page.contains("Same name and a similar email"));
+    assert!(!html_page.contains("someone@example.com"));
+}
+
+#[tokio::test]
+async fn merging_duplicates_keeps_one_subscriber_with_the_tags_of_all() {
+    let app = spawn_app().await;
+    let kept_id = insert_subscriber(&app, "ursula@gmail.com", "Ursula").await;
+    let merged_id = insert_subscriber(&app, "ursula+news@gmail.com", "Ursula").await;
+    sqlx::query!(
+        "INSERT INTO subscriber_tags (subscriber_id, tag, tagged_at) VALUES ($1, 'vip', now())",
+        merged_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    login(&app).await;
+
+    let response = post_duplicates_form(
+        &app,
+        "merge",
+        serde_json::json!({
+            "kept_id": kept_id,
+            "members": members(&[kept_id, merged_id]),
+        }),
+    )
+    .await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers/duplicates");
+    let subscribers = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0].id, kept_id);
+    let tags = sqlx::query!(
+        "SELECT tag FROM subscriber_tags WHERE subscriber_id = $1",
+        kept_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(tags.len(), 1);
+    assert_eq!(tags[0].tag, "vip");
+    let events = sqlx::query!(
+        "SELECT event_type FROM subscriber_events WHERE subscriber_id = $1 ORDER BY event_id DESC",
+        kept_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(events[0].event_type, "merged");
+}
+
+#[tokio::test]
+async fn dismissed_duplicates_are_no_longer_reported() {
+    let app = spawn_app().await;
+    let first_id = insert_subscriber(&app, "ursula@gmail.com", "Ursula").await;
+    let second_id = insert_subscriber(&app, "ursula+news@gmail.com", "Ursula").await;
+    login(&app).await;
+
+    let response = post_duplicates_form(
+        &app,
+        "dismiss",
+        serde_json::json!({ "members": members(&[first_id, second_id]) }),
+    )
+    .await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers/duplicates");
+    let html_page = get_duplicates_html(&app).await;
+    assert!(html_page.contains("No probable duplicates."));
+}
+
+#[tokio::test]
+async fn collaborators_cannot_see_duplicates() {
+    let app = spawn_app().await;
+    let collaborator = app.create_collaborator().await;
+    app.post_login(&serde_json::json!({
+        "username": &collaborator.username,
+        "password": &collaborator.password,
+    }))
+    .await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/subscribers/duplicates", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 405);
+}
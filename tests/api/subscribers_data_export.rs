@@ -0,0 +1,94 @@
+use newsletter::newsletter_list::DEFAULT_LIST_ID;
+use uuid::Uuid;
+use wiremock::{matchers::path, Mock};
+
+use crate::helpers::{accept_email_batch, spawn_app, TestApp};
+
+async fn insert_confirmed_subscriber(app: &TestApp, email: &str) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, 'le guin', now(), 'confirmed')
+        "#,
+        subscriber_id,
+        DEFAULT_LIST_ID,
+        email,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+
+    subscriber_id
+}
+
+async fn get_subscriber_export(app: &TestApp, subscriber_id: Uuid) -> reqwest::Response {
+    app.api_client
+        .get(format!(
+            "{}/admin/subscribers/{}/export",
+            app.address, subscriber_id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn login(app: &TestApp) {
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+}
+
+#[tokio::test]
+async fn the_export_holds_the_subscription_and_its_delivery_history() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    insert_confirmed_subscriber(&app, "le_guin@gmail.com").await;
+    Mock::given(path("/email/batch"))
+        .respond_with(accept_email_batch)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        },
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+    login(&app).await;
+
+    let response = get_subscriber_export(&app, subscriber_id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Disposition"],
+        format!(
+            r#"attachment; filename="subscriber-{}.json""#,
+            subscriber_id
+        )
+    );
+    let data: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(data["subscription"]["email"], "ursula@gmail.com");
+    assert_eq!(data["subscription"]["status"], "confirmed");
+    let deliveries = data["deliveries"].as_array().unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0]["title"], "Newsletter title");
+    let events = data["events"].as_array().unwrap();
+    assert!(events.iter().any(|event| event["type"] == "delivered"));
+}
+
+#[tokio::test]
+async fn exporting_an_unknown_subscriber_returns_404() {
+    let app = spawn_app().await;
+    login(&app).await;
+
+    let response = get_subscriber_export(&app, Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,133 @@
+use newsletter::{erasure::erased_email, newsletter_list::DEFAULT_LIST_ID};
+use uuid::Uuid;
+use wiremock::{matchers::path, Mock};
+
+use crate::helpers::{accept_email_batch, assert_is_redirect_to, spawn_app, TestApp};
+
+async fn insert_confirmed_subscriber(app: &TestApp, email: &str) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, 'le guin', now(), 'confirmed')
+        "#,
+        subscriber_id,
+        DEFAULT_LIST_ID,
+        email,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+
+    subscriber_id
+}
+
+async fn post_erase_subscriber(app: &TestApp, subscriber_id: Uuid) -> reqwest::Response {
+    app.api_client
+        .post(format!(
+            "{}/admin/subscribers/{}/erase",
+            app.address, subscriber_id
+        ))
+        .header("X-CSRF-Token", app.csrf_token().await)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn login(app: &TestApp) {
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+}
+
+#[tokio::test]
+async fn erasing_a_subscriber_anonymizes_it_and_keeps_the_issue_counts() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    insert_confirmed_subscriber(&app, "le_guin@gmail.com").await;
+    Mock::given(path("/email/batch"))
+        .respond_with(accept_email_batch)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        },
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+    login(&app).await;
+
+    let response = post_erase_subscriber(&app, subscriber_id).await;
+
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{}", subscriber_id));
+    let subscriber = sqlx::query!(
+        "SELECT email, name FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(subscriber.email, erased_email(subscriber_id));
+    assert_eq!(subscriber.name, "");
+    let deliveries = sqlx::query!("SELECT subscriber_email FROM issue_deliveries")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.len(), 2);
+    assert!(deliveries
+        .iter()
+        .all(|delivery| delivery.subscriber_email != "ursula@gmail.com"));
+    let events = sqlx::query!(
+        "SELECT event_type FROM subscriber_events WHERE subscriber_id = $1 ORDER BY event_id DESC",
+        subscriber_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(events[0].event_type, "erased");
+
+    let html_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers/{}",
+            app.address, subscriber_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>The subscriber was erased.</i></p>"));
+}
+
+#[tokio::test]
+async fn erasing_a_subscriber_twice_is_reported() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    login(&app).await;
+    post_erase_subscriber(&app, subscriber_id).await;
+
+    post_erase_subscriber(&app, subscriber_id).await;
+
+    let html_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers/{}",
+            app.address, subscriber_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("was already erased"));
+}
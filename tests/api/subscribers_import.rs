@@ -0,0 +1,226 @@
+use newsletter::domain::SubscriptionStatus;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn login(app: &TestApp, username: &str, password: &str) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": username,
+            "password": password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+const CSV: &str = "email,name\n\
+    ursula@gmail.com,Ursula\n\
+    not-an-email,Le Guin\n\
+    le_guin@gmail.com,Le Guin\n";
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_import_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app.post_import_subscribers(CSV).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn you_must_be_admin_to_import_subscribers() {
+    let app = spawn_app().await;
+    let collaborator = app.create_collaborator().await;
+    login(&app, &collaborator.username, &collaborator.password).await;
+
+    let response = app.post_import_subscribers(CSV).await;
+
+    assert_eq!(405, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn valid_rows_are_imported_as_confirmed_subscribers() {
+    let app = spawn_app().await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+
+    let response = app.post_import_subscribers(CSV).await;
+
+    assert_eq!(200, response.status().as_u16());
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 2);
+    assert_eq!(report["errors"].as_array().unwrap().len(), 1);
+    assert_eq!(report["errors"][0]["line"], 3);
+
+    let saved = sqlx::query!(
+        r#"SELECT email, status as "status: SubscriptionStatus" FROM subscriptions ORDER BY email"#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved subscriptions.");
+    assert_eq!(saved.len(), 2);
+    assert!(saved
+        .iter()
+        .all(|s| s.status == SubscriptionStatus::Confirmed));
+}
+
+#[tokio::test]
+async fn already_subscribed_emails_are_reported() {
+    let app = spawn_app().await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+    app.post_import_subscribers(CSV).await;
+
+    let response = app.post_import_subscribers(CSV).await;
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 0);
+    assert_eq!(report["errors"].as_array().unwrap().len(), 3);
+    assert_eq!(report["errors"][0]["error"], "Email is already subscribed");
+}
+
+#[tokio::test]
+async fn csv_without_expected_columns_returns_400() {
+    let app = spawn_app().await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+
+    let response = app
+        .post_import_subscribers("mail\nursula@gmail.com\n")
+        .await;
+
+    assert_eq!(400, response.status().as_u16());
+}
+
+const MAILCHIMP_MEMBERS: &str = r#"{"members": [
+    {"email_address": "ursula@gmail.com", "status": "subscribed",
+        "merge_fields": {"FNAME": "Ursula", "LNAME": "Le Guin"}},
+    {"email_address": "bounced@gmail.com", "status": "cleaned"},
+    {"email_address": "pending@gmail.com", "status": "pending"}
+]}"#;
+
+#[tokio::test]
+async fn mailchimp_members_are_imported_with_their_status() {
+    let app = spawn_app().await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+
+    let response = app
+        .post_subscribers_export("/admin/subscribers/import", "mailchimp", MAILCHIMP_MEMBERS)
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 2);
+    assert_eq!(report["errors"][0]["line"], 3);
+
+    let saved = sqlx::query!(
+        r#"SELECT email, name, status as "status: SubscriptionStatus" FROM subscriptions ORDER BY email"#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved subscriptions.");
+    assert_eq!(saved.len(), 2);
+    assert_eq!(saved[0].email, "bounced@gmail.com");
+    assert_eq!(saved[0].name, "bounced");
+    assert_eq!(saved[0].status, SubscriptionStatus::Suppressed);
+    assert_eq!(saved[1].name, "Ursula Le Guin");
+    assert_eq!(saved[1].status, SubscriptionStatus::Confirmed);
+}
+
+#[tokio::test]
+async fn buttondown_exports_are_imported_with_their_status() {
+    let app = spawn_app().await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+    let export = "email,notes,subscriber_type\n\
+        ursula@gmail.com,,regular\n\
+        gone@gmail.com,,unsubscribed\n";
+
+    let response = app
+        .post_subscribers_export("/admin/subscribers/import", "buttondown", export)
+        .await;
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 2);
+
+    let unsubscribed = sqlx::query!(
+        r#"SELECT status as "status: SubscriptionStatus", confirmed_at FROM subscriptions
+        WHERE email = 'gone@gmail.com'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved subscription.");
+    assert_eq!(unsubscribed.status, SubscriptionStatus::Unsubscribed);
+    assert!(unsubscribed.confirmed_at.is_none());
+}
+
+#[tokio::test]
+async fn previewing_an_import_shows_the_mapping_without_importing() {
+    let app = spawn_app().await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+
+    let response = app
+        .post_import_preview("mailchimp", MAILCHIMP_MEMBERS)
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("2 subscribers found in the Mailchimp export, 1 rows skipped."));
+    assert!(html_page.contains("<td>bounced@gmail.com</td><td>bounced</td><td>Suppressed</td>"));
+    assert!(html_page.contains("Line 3: Subscriber never confirmed the subscription"));
+
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.");
+    assert!(saved.is_empty());
+}
+
+#[tokio::test]
+async fn you_must_be_admin_to_preview_an_import() {
+    let app = spawn_app().await;
+    let collaborator = app.create_collaborator().await;
+    login(&app, &collaborator.username, &collaborator.password).await;
+
+    let response = app.post_import_preview("csv", CSV).await;
+
+    assert_eq!(405, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn the_import_form_submits_its_csrf_token_in_the_multipart_body() {
+    let app = spawn_app().await;
+    login(&app, &app.test_user.username, &app.test_user.password).await;
+
+    let html_page = app
+        .api_client
+        .get(&format!("{}/admin/subscribers/import", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(r#"enctype="multipart/form-data""#));
+
+    let form = reqwest::multipart::Form::new()
+        .text("csrf_token", app.csrf_token().await)
+        .text("source", "csv")
+        .text("file", CSV);
+    let response = app
+        .api_client
+        .post(&format!("{}/admin/subscribers/import", &app.address))
+        .multipart(form)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(200, response.status().as_u16());
+
+    let form = reqwest::multipart::Form::new()
+        .text("csrf_token", "forged")
+        .text("file", CSV);
+    let response = app
+        .api_client
+        .post(&format!("{}/admin/subscribers/import", &app.address))
+        .multipart(form)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(403, response.status().as_u16());
+}
@@ -1,9 +1,33 @@
+use newsletter::domain::{SignupField, SubscriptionStatus};
+use reqwest::Method;
 use wiremock::{
     matchers::{method, path},
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with_configuration, TestApp};
+
+async fn spawn_app_with_signup_fields() -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.application.signup_page.fields = vec![
+            SignupField {
+                name: "company".into(),
+                label: "Company".into(),
+                required: true,
+                max_length: 50,
+                options: vec![],
+            },
+            SignupField {
+                name: "heard_from".into(),
+                label: "How did you hear about us?".into(),
+                required: false,
+                max_length: 200,
+                options: vec!["Search".into(), "A friend".into()],
+            },
+        ];
+    })
+    .await
+}
 
 #[tokio::test]
 async fn subscribe_returns_a_200_for_valid_form_data() {
@@ -36,14 +60,16 @@ async fn subscribe_persists_the_new_subscriber() {
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
     test_app.post_subscription(body.into()).await;
 
-    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
-        .fetch_one(&test_app.db_pool)
-        .await
-        .expect("Failed to fetch saved subscriptions");
+    let saved = sqlx::query!(
+        r#"SELECT email, name, status as "status: SubscriptionStatus" FROM subscriptions"#,
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .expect("Failed to fetch saved subscriptions");
 
     assert_eq!(saved.email, "ursula_le_guin@gmail.com");
     assert_eq!(saved.name, "le guin");
-    assert_eq!(saved.status, "pending_confirmation");
+    assert_eq!(saved.status, SubscriptionStatus::PendingConfirmation);
 }
 
 #[tokio::test]
@@ -121,7 +147,7 @@ async fn subscribe_sends_a_confirmation_email_with_a_link() {
 }
 
 #[tokio::test]
-async fn subscribe_sends_new_confirmation_email_when_subscriber_is_repeated() {
+async fn subscribe_sends_a_new_confirmation_link_when_subscriber_is_repeated() {
     let test_app = spawn_app().await;
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
@@ -141,5 +167,245 @@ async fn subscribe_sends_new_confirmation_email_when_subscriber_is_repeated() {
     let email_request = &test_app.email_server.received_requests().await.unwrap()[1];
     let second_confirmation_link = test_app.get_links(email_request);
 
-    assert_eq!(first_confirmation_link.html, second_confirmation_link.html);
+    assert_ne!(first_confirmation_link.html, second_confirmation_link.html);
+
+    let response = reqwest::get(first_confirmation_link.html).await.unwrap();
+    assert_eq!(401, response.status().as_u16());
+
+    let response = reqwest::get(second_confirmation_link.html).await.unwrap();
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_describes_invalid_data_as_problem_details() {
+    let test_app = spawn_app().await;
+
+    let body = "name=Ursula&email=definetely-not-an-email";
+    let response = test_app.post_subscription(body.into()).await;
+
+    assert_eq!(400, response.status().as_u16());
+    assert_eq!(
+        "application/problem+json",
+        response.headers()["Content-Type"].to_str().unwrap()
+    );
+
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["type"], "/problems/invalid-subscriber");
+    assert_eq!(problem["title"], "Bad Request");
+    assert_eq!(problem["status"], 400);
+    assert!(problem["detail"].is_string());
+    assert!(problem["trace_id"].is_string());
+}
+
+#[tokio::test]
+async fn subscribe_does_not_leak_details_of_unexpected_errors() {
+    let test_app = spawn_app().await;
+
+    sqlx::query!("ALTER TABLE subscription_tokens DROP COLUMN subscription_token_hash;",)
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let response = test_app.post_subscription(body.into()).await;
+
+    assert_eq!(500, response.status().as_u16());
+
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["type"], "/problems/internal-error");
+    assert!(problem.get("detail").is_none());
+    assert!(problem["trace_id"].is_string());
+}
+
+#[tokio::test]
+async fn subscribe_sends_the_confirmation_email_in_the_language_of_the_subscriber() {
+    let test_app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .api_client
+        .post(&format!("{}/subscriptions", test_app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept-Language", "de;q=0.5, pt-BR, en;q=0.8")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(200, response.status().as_u16());
+
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = email_request.body_json().unwrap();
+    assert!(body["TextBody"].as_str().unwrap().starts_with("Bem-vindo"));
+    assert!(body["HtmlBody"].as_str().unwrap().starts_with("Bem-vindo"));
+
+    let saved = sqlx::query!("SELECT locale FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.locale.as_deref(), Some("pt"));
+}
+
+#[tokio::test]
+async fn subscribe_prefers_the_language_picked_by_the_subscriber() {
+    let test_app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app
+        .api_client
+        .post(&format!("{}/subscriptions", test_app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept-Language", "pt")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com&locale=en-US")
+        .send()
+        .await
+        .unwrap();
+
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = email_request.body_json().unwrap();
+    assert!(body["TextBody"].as_str().unwrap().starts_with("Welcome"));
+
+    let saved = sqlx::query!("SELECT locale FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.locale.as_deref(), Some("en"));
+}
+
+#[tokio::test]
+async fn subscribe_returns_a_400_for_an_invalid_locale() {
+    let test_app = spawn_app().await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com&locale=%3Cscript%3E";
+    let response = test_app.post_subscription(body.into()).await;
+
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_accepts_emails_whose_domain_cannot_be_resolved_in_time() {
+    let test_app = spawn_app_with_configuration(|c| {
+        c.features.mx_validation = true;
+        c.mx_check.timeout_milliseconds = 0;
+    })
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let response = test_app.post_subscription(body.into()).await;
+
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_stores_the_answers_to_the_signup_fields() {
+    let test_app = spawn_app_with_signup_fields().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com\
+        &company=Earthsea&heard_from=A%20friend&unknown=dropped";
+    let response = test_app.post_subscription(body.into()).await;
+    assert_eq!(200, response.status().as_u16());
+
+    let subscribers: serde_json::Value = test_app
+        .api_request(Method::GET, "/admin/subscribers")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        subscribers[0]["attributes"],
+        serde_json::json!({"company": "Earthsea", "heard_from": "A friend"})
+    );
+}
+
+#[tokio::test]
+async fn subscribe_returns_a_400_when_the_signup_fields_are_invalid() {
+    let test_app = spawn_app_with_signup_fields().await;
+    let too_long = format!("company={}", "a".repeat(51));
+    let test_cases = vec![
+        ("heard_from=Search", "no company"),
+        ("company=%20", "a blank company"),
+        (
+            "company=Earthsea&heard_from=Radio",
+            "an answer that isn't an option",
+        ),
+        (too_long.as_str(), "an answer that is too long"),
+    ];
+
+    for (fields, description) in test_cases {
+        let body = format!("name=le%20guin&email=ursula_le_guin%40gmail.com&{}", fields);
+        let response = test_app.post_subscription(body).await;
+
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not fail with 400 Bad Request when the payload had {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn subscribe_without_double_opt_in_confirms_and_welcomes_the_subscriber() {
+    let test_app = spawn_app_with_configuration(|c| {
+        c.application.require_confirmation = false;
+    })
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let response = test_app.post_subscription(body.into()).await;
+    assert_eq!(200, response.status().as_u16());
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("we've sent you a welcome email"));
+
+    let subscribers: serde_json::Value = test_app
+        .api_request(Method::GET, "/admin/subscribers")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(subscribers[0]["status"], "confirmed");
+    assert!(subscribers[0]["confirmed_at"].is_string());
+
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let welcome_link = test_app.get_links(email_request);
+    assert_eq!(welcome_link.html.path(), "/preferences");
+
+    let response = reqwest::get(welcome_link.html).await.unwrap();
+    assert_eq!(200, response.status().as_u16());
 }
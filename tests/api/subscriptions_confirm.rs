@@ -1,10 +1,15 @@
 use claims::assert_none;
+use newsletter::{
+    configuration::ConfirmationTokenMode,
+    domain::SubscriptionStatus,
+    token_generator::{generate_subscription_token, hash_token},
+};
 use wiremock::{
     matchers::{method, path},
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with_configuration, TestApp};
 
 #[tokio::test]
 async fn confirmations_without_tokens_are_rejected_with_a_400() {
@@ -37,6 +42,29 @@ async fn the_link_returned_by_subscribe_returns_a_200_if_called() {
     assert_eq!(response.status().as_u16(), 200);
 }
 
+#[tokio::test]
+async fn the_confirmation_link_carries_the_generated_token() {
+    let test_app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let subscription_token = generate_subscription_token(&test_app.tokens);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app.post_subscription(body.into()).await;
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = test_app.get_links(email_request);
+
+    assert_eq!(
+        confirmation_link.html.query(),
+        Some(format!("subscription_token={}", subscription_token).as_str())
+    );
+    assert_eq!(confirmation_link.html.path(), "/subscriptions/confirm");
+}
+
 #[tokio::test]
 async fn clicking_on_the_confirmation_link_confirms_subscriber() {
     let test_app = spawn_app().await;
@@ -58,14 +86,16 @@ async fn clicking_on_the_confirmation_link_confirms_subscriber() {
         .error_for_status()
         .unwrap();
 
-    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
-        .fetch_one(&test_app.db_pool)
-        .await
-        .expect("Failed to fetch saved subscriptions");
+    let saved = sqlx::query!(
+        r#"SELECT email, name, status as "status: SubscriptionStatus" FROM subscriptions"#,
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .expect("Failed to fetch saved subscriptions");
 
     assert_eq!(saved.email, "ursula_le_guin@gmail.com");
     assert_eq!(saved.name, "le guin");
-    assert_eq!(saved.status, "confirmed");
+    assert_eq!(saved.status, SubscriptionStatus::Confirmed);
 }
 
 #[tokio::test]
@@ -128,9 +158,9 @@ async fn clicking_on_the_confirmation_link_removes_subscription_token() {
         r#"
         SELECT *
         FROM subscription_tokens
-        WHERE subscription_token = $1
+        WHERE subscription_token_hash = $1
         "#,
-        subscription_token
+        hash_token(subscription_token)
     )
     .fetch_optional(&test_app.db_pool)
     .await
@@ -187,3 +217,110 @@ async fn confirm_returns_a_400_when_token_is_invalid() {
 
     assert_eq!(result.status().as_u16(), 400);
 }
+
+#[tokio::test]
+async fn subscribers_that_bounced_since_they_subscribed_cannot_be_confirmed() {
+    let test_app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app.post_subscription(body.into()).await;
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = test_app.get_links(email_request);
+    sqlx::query!("UPDATE subscriptions SET status = 'bounced'")
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+
+    let response = reqwest::get(confirmation_link.html).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 409);
+    let saved = sqlx::query!(r#"SELECT status AS "status: SubscriptionStatus" FROM subscriptions"#)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, SubscriptionStatus::Bounced);
+}
+
+async fn spawn_app_with_signed_confirmations(valid_for_hours: u32) -> TestApp {
+    let test_app = spawn_app_with_configuration(|c| {
+        c.application.confirmation_tokens.mode = ConfirmationTokenMode::Signed;
+        c.application.confirmation_tokens.signed_valid_for_hours = valid_for_hours;
+    })
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    test_app.post_subscription(body.into()).await;
+
+    test_app
+}
+
+#[tokio::test]
+async fn signed_confirmation_links_confirm_without_a_stored_token() {
+    let test_app = spawn_app_with_signed_confirmations(72).await;
+
+    let stored_tokens: i64 = sqlx::query_scalar("SELECT count(*) FROM subscription_tokens")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(stored_tokens, 0);
+
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = test_app.get_links(email_request);
+    let response = reqwest::get(confirmation_link.html.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let saved = sqlx::query!(
+        r#"SELECT email, name, status as "status: SubscriptionStatus" FROM subscriptions"#,
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .expect("Failed to fetch saved subscriptions");
+    assert_eq!(saved.status, SubscriptionStatus::Confirmed);
+
+    let response = reqwest::get(confirmation_link.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn expired_signed_confirmation_links_are_rejected_with_a_410() {
+    let test_app = spawn_app_with_signed_confirmations(0).await;
+
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = test_app.get_links(email_request);
+    let response = reqwest::get(confirmation_link.html).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 410);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["type"], "/problems/expired-subscription-token");
+}
+
+#[tokio::test]
+async fn tampered_signed_confirmation_links_are_rejected_with_a_400() {
+    let test_app = spawn_app_with_signed_confirmations(72).await;
+
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let mut confirmation_link = test_app.get_links(email_request).html;
+    let token = confirmation_link
+        .query_pairs()
+        .find(|(k, _)| k == "subscription_token")
+        .unwrap()
+        .1
+        .into_owned();
+    confirmation_link.set_query(Some(&format!("subscription_token={}A", token)));
+
+    let response = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+}
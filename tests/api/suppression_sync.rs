@@ -0,0 +1,128 @@
+use newsletter::{domain::SubscriptionStatus, newsletter_list::DEFAULT_LIST_ID};
+use reqwest::Method;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn insert_subscriber(app: &TestApp, email: &str, status: SubscriptionStatus) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, 'le guin', now(), $4)
+        "#,
+        Uuid::new_v4(),
+        DEFAULT_LIST_ID,
+        email,
+        status as SubscriptionStatus,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+}
+
+async fn status_of(app: &TestApp, email: &str) -> SubscriptionStatus {
+    sqlx::query!(
+        r#"SELECT status as "status: SubscriptionStatus" FROM subscriptions WHERE email = $1"#,
+        email,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch subscriber.")
+    .status
+}
+
+async fn mount_suppressions(app: &TestApp, suppressions: serde_json::Value) {
+    Mock::given(method("GET"))
+        .and(path("/message-streams/outbound/suppressions/dump"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({ "Suppressions": suppressions })),
+        )
+        .mount(&app.email_server)
+        .await;
+}
+
+#[tokio::test]
+async fn suppressions_of_the_provider_apply_to_active_subscriptions() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "bounced@example.com", SubscriptionStatus::Confirmed).await;
+    insert_subscriber(&app, "active@example.com", SubscriptionStatus::Confirmed).await;
+    mount_suppressions(
+        &app,
+        serde_json::json!([
+            { "EmailAddress": "Bounced@example.com", "SuppressionReason": "HardBounce" },
+            { "EmailAddress": "unknown@example.com", "SuppressionReason": "ManualSuppression" }
+        ]),
+    )
+    .await;
+
+    let response = app
+        .api_request(Method::POST, "/admin/suppressions/sync")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        report["suppressed_locally"],
+        serde_json::json!(["bounced@example.com"])
+    );
+    assert_eq!(report["suppressed_at_provider"], serde_json::json!([]));
+    assert_eq!(
+        status_of(&app, "bounced@example.com").await,
+        SubscriptionStatus::Bounced
+    );
+    assert_eq!(
+        status_of(&app, "active@example.com").await,
+        SubscriptionStatus::Confirmed
+    );
+}
+
+#[tokio::test]
+async fn local_suppressions_missing_at_the_provider_are_pushed_to_it() {
+    let app = spawn_app().await;
+    insert_subscriber(
+        &app,
+        "complained@example.com",
+        SubscriptionStatus::Complained,
+    )
+    .await;
+    insert_subscriber(&app, "known@example.com", SubscriptionStatus::Bounced).await;
+    mount_suppressions(
+        &app,
+        serde_json::json!([
+            { "EmailAddress": "known@example.com", "SuppressionReason": "HardBounce" }
+        ]),
+    )
+    .await;
+    Mock::given(method("POST"))
+        .and(path("/message-streams/outbound/suppressions"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .api_request(Method::POST, "/admin/suppressions/sync")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        report["suppressed_at_provider"],
+        serde_json::json!(["complained@example.com"])
+    );
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    assert_eq!(
+        body["Suppressions"],
+        serde_json::json!([{ "EmailAddress": "complained@example.com" }])
+    );
+}
@@ -0,0 +1,50 @@
+use newsletter::configuration::TlsSettings;
+
+use crate::helpers::spawn_app_with_configuration;
+
+fn write_self_signed_certificate() -> TlsSettings {
+    let certificate = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let directory = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+    std::fs::create_dir(&directory).unwrap();
+
+    let settings = TlsSettings {
+        cert_path: directory.join("cert.pem"),
+        key_path: directory.join("key.pem"),
+    };
+    std::fs::write(&settings.cert_path, certificate.cert.pem()).unwrap();
+    std::fs::write(&settings.key_path, certificate.key_pair.serialize_pem()).unwrap();
+
+    settings
+}
+
+#[tokio::test]
+async fn the_server_speaks_https_when_a_certificate_is_configured() {
+    let tls = write_self_signed_certificate();
+    let app = spawn_app_with_configuration(|c| c.application.tls = Some(tls)).await;
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+
+    let response = client
+        .get(&format!("https://localhost:{}/health_check", app.port))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert!(response.status().is_success());
+}
+
+#[tokio::test]
+async fn plain_http_is_refused_when_a_certificate_is_configured() {
+    let tls = write_self_signed_certificate();
+    let app = spawn_app_with_configuration(|c| c.application.tls = Some(tls)).await;
+
+    let response = app
+        .api_client
+        .get(&format!("http://localhost:{}/health_check", app.port))
+        .send()
+        .await;
+
+    assert!(response.is_err());
+}
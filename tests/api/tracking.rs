@@ -0,0 +1,306 @@
+use std::time::Duration;
+
+use newsletter::newsletter_list::DEFAULT_LIST_ID;
+use uuid::Uuid;
+use wiremock::{matchers::path, Mock};
+
+use crate::helpers::{accept_email_batch, spawn_app, spawn_app_with_configuration, TestApp};
+
+async fn spawn_app_with_tracking() -> TestApp {
+    spawn_app_with_configuration(|c| {
+        c.features.tracking = true;
+        c.tracking.event_flush_milliseconds = 10;
+    })
+    .await
+}
+
+/// Events are written behind the tracking routes, waits for them to land.
+async fn wait_for_email_events(app: &TestApp, count: i64) {
+    for _ in 0..100 {
+        let recorded = sqlx::query!(r#"SELECT count(*) AS "count!" FROM email_events"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+        if recorded.count >= count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    panic!("The email events were not recorded in time.");
+}
+
+async fn insert_subscriber(app: &TestApp, email: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, 'ursula', now(), 'confirmed')
+        "#,
+        Uuid::new_v4(),
+        DEFAULT_LIST_ID,
+        email,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+}
+
+/// Publishes an issue linking to `https://example.org/post` and gives back
+/// its id along with the HTML body sent to the single subscriber.
+async fn publish_and_deliver(app: &TestApp) -> (Uuid, String) {
+    Mock::given(path("/email/batch"))
+        .respond_with(accept_email_batch)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": { "html": r#"<p><a href="https://example.org/post">Read the post</a></p>"# },
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
+
+    let body = app.delivered_issue_emails().await.pop().unwrap();
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    (
+        newsletter_issue_id,
+        body["HtmlBody"].as_str().unwrap().to_string(),
+    )
+}
+
+/// The tracking url embedded in the HTML, relative to the application.
+fn tracking_path(html: &str, prefix: &str) -> String {
+    let start = html.find(prefix).expect("No tracking url in the email.");
+    let end = html[start..].find('"').unwrap() + start;
+
+    html[start..end].to_string()
+}
+
+#[tokio::test]
+async fn issues_are_not_tracked_when_tracking_is_disabled() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "ursula@example.com").await;
+
+    let (_, html) = publish_and_deliver(&app).await;
+
+    assert!(html.contains(r#"href="https://example.org/post""#));
+    assert!(!html.contains("/t/open/"));
+}
+
+#[tokio::test]
+async fn opening_an_issue_is_recorded() {
+    let app = spawn_app_with_tracking().await;
+    insert_subscriber(&app, "ursula@example.com").await;
+    let (_, html) = publish_and_deliver(&app).await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}{}",
+            app.address,
+            tracking_path(&html, "/t/open/")
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "image/gif");
+    wait_for_email_events(&app, 1).await;
+    let event = sqlx::query!("SELECT subscriber_email, event_type FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(event.subscriber_email, "ursula@example.com");
+    assert_eq!(event.event_type, "open");
+}
+
+#[tokio::test]
+async fn clicking_a_link_is_recorded_and_redirects_to_it() {
+    let app = spawn_app_with_tracking().await;
+    insert_subscriber(&app, "ursula@example.com").await;
+    let (_, html) = publish_and_deliver(&app).await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}{}",
+            app.address,
+            tracking_path(&html, "/t/click/")
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 302);
+    assert_eq!(response.headers()["Location"], "https://example.org/post");
+    wait_for_email_events(&app, 1).await;
+    let event = sqlx::query!("SELECT event_type, url FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(event.event_type, "click");
+    assert_eq!(event.url.as_deref(), Some("https://example.org/post"));
+}
+
+#[tokio::test]
+async fn invalid_tokens_are_rejected() {
+    let app = spawn_app_with_tracking().await;
+
+    for path in ["/t/open/not-a-token", "/t/click/not-a.token"] {
+        let response = app
+            .api_client
+            .get(format!("{}{}", app.address, path))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status().as_u16(), 404);
+    }
+}
+
+#[tokio::test]
+async fn admins_see_the_engagement_of_an_issue() {
+    let app = spawn_app_with_tracking().await;
+    insert_subscriber(&app, "ursula@example.com").await;
+    let (newsletter_issue_id, html) = publish_and_deliver(&app).await;
+    for prefix in ["/t/open/", "/t/click/", "/t/click/"] {
+        app.api_client
+            .get(format!("{}{}", app.address, tracking_path(&html, prefix)))
+            .send()
+            .await
+            .unwrap();
+    }
+    wait_for_email_events(&app, 3).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let html_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/issues/{}/stats",
+            app.address, newsletter_issue_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html_page.contains("<dt>Opens</dt><dd>1 (1 unique, 100.0%)</dd>"));
+    assert!(html_page.contains("<dt>Clicks</dt><dd>2 (1 unique, 100.0%)</dd>"));
+    assert!(html_page.contains("<tr><td>https://example.org/post</td><td>2</td></tr>"));
+}
+
+#[tokio::test]
+async fn events_are_written_in_batches_with_their_own_time() {
+    let app = spawn_app_with_configuration(|c| {
+        c.features.tracking = true;
+        c.tracking.event_batch_size = 3;
+        c.tracking.event_flush_milliseconds = 60_000;
+    })
+    .await;
+    insert_subscriber(&app, "ursula@example.com").await;
+    let (_, html) = publish_and_deliver(&app).await;
+
+    let before = chrono::Utc::now();
+    for prefix in ["/t/open/", "/t/click/", "/t/click/"] {
+        app.api_client
+            .get(format!("{}{}", app.address, tracking_path(&html, prefix)))
+            .send()
+            .await
+            .unwrap();
+    }
+    wait_for_email_events(&app, 3).await;
+
+    let events = sqlx::query!("SELECT occurred_at FROM email_events")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 3);
+    assert!(events.iter().all(|e| e.occurred_at >= before));
+}
+
+#[tokio::test]
+async fn open_rates_are_compared_per_subject() {
+    let app = spawn_app_with_tracking().await;
+    insert_subscriber(&app, "ursula@example.com").await;
+    insert_subscriber(&app, "octavia@example.com").await;
+    Mock::given(path("/email/batch"))
+        .respond_with(accept_email_batch)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "The dispossessed",
+            "alternative_title": "An ambiguous utopia",
+            "content": { "html": "<p>Newsletter body as HTML</p>" },
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
+
+    // Recipients are split in halves, one subject each.
+    let emails = app.delivered_issue_emails().await;
+    let mut subjects: Vec<&str> = emails
+        .iter()
+        .map(|email| email["Subject"].as_str().unwrap())
+        .collect();
+    subjects.sort();
+    assert_eq!(subjects, ["An ambiguous utopia", "The dispossessed"]);
+
+    let opened = emails
+        .iter()
+        .find(|email| email["Subject"] == "An ambiguous utopia")
+        .unwrap();
+    app.api_client
+        .get(format!(
+            "{}{}",
+            app.address,
+            tracking_path(opened["HtmlBody"].as_str().unwrap(), "/t/open/")
+        ))
+        .send()
+        .await
+        .unwrap();
+    wait_for_email_events(&app, 1).await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    let html_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/issues/{}/stats",
+            app.address, newsletter_issue_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html_page
+        .contains("<tr><td>An ambiguous utopia</td><td>1</td><td>1</td><td>100.0%</td></tr>"));
+    assert!(
+        html_page.contains("<tr><td>The dispossessed</td><td>1</td><td>0</td><td>0.0%</td></tr>")
+    );
+}
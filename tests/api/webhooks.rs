@@ -0,0 +1,240 @@
+use hmac::{Hmac, Mac};
+use reqwest::Method;
+use sha2::Sha256;
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app_with_configuration, TestApp};
+
+async fn spawn_app_with_webhooks() -> TestApp {
+    spawn_app_with_configuration(|c| c.features.webhooks = true).await
+}
+
+async fn register_webhook(app: &TestApp, url: &str) -> serde_json::Value {
+    let response = app
+        .api_request(Method::POST, "/webhooks")
+        .json(&serde_json::json!({ "url": url }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 201);
+
+    response.json().await.unwrap()
+}
+
+async fn publish_issue(app: &TestApp) {
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+}
+
+async fn login(app: &TestApp) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+async fn get_webhooks_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/admin/webhooks", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn published_issues_are_delivered_to_webhooks() {
+    let app = spawn_app_with_webhooks().await;
+    let webhook_server = MockServer::start().await;
+    register_webhook(&app, &format!("{}/hooks", webhook_server.uri())).await;
+    Mock::given(path("/hooks"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&webhook_server)
+        .await;
+
+    publish_issue(&app).await;
+    app.dispatch_all_pending_webhook_events().await;
+
+    let payload: serde_json::Value = webhook_server.received_requests().await.unwrap()[0]
+        .body_json()
+        .unwrap();
+    assert_eq!(payload["type"], "issue.published");
+    assert_eq!(payload["details"]["title"], "Newsletter title");
+    assert!(payload.get("subscriber_id").is_none());
+}
+
+#[tokio::test]
+async fn deliveries_are_signed_with_the_secret_of_the_webhook() {
+    let app = spawn_app_with_webhooks().await;
+    let webhook_server = MockServer::start().await;
+    let webhook = register_webhook(&app, &format!("{}/hooks", webhook_server.uri())).await;
+    let secret = webhook["secret"].as_str().unwrap();
+    Mock::given(path("/hooks"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&webhook_server)
+        .await;
+
+    publish_issue(&app).await;
+    app.dispatch_all_pending_webhook_events().await;
+
+    let request = &webhook_server.received_requests().await.unwrap()[0];
+    let header = request.headers["Webhook-Signature"].to_str().unwrap();
+    let (timestamp, signature) = header
+        .strip_prefix("t=")
+        .and_then(|rest| rest.split_once(",v1="))
+        .expect("Malformed signature header");
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(&request.body);
+    mac.verify_slice(&hex::decode(signature).unwrap())
+        .expect("Invalid signature");
+}
+
+#[tokio::test]
+async fn the_secret_of_a_webhook_is_only_shown_on_registration() {
+    let app = spawn_app_with_webhooks().await;
+    register_webhook(&app, "https://example.com/hooks").await;
+
+    let webhooks: serde_json::Value = app
+        .api_request(Method::GET, "/webhooks")
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+
+    assert!(webhooks[0].get("secret").is_none());
+}
+
+#[tokio::test]
+async fn every_delivery_attempt_is_logged() {
+    let app = spawn_app_with_webhooks().await;
+    let webhook_server = MockServer::start().await;
+    let webhook = register_webhook(&app, &format!("{}/hooks", webhook_server.uri())).await;
+    Mock::given(path("/hooks"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .mount(&webhook_server)
+        .await;
+    Mock::given(path("/hooks"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&webhook_server)
+        .await;
+
+    publish_issue(&app).await;
+    app.dispatch_all_pending_webhook_events().await;
+    // The failed attempt is retried once its backoff is over.
+    sqlx::query!("UPDATE webhook_delivery_queue SET execute_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_webhook_events().await;
+
+    let deliveries: serde_json::Value = app
+        .api_request(
+            Method::GET,
+            &format!("/webhooks/{}/deliveries", webhook["id"].as_str().unwrap()),
+        )
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+    let deliveries = deliveries.as_array().unwrap();
+    assert_eq!(deliveries.len(), 2);
+    assert_eq!(deliveries[0]["attempt"], 2);
+    assert_eq!(deliveries[0]["status_code"], 200);
+    assert!(deliveries[0]["error"].is_null());
+    assert_eq!(deliveries[1]["attempt"], 1);
+    assert_eq!(deliveries[1]["status_code"], 500);
+    assert!(deliveries[1]["error"].is_string());
+}
+
+#[tokio::test]
+async fn admins_register_and_delete_webhooks_from_the_admin_ui() {
+    let app = spawn_app_with_webhooks().await;
+    login(&app).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/webhooks", app.address))
+        .form(&[
+            ("url", "https://crm.example.com/hooks"),
+            ("csrf_token", &app.csrf_token().await),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/admin/webhooks");
+
+    let html = get_webhooks_html(&app).await;
+    assert!(html.contains("<td>https:&#x2F;&#x2F;crm.example.com&#x2F;hooks</td>"));
+    assert!(html.contains("Payloads are signed with this secret, only shown now: "));
+
+    let webhook_id = sqlx::query!("SELECT webhook_id FROM webhooks")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .webhook_id;
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/webhooks/{}/delete",
+            app.address, webhook_id
+        ))
+        .form(&[("csrf_token", &app.csrf_token().await)])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/admin/webhooks");
+
+    let html = get_webhooks_html(&app).await;
+    assert!(html.contains("The webhook was deleted."));
+    assert!(!html.contains("https://crm.example.com/hooks"));
+}
+
+#[tokio::test]
+async fn invalid_webhook_urls_are_refused_by_the_admin_ui() {
+    let app = spawn_app_with_webhooks().await;
+    login(&app).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/webhooks", app.address))
+        .form(&[
+            ("url", "ftp://crm.example.com/hooks"),
+            ("csrf_token", &app.csrf_token().await),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/admin/webhooks");
+
+    let html = get_webhooks_html(&app).await;
+    assert!(html.contains("Webhook URL must use http or https"));
+    assert!(html.contains("No endpoint is registered yet."));
+}
@@ -0,0 +1,202 @@
+use chrono::{Duration, Utc};
+use newsletter::{digest::send_weekly_digests, newsletter_list::DEFAULT_LIST_ID};
+use uuid::Uuid;
+use wiremock::{
+    matchers::{any, body_string_contains, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{accept_email_batch, spawn_app, TestApp};
+
+const DIGEST_EMAIL: &str = "ursula@gmail.com";
+const EVERY_ISSUE_EMAIL: &str = "ged@earthsea.org";
+
+async fn insert_confirmed_subscriber(app: &TestApp, email: &str, delivery_frequency: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (
+            id, list_id, email, name, subscribed_at, status, delivery_frequency
+        )
+        VALUES ($1, $2, $3, 'le guin', now(), 'confirmed', $4::TEXT::delivery_frequency)
+        "#,
+        Uuid::new_v4(),
+        DEFAULT_LIST_ID,
+        email,
+        delivery_frequency,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+}
+
+async fn publish_issue(app: &TestApp, title: &str) {
+    app.post_newsletters(serde_json::json!({
+        "title": title,
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn digest_subscribers_get_the_issues_of_the_week_in_a_single_email() {
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, DIGEST_EMAIL, "weekly_digest").await;
+    insert_confirmed_subscriber(&app, EVERY_ISSUE_EMAIL, "every_issue").await;
+    Mock::given(path("/email/batch"))
+        .respond_with(accept_email_batch)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_issue(&app, "A Wizard of Earthsea").await;
+    publish_issue(&app, "The Tombs of Atuan").await;
+
+    let recipients: Vec<_> = app
+        .delivered_issue_emails()
+        .await
+        .into_iter()
+        .map(|email| email["To"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(recipients, vec![EVERY_ISSUE_EMAIL, EVERY_ISSUE_EMAIL]);
+
+    // The digest of the current week goes out the week after.
+    let next_week = Utc::now() + Duration::weeks(1);
+    let sent = send_weekly_digests(&app.db_pool, &app.email_client, &app.address, next_week)
+        .await
+        .unwrap();
+
+    assert!(sent);
+    let email_request = app.email_server.received_requests().await.unwrap().pop();
+    let body: serde_json::Value = email_request.unwrap().body_json().unwrap();
+    assert_eq!(body["To"], DIGEST_EMAIL);
+    let text = body["TextBody"].as_str().unwrap();
+    let first = text.find("A Wizard of Earthsea").unwrap();
+    let second = text.find("The Tombs of Atuan").unwrap();
+    assert!(first < second);
+    let preferences_link = format!("/preferences/link?list_id={}", DEFAULT_LIST_ID);
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains(&preferences_link));
+
+    let delivered = sqlx::query_scalar!(
+        r#"SELECT count(*) AS "count!" FROM issue_deliveries WHERE subscriber_email = $1"#,
+        DIGEST_EMAIL,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(delivered, 2);
+}
+
+#[tokio::test]
+async fn the_digest_of_a_week_is_sent_once() {
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, DIGEST_EMAIL, "weekly_digest").await;
+    publish_issue(&app, "A Wizard of Earthsea").await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let next_week = Utc::now() + Duration::weeks(1);
+
+    let first = send_weekly_digests(&app.db_pool, &app.email_client, &app.address, next_week)
+        .await
+        .unwrap();
+    let second = send_weekly_digests(&app.db_pool, &app.email_client, &app.address, next_week)
+        .await
+        .unwrap();
+
+    assert!(first);
+    assert!(!second);
+}
+
+#[tokio::test]
+async fn a_digest_the_provider_refuses_does_not_hold_back_the_others() {
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, DIGEST_EMAIL, "weekly_digest").await;
+    insert_confirmed_subscriber(&app, EVERY_ISSUE_EMAIL, "weekly_digest").await;
+    publish_issue(&app, "A Wizard of Earthsea").await;
+    Mock::given(body_string_contains(DIGEST_EMAIL))
+        .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+            "ErrorCode": 300,
+            "Message": "Invalid email request",
+        })))
+        .expect(1)
+        .with_priority(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let next_week = Utc::now() + Duration::weeks(1);
+
+    let sent = send_weekly_digests(&app.db_pool, &app.email_client, &app.address, next_week)
+        .await
+        .unwrap();
+
+    assert!(sent);
+    let delivered = sqlx::query_scalar!(r#"SELECT subscriber_email FROM issue_deliveries"#,)
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivered, vec![EVERY_ISSUE_EMAIL]);
+}
+
+#[tokio::test]
+async fn a_digest_that_failed_to_be_sent_is_sent_on_the_next_run() {
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, DIGEST_EMAIL, "weekly_digest").await;
+    insert_confirmed_subscriber(&app, EVERY_ISSUE_EMAIL, "weekly_digest").await;
+    publish_issue(&app, "A Wizard of Earthsea").await;
+    let next_week = Utc::now() + Duration::weeks(1);
+    {
+        let _failing_guard = Mock::given(body_string_contains(DIGEST_EMAIL))
+            .respond_with(ResponseTemplate::new(500))
+            .with_priority(1)
+            .mount_as_scoped(&app.email_server)
+            .await;
+        let _accepted_guard = Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount_as_scoped(&app.email_server)
+            .await;
+
+        let outcome =
+            send_weekly_digests(&app.db_pool, &app.email_client, &app.address, next_week).await;
+
+        assert!(outcome.is_err());
+    }
+    Mock::given(body_string_contains(DIGEST_EMAIL))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let sent = send_weekly_digests(&app.db_pool, &app.email_client, &app.address, next_week)
+        .await
+        .unwrap();
+
+    assert!(sent);
+    let mut delivered = sqlx::query_scalar!(r#"SELECT subscriber_email FROM issue_deliveries"#,)
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    delivered.sort();
+    let mut expected = vec![DIGEST_EMAIL, EVERY_ISSUE_EMAIL];
+    expected.sort();
+    assert_eq!(delivered, expected);
+}
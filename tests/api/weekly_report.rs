@@ -0,0 +1,126 @@
+use chrono::{Duration, Utc};
+use newsletter::{growth_report::send_weekly_report, newsletter_list::DEFAULT_LIST_ID};
+use reqwest::Method;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{any, body_string_contains},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn insert_confirmed_subscriber(app: &TestApp, email: &str) {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, list_id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, 'le guin', now(), 'confirmed')
+        "#,
+        subscriber_id,
+        DEFAULT_LIST_ID,
+        email,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber.");
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_events (subscriber_id, event_type, occurred_at)
+        VALUES ($1, 'confirmed', now())
+        "#,
+        subscriber_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber event.");
+}
+
+async fn opt_in(app: &TestApp, email: &str) -> reqwest::Response {
+    app.api_request(Method::PUT, "/admin/report")
+        .json(&serde_json::json!({ "email": email }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn set_goal(app: &TestApp, list_id: Uuid, goal: i32) -> reqwest::Response {
+    app.api_request(Method::PUT, &format!("/admin/lists/{}/goal", list_id))
+        .json(&serde_json::json!({ "weekly_new_subscribers": goal }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn opted_in_admins_get_the_growth_of_the_week_against_the_goal() {
+    let app = spawn_app().await;
+    assert_eq!(
+        set_goal(&app, DEFAULT_LIST_ID, 4).await.status().as_u16(),
+        204
+    );
+    assert_eq!(
+        opt_in(&app, "admin@example.com").await.status().as_u16(),
+        204
+    );
+    insert_confirmed_subscriber(&app, "ursula@gmail.com").await;
+    Mock::given(body_string_contains("admin@example.com"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // The report of the current week goes out the week after.
+    let next_week = Utc::now() + Duration::weeks(1);
+    let sent = send_weekly_report(&app.db_pool, &app.email_client, next_week)
+        .await
+        .unwrap();
+
+    assert!(sent);
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = email_request.body_json().unwrap();
+    let text = body["TextBody"].as_str().unwrap();
+    assert!(text.contains("Newsletter: 1 new subscribers out of 4 (25%), 0 churned"));
+    assert!(text.contains("No issue was published this week."));
+}
+
+#[tokio::test]
+async fn the_report_of_a_week_is_sent_once() {
+    let app = spawn_app().await;
+    opt_in(&app, "admin@example.com").await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let next_week = Utc::now() + Duration::weeks(1);
+
+    let first = send_weekly_report(&app.db_pool, &app.email_client, next_week)
+        .await
+        .unwrap();
+    let second = send_weekly_report(&app.db_pool, &app.email_client, next_week)
+        .await
+        .unwrap();
+
+    assert!(first);
+    assert!(!second);
+}
+
+#[tokio::test]
+async fn goals_must_be_positive_and_for_a_known_list() {
+    let app = spawn_app().await;
+
+    let response = set_goal(&app, DEFAULT_LIST_ID, 0).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = set_goal(&app, Uuid::new_v4(), 10).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn invalid_report_emails_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = opt_in(&app, "not-an-email").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}